@@ -16,7 +16,7 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::{SideMetadataSanity, SideMetadataContext};
+use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::options::Options;
 use crate::util::opaque_pointer::*;
 use crate::vm::VMBinding;
@@ -79,11 +79,10 @@ impl<VM: VMBinding> Plan for MyGC<VM> {
     // ANCHOR_END: create_copy_config
 
     // ANCHOR: get_spaces
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.copyspace0);
-        ret.push(&self.copyspace1);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.copyspace0);
+        func(&self.copyspace1);
     }
     // ANCHOR_EN: get_spaces
 
@@ -187,6 +186,7 @@ impl<VM: VMBinding> MyGC<VM> {
                 "copyspace0",
                 false,
                 true,
+                false,
                 VMRequest::discontiguous(),
                 global_metadata_specs.clone(),
                 vm_map,
@@ -198,6 +198,7 @@ impl<VM: VMBinding> MyGC<VM> {
                 "copyspace1",
                 true,
                 true,
+                false,
                 VMRequest::discontiguous(),
                 global_metadata_specs.clone(),
                 vm_map,
@@ -207,13 +208,6 @@ impl<VM: VMBinding> MyGC<VM> {
             common: CommonPlan::new(vm_map, mmapper, options, heap, &MYGC_CONSTRAINTS, global_metadata_specs.clone()),
         };
 
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-        res.common.verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-        res.copyspace0.verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-        res.copyspace1.verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-
         res
     }
     // ANCHOR_END: plan_new
@@ -0,0 +1,104 @@
+// The C API generated by the `export_c_api!` macro in mmtk-core (see src/c_api.rs).
+//
+// A binding that invokes `mmtk::export_c_api!(MyVM, MY_MMTK_INSTANCE)` exports the
+// following functions. A non-Rust runtime can include this header to call them.
+
+#ifndef MMTK_C_API_H
+#define MMTK_C_API_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef void* MMTk_Mutator;
+
+// Cumulative GC statistics. See `mmtk::util::gc_stats::GCStats`.
+typedef struct {
+    uint64_t gc_count;
+    uint64_t nursery_gc_count;
+    uint64_t full_heap_gc_count;
+    uint64_t total_pause_ns;
+    uint64_t total_allocated_bytes;
+    uint64_t total_copied_bytes;
+    uint64_t total_promoted_bytes;
+    uint64_t total_freed_bytes;
+    uint64_t heap_size_bytes;
+    uint64_t used_bytes;
+    uint64_t total_traced_objects;
+    uint64_t total_traced_bytes;
+} MMTk_GCStats;
+
+// Request MMTk to create a new mutator for the given `tls` thread
+extern MMTk_Mutator mmtk_bind_mutator(void* tls);
+
+// Reclaim mutator that is no longer needed
+extern void mmtk_destroy_mutator(MMTk_Mutator mutator);
+
+// Flush mutator local state
+extern void mmtk_flush_mutator(MMTk_Mutator mutator);
+
+// Allocate memory for an object. `semantics` is a value of `AllocationSemantics`. Objects that are
+// too large for the other allocators of the plan are allocated in the large object space
+extern void* mmtk_alloc(MMTk_Mutator mutator,
+                        size_t size,
+                        size_t align,
+                        ssize_t offset,
+                        int semantics);
+
+// Perform post-allocation hooks or actions such as initializing object metadata
+extern void mmtk_post_alloc(MMTk_Mutator mutator,
+                            void* object,
+                            size_t bytes,
+                            int semantics);
+
+// The post write barrier for a reference field of the object `src`
+extern void mmtk_object_reference_write_post(MMTk_Mutator mutator, void* src);
+
+// Poll for GC. The calling thread may be blocked for a GC
+extern void mmtk_gc_poll(void* tls);
+
+// Request MMTk to trigger a GC. Note that this may not actually trigger a GC
+extern void mmtk_handle_user_collection_request(void* tls);
+
+// Change the heap size. Shrinking below the current usage may trigger a GC
+extern bool mmtk_set_heap_size(void* tls, size_t bytes);
+
+// Return the current amount of used memory in bytes
+extern size_t mmtk_used_bytes();
+
+// Return the current amount of free memory in bytes
+extern size_t mmtk_free_bytes();
+
+// Return the current amount of total memory in bytes
+extern size_t mmtk_total_bytes();
+
+// Return the cumulative GC statistics
+extern MMTk_GCStats mmtk_gc_stats();
+
+// Return if the object pointed to by `object` is live
+extern bool mmtk_is_live_object(void* object);
+
+// Return if the object pointed to by `object` is in any MMTk space
+extern bool mmtk_is_in_mmtk_spaces(void* object);
+
+// Return if the object pointed to by `object` will never move
+extern bool mmtk_will_never_move(void* object);
+
+// Pin the object so GCs do not move it until it is unpinned. Pins are counted.
+// Return false if the policy of the object cannot pin objects
+extern bool mmtk_pin_object(void* object);
+
+// Remove a pin added with `mmtk_pin_object`
+// Return false if the policy of the object cannot pin objects
+extern bool mmtk_unpin_object(void* object);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif  // MMTK_C_API_H
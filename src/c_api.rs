@@ -0,0 +1,175 @@
+//! A C API for the core entry points in [`memory_manager`](crate::memory_manager).
+//!
+//! The functions in `memory_manager` are generic over the VM binding, so mmtk-core cannot export
+//! them as C functions directly. Instead, a binding can use the [`export_c_api!`](crate::export_c_api)
+//! macro to generate `extern "C"` functions with stable symbol names for its own `VMBinding` type and
+//! MMTk instance. A non-Rust runtime can then call those functions with the header file
+//! `include/mmtk_c_api.h` in the mmtk-core repository, instead of writing and maintaining
+//! the same shims in every binding.
+//!
+//! The generated functions are:
+//! * `mmtk_bind_mutator`, `mmtk_destroy_mutator`, `mmtk_flush_mutator`
+//! * `mmtk_alloc`, `mmtk_post_alloc`
+//! * `mmtk_object_reference_write_post`
+//! * `mmtk_gc_poll`, `mmtk_handle_user_collection_request`
+//! * `mmtk_set_heap_size`, `mmtk_used_bytes`, `mmtk_free_bytes`, `mmtk_total_bytes`, `mmtk_gc_stats`
+//! * `mmtk_is_live_object`, `mmtk_is_in_mmtk_spaces`, `mmtk_will_never_move`
+//! * `mmtk_pin_object`, `mmtk_unpin_object`
+//!
+//! `mmtk_alloc` and `mmtk_post_alloc` use `AllocationSemantics::Los` for the objects that are too
+//! large for the other allocators of the plan (see `max_non_los_default_alloc_bytes` in
+//! `PlanConstraints`), so the runtime does not need to choose the semantics by the size.
+
+/// Generate `extern "C"` functions for the core `memory_manager` entry points.
+///
+/// Arguments:
+/// * `$vm`: The `VMBinding` type of the binding.
+/// * `$mmtk`: A static MMTk instance of the binding, e.g. a `lazy_static` `MMTK<$vm>`.
+///
+/// For example, `mmtk::export_c_api!(DummyVM, SINGLETON);`.
+///
+/// Note that the generated functions use the `mmtk_` prefix. A binding should not define its
+/// own functions with the same names if it uses this macro.
+#[macro_export]
+macro_rules! export_c_api {
+    ($vm: ty, $mmtk: expr) => {
+        #[no_mangle]
+        pub extern "C" fn mmtk_bind_mutator(
+            tls: $crate::util::VMMutatorThread,
+        ) -> *mut $crate::Mutator<$vm> {
+            Box::into_raw($crate::memory_manager::bind_mutator(&$mmtk, tls))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_destroy_mutator(mutator: *mut $crate::Mutator<$vm>) {
+            $crate::memory_manager::destroy_mutator(unsafe { Box::from_raw(mutator) })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_flush_mutator(mutator: *mut $crate::Mutator<$vm>) {
+            $crate::memory_manager::flush_mutator(unsafe { &mut *mutator })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_alloc(
+            mutator: *mut $crate::Mutator<$vm>,
+            size: usize,
+            align: usize,
+            offset: isize,
+            mut semantics: $crate::AllocationSemantics,
+        ) -> $crate::util::Address {
+            if size
+                >= $mmtk
+                    .get_plan()
+                    .constraints()
+                    .max_non_los_default_alloc_bytes
+            {
+                semantics = $crate::AllocationSemantics::Los;
+            }
+            $crate::memory_manager::alloc::<$vm>(
+                unsafe { &mut *mutator },
+                size,
+                align,
+                offset,
+                semantics,
+            )
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_post_alloc(
+            mutator: *mut $crate::Mutator<$vm>,
+            object: $crate::util::ObjectReference,
+            bytes: usize,
+            mut semantics: $crate::AllocationSemantics,
+        ) {
+            if bytes
+                >= $mmtk
+                    .get_plan()
+                    .constraints()
+                    .max_non_los_default_alloc_bytes
+            {
+                semantics = $crate::AllocationSemantics::Los;
+            }
+            $crate::memory_manager::post_alloc::<$vm>(
+                unsafe { &mut *mutator },
+                object,
+                bytes,
+                semantics,
+            )
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_object_reference_write_post(
+            mutator: *mut $crate::Mutator<$vm>,
+            src: $crate::util::ObjectReference,
+        ) {
+            $crate::memory_manager::post_write_barrier::<$vm>(
+                unsafe { &mut *mutator },
+                $crate::plan::BarrierWriteTarget::Object(src),
+            )
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_gc_poll(tls: $crate::util::VMMutatorThread) {
+            $crate::memory_manager::gc_poll::<$vm>(&$mmtk, tls)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_handle_user_collection_request(tls: $crate::util::VMMutatorThread) {
+            $crate::memory_manager::handle_user_collection_request::<$vm>(&$mmtk, tls)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_set_heap_size(
+            tls: $crate::util::VMMutatorThread,
+            bytes: usize,
+        ) -> bool {
+            $crate::memory_manager::set_heap_size::<$vm>(&$mmtk, tls, bytes)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_used_bytes() -> usize {
+            $crate::memory_manager::used_bytes::<$vm>(&$mmtk)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_free_bytes() -> usize {
+            $crate::memory_manager::free_bytes::<$vm>(&$mmtk)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_total_bytes() -> usize {
+            $crate::memory_manager::total_bytes::<$vm>(&$mmtk)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_gc_stats() -> $crate::util::gc_stats::GCStats {
+            $crate::memory_manager::gc_stats::<$vm>(&$mmtk)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_is_live_object(object: $crate::util::ObjectReference) -> bool {
+            $crate::memory_manager::is_live_object(object)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_is_in_mmtk_spaces(object: $crate::util::ObjectReference) -> bool {
+            $crate::memory_manager::is_in_mmtk_spaces(object)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_will_never_move(object: $crate::util::ObjectReference) -> bool {
+            !object.is_movable()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_pin_object(object: $crate::util::ObjectReference) -> bool {
+            $crate::memory_manager::pin_object(object)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mmtk_unpin_object(object: $crate::util::ObjectReference) -> bool {
+            $crate::memory_manager::unpin_object(object)
+        }
+    };
+}
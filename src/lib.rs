@@ -52,6 +52,7 @@ pub use mmtk::MMTK;
 mod policy;
 
 pub mod build_info;
+pub mod c_api;
 pub mod memory_manager;
 pub mod plan;
 pub mod scheduler;
@@ -62,3 +63,4 @@ pub use crate::plan::{
     AllocationSemantics, BarrierSelector, Mutator, MutatorContext, ObjectQueue, Plan,
 };
 pub use crate::policy::copy_context::PolicyCopyContext;
+pub use crate::policy::space::SpaceInfo;
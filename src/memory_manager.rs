@@ -15,18 +15,46 @@ use crate::mmtk::MMTKBuilder;
 use crate::mmtk::MMTK;
 use crate::plan::AllocationSemantics;
 use crate::plan::BarrierWriteTarget;
+use crate::plan::CollectionScope;
+use crate::plan::{BlockingGCError, GCKind};
 use crate::plan::{Mutator, MutatorContext};
+use crate::plan::{ObjectGeneration, TenuringPolicy};
 use crate::scheduler::WorkBucketStage;
-use crate::scheduler::{GCController, GCWork, GCWorker};
+use crate::scheduler::{GCController, GCWork, GCWorker, VMTaskBuilder};
 use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::code_roots::CodeRootUpdater;
+use crate::util::colocation::ColocationStats;
 use crate::util::constants::{LOG_BYTES_IN_PAGE, MIN_OBJECT_SIZE};
+use crate::util::epoch::DeferredCallback;
+use crate::util::error::MMTKError;
+use crate::util::gc_stats::GCTracingStats;
+use crate::util::gc_stats::{CopyReserveUsage, GCStats, GCStatsDelta, GCStatsEpoch, WindowStats};
 use crate::util::heap::layout::vm_layout_constants::HEAP_END;
 use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+use crate::util::heap_timeline::HeapTimelineSample;
+use crate::util::metadata::side_metadata::SideMetadataLayout;
+use crate::util::object_layout::ObjectLayout;
 use crate::util::opaque_pointer::*;
+use crate::util::statistics::{EventCounter, SizeCounter, Timer};
+#[cfg(feature = "transitive_pinning")]
+use crate::util::transitive_pin::PinningRegion;
 use crate::util::{Address, ObjectReference};
-use crate::vm::ReferenceGlue;
 use crate::vm::VMBinding;
+use crate::vm::{Finalizable, ReferenceGlue};
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+/// Provide the OS services that MMTk uses to map the memory of the heap and its metadata, to spawn
+/// its helper threads, and to keep its thread-local state, for a binding on a target without mmap
+/// or `std` threads, or with its own primitives. This must be called before the first MMTk
+/// instance is initialized, and at most once. If it is not called, MMTk uses
+/// [`OSPlatform`](crate::util::platform::OSPlatform).
+///
+/// Arguments:
+/// * `platform`: The platform to use for the rest of the process.
+pub fn set_platform(platform: &'static dyn crate::util::platform::Platform) {
+    crate::util::platform::set_platform(platform)
+}
 
 /// Initialize an MMTk instance. A VM should call this method after creating an [MMTK](../mmtk/struct.MMTK.html)
 /// instance but before using any of the methods provided in MMTk (except `process()` and `process_bulk()`).
@@ -54,6 +82,15 @@ use std::sync::atomic::Ordering;
 /// Arguments:
 /// * `builder`: The reference to a MMTk builder.
 pub fn mmtk_init<VM: VMBinding>(builder: &MMTKBuilder) -> Box<MMTK<VM>> {
+    try_mmtk_init(builder).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Initialize an MMTk instance like [`mmtk_init`], but return an error instead of panicking if the
+/// options in the builder are invalid, so the VM can report the error to its user.
+///
+/// Arguments:
+/// * `builder`: The reference to a MMTk builder.
+pub fn try_mmtk_init<VM: VMBinding>(builder: &MMTKBuilder) -> Result<Box<MMTK<VM>>, MMTKError> {
     match crate::util::logger::try_init() {
         Ok(_) => debug!("MMTk initialized the logger."),
         Err(_) => debug!(
@@ -77,11 +114,11 @@ pub fn mmtk_init<VM: VMBinding>(builder: &MMTKBuilder) -> Box<MMTK<VM>> {
             }
         }
     }
-    let mmtk = builder.build();
+    let mmtk = builder.build()?;
     info!("Initialized MMTk with {:?}", *mmtk.options.plan);
     #[cfg(feature = "extreme_assertions")]
     warn!("The feature 'extreme_assertions' is enabled. MMTk will run expensive run-time checks. Slow performance should be expected.");
-    Box::new(mmtk)
+    Ok(Box::new(mmtk))
 }
 
 /// Request MMTk to create a mutator for the given thread. For performance reasons, A VM should
@@ -97,11 +134,38 @@ pub fn bind_mutator<VM: VMBinding>(
     crate::plan::create_mutator(tls, mmtk)
 }
 
-/// Reclaim a mutator that is no longer needed.
+/// Request MMTk to create a mutator for the given thread like [`bind_mutator`], but return an error
+/// if the MMTk instance is shutting down (see [`prepare_for_shutdown`]), as the mutator could not
+/// allocate.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The thread that will be associated with the mutator.
+pub fn try_bind_mutator<VM: VMBinding>(
+    mmtk: &'static MMTK<VM>,
+    tls: VMMutatorThread,
+) -> Result<Box<Mutator<VM>>, MMTKError> {
+    if mmtk.plan.base().gc_requester.is_shutting_down() {
+        return Err(MMTKError::ShuttingDown);
+    }
+    Ok(crate::plan::create_mutator(tls, mmtk))
+}
+
+/// Reclaim a mutator that is no longer needed. Before the mutator is dropped, its barrier is
+/// flushed into the scheduler, the unused parts of the thread local buffers of its allocators are
+/// given back to the spaces (if the spaces can reuse them before the next GC), and the mutator is
+/// unregistered with `ActivePlan::unregister_mutator()`. This is done atomically with respect to
+/// GCs: if a GC is in progress, the current thread is blocked for the GC with
+/// `Collection::block_for_gc()` first, and no GC may start until the mutator is unregistered.
+/// If the mutator has blocked for a GC that has been requested but not started (e.g. the binding
+/// released it from `block_for_gc()` because its thread exits), and no other thread waits for
+/// that GC, the request is withdrawn.
+/// This should be called by the thread of the mutator, before the thread exits.
 ///
 /// Arguments:
 /// * `mutator`: A reference to the mutator to be destroyed.
-pub fn destroy_mutator<VM: VMBinding>(mutator: Box<Mutator<VM>>) {
+pub fn destroy_mutator<VM: VMBinding>(mut mutator: Box<Mutator<VM>>) {
+    mutator.on_destroy();
     drop(mutator);
 }
 
@@ -113,6 +177,94 @@ pub fn flush_mutator<VM: VMBinding>(mutator: &mut Mutator<VM>) {
     mutator.flush()
 }
 
+/// Apply a function to each mutator, e.g. to flush the allocators and the barriers of all the
+/// mutators with [`flush_mutator`]. The mutators are enumerated with `ActivePlan` while holding
+/// the lock that MMTk also takes when it iterates through the mutators itself, so this never
+/// interleaves with the enumeration by a GC, or with another call of this function.
+///
+/// # Safety
+///
+/// The function is given a mutable reference to each mutator, so the binding must make sure
+/// that no mutator uses its own state meanwhile. That is, the binding must either call this
+/// function while the mutators are stopped for a GC (e.g. from a `Collection` or `Scanning`
+/// callback), or while holding a lock of its own that the mutator threads take before they
+/// allocate or run barriers (e.g. the lock that a thread holds while it is attached to the VM).
+/// The function must not call `map_mutators` again, or do anything that makes MMTk iterate the
+/// mutators, such as triggering a GC, as the lock is not reentrant.
+///
+/// Arguments:
+/// * `f`: The function to apply to each mutator.
+pub unsafe fn map_mutators<VM: VMBinding, F>(mut f: F)
+where
+    F: FnMut(&mut Mutator<VM>),
+{
+    use crate::vm::ActivePlan;
+    for mutator in VM::VMActivePlan::mutators() {
+        f(mutator);
+    }
+}
+
+/// Enter a GC-critical region for a mutator thread. Until the thread exits the region with
+/// [`exit_gc_critical_region`], a GC waits before it stops the mutators, so the thread may hold
+/// raw pointers into objects, e.g. across a short native operation. If a GC is waiting for other
+/// regions to exit, or is in progress, the thread first blocks for the GC with
+/// `Collection::block_for_gc()`, unless it is already in a critical region. Critical regions may
+/// be nested.
+///
+/// A thread in a critical region must not allocate with MMTk or otherwise block for a GC, as the
+/// GC would wait for the thread to exit the region. See the option `gc_critical_region_timeout`
+/// to report regions that take too long.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The mutator thread that enters the region.
+pub fn enter_gc_critical_region<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMutatorThread) {
+    while !mmtk.gc_critical_regions.try_enter(tls) {
+        mmtk.plan.base().block_for_gc(tls);
+    }
+}
+
+/// Exit a GC-critical region entered with [`enter_gc_critical_region`]. A GC that waits for the
+/// critical regions continues once all the regions have exited.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The mutator thread that exits the region.
+pub fn exit_gc_critical_region<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMutatorThread) {
+    mmtk.gc_critical_regions.exit(tls)
+}
+
+/// Is the mutator thread in a GC-critical region?
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The mutator thread to query.
+pub fn is_in_gc_critical_region<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMutatorThread) -> bool {
+    mmtk.gc_critical_regions.is_in_critical_region(tls)
+}
+
+/// Run `callback` once all the mutators have passed a safepoint, e.g. to free a node that a mutator
+/// has unlinked from a lock-free data structure. The mutators must not hold pointers to the nodes
+/// of such structures across safepoints. MMTk runs the callback on a GC worker after the next GC
+/// stops all the mutators, while the mutators are running again. See [`crate::util::epoch`].
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `callback`: The callback to run.
+pub fn defer_until_safepoint<VM: VMBinding>(mmtk: &MMTK<VM>, callback: DeferredCallback) {
+    mmtk.epochs.defer(callback)
+}
+
+/// The current epoch, i.e. the number of times that all the mutators have been stopped. A value
+/// read by a mutator before it unlinks a node is older than the current epoch once all the
+/// mutators have passed a safepoint after that.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn current_epoch<VM: VMBinding>(mmtk: &MMTK<VM>) -> usize {
+    mmtk.epochs.current()
+}
+
 /// Allocate memory for an object. For performance reasons, a VM should
 /// implement the allocation fast-path on their side rather than just calling this function.
 ///
@@ -141,9 +293,128 @@ pub fn alloc<VM: VMBinding>(
     mutator.alloc(size, align, offset, semantics)
 }
 
+/// Allocate memory for an object like [`alloc`], but return an error instead of reporting it to the
+/// binding with `Collection::out_of_memory` if the allocation fails: `MMTKError::HeapOutOfMemory`
+/// if the heap is exhausted even after an emergency GC, or `MMTKError::MmapFailed` if the OS
+/// cannot map more memory for the heap. The VM may then throw an out-of-memory exception to the
+/// program, for example. This first allocates with [`try_alloc`], and only if a GC is required,
+/// allocates again like [`alloc`], which blocks the current thread for the GC.
+///
+/// Arguments:
+/// * `mutator`: The mutator to perform this allocation request.
+/// * `size`: The number of bytes required for the object.
+/// * `align`: Required alignment for the object.
+/// * `offset`: Offset associated with the alignment.
+/// * `semantics`: The allocation semantic required for the allocation.
+pub fn alloc_checked<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    size: usize,
+    align: usize,
+    offset: isize,
+    semantics: AllocationSemantics,
+) -> Result<Address, MMTKError> {
+    let (addr, error) = crate::util::error::with_allocation_errors_returned(|| {
+        match try_alloc(mutator, size, align, offset, semantics) {
+            Some(addr) => addr,
+            // The allocation failed with an error, which is returned below.
+            None if crate::util::error::has_allocation_error() => Address::ZERO,
+            None => mutator.alloc(size, align, offset, semantics),
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None if addr.is_zero() => Err(MMTKError::HeapOutOfMemory),
+        None => Ok(addr),
+    }
+}
+
+/// Allocate memory for an object, without triggering a GC. Unlike [`alloc`], if the heap is full
+/// (i.e. a GC is required), this returns `None` instead of blocking the current thread for a GC.
+/// The caller can then decide when to yield to collection, e.g. by calling [`gc_poll`] or
+/// [`handle_user_collection_request`].
+///
+/// Arguments:
+/// * `mutator`: The mutator to perform this allocation request.
+/// * `size`: The number of bytes required for the object.
+/// * `align`: Required alignment for the object.
+/// * `offset`: Offset associated with the alignment.
+/// * `semantics`: The allocation semantic required for the allocation.
+pub fn try_alloc<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    size: usize,
+    align: usize,
+    offset: isize,
+    semantics: AllocationSemantics,
+) -> Option<Address> {
+    debug_assert!(size >= MIN_OBJECT_SIZE);
+    let addr = crate::util::alloc::allocator::with_no_gc_on_failure(|| {
+        mutator.alloc(size, align, offset, semantics)
+    });
+    if addr.is_zero() {
+        None
+    } else {
+        Some(addr)
+    }
+}
+
+/// Allocate memory for a batch of objects of the same size and semantics. This fills `result` with the
+/// addresses of `result.len()` objects. For bump pointer allocators (e.g. copyspace and immix), the objects
+/// are carved from one allocation request to the allocator, so the cost of the slow path (if any) is paid once
+/// for the whole batch instead of once per object. For other allocators, or large batches that cannot be
+/// served by a single allocation, the objects are allocated individually.
+///
+/// The binding still needs to call [`post_alloc`] for each of the objects. If an allocation fails,
+/// the corresponding addresses are zero.
+///
+/// Arguments:
+/// * `mutator`: The mutator to perform this allocation request.
+/// * `size`: The number of bytes required for each object.
+/// * `align`: Required alignment for each object.
+/// * `offset`: Offset associated with the alignment.
+/// * `semantics`: The allocation semantic required for the allocation.
+/// * `result`: The slice to store the addresses of the allocated objects.
+pub fn alloc_bulk<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    size: usize,
+    align: usize,
+    offset: isize,
+    semantics: AllocationSemantics,
+    result: &mut [Address],
+) {
+    debug_assert!(size >= MIN_OBJECT_SIZE);
+    // Each object starts at a multiple of `stride` from the first object, so if the first object
+    // satisfies the alignment and offset requirement, all the objects in the batch do.
+    let stride = crate::util::conversions::raw_align_up(size, align);
+    let can_carve = matches!(
+        mutator.config.allocator_mapping[semantics],
+        AllocatorSelector::BumpPointer(_) | AllocatorSelector::Immix(_)
+    );
+    let max_bytes = mutator.plan.constraints().max_non_los_default_alloc_bytes;
+    let batch = if can_carve { max_bytes / stride } else { 0 };
+
+    if batch <= 1 {
+        for addr in result.iter_mut() {
+            *addr = mutator.alloc(size, align, offset, semantics);
+        }
+        return;
+    }
+
+    for chunk in result.chunks_mut(batch) {
+        let start = mutator.alloc(stride * chunk.len(), align, offset, semantics);
+        for (i, addr) in chunk.iter_mut().enumerate() {
+            *addr = if start.is_zero() {
+                Address::ZERO
+            } else {
+                start + i * stride
+            };
+        }
+    }
+}
+
 /// Perform post-allocation actions, usually initializing object metadata. For many allocators none are
 /// required. For performance reasons, a VM should implement the post alloc fast-path on their side
-/// rather than just calling this function.
+/// rather than just calling this function. The fast-path on the VM side must call this
+/// function instead when [`is_black_allocation`] returns true, as the object needs to be marked.
 ///
 /// Arguments:
 /// * `mutator`: The mutator to perform post-alloc actions.
@@ -160,6 +431,17 @@ pub fn post_alloc<VM: VMBinding>(
     mutator.post_alloc(refer, bytes, semantics);
 }
 
+/// Return true if new objects are allocated as marked (black), i.e. a concurrent GC is marking.
+/// In that case, bindings that implement the post alloc fast-path on their side must call
+/// [`post_alloc`] for every new object.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+#[inline(always)]
+pub fn is_black_allocation<VM: VMBinding>(mmtk: &MMTK<VM>) -> bool {
+    mmtk.plan.base().is_black_allocation()
+}
+
 /// The write barrier by MMTk. This is a *post* write barrier, which we expect a binding to call
 /// *after* they modify an object. For performance reasons, a VM should implement the write barrier
 /// fast-path on their side rather than just calling this function.
@@ -174,6 +456,69 @@ pub fn post_write_barrier<VM: VMBinding>(mutator: &mut Mutator<VM>, target: Barr
     mutator.barrier().post_write_barrier(target)
 }
 
+/// The barrier for the reference counts of large objects (see the option `los_ref_counting`). If
+/// the option is enabled, a binding must call this for every update of a reference field in the
+/// heap, including the stores that initialize the fields of new objects, and the stores by bulk
+/// copies of arrays. The barrier is a no-op for the plans that do not count the references.
+///
+/// Arguments:
+/// * `mutator`: The mutator for the current thread.
+/// * `old`: The reference that the field held before the update, or null.
+/// * `new`: The reference that the field holds after the update, or null.
+#[inline(always)]
+pub fn reference_update_barrier<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    old: ObjectReference,
+    new: ObjectReference,
+) {
+    mutator.barrier().reference_update(old, new)
+}
+
+/// The barrier for the references that MarkSweep remembers for its partial-heap GCs (see the
+/// options `remember_los_references` and `remember_heap_references`). If either option is enabled,
+/// a binding must call this for every store of a reference into a field of an object in the heap,
+/// including the stores that initialize the fields of new objects, and the stores by bulk copies of
+/// arrays. The barrier is a no-op for the other plans.
+///
+/// Arguments:
+/// * `mutator`: The mutator for the current thread.
+/// * `src`: The object whose field is updated.
+/// * `target`: The reference that the field holds after the update, or null.
+#[inline(always)]
+pub fn reference_write_barrier<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    src: ObjectReference,
+    target: ObjectReference,
+) {
+    mutator.barrier().reference_write(src, target)
+}
+
+/// The same as `post_write_barrier()`, but also records which path the barrier took at the given
+/// call site for the write barrier profiler. The profile is reported at the end of the harness (see the option
+/// `barrier_profile_file`). The outcomes are buffered in the mutator, and added to the profile in
+/// batches and when the mutator is flushed (see `flush_mutator()`).
+/// This requires the feature `analysis`.
+///
+/// Arguments:
+/// * `mutator`: The mutator for the current thread.
+/// * `target`: The target for the write operation.
+/// * `site`: An identifier of the call site, chosen by the binding.
+#[cfg(feature = "analysis")]
+pub fn post_write_barrier_at_site<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+    target: BarrierWriteTarget,
+    site: usize,
+) {
+    let outcome = mutator.barrier().post_write_barrier_profiled(target);
+    if mutator.barrier_counts.record(site, outcome) {
+        mutator
+            .plan
+            .base()
+            .analysis_manager
+            .flush_barrier_counts(&mut mutator.barrier_counts);
+    }
+}
+
 /// Return an AllocatorSelector for the given allocation semantic. This method is provided
 /// so that VM compilers may call it to help generate allocation fast-path.
 ///
@@ -245,27 +590,73 @@ pub fn free_with_size<VM: VMBinding>(mmtk: &MMTK<VM>, addr: Address, old_size: u
     crate::util::malloc::free_with_size(mmtk, addr, old_size)
 }
 
+/// Allocate raw memory for the binding's own metadata (e.g. type information or bytecode) from
+/// the raw memory space of MMTk. The memory is counted in the heap, and an allocation by a mutator
+/// may trigger a GC if the heap is full, but the memory is never traced, moved or reclaimed by the
+/// GC. It must be freed with [`free_raw`]. The memory is not zeroed. Return zero if the heap is
+/// out of memory, after reporting it with `Collection::out_of_memory()`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The thread that allocates the memory. A GC is only triggered for a mutator thread.
+/// * `bytes`: The size of the memory in bytes.
+/// * `align`: The alignment of the memory. This must be a power of two, and at most a page.
+#[cfg(feature = "raw_memory_space")]
+pub fn alloc_raw<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    tls: VMThread,
+    bytes: usize,
+    align: usize,
+) -> Address {
+    mmtk.plan.base().raw_memory_space.alloc(tls, bytes, align)
+}
+
+/// Free the raw memory that was allocated by [`alloc_raw`] with the same size and alignment.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `addr`: The address returned by `alloc_raw()`.
+/// * `bytes`: The size that was passed to `alloc_raw()`.
+/// * `align`: The alignment that was passed to `alloc_raw()`.
+#[cfg(feature = "raw_memory_space")]
+pub fn free_raw<VM: VMBinding>(mmtk: &MMTK<VM>, addr: Address, bytes: usize, align: usize) {
+    mmtk.plan.base().raw_memory_space.free(addr, bytes, align)
+}
+
+/// Return the bytes of raw memory that are allocated by [`alloc_raw`] and not freed yet. This
+/// includes the rounding up of the sizes, but not the free memory in the pages of the space,
+/// which is included in the reserved pages of the space.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+#[cfg(feature = "raw_memory_space")]
+pub fn raw_memory_used_bytes<VM: VMBinding>(mmtk: &MMTK<VM>) -> usize {
+    mmtk.plan.base().raw_memory_space.used_bytes()
+}
+
 /// Poll for GC. MMTk will decide if a GC is needed. If so, this call will block
 /// the current thread, and trigger a GC. Otherwise, it will simply return.
 /// Usually a binding does not need to call this function. MMTk will poll for GC during its allocation.
 /// However, if a binding uses counted malloc (which won't poll for GC), they may want to poll for GC manually.
 /// This function should only be used by mutator threads.
 pub fn gc_poll<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMutatorThread) {
-    use crate::vm::{ActivePlan, Collection};
+    use crate::vm::ActivePlan;
     debug_assert!(
         VM::VMActivePlan::is_mutator(tls.0),
         "gc_poll() can only be called by a mutator thread."
     );
 
     let plan = mmtk.get_plan();
+    let plan = crate::plan::static_plan::devirtualize(plan);
     if plan.should_trigger_gc_when_heap_is_full() && plan.poll(false, None) {
         debug!("Collection required");
         assert!(plan.is_initialized(), "GC is not allowed here: collection is not initialized (did you call initialize_collection()?).");
-        VM::VMCollection::block_for_gc(tls);
+        plan.base().block_for_gc(tls);
     }
 }
 
-/// Run the main loop for the GC controller thread. This method does not return.
+/// Run the main loop for the GC controller thread. This method only returns after the MMTk instance
+/// shuts down (see `MMTK::shutdown`).
 ///
 /// Arguments:
 /// * `tls`: The thread that will be used as the GC controller.
@@ -280,7 +671,8 @@ pub fn start_control_collector<VM: VMBinding>(
     gc_controller.run(tls);
 }
 
-/// Run the main loop of a GC worker. This method does not return.
+/// Run the main loop of a GC worker. This method only returns after the MMTk instance shuts
+/// down (see `MMTK::shutdown`).
 ///
 /// Arguments:
 /// * `tls`: The thread that will be used as the GC worker.
@@ -298,7 +690,10 @@ pub fn start_worker<VM: VMBinding>(
 /// Initialize the scheduler and GC workers that are required for doing garbage collections.
 /// This is a mandatory call for a VM during its boot process once its thread system
 /// is ready. This should only be called once. This call will invoke Collection::spawn_gc_thread()
-/// to create GC threads.
+/// to create GC threads, unless MMTk is built with the `single_thread` feature or the option
+/// `threads` is 0, in which case GCs are done on the mutator threads that trigger them, and no
+/// thread is created. The `memory_pressure_gc` and `periodic_gc_ms` options need their own
+/// threads, and are ignored without GC threads.
 ///
 /// Arguments:
 /// * `mmtk`: A reference to an MMTk instance.
@@ -311,6 +706,71 @@ pub fn initialize_collection<VM: VMBinding>(mmtk: &'static MMTK<VM>, tls: VMThre
     );
     mmtk.scheduler.spawn_gc_threads(mmtk, tls);
     mmtk.plan.base().initialized.store(true, Ordering::SeqCst);
+    if mmtk.options.is_single_threaded() {
+        if *mmtk.options.memory_pressure_gc || *mmtk.options.periodic_gc_ms != 0 {
+            warn!("memory_pressure_gc and periodic_gc_ms are ignored without GC threads");
+        }
+        return;
+    }
+    if *mmtk.options.memory_pressure_gc {
+        *mmtk.memory_pressure_listener.lock().unwrap() =
+            crate::util::cgroup::spawn_memory_pressure_listener(mmtk);
+    }
+    if *mmtk.options.periodic_gc_ms != 0 {
+        *mmtk.periodic_gc_trigger.lock().unwrap() = Some(
+            crate::util::periodic_gc::spawn_periodic_gc_trigger(mmtk, *mmtk.options.periodic_gc_ms),
+        );
+    }
+}
+
+/// Notify MMTk that the VM expects to be idle for the given duration, e.g. while an event loop
+/// waits for its next event. If a GC is expected to fit in the idle period, MMTk triggers one now
+/// so it is less likely to interrupt the VM later: a full heap GC if the period is long enough for
+/// one, otherwise a GC of the plan's choice. The expected pause is estimated from the previous
+/// GCs, so MMTk does not collect before the first GC, nor if little has been allocated since the
+/// last GC. The GC is requested the same way as a GC triggered by the heap being full, so this
+/// function returns without waiting for it. Background work packets (see
+/// `add_background_work_packet`) run whenever the GC workers are idle, and do not need the
+/// notification.
+///
+/// Return what MMTk does in the idle period.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `idle`: How long the VM expects to be idle.
+pub fn notify_idle<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    idle: std::time::Duration,
+) -> crate::util::idle_gc::IdleGCAction {
+    use crate::util::idle_gc::{IdleGCAction, IdleGCEstimate};
+    let plan = mmtk.get_plan();
+    if !plan.is_initialized()
+        || !plan.should_trigger_gc_when_heap_is_full()
+        || plan.base().gc_in_progress()
+    {
+        return IdleGCAction::Nothing;
+    }
+    let stats = &plan.base().gc_stats;
+    let (average_pause, average_full_heap_pause) = stats.average_pauses();
+    let estimate = IdleGCEstimate {
+        average_pause,
+        average_full_heap_pause,
+        allocated_bytes: stats.allocated_bytes_since_gc(),
+        heap_size_bytes: (plan.get_total_pages() << LOG_BYTES_IN_PAGE) as u64,
+    };
+    let action = estimate.decide(idle);
+    match action {
+        IdleGCAction::Nothing => {}
+        IdleGCAction::Collection => plan.base().trigger_internal_collection_request(),
+        IdleGCAction::FullHeapCollection => {
+            plan.force_full_heap_collection();
+            plan.base().trigger_internal_collection_request();
+        }
+    }
+    if action != IdleGCAction::Nothing {
+        info!("Idle for {:?}, triggering {:?}", idle, action);
+    }
+    action
 }
 
 /// Allow MMTk to trigger garbage collection when heap is full. This should only be used in pair with disable_collection().
@@ -351,6 +811,23 @@ pub fn disable_collection<VM: VMBinding>(mmtk: &'static MMTK<VM>) {
         .store(false, Ordering::SeqCst);
 }
 
+/// Let MMTk know that the VM is shutting down, so MMTk stops triggering GCs. After this call, MMTk
+/// refuses GC requests (including explicit GCs through handle_user_collection_request()), and the
+/// allocation proceeds without a GC even if the heap is full, as if the collection is disabled.
+/// If an allocation still fails, it fails with `AllocationError::HeapOutOfMemory` instead of
+/// waiting for a GC.
+/// A GC that has been requested but not started yet is cancelled: MMTk does not stop the mutators
+/// for it, and calls `Collection::gc_cancelled()` so the binding can release the mutators blocked
+/// for it. A GC that has already started runs to completion, as it cannot be abandoned without
+/// leaving the heap in an inconsistent state. The binding should still be able to stop and
+/// resume the mutators for such a GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn prepare_for_shutdown<VM: VMBinding>(mmtk: &'static MMTK<VM>) {
+    mmtk.plan.base().gc_requester.shut_down();
+}
+
 /// Process MMTk run-time options. Returns true if the option is processed successfully.
 ///
 /// Arguments:
@@ -406,6 +883,145 @@ pub fn total_bytes<VM: VMBinding>(mmtk: &MMTK<VM>) -> usize {
     mmtk.plan.get_total_pages() << LOG_BYTES_IN_PAGE
 }
 
+/// Change the heap size at run time. Returns true if the new size is accepted.
+///
+/// The new bound is used by the GC trigger from the next allocation poll. Growing the heap
+/// takes effect immediately. If the heap is shrunk, the pages released by the spaces in the next
+/// GC are given back to the OS (see [`crate::util::platform::Platform::decommit`]), and if the
+/// heap is shrunk below the pages currently reserved, this triggers that GC and blocks the calling
+/// thread until the GC finishes. MallocSpace leaves it to malloc to give memory back to the OS.
+/// Note that the new size is not reflected in
+/// the `heap_size` option, and spaces that are sized by the initial heap size
+/// (e.g. the lock-free immortal space) are not affected.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The mutator thread that requests the resize.
+/// * `bytes`: The new heap size in bytes. It must not be zero.
+pub fn set_heap_size<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMutatorThread, bytes: usize) -> bool {
+    if bytes == 0 {
+        return false;
+    }
+    let pages = crate::util::conversions::bytes_to_pages_up(bytes);
+    info!(
+        "Heap size changed from {} pages to {} pages",
+        mmtk.plan.get_total_pages(),
+        pages
+    );
+    if pages < mmtk.plan.get_total_pages() {
+        // This is reset at the end of the next GC.
+        mmtk.plan
+            .for_each_space(&mut |space| space.set_decommit_on_release(true));
+    }
+    mmtk.plan.base().heap.set_total_pages(pages);
+    if mmtk.plan.is_initialized()
+        && mmtk.plan.should_trigger_gc_when_heap_is_full()
+        && mmtk.plan.get_reserved_pages() > pages
+    {
+        if mmtk.plan.base().gc_requester.request() {
+            mmtk.plan.base().block_for_gc(tls);
+        }
+    }
+    true
+}
+
+/// Return the cumulative GC statistics, such as the GC counts, the total pause time, and the total
+/// bytes allocated, copied and freed. This is cheap, and can be called at any time without stopping mutators.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn gc_stats<VM: VMBinding>(mmtk: &MMTK<VM>) -> GCStats {
+    mmtk.plan
+        .base()
+        .gc_stats
+        .snapshot(mmtk.plan.get_total_pages(), mmtk.plan.get_used_pages())
+}
+
+/// Return what each GC worker traced and copied in the last GC, and the pause time of the GC, so
+/// the binding can compute the tracing bandwidth and the liveness of the heap. The bytes of the
+/// traced objects are only counted with the option `count_traced_bytes`. The statistics are empty
+/// before the first GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn gc_tracing_stats<VM: VMBinding>(mmtk: &MMTK<VM>) -> GCTracingStats {
+    mmtk.plan.base().gc_stats.last_gc_tracing()
+}
+
+/// Return a token for the current GC statistics. Pass it to [`gc_stats_since`] later to get the
+/// statistics of the interval in between, e.g. to report the GC metrics of each interval to a
+/// monitoring agent. The statistics of a GC are either all in the interval or not at all.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn gc_stats_epoch<VM: VMBinding>(mmtk: &MMTK<VM>) -> GCStatsEpoch {
+    GCStatsEpoch::new(gc_stats(mmtk))
+}
+
+/// Return the GC statistics since the given epoch: the counters are the differences between now
+/// and the epoch, and the heap size and usage are the current ones.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `epoch`: A token returned by [`gc_stats_epoch`].
+pub fn gc_stats_since<VM: VMBinding>(mmtk: &MMTK<VM>, epoch: &GCStatsEpoch) -> GCStatsDelta {
+    epoch.delta(&gc_stats(mmtk))
+}
+
+/// Start measuring the GC statistics in the window with the given label, e.g. for a phase of a
+/// benchmark. A window that has been stopped continues measuring, and accumulates the
+/// statistics of all the periods in which it was running. Windows with different labels are
+/// independent, and can overlap. Return false if the window is already running.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `label`: The name of the window.
+pub fn start_stats_window<VM: VMBinding>(mmtk: &MMTK<VM>, label: &str) -> bool {
+    mmtk.plan.base().stats_windows.start(label, gc_stats(mmtk))
+}
+
+/// Stop measuring the GC statistics in the window with the given label, and return the
+/// statistics of the window. Return `None` if the window has never been started.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `label`: The name of the window.
+pub fn stop_stats_window<VM: VMBinding>(mmtk: &MMTK<VM>, label: &str) -> Option<WindowStats> {
+    mmtk.plan.base().stats_windows.stop(label, gc_stats(mmtk))
+}
+
+/// Clear the GC statistics of the window with the given label. If the window is running, it keeps
+/// measuring from now on. Return false if the window has never been started.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `label`: The name of the window.
+pub fn reset_stats_window<VM: VMBinding>(mmtk: &MMTK<VM>, label: &str) -> bool {
+    mmtk.plan.base().stats_windows.reset(label, gc_stats(mmtk))
+}
+
+/// Return the GC statistics of the window with the given label so far, or `None` if the window
+/// has never been started. The window may be running or stopped. The window measured between
+/// [`harness_begin`] and [`harness_end`] is labelled
+/// [`HARNESS_STATS_WINDOW`](crate::util::gc_stats::HARNESS_STATS_WINDOW).
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `label`: The name of the window.
+pub fn stats_window<VM: VMBinding>(mmtk: &MMTK<VM>, label: &str) -> Option<WindowStats> {
+    mmtk.plan.base().stats_windows.get(label, gc_stats(mmtk))
+}
+
+/// Return how much memory the current GC (or the last GC if no GC is in progress) used for copying,
+/// compared to the copy reserve of the plan. A GC that has used up all the free pages in the heap
+/// stops evacuating objects in the spaces that can keep objects in place (e.g. defrag in Immix).
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn copy_reserve_usage<VM: VMBinding>(mmtk: &MMTK<VM>) -> CopyReserveUsage {
+    mmtk.plan.base().gc_stats.copy_reserve_usage()
+}
+
 /// Trigger a garbage collection as requested by the user.
 ///
 /// Arguments:
@@ -415,6 +1031,83 @@ pub fn handle_user_collection_request<VM: VMBinding>(mmtk: &MMTK<VM>, tls: VMMut
     mmtk.plan.handle_user_collection_request(tls, false);
 }
 
+/// Trigger a garbage collection from a thread that is not a mutator (e.g. a thread that monitors
+/// the memory pressure of the system, or a test harness), and block the thread until the GC is
+/// finished. A GC that has not stopped the mutators yet serves the request, so the GC may start
+/// before this is called. If that GC does not collect the full heap, a request of
+/// `GCKind::FullHeap` waits for another GC. This is not ignored with the option `ignore_system_gc`.
+/// This must not be called from a mutator thread, as the GC waits for all the mutators to stop,
+/// and it cannot be used without GC threads, as the GCs are then done on the mutator threads.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `kind`: The kind of GC to do.
+pub fn request_gc_blocking<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    kind: GCKind,
+) -> Result<(), BlockingGCError> {
+    mmtk.plan.base().request_gc_blocking(kind)
+}
+
+/// Trigger a garbage collection of only the given spaces (a partial-heap collection), as
+/// requested by the user. The other spaces are not traced, so this is cheaper than a full heap
+/// collection, but the plan must remember the references into the spaces to offer it, e.g. the
+/// generational plans can collect only the space named `nursery`, and MarkSweep can collect only
+/// the `los` with the option `remember_los_references`. The plan may still collect the
+/// whole heap if it has to, e.g. if the heap is full. Return false without a GC if
+/// the plan does not have the spaces or cannot collect only them, or if the option
+/// `ignore_system_gc` is set.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The thread that triggers this collection request.
+/// * `spaces`: The names of the spaces to collect.
+pub fn handle_user_partial_collection_request<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    tls: VMMutatorThread,
+    spaces: &[&str],
+) -> bool {
+    match CollectionScope::from_names(&*mmtk.plan, spaces) {
+        Some(scope) if mmtk.plan.supports_partial_collection(&scope) => mmtk
+            .plan
+            .base()
+            .handle_user_partial_collection_request(tls, scope),
+        _ => false,
+    }
+}
+
+/// Trigger a garbage collection of only the objects of a heap (see [`crate::util::heap_id`]), as
+/// requested by the user. The objects of the other heaps are not collected, and their references
+/// into the heap are roots of the GC, so the plan must remember those references to offer it, e.g.
+/// MarkSweep can collect the objects of a heap with the option `remember_heap_references`. The
+/// mutators of all the heaps are stopped for the GC. The plan may still collect the whole heap if
+/// it has to. Return false without a GC if the plan cannot collect only the objects of a heap, if
+/// the heap is the shared heap, which the objects of all the heaps may refer to, or if the option
+/// `ignore_system_gc` is set.
+///
+/// This requires the feature `heap_ids`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The thread that triggers this collection request.
+/// * `heap_id`: The heap to collect.
+#[cfg(feature = "heap_ids")]
+pub fn handle_user_heap_collection_request<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    tls: VMMutatorThread,
+    heap_id: u8,
+) -> bool {
+    if heap_id == crate::util::heap_id::SHARED_HEAP {
+        return false;
+    }
+    let scope = CollectionScope::of_heap(&*mmtk.plan, heap_id);
+    mmtk.plan.supports_partial_collection(&scope)
+        && mmtk
+            .plan
+            .base()
+            .handle_user_partial_collection_request(tls, scope)
+}
+
 /// Is the object alive?
 ///
 /// Arguments:
@@ -423,6 +1116,54 @@ pub fn is_live_object(object: ObjectReference) -> bool {
     object.is_live()
 }
 
+/// Is the object reachable in the current GC? This is meant for the binding to process weak
+/// references, finalizers and other weak tables, e.g. in [`crate::vm::Collection::process_weak_refs`].
+///
+/// The result is only meaningful after the transitive closure that the binding wants to observe
+/// is complete, and before the spaces are released at the end of the GC:
+/// * An object in a space that is collected in the current GC is reachable if it has been traced.
+/// * An object in a space that is not collected in the current GC (e.g. the mature space in
+///   a nursery GC) is always reachable.
+/// * An object in a space that is never collected (e.g. the immortal space) is reachable if it
+///   has been traced in the current GC, although it will never be reclaimed.
+///
+/// If the object has been moved in the current GC, both the old and the new reference are
+/// reachable. Use [`get_forwarded_object`] to get the new reference.
+///
+/// Arguments:
+/// * `object`: The object reference to query. This returns false for a null reference.
+pub fn is_reachable(object: ObjectReference) -> bool {
+    object.is_reachable()
+}
+
+/// Get the new reference of an object if it has been moved in the current GC, or `None` if the
+/// object has not been moved (or is not reachable). This is meant for the binding to update its
+/// weak tables during a GC, e.g. in [`crate::vm::Collection::process_weak_refs`].
+///
+/// The result depends on the policy of the space that the object is in:
+/// * Copying spaces (e.g. SemiSpace, GenCopy and the nursery of the generational plans) forward
+///   an object when they trace it. The forwarding pointer is available from the moment the
+///   object is traced until the end of the GC.
+/// * Immix moves objects out of the defrag source blocks in a defrag GC. The forwarding pointer
+///   is available from the moment the object is traced until the end of the GC.
+/// * MarkCompact computes the new locations after the marking trace. The forwarding pointer is
+///   available from the moment the new locations are computed until the objects are compacted,
+///   which includes the reference updating trace.
+/// * Non-moving spaces always return `None`.
+///
+/// The result is undefined outside the windows above. In particular, the binding should not call
+/// this function from a mutator, as the memory of a moved object may have been reused.
+///
+/// Arguments:
+/// * `object`: The object reference to query. This returns `None` for a null reference.
+pub fn get_forwarded_object(object: ObjectReference) -> Option<ObjectReference> {
+    if object.is_null() {
+        None
+    } else {
+        object.get_forwarded_object()
+    }
+}
+
 /// Check if `addr` is the address of an object reference to an MMTk object.
 ///
 /// Concretely:
@@ -469,6 +1210,56 @@ pub fn is_mmtk_object(addr: Address) -> bool {
     crate::util::is_mmtk_object::is_mmtk_object(addr)
 }
 
+/// Find the object that may contain an interior pointer, i.e. the object with the highest address
+/// at or before `internal_ptr`. MMTk finds the object from the object start map, which records the
+/// first object that starts in each card, so it does not scan the alloc bits from the start of the
+/// chunk. MMTk does not know the size of the object, so the binding needs to check if the object
+/// contains `internal_ptr` (e.g. with `ObjectModel::get_current_size()`).
+///
+/// This requires the feature `object_start_map`.
+///
+/// Arguments:
+/// * `internal_ptr`: An address that may point into an object.
+/// * `max_search_bytes`: The maximum distance from the object to `internal_ptr`. The binding can
+///   use the size of its largest objects that may have interior pointers to bound the search.
+#[cfg(feature = "object_start_map")]
+pub fn find_object_from_internal_pointer(
+    internal_ptr: Address,
+    max_search_bytes: usize,
+) -> Option<ObjectReference> {
+    crate::util::object_start_map::find_object_before(internal_ptr, max_search_bytes)
+}
+
+/// The number of chunks that are compressed, and the bytes of their compressed data, if the
+/// option `chunk_compression_gcs` is set.
+///
+/// This requires the feature `chunk_compression`.
+#[cfg(feature = "chunk_compression")]
+pub fn chunk_compression_stats<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+) -> crate::util::heap::chunk_compression::ChunkCompressionStats {
+    mmtk.plan
+        .base()
+        .chunk_compression
+        .as_ref()
+        .map(|chunk_compression| chunk_compression.stats())
+        .unwrap_or_default()
+}
+
+/// Decompress the compressed chunks in `[start, start + bytes)`, if the option
+/// `chunk_compression_gcs` is set. When the program accesses a compressed chunk, the access
+/// faults and MMTk decompresses the chunk. The kernel does not fault when a system call accesses
+/// a compressed chunk, but fails the call with `EFAULT`, so the binding must call this before it
+/// passes heap memory to the kernel (e.g. as the buffer of `read()` or `write()`).
+///
+/// This requires the feature `chunk_compression`.
+#[cfg(feature = "chunk_compression")]
+pub fn ensure_uncompressed<VM: VMBinding>(mmtk: &MMTK<VM>, start: Address, bytes: usize) {
+    if let Some(chunk_compression) = mmtk.plan.base().chunk_compression.as_ref() {
+        chunk_compression.touch(start, bytes);
+    }
+}
+
 /// Return true if the `object` lies in a region of memory where
 /// -   only MMTk can allocate into, or
 /// -   only MMTk's delegated memory allocator (such as a malloc implementation) can allocate into
@@ -501,6 +1292,523 @@ pub fn is_in_mmtk_spaces(object: ObjectReference) -> bool {
     object.is_in_any_space()
 }
 
+/// Shrink an object in place. The binding can call this when an object (e.g. a string, or an array
+/// with spare capacity) no longer needs all of its bytes. If this returns true, the binding should report
+/// `new_size` as the size of the object from then on (e.g. in `ObjectModel::get_current_size()`).
+/// The large object space gives the whole pages after the shrunk object back immediately.
+/// Other policies reclaim the bytes after the object along with the object.
+/// If this returns false, the object and its size are unchanged.
+///
+/// Arguments:
+/// * `object`: The object to shrink. It must be an object allocated by MMTk.
+/// * `old_size`: The current size of the object (in bytes).
+/// * `new_size`: The new size of the object (in bytes). It must not be larger than `old_size`.
+pub fn shrink_object(object: ObjectReference, old_size: usize, new_size: usize) -> bool {
+    debug_assert!(new_size <= old_size);
+    debug_assert!(new_size >= MIN_OBJECT_SIZE);
+    crate::mmtk::SFT_MAP
+        .get(object.to_address())
+        .resize_object_in_place(object, old_size, new_size)
+}
+
+/// Grow an object in place, without moving it. This only succeeds if the policy of the object has
+/// unused bytes right after the object that it can give to the object. For example, objects in the large
+/// object space can grow up to the end of their last page, and objects in malloc spaces can grow up to
+/// the usable size of their malloc'd memory. If this returns true, the binding can use the bytes in
+/// `[object_start + old_size, object_start + new_size)`, and should report `new_size` as the size of the
+/// object from then on. If this returns false, the binding needs to allocate a new object instead.
+///
+/// Arguments:
+/// * `object`: The object to grow. It must be an object allocated by MMTk.
+/// * `old_size`: The current size of the object (in bytes).
+/// * `new_size`: The new size of the object (in bytes). It must not be smaller than `old_size`.
+pub fn grow_object_in_place(object: ObjectReference, old_size: usize, new_size: usize) -> bool {
+    debug_assert!(new_size >= old_size);
+    crate::mmtk::SFT_MAP
+        .get(object.to_address())
+        .resize_object_in_place(object, old_size, new_size)
+}
+
+/// Request MMTk to move an object in the next GC that collects the space of the object. This is a hint,
+/// and the object will not necessarily be moved, e.g. if the GC runs out of space for copying, or the
+/// object is pinned. The binding will see the new location of the object (if it is moved) through the
+/// normal forwarding mechanism, i.e. the references to the object are updated during the GC.
+/// This can be used to implement locality optimizations in the binding, e.g. to colocate objects
+/// that are frequently accessed together.
+///
+/// For copying spaces (e.g. the nursery), every live object is moved anyway. For Immix spaces,
+/// the block that contains the object will be evacuated in the next defrag GC.
+///
+/// Return true if the policy of the object accepts the request, and false if the object cannot be
+/// moved by its policy (e.g. it is in a non-moving space).
+///
+/// Arguments:
+/// * `object`: The object to move. It must be an object allocated by MMTk.
+pub fn request_relocation(object: ObjectReference) -> bool {
+    crate::mmtk::SFT_MAP
+        .get(object.to_address())
+        .request_relocation(object)
+}
+
+/// Pin an object, so GCs do not move it until it is unpinned with `unpin_object`. This is meant for
+/// objects that temporarily cannot move, e.g. while native code accesses them. Pins are counted, so
+/// an object pinned twice stays pinned until it is unpinned twice. The binding must keep the object
+/// alive while it is pinned, and must not pin or unpin objects during a GC.
+///
+/// Immix spaces pin the lines of the object, not its whole block, so the rest of the block can
+/// still be evacuated by defrag GCs. The objects that start on a pinned line are kept in place,
+/// and the lines are considered for defrag again in the first GC after they are unpinned. The
+/// pinned roots and the transitive pinning regions pin the lines in the same way, for one GC. The
+/// pins of the objects on a line are counted in 8 bits, so pinning them more than 255 times
+/// panics.
+///
+/// Return true if the object is pinned, and false if its policy cannot pin objects (e.g. a copying
+/// space).
+///
+/// Arguments:
+/// * `object`: The object to pin. It must be an object allocated by MMTk.
+pub fn pin_object(object: ObjectReference) -> bool {
+    crate::mmtk::SFT_MAP
+        .get(object.to_address())
+        .pin_object(object)
+}
+
+/// Remove a pin of an object added with `pin_object`. Return false if the policy of the object
+/// cannot pin objects.
+///
+/// Arguments:
+/// * `object`: The object to unpin. It must be pinned.
+pub fn unpin_object(object: ObjectReference) -> bool {
+    crate::mmtk::SFT_MAP
+        .get(object.to_address())
+        .unpin_object(object)
+}
+
+/// Transitively pin the objects reachable from `root` until the returned region is dropped. GCs do
+/// not move those objects while the region is active, so a graph of objects can be exposed to
+/// native code without pinning each object.  The objects reachable from `root` are found by GC
+/// when it starts, so objects that become reachable while the region is active are also pinned.
+/// Regions can be nested, and the same object can be the root of multiple regions.
+///
+/// The root (and so all the pinned objects) is kept alive while the region is active. The plan
+/// needs to be able to keep any object in place (see `PlanConstraints::supports_pinned_roots`),
+/// which is not the case for the plans with copy spaces, such as the generational plans, whose
+/// nursery objects are always moved. Return `None` if the plan cannot pin the objects.
+///
+/// This requires the feature `transitive_pinning`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `root`: The object to pin transitively. It must be an object allocated by MMTk.
+#[cfg(feature = "transitive_pinning")]
+pub fn pin_transitively<VM: VMBinding>(
+    mmtk: &'static MMTK<VM>,
+    root: ObjectReference,
+) -> Option<PinningRegion<VM>> {
+    if !mmtk.plan.constraints().supports_pinned_roots {
+        return None;
+    }
+    Some(PinningRegion::enter(mmtk, root))
+}
+
+/// Register a reference embedded in compiled code, e.g. in an instruction immediate, as a strong
+/// root. MMTk cannot update such a reference as an edge, so if a GC moves the object, MMTk calls
+/// `updater` with the slot and the new address of the object, so the binding can re-encode the
+/// reference into the code. The updaters are called in the Release stage of a GC, while the
+/// mutators are stopped. Registering a slot again replaces its previous registration.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `slot`: The address of the reference in the code. It identifies the code root.
+/// * `object`: The object that the slot refers to.
+/// * `updater`: The function to update the slot once the object has moved.
+pub fn register_code_root<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    slot: Address,
+    object: ObjectReference,
+    updater: CodeRootUpdater,
+) {
+    mmtk.code_roots.register(slot, object, updater)
+}
+
+/// Unregister a code root, e.g. when its code is freed. This does nothing if the slot is not
+/// registered.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `slot`: The address of the reference in the code.
+pub fn unregister_code_root<VM: VMBinding>(mmtk: &MMTK<VM>, slot: Address) {
+    mmtk.code_roots.unregister(slot)
+}
+
+/// Request a group of objects to be placed together, e.g. the objects that are accessed together
+/// by a hot call site. The next GC that copies objects as it traces them copies the objects of the
+/// group one after another, before the closure, so they usually end up in the same block. The
+/// request does not keep the objects alive until then: it is dropped if one of them dies. The
+/// plans that cannot copy objects this way (e.g. MarkSweep and MarkCompact) drop the request in
+/// their next GC. See [`crate::util::colocation`].
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `objects`: The objects to co-locate, in the order to place them.
+pub fn request_colocation<VM: VMBinding>(mmtk: &MMTK<VM>, objects: Vec<ObjectReference>) {
+    mmtk.colocation.request(objects)
+}
+
+/// The layout of the side metadata of the spaces of an MMTk instance, i.e. the address range of
+/// each side metadata spec, and the spaces that use it. This can be printed for debugging.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn side_metadata_layout<VM: VMBinding>(mmtk: &MMTK<VM>) -> &SideMetadataLayout {
+    &mmtk.side_metadata_layout
+}
+
+/// The statistics of the co-location requests, e.g. how many groups the GCs have placed in the
+/// same block.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn colocation_stats<VM: VMBinding>(mmtk: &MMTK<VM>) -> ColocationStats {
+    mmtk.colocation.stats()
+}
+
+/// Register the layout of a type of objects, so MMTk core scans the objects of the type without
+/// calling back to the binding for each object. The type of an object is identified by the word
+/// at `Scanning::LAYOUT_TYPE_ID_OFFSET` from the object reference, which must be set for the
+/// layouts to be used. A type can only have one layout: registering a different layout for a type
+/// that already has one panics. At most `max_object_layouts` (an option) layouts can be
+/// registered, and the type id must not be 0.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `type_id`: The type id, as stored in the header of the objects of the type.
+/// * `layout`: The reference fields of the objects of the type.
+pub fn register_object_layout<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    type_id: usize,
+    layout: ObjectLayout,
+) {
+    mmtk.object_layouts.register(type_id, layout)
+}
+
+/// Iterate over the objects in a space in address order. The objects are found by the alloc bit, so this
+/// includes all the objects allocated in the space that have not been reclaimed by a GC yet. This can be
+/// used by a binding for tasks that need to visit the objects of a space, such as rebuilding its own
+/// tables of objects or verifying its invariants. Return `None` if there is no space with the given name.
+/// The names of the spaces can be found by [`MMTK::spaces()`](../mmtk/struct.MMTK.html#method.spaces).
+///
+/// This requires the feature `global_alloc_bit`.
+///
+/// # Safety
+///
+/// The iterator reads the alloc bits and the objects in the space without synchronization. The binding
+/// must only use the iterator at a safepoint, i.e. all the mutators are stopped and will not allocate,
+/// and there is no GC in progress. The binding must not hold any object reference from the iterator
+/// beyond the safepoint, as the object may be moved or reclaimed by a later GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `space_name`: The name of the space.
+#[cfg(feature = "global_alloc_bit")]
+pub unsafe fn iterate_objects_in_space<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    space_name: &str,
+) -> Option<Box<dyn Iterator<Item = ObjectReference>>> {
+    mmtk.plan
+        .get_spaces()
+        .into_iter()
+        .find(|space| space.get_name() == space_name)
+        .map(|space| space.iterate_objects())
+}
+
+/// Get the age of an object, i.e. the number of GCs that have finished since the object was allocated.
+/// The age is recorded in a side metadata byte, and wraps around every 256 GCs. The age is only
+/// recorded for objects whose allocation is followed by [`post_alloc`]. If a binding implements
+/// the post alloc fast-path on its side, it should not call this function.
+///
+/// This requires the feature `object_age`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "object_age")]
+pub fn object_age<VM: VMBinding>(mmtk: &MMTK<VM>, object: ObjectReference) -> usize {
+    let birth = crate::util::object_age::get_birth_epoch(object);
+    crate::util::object_age::age(birth, mmtk.plan.base().gc_stats.gc_count()) as usize
+}
+
+/// Get the generation of an object in a generational plan (GenCopy or GenImmix), or `None` with the
+/// other plans. A young object has survived fewer nursery GCs than the tenuring threshold of the
+/// plan, and may be moved or promoted in the next nursery GC. A mature object is only collected by
+/// full heap GCs. Bindings can use this for generational hooks, e.g. to only keep the caches keyed
+/// to objects that are tenured. The generation of an object may change at any GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+pub fn object_generation<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    object: ObjectReference,
+) -> Option<ObjectGeneration> {
+    mmtk.plan.object_generation(object)
+}
+
+/// Replace the policy that decides how many nursery GCs a young object survives before it is
+/// promoted to the mature space. The threshold of the policy is read at the start of each GC, and
+/// is capped by the number of nursery GCs that the plan can keep young objects for: 1 for GenImmix,
+/// and the option `survivor_age_threshold` for GenCopy. Returns false, and keeps the policy, if the
+/// plan is not generational.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `policy`: The tenuring policy.
+pub fn set_tenuring_policy<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    policy: Box<dyn TenuringPolicy>,
+) -> bool {
+    mmtk.plan.set_tenuring_policy(policy)
+}
+
+/// Get the user data of an object, which the binding sets with [`set_object_user_data`]. MMTk keeps
+/// the value when the object is moved. The value is 0 for the objects allocated through
+/// [`post_alloc`] until it is set. If a binding implements the post alloc fast-path on its side, it
+/// needs to clear the value itself.
+///
+/// The objects that use the user data need to be at least
+/// [`MIN_OBJECT_SIZE`](crate::util::object_user_data::MIN_OBJECT_SIZE) bytes. Smaller objects
+/// may share the value with their neighbours.
+///
+/// This requires the feature `object_user_data`.
+///
+/// Arguments:
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "object_user_data")]
+pub fn object_user_data(object: ObjectReference) -> usize {
+    crate::util::object_user_data::get_user_data(object)
+}
+
+/// Set the user data of an object (see [`object_user_data`]).
+///
+/// This requires the feature `object_user_data`.
+///
+/// Arguments:
+/// * `object`: The object to update. It must be an object allocated by MMTk.
+/// * `value`: The value. It must fit in
+///   [`BITS_IN_USER_DATA`](crate::util::object_user_data::BITS_IN_USER_DATA) bits.
+#[cfg(feature = "object_user_data")]
+pub fn set_object_user_data(object: ObjectReference, value: usize) {
+    crate::util::object_user_data::set_user_data(object, value)
+}
+
+/// Set the heap (e.g. the isolate) of the objects that a mutator allocates after this (see
+/// [`crate::util::heap_id`]). The objects are tagged with the heap in [`post_alloc`], so if a
+/// binding implements the post alloc fast-path on its side, it needs to tag the objects itself with
+/// [`set_object_heap_id`]. The heap of a mutator is the shared heap 0 until it is set.
+///
+/// This requires the feature `heap_ids`.
+///
+/// Arguments:
+/// * `mutator`: The mutator.
+/// * `heap_id`: The heap of the mutator.
+#[cfg(feature = "heap_ids")]
+pub fn set_mutator_heap_id<VM: VMBinding>(mutator: &mut Mutator<VM>, heap_id: u8) {
+    mutator.heap_id = heap_id;
+}
+
+/// Get the heap of an object (see [`set_mutator_heap_id`]).
+///
+/// This requires the feature `heap_ids`.
+///
+/// Arguments:
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "heap_ids")]
+pub fn object_heap_id(object: ObjectReference) -> u8 {
+    crate::util::heap_id::get_heap_id(object)
+}
+
+/// Set the heap of an object, e.g. for an object that the binding allocates on its fast path, or
+/// moves to the shared heap.
+///
+/// This requires the feature `heap_ids`.
+///
+/// Arguments:
+/// * `object`: The object to update. It must be an object allocated by MMTk.
+/// * `heap_id`: The heap of the object.
+#[cfg(feature = "heap_ids")]
+pub fn set_object_heap_id(object: ObjectReference, heap_id: u8) {
+    crate::util::heap_id::set_heap_id(object, heap_id)
+}
+
+/// Declare an object immutable: its reference fields will not change any more (see
+/// [`crate::util::immutable_object`]). The binding must only call this once all the reference
+/// fields of the object have been initialized. An object is mutable when it is allocated, if its
+/// allocation is followed by [`post_alloc`].
+///
+/// This requires the feature `immutable_objects`.
+///
+/// Arguments:
+/// * `object`: The object. It must be an object allocated by MMTk.
+#[cfg(feature = "immutable_objects")]
+pub fn set_object_immutable(object: ObjectReference) {
+    crate::util::immutable_object::set_immutable(object)
+}
+
+/// Is an object immutable (see [`set_object_immutable`])?
+///
+/// This requires the feature `immutable_objects`.
+///
+/// Arguments:
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "immutable_objects")]
+pub fn is_object_immutable(object: ObjectReference) -> bool {
+    crate::util::immutable_object::is_immutable(object)
+}
+
+/// Get the identity hash code of an object. The hash code is the address of the object when this
+/// function is first called for the object, and it stays the same after the object is moved. MMTk
+/// records the hash state of the object in side metadata, and stores the hash code in the object
+/// (see [`crate::vm::ObjectModel::hash_word_address`]) when the object is first moved after being
+/// hashed. The state is reset for the objects allocated through [`post_alloc`]. If a binding
+/// implements the post alloc fast-path on its side, it needs to reset the state itself.
+///
+/// This requires the feature `object_hash`.
+///
+/// Arguments:
+/// * `object`: The object to hash. It must be an object allocated by MMTk.
+#[cfg(feature = "object_hash")]
+pub fn identity_hash<VM: VMBinding>(object: ObjectReference) -> usize {
+    crate::util::object_hash::identity_hash::<VM>(object)
+}
+
+/// The extra bytes that an object has for its identity hash code, i.e. a word if the object has moved
+/// since its hash code was taken, or 0 otherwise. The binding includes this in the current size
+/// of the object.
+///
+/// This requires the feature `object_hash`.
+///
+/// Arguments:
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "object_hash")]
+pub fn hash_extra_bytes(object: ObjectReference) -> usize {
+    crate::util::object_hash::hash_extra_bytes(object)
+}
+
+/// The extra bytes that a new copy of an object needs for its identity hash code, i.e. a word if the
+/// hash code of the object has been taken, or 0 otherwise. The binding includes this in the size of
+/// the object when copied.
+///
+/// This requires the feature `object_hash`.
+///
+/// Arguments:
+/// * `object`: The object to query. It must be an object allocated by MMTk.
+#[cfg(feature = "object_hash")]
+pub fn hash_extra_bytes_when_copied(object: ObjectReference) -> usize {
+    crate::util::object_hash::hash_extra_bytes_when_copied(object)
+}
+
+/// Compute the dominator tree of the objects reachable from the given roots, and write a report of the
+/// objects with the largest retained sizes (the bytes that would be freed if the object became unreachable),
+/// along with their immediate dominators. This can be used to find the objects that keep most of the heap
+/// alive, e.g. to hunt memory leaks. The object graph is built with [`Scanning::scan_object()`](../vm/trait.Scanning.html#tymethod.scan_object).
+///
+/// This requires the feature `analysis`. The analysis is expensive in both time and memory, and it is
+/// intended for debugging.
+///
+/// The binding must call this at a safepoint, i.e. all the mutators are stopped and there is no GC in progress,
+/// so the object graph does not change during the analysis.
+///
+/// Arguments:
+/// * `tls`: The thread that runs the analysis. It is passed to `Scanning::scan_object()`.
+/// * `roots`: The roots of the object graph.
+/// * `max_entries`: The number of objects to include in the report.
+/// * `out`: Where the report is written.
+#[cfg(feature = "analysis")]
+pub fn write_dominator_report<VM: VMBinding>(
+    tls: VMWorkerThread,
+    roots: &[ObjectReference],
+    max_entries: usize,
+    out: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    crate::util::analysis::dominators::write_dominator_report::<VM>(tls, roots, max_entries, out)
+}
+
+/// Register a sink that receives the object graph traced by GCs. The roots, the edges and the moved
+/// objects are reported to the sink as GC workers trace the heap, and the sink can decide for each GC
+/// whether the graph should be exported. A previously registered sink is replaced.
+/// This requires the feature `graph_export`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `sink`: The sink that receives the object graph.
+#[cfg(feature = "graph_export")]
+pub fn set_graph_sink<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    sink: Box<dyn crate::util::graph_export::GraphSink>,
+) {
+    mmtk.plan.base().graph_exporter.set_sink(Some(sink));
+}
+
+/// Add a callback that validates the objects reached by the sanity GC, so the sanity GC also
+/// verifies the invariants of the binding, e.g. that the class pointer of each object is valid.
+/// The sanity GC calls each validator once for every object that it reaches from the roots after a
+/// GC, on a GC worker while the mutators are stopped. If any object fails a validator, the sanity
+/// GC fails with the errors of the objects (see `Collection::gc_failed`).
+/// This requires the feature `sanity`.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `validator`: The callback, which returns an error that tells what is wrong with an object.
+#[cfg(feature = "sanity")]
+pub fn add_sanity_object_validator<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    validator: Box<dyn Fn(ObjectReference) -> Result<(), String> + Send>,
+) {
+    mmtk.sanity_checker.lock().unwrap().add_validator(validator);
+}
+
+/// Take a snapshot of the alloc bits and the mark bits of an address range, e.g. for a memory
+/// visualizer to render the occupancy of the heap. The snapshot tells for each granule (the minimum
+/// object size) of the range whether there is an object at the granule, and whether the last GC
+/// found the object live. It can be serialized with
+/// [`HeapBitmap::to_bytes`](crate::util::heap_bitmap::HeapBitmap::to_bytes).
+/// This requires the feature `heap_bitmap`.
+///
+/// This must be called at a safepoint: all the mutators are stopped, and no GC is in progress.
+///
+/// Arguments:
+/// * `start`: The start address of the range.
+/// * `end`: The end address of the range (exclusive).
+#[cfg(feature = "heap_bitmap")]
+pub fn heap_bitmap(start: Address, end: Address) -> crate::util::heap_bitmap::HeapBitmap {
+    crate::util::heap_bitmap::HeapBitmap::capture(start, end)
+}
+
+/// Return the occupancy of the spaces sampled at the end of each of the latest GCs (see the option
+/// `heap_timeline_gcs`), in the order the samples were taken. There is a sample for each space at
+/// each GC. This is empty unless the option `heap_timeline` is set.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn heap_timeline<VM: VMBinding>(mmtk: &MMTK<VM>) -> Vec<HeapTimelineSample> {
+    mmtk.plan.base().heap_timeline.samples()
+}
+
+/// Write the occupancy of the spaces sampled at the end of each GC so far (see [`heap_timeline`])
+/// as CSV, with the columns `gc`, `time_ns`, `space`, `committed_bytes`, `used_bytes`,
+/// `fragmentation` and `resident_bytes`. The resident bytes are empty unless the option
+/// `heap_timeline_resident` is set.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `out`: Where to write the CSV.
+pub fn write_heap_timeline_csv<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    out: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    mmtk.plan.base().heap_timeline.write_csv(out)
+}
+
 /// Is the address in the mapped memory? The runtime can use this function to check
 /// if an address is mapped by MMTk. Note that this is different than is_in_mmtk_spaces().
 /// For malloc spaces, MMTk does not map those addresses (malloc does the mmap), so
@@ -535,6 +1843,7 @@ pub fn modify_check<VM: VMBinding>(mmtk: &MMTK<VM>, object: ObjectReference) {
 /// * `reff`: The weak reference to add.
 pub fn add_weak_candidate<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference) {
     mmtk.reference_processors.add_weak_candidate::<VM>(reff);
+    keep_reference_out_of_los_ref_counting(mmtk, reff);
 }
 
 /// Add a reference to the list of soft references. A binding may
@@ -545,6 +1854,7 @@ pub fn add_weak_candidate<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference)
 /// * `reff`: The soft reference to add.
 pub fn add_soft_candidate<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference) {
     mmtk.reference_processors.add_soft_candidate::<VM>(reff);
+    keep_reference_out_of_los_ref_counting(mmtk, reff);
 }
 
 /// Add a reference to the list of phantom references. A binding may
@@ -555,6 +1865,19 @@ pub fn add_soft_candidate<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference)
 /// * `reff`: The phantom reference to add.
 pub fn add_phantom_candidate<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference) {
     mmtk.reference_processors.add_phantom_candidate::<VM>(reff);
+    keep_reference_out_of_los_ref_counting(mmtk, reff);
+}
+
+/// A nursery GC does not reach the mature references and referents that the reference processors
+/// hold, so they must not be reclaimed by the reference counts of large objects.
+fn keep_reference_out_of_los_ref_counting<VM: VMBinding>(mmtk: &MMTK<VM>, reff: ObjectReference) {
+    if crate::policy::los_ref_count::is_enabled(&mmtk.options, mmtk.plan.constraints()) {
+        let los = mmtk.plan.common().get_los();
+        los.keep_out_of_ref_counting(reff);
+        los.keep_out_of_ref_counting(<VM::VMReferenceGlue as ReferenceGlue<VM>>::get_referent(
+            reff,
+        ));
+    }
 }
 
 /// Generic hook to allow benchmarks to be harnessed. We do a full heap
@@ -576,6 +1899,58 @@ pub fn harness_end<VM: VMBinding>(mmtk: &'static MMTK<VM>) {
     mmtk.harness_end();
 }
 
+/// Add an event counter of the binding (e.g. the number of JNI weak references cleared) to the
+/// MMTk statistics. Like the counters of MMTk, it is reported in the statistics printed at the end
+/// of the harness, counted separately for the mutator phases and the GC pauses.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `name`: The name of the counter. It must be different from the names of other counters.
+/// * `implicit_start`: Whether the counter starts when the harness begins. Otherwise the
+///   binding needs to start it (see [`Counter::start`](crate::util::statistics::Counter::start)).
+/// * `merge_phases`: Whether the mutator phases and the GC pauses are reported together.
+pub fn new_event_counter<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    name: &str,
+    implicit_start: bool,
+    merge_phases: bool,
+) -> Arc<Mutex<EventCounter>> {
+    mmtk.plan
+        .base()
+        .stats
+        .new_event_counter(name, implicit_start, merge_phases)
+}
+
+/// Add a size counter of the binding (e.g. the bytes deduplicated by string deduplication) to
+/// the MMTk statistics. It counts both the events and their volume, which are reported as
+/// `name` and `name.volume`. See [`new_event_counter`] for the arguments.
+pub fn new_size_counter<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    name: &str,
+    implicit_start: bool,
+    merge_phases: bool,
+) -> Mutex<SizeCounter> {
+    mmtk.plan
+        .base()
+        .stats
+        .new_size_counter(name, implicit_start, merge_phases)
+}
+
+/// Add a timer of the binding to the MMTk statistics. The binding starts and stops the timer to
+/// measure the time of its work, which is reported in milliseconds. See [`new_event_counter`]
+/// for the arguments.
+pub fn new_timer<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    name: &str,
+    implicit_start: bool,
+    merge_phases: bool,
+) -> Arc<Mutex<Timer>> {
+    mmtk.plan
+        .base()
+        .stats
+        .new_timer(name, implicit_start, merge_phases)
+}
+
 /// Register a finalizable object. MMTk will retain the liveness of
 /// the object even if it is not reachable from the program.
 /// Note that finalization upon exit is not supported.
@@ -591,6 +1966,14 @@ pub fn add_finalizer<VM: VMBinding>(
         warn!("add_finalizer() is called when no_finalizer = true");
     }
 
+    // A nursery GC does not reach the mature objects that are registered for finalization, so
+    // they must not be reclaimed by the reference counts of large objects.
+    if crate::policy::los_ref_count::is_enabled(&mmtk.options, mmtk.plan.constraints()) {
+        mmtk.plan
+            .common()
+            .get_los()
+            .keep_out_of_ref_counting(object.get_reference());
+    }
     mmtk.finalizable_processor.lock().unwrap().add(object);
 }
 
@@ -695,6 +2078,40 @@ pub fn add_work_packets<VM: VMBinding>(
     mmtk.scheduler.work_buckets[bucket].bulk_add(packets)
 }
 
+/// Schedule the parallel tasks of the binding, such as string deduplication or sweeping the code
+/// cache, to be executed by the GC workers in the stage of the builder. The tasks without
+/// dependencies are added to the work bucket of the stage now, and the other tasks are added once
+/// the tasks they depend on have finished, so all the tasks are finished before the stage ends.
+/// This should be called during a GC, before the stage of the builder ends, e.g. from
+/// `Collection::stop_all_mutators` or from a work packet of an earlier stage.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tasks`: The tasks to be scheduled.
+pub fn schedule_vm_tasks<VM: VMBinding>(mmtk: &'static MMTK<VM>, tasks: VMTaskBuilder<VM>) {
+    tasks.schedule(&mmtk.scheduler)
+}
+
+/// Add a low-priority background work packet, such as a packet that sweeps or zeroes memory
+/// lazily, or aggregates statistics. A GC worker executes the packet when the worker has nothing
+/// else to do and no GC is in progress. When a GC is triggered, the GC waits for the background
+/// packets being executed to finish, so a background packet should be short, and split long
+/// tasks into several packets. The background packets that have not started when a GC is
+/// triggered are executed after the GC.
+///
+/// Work packets that a background packet adds to a work bucket are not background packets.
+/// A background packet should use this function to add more background packets.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `packet`: The work packet to be added.
+pub fn add_background_work_packet<VM: VMBinding, W: GCWork<VM>>(
+    mmtk: &'static MMTK<VM>,
+    packet: W,
+) {
+    mmtk.scheduler.add_background_work(Box::new(packet))
+}
+
 /// Add a callback to be notified after the transitive closure is finished.
 /// The callback should return true if it add more work packets to the closure bucket.
 pub fn on_closure_end<VM: VMBinding>(mmtk: &'static MMTK<VM>, f: Box<dyn Send + Fn() -> bool>) {
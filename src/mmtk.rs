@@ -1,23 +1,35 @@
 ///! MMTk instance.
 use crate::plan::Plan;
 use crate::policy::space::SFTMap;
+use crate::policy::space::SpaceInfo;
 use crate::scheduler::GCWorkScheduler;
 
+use crate::util::code_roots::CodeRoots;
+use crate::util::colocation::Colocation;
 #[cfg(feature = "extreme_assertions")]
 use crate::util::edge_logger::EdgeLogger;
+use crate::util::epoch::Epochs;
+use crate::util::error::MMTKError;
 use crate::util::finalizable_processor::FinalizableProcessor;
+use crate::util::gc_critical::GCCriticalRegions;
+use crate::util::gc_stats::HARNESS_STATS_WINDOW;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::map::Map;
+use crate::util::metadata::side_metadata::SideMetadataLayout;
+use crate::util::object_layout::ObjectLayouts;
 use crate::util::opaque_pointer::*;
-use crate::util::options::Options;
+use crate::util::options::{Options, OptionsBuilder};
+use crate::util::platform::PlatformThread;
 use crate::util::reference_processor::ReferenceProcessors;
 #[cfg(feature = "sanity")]
 use crate::util::sanity::sanity_checker::SanityChecker;
+#[cfg(feature = "transitive_pinning")]
+use crate::util::transitive_pin::TransitivePinning;
 use crate::vm::ReferenceGlue;
 use crate::vm::VMBinding;
 use std::default::Default;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -37,6 +49,10 @@ lazy_static! {
     pub static ref MMAPPER: Mmapper = Mmapper::new();
 }
 
+/// The number of MMTk instances that have been created and not shut down. The VM map is reset when
+/// an instance shuts down, which is only correct if the instance is the only one.
+static LIVE_INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
 use crate::util::rust_util::InitializeOnce;
 
 // A global space function table that allows efficient dispatch space specific code for addresses in our heap.
@@ -61,15 +77,43 @@ impl MMTKBuilder {
         self.options.set_from_command_line(name, val)
     }
 
+    /// Set an option, and return an error if there is no such option, or the value is invalid.
+    pub fn try_set_option(&mut self, name: &str, val: &str) -> Result<(), MMTKError> {
+        self.options.try_set_from_command_line(name, val)
+    }
+
     /// Set multiple options by a string. The string should be key-value pairs separated by white spaces,
     /// such as `threads=1 stress_factor=4096`.
     pub fn set_options_bulk_by_str(&mut self, options: &str) -> bool {
         self.options.set_bulk_from_command_line(options)
     }
 
-    /// Build an MMTk instance from the builder.
-    pub fn build<VM: VMBinding>(&self) -> MMTK<VM> {
-        MMTK::new(Arc::new(self.options.clone()))
+    /// Set multiple options by a string like `set_options_bulk_by_str()`, and return the error of
+    /// the first option that cannot be set.
+    pub fn try_set_options_bulk_by_str(&mut self, options: &str) -> Result<(), MMTKError> {
+        self.options.try_set_bulk_from_command_line(options)
+    }
+
+    /// Get a typed builder to set options, e.g. `builder.options().threads(4).heap_size(1 << 30).done()`.
+    /// The options are validated when `done()` is called on the returned builder.
+    pub fn options(&mut self) -> OptionsBuilder<'_> {
+        OptionsBuilder::new(&mut self.options)
+    }
+
+    /// Build an MMTk instance from the builder, or return an error if the options are invalid, or
+    /// if the constants of the binding cannot work with MMTk (see `vm::checks`).
+    /// If any value from env vars or the command line could not be set (and has not been replaced
+    /// by a valid value), this returns `MMTKError::InvalidOptionValues` with the details of each
+    /// of them.
+    pub fn build<VM: VMBinding>(&self) -> Result<MMTK<VM>, MMTKError> {
+        let diagnostics = self.options.diagnostics();
+        if !diagnostics.is_empty() {
+            return Err(MMTKError::InvalidOptionValues(diagnostics));
+        }
+        self.options.validate().map_err(MMTKError::InvalidOptions)?;
+        crate::vm::checks::check_binding::<VM>(&self.options)
+            .map_err(MMTKError::IncompatibleBinding)?;
+        MMTK::try_new(Arc::new(self.options.clone()))
     }
 }
 
@@ -92,29 +136,54 @@ pub struct MMTK<VM: VMBinding> {
     pub(crate) sanity_checker: Mutex<SanityChecker<VM::VMEdge>>,
     #[cfg(feature = "extreme_assertions")]
     pub(crate) edge_logger: EdgeLogger<VM::VMEdge>,
+    #[cfg(feature = "transitive_pinning")]
+    pub(crate) transitive_pinning: TransitivePinning,
+    /// The references embedded in compiled code (see `memory_manager::register_code_root`).
+    pub(crate) code_roots: CodeRoots,
+    /// The groups of objects to co-locate (see `memory_manager::request_colocation`).
+    pub(crate) colocation: Colocation,
+    /// The object layouts registered by the binding (see `memory_manager::register_object_layout`).
+    pub(crate) object_layouts: ObjectLayouts,
+    /// The GC-critical regions of the mutators (see `memory_manager::enter_gc_critical_region`).
+    pub(crate) gc_critical_regions: GCCriticalRegions,
+    /// The epochs of the stops of the world (see `memory_manager::defer_until_safepoint`).
+    pub(crate) epochs: Epochs,
+    /// The layout of the side metadata of the spaces (see `memory_manager::side_metadata_layout`).
+    pub(crate) side_metadata_layout: SideMetadataLayout,
+    /// The thread that triggers GCs on memory pressure (see the option `memory_pressure_gc`).
+    pub(crate) memory_pressure_listener: Mutex<Option<Box<dyn PlatformThread>>>,
+    /// The thread that triggers GCs periodically (see the option `periodic_gc_ms`).
+    pub(crate) periodic_gc_trigger: Mutex<Option<Box<dyn PlatformThread>>>,
     inside_harness: AtomicBool,
+    is_shut_down: AtomicBool,
 }
 
 impl<VM: VMBinding> MMTK<VM> {
     pub fn new(options: Arc<Options>) -> Self {
+        Self::try_new(options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create an MMTk instance, or return an error if the side metadata of the spaces conflict.
+    pub(crate) fn try_new(options: Arc<Options>) -> Result<Self, MMTKError> {
         // Initialize SFT first in case we need to use this in the constructor.
         // The first call will initialize SFT map. Other calls will be blocked until SFT map is initialized.
         SFT_MAP.initialize_once(&SFTMap::new);
 
-        let num_workers = if cfg!(feature = "single_worker") {
+        // Without GC threads, the only worker runs on the mutator thread that triggers a GC.
+        let num_workers = if cfg!(feature = "single_worker") || options.is_single_threaded() {
             1
         } else {
             *options.threads
         };
 
-        let scheduler = GCWorkScheduler::new(num_workers);
-        let plan = crate::plan::create_plan(
+        let scheduler = GCWorkScheduler::new(num_workers, options.is_single_threaded());
+        let (plan, side_metadata_layout) = crate::plan::create_plan(
             *options.plan,
             &VM_MAP,
             &MMAPPER,
             options.clone(),
             scheduler.clone(),
-        );
+        )?;
 
         // TODO: This probably does not work if we have multiple MMTk instances.
         VM_MAP.boot();
@@ -124,7 +193,10 @@ impl<VM: VMBinding> MMTK<VM> {
             plan.base().heap.get_discontig_end(),
         );
 
-        MMTK {
+        let gc_critical_regions = GCCriticalRegions::new(*options.gc_critical_region_timeout);
+
+        LIVE_INSTANCES.fetch_add(1, Ordering::SeqCst);
+        Ok(MMTK {
             options,
             plan,
             reference_processors: ReferenceProcessors::new(),
@@ -137,7 +209,69 @@ impl<VM: VMBinding> MMTK<VM> {
             inside_harness: AtomicBool::new(false),
             #[cfg(feature = "extreme_assertions")]
             edge_logger: EdgeLogger::new(),
+            #[cfg(feature = "transitive_pinning")]
+            transitive_pinning: TransitivePinning::new(),
+            code_roots: CodeRoots::new(),
+            colocation: Colocation::new(),
+            object_layouts: ObjectLayouts::new(
+                if <VM as VMBinding>::VMScanning::LAYOUT_TYPE_ID_OFFSET.is_some() {
+                    *options.max_object_layouts
+                } else {
+                    0
+                },
+            ),
+            gc_critical_regions,
+            epochs: Epochs::new(),
+            side_metadata_layout,
+            memory_pressure_listener: Mutex::new(None),
+            periodic_gc_trigger: Mutex::new(None),
+            is_shut_down: AtomicBool::new(false),
+        })
+    }
+
+    /// Shut down this MMTk instance, and release its resources, so the VM can create another MMTk
+    /// instance in the same process. This stops triggering GCs (see
+    /// `memory_manager::prepare_for_shutdown`), waits for the GC in progress (if any) to finish,
+    /// lets the GC threads exit (the calls to `memory_manager::start_control_collector` and
+    /// `memory_manager::start_worker` return), and then unmaps the heap and the side metadata.
+    ///
+    /// Before calling this, the VM must have destroyed all the mutators, and must not access any
+    /// object in the heap afterwards. The instance must not be used after this returns, except
+    /// to be dropped, which frees the rest of its memory (e.g. the page resources).
+    ///
+    /// This resets the global VM map, which also maps the chunks of any other MMTk instance, so
+    /// this instance must be the only one in the process.
+    pub fn shutdown(&self) {
+        assert!(
+            !self.is_shut_down.swap(true, Ordering::SeqCst),
+            "The MMTk instance has already been shut down"
+        );
+        let requester = &self.plan.base().gc_requester;
+        requester.shut_down();
+        if self.plan.is_initialized() {
+            self.scheduler.shut_down_gc_threads(requester);
+        }
+        if let Some(listener) = self.memory_pressure_listener.lock().unwrap().take() {
+            listener.join();
+        }
+        if let Some(trigger) = self.periodic_gc_trigger.lock().unwrap().take() {
+            trigger.join();
         }
+
+        let heap = &self.plan.base().heap;
+        let discontiguous_range = (heap.get_discontig_start(), heap.get_discontig_end() + 1);
+        for space in self.plan.get_spaces() {
+            space.release_address_space(discontiguous_range);
+        }
+        let others = LIVE_INSTANCES.fetch_sub(1, Ordering::SeqCst) - 1;
+        debug_assert_eq!(
+            others, 0,
+            "Shutting down an MMTk instance while other instances exist"
+        );
+        // Safety: This is the only instance. Its GC threads have exited and its mutators are
+        // destroyed, so no other thread uses the map, and the spaces of this instance are not
+        // used after this returns.
+        unsafe { VM_MAP.reset() };
     }
 
     pub fn harness_begin(&self, tls: VMMutatorThread) {
@@ -146,10 +280,19 @@ impl<VM: VMBinding> MMTK<VM> {
         self.inside_harness.store(true, Ordering::SeqCst);
         self.plan.base().stats.start_all();
         self.scheduler.enable_stat();
+        // Each iteration of the harness is measured from scratch.
+        let now = crate::memory_manager::gc_stats(self);
+        let windows = &self.plan.base().stats_windows;
+        windows.reset(HARNESS_STATS_WINDOW, now);
+        windows.start(HARNESS_STATS_WINDOW, now);
     }
 
     pub fn harness_end(&'static self) {
+        let windows = &self.plan.base().stats_windows;
+        windows.stop(HARNESS_STATS_WINDOW, crate::memory_manager::gc_stats(self));
         self.plan.base().stats.stop_all(self);
+        #[cfg(feature = "analysis")]
+        self.plan.base().analysis_manager.harness_end_hook(self);
         self.inside_harness.store(false, Ordering::SeqCst);
     }
 
@@ -161,4 +304,29 @@ impl<VM: VMBinding> MMTK<VM> {
     pub fn get_options(&self) -> &Options {
         &self.options
     }
+
+    /// Get the name of the plan in use, e.g. `GenImmix`.
+    pub fn plan_name(&self) -> String {
+        format!("{:?}", *self.options.plan)
+    }
+
+    /// Get descriptions of all the spaces in the plan, including their policies,
+    /// bounds and current usage.
+    pub fn spaces(&self) -> Vec<SpaceInfo> {
+        self.plan
+            .get_spaces()
+            .iter()
+            .map(|space| space.get_space_info())
+            .collect()
+    }
+
+    /// Change a live option (an option that can be changed after the MMTk instance is created, such as
+    /// `stress_factor` or `ignore_system_gc`). Returns an error if there is no such option, the
+    /// option is not live, or the value is invalid. The new value will be used the next time MMTk
+    /// reads the option, e.g. at the next allocation slow path or the next GC.
+    pub fn tune_option(&self, name: &str, value: &str) -> Result<(), MMTKError> {
+        // The live options are atomics (`LiveValue`), so they can be changed while other threads
+        // read the options.
+        self.options.set_live(name, value)
+    }
 }
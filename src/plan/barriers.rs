@@ -2,11 +2,17 @@
 
 use atomic::Ordering;
 
+use crate::policy::largeobjectspace::LargeObjectSpace;
+use crate::policy::los_ref_count::{ApplyLOSRefCounts, RefCountBuffer};
+use crate::policy::space::Space;
 use crate::scheduler::gc_work::*;
 use crate::scheduler::WorkBucketStage;
+use crate::util::heap::regions::{Chunk, Region};
 use crate::util::metadata::load_metadata;
 use crate::util::metadata::{compare_exchange_metadata, MetadataSpec};
+use crate::util::rememberset::{RememberedSet, BYTES_IN_CARD};
 use crate::util::*;
+use crate::vm::VMBinding;
 use crate::MMTK;
 
 /// BarrierSelector describes which barrier to use.
@@ -29,10 +35,35 @@ pub enum BarrierWriteTarget {
     Slot(Address),
 }
 
+/// Which path a write barrier took. This is reported to the barrier profiler.
+#[cfg(feature = "analysis")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BarrierOutcome {
+    /// The barrier returned in its fast path.
+    FastPath,
+    /// The barrier went to its slow path, and logged the object.
+    SlowPath,
+    /// The barrier went to its slow path, but the object has been logged by someone else.
+    RedundantLog,
+}
+
 pub trait Barrier: 'static + Send {
     fn flush(&mut self);
     fn post_write_barrier(&mut self, target: BarrierWriteTarget);
     fn post_write_barrier_slow(&mut self, target: BarrierWriteTarget);
+    /// Record that a reference field is updated from `old` to `new` (either may be null), for the
+    /// reference counts of large objects (see the option `los_ref_counting`).
+    fn reference_update(&mut self, _old: ObjectReference, _new: ObjectReference) {}
+    /// Record that a reference to `target` (which may be null) is stored into a field of `src`, for
+    /// the remembered references of the partial-heap GCs (see the options `remember_los_references`
+    /// and `remember_heap_references`).
+    fn reference_write(&mut self, _src: ObjectReference, _target: ObjectReference) {}
+    /// The same as `post_write_barrier()`, but also returns which path the barrier took.
+    #[cfg(feature = "analysis")]
+    fn post_write_barrier_profiled(&mut self, target: BarrierWriteTarget) -> BarrierOutcome {
+        self.post_write_barrier(target);
+        BarrierOutcome::FastPath
+    }
 }
 
 pub struct NoBarrier;
@@ -43,21 +74,128 @@ impl Barrier for NoBarrier {
     fn post_write_barrier_slow(&mut self, _target: BarrierWriteTarget) {}
 }
 
+/// A barrier that remembers references in a remembered set of chunks, so the plan can collect
+/// part of the heap without tracing the rest. It only records the stores reported with
+/// `reference_write()`, and it remembers:
+/// * the references from the other spaces into the LOS (see the option
+///   `remember_los_references`), and
+/// * the references into the objects of a heap in the collected spaces from outside the heap
+///   (see the option `remember_heap_references`).
+pub struct RememberedSetBarrier<VM: VMBinding> {
+    remset: &'static RememberedSet<Chunk>,
+    /// Remember the references from the other spaces into this LOS.
+    los: Option<&'static LargeObjectSpace<VM>>,
+    /// Remember the references into the objects of a heap in these spaces, except the references
+    /// from the objects of the same heap in the spaces. The objects of the shared heap may be
+    /// referred to by any heap, so the references into the shared heap are not remembered.
+    #[cfg(feature = "heap_ids")]
+    heap_spaces: Vec<&'static dyn Space<VM>>,
+    /// The card of the source and the chunk of the target of the last recorded reference, so a run
+    /// of stores into the same object does not take the lock of the remembered set each time.
+    last: Option<(Address, Address)>,
+}
+
+impl<VM: VMBinding> RememberedSetBarrier<VM> {
+    pub fn new(remset: &'static RememberedSet<Chunk>) -> Self {
+        Self {
+            remset,
+            los: None,
+            #[cfg(feature = "heap_ids")]
+            heap_spaces: vec![],
+            last: None,
+        }
+    }
+
+    /// Remember the references into the LOS.
+    pub fn remember_los(mut self, los: &'static LargeObjectSpace<VM>) -> Self {
+        self.los = Some(los);
+        self
+    }
+
+    /// Remember the references into the objects of each heap in the spaces.
+    #[cfg(feature = "heap_ids")]
+    pub fn remember_heaps(mut self, spaces: Vec<&'static dyn Space<VM>>) -> Self {
+        self.heap_spaces = spaces;
+        self
+    }
+
+    #[inline(always)]
+    fn is_los_reference(&self, src: ObjectReference, target: ObjectReference) -> bool {
+        self.los
+            .map_or(false, |los| los.in_space(target) && !los.in_space(src))
+    }
+
+    #[cfg(feature = "heap_ids")]
+    #[inline(always)]
+    fn is_cross_heap_reference(&self, src: ObjectReference, target: ObjectReference) -> bool {
+        use crate::util::heap_id::{get_heap_id, SHARED_HEAP};
+        let in_spaces = |object| self.heap_spaces.iter().any(|space| space.in_space(object));
+        if !in_spaces(target) {
+            return false;
+        }
+        let heap_id = get_heap_id(target);
+        heap_id != SHARED_HEAP && (!in_spaces(src) || get_heap_id(src) != heap_id)
+    }
+
+    #[inline(never)]
+    fn reference_write_slow(&mut self, src: ObjectReference, target: ObjectReference) {
+        let key = (
+            src.to_address().align_down(BYTES_IN_CARD),
+            Chunk::align(target.to_address()),
+        );
+        if self.last != Some(key) {
+            // The objects of a heap may share a chunk with the objects of the other heaps.
+            self.remset.record_any(src, target);
+            self.last = Some(key);
+        }
+    }
+}
+
+impl<VM: VMBinding> Barrier for RememberedSetBarrier<VM> {
+    fn flush(&mut self) {}
+
+    fn post_write_barrier(&mut self, _target: BarrierWriteTarget) {}
+
+    fn post_write_barrier_slow(&mut self, _target: BarrierWriteTarget) {}
+
+    #[inline(always)]
+    fn reference_write(&mut self, src: ObjectReference, target: ObjectReference) {
+        if target.is_null() {
+            return;
+        }
+        let remember = self.is_los_reference(src, target);
+        #[cfg(feature = "heap_ids")]
+        let remember = remember || self.is_cross_heap_reference(src, target);
+        if remember {
+            self.reference_write_slow(src, target);
+        }
+    }
+}
+
 pub struct ObjectRememberingBarrier<E: ProcessEdgesWork> {
     mmtk: &'static MMTK<E::VM>,
     modbuf: Vec<ObjectReference>,
     /// The metadata used for log bit. Though this allows taking an arbitrary metadata spec,
     /// for this field, 0 means logged, and 1 means unlogged (the same as the vm::object_model::VMGlobalLogBitSpec).
     meta: MetadataSpec,
+    /// The buffered updates of the reference counts of large objects, if the LOS counts them.
+    ref_counts: Option<RefCountBuffer>,
 }
 
 impl<E: ProcessEdgesWork> ObjectRememberingBarrier<E> {
     #[allow(unused)]
     pub fn new(mmtk: &'static MMTK<E::VM>, meta: MetadataSpec) -> Self {
+        let ref_counts = mmtk
+            .plan
+            .common()
+            .get_los()
+            .ref_counts()
+            .map(|_| RefCountBuffer::default());
         Self {
             mmtk,
             modbuf: vec![],
             meta,
+            ref_counts,
         }
     }
 
@@ -91,14 +229,18 @@ impl<E: ProcessEdgesWork> ObjectRememberingBarrier<E> {
         }
     }
 
+    /// Returns true if we logged the object.
     #[inline(always)]
-    fn enqueue_node(&mut self, obj: ObjectReference) {
+    fn enqueue_node(&mut self, obj: ObjectReference) -> bool {
         // If the objecct is unlogged, log it and push it to mod buffer
         if self.log_object(obj) {
             self.modbuf.push(obj);
             if self.modbuf.len() >= E::CAPACITY {
                 self.flush();
             }
+            true
+        } else {
+            false
         }
     }
 
@@ -110,15 +252,56 @@ impl<E: ProcessEdgesWork> ObjectRememberingBarrier<E> {
         self.barrier_slow(obj);
     }
 
+    #[cfg(feature = "analysis")]
+    fn barrier_profiled(&mut self, obj: ObjectReference) -> BarrierOutcome {
+        if load_metadata::<E::VM>(&self.meta, obj, None, None) == 0 {
+            BarrierOutcome::FastPath
+        } else if self.barrier_slow(obj) {
+            BarrierOutcome::SlowPath
+        } else {
+            BarrierOutcome::RedundantLog
+        }
+    }
+
     #[inline(never)]
-    fn barrier_slow(&mut self, obj: ObjectReference) {
-        self.enqueue_node(obj);
+    fn barrier_slow(&mut self, obj: ObjectReference) -> bool {
+        self.enqueue_node(obj)
+    }
+
+    #[inline(never)]
+    fn reference_update_slow(&mut self, old: ObjectReference, new: ObjectReference) {
+        let los = self.mmtk.plan.common().get_los();
+        let buffer = self.ref_counts.as_mut().unwrap();
+        if !new.is_null() && los.in_space(new) {
+            buffer.incs.push(new);
+        }
+        if !old.is_null() && los.in_space(old) {
+            buffer.decs.push(old);
+        }
+        if buffer.len() >= E::CAPACITY {
+            self.flush_ref_counts();
+        }
+    }
+
+    /// Hand the buffered reference count updates to the LOS, and apply them in the background.
+    fn flush_ref_counts(&mut self) {
+        if let Some(buffer) = self.ref_counts.as_mut() {
+            if !buffer.is_empty() {
+                let buffer = std::mem::take(buffer);
+                let los = self.mmtk.plan.common().get_los();
+                los.ref_counts().unwrap().push(buffer);
+                self.mmtk
+                    .scheduler
+                    .add_background_work(Box::new(ApplyLOSRefCounts));
+            }
+        }
     }
 }
 
 impl<E: ProcessEdgesWork> Barrier for ObjectRememberingBarrier<E> {
     #[cold]
     fn flush(&mut self) {
+        self.flush_ref_counts();
         let mut modbuf = vec![];
         std::mem::swap(&mut modbuf, &mut self.modbuf);
         debug_assert!(
@@ -149,4 +332,19 @@ impl<E: ProcessEdgesWork> Barrier for ObjectRememberingBarrier<E> {
             _ => unreachable!(),
         }
     }
+
+    #[inline(always)]
+    fn reference_update(&mut self, old: ObjectReference, new: ObjectReference) {
+        if self.ref_counts.is_some() {
+            self.reference_update_slow(old, new);
+        }
+    }
+
+    #[cfg(feature = "analysis")]
+    fn post_write_barrier_profiled(&mut self, target: BarrierWriteTarget) -> BarrierOutcome {
+        match target {
+            BarrierWriteTarget::Object(obj) => self.barrier_profiled(obj),
+            _ => unreachable!(),
+        }
+    }
 }
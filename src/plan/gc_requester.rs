@@ -1,11 +1,81 @@
+use crate::scheduler::GCController;
+use crate::util::{VMMutatorThread, VMWorkerThread};
 use crate::vm::VMBinding;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Condvar, Mutex};
+use std::sync::{Condvar, Mutex, TryLockError};
 
 struct RequestSync {
     request_count: isize,
     last_request_count: isize,
+    /// Should the GC controller exit? This is set when the MMTk instance shuts down.
+    exit: bool,
+    /// The request that the current GC (or the last GC) serves.
+    serving_request: isize,
+    /// The last request whose GC is finished or cancelled.
+    finished_request: isize,
+    /// The last request whose GC is cancelled.
+    cancelled_request: isize,
+    /// Was the last finished GC a full-heap GC?
+    finished_full_heap: bool,
+    /// The mutators that are blocked for the pending request (see `block_mutator_for_request()`).
+    requesting_mutators: Vec<VMMutatorThread>,
+    /// The number of threads that wait for a GC in `wait_for_gc()`.
+    waiting_threads: usize,
+}
+
+impl RequestSync {
+    /// Is there a request that the GC controller has not taken?
+    fn has_pending_request(&self) -> bool {
+        self.serving_request < self.request_count && self.finished_request < self.request_count
+    }
+}
+
+/// What the GC controller should do after it waits for a request.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GCRequestResult {
+    /// A GC is requested. The controller should start the GC.
+    Collect,
+    /// A GC was requested, but it is cancelled because the VM is shutting down.
+    Cancelled,
+    /// The MMTk instance is shutting down. The controller should exit.
+    Exit,
+}
+
+/// The kind of GC that a thread requests with
+/// [`memory_manager::request_gc_blocking`](crate::memory_manager::request_gc_blocking).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GCKind {
+    /// The plan decides what to collect, as for a GC triggered by MMTk (e.g. a nursery GC for the
+    /// generational plans).
+    Default,
+    /// A full-heap GC, as for a GC requested by the user with the option `full_heap_system_gc`,
+    /// but regardless of the option.
+    FullHeap,
+}
+
+/// Why a GC requested with
+/// [`memory_manager::request_gc_blocking`](crate::memory_manager::request_gc_blocking) was not
+/// done.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BlockingGCError {
+    /// The collection is not initialized (see `memory_manager::initialize_collection()`).
+    NotInitialized,
+    /// There are no GC threads (see `Options::is_single_threaded()`), so the GCs are only done on
+    /// the mutator threads.
+    NoGCThreads,
+    /// The VM is shutting down, so the request is refused or cancelled.
+    ShuttingDown,
+}
+
+impl std::fmt::Display for BlockingGCError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlockingGCError::NotInitialized => write!(f, "The collection is not initialized"),
+            BlockingGCError::NoGCThreads => write!(f, "There are no GC threads"),
+            BlockingGCError::ShuttingDown => write!(f, "The VM is shutting down"),
+        }
+    }
 }
 
 /// GC requester.  This object allows other threads to request (trigger) GC,
@@ -13,7 +83,19 @@ struct RequestSync {
 pub struct GCRequester<VM: VMBinding> {
     request_sync: Mutex<RequestSync>,
     request_condvar: Condvar,
+    /// Notified when a GC is finished or cancelled.
+    finished_condvar: Condvar,
     request_flag: AtomicBool,
+    /// Is the VM shutting down? If so, GC requests are refused, and a request that has not been
+    /// taken by the GC controller is cancelled.
+    shutting_down: AtomicBool,
+    /// The GC controller, if there are no GC threads (see `Options::is_single_threaded()`). The
+    /// requested GCs are done by the controller on the mutator thread that blocks for them (see
+    /// `collect_on_current_thread()`).
+    controller: Mutex<Option<Box<GCController<VM>>>>,
+    /// Is the controller set? This is checked without taking the controller lock, which is held
+    /// for the whole GC.
+    collects_on_mutators: AtomicBool,
     phantom: PhantomData<VM>,
 }
 
@@ -30,24 +112,128 @@ impl<VM: VMBinding> GCRequester<VM> {
             request_sync: Mutex::new(RequestSync {
                 request_count: 0,
                 last_request_count: -1,
+                exit: false,
+                serving_request: 0,
+                finished_request: 0,
+                cancelled_request: 0,
+                finished_full_heap: false,
+                requesting_mutators: vec![],
+                waiting_threads: 0,
             }),
             request_condvar: Condvar::new(),
+            finished_condvar: Condvar::new(),
             request_flag: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            controller: Mutex::new(None),
+            collects_on_mutators: AtomicBool::new(false),
             phantom: PhantomData,
         }
     }
 
-    pub fn request(&self) {
+    /// Request a GC. Return false if the request is refused because the VM is shutting down, in
+    /// which case the caller should not block for a GC.
+    pub fn request(&self) -> bool {
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return false;
+        }
         if self.request_flag.load(Ordering::Relaxed) {
-            return;
+            return true;
         }
+        self.request_with_id().is_some()
+    }
 
+    /// Request a GC like `request()`, and return the ID of the request, which can be passed to
+    /// `wait_for_gc()`. A request made before the current GC has stopped the mutators is served by
+    /// that GC.
+    pub fn request_with_id(&self) -> Option<isize> {
         let mut guard = self.request_sync.lock().unwrap();
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return None;
+        }
         if !self.request_flag.load(Ordering::Relaxed) {
             self.request_flag.store(true, Ordering::Relaxed);
             guard.request_count += 1;
             self.request_condvar.notify_all();
         }
+        Some(guard.request_count)
+    }
+
+    /// Wait until the GC that serves the request is finished, and return whether the last finished
+    /// GC was a full-heap GC. Without GC threads, the GC is only done when a mutator blocks for it,
+    /// so this refuses to wait rather than block forever, and the request is served by the next
+    /// mutator that polls for a GC.
+    pub fn wait_for_gc(&self, request: isize) -> Result<bool, BlockingGCError> {
+        if self.collects_on_mutators.load(Ordering::Relaxed) {
+            return Err(BlockingGCError::NoGCThreads);
+        }
+        let mut guard = self.request_sync.lock().unwrap();
+        guard.waiting_threads += 1;
+        while guard.finished_request < request {
+            guard = self.finished_condvar.wait(guard).unwrap();
+        }
+        guard.waiting_threads -= 1;
+        if guard.cancelled_request < request {
+            Ok(guard.finished_full_heap)
+        } else {
+            Err(BlockingGCError::ShuttingDown)
+        }
+    }
+
+    /// Record that the mutator is about to block for the pending request, if there is one.
+    pub fn block_mutator_for_request(&self, tls: VMMutatorThread) {
+        let mut guard = self.request_sync.lock().unwrap();
+        if guard.has_pending_request() && !guard.requesting_mutators.contains(&tls) {
+            guard.requesting_mutators.push(tls);
+        }
+    }
+
+    /// The mutator is destroyed, e.g. because its thread exits. If it blocked for the pending
+    /// request, and all the other mutators that blocked for the request have been destroyed too,
+    /// the request is withdrawn before the GC controller takes it, as no thread waits for that
+    /// GC any more. The mutators poll for a GC again when they need one.
+    pub fn on_mutator_exit(&self, tls: VMMutatorThread) {
+        let mut guard = self.request_sync.lock().unwrap();
+        let index = match guard.requesting_mutators.iter().position(|m| *m == tls) {
+            Some(index) => index,
+            None => return,
+        };
+        guard.requesting_mutators.swap_remove(index);
+        if guard.requesting_mutators.is_empty()
+            && guard.waiting_threads == 0
+            && guard.has_pending_request()
+        {
+            // The controller waits until the request count changes again.
+            guard.request_count -= 1;
+            self.request_flag.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// The current GC is finished. This releases the threads that wait for it in `wait_for_gc()`.
+    pub fn finish_gc(&self, full_heap: bool) {
+        let mut guard = self.request_sync.lock().unwrap();
+        guard.finished_request = guard.serving_request;
+        guard.finished_full_heap = full_heap;
+        self.finished_condvar.notify_all();
+    }
+
+    /// Refuse any further GC requests, as the VM is shutting down. A request that the GC
+    /// controller has not taken is cancelled when the controller takes it.
+    pub fn shut_down(&self) {
+        let _guard = self.request_sync.lock().unwrap();
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Let the GC controller exit once it has finished the current GC, if any. This also refuses
+    /// any further GC requests.
+    pub fn exit(&self) {
+        let mut guard = self.request_sync.lock().unwrap();
+        self.shutting_down.store(true, Ordering::Relaxed);
+        guard.exit = true;
+        self.request_condvar.notify_all();
     }
 
     pub fn clear_request(&self) {
@@ -56,11 +242,72 @@ impl<VM: VMBinding> GCRequester<VM> {
         drop(guard);
     }
 
-    pub fn wait_for_request(&self) {
+    /// Wait for a GC request. The GC controller must not start the GC unless this returns
+    /// `GCRequestResult::Collect`.
+    pub fn wait_for_request(&self) -> GCRequestResult {
         let mut guard = self.request_sync.lock().unwrap();
         guard.last_request_count += 1;
-        while guard.last_request_count == guard.request_count {
+        while guard.last_request_count == guard.request_count && !guard.exit {
             guard = self.request_condvar.wait(guard).unwrap();
         }
+        self.take_request(&mut guard)
+    }
+
+    /// Take the GC request that the controller has waited for.
+    fn take_request(&self, guard: &mut RequestSync) -> GCRequestResult {
+        // This is checked while holding the lock, so either the GC starts before `shut_down()`
+        // returns, or the GC is cancelled.
+        if self.shutting_down.load(Ordering::Relaxed) {
+            if guard.last_request_count != guard.request_count {
+                // Cancel the pending request first, so the mutators blocked for it are released
+                // before the controller exits.
+                self.request_flag.store(false, Ordering::Relaxed);
+                guard.finished_request = guard.request_count;
+                guard.cancelled_request = guard.request_count;
+                guard.requesting_mutators.clear();
+                self.finished_condvar.notify_all();
+                return GCRequestResult::Cancelled;
+            }
+            debug_assert!(guard.exit);
+            return GCRequestResult::Exit;
+        }
+        guard.serving_request = guard.request_count;
+        guard.requesting_mutators.clear();
+        GCRequestResult::Collect
+    }
+
+    /// Take a GC request without waiting. Return `None` if no GC is requested.
+    pub fn poll_request(&self) -> Option<GCRequestResult> {
+        let mut guard = self.request_sync.lock().unwrap();
+        if guard.last_request_count + 1 == guard.request_count || guard.exit {
+            guard.last_request_count += 1;
+            Some(self.take_request(&mut guard))
+        } else {
+            None
+        }
+    }
+
+    /// Keep the GC controller to do the requested GCs on the mutator threads.
+    pub(crate) fn set_controller(&self, controller: Box<GCController<VM>>) {
+        let mut guard = self.controller.lock().unwrap();
+        debug_assert!(guard.is_none(), "The GC controller is already set");
+        *guard = Some(controller);
+        self.collects_on_mutators.store(true, Ordering::Relaxed);
+    }
+
+    /// Do the requested GC, if any, on the current thread. This is called where a mutator would
+    /// block for the GC if there were GC threads. Return false without waiting if another thread
+    /// is doing a GC, as that GC stops this thread, which must not block on the controller lock.
+    pub(crate) fn try_collect_on_current_thread(&self, tls: VMWorkerThread) -> bool {
+        let mut guard = match self.controller.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => return false,
+            Err(TryLockError::Poisoned(e)) => panic!("{}", e),
+        };
+        let controller = guard.as_mut().expect(
+            "GC is not allowed here: collection is not initialized (did you call initialize_collection()?).",
+        );
+        controller.run_requested_gc(tls);
+        true
     }
 }
@@ -1,6 +1,12 @@
 use super::global::GenCopy;
 use crate::plan::generational::gc_work::GenNurseryProcessEdges;
+use crate::scheduler::gc_work::*;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::ObjectReference;
+use crate::vm::edge_shape::Edge;
 use crate::vm::*;
+use crate::MMTK;
+use std::ops::{Deref, DerefMut};
 
 use crate::policy::gc_work::DEFAULT_TRACE;
 use crate::scheduler::gc_work::PlanProcessEdges;
@@ -12,9 +18,181 @@ impl<VM: VMBinding> crate::scheduler::GCWorkContext for GenCopyNurseryGCWorkCont
     type ProcessEdgesWorkType = GenNurseryProcessEdges<Self::VM>;
 }
 
+/// The work context for nursery GCs when GenCopy uses survivor spaces.
+pub struct GenCopySurvivorGCWorkContext<VM: VMBinding>(std::marker::PhantomData<VM>);
+impl<VM: VMBinding> crate::scheduler::GCWorkContext for GenCopySurvivorGCWorkContext<VM> {
+    type VM = VM;
+    type PlanType = GenCopy<VM>;
+    type ProcessEdgesWorkType = GenCopySurvivorProcessEdges<Self::VM>;
+}
+
 pub struct GenCopyGCWorkContext<VM: VMBinding>(std::marker::PhantomData<VM>);
 impl<VM: VMBinding> crate::scheduler::GCWorkContext for GenCopyGCWorkContext<VM> {
     type VM = VM;
     type PlanType = GenCopy<VM>;
     type ProcessEdgesWorkType = PlanProcessEdges<Self::VM, GenCopy<VM>, DEFAULT_TRACE>;
 }
+
+/// Process edges for a nursery GC when GenCopy uses survivor spaces. Young objects are copied to
+/// the survivor space until they are old enough to be promoted to the mature space.
+pub struct GenCopySurvivorProcessEdges<VM: VMBinding> {
+    plan: &'static GenCopy<VM>,
+    base: ProcessEdgesBase<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for GenCopySurvivorProcessEdges<VM> {
+    type VM = VM;
+    type ScanObjectsWorkType = GenCopySurvivorScanObjects<VM>;
+
+    fn new(edges: Vec<EdgeOf<Self>>, roots: bool, mmtk: &'static MMTK<VM>) -> Self {
+        let base = ProcessEdgesBase::new(edges, roots, mmtk);
+        let plan = base.plan().downcast_ref::<GenCopy<VM>>().unwrap();
+        Self { plan, base }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        // We cannot borrow `self` twice in a call, so we extract `worker` as a local variable.
+        let worker = self.worker();
+        self.plan
+            .trace_object_nursery_with_survivors(&mut self.base.nodes, object, worker)
+    }
+    #[inline]
+    fn process_edge(&mut self, slot: EdgeOf<Self>) {
+        let object = slot.load();
+        let new_object = self.trace_object(object);
+        debug_assert!(!self.plan.gen.nursery.in_space(new_object));
+        slot.store(new_object);
+    }
+
+    #[inline(always)]
+    fn create_scan_work(
+        &self,
+        nodes: Vec<ObjectReference>,
+        roots: bool,
+    ) -> GenCopySurvivorScanObjects<VM> {
+        GenCopySurvivorScanObjects::new(self.plan, nodes, roots)
+    }
+}
+
+impl<VM: VMBinding> Deref for GenCopySurvivorProcessEdges<VM> {
+    type Target = ProcessEdgesBase<VM>;
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for GenCopySurvivorProcessEdges<VM> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Scan objects in a nursery GC when GenCopy uses survivor spaces. The mature objects scanned in
+/// a nursery GC (the promoted objects, and the objects in the mod buffers) may point to survivor
+/// objects after the GC, so we remember them, and scan them again in the next nursery GC.
+pub struct GenCopySurvivorScanObjects<VM: VMBinding> {
+    plan: &'static GenCopy<VM>,
+    buffer: Vec<ObjectReference>,
+    roots: bool,
+}
+
+impl<VM: VMBinding> GenCopySurvivorScanObjects<VM> {
+    pub fn new(plan: &'static GenCopy<VM>, buffer: Vec<ObjectReference>, roots: bool) -> Self {
+        Self {
+            plan,
+            buffer,
+            roots,
+        }
+    }
+}
+
+impl<VM: VMBinding> ScanObjectsWork<VM> for GenCopySurvivorScanObjects<VM> {
+    type E = GenCopySurvivorProcessEdges<VM>;
+
+    fn roots(&self) -> bool {
+        self.roots
+    }
+
+    #[inline(always)]
+    fn post_scan_object(&self, _object: ObjectReference) {
+        // Do nothing.
+    }
+
+    fn make_another(&self, buffer: Vec<ObjectReference>) -> Self {
+        Self::new(self.plan, buffer, false)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for GenCopySurvivorScanObjects<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        trace!("GenCopySurvivorScanObjects");
+        self.do_work_common(&self.buffer, worker, mmtk);
+        // Root objects are scanned in every GC. We do not need to remember them.
+        if !self.roots {
+            self.plan.remember(
+                self.buffer
+                    .iter()
+                    .copied()
+                    .filter(|object| !self.plan.is_young_object(*object)),
+            );
+        }
+        trace!("GenCopySurvivorScanObjects End");
+    }
+}
+
+/// Scan the mature objects remembered in the last nursery GC. An object is remembered again if it
+/// still points to a survivor object.
+pub struct GenCopyScanRemembered<VM: VMBinding> {
+    objects: Vec<ObjectReference>,
+}
+
+impl<VM: VMBinding> GenCopyScanRemembered<VM> {
+    pub fn new(objects: Vec<ObjectReference>) -> Self {
+        Self { objects }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for GenCopyScanRemembered<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let tls = worker.tls;
+        let plan = mmtk.plan.downcast_ref::<GenCopy<VM>>().unwrap();
+        let mut process_edges_work = GenCopySurvivorProcessEdges::<VM>::new(vec![], false, mmtk);
+        process_edges_work.set_worker(worker);
+
+        let mut remembered = vec![];
+        for object in self.objects.iter().copied() {
+            let mut points_to_survivor = false;
+            if VM::VMScanning::support_edge_enqueuing(tls, object) {
+                let mut edges = vec![];
+                VM::VMScanning::scan_object(tls, object, &mut |edge: VM::VMEdge| edges.push(edge));
+                for edge in edges {
+                    let new_object = process_edges_work.trace_object(edge.load());
+                    edge.store(new_object);
+                    points_to_survivor |= plan.is_young_object(new_object);
+                }
+            } else {
+                VM::VMScanning::scan_object_and_trace_edges(
+                    tls,
+                    object,
+                    &mut |target: ObjectReference| {
+                        let new_object = process_edges_work.trace_object(target);
+                        points_to_survivor |= plan.is_young_object(new_object);
+                        new_object
+                    },
+                );
+            }
+            if points_to_survivor {
+                remembered.push(object);
+            }
+        }
+        plan.remember(remembered.into_iter());
+
+        // Scan the objects we copied.
+        if !process_edges_work.nodes.is_empty() {
+            process_edges_work.flush();
+        }
+    }
+}
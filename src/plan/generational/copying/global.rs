@@ -1,11 +1,16 @@
 use super::gc_work::GenCopyGCWorkContext;
 use super::gc_work::GenCopyNurseryGCWorkContext;
+use super::gc_work::GenCopyScanRemembered;
+use super::gc_work::GenCopySurvivorGCWorkContext;
 use super::mutator::ALLOCATOR_MAPPING;
 use crate::plan::generational::global::Gen;
+use crate::plan::generational::tenuring::{AdaptiveTenuring, ObjectGeneration, TenuringPolicy};
 use crate::plan::global::BasePlan;
 use crate::plan::global::CommonPlan;
 use crate::plan::global::GcStatus;
 use crate::plan::AllocationSemantics;
+use crate::plan::CollectionScope;
+use crate::plan::ObjectQueue;
 use crate::plan::Plan;
 use crate::plan::PlanConstraints;
 use crate::policy::copyspace::CopySpace;
@@ -17,13 +22,13 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::SideMetadataSanity;
 use crate::util::options::Options;
+use crate::util::ObjectReference;
 use crate::util::VMWorkerThread;
 use crate::vm::*;
 use enum_map::EnumMap;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use mmtk_macros::PlanTraceObject;
 
@@ -36,6 +41,17 @@ pub struct GenCopy<VM: VMBinding> {
     pub copyspace0: CopySpace<VM>,
     #[trace(CopySemantics::Mature)]
     pub copyspace1: CopySpace<VM>,
+    /// Which survivor space is the current one? The current survivor space holds the objects that survived
+    /// the last nursery GC, and it is the to-space for survivors in a nursery GC.
+    pub survivor_hi: AtomicBool,
+    /// The survivor spaces. They are only used if the option `survivor_age_threshold` is larger than 1.
+    /// A full heap GC promotes all the objects in the survivor spaces.
+    #[trace(CopySemantics::Mature)]
+    pub survivor0: CopySpace<VM>,
+    #[trace(CopySemantics::Mature)]
+    pub survivor1: CopySpace<VM>,
+    /// The mature objects that may point to objects in the survivor spaces. They are scanned in the next nursery GC.
+    remembered: Mutex<Vec<ObjectReference>>,
 }
 
 pub const GENCOPY_CONSTRAINTS: PlanConstraints = crate::plan::generational::GEN_CONSTRAINTS;
@@ -50,14 +66,18 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
     fn create_copy_config(&'static self) -> CopyConfig<Self::VM> {
         use enum_map::enum_map;
         CopyConfig {
+            // Mature copying and promotion use separate copy buffers in the tospace.
             copy_mapping: enum_map! {
                 CopySemantics::Mature => CopySelector::CopySpace(0),
-                CopySemantics::PromoteToMature => CopySelector::CopySpace(0),
+                CopySemantics::PromoteToMature => CopySelector::CopySpace(2),
+                CopySemantics::Nursery => CopySelector::CopySpace(1),
                 _ => CopySelector::Unused,
             },
             space_mapping: vec![
                 // The tospace argument doesn't matter, we will rebind before a GC anyway.
                 (CopySelector::CopySpace(0), self.tospace()),
+                (CopySelector::CopySpace(1), self.tosurvivor()),
+                (CopySelector::CopySpace(2), self.tospace()),
             ],
             constraints: &GENCOPY_CONSTRAINTS,
         }
@@ -74,15 +94,35 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         self.gen.force_full_heap_collection()
     }
 
+    fn supports_partial_collection(&self, scope: &CollectionScope) -> bool {
+        self.gen.supports_partial_collection(scope)
+    }
+
     fn last_collection_full_heap(&self) -> bool {
         self.gen.last_collection_full_heap()
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.gen.get_spaces();
-        ret.push(&self.copyspace0);
-        ret.push(&self.copyspace1);
-        ret
+    fn object_generation(&self, object: ObjectReference) -> Option<ObjectGeneration> {
+        for survivor in [&self.survivor0, &self.survivor1] {
+            if survivor.in_space(object) {
+                let age = Gen::<VM>::get_age(object) as usize;
+                return Some(ObjectGeneration::Young { age });
+            }
+        }
+        Some(self.gen.object_generation(object))
+    }
+
+    fn set_tenuring_policy(&self, policy: Box<dyn TenuringPolicy>) -> bool {
+        self.gen.set_tenuring_policy(policy);
+        true
+    }
+
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.gen.for_each_space(func);
+        func(&self.copyspace0);
+        func(&self.copyspace1);
+        func(&self.survivor0);
+        func(&self.survivor1);
     }
 
     fn schedule_collection(&'static self, scheduler: &GCWorkScheduler<VM>) {
@@ -91,8 +131,16 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         self.base().set_gc_status(GcStatus::GcPrepare);
         if is_full_heap {
             scheduler.schedule_common_work::<GenCopyGCWorkContext<VM>>(self);
+        } else if self.use_survivor_spaces() {
+            scheduler.schedule_common_work::<GenCopySurvivorGCWorkContext<VM>>(self);
+            let remembered = std::mem::take(&mut *self.remembered.lock().unwrap());
+            if !remembered.is_empty() {
+                scheduler.work_buckets[WorkBucketStage::Closure]
+                    .add(GenCopyScanRemembered::<VM>::new(remembered));
+            }
         } else {
             scheduler.schedule_common_work::<GenCopyNurseryGCWorkContext<VM>>(self);
+            self.gen.schedule_remembered_edges(scheduler);
         }
     }
 
@@ -114,10 +162,34 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         self.fromspace_mut()
             .set_copy_for_sft_trace(Some(CopySemantics::Mature));
         self.tospace_mut().set_copy_for_sft_trace(None);
+
+        if self.use_survivor_spaces() {
+            if full_heap {
+                // Promote all the survivors. The other survivor space is empty.
+                self.tosurvivor().prepare(true);
+                self.fromsurvivor().prepare(false);
+                self.tosurvivor_mut()
+                    .set_copy_for_sft_trace(Some(CopySemantics::Mature));
+            } else {
+                // Flip the survivor spaces. The survivors of the last nursery GC are in the from-survivor space now.
+                self.survivor_hi
+                    .store(!self.survivor_hi.load(Ordering::SeqCst), Ordering::SeqCst);
+                self.fromsurvivor().prepare(true);
+                self.tosurvivor().prepare(false);
+                // The SFT trace cannot tell the age of an object. Just promote it.
+                self.fromsurvivor_mut()
+                    .set_copy_for_sft_trace(Some(CopySemantics::PromoteToMature));
+                self.tosurvivor_mut().set_copy_for_sft_trace(None);
+                self.gen
+                    .add_young_pages(self.fromsurvivor().reserved_pages());
+            }
+        }
     }
 
     fn prepare_worker(&self, worker: &mut GCWorker<Self::VM>) {
-        unsafe { worker.get_copy_context_mut().copy[0].assume_init_mut() }.rebind(self.tospace());
+        let copy = worker.get_copy_context_mut();
+        copy.rebind_semispaces([&self.copyspace0, &self.copyspace1], self.tospace());
+        copy.rebind_semispaces([&self.survivor0, &self.survivor1], self.tosurvivor());
     }
 
     fn release(&mut self, tls: VMWorkerThread) {
@@ -126,6 +198,21 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         if full_heap {
             self.fromspace().release();
         }
+        if self.use_survivor_spaces() {
+            if full_heap {
+                self.tosurvivor().release();
+                // All the survivors are promoted, so no mature object points to survivors.
+                self.remembered.lock().unwrap().clear();
+            } else {
+                self.fromsurvivor().release();
+            }
+        }
+        // The from-spaces are not counted as used until the next GC.
+        self.fromspace().set_refill_zeroed_block_pool(false);
+        self.tospace().set_refill_zeroed_block_pool(true);
+        self.fromsurvivor().set_refill_zeroed_block_pool(false);
+        self.tosurvivor()
+            .set_refill_zeroed_block_pool(self.use_survivor_spaces());
 
         // TODO: Refactor so that we set the next_gc_full_heap in gen.release(). Currently have to fight with Rust borrow checker
         // NOTE: We have to take care that the `Gen::should_next_gc_be_full_heap()` function is
@@ -134,14 +221,33 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         // will change dependant on which spaces have been released
         self.gen
             .set_next_gc_full_heap(Gen::should_next_gc_be_full_heap(self));
+        self.gen.update_nursery_size(self);
+    }
+
+    fn end_of_gc(&self, _tls: VMWorkerThread) {
+        let survival = match self.gen.end_of_gc(self) {
+            Some(survival) => survival,
+            None => return,
+        };
+        if self.use_survivor_spaces() && self.base().options.nursery_feedback.get() {
+            // Aim to keep the survivors within half of the nursery size.
+            let target_pages = self.gen.nursery_pages.load(Ordering::Relaxed) / 2;
+            let survivor_pages = self.tosurvivor().reserved_pages();
+            self.gen
+                .update_tenuring(&survival, survivor_pages, target_pages);
+        }
     }
 
     fn get_collection_reserved_pages(&self) -> usize {
-        self.gen.get_collection_reserved_pages() + self.tospace().reserved_pages()
+        self.gen.get_collection_reserved_pages()
+            + self.tospace().reserved_pages()
+            + self.tosurvivor().reserved_pages()
     }
 
     fn get_used_pages(&self) -> usize {
-        self.gen.get_used_pages() + self.tospace().reserved_pages()
+        self.gen.get_used_pages()
+            + self.tospace().reserved_pages()
+            + self.tosurvivor().reserved_pages()
     }
 
     /// Return the number of pages available for allocation. Assuming all future allocations goes to nursery.
@@ -185,6 +291,7 @@ impl<VM: VMBinding> GenCopy<VM> {
             "copyspace0",
             false,
             true,
+            false,
             VMRequest::discontiguous(),
             global_metadata_specs.clone(),
             vm_map,
@@ -195,6 +302,29 @@ impl<VM: VMBinding> GenCopy<VM> {
             "copyspace1",
             true,
             true,
+            false,
+            VMRequest::discontiguous(),
+            global_metadata_specs.clone(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        let survivor0 = CopySpace::new(
+            "survivor0",
+            false,
+            true,
+            true,
+            VMRequest::discontiguous(),
+            global_metadata_specs.clone(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        let survivor1 = CopySpace::new(
+            "survivor1",
+            false,
+            true,
+            true,
             VMRequest::discontiguous(),
             global_metadata_specs.clone(),
             vm_map,
@@ -202,7 +332,10 @@ impl<VM: VMBinding> GenCopy<VM> {
             &mut heap,
         );
 
-        let res = GenCopy {
+        // The threshold starts at the option `survivor_age_threshold`, and is only adapted with
+        // `nursery_feedback`.
+        let tenuring = Box::new(AdaptiveTenuring::new(*options.survivor_age_threshold));
+        GenCopy {
             gen: Gen::new(
                 heap,
                 global_metadata_specs,
@@ -210,25 +343,16 @@ impl<VM: VMBinding> GenCopy<VM> {
                 vm_map,
                 mmapper,
                 options,
+                tenuring,
             ),
             hi: AtomicBool::new(false),
             copyspace0,
             copyspace1,
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        {
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            res.gen
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            res.copyspace0
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            res.copyspace1
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
+            survivor_hi: AtomicBool::new(false),
+            survivor0,
+            survivor1,
+            remembered: Mutex::new(vec![]),
         }
-
-        res
     }
 
     fn requires_full_heap_collection(&self) -> bool {
@@ -266,4 +390,82 @@ impl<VM: VMBinding> GenCopy<VM> {
             &mut self.copyspace1
         }
     }
+
+    /// Do we keep young objects in the survivor spaces before promoting them?
+    pub fn use_survivor_spaces(&self) -> bool {
+        *self.base().options.survivor_age_threshold > 1
+    }
+
+    /// The survivor space for the objects that survive the current nursery GC, which also holds the
+    /// survivors between GCs.
+    pub fn tosurvivor(&self) -> &CopySpace<VM> {
+        if self.survivor_hi.load(Ordering::SeqCst) {
+            &self.survivor1
+        } else {
+            &self.survivor0
+        }
+    }
+
+    pub fn tosurvivor_mut(&mut self) -> &mut CopySpace<VM> {
+        if self.survivor_hi.load(Ordering::SeqCst) {
+            &mut self.survivor1
+        } else {
+            &mut self.survivor0
+        }
+    }
+
+    /// The survivor space for the objects that survived the last nursery GC, during a nursery GC.
+    pub fn fromsurvivor(&self) -> &CopySpace<VM> {
+        if self.survivor_hi.load(Ordering::SeqCst) {
+            &self.survivor0
+        } else {
+            &self.survivor1
+        }
+    }
+
+    pub fn fromsurvivor_mut(&mut self) -> &mut CopySpace<VM> {
+        if self.survivor_hi.load(Ordering::SeqCst) {
+            &mut self.survivor0
+        } else {
+            &mut self.survivor1
+        }
+    }
+
+    /// Is the object in the nursery or in a survivor space?
+    pub fn is_young_object(&self, object: ObjectReference) -> bool {
+        self.gen.nursery.in_space(object)
+            || self.survivor0.in_space(object)
+            || self.survivor1.in_space(object)
+    }
+
+    /// Remember mature objects that may point to survivors, so they are scanned in the next nursery GC.
+    pub(super) fn remember(&self, objects: impl Iterator<Item = ObjectReference>) {
+        let mut remembered = self.remembered.lock().unwrap();
+        remembered.extend(objects);
+    }
+
+    /// The number of nursery GCs a young object has to survive before it is promoted.
+    pub fn tenuring_threshold(&self) -> usize {
+        self.gen.tenuring_threshold()
+    }
+
+    /// Trace an object in a nursery GC with survivor spaces. A young object is copied to the survivor
+    /// space, unless it has survived `tenuring_threshold()` nursery GCs, in which case it is promoted
+    /// to the mature space.
+    pub fn trace_object_nursery_with_survivors<Q: ObjectQueue>(
+        &self,
+        queue: &mut Q,
+        object: ObjectReference,
+        worker: &mut GCWorker<VM>,
+    ) -> ObjectReference {
+        let (space, age) = if self.gen.nursery.in_space(object) {
+            (&self.gen.nursery, 1)
+        } else if self.fromsurvivor().in_space(object) {
+            (self.fromsurvivor(), Gen::<VM>::get_age(object) + 1)
+        } else {
+            return self.gen.trace_object_nursery(queue, object, worker);
+        };
+        self.gen
+            .trace_young_object(space, queue, object, age, worker)
+    }
 }
@@ -1,16 +1,24 @@
-pub(super) use super::super::ALLOCATOR_MAPPING;
+use super::gc_work::GenCopySurvivorProcessEdges;
 use super::GenCopy;
 use crate::plan::barriers::*;
+use crate::plan::generational::create_gen_allocator_mapping;
 use crate::plan::generational::create_gen_space_mapping;
 use crate::plan::generational::gc_work::GenNurseryProcessEdges;
 use crate::plan::mutator_context::Mutator;
 use crate::plan::mutator_context::MutatorConfig;
 use crate::plan::AllocationSemantics;
-use crate::util::alloc::allocators::Allocators;
+use crate::plan::Plan;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
 use crate::util::alloc::BumpAllocator;
 use crate::util::{VMMutatorThread, VMWorkerThread};
 use crate::vm::{ObjectModel, VMBinding};
 use crate::MMTK;
+use enum_map::EnumMap;
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> =
+        create_gen_allocator_mapping(AllocatorSelector::BumpPointer(1));
+}
 
 pub fn gencopy_mutator_prepare<VM: VMBinding>(_mutator: &mut Mutator<VM>, _tls: VMWorkerThread) {
     // Do nothing
@@ -26,6 +34,18 @@ pub fn gencopy_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: V
     .downcast_mut::<BumpAllocator<VM>>()
     .unwrap();
     bump_allocator.reset();
+    // rebind the allocator for long-lived objects to the mature tospace after a full heap GC
+    let gencopy = mutator.plan.downcast_ref::<GenCopy<VM>>().unwrap();
+    if !gencopy.is_current_gc_nursery() {
+        let mature_allocator = unsafe {
+            mutator
+                .allocators
+                .get_allocator_mut(mutator.config.allocator_mapping[AllocationSemantics::LongLived])
+        }
+        .downcast_mut::<BumpAllocator<VM>>()
+        .unwrap();
+        mature_allocator.rebind(gencopy.tospace());
+    }
 }
 
 pub fn create_gencopy_mutator<VM: VMBinding>(
@@ -35,19 +55,37 @@ pub fn create_gencopy_mutator<VM: VMBinding>(
     let gencopy = mmtk.plan.downcast_ref::<GenCopy<VM>>().unwrap();
     let config = MutatorConfig {
         allocator_mapping: &*ALLOCATOR_MAPPING,
-        space_mapping: Box::new(create_gen_space_mapping(&*mmtk.plan, &gencopy.gen.nursery)),
+        space_mapping: Box::new(create_gen_space_mapping(
+            &*mmtk.plan,
+            &gencopy.gen.nursery,
+            (AllocatorSelector::BumpPointer(1), gencopy.tospace()),
+        )),
         prepare_func: &gencopy_mutator_prepare,
         release_func: &gencopy_mutator_release,
     };
 
     Mutator {
         allocators: Allocators::<VM>::new(mutator_tls, &*mmtk.plan, &config.space_mapping),
-        barrier: Box::new(ObjectRememberingBarrier::<GenNurseryProcessEdges<VM>>::new(
-            mmtk,
-            *VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC,
-        )),
+        // The mod buffers are processed with the same edge processing as nursery GCs.
+        barrier: if gencopy.use_survivor_spaces() {
+            Box::new(
+                ObjectRememberingBarrier::<GenCopySurvivorProcessEdges<VM>>::new(
+                    mmtk,
+                    *VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC,
+                ),
+            )
+        } else {
+            Box::new(ObjectRememberingBarrier::<GenNurseryProcessEdges<VM>>::new(
+                mmtk,
+                *VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC,
+            ))
+        },
         mutator_tls,
         config,
         plan: gencopy,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
@@ -1,6 +1,7 @@
 use crate::plan::generational::global::Gen;
 use crate::policy::space::Space;
 use crate::scheduler::gc_work::*;
+use crate::scheduler::{GCWork, GCWorker, WorkBucketStage};
 use crate::util::ObjectReference;
 use crate::vm::edge_shape::Edge;
 use crate::vm::*;
@@ -13,6 +14,11 @@ use std::ops::{Deref, DerefMut};
 pub struct GenNurseryProcessEdges<VM: VMBinding> {
     gen: &'static Gen<VM>,
     base: ProcessEdgesBase<VM>,
+    /// Is the current GC an incremental nursery GC?
+    incremental: bool,
+    /// The edges that point to young objects which are not collected by the current incremental
+    /// nursery GC. They are handed to `Gen` when the packet is dropped.
+    remembered: Vec<EdgeOf<Self>>,
 }
 
 impl<VM: VMBinding> ProcessEdgesWork for GenNurseryProcessEdges<VM> {
@@ -22,7 +28,12 @@ impl<VM: VMBinding> ProcessEdgesWork for GenNurseryProcessEdges<VM> {
     fn new(edges: Vec<EdgeOf<Self>>, roots: bool, mmtk: &'static MMTK<VM>) -> Self {
         let base = ProcessEdgesBase::new(edges, roots, mmtk);
         let gen = base.plan().generational();
-        Self { gen, base }
+        Self {
+            gen,
+            base,
+            incremental: gen.is_current_gc_incremental(),
+            remembered: vec![],
+        }
     }
     #[inline]
     fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
@@ -38,7 +49,14 @@ impl<VM: VMBinding> ProcessEdgesWork for GenNurseryProcessEdges<VM> {
     fn process_edge(&mut self, slot: EdgeOf<Self>) {
         let object = slot.load();
         let new_object = self.trace_object(object);
-        debug_assert!(!self.gen.nursery.in_space(new_object));
+        if self.gen.nursery.in_space(new_object) {
+            // Only an incremental nursery GC leaves young objects in the nursery. The roots are
+            // scanned again in the next GC, but the other edges need to be remembered.
+            debug_assert!(self.incremental && !self.gen.nursery.in_from_space(new_object));
+            if !self.roots {
+                self.remembered.push(slot);
+            }
+        }
         slot.store(new_object);
     }
 
@@ -48,6 +66,15 @@ impl<VM: VMBinding> ProcessEdgesWork for GenNurseryProcessEdges<VM> {
     }
 }
 
+impl<VM: VMBinding> Drop for GenNurseryProcessEdges<VM> {
+    fn drop(&mut self) {
+        if !self.remembered.is_empty() {
+            self.gen
+                .remember_edges(std::mem::take(&mut self.remembered));
+        }
+    }
+}
+
 impl<VM: VMBinding> Deref for GenNurseryProcessEdges<VM> {
     type Target = ProcessEdgesBase<VM>;
     fn deref(&self) -> &Self::Target {
@@ -60,3 +87,27 @@ impl<VM: VMBinding> DerefMut for GenNurseryProcessEdges<VM> {
         &mut self.base
     }
 }
+
+/// Process the edges remembered by the last incremental nursery GC (see `Gen::remember_edges()`)
+/// with `GenNurseryProcessEdges`. The edges that still point to young objects which are not
+/// collected are remembered again.
+pub struct ProcessRememberedEdges<VM: VMBinding> {
+    edges: Vec<VM::VMEdge>,
+}
+
+impl<VM: VMBinding> ProcessRememberedEdges<VM> {
+    pub fn new(edges: Vec<VM::VMEdge>) -> Self {
+        Self { edges }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for ProcessRememberedEdges<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        for edges in self.edges.chunks(GenNurseryProcessEdges::<VM>::CAPACITY) {
+            worker.add_work(
+                WorkBucketStage::Closure,
+                GenNurseryProcessEdges::<VM>::new(edges.to_vec(), false, mmtk),
+            );
+        }
+    }
+}
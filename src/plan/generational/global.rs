@@ -1,25 +1,28 @@
+use crate::plan::generational::gc_work::ProcessRememberedEdges;
+use crate::plan::generational::tenuring::{ObjectGeneration, TenuringPolicy};
 use crate::plan::global::CommonPlan;
+use crate::plan::CollectionScope;
 use crate::plan::ObjectQueue;
 use crate::plan::Plan;
 use crate::plan::PlanConstraints;
 use crate::policy::copyspace::CopySpace;
 use crate::policy::space::Space;
 use crate::scheduler::*;
+use crate::util::constants::LOG_BYTES_IN_PAGE;
 use crate::util::conversions;
 use crate::util::copy::CopySemantics;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::SideMetadataSanity;
-use crate::util::metadata::side_metadata::SideMetadataSpec;
-use crate::util::options::Options;
+use crate::util::metadata::side_metadata::{self, spec_defs, SideMetadataSpec};
+use crate::util::options::{NurseryKind, Options};
 use crate::util::statistics::counter::EventCounter;
-use crate::util::ObjectReference;
 use crate::util::VMWorkerThread;
+use crate::util::{Address, ObjectReference};
 use crate::vm::{ObjectModel, VMBinding};
-use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::{Arc, Mutex};
 
 use mmtk_macros::PlanTraceObject;
@@ -39,9 +42,153 @@ pub struct Gen<VM: VMBinding> {
     /// Is next GC full heap?
     pub next_gc_full_heap: AtomicBool,
     pub full_heap_gc_count: Arc<Mutex<EventCounter>>,
+    /// The current nursery size in pages. A nursery GC is triggered when the nursery reaches
+    /// this size. This is updated after each GC (see `update_nursery_size()`).
+    pub nursery_pages: AtomicUsize,
+    /// The nursery size in pages that we expect to be collected within the `max_pause_ms` option.
+    /// This is updated after each nursery GC if `max_pause_ms` is set.
+    pause_bounded_nursery_pages: AtomicUsize,
+    /// The nursery size in pages that keeps the nursery survival rate within the target range.
+    /// This is updated after each nursery GC if `nursery_feedback` is set.
+    survival_bounded_nursery_pages: AtomicUsize,
+    /// The pages of young objects (in the nursery, and in the survivor spaces if the plan has any) when
+    /// the current nursery GC started.
+    young_pages_at_gc_start: AtomicUsize,
+    /// The bytes of young objects at the start of nursery GCs.
+    pub young_bytes: Arc<Mutex<EventCounter>>,
+    /// The bytes of young objects that survived nursery GCs, including the promoted bytes.
+    pub survived_bytes: Arc<Mutex<EventCounter>>,
+    /// The bytes of young objects that were promoted to the mature space in nursery GCs.
+    pub promoted_bytes: Arc<Mutex<EventCounter>>,
+    /// Decides when the young objects are promoted. Bindings can replace it with
+    /// `set_tenuring_policy()`.
+    tenuring: Mutex<Box<dyn TenuringPolicy>>,
+    /// The most nursery GCs that the plan can keep young objects for, which is the threshold of the
+    /// policy that the plan creates this struct with. The threshold of any policy is capped by it.
+    max_tenuring_threshold: usize,
+    /// The tenuring threshold of the current GC. It is read from the policy when a GC starts.
+    tenuring_threshold: AtomicUsize,
+    /// Is the nursery collected in increments (see the option `incremental_nursery`)?
+    incremental_nursery: bool,
+    /// Is the current GC an incremental nursery GC, which collects only part of the nursery?
+    gc_incremental: AtomicBool,
+    /// Is the next GC an incremental nursery GC?
+    next_gc_incremental: AtomicBool,
+    /// The part of the nursery that the next incremental nursery GC collects.
+    increment: Mutex<NurseryIncrement>,
+    /// The edges outside the nursery that pointed to the young objects which were not collected by
+    /// the last incremental nursery GC. They are processed in the next nursery GC.
+    remembered_edges: Mutex<Vec<VM::VMEdge>>,
+}
+
+/// The part of the nursery that an incremental nursery GC collects: the objects below a limit.
+/// The objects above the limit are allocated later, and are collected by later GCs.
+struct NurseryIncrement {
+    /// The objects below this address are collected by the next incremental nursery GC.
+    limit: Address,
+    /// The reserved pages of the nursery when the limit was set.
+    limit_pages: usize,
+    /// The reserved pages of the nursery below the limit of the last incremental nursery GC. The
+    /// objects in them have been collected.
+    collected_pages: usize,
+}
+
+impl NurseryIncrement {
+    /// No objects are collected by the next increment of a nursery that starts at `start`.
+    fn new(start: Address) -> Self {
+        NurseryIncrement {
+            limit: start,
+            limit_pages: 0,
+            collected_pages: 0,
+        }
+    }
+
+    /// Check the increment when the nursery has `reserved` pages and allocates up to `cursor`.
+    /// Returns true if the objects allocated since the limit was set have used up the allocation
+    /// budget, and there are objects below the limit for an incremental nursery GC to collect. If
+    /// there are none (e.g. after the whole nursery is collected), the limit is just moved up to
+    /// `cursor`, so the next increment collects the objects allocated so far.
+    fn poll(&mut self, reserved: usize, budget: usize, cursor: Address) -> bool {
+        if reserved < self.limit_pages.saturating_add(budget) {
+            return false;
+        }
+        if self.limit_pages > self.collected_pages {
+            return true;
+        }
+        self.limit = cursor;
+        self.limit_pages = reserved;
+        false
+    }
+
+    /// An incremental nursery GC has collected the objects below the limit. The objects that the
+    /// nursery has allocated up to `cursor` are collected by the next increment.
+    fn collected(&mut self, reserved: usize, cursor: Address) {
+        self.collected_pages = self.limit_pages;
+        self.limit = cursor;
+        self.limit_pages = reserved;
+    }
+}
+
+/// The survival of young objects in a nursery GC.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NurserySurvival {
+    /// The bytes of young objects when the GC started. This is at the granularity of pages.
+    pub young_bytes: usize,
+    /// The bytes of young objects that survived the GC, including the promoted bytes.
+    pub survived_bytes: usize,
+    /// The bytes of young objects that were promoted to the mature space.
+    pub promoted_bytes: usize,
+}
+
+impl NurserySurvival {
+    /// The fraction of young objects (in bytes) that survived the GC.
+    pub fn survival_rate(&self) -> f64 {
+        if self.young_bytes == 0 {
+            0f64
+        } else {
+            (self.survived_bytes as f64 / self.young_bytes as f64).min(1f64)
+        }
+    }
+
+    /// The fraction of young objects (in bytes) that were promoted in the GC.
+    pub fn promotion_rate(&self) -> f64 {
+        if self.young_bytes == 0 {
+            0f64
+        } else {
+            (self.promoted_bytes as f64 / self.young_bytes as f64).min(1f64)
+        }
+    }
+}
+
+/// With `nursery_feedback`, the nursery is grown when more than this fraction of the young objects survive a nursery GC.
+const HIGH_SURVIVAL_RATE: f64 = 0.2;
+/// With `nursery_feedback`, the nursery is shrunk when less than this fraction of the young objects survive a nursery GC.
+const LOW_SURVIVAL_RATE: f64 = 0.05;
+
+/// Scale the nursery size to move the survival rate towards the range between `LOW_SURVIVAL_RATE` and `HIGH_SURVIVAL_RATE`.
+/// A larger nursery gives young objects more time to die before they are traced, so fewer of them survive.
+/// We grow faster than we shrink, as a nursery that is too small causes premature promotion.
+fn scale_nursery_pages_for_survival(current: usize, survival_rate: f64) -> usize {
+    if survival_rate > HIGH_SURVIVAL_RATE {
+        current.saturating_add(current / 2)
+    } else if survival_rate < LOW_SURVIVAL_RATE {
+        current - current / 4
+    } else {
+        current
+    }
 }
 
 impl<VM: VMBinding> Gen<VM> {
+    /// The side metadata for the number of nursery GCs that each young object has survived. The
+    /// spaces that keep young objects across nursery GCs need to have it in their local specs.
+    pub const AGE_SPEC: SideMetadataSpec = spec_defs::GEN_OBJECT_AGE;
+    /// The maximum age that can be recorded for an object.
+    pub const MAX_AGE: u8 = (1 << (1 << Self::AGE_SPEC.log_num_of_bits)) - 1;
+
+    /// Create the common part of a generational plan. The threshold of `tenuring` when it is
+    /// created is the most nursery GCs that the plan can keep young objects for. If it is larger
+    /// than 1, the plan needs to copy for `CopySemantics::Nursery` to a space that keeps the young
+    /// objects, and has `AGE_SPEC` in its local specs.
     pub fn new(
         mut heap: HeapMeta,
         global_metadata_specs: Vec<SideMetadataSpec>,
@@ -49,18 +196,24 @@ impl<VM: VMBinding> Gen<VM> {
         vm_map: &'static VMMap,
         mmapper: &'static Mmapper,
         options: Arc<Options>,
+        tenuring: Box<dyn TenuringPolicy>,
     ) -> Self {
-        let nursery = CopySpace::new(
+        let total_pages = heap.get_total_pages();
+        let max_tenuring_threshold = tenuring.threshold();
+        debug_assert!(max_tenuring_threshold >= 1);
+        let mut nursery = CopySpace::new(
             "nursery",
             false,
             true,
+            false,
             VMRequest::fixed_extent(options.get_max_nursery(), false),
             global_metadata_specs.clone(),
             vm_map,
             mmapper,
             &mut heap,
         );
-        let common = CommonPlan::new(
+        let nursery_pages = Self::compute_nursery_pages(&options, total_pages, total_pages);
+        let mut common = CommonPlan::new(
             vm_map,
             mmapper,
             options,
@@ -70,6 +223,29 @@ impl<VM: VMBinding> Gen<VM> {
         );
 
         let full_heap_gc_count = common.base.stats.new_event_counter("majorGC", true, true);
+        let young_bytes = common
+            .base
+            .stats
+            .new_event_counter("nurseryYoungBytes", true, true);
+        let survived_bytes =
+            common
+                .base
+                .stats
+                .new_event_counter("nurserySurvivedBytes", true, true);
+        let promoted_bytes =
+            common
+                .base
+                .stats
+                .new_event_counter("nurseryPromotedBytes", true, true);
+
+        let incremental_nursery = *options.incremental_nursery && constraints.needs_log_bit;
+        if incremental_nursery {
+            // The young objects that are not collected by an incremental nursery GC are not
+            // scanned, so the barrier needs to remember the writes to them.
+            nursery.set_unlog_new_objects();
+            common.los.set_unlog_new_objects();
+        }
+        let increment = NurseryIncrement::new(nursery.common().start);
 
         Gen {
             nursery,
@@ -77,20 +253,170 @@ impl<VM: VMBinding> Gen<VM> {
             gc_full_heap: AtomicBool::default(),
             next_gc_full_heap: AtomicBool::new(false),
             full_heap_gc_count,
+            nursery_pages: AtomicUsize::new(nursery_pages),
+            pause_bounded_nursery_pages: AtomicUsize::new(usize::MAX),
+            survival_bounded_nursery_pages: AtomicUsize::new(usize::MAX),
+            young_pages_at_gc_start: AtomicUsize::new(0),
+            young_bytes,
+            survived_bytes,
+            promoted_bytes,
+            max_tenuring_threshold,
+            tenuring_threshold: AtomicUsize::new(max_tenuring_threshold),
+            tenuring: Mutex::new(tenuring),
+            incremental_nursery,
+            gc_incremental: AtomicBool::new(false),
+            next_gc_incremental: AtomicBool::new(false),
+            increment: Mutex::new(increment),
+            remembered_edges: Mutex::new(vec![]),
         }
     }
 
-    /// Verify side metadata specs used in the spaces in Gen.
-    pub fn verify_side_metadata_sanity(&self, sanity: &mut SideMetadataSanity) {
-        self.common.verify_side_metadata_sanity(sanity);
-        self.nursery.verify_side_metadata_sanity(sanity);
+    /// Scale the nursery by the ratio of the target pause time and the time of the current nursery GC
+    /// so far. The pause time of a nursery GC is roughly proportional to the survivors in the nursery,
+    /// so a smaller nursery means a shorter pause. We grow the nursery by at most twice each time
+    /// to avoid oscillation. If the nursery is collected in increments, this is the size of the
+    /// increments instead.
+    fn update_pause_bounded_nursery_pages(&self, max_pause_ms: usize) {
+        let elapsed = match self.common.base.current_gc_elapsed() {
+            Some(elapsed) => elapsed,
+            None => return,
+        };
+        let current = if self.incremental_nursery {
+            self.young_pages_at_gc_start.load(Ordering::Relaxed).max(1)
+        } else {
+            self.nursery_pages.load(Ordering::Relaxed)
+        };
+        let elapsed_ms = (elapsed.as_secs_f64() * 1000f64).max(f64::EPSILON);
+        let ratio = (max_pause_ms as f64 / elapsed_ms).min(2f64);
+        let pages = (current as f64 * ratio) as usize;
+        debug!(
+            "Nursery GC took {:.3} ms (target {} ms). Pause bounded nursery size is {} pages",
+            elapsed_ms, max_pause_ms, pages
+        );
+        self.pause_bounded_nursery_pages
+            .store(pages, Ordering::Relaxed);
+    }
+
+    /// Compute the nursery size in pages, given the total pages and the available pages of the heap.
+    fn compute_nursery_pages(
+        options: &Options,
+        total_pages: usize,
+        available_pages: usize,
+    ) -> usize {
+        let min = conversions::bytes_to_pages_up(options.get_min_nursery());
+        let max = conversions::bytes_to_pages_up(options.get_max_nursery());
+        let pages = match options.nursery.kind {
+            NurseryKind::Fixed => max,
+            // Appel-style: the nursery can use the free space in the heap. As the nursery needs the same
+            // amount of pages as copy reserve, it can take half of the free space.
+            NurseryKind::Bounded => available_pages / 2,
+            NurseryKind::Proportional => total_pages / 100 * options.nursery.percent,
+        };
+        pages.max(min).min(max)
+    }
+
+    /// Update the nursery size based on the current heap usage. Similar to `should_next_gc_be_full_heap()`,
+    /// this function should be called after all spaces have been released.
+    pub fn update_nursery_size(&self, plan: &dyn Plan<VM = VM>) {
+        let options = &self.common.base.options;
+        if options.max_pause_ms.get() != 0 && self.is_current_gc_nursery() {
+            self.update_pause_bounded_nursery_pages(options.max_pause_ms.get());
+        }
+        self.set_nursery_size(plan);
+    }
+
+    /// Set the nursery size from the current heap usage and the bounds computed in previous GCs.
+    fn set_nursery_size(&self, plan: &dyn Plan<VM = VM>) {
+        let options = &self.common.base.options;
+        let mut pages = Self::compute_nursery_pages(
+            options,
+            plan.get_total_pages(),
+            plan.get_available_pages(),
+        );
+        let min = conversions::bytes_to_pages_up(options.get_min_nursery());
+        // With incremental nursery GCs, the pause time bounds the increments, not the nursery.
+        if options.max_pause_ms.get() != 0 && !self.incremental_nursery {
+            let bounded = self.pause_bounded_nursery_pages.load(Ordering::Relaxed);
+            pages = pages.min(bounded.max(min));
+        }
+        if options.nursery_feedback.get() && options.nursery.kind != NurseryKind::Fixed {
+            let bounded = self.survival_bounded_nursery_pages.load(Ordering::Relaxed);
+            pages = pages.min(bounded.max(min));
+        }
+        trace!("Nursery size is set to {} pages", pages);
+        self.nursery_pages.store(pages, Ordering::Relaxed);
+    }
+
+    /// Add the pages of young objects outside the nursery (e.g. in survivor spaces) at the start of a nursery GC.
+    /// This should be called in the plan's `prepare()` after `Gen::prepare()`.
+    pub fn add_young_pages(&self, pages: usize) {
+        self.young_pages_at_gc_start
+            .fetch_add(pages, Ordering::Relaxed);
+    }
+
+    /// Finish a GC. This should be called in the plan's `end_of_gc()`. For a nursery GC, this records the survival
+    /// of the young objects in the statistics, makes the next GC a full heap GC if the objects that the next nursery
+    /// GC is likely to promote would not fit in the heap, and adapts the nursery size if `nursery_feedback` is set.
+    /// It returns the survival of the young objects for a nursery GC, and `None` for a full heap GC.
+    pub fn end_of_gc(&self, plan: &dyn Plan<VM = VM>) -> Option<NurserySurvival> {
+        if !self.is_current_gc_nursery() {
+            return None;
+        }
+        let (survived_bytes, promoted_bytes) =
+            self.common.base.gc_stats.gc_copied_and_promoted_bytes();
+        let survival = NurserySurvival {
+            young_bytes: self.young_pages_at_gc_start.load(Ordering::Relaxed) << LOG_BYTES_IN_PAGE,
+            survived_bytes,
+            promoted_bytes,
+        };
+        self.young_bytes
+            .lock()
+            .unwrap()
+            .inc_by(survival.young_bytes as u64);
+        self.survived_bytes
+            .lock()
+            .unwrap()
+            .inc_by(survival.survived_bytes as u64);
+        self.promoted_bytes
+            .lock()
+            .unwrap()
+            .inc_by(survival.promoted_bytes as u64);
+        debug!(
+            "Nursery GC: {} young bytes, survival rate {:.3}, promotion rate {:.3}",
+            survival.young_bytes,
+            survival.survival_rate(),
+            survival.promotion_rate()
+        );
+
+        // The next nursery GC is likely to promote about as much as this one. If the promoted
+        // objects would not fit in the heap together with the minimal nursery, the next GC
+        // should be a full heap GC, like in `should_next_gc_be_full_heap()`.
+        let promoted_pages = conversions::bytes_to_pages_up(survival.promoted_bytes);
+        let min_nursery_pages =
+            conversions::bytes_to_pages_up(self.common.base.options.get_min_nursery());
+        if plan.get_available_pages() < min_nursery_pages + promoted_pages {
+            debug!(
+                "{} pages promoted, and {} pages available. The next GC is full heap",
+                promoted_pages,
+                plan.get_available_pages()
+            );
+            self.set_next_gc_full_heap(true);
+        }
+
+        if self.common.base.options.nursery_feedback.get() {
+            let current = self.nursery_pages.load(Ordering::Relaxed);
+            let pages = scale_nursery_pages_for_survival(current, survival.survival_rate());
+            self.survival_bounded_nursery_pages
+                .store(pages, Ordering::Relaxed);
+            self.set_nursery_size(plan);
+        }
+        Some(survival)
     }
 
     /// Get spaces in generation plans
-    pub fn get_spaces(&self) -> Vec<&dyn Space<VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.nursery);
-        ret
+    pub fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<VM>)) {
+        self.common.for_each_space(func);
+        func(&self.nursery);
     }
 
     /// Prepare Gen. This should be called by a single thread in GC prepare work.
@@ -99,8 +425,35 @@ impl<VM: VMBinding> Gen<VM> {
         if full_heap {
             self.full_heap_gc_count.lock().unwrap().inc();
         }
-        self.common.prepare(tls, full_heap);
-        self.nursery.prepare(true);
+        self.tenuring_threshold.store(
+            self.tenuring
+                .get_mut()
+                .unwrap()
+                .threshold()
+                .clamp(1, self.max_tenuring_threshold),
+            Ordering::Relaxed,
+        );
+        let increment = self.increment.get_mut().unwrap();
+        if self.gc_incremental.load(Ordering::SeqCst) {
+            // Only the nursery is collected. The young objects in the LOS may be referenced by the
+            // young objects above the limit, which are not scanned, so they are collected by the
+            // next nursery GC that collects the whole nursery.
+            self.common.base.prepare(tls, false);
+            self.young_pages_at_gc_start.store(
+                increment.limit_pages - increment.collected_pages,
+                Ordering::Relaxed,
+            );
+            self.nursery.prepare_partial(increment.limit);
+        } else {
+            self.common.prepare(tls, full_heap);
+            if !full_heap {
+                self.young_pages_at_gc_start.store(
+                    self.nursery.reserved_pages() - increment.collected_pages,
+                    Ordering::Relaxed,
+                );
+            }
+            self.nursery.prepare(true);
+        }
         self.nursery
             .set_copy_for_sft_trace(Some(CopySemantics::PromoteToMature));
     }
@@ -108,8 +461,22 @@ impl<VM: VMBinding> Gen<VM> {
     /// Release Gen. This should be called by a single thread in GC release work.
     pub fn release(&mut self, tls: VMWorkerThread) {
         let full_heap = !self.is_current_gc_nursery();
-        self.common.release(tls, full_heap);
-        self.nursery.release();
+        if self.gc_incremental.load(Ordering::SeqCst) {
+            self.common.base.release(tls, false);
+            self.nursery.release_partial();
+            let (reserved, cursor) = (self.nursery.reserved_pages(), self.nursery.cursor());
+            self.increment
+                .get_mut()
+                .unwrap()
+                .collected(reserved, cursor);
+        } else {
+            self.common.release(tls, full_heap);
+            self.nursery.release();
+            *self.increment.get_mut().unwrap() = NurseryIncrement::new(self.nursery.common().start);
+            // All the young objects have been collected. The remembered edges are not processed
+            // by a full heap GC.
+            self.remembered_edges.get_mut().unwrap().clear();
+        }
     }
 
     /// Independent of how many pages remain in the page budget (a function of heap size), we must
@@ -132,13 +499,17 @@ impl<VM: VMBinding> Gen<VM> {
         space_full: bool,
         space: Option<&dyn Space<VM>>,
     ) -> bool {
-        let nursery_full = self.nursery.reserved_pages()
-            >= (conversions::bytes_to_pages_up(self.common.base.options.get_max_nursery()));
+        let nursery_full =
+            self.nursery.reserved_pages() >= self.nursery_pages.load(Ordering::Relaxed);
 
         if nursery_full {
             return true;
         }
 
+        if self.incremental_nursery && self.is_increment_due() {
+            return true;
+        }
+
         if self.virtual_memory_exhausted(plan) {
             return true;
         }
@@ -157,14 +528,142 @@ impl<VM: VMBinding> Gen<VM> {
         self.common.base.collection_required(plan, space_full)
     }
 
+    /// Check if the next GC should be an incremental nursery GC. The allocation budget between
+    /// incremental nursery GCs is the pages that we expect to be collected within `max_pause_ms`.
+    /// As all the young objects are unlogged, any address that the nursery has allocated up to is
+    /// a valid limit for an increment (see `NurseryIncrement::poll()`).
+    fn is_increment_due(&self) -> bool {
+        let max_pause_ms = self.common.base.options.max_pause_ms.get();
+        let budget = self.pause_bounded_nursery_pages.load(Ordering::Relaxed);
+        // We do not know the budget until we have timed a nursery GC.
+        if max_pause_ms == 0 || budget == usize::MAX {
+            return false;
+        }
+        let min = conversions::bytes_to_pages_up(self.common.base.options.get_min_nursery());
+        let due = self.increment.lock().unwrap().poll(
+            self.nursery.reserved_pages(),
+            budget.max(min),
+            self.nursery.cursor(),
+        );
+        if due {
+            self.next_gc_incremental.store(true, Ordering::SeqCst);
+        }
+        due
+    }
+
+    /// Is the current GC an incremental nursery GC? It collects the young objects in the nursery
+    /// that were allocated before the last GC, and does not collect the other spaces.
+    pub fn is_current_gc_incremental(&self) -> bool {
+        self.gc_incremental.load(Ordering::SeqCst)
+    }
+
+    /// Remember the edges that point to the young objects which are not collected by the current
+    /// incremental nursery GC, so the next nursery GC updates them.
+    pub fn remember_edges(&self, edges: Vec<VM::VMEdge>) {
+        self.remembered_edges.lock().unwrap().extend(edges);
+    }
+
+    /// Schedule the edges remembered by the last incremental nursery GC to be processed with
+    /// `GenNurseryProcessEdges`. A plan should call this when it schedules a nursery GC.
+    pub fn schedule_remembered_edges(&self, scheduler: &GCWorkScheduler<VM>) {
+        let edges = std::mem::take(&mut *self.remembered_edges.lock().unwrap());
+        if !edges.is_empty() {
+            scheduler.work_buckets[WorkBucketStage::Closure]
+                .add(ProcessRememberedEdges::<VM>::new(edges));
+        }
+    }
+
     pub fn force_full_heap_collection(&self) {
         self.next_gc_full_heap.store(true, Ordering::Relaxed);
     }
 
+    /// A nursery GC collects only the nursery. The mature objects are not traced, and the
+    /// references from them into the nursery are remembered by the object barrier. The nursery
+    /// objects in the LOS are collected in a nursery GC too, but the nursery is the only space
+    /// whose objects are all collected, so it is the only scope we support.
+    pub fn supports_partial_collection(&self, scope: &CollectionScope) -> bool {
+        scope.is(&[self.nursery.get_name()])
+    }
+
     pub fn last_collection_full_heap(&self) -> bool {
         self.gc_full_heap.load(Ordering::Relaxed)
     }
 
+    /// The generation of an object in the spaces of this struct. The objects in the nursery, and
+    /// the large objects allocated since the last GC, have not survived any GC yet.
+    pub fn object_generation(&self, object: ObjectReference) -> ObjectGeneration {
+        let los = self.common.get_los();
+        if self.nursery.in_space(object) || (los.in_space(object) && los.is_in_nursery(object)) {
+            ObjectGeneration::Young { age: 0 }
+        } else {
+            ObjectGeneration::Mature
+        }
+    }
+
+    /// Get the number of nursery GCs that a young object has survived. The object needs to be in a
+    /// space that has `AGE_SPEC`.
+    #[inline(always)]
+    pub fn get_age(object: ObjectReference) -> u8 {
+        side_metadata::load_atomic(&Self::AGE_SPEC, object.to_address(), Ordering::Relaxed) as u8
+    }
+
+    /// Set the number of nursery GCs that a young object has survived, saturated at `MAX_AGE`. The
+    /// object needs to be in a space that has `AGE_SPEC`.
+    #[inline(always)]
+    pub fn set_age(object: ObjectReference, age: u8) {
+        side_metadata::store_atomic(
+            &Self::AGE_SPEC,
+            object.to_address(),
+            age.min(Self::MAX_AGE) as usize,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// The number of nursery GCs a young object has to survive in the current GC before it is
+    /// promoted.
+    pub fn tenuring_threshold(&self) -> usize {
+        self.tenuring_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Replace the tenuring policy. Its threshold is read when the next GC starts.
+    pub fn set_tenuring_policy(&self, policy: Box<dyn TenuringPolicy>) {
+        *self.tenuring.lock().unwrap() = policy;
+    }
+
+    /// Update the tenuring policy after a nursery GC (see `TenuringPolicy::update()`).
+    pub fn update_tenuring(
+        &self,
+        survival: &NurserySurvival,
+        survivor_pages: usize,
+        target_pages: usize,
+    ) {
+        self.tenuring
+            .lock()
+            .unwrap()
+            .update(survival, survivor_pages, target_pages);
+    }
+
+    /// Trace a young object in `space` in a nursery GC, which will have survived `age` nursery GCs
+    /// after this GC. The object is promoted if the age reaches the tenuring threshold. Otherwise,
+    /// it is copied with `CopySemantics::Nursery`, and its age is recorded.
+    pub fn trace_young_object<Q: ObjectQueue>(
+        &self,
+        space: &CopySpace<VM>,
+        queue: &mut Q,
+        object: ObjectReference,
+        age: u8,
+        worker: &mut GCWorker<VM>,
+    ) -> ObjectReference {
+        if age as usize >= self.tenuring_threshold() {
+            space.trace_object(queue, object, Some(CopySemantics::PromoteToMature), worker)
+        } else {
+            let new_object =
+                space.trace_object(queue, object, Some(CopySemantics::Nursery), worker);
+            Self::set_age(new_object, age);
+            new_object
+        }
+    }
+
     /// Check if we should do a full heap GC. It returns true if we should have a full heap GC.
     /// It also sets gc_full_heap based on the result.
     pub fn requires_full_heap_collection<P: Plan>(&self, plan: &P) -> bool {
@@ -177,11 +676,10 @@ impl<VM: VMBinding> Gen<VM> {
         } else if self
             .common
             .base
-            .user_triggered_collection
+            .full_heap_collection_requested
             .load(Ordering::SeqCst)
-            && *self.common.base.options.full_heap_system_gc
         {
-            // User triggered collection, and we force full heap for user triggered collection
+            // A full-heap collection is requested with request_gc_blocking().
             true
         } else if self.next_gc_full_heap.load(Ordering::SeqCst)
             || self
@@ -195,16 +693,38 @@ impl<VM: VMBinding> Gen<VM> {
             true
         } else if self.virtual_memory_exhausted(plan) {
             true
+        } else if plan.get_total_pages() <= plan.get_reserved_pages() {
+            // The heap is full, so a nursery GC may not free enough memory.
+            true
+        } else if self.common.base.collection_scope().is_some() {
+            // The binding asked for a partial-heap collection of the nursery. This is a user
+            // triggered collection, but it is not turned into a full heap one.
+            false
         } else {
-            plan.get_total_pages() <= plan.get_reserved_pages()
+            // User triggered collection, and we force full heap for user triggered collection
+            self.common
+                .base
+                .user_triggered_collection
+                .load(Ordering::SeqCst)
+                && self.common.base.options.full_heap_system_gc.get()
         };
 
         self.gc_full_heap.store(is_full_heap, Ordering::SeqCst);
+        if is_full_heap {
+            self.common.base.clear_collection_scope();
+        }
+        // An incremental nursery GC is not enough if the nursery is full.
+        let is_incremental = self.next_gc_incremental.swap(false, Ordering::SeqCst)
+            && !is_full_heap
+            && self.nursery.reserved_pages() < self.nursery_pages.load(Ordering::Relaxed);
+        self.gc_incremental.store(is_incremental, Ordering::SeqCst);
 
         info!(
             "{}",
             if is_full_heap {
                 "Full heap GC"
+            } else if is_incremental {
+                "Incremental nursery GC"
             } else {
                 "Nursery GC"
             }
@@ -238,14 +758,20 @@ impl<VM: VMBinding> Gen<VM> {
         object: ObjectReference,
         worker: &mut GCWorker<VM>,
     ) -> ObjectReference {
-        // Evacuate nursery objects
+        // Evacuate nursery objects. They are promoted, unless the plan keeps young objects across
+        // nursery GCs.
         if self.nursery.in_space(object) {
-            return self.nursery.trace_object::<Q>(
-                queue,
-                object,
-                Some(CopySemantics::PromoteToMature),
-                worker,
-            );
+            return self.trace_young_object(&self.nursery, queue, object, 1, worker);
+        }
+        // An incremental nursery GC traces only the nursery.
+        if self.is_current_gc_incremental() {
+            return object;
+        }
+        // Mature objects are not traced in a nursery GC. Most of them are unlogged, which is cheaper
+        // to check than finding out which space they are in. If the nursery is collected in
+        // increments, the young objects are unlogged too.
+        if !self.incremental_nursery && Self::is_unlogged_mature_object(object) {
+            return object;
         }
         // We may alloc large object into LOS as nursery objects. Trace them here.
         if self.common.get_los().in_space(object) {
@@ -254,6 +780,21 @@ impl<VM: VMBinding> Gen<VM> {
         object
     }
 
+    /// Check if an object outside the nursery is known to be mature because it is unlogged. Only
+    /// mature objects are unlogged (objects in the logical nursery of the LOS are not), but a mature
+    /// object that has been logged by the barrier since the last GC is not identified by this.
+    /// This always returns false if the plan does not use the log bit.
+    #[inline(always)]
+    fn is_unlogged_mature_object(object: ObjectReference) -> bool {
+        use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+        // The log bit is only mapped for the spaces in the MMTk heap.
+        let address = object.to_address();
+        super::GEN_CONSTRAINTS.needs_log_bit
+            && address >= HEAP_START
+            && address < HEAP_END
+            && VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.is_unlogged::<VM>(object, Ordering::Relaxed)
+    }
+
     /// Is the current GC a nursery GC?
     pub fn is_current_gc_nursery(&self) -> bool {
         !self.gc_full_heap.load(Ordering::SeqCst)
@@ -275,6 +816,12 @@ impl<VM: VMBinding> Gen<VM> {
             .store(next_gc_full_heap, Ordering::SeqCst);
     }
 
+    /// Are the young objects logged by the barrier, so the mod buffers may contain them? This is
+    /// the case if the nursery is collected in increments.
+    pub fn logs_young_objects(&self) -> bool {
+        self.incremental_nursery
+    }
+
     /// Get pages reserved for the collection by a generational plan. A generational plan should
     /// add their own reservatioin with the value returned by this method.
     pub fn get_collection_reserved_pages(&self) -> usize {
@@ -287,3 +834,55 @@ impl<VM: VMBinding> Gen<VM> {
         self.nursery.reserved_pages() + self.common.get_used_pages()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_survival_rates() {
+        let survival = NurserySurvival {
+            young_bytes: 1000,
+            survived_bytes: 300,
+            promoted_bytes: 100,
+        };
+        assert!((survival.survival_rate() - 0.3).abs() < f64::EPSILON);
+        assert!((survival.promotion_rate() - 0.1).abs() < f64::EPSILON);
+        let empty = NurserySurvival {
+            young_bytes: 0,
+            survived_bytes: 0,
+            promoted_bytes: 0,
+        };
+        assert_eq!(empty.survival_rate(), 0f64);
+    }
+
+    #[test]
+    fn test_nursery_increments() {
+        let start = unsafe { Address::from_usize(0x1000_0000) };
+        let page = |pages: usize| start + (pages << LOG_BYTES_IN_PAGE);
+        let mut increment = NurseryIncrement::new(start);
+        // Nothing is below the limit yet, so the first budget only moves the limit up.
+        assert!(!increment.poll(5, 10, page(5)));
+        assert!(!increment.poll(10, 10, page(10)));
+        assert_eq!(increment.limit, page(10));
+        // The next budget makes an incremental nursery GC collect the first 10 pages.
+        assert!(!increment.poll(19, 10, page(19)));
+        assert!(increment.poll(20, 10, page(20)));
+        increment.collected(22, page(22));
+        assert_eq!(increment.collected_pages, 10);
+        assert_eq!(increment.limit, page(22));
+        assert!(!increment.poll(31, 10, page(31)));
+        assert!(increment.poll(32, 10, page(32)));
+    }
+
+    #[test]
+    fn test_scale_nursery_pages_for_survival() {
+        assert_eq!(scale_nursery_pages_for_survival(100, 0.5), 150);
+        assert_eq!(scale_nursery_pages_for_survival(100, 0.1), 100);
+        assert_eq!(scale_nursery_pages_for_survival(100, 0.01), 75);
+        assert_eq!(
+            scale_nursery_pages_for_survival(usize::MAX, 0.5),
+            usize::MAX
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use super::gc_work::GenImmixMatureGCWorkContext;
 use super::gc_work::GenImmixNurseryGCWorkContext;
 use crate::plan::generational::global::Gen;
+use crate::plan::generational::tenuring::{FixedTenuring, ObjectGeneration, TenuringPolicy};
 use crate::plan::global::BasePlan;
 use crate::plan::global::CommonPlan;
 use crate::plan::global::GcStatus;
 use crate::plan::AllocationSemantics;
+use crate::plan::CollectionScope;
 use crate::plan::Plan;
 use crate::plan::PlanConstraints;
 use crate::policy::immix::ImmixSpace;
@@ -17,6 +19,7 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::options::Options;
+use crate::util::ObjectReference;
 use crate::util::VMWorkerThread;
 use crate::vm::*;
 
@@ -70,12 +73,17 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
     fn create_copy_config(&'static self) -> CopyConfig<Self::VM> {
         use enum_map::enum_map;
         CopyConfig {
+            // Promotion and mature copying use separate copy buffers, so promoted objects and
+            // defragmented mature objects do not interleave in the same blocks.
             copy_mapping: enum_map! {
                 CopySemantics::PromoteToMature => CopySelector::Immix(0),
-                CopySemantics::Mature => CopySelector::Immix(0),
+                CopySemantics::Mature => CopySelector::Immix(1),
                 _ => CopySelector::Unused,
             },
-            space_mapping: vec![(CopySelector::Immix(0), &self.immix)],
+            space_mapping: vec![
+                (CopySelector::Immix(0), &self.immix),
+                (CopySelector::Immix(1), &self.immix),
+            ],
             constraints: &GENIMMIX_CONSTRAINTS,
         }
     }
@@ -91,10 +99,23 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
         self.gen.force_full_heap_collection()
     }
 
+    fn supports_partial_collection(&self, scope: &CollectionScope) -> bool {
+        self.gen.supports_partial_collection(scope)
+    }
+
     fn last_collection_full_heap(&self) -> bool {
         self.gen.last_collection_full_heap()
     }
 
+    fn object_generation(&self, object: ObjectReference) -> Option<ObjectGeneration> {
+        Some(self.gen.object_generation(object))
+    }
+
+    fn set_tenuring_policy(&self, policy: Box<dyn TenuringPolicy>) -> bool {
+        self.gen.set_tenuring_policy(policy);
+        true
+    }
+
     fn collection_required(&self, space_full: bool, space: Option<&dyn Space<Self::VM>>) -> bool
     where
         Self: Sized,
@@ -102,10 +123,9 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
         self.gen.collection_required(self, space_full, space)
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.gen.get_spaces();
-        ret.push(&self.immix);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.gen.for_each_space(func);
+        func(&self.immix);
     }
 
     // GenImmixMatureProcessEdges<VM, { TraceKind::Defrag }> and GenImmixMatureProcessEdges<VM, { TraceKind::Fast }>
@@ -124,7 +144,8 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
                 true,
                 self.base().cur_collection_attempts.load(Ordering::SeqCst),
                 self.base().is_user_triggered_collection(),
-                *self.base().options.full_heap_system_gc,
+                self.base().options.full_heap_system_gc.get(),
+                self.base().gc_stats.fragmentation_after_full_heap_gc(),
             )
         } else {
             false
@@ -133,6 +154,7 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
         if !is_full_heap {
             debug!("Nursery GC");
             scheduler.schedule_common_work::<GenImmixNurseryGCWorkContext<VM>>(self);
+            self.gen.schedule_remembered_edges(scheduler);
         } else if defrag {
             debug!("Full heap GC Defrag");
             scheduler
@@ -175,6 +197,14 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
         // will change dependant on which spaces have been released
         self.gen
             .set_next_gc_full_heap(Gen::should_next_gc_be_full_heap(self));
+        self.gen.update_nursery_size(self);
+    }
+
+    fn end_of_gc(&self, _tls: VMWorkerThread) {
+        if let Some(survival) = self.gen.end_of_gc(self) {
+            // No young objects are kept after a nursery GC, and there is no room for them.
+            self.gen.update_tenuring(&survival, 0, 0);
+        }
     }
 
     fn get_collection_reserved_pages(&self) -> usize {
@@ -213,6 +243,11 @@ impl<VM: VMBinding> Plan for GenImmix<VM> {
     fn is_current_gc_nursery(&self) -> bool {
         !self.gen.gc_full_heap.load(Ordering::SeqCst)
     }
+
+    // A full-heap GC only copies the mature objects if it is a defrag GC.
+    fn current_gc_copies_objects(&self) -> bool {
+        self.is_current_gc_nursery() || self.immix.in_defrag()
+    }
 }
 
 impl<VM: VMBinding> GenImmix<VM> {
@@ -226,16 +261,18 @@ impl<VM: VMBinding> GenImmix<VM> {
         // We have no specific side metadata for copying. So just use the ones from generational.
         let global_metadata_specs =
             crate::plan::generational::new_generational_global_metadata_specs::<VM>();
-        let immix_space = ImmixSpace::new(
+        let mut immix_space = ImmixSpace::new(
             "immix_mature",
             vm_map,
             mmapper,
             &mut heap,
             scheduler,
             global_metadata_specs.clone(),
+            &GENIMMIX_CONSTRAINTS,
         );
+        immix_space.set_unlog_blocks();
 
-        let genimmix = GenImmix {
+        GenImmix {
             gen: Gen::new(
                 heap,
                 global_metadata_specs,
@@ -243,26 +280,14 @@ impl<VM: VMBinding> GenImmix<VM> {
                 vm_map,
                 mmapper,
                 options,
+                // There are no survivor spaces, so the young objects are promoted in the first GC
+                // they survive. This also caps the threshold of the policies set by bindings.
+                Box::new(FixedTenuring(1)),
             ),
             immix: immix_space,
             last_gc_was_defrag: AtomicBool::new(false),
             last_gc_was_full_heap: AtomicBool::new(false),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        {
-            use crate::util::metadata::side_metadata::SideMetadataSanity;
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            genimmix
-                .gen
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            genimmix
-                .immix
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
         }
-
-        genimmix
     }
 
     fn requires_full_heap_collection(&self) -> bool {
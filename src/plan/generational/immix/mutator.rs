@@ -1,18 +1,38 @@
-pub(super) use super::super::ALLOCATOR_MAPPING;
 use crate::plan::barriers::ObjectRememberingBarrier;
+use crate::plan::generational::create_gen_allocator_mapping;
 use crate::plan::generational::create_gen_space_mapping;
 use crate::plan::generational::gc_work::GenNurseryProcessEdges;
 use crate::plan::generational::immix::GenImmix;
 use crate::plan::mutator_context::Mutator;
 use crate::plan::mutator_context::MutatorConfig;
 use crate::plan::AllocationSemantics;
-use crate::util::alloc::allocators::Allocators;
-use crate::util::alloc::BumpAllocator;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::{BumpAllocator, ImmixAllocator};
 use crate::util::{VMMutatorThread, VMWorkerThread};
 use crate::vm::{ObjectModel, VMBinding};
 use crate::MMTK;
+use enum_map::EnumMap;
 
-pub fn genimmix_mutator_prepare<VM: VMBinding>(_mutator: &mut Mutator<VM>, _tls: VMWorkerThread) {}
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> =
+        create_gen_allocator_mapping(AllocatorSelector::Immix(0));
+}
+
+/// Reset the allocator for long-lived objects, which allocates in the mature immix space.
+fn reset_mature_allocator<VM: VMBinding>(mutator: &mut Mutator<VM>) {
+    let immix_allocator = unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationSemantics::LongLived])
+    }
+    .downcast_mut::<ImmixAllocator<VM>>()
+    .unwrap();
+    immix_allocator.reset();
+}
+
+pub fn genimmix_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: VMWorkerThread) {
+    reset_mature_allocator(mutator);
+}
 
 pub fn genimmix_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: VMWorkerThread) {
     // reset nursery allocator
@@ -24,6 +44,7 @@ pub fn genimmix_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls:
     .downcast_mut::<BumpAllocator<VM>>()
     .unwrap();
     bump_allocator.reset();
+    reset_mature_allocator(mutator);
 }
 
 pub fn create_genimmix_mutator<VM: VMBinding>(
@@ -33,7 +54,11 @@ pub fn create_genimmix_mutator<VM: VMBinding>(
     let genimmix = mmtk.plan.downcast_ref::<GenImmix<VM>>().unwrap();
     let config = MutatorConfig {
         allocator_mapping: &*ALLOCATOR_MAPPING,
-        space_mapping: Box::new(create_gen_space_mapping(&*mmtk.plan, &genimmix.gen.nursery)),
+        space_mapping: Box::new(create_gen_space_mapping(
+            &*mmtk.plan,
+            &genimmix.gen.nursery,
+            (AllocatorSelector::Immix(0), &genimmix.immix),
+        )),
         prepare_func: &genimmix_mutator_prepare,
         release_func: &genimmix_mutator_release,
     };
@@ -47,5 +72,9 @@ pub fn create_genimmix_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan: genimmix,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
@@ -28,6 +28,7 @@ pub mod immix;
 
 pub(super) mod gc_work;
 pub(super) mod global;
+pub(super) mod tenuring;
 
 /// # Barrier overhead measurement:
 ///  - Set `FULL_NURSERY_GC` to `true`.
@@ -51,6 +52,8 @@ pub const GEN_CONSTRAINTS: PlanConstraints = PlanConstraints {
         crate::plan::plan_constraints::MAX_NON_LOS_ALLOC_BYTES_COPYING_PLAN,
         crate::util::options::NURSERY_SIZE,
     ),
+    // The nursery is a copy space.
+    supports_pinned_roots: false,
     ..PlanConstraints::default()
 };
 
@@ -65,24 +68,34 @@ pub fn new_generational_global_metadata_specs<VM: VMBinding>() -> Vec<SideMetada
     SideMetadataContext::new_global_specs(&specs)
 }
 
+/// The allocators reserved by generational plans: the nursery allocator `BumpPointer(0)`, and
+/// the allocator for long-lived objects in the mature space, which is `BumpPointer(1)` for GenCopy
+/// and `Immix(0)` for GenImmix.
 const RESERVED_ALLOCATORS: ReservedAllocators = ReservedAllocators {
-    n_bump_pointer: 1,
+    n_bump_pointer: 2,
+    n_immix: 1,
     ..ReservedAllocators::DEFAULT
 };
 
-lazy_static! {
-    static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
-        let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
-        map[AllocationSemantics::Default] = AllocatorSelector::BumpPointer(0);
-        map
-    };
+/// Create the allocator mapping for a generational plan. `mature` is the allocator for
+/// `AllocationSemantics::LongLived`.
+fn create_gen_allocator_mapping(
+    mature: AllocatorSelector,
+) -> EnumMap<AllocationSemantics, AllocatorSelector> {
+    let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
+    map[AllocationSemantics::Default] = AllocatorSelector::BumpPointer(0);
+    map[AllocationSemantics::ShortLived] = AllocatorSelector::BumpPointer(0);
+    map[AllocationSemantics::LongLived] = mature;
+    map
 }
 
 fn create_gen_space_mapping<VM: VMBinding>(
     plan: &'static dyn Plan<VM = VM>,
     nursery: &'static CopySpace<VM>,
+    mature: (AllocatorSelector, &'static dyn Space<VM>),
 ) -> Vec<(AllocatorSelector, &'static dyn Space<VM>)> {
     let mut vec = create_space_mapping(RESERVED_ALLOCATORS, true, plan);
     vec.push((AllocatorSelector::BumpPointer(0), nursery));
+    vec.push(mature);
     vec
 }
@@ -0,0 +1,136 @@
+//! Tenuring for the generational plans. A young object is promoted to the mature space once it has
+//! survived as many nursery GCs as the tenuring threshold of the plan. The plans that keep young
+//! objects across nursery GCs (GenCopy with survivor spaces) record the age of each of them in
+//! side metadata (see `Gen::get_age()`). The threshold is decided by a [`TenuringPolicy`], which
+//! bindings can replace with
+//! [`memory_manager::set_tenuring_policy`](crate::memory_manager::set_tenuring_policy).
+//!
+//! Bindings can ask which generation an object is in with
+//! [`memory_manager::object_generation`](crate::memory_manager::object_generation), e.g. to only
+//! cache the data derived from objects that are not going to move in nursery GCs any more.
+
+use super::global::NurserySurvival;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The generation of an object in a generational plan.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObjectGeneration {
+    /// The object is young, and has survived `age` nursery GCs. A young object may be moved, or
+    /// promoted to the mature space, in the next nursery GC.
+    Young { age: usize },
+    /// The object is in the mature space, or in a space that is not in the young generation
+    /// (e.g. the large object space). It is only collected by full heap GCs.
+    Mature,
+}
+
+/// Decides when young objects are promoted.
+pub trait TenuringPolicy: Send + Sync {
+    /// The number of nursery GCs a young object has to survive before it is promoted. This is at
+    /// least 1, which promotes young objects in the first GC they survive.
+    fn threshold(&self) -> usize;
+
+    /// Update the threshold after a nursery GC, with the survival of the young objects, and the
+    /// pages of the young objects that are kept in the young generation and the pages they should
+    /// fit in.
+    fn update(&self, _survival: &NurserySurvival, _survivor_pages: usize, _target_pages: usize) {}
+}
+
+/// Promote young objects after a fixed number of nursery GCs.
+pub struct FixedTenuring(pub usize);
+
+impl TenuringPolicy for FixedTenuring {
+    fn threshold(&self) -> usize {
+        self.0
+    }
+}
+
+/// Start with a maximum threshold, and adapt it to the room for the survivors. If the survivors
+/// take up more than their target pages, objects are promoted earlier to make room. If they take
+/// up less than half of it while objects are still promoted, objects are kept longer in the hope
+/// that they die young.
+pub struct AdaptiveTenuring {
+    threshold: AtomicUsize,
+    max: usize,
+}
+
+impl AdaptiveTenuring {
+    pub fn new(max: usize) -> Self {
+        debug_assert!(max >= 1);
+        Self {
+            threshold: AtomicUsize::new(max),
+            max,
+        }
+    }
+}
+
+impl TenuringPolicy for AdaptiveTenuring {
+    fn threshold(&self) -> usize {
+        self.threshold.load(Ordering::Relaxed)
+    }
+
+    fn update(&self, survival: &NurserySurvival, survivor_pages: usize, target_pages: usize) {
+        let current = self.threshold();
+        let threshold = adapt_threshold(
+            current,
+            self.max,
+            survivor_pages,
+            target_pages,
+            survival.promoted_bytes,
+        );
+        if threshold != current {
+            debug!("Tenuring threshold is set to {}", threshold);
+            self.threshold.store(threshold, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The threshold of `AdaptiveTenuring` after a nursery GC.
+fn adapt_threshold(
+    current: usize,
+    max: usize,
+    survivor_pages: usize,
+    target_pages: usize,
+    promoted_bytes: usize,
+) -> usize {
+    if survivor_pages > target_pages {
+        current.saturating_sub(1).max(1)
+    } else if survivor_pages < target_pages / 2 && promoted_bytes > 0 {
+        (current + 1).min(max)
+    } else {
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adapt_threshold() {
+        // The survivors overflow the target: promote earlier, but never below 1.
+        assert_eq!(adapt_threshold(4, 4, 20, 10, 0), 3);
+        assert_eq!(adapt_threshold(1, 4, 20, 10, 0), 1);
+        // Room for the survivors, and objects are promoted: keep them longer, up to the maximum.
+        assert_eq!(adapt_threshold(2, 4, 2, 10, 100), 3);
+        assert_eq!(adapt_threshold(4, 4, 2, 10, 100), 4);
+        // Nothing is promoted, or the survivors are close to the target.
+        assert_eq!(adapt_threshold(2, 4, 2, 10, 0), 2);
+        assert_eq!(adapt_threshold(2, 4, 8, 10, 100), 2);
+    }
+
+    #[test]
+    fn test_adaptive_tenuring() {
+        let policy = AdaptiveTenuring::new(3);
+        assert_eq!(policy.threshold(), 3);
+        let survival = NurserySurvival {
+            young_bytes: 1000,
+            survived_bytes: 500,
+            promoted_bytes: 100,
+        };
+        policy.update(&survival, 20, 10);
+        assert_eq!(policy.threshold(), 2);
+        policy.update(&survival, 2, 10);
+        assert_eq!(policy.threshold(), 3);
+        assert_eq!(FixedTenuring(1).threshold(), 1);
+    }
+}
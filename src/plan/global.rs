@@ -1,27 +1,37 @@
 //! The global part of a plan implementation.
 
-use super::gc_requester::GCRequester;
+use super::gc_requester::{BlockingGCError, GCKind, GCRequester};
+use super::partial_gc::CollectionScope;
 use super::PlanConstraints;
 use crate::mmtk::MMTK;
 use crate::plan::generational::global::Gen;
+use crate::plan::generational::tenuring::{ObjectGeneration, TenuringPolicy};
 use crate::plan::tracing::ObjectQueue;
 use crate::plan::Mutator;
 use crate::policy::immortalspace::ImmortalSpace;
 use crate::policy::largeobjectspace::LargeObjectSpace;
+#[cfg(feature = "raw_memory_space")]
+use crate::policy::rawmemoryspace::RawMemorySpace;
 use crate::policy::space::Space;
 use crate::scheduler::*;
 use crate::util::alloc::allocators::AllocatorSelector;
 #[cfg(feature = "analysis")]
 use crate::util::analysis::AnalysisManager;
 use crate::util::copy::{CopyConfig, GCWorkerCopyContext};
+use crate::util::error::MMTKError;
+use crate::util::gc_stats::{CumulativeGCStats, StatsWindows};
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
+use crate::util::heap_timeline::HeapTimeline;
+use crate::util::metadata::side_metadata::SideMetadataLayout;
 use crate::util::metadata::side_metadata::SideMetadataSanity;
 use crate::util::metadata::side_metadata::SideMetadataSpec;
+use crate::util::mutator_assist::MutatorAssist;
 use crate::util::options::Options;
 use crate::util::options::PlanSelector;
+use crate::util::statistics::counter::EventCounter;
 use crate::util::statistics::stats::Stats;
 use crate::util::ObjectReference;
 use crate::util::{VMMutatorThread, VMWorkerThread};
@@ -30,6 +40,7 @@ use downcast_rs::Downcast;
 use enum_map::EnumMap;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use mmtk_macros::PlanTraceObject;
 
@@ -38,69 +49,111 @@ pub fn create_mutator<VM: VMBinding>(
     mmtk: &'static MMTK<VM>,
 ) -> Box<Mutator<VM>> {
     Box::new(match *mmtk.options.plan {
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_nogc"))]
         PlanSelector::NoGC => crate::plan::nogc::mutator::create_nogc_mutator(tls, &*mmtk.plan),
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_semispace"))]
         PlanSelector::SemiSpace => {
             crate::plan::semispace::mutator::create_ss_mutator(tls, &*mmtk.plan)
         }
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_gencopy"))]
         PlanSelector::GenCopy => {
             crate::plan::generational::copying::mutator::create_gencopy_mutator(tls, mmtk)
         }
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_genimmix"))]
         PlanSelector::GenImmix => {
             crate::plan::generational::immix::mutator::create_genimmix_mutator(tls, mmtk)
         }
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_marksweep"))]
         PlanSelector::MarkSweep => {
             crate::plan::marksweep::mutator::create_ms_mutator(tls, &*mmtk.plan)
         }
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_immix"))]
         PlanSelector::Immix => crate::plan::immix::mutator::create_immix_mutator(tls, &*mmtk.plan),
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_pageprotect"))]
         PlanSelector::PageProtect => {
             crate::plan::pageprotect::mutator::create_pp_mutator(tls, &*mmtk.plan)
         }
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_markcompact"))]
         PlanSelector::MarkCompact => {
             crate::plan::markcompact::mutator::create_markcompact_mutator(tls, &*mmtk.plan)
         }
+        #[cfg(feature = "static_plan")]
+        plan => unreachable!("MMTk is built without the plan {:?}", plan),
     })
 }
 
+// Only GenImmix and Immix use the scheduler.
+#[cfg_attr(feature = "static_plan", allow(unused_variables))]
 pub fn create_plan<VM: VMBinding>(
     plan: PlanSelector,
     vm_map: &'static VMMap,
     mmapper: &'static Mmapper,
     options: Arc<Options>,
     scheduler: Arc<GCWorkScheduler<VM>>,
-) -> Box<dyn Plan<VM = VM>> {
+) -> Result<(Box<dyn Plan<VM = VM>>, SideMetadataLayout), MMTKError> {
     let plan = match plan {
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_nogc"))]
         PlanSelector::NoGC => Box::new(crate::plan::nogc::NoGC::new(vm_map, mmapper, options))
             as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_semispace"))]
         PlanSelector::SemiSpace => Box::new(crate::plan::semispace::SemiSpace::new(
             vm_map, mmapper, options,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_gencopy"))]
         PlanSelector::GenCopy => Box::new(crate::plan::generational::copying::GenCopy::new(
             vm_map, mmapper, options,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_genimmix"))]
         PlanSelector::GenImmix => Box::new(crate::plan::generational::immix::GenImmix::new(
             vm_map, mmapper, options, scheduler,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_marksweep"))]
         PlanSelector::MarkSweep => Box::new(crate::plan::marksweep::MarkSweep::new(
             vm_map, mmapper, options,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_immix"))]
         PlanSelector::Immix => Box::new(crate::plan::immix::Immix::new(
             vm_map, mmapper, options, scheduler,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_pageprotect"))]
         PlanSelector::PageProtect => Box::new(crate::plan::pageprotect::PageProtect::new(
             vm_map, mmapper, options,
         )) as Box<dyn Plan<VM = VM>>,
+        #[cfg(any(not(feature = "static_plan"), feature = "static_plan_markcompact"))]
         PlanSelector::MarkCompact => Box::new(crate::plan::markcompact::MarkCompact::new(
             vm_map, mmapper, options,
         )) as Box<dyn Plan<VM = VM>>,
+        // The other plans are left out of the binary.
+        #[cfg(feature = "static_plan")]
+        plan => panic!(
+            "MMTk is built with the plan {:?} only, but the plan {:?} is selected",
+            crate::plan::static_plan::DEFAULT_PLAN,
+            plan
+        ),
     };
 
+    // Lay out the side metadata of all the spaces first, so the conflicts are reported with the
+    // whole layout, instead of by the sanity checks of the first space that has a conflict. The
+    // spaces do not map any metadata or insert their ranges to the VM map until the layout is
+    // checked.
+    let mut spaces = vec![];
+    plan.for_each_space(&mut |space| spaces.push((space.get_name(), space.side_metadata())));
+    let layout = SideMetadataLayout::compute(spaces).map_err(MMTKError::SideMetadataConflict)?;
+    debug!("{}", layout);
+
+    plan.verify_side_metadata_sanity();
+
+    plan.for_each_space(&mut |s| s.initialize_address_range());
+    // The VM space is mapped externally by the VM. We need to update our mmapper to mark the range
+    // as mapped.
+    #[cfg(feature = "vm_space")]
+    plan.base().vm_space.ensure_mapped();
+
     // We have created Plan in the heap, and we won't explicitly move it. So each space
     // now has a fixed address for its lifetime. It is safe now to initialize SFT.
-    plan.get_spaces()
-        .into_iter()
-        .for_each(|s| s.initialize_sft());
+    plan.for_each_space(&mut |s| s.initialize_sft());
 
-    plan
+    Ok((plan, layout))
 }
 
 /// Create thread local GC worker.
@@ -108,7 +161,9 @@ pub fn create_gc_worker_context<VM: VMBinding>(
     tls: VMWorkerThread,
     mmtk: &'static MMTK<VM>,
 ) -> GCWorkerCopyContext<VM> {
-    GCWorkerCopyContext::<VM>::new(tls, &*mmtk.plan, mmtk.plan.create_copy_config())
+    let mut config = mmtk.plan.create_copy_config();
+    <VM::VMCollection as Collection<VM>>::customize_copy_config(*mmtk.options.plan, &mut config);
+    GCWorkerCopyContext::<VM>::new(tls, &*mmtk.plan, config)
 }
 
 /// A plan describes the global core functionality for all memory management schemes.
@@ -125,8 +180,9 @@ pub fn create_gc_worker_context<VM: VMBinding>(
 /// 2. Create a vector of all the side metadata specs with `SideMetadataContext::new_global_specs()`,
 ///    the parameter is a vector of global side metadata specs that are specific to the plan.
 /// 3. Initialize all the spaces the plan uses with the heap meta, and the global metadata specs vector.
-/// 4. Create a `SideMetadataSanity` object, and invoke verify_side_metadata_sanity() for each space (or
-///    invoke verify_side_metadata_sanity() in `CommonPlan`/`BasePlan` for the spaces in the common/base plan).
+/// 4. List all the spaces in `for_each_space()`. `create_plan()` uses it to lay out the side
+///    metadata of the spaces, to check it with `SideMetadataSanity`, and then to initialize the
+///    address ranges and the SFT of the spaces.
 ///
 /// Methods in this trait:
 ///
@@ -163,8 +219,26 @@ pub trait Plan: 'static + Sync + Downcast {
         &self.base().options
     }
 
-    /// get all the spaces in the plan
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>>;
+    /// Call `func` for each space in the plan. This is the only place where a plan lists its
+    /// spaces: the operations on all the spaces (e.g. SFT initialization, side metadata sanity
+    /// checks and space statistics) are implemented with it.
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>));
+
+    /// Get all the spaces in the plan.
+    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
+        let mut ret = vec![];
+        self.for_each_space(&mut |space| ret.push(space));
+        ret
+    }
+
+    /// Use SideMetadataSanity to check if the side metadata specs of the spaces are valid. This is
+    /// also needed to check side metadata in extreme_assertions.
+    fn verify_side_metadata_sanity(&self) {
+        let mut side_metadata_sanity_checker = SideMetadataSanity::new();
+        self.for_each_space(&mut |space| {
+            space.verify_side_metadata_sanity(&mut side_metadata_sanity_checker)
+        });
+    }
 
     fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector>;
 
@@ -173,6 +247,12 @@ pub trait Plan: 'static + Sync + Downcast {
         false
     }
 
+    /// Does the current GC copy objects as it traces them? If so, the GC tries to co-locate the
+    /// groups of objects requested by the binding (see [`crate::util::colocation`]).
+    fn current_gc_copies_objects(&self) -> bool {
+        self.constraints().moves_objects && !self.constraints().needs_forward_after_liveness
+    }
+
     #[cfg(feature = "sanity")]
     fn enter_sanity(&self) {
         self.base().inside_sanity.store(true, Ordering::Relaxed)
@@ -210,6 +290,11 @@ pub trait Plan: 'static + Sync + Downcast {
     /// This is invoked once per GC by one worker thread. 'tls' is the worker thread that executes this method.
     fn release(&mut self, tls: VMWorkerThread);
 
+    /// Inform the plan that a GC has finished. This is invoked once per GC by one worker thread,
+    /// after all the GC work is done and before mutators are resumed. Unlike `release()`, the statistics
+    /// of the GC (e.g. the bytes copied by all the workers) are complete at this point.
+    fn end_of_gc(&self, _tls: VMWorkerThread) {}
+
     /// This method is called periodically by the allocation subsystem
     /// (by default, each time a page is consumed), and provides the
     /// collector with an opportunity to collect.
@@ -230,8 +315,9 @@ pub trait Plan: 'static + Sync + Downcast {
                 return false;
             }*/
             self.log_poll(space, "Triggering collection");
-            self.base().gc_requester.request();
-            return true;
+            // The request is refused if the VM is shutting down. The allocation then proceeds
+            // without a GC, as if the collection is disabled.
+            return self.base().gc_requester.request();
         }
 
         // FIXME
@@ -275,7 +361,28 @@ pub trait Plan: 'static + Sync + Downcast {
     /// Get the number of pages that are reserved, including used pages and pages that will
     /// be used (e.g. for copying).
     fn get_reserved_pages(&self) -> usize {
-        self.get_used_pages() + self.get_collection_reserved_pages()
+        self.get_used_pages()
+            + self.get_collection_reserved_pages()
+            + self.get_space_reservation_pages()
+    }
+
+    /// Get the number of pages that are reserved for specific spaces (by the `space_reservations` option)
+    /// but not yet used by those spaces. Other spaces cannot use those pages.
+    fn get_space_reservation_pages(&self) -> usize {
+        let reservations = &self.base().options.space_reservations;
+        if reservations.is_empty() {
+            return 0;
+        }
+        let total_pages = self.get_total_pages();
+        let mut pages = 0;
+        self.for_each_space(&mut |space| {
+            if let Some(size) = reservations.get(space.get_name()) {
+                pages += size
+                    .to_pages(total_pages)
+                    .saturating_sub(space.reserved_pages());
+            }
+        });
+        pages
     }
 
     /// Get the total number of pages for the heap.
@@ -343,6 +450,26 @@ pub trait Plan: 'static + Sync + Downcast {
     /// Force the next collection to be full heap.
     fn force_full_heap_collection(&self) {}
 
+    /// Can the plan collect only the spaces in the scope, without tracing the other spaces (a
+    /// partial-heap collection)? The plan must remember the references from the other spaces into
+    /// the scope for this. If this returns true, the next GC after
+    /// `BasePlan::handle_user_partial_collection_request()` should only collect the scope. It may
+    /// still collect more spaces if it has to, e.g. if a GC in the scope cannot free enough memory.
+    fn supports_partial_collection(&self, _scope: &CollectionScope) -> bool {
+        false
+    }
+
+    /// The generation of an object, or `None` if this is not a generational plan.
+    fn object_generation(&self, _object: ObjectReference) -> Option<ObjectGeneration> {
+        None
+    }
+
+    /// Replace the policy that decides when young objects are promoted. This returns false if this
+    /// is not a generational plan.
+    fn set_tenuring_policy(&self, _policy: Box<dyn TenuringPolicy>) -> bool {
+        false
+    }
+
     fn modify_check(&self, object: ObjectReference) {
         assert!(
             !(self.base().gc_in_progress_proper() && object.is_movable()),
@@ -377,6 +504,8 @@ pub struct BasePlan<VM: VMBinding> {
     pub user_triggered_collection: AtomicBool,
     pub internal_triggered_collection: AtomicBool,
     pub last_internal_triggered_collection: AtomicBool,
+    /// Has a full-heap GC been requested with `memory_manager::request_gc_blocking()`?
+    pub full_heap_collection_requested: AtomicBool,
     // Has an allocation succeeded since the emergency collection?
     pub allocation_success: AtomicBool,
     // Maximum number of failed attempts by a single thread
@@ -385,6 +514,22 @@ pub struct BasePlan<VM: VMBinding> {
     pub cur_collection_attempts: AtomicUsize,
     pub gc_requester: Arc<GCRequester<VM>>,
     pub stats: Stats,
+    /// Cumulative GC statistics that are always collected.
+    pub gc_stats: CumulativeGCStats,
+    /// The named measurement windows of the GC statistics.
+    pub stats_windows: StatsWindows,
+    /// The occupancy of the spaces at the end of each GC, if the option `heap_timeline` is set.
+    pub heap_timeline: HeapTimeline,
+    /// The number of times a GC thread waited for another thread to forward an object.
+    pub forwarding_lost_races: Arc<Mutex<EventCounter>>,
+    /// The number of times a GC thread yielded while waiting for another thread to forward an object.
+    pub forwarding_yields: Arc<Mutex<EventCounter>>,
+    /// Exports the object graph traced by GCs.
+    #[cfg(feature = "graph_export")]
+    pub graph_exporter: crate::util::graph_export::GraphExporter,
+    /// Compresses the cold chunks, if the option `chunk_compression_gcs` is set.
+    #[cfg(feature = "chunk_compression")]
+    pub chunk_compression: Option<Box<crate::util::heap::chunk_compression::ChunkCompression>>,
     mmapper: &'static Mmapper,
     pub vm_map: &'static VMMap,
     pub options: Arc<Options>,
@@ -395,6 +540,14 @@ pub struct BasePlan<VM: VMBinding> {
     scanned_stacks: AtomicUsize,
     /// Have we scanned all the stacks?
     stacks_prepared: AtomicBool,
+    /// When did the current GC start? This is `None` if we are not in a GC.
+    gc_start_time: Mutex<Option<Instant>>,
+    /// The spaces that the binding asked the next GC to collect, or `None` if the next GC is not
+    /// a partial-heap collection. This is cleared at the end of the GC.
+    collection_scope: Mutex<Option<Arc<CollectionScope>>>,
+    /// The reserved pages at the end of the last GC of each space that has a growth trigger
+    /// (set by the `space_growth_triggers` option).
+    space_pages_after_gc: Vec<(String, AtomicUsize)>,
     pub mutator_iterator_lock: Mutex<()>,
     /// A counter that keeps tracks of the number of bytes allocated since last stress test
     allocation_bytes: AtomicUsize,
@@ -404,6 +557,10 @@ pub struct BasePlan<VM: VMBinding> {
     /// Wrapper around analysis counters
     #[cfg(feature = "analysis")]
     pub analysis_manager: AnalysisManager<VM>,
+    /// The GC work that mutators perform on the allocation slow path during a concurrent phase.
+    pub mutator_assist: MutatorAssist,
+    /// Are new objects allocated as marked (black)? See `set_black_allocation()`.
+    black_allocation: AtomicBool,
 
     // Spaces in base plan
     #[cfg(feature = "code_space")]
@@ -415,6 +572,10 @@ pub struct BasePlan<VM: VMBinding> {
     #[cfg(feature = "ro_space")]
     #[trace]
     pub ro_space: ImmortalSpace<VM>,
+    /// The raw memory that the binding allocates for its own metadata with
+    /// `memory_manager::alloc_raw()`. This space is not traced.
+    #[cfg(feature = "raw_memory_space")]
+    pub raw_memory_space: RawMemorySpace<VM>,
 
     /// A VM space is a space allocated and populated by the VM.  Currently it is used by JikesRVM
     /// for boot image.
@@ -450,7 +611,7 @@ pub fn create_vm_space<VM: VMBinding>(
     use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
     let boot_segment_mb = raw_align_up(boot_segment_bytes, BYTES_IN_CHUNK) >> LOG_BYTES_IN_MBYTE;
 
-    let space = ImmortalSpace::new(
+    ImmortalSpace::new(
         "boot",
         false,
         VMRequest::fixed_size(boot_segment_mb),
@@ -459,12 +620,7 @@ pub fn create_vm_space<VM: VMBinding>(
         mmapper,
         heap,
         constraints,
-    );
-
-    // The space is mapped externally by the VM. We need to update our mmapper to mark the range as mapped.
-    space.ensure_mapped();
-
-    space
+    )
 }
 
 impl<VM: VMBinding> BasePlan<VM> {
@@ -480,9 +636,21 @@ impl<VM: VMBinding> BasePlan<VM> {
         global_side_metadata_specs: Vec<SideMetadataSpec>,
     ) -> BasePlan<VM> {
         let stats = Stats::new(&options);
+        let forwarding_lost_races = stats.new_event_counter("forwardingLostRaces", true, true);
+        let forwarding_yields = stats.new_event_counter("forwardingYields", true, true);
         // Initializing the analysis manager and routines
         #[cfg(feature = "analysis")]
         let analysis_manager = AnalysisManager::new(&stats);
+        let space_pages_after_gc = options
+            .space_growth_triggers
+            .sizes
+            .iter()
+            .map(|(name, _)| (name.clone(), AtomicUsize::new(0)))
+            .collect();
+        #[cfg(feature = "chunk_compression")]
+        let chunk_compression = (*options.chunk_compression_gcs > 0
+            && !matches!(*options.plan, PlanSelector::PageProtect))
+        .then(crate::util::heap::chunk_compression::ChunkCompression::new);
         BasePlan {
             #[cfg(feature = "code_space")]
             code_space: ImmortalSpace::new(
@@ -517,6 +685,16 @@ impl<VM: VMBinding> BasePlan<VM> {
                 &mut heap,
                 constraints,
             ),
+            #[cfg(feature = "raw_memory_space")]
+            raw_memory_space: RawMemorySpace::new(
+                "raw_memory_space",
+                VMRequest::discontiguous(),
+                global_side_metadata_specs.clone(),
+                vm_map,
+                mmapper,
+                &mut heap,
+                constraints,
+            ),
             #[cfg(feature = "vm_space")]
             vm_space: create_vm_space(
                 vm_map,
@@ -532,15 +710,28 @@ impl<VM: VMBinding> BasePlan<VM> {
             gc_status: Mutex::new(GcStatus::NotInGC),
             last_stress_pages: AtomicUsize::new(0),
             stacks_prepared: AtomicBool::new(false),
+            gc_start_time: Mutex::new(None),
+            collection_scope: Mutex::new(None),
+            space_pages_after_gc,
             emergency_collection: AtomicBool::new(false),
             user_triggered_collection: AtomicBool::new(false),
             internal_triggered_collection: AtomicBool::new(false),
             last_internal_triggered_collection: AtomicBool::new(false),
+            full_heap_collection_requested: AtomicBool::new(false),
             allocation_success: AtomicBool::new(false),
             max_collection_attempts: AtomicUsize::new(0),
             cur_collection_attempts: AtomicUsize::new(0),
             gc_requester: Arc::new(GCRequester::new()),
             stats,
+            gc_stats: CumulativeGCStats::new(*options.count_traced_bytes),
+            stats_windows: StatsWindows::default(),
+            heap_timeline: HeapTimeline::new(*options.heap_timeline_gcs),
+            forwarding_lost_races,
+            forwarding_yields,
+            #[cfg(feature = "graph_export")]
+            graph_exporter: Default::default(),
+            #[cfg(feature = "chunk_compression")]
+            chunk_compression,
             mmapper,
             heap,
             vm_map,
@@ -554,36 +745,184 @@ impl<VM: VMBinding> BasePlan<VM> {
             malloc_bytes: AtomicUsize::new(0),
             #[cfg(feature = "analysis")]
             analysis_manager,
+            mutator_assist: MutatorAssist::new(),
+            black_allocation: AtomicBool::new(false),
         }
     }
 
-    pub fn get_spaces(&self) -> Vec<&dyn Space<VM>> {
-        vec![
-            #[cfg(feature = "code_space")]
-            &self.code_space,
-            #[cfg(feature = "code_space")]
-            &self.code_lo_space,
-            #[cfg(feature = "ro_space")]
-            &self.ro_space,
-            #[cfg(feature = "vm_space")]
-            &self.vm_space,
-        ]
+    #[allow(unused_variables)] // depending on the enabled features, func may not be used.
+    pub fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<VM>)) {
+        #[cfg(feature = "code_space")]
+        func(&self.code_space);
+        #[cfg(feature = "code_space")]
+        func(&self.code_lo_space);
+        #[cfg(feature = "ro_space")]
+        func(&self.ro_space);
+        #[cfg(feature = "raw_memory_space")]
+        func(&self.raw_memory_space);
+        #[cfg(feature = "vm_space")]
+        func(&self.vm_space);
     }
 
     /// The application code has requested a collection.
     pub fn handle_user_collection_request(&self, tls: VMMutatorThread, force: bool) {
-        if force || !*self.options.ignore_system_gc {
+        if force || !self.options.ignore_system_gc.get() {
             info!("User triggering collection");
             self.user_triggered_collection
                 .store(true, Ordering::Relaxed);
-            self.gc_requester.request();
+            if self.gc_requester.request() {
+                self.block_for_gc(tls);
+            }
+        }
+    }
+
+    /// The application code has requested a collection of only the spaces in the scope. The plan
+    /// must support the scope (see `Plan::supports_partial_collection()`). Return false if the
+    /// request is ignored because of the option `ignore_system_gc`.
+    pub fn handle_user_partial_collection_request(
+        &self,
+        tls: VMMutatorThread,
+        scope: CollectionScope,
+    ) -> bool {
+        if self.options.ignore_system_gc.get() {
+            return false;
+        }
+        info!("User triggering collection of {:?}", scope.names());
+        *self.collection_scope.lock().unwrap() = Some(Arc::new(scope));
+        self.user_triggered_collection
+            .store(true, Ordering::Relaxed);
+        if self.gc_requester.request() {
+            self.block_for_gc(tls);
+        }
+        true
+    }
+
+    /// The spaces that the current (or the next) GC collects, if it is a partial-heap collection.
+    pub fn collection_scope(&self) -> Option<Arc<CollectionScope>> {
+        self.collection_scope.lock().unwrap().clone()
+    }
+
+    /// The plan collects the whole heap instead of the requested scope.
+    pub fn clear_collection_scope(&self) {
+        *self.collection_scope.lock().unwrap() = None;
+    }
+
+    /// A thread that is not a mutator has requested a GC, and waits until the GC is finished. See
+    /// `memory_manager::request_gc_blocking()`.
+    pub fn request_gc_blocking(&self, kind: GCKind) -> Result<(), BlockingGCError> {
+        if !self.initialized.load(Ordering::SeqCst) {
+            return Err(BlockingGCError::NotInitialized);
+        }
+        if self.options.is_single_threaded() {
+            return Err(BlockingGCError::NoGCThreads);
+        }
+        info!("Blocking collection of {:?} requested", kind);
+        loop {
+            match kind {
+                GCKind::Default => {
+                    self.last_internal_triggered_collection
+                        .store(true, Ordering::Relaxed);
+                    self.internal_triggered_collection
+                        .store(true, Ordering::Relaxed);
+                }
+                GCKind::FullHeap => {
+                    self.user_triggered_collection
+                        .store(true, Ordering::Relaxed);
+                    self.full_heap_collection_requested
+                        .store(true, Ordering::Relaxed);
+                }
+            }
+            let request = self
+                .gc_requester
+                .request_with_id()
+                .ok_or(BlockingGCError::ShuttingDown)?;
+            let full_heap = self.gc_requester.wait_for_gc(request)?;
+            // A GC that has decided to collect only the nursery before the request arrived, but
+            // has not stopped the mutators yet, serves the request too. Request another GC then.
+            if kind == GCKind::Default || full_heap {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Block the mutator for the requested GC with `Collection::block_for_gc()`. If there are no
+    /// GC threads, the GC is done on the current thread instead, and the binding is not called,
+    /// unless another mutator is doing a GC on its thread.
+    pub fn block_for_gc(&self, tls: VMMutatorThread) {
+        if self.options.is_single_threaded() {
+            while !self
+                .gc_requester
+                .try_collect_on_current_thread(VMWorkerThread(tls.0))
+            {
+                // Another mutator is doing a GC. Once the GC has started, it stops this mutator,
+                // so block at a safepoint of the VM, as with GC threads. Otherwise, the GC has
+                // not started yet, or it has finished and we can take the controller.
+                if self.gc_in_progress() {
+                    self.gc_requester.block_mutator_for_request(tls);
+                    VM::VMCollection::block_for_gc(tls);
+                    return;
+                }
+                std::thread::yield_now();
+            }
+        } else {
+            self.gc_requester.block_mutator_for_request(tls);
             VM::VMCollection::block_for_gc(tls);
         }
     }
 
-    /// MMTK has requested stop-the-world activity (e.g., stw within a concurrent gc).
-    // This is not used, as we do not have a concurrent plan.
-    #[allow(unused)]
+    /// Has the space reserved more pages than its maximum size (set by the `space_max_sizes` option)?
+    pub fn is_space_over_limit(&self, space: &dyn Space<VM>) -> bool {
+        match self.options.space_max_sizes.get(space.get_name()) {
+            Some(size) => space.reserved_pages() > size.to_pages(self.heap.get_total_pages()),
+            None => false,
+        }
+    }
+
+    /// Allocate new objects as marked (black) or not. A concurrent plan enables this while it is
+    /// marking, so the objects allocated by mutators in the meantime survive the GC without being
+    /// traced, and disables it once marking is done. The spaces that mutators allocate into must
+    /// support it (see `SFT::mark_allocated_object()`).
+    pub fn set_black_allocation(&self, enabled: bool) {
+        self.black_allocation.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Are new objects allocated as marked (black)?
+    #[inline(always)]
+    pub fn is_black_allocation(&self) -> bool {
+        self.black_allocation.load(Ordering::Relaxed)
+    }
+
+    /// Has the space grown by more than its growth trigger (set by the `space_growth_triggers`
+    /// option) since the end of the last GC?
+    pub fn is_space_over_growth_trigger(&self, space: &dyn Space<VM>) -> bool {
+        match self.options.space_growth_triggers.get(space.get_name()) {
+            Some(size) => {
+                let pages_after_gc = self
+                    .space_pages_after_gc
+                    .iter()
+                    .find(|(name, _)| name == space.get_name())
+                    .map_or(0, |(_, pages)| pages.load(Ordering::Relaxed));
+                let growth = space.reserved_pages().saturating_sub(pages_after_gc);
+                growth >= size.to_pages(self.heap.get_total_pages())
+            }
+            None => false,
+        }
+    }
+
+    /// A GC has finished. If the space has a growth trigger, record its reserved pages, so the
+    /// growth is measured from now on.
+    pub(crate) fn record_space_pages_after_gc(&self, space: &dyn Space<VM>) {
+        if let Some((_, pages)) = self
+            .space_pages_after_gc
+            .iter()
+            .find(|(name, _)| name == space.get_name())
+        {
+            pages.store(space.reserved_pages(), Ordering::Relaxed);
+        }
+    }
+
+    /// MMTK has requested stop-the-world activity (e.g., stw within a concurrent gc, or
+    /// a GC triggered by memory pressure).
     pub fn trigger_internal_collection_request(&self) {
         self.last_internal_triggered_collection
             .store(true, Ordering::Relaxed);
@@ -602,6 +941,9 @@ impl<VM: VMBinding> BasePlan<VM> {
             .store(false, Ordering::SeqCst);
         self.user_triggered_collection
             .store(false, Ordering::Relaxed);
+        self.full_heap_collection_requested
+            .store(false, Ordering::Relaxed);
+        *self.collection_scope.lock().unwrap() = None;
     }
 
     // Depends on what base spaces we use, unsync may be unused.
@@ -619,6 +961,10 @@ impl<VM: VMBinding> BasePlan<VM> {
         {
             pages += self.ro_space.reserved_pages();
         }
+        #[cfg(feature = "raw_memory_space")]
+        {
+            pages += self.raw_memory_space.reserved_pages();
+        }
 
         // If we need to count malloc'd size as part of our heap, we add it here.
         #[cfg(feature = "malloc_counted_size")]
@@ -709,15 +1055,35 @@ impl<VM: VMBinding> BasePlan<VM> {
         }
     }
 
+    /// Add the forwarding contention of a GC worker to the counters.
+    pub(crate) fn record_forwarding_contention(
+        &self,
+        contention: crate::util::object_forwarding::ForwardingContention,
+    ) {
+        if contention == Default::default() {
+            return;
+        }
+        self.forwarding_lost_races
+            .lock()
+            .unwrap()
+            .inc_by(contention.lost_races as u64);
+        self.forwarding_yields
+            .lock()
+            .unwrap()
+            .inc_by(contention.yields as u64);
+    }
+
     pub fn set_gc_status(&self, s: GcStatus) {
         let mut gc_status = self.gc_status.lock().unwrap();
         if *gc_status == GcStatus::NotInGC {
             self.stacks_prepared.store(false, Ordering::SeqCst);
+            *self.gc_start_time.lock().unwrap() = Some(Instant::now());
             // FIXME stats
             self.stats.start_gc();
         }
         *gc_status = s;
         if *gc_status == GcStatus::NotInGC {
+            *self.gc_start_time.lock().unwrap() = None;
             // FIXME stats
             if self.stats.get_gathering_stats() {
                 self.stats.end_gc();
@@ -725,6 +1091,14 @@ impl<VM: VMBinding> BasePlan<VM> {
         }
     }
 
+    /// How long has the current GC been running? Returns `None` if we are not in a GC.
+    pub fn current_gc_elapsed(&self) -> Option<Duration> {
+        self.gc_start_time
+            .lock()
+            .unwrap()
+            .map(|start| start.elapsed())
+    }
+
     /// Are the stacks scanned?
     pub fn stacks_prepared(&self) -> bool {
         self.stacks_prepared.load(Ordering::SeqCst)
@@ -809,21 +1183,21 @@ impl<VM: VMBinding> BasePlan<VM> {
     /// we should do stress GC.
     pub fn is_stress_test_gc_enabled(&self) -> bool {
         use crate::util::constants::DEFAULT_STRESS_FACTOR;
-        *self.options.stress_factor != DEFAULT_STRESS_FACTOR
-            || *self.options.analysis_factor != DEFAULT_STRESS_FACTOR
+        self.options.stress_factor.get() != DEFAULT_STRESS_FACTOR
+            || self.options.analysis_factor.get() != DEFAULT_STRESS_FACTOR
     }
 
     /// Check if we should do precise stress test. If so, we need to check for stress GCs for every allocation.
     /// Otherwise, we only check in the allocation slow path.
     pub fn is_precise_stress(&self) -> bool {
-        *self.options.precise_stress
+        self.options.precise_stress.get()
     }
 
     /// Check if we should do a stress GC now. If GC is initialized and the allocation bytes exceeds
     /// the stress factor, we should do a stress GC.
     pub fn should_do_stress_gc(&self) -> bool {
         self.initialized.load(Ordering::SeqCst)
-            && (self.allocation_bytes.load(Ordering::SeqCst) > *self.options.stress_factor)
+            && (self.allocation_bytes.load(Ordering::SeqCst) > self.options.stress_factor.get())
     }
 
     pub(super) fn collection_required<P: Plan>(&self, plan: &P, space_full: bool) -> bool {
@@ -832,7 +1206,7 @@ impl<VM: VMBinding> BasePlan<VM> {
             debug!(
                 "Stress GC: allocation_bytes = {}, stress_factor = {}",
                 self.allocation_bytes.load(Ordering::Relaxed),
-                *self.options.stress_factor
+                self.options.stress_factor.get()
             );
             debug!("Doing stress GC");
             self.allocation_bytes.store(0, Ordering::SeqCst);
@@ -850,22 +1224,6 @@ impl<VM: VMBinding> BasePlan<VM> {
         space_full || stress_force_gc || heap_full
     }
 
-    #[allow(unused_variables)] // depending on the enabled features, base may not be used.
-    pub(crate) fn verify_side_metadata_sanity(
-        &self,
-        side_metadata_sanity_checker: &mut SideMetadataSanity,
-    ) {
-        #[cfg(feature = "code_space")]
-        self.code_space
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-        #[cfg(feature = "ro_space")]
-        self.ro_space
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-        #[cfg(feature = "vm_space")]
-        self.vm_space
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-    }
-
     #[cfg(feature = "malloc_counted_size")]
     pub(crate) fn increase_malloc_bytes_by(&self, size: usize) {
         self.malloc_bytes.fetch_add(size, Ordering::SeqCst);
@@ -902,28 +1260,31 @@ impl<VM: VMBinding> CommonPlan<VM> {
         constraints: &'static PlanConstraints,
         global_side_metadata_specs: Vec<SideMetadataSpec>,
     ) -> CommonPlan<VM> {
+        let immortal = ImmortalSpace::new(
+            "immortal",
+            true,
+            VMRequest::discontiguous(),
+            global_side_metadata_specs.clone(),
+            vm_map,
+            mmapper,
+            &mut heap,
+            constraints,
+        );
+        let los = LargeObjectSpace::new(
+            "los",
+            true,
+            VMRequest::discontiguous(),
+            global_side_metadata_specs.clone(),
+            vm_map,
+            mmapper,
+            &mut heap,
+            constraints,
+            false,
+            crate::policy::los_ref_count::is_enabled(&options, constraints),
+        );
         CommonPlan {
-            immortal: ImmortalSpace::new(
-                "immortal",
-                true,
-                VMRequest::discontiguous(),
-                global_side_metadata_specs.clone(),
-                vm_map,
-                mmapper,
-                &mut heap,
-                constraints,
-            ),
-            los: LargeObjectSpace::new(
-                "los",
-                true,
-                VMRequest::discontiguous(),
-                global_side_metadata_specs.clone(),
-                vm_map,
-                mmapper,
-                &mut heap,
-                constraints,
-                false,
-            ),
+            immortal,
+            los,
             base: BasePlan::new(
                 vm_map,
                 mmapper,
@@ -935,11 +1296,10 @@ impl<VM: VMBinding> CommonPlan<VM> {
         }
     }
 
-    pub fn get_spaces(&self) -> Vec<&dyn Space<VM>> {
-        let mut ret = self.base.get_spaces();
-        ret.push(&self.immortal);
-        ret.push(&self.los);
-        ret
+    pub fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<VM>)) {
+        self.base.for_each_space(func);
+        func(&self.immortal);
+        func(&self.los);
     }
 
     pub fn get_used_pages(&self) -> usize {
@@ -986,18 +1346,6 @@ impl<VM: VMBinding> CommonPlan<VM> {
     pub fn get_los(&self) -> &LargeObjectSpace<VM> {
         &self.los
     }
-
-    pub(crate) fn verify_side_metadata_sanity(
-        &self,
-        side_metadata_sanity_checker: &mut SideMetadataSanity,
-    ) {
-        self.base
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-        self.immortal
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-        self.los
-            .verify_side_metadata_sanity(side_metadata_sanity_checker);
-    }
 }
 
 use crate::policy::gc_work::TraceKind;
@@ -1049,4 +1397,13 @@ pub enum AllocationSemantics {
     Code = 3,
     ReadOnly = 4,
     LargeCode = 5,
+    /// The binding expects the object to live long, e.g. an entry of a session cache. Generational
+    /// plans allocate such objects directly in the mature space, so they are not copied out of the
+    /// nursery later. Other plans treat this the same as `Default`. The binding needs to call
+    /// `post_alloc` for such objects even if it implements the post alloc fast-path, so the write
+    /// barrier works for them.
+    LongLived = 6,
+    /// The binding expects the object to die young. Generational plans allocate such objects in
+    /// the nursery, as they do for `Default`. Other plans treat this the same as `Default`.
+    ShortLived = 7,
 }
@@ -15,7 +15,6 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::metadata::side_metadata::SideMetadataContext;
-use crate::util::metadata::side_metadata::SideMetadataSanity;
 use crate::util::options::Options;
 use crate::vm::VMBinding;
 use crate::{policy::immix::ImmixSpace, util::opaque_pointer::VMWorkerThread};
@@ -58,6 +57,10 @@ impl<VM: VMBinding> Plan for Immix<VM> {
         ImmixSpace::<VM>::is_last_gc_exhaustive(self.last_gc_was_defrag.load(Ordering::Relaxed))
     }
 
+    fn current_gc_copies_objects(&self) -> bool {
+        self.immix_space.in_defrag()
+    }
+
     fn constraints(&self) -> &'static PlanConstraints {
         &IMMIX_CONSTRAINTS
     }
@@ -74,10 +77,9 @@ impl<VM: VMBinding> Plan for Immix<VM> {
         }
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.immix_space);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.immix_space);
     }
 
     fn schedule_collection(&'static self, scheduler: &GCWorkScheduler<VM>) {
@@ -88,7 +90,8 @@ impl<VM: VMBinding> Plan for Immix<VM> {
             true,
             self.base().cur_collection_attempts.load(Ordering::SeqCst),
             self.base().is_user_triggered_collection(),
-            *self.base().options.full_heap_system_gc,
+            self.base().options.full_heap_system_gc.get(),
+            self.base().gc_stats.fragmentation_after_full_heap_gc(),
         );
 
         // The blocks are not identical, clippy is wrong. Probably it does not recognize the constant type parameter.
@@ -142,7 +145,7 @@ impl<VM: VMBinding> Immix<VM> {
     ) -> Self {
         let mut heap = HeapMeta::new(&options);
         let global_metadata_specs = SideMetadataContext::new_global_specs(&[]);
-        let immix = Immix {
+        Immix {
             immix_space: ImmixSpace::new(
                 "immix",
                 vm_map,
@@ -150,6 +153,7 @@ impl<VM: VMBinding> Immix<VM> {
                 &mut heap,
                 scheduler,
                 global_metadata_specs.clone(),
+                &IMMIX_CONSTRAINTS,
             ),
             common: CommonPlan::new(
                 vm_map,
@@ -160,18 +164,6 @@ impl<VM: VMBinding> Immix<VM> {
                 global_metadata_specs,
             ),
             last_gc_was_defrag: AtomicBool::new(false),
-        };
-
-        {
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            immix
-                .common
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            immix
-                .immix_space
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
         }
-
-        immix
     }
 }
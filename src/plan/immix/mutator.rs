@@ -46,6 +46,8 @@ lazy_static! {
     pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
         let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
         map[AllocationSemantics::Default] = AllocatorSelector::Immix(0);
+        map[AllocationSemantics::LongLived] = AllocatorSelector::Immix(0);
+        map[AllocationSemantics::ShortLived] = AllocatorSelector::Immix(0);
         map
     };
 }
@@ -72,5 +74,9 @@ pub fn create_immix_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
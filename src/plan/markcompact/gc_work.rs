@@ -55,6 +55,8 @@ impl<VM: VMBinding> GCWork<VM> for UpdateReferences<VM> {
 
         mmtk.scheduler.work_buckets[WorkBucketStage::SecondRoots]
             .add(ScanVMSpecificRoots::<ForwardingProcessEdges<VM>>::new());
+        mmtk.scheduler.work_buckets[WorkBucketStage::SecondRoots]
+            .add(ProcessCodeRoots::<ForwardingProcessEdges<VM>>::new());
     }
 }
 
@@ -71,8 +73,13 @@ pub struct Compact<VM: VMBinding> {
 
 impl<VM: VMBinding> GCWork<VM> for Compact<VM> {
     #[inline]
-    fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
-        self.mc_space.compact();
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+        for index in self.mc_space.prepare_compaction() {
+            worker.add_work(
+                WorkBucketStage::Compact,
+                CompactRegion::new(self.mc_space, index),
+            );
+        }
     }
 }
 
@@ -82,6 +89,31 @@ impl<VM: VMBinding> Compact<VM> {
     }
 }
 
+/// compact the live objects of a compaction region, and create the packets for the regions
+/// that were waiting for this region
+pub struct CompactRegion<VM: VMBinding> {
+    mc_space: &'static MarkCompactSpace<VM>,
+    index: usize,
+}
+
+impl<VM: VMBinding> GCWork<VM> for CompactRegion<VM> {
+    #[inline]
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+        for index in self.mc_space.compact_region(self.index) {
+            worker.add_work(
+                WorkBucketStage::Compact,
+                CompactRegion::new(self.mc_space, index),
+            );
+        }
+    }
+}
+
+impl<VM: VMBinding> CompactRegion<VM> {
+    pub fn new(mc_space: &'static MarkCompactSpace<VM>, index: usize) -> Self {
+        Self { mc_space, index }
+    }
+}
+
 /// Marking trace
 pub type MarkingProcessEdges<VM> = PlanProcessEdges<VM, MarkCompact<VM>, TRACE_KIND_MARK>;
 /// Forwarding trace
@@ -22,7 +22,7 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSanity};
+use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::opaque_pointer::*;
 use crate::util::options::Options;
 use crate::vm::VMBinding;
@@ -46,6 +46,7 @@ pub const MARKCOMPACT_CONSTRAINTS: PlanConstraints = PlanConstraints {
     gc_header_words: 1,
     num_specialized_scans: 2,
     needs_forward_after_liveness: true,
+    supports_pinned_roots: false,
     ..PlanConstraints::default()
 };
 
@@ -56,10 +57,9 @@ impl<VM: VMBinding> Plan for MarkCompact<VM> {
         &MARKCOMPACT_CONSTRAINTS
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.mc_space);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.mc_space);
     }
 
     fn base(&self) -> &BasePlan<VM> {
@@ -199,7 +199,7 @@ impl<VM: VMBinding> MarkCompact<VM> {
             &mut heap,
         );
 
-        let res = MarkCompact {
+        MarkCompact {
             mc_space,
             common: CommonPlan::new(
                 vm_map,
@@ -209,17 +209,7 @@ impl<VM: VMBinding> MarkCompact<VM> {
                 &MARKCOMPACT_CONSTRAINTS,
                 global_metadata_specs,
             ),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-        res.common
-            .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-        res.mc_space
-            .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-
-        res
+        }
     }
 }
 
@@ -22,6 +22,8 @@ lazy_static! {
     pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
         let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
         map[AllocationSemantics::Default] = AllocatorSelector::MarkCompact(0);
+        map[AllocationSemantics::LongLived] = AllocatorSelector::MarkCompact(0);
+        map[AllocationSemantics::ShortLived] = AllocatorSelector::MarkCompact(0);
         map
     };
 }
@@ -48,6 +50,10 @@ pub fn create_markcompact_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
 
@@ -1,26 +1,31 @@
-use crate::policy::mallocspace::metadata::is_chunk_mapped;
-use crate::policy::mallocspace::metadata::is_chunk_marked_unsafe;
+use crate::plan::{CollectionScope, Plan, PlanTraceObject, VectorObjectQueue};
+use crate::policy::gc_work::DEFAULT_TRACE;
 use crate::policy::mallocspace::MallocSpace;
-use crate::scheduler::{GCWork, GCWorker, WorkBucketStage};
-use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
-use crate::util::Address;
+use crate::scheduler::gc_work::{EdgeOf, PlanProcessEdges, ProcessEdgesBase, ScanObjects};
+use crate::scheduler::{GCWork, GCWorker, ProcessEdgesWork, WorkBucketStage};
+use crate::util::heap::regions::Chunk;
+use crate::util::ObjectReference;
+use crate::vm::edge_shape::Edge;
 use crate::vm::VMBinding;
 use crate::MMTK;
+use std::ops::{Deref, DerefMut};
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use super::MarkSweep;
 
 /// Simple work packet that just sweeps a single chunk
 pub struct MSSweepChunk<VM: VMBinding> {
     ms: &'static MallocSpace<VM>,
-    // starting address of a chunk
-    chunk: Address,
+    chunk: Chunk,
+    /// The scope of the GC, if it only collects the objects of a heap.
+    scope: Option<Arc<CollectionScope>>,
 }
 
 impl<VM: VMBinding> GCWork<VM> for MSSweepChunk<VM> {
     #[inline]
     fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
-        self.ms.sweep_chunk(self.chunk);
+        self.ms.sweep_chunk(self.chunk, self.scope.as_deref());
     }
 }
 
@@ -39,21 +44,15 @@ impl<VM: VMBinding> GCWork<VM> for MSSweepChunks<VM> {
     #[inline]
     fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
         let ms = self.plan.ms_space();
-        let mut work_packets: Vec<Box<dyn GCWork<VM>>> = vec![];
-        let mut chunk = unsafe { Address::from_usize(ms.chunk_addr_min.load(Ordering::Relaxed)) }; // XXX: have to use AtomicUsize to represent an Address
-        let end = unsafe { Address::from_usize(ms.chunk_addr_max.load(Ordering::Relaxed)) }
-            + BYTES_IN_CHUNK;
-
-        // Since only a single thread generates the sweep work packets as well as it is a Stop-the-World collector,
-        // we can assume that the chunk mark metadata is not being accessed by anything else and hence we use
-        // non-atomic accesses
-        while chunk < end {
-            if is_chunk_mapped(chunk) && unsafe { is_chunk_marked_unsafe(chunk) } {
-                work_packets.push(Box::new(MSSweepChunk { ms, chunk }));
-            }
-
-            chunk += BYTES_IN_CHUNK;
-        }
+        let scope = self.plan.base().collection_scope();
+        ms.chunk_map.prepare_sweep();
+        let work_packets = ms.chunk_map.generate_sweep_tasks(|chunk| {
+            Box::new(MSSweepChunk {
+                ms,
+                chunk,
+                scope: scope.clone(),
+            })
+        });
 
         debug!("Generated {} sweep work packets", work_packets.len());
         #[cfg(debug_assertions)]
@@ -69,11 +68,107 @@ impl<VM: VMBinding> GCWork<VM> for MSSweepChunks<VM> {
 }
 
 pub struct MSGCWorkContext<VM: VMBinding>(std::marker::PhantomData<VM>);
-use crate::policy::gc_work::DEFAULT_TRACE;
-use crate::scheduler::gc_work::PlanProcessEdges;
 
 impl<VM: VMBinding> crate::scheduler::GCWorkContext for MSGCWorkContext<VM> {
     type VM = VM;
     type PlanType = MarkSweep<VM>;
     type ProcessEdgesWorkType = PlanProcessEdges<Self::VM, MarkSweep<VM>, DEFAULT_TRACE>;
 }
+
+/// The work context of a partial-heap GC, which only collects the LOS, or the objects of a heap.
+pub struct MSPartialGCWorkContext<VM: VMBinding>(std::marker::PhantomData<VM>);
+
+impl<VM: VMBinding> crate::scheduler::GCWorkContext for MSPartialGCWorkContext<VM> {
+    type VM = VM;
+    type PlanType = MarkSweep<VM>;
+    type ProcessEdgesWorkType = MSPartialProcessEdges<VM>;
+}
+
+/// Process edges for a partial-heap GC. Only the objects in the collection scope are traced. The
+/// other objects are not collected, so they are live, and their references into the scope are
+/// found from the remembered set.
+pub struct MSPartialProcessEdges<VM: VMBinding> {
+    plan: &'static MarkSweep<VM>,
+    scope: Arc<CollectionScope>,
+    base: ProcessEdgesBase<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for MSPartialProcessEdges<VM> {
+    type VM = VM;
+    type ScanObjectsWorkType = ScanObjects<Self>;
+
+    fn new(edges: Vec<EdgeOf<Self>>, roots: bool, mmtk: &'static MMTK<VM>) -> Self {
+        let base = ProcessEdgesBase::new(edges, roots, mmtk);
+        let plan = base.plan().downcast_ref::<MarkSweep<VM>>().unwrap();
+        let scope = plan
+            .base()
+            .collection_scope()
+            .expect("A partial-heap GC has no collection scope");
+        Self { plan, scope, base }
+    }
+
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() || !self.scope.includes(object) {
+            return object;
+        }
+        // We cannot borrow `self` twice in a call, so we extract `worker` as a local variable.
+        let worker = self.worker();
+        self.plan.trace_object::<VectorObjectQueue, DEFAULT_TRACE>(
+            &mut self.base.nodes,
+            object,
+            worker,
+        )
+    }
+
+    #[inline]
+    fn process_edge(&mut self, slot: EdgeOf<Self>) {
+        // MarkSweep does not move objects.
+        let object = slot.load();
+        self.trace_object(object);
+    }
+
+    #[inline(always)]
+    fn create_scan_work(&self, nodes: Vec<ObjectReference>, roots: bool) -> ScanObjects<Self> {
+        ScanObjects::<Self>::new(nodes, false, roots)
+    }
+}
+
+impl<VM: VMBinding> Deref for MSPartialProcessEdges<VM> {
+    type Target = ProcessEdgesBase<VM>;
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for MSPartialProcessEdges<VM> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Scan the objects in the remembered cards in a partial-heap GC. This is done in the closure
+/// stage, once the mutators are stopped, so no barrier records more references into the LOS.
+pub struct MSScanRememberedSet<VM: VMBinding> {
+    plan: &'static MarkSweep<VM>,
+}
+
+impl<VM: VMBinding> MSScanRememberedSet<VM> {
+    pub fn new(plan: &'static MarkSweep<VM>) -> Self {
+        Self { plan }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for MSScanRememberedSet<VM> {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let remset = self.plan.remset();
+        // The references into the LOS and into the heaps are in the same remembered set. Scanning
+        // the cards of the references into the scope of the other kind of GC is only extra work.
+        let packets = remset.scan_packets::<MSPartialProcessEdges<VM>>(&remset.regions());
+        debug!(
+            "Generated {} remembered set scanning packets",
+            packets.len()
+        );
+        mmtk.scheduler.work_buckets[WorkBucketStage::Closure].bulk_add(packets);
+    }
+}
@@ -1,12 +1,14 @@
 use crate::plan::global::BasePlan;
 use crate::plan::global::CommonPlan;
 use crate::plan::global::GcStatus;
-use crate::plan::marksweep::gc_work::{MSGCWorkContext, MSSweepChunks};
+use crate::plan::marksweep::gc_work::{
+    MSGCWorkContext, MSPartialGCWorkContext, MSScanRememberedSet, MSSweepChunks,
+};
 use crate::plan::marksweep::mutator::ALLOCATOR_MAPPING;
 use crate::plan::AllocationSemantics;
+use crate::plan::CollectionScope;
 use crate::plan::Plan;
 use crate::plan::PlanConstraints;
-use crate::policy::mallocspace::metadata::ACTIVE_CHUNK_METADATA_SPEC;
 use crate::policy::mallocspace::MallocSpace;
 use crate::policy::space::Space;
 use crate::scheduler::*;
@@ -15,11 +17,14 @@ use crate::util::alloc::allocators::AllocatorSelector;
 use crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::regions::Chunk;
 use crate::util::heap::HeapMeta;
-use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSanity};
+use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::options::Options;
+use crate::util::rememberset::RememberedSet;
 use crate::util::VMWorkerThread;
 use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use enum_map::EnumMap;
@@ -32,6 +37,13 @@ pub struct MarkSweep<VM: VMBinding> {
     common: CommonPlan<VM>,
     #[trace]
     ms: MallocSpace<VM>,
+    /// The references from the other spaces into the LOS, and the references into the objects of
+    /// each heap from outside the heap, if the plan remembers them (see the options
+    /// `remember_los_references` and `remember_heap_references`).
+    remset: RememberedSet<Chunk>,
+    /// Is the current GC a full heap GC? Otherwise, it collects only the LOS or the objects of a
+    /// heap.
+    gc_full_heap: AtomicBool,
 }
 
 pub const MS_CONSTRAINTS: PlanConstraints = PlanConstraints {
@@ -46,17 +58,39 @@ pub const MS_CONSTRAINTS: PlanConstraints = PlanConstraints {
 impl<VM: VMBinding> Plan for MarkSweep<VM> {
     type VM = VM;
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.ms);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.ms);
     }
 
     fn schedule_collection(&'static self, scheduler: &GCWorkScheduler<VM>) {
         self.base().set_collection_kind::<Self>(self);
         self.base().set_gc_status(GcStatus::GcPrepare);
-        scheduler.schedule_common_work::<MSGCWorkContext<VM>>(self);
-        scheduler.work_buckets[WorkBucketStage::Prepare].add(MSSweepChunks::<VM>::new(self));
+        if self.base().collection_scope().is_some() && self.is_emergency_collection() {
+            // A GC of the LOS or of a heap may not free enough memory.
+            self.base().clear_collection_scope();
+        }
+        let scope = self.base().collection_scope();
+        self.gc_full_heap.store(scope.is_none(), Ordering::SeqCst);
+        match scope {
+            None => {
+                scheduler.schedule_common_work::<MSGCWorkContext<VM>>(self);
+                scheduler.work_buckets[WorkBucketStage::Prepare]
+                    .add(MSSweepChunks::<VM>::new(self));
+            }
+            Some(scope) => {
+                // Only the LOS, or the objects of a heap, are collected. The remembered
+                // references into the scope are roots. The malloc space is only swept in a GC of
+                // a heap, which keeps the objects of the other heaps.
+                scheduler.schedule_common_work::<MSPartialGCWorkContext<VM>>(self);
+                scheduler.work_buckets[WorkBucketStage::Closure]
+                    .add(MSScanRememberedSet::new(self));
+                if scope.heap_id().is_some() {
+                    scheduler.work_buckets[WorkBucketStage::Prepare]
+                        .add(MSSweepChunks::<VM>::new(self));
+                }
+            }
+        }
     }
 
     fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
@@ -64,13 +98,39 @@ impl<VM: VMBinding> Plan for MarkSweep<VM> {
     }
 
     fn prepare(&mut self, tls: VMWorkerThread) {
-        self.common.prepare(tls, true);
+        if self.gc_full_heap.load(Ordering::SeqCst) {
+            self.common.prepare(tls, true);
+        } else {
+            // The other spaces are not traced, so they keep their mark states.
+            self.common.los.prepare(true);
+        }
         // Dont need to prepare for MallocSpace
     }
 
     fn release(&mut self, tls: VMWorkerThread) {
         trace!("Marksweep: Release");
-        self.common.release(tls, true);
+        if self.gc_full_heap.load(Ordering::SeqCst) {
+            self.common.release(tls, true);
+        } else {
+            // A GC of a heap keeps the large objects of the other heaps.
+            let scope = self.base().collection_scope().unwrap();
+            self.common.los.keep_out_of_scope(&scope);
+            self.common.los.release(true);
+        }
+    }
+
+    fn last_collection_full_heap(&self) -> bool {
+        self.gc_full_heap.load(Ordering::Relaxed)
+    }
+
+    /// MarkSweep can collect only the LOS if it remembers the references into the LOS, and only
+    /// the objects of a heap if it remembers the references into the heaps.
+    fn supports_partial_collection(&self, scope: &CollectionScope) -> bool {
+        if scope.heap_id().is_some() {
+            self.remembers_heap_references()
+        } else {
+            self.remembers_los_references() && scope.is(&[self.common.los.get_name()])
+        }
     }
 
     fn collection_required(&self, space_full: bool, _space: Option<&dyn Space<Self::VM>>) -> bool {
@@ -99,19 +159,19 @@ impl<VM: VMBinding> MarkSweep<VM> {
         let heap = HeapMeta::new(&options);
         // if global_alloc_bit is enabled, ALLOC_SIDE_METADATA_SPEC will be added to
         // SideMetadataContext by default, so we don't need to add it here.
+        // The chunk map (ACTIVE_CHUNK_METADATA_SPEC) is always added by default.
         #[cfg(feature = "global_alloc_bit")]
-        let global_metadata_specs =
-            SideMetadataContext::new_global_specs(&[ACTIVE_CHUNK_METADATA_SPEC]);
+        let global_metadata_specs = SideMetadataContext::new_global_specs(&[]);
         // if global_alloc_bit is NOT enabled,
         // we need to add ALLOC_SIDE_METADATA_SPEC to SideMetadataContext here.
         #[cfg(not(feature = "global_alloc_bit"))]
-        let global_metadata_specs = SideMetadataContext::new_global_specs(&[
-            ALLOC_SIDE_METADATA_SPEC,
-            ACTIVE_CHUNK_METADATA_SPEC,
-        ]);
+        let global_metadata_specs =
+            SideMetadataContext::new_global_specs(&[ALLOC_SIDE_METADATA_SPEC]);
 
-        let res = MarkSweep {
+        MarkSweep {
             ms: MallocSpace::new(global_metadata_specs.clone()),
+            remset: RememberedSet::new(),
+            gc_full_heap: AtomicBool::new(true),
             common: CommonPlan::new(
                 vm_map,
                 mmapper,
@@ -120,22 +180,27 @@ impl<VM: VMBinding> MarkSweep<VM> {
                 &MS_CONSTRAINTS,
                 global_metadata_specs,
             ),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        {
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            res.common
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            res.ms
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
         }
-
-        res
     }
 
     pub fn ms_space(&self) -> &MallocSpace<VM> {
         &self.ms
     }
+
+    /// Does the plan remember the references into the LOS? The remembered objects are found by
+    /// their alloc bits, which all the spaces only set with the feature `global_alloc_bit`.
+    pub fn remembers_los_references(&self) -> bool {
+        cfg!(feature = "global_alloc_bit") && *self.base().options.remember_los_references
+    }
+
+    /// Does the plan remember the references into the objects of each heap from outside the heap?
+    /// This needs the heap ids, and the alloc bits to find the remembered objects.
+    pub fn remembers_heap_references(&self) -> bool {
+        cfg!(all(feature = "heap_ids", feature = "global_alloc_bit"))
+            && *self.base().options.remember_heap_references
+    }
+
+    pub fn remset(&self) -> &RememberedSet<Chunk> {
+        &self.remset
+    }
 }
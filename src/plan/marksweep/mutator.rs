@@ -1,5 +1,5 @@
 use super::MarkSweep;
-use crate::plan::barriers::NoBarrier;
+use crate::plan::barriers::{Barrier, NoBarrier, RememberedSetBarrier};
 use crate::plan::mutator_context::create_allocator_mapping;
 use crate::plan::mutator_context::create_space_mapping;
 use crate::plan::mutator_context::Mutator;
@@ -30,6 +30,8 @@ lazy_static! {
     pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
         let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
         map[AllocationSemantics::Default] = AllocatorSelector::Malloc(0);
+        map[AllocationSemantics::LongLived] = AllocatorSelector::Malloc(0);
+        map[AllocationSemantics::ShortLived] = AllocatorSelector::Malloc(0);
         map
     };
 }
@@ -49,12 +51,32 @@ pub fn create_ms_mutator<VM: VMBinding>(
         prepare_func: &ms_mutator_prepare,
         release_func: &ms_mutator_release,
     };
+    let barrier: Box<dyn Barrier> =
+        if ms.remembers_los_references() || ms.remembers_heap_references() {
+            let mut barrier = RememberedSetBarrier::new(ms.remset());
+            if ms.remembers_los_references() {
+                barrier = barrier.remember_los(ms.common().get_los());
+            }
+            #[cfg(feature = "heap_ids")]
+            if ms.remembers_heap_references() {
+                let spaces: Vec<&'static dyn crate::policy::space::Space<VM>> =
+                    vec![ms.ms_space(), ms.common().get_los()];
+                barrier = barrier.remember_heaps(spaces);
+            }
+            Box::new(barrier)
+        } else {
+            Box::new(NoBarrier)
+        };
 
     Mutator {
         allocators: Allocators::<VM>::new(mutator_tls, plan, &config.space_mapping),
-        barrier: Box::new(NoBarrier),
+        barrier,
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
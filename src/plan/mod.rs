@@ -15,10 +15,13 @@
 
 mod barriers;
 pub use barriers::Barrier;
+#[cfg(feature = "analysis")]
+pub use barriers::BarrierOutcome;
 pub use barriers::BarrierSelector;
 pub use barriers::BarrierWriteTarget;
 
 pub(crate) mod gc_requester;
+pub use gc_requester::{BlockingGCError, GCKind};
 
 mod global;
 pub(crate) use global::create_gc_worker_context;
@@ -29,6 +32,10 @@ pub(crate) use global::GcStatus;
 pub use global::Plan;
 pub(crate) use global::PlanTraceObject;
 
+pub(crate) mod partial_gc;
+pub(crate) mod static_plan;
+pub use partial_gc::CollectionScope;
+
 mod mutator_context;
 pub use mutator_context::Mutator;
 pub use mutator_context::MutatorContext;
@@ -37,15 +44,28 @@ mod plan_constraints;
 pub use plan_constraints::PlanConstraints;
 pub use plan_constraints::DEFAULT_PLAN_CONSTRAINTS;
 
+pub use generational::global::NurserySurvival;
+pub use generational::tenuring::{
+    AdaptiveTenuring, FixedTenuring, ObjectGeneration, TenuringPolicy,
+};
+
 mod tracing;
 pub use tracing::{ObjectQueue, ObjectsClosure, VectorObjectQueue};
 
+// With a static plan, the code of the other plans is not used.
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod generational;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod immix;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod markcompact;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod marksweep;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod nogc;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod pageprotect;
+#[cfg_attr(feature = "static_plan", allow(dead_code))]
 mod semispace;
 
 // Expose plan constraints as public. Though a binding can get them from plan.constraints(),
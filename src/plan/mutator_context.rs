@@ -3,13 +3,16 @@
 use crate::plan::barriers::Barrier;
 use crate::plan::global::Plan;
 use crate::plan::AllocationSemantics;
+use crate::plan::GcStatus;
 use crate::policy::space::Space;
 use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
 use crate::util::{Address, ObjectReference};
 use crate::util::{VMMutatorThread, VMWorkerThread};
 use crate::vm::VMBinding;
+use crate::vm::{ActivePlan, ObjectModel};
 
 use enum_map::EnumMap;
+use std::sync::atomic::Ordering;
 
 type SpaceMapping<VM> = Vec<(AllocatorSelector, &'static dyn Space<VM>)>;
 
@@ -62,6 +65,42 @@ pub struct Mutator<VM: VMBinding> {
     pub mutator_tls: VMMutatorThread,
     pub plan: &'static dyn Plan<VM = VM>,
     pub config: MutatorConfig<VM>,
+    /// The heap of the objects allocated by this mutator (see `util::heap_id`).
+    #[cfg(feature = "heap_ids")]
+    pub heap_id: u8,
+    /// The write barrier outcomes recorded by this mutator for the barrier profiler.
+    #[cfg(feature = "analysis")]
+    pub(crate) barrier_counts: crate::util::analysis::barrier_profile::BarrierSiteCounts,
+}
+
+impl<VM: VMBinding> Mutator<VM> {
+    /// Prepare the mutator to be destroyed: flush its barrier into the scheduler, give the unused
+    /// thread local buffers of its allocators back to the spaces, and unregister it from the
+    /// binding with `ActivePlan::unregister_mutator()`. This is done while no GC is in progress,
+    /// and a GC cannot start until it is done, so a GC either sees the mutator with all its state,
+    /// or does not see it at all. If a GC is in progress, the current thread is blocked for the GC
+    /// with `Collection::block_for_gc()` first.
+    pub(crate) fn on_destroy(&mut self) {
+        let plan = self.plan;
+        loop {
+            let gc_status = plan.base().gc_status.lock().unwrap();
+            if *gc_status != GcStatus::NotInGC {
+                drop(gc_status);
+                plan.base().block_for_gc(self.mutator_tls);
+                continue;
+            }
+            // Keep MMTk (and `memory_manager::map_mutators`) from iterating the mutators meanwhile.
+            let _guard = plan.base().mutator_iterator_lock.lock().unwrap();
+            self.flush();
+            for &(selector, _) in self.config.space_mapping.iter() {
+                unsafe { self.allocators.get_allocator_mut(selector) }.on_mutator_destroy();
+            }
+            VM::VMActivePlan::unregister_mutator(self);
+            // Withdraw the pending GC request if no one else waits for it.
+            plan.base().gc_requester.on_mutator_exit(self.mutator_tls);
+            return;
+        }
+    }
 }
 
 impl<VM: VMBinding> MutatorContext<VM> for Mutator<VM> {
@@ -94,12 +133,39 @@ impl<VM: VMBinding> MutatorContext<VM> for Mutator<VM> {
         _bytes: usize,
         allocator: AllocationSemantics,
     ) {
-        unsafe {
+        #[cfg(feature = "object_age")]
+        crate::util::object_age::set_birth_epoch(refer, self.plan.base().gc_stats.gc_count());
+        #[cfg(feature = "object_hash")]
+        crate::util::object_hash::clear_hash_state(refer);
+        #[cfg(feature = "object_user_data")]
+        crate::util::object_user_data::clear_user_data(refer, _bytes);
+        #[cfg(feature = "heap_ids")]
+        crate::util::heap_id::set_heap_id(refer, self.heap_id);
+        #[cfg(feature = "immutable_objects")]
+        crate::util::immutable_object::clear_state(refer);
+        let space = unsafe {
             self.allocators
                 .get_allocator_mut(self.config.allocator_mapping[allocator])
         }
-        .get_space()
-        .initialize_object_metadata(refer, true)
+        .get_space();
+        space.initialize_object_metadata(refer, true);
+        if allocator == AllocationSemantics::LongLived && self.plan.constraints().needs_log_bit {
+            // The object is allocated in the mature space, so the barrier needs to remember the
+            // young objects it points to, as it does for promoted objects.
+            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.mark_as_unlogged::<VM>(refer, Ordering::SeqCst);
+        }
+        if self.plan.base().is_black_allocation() {
+            space.mark_allocated_object(refer);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.flush_remembered_sets();
+        #[cfg(feature = "analysis")]
+        self.plan
+            .base()
+            .analysis_manager
+            .flush_barrier_counts(&mut self.barrier_counts);
     }
 
     fn get_tls(&self) -> VMMutatorThread {
@@ -12,7 +12,7 @@ use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 #[allow(unused_imports)]
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSanity};
+use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::opaque_pointer::*;
 use crate::util::options::Options;
 use crate::vm::VMBinding;
@@ -40,12 +40,11 @@ impl<VM: VMBinding> Plan for NoGC<VM> {
         &NOGC_CONSTRAINTS
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.base.get_spaces();
-        ret.push(&self.nogc_space);
-        ret.push(&self.immortal);
-        ret.push(&self.los);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.base.for_each_space(func);
+        func(&self.nogc_space);
+        func(&self.immortal);
+        func(&self.los);
     }
 
     fn collection_required(&self, space_full: bool, _space: Option<&dyn Space<Self::VM>>) -> bool {
@@ -112,7 +111,7 @@ impl<VM: VMBinding> NoGC<VM> {
             &NOGC_CONSTRAINTS,
         );
 
-        let res = NoGC {
+        NoGC {
             nogc_space,
             immortal: ImmortalSpace::new(
                 "immortal",
@@ -142,16 +141,6 @@ impl<VM: VMBinding> NoGC<VM> {
                 &NOGC_CONSTRAINTS,
                 global_specs,
             ),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-        res.base()
-            .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-        res.nogc_space
-            .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-
-        res
+        }
     }
 }
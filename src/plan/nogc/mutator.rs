@@ -27,6 +27,8 @@ lazy_static! {
         if cfg!(feature = "nogc_multi_space") {
             let mut map = create_allocator_mapping(MULTI_SPACE_RESERVED_ALLOCATORS, false);
             map[AllocationSemantics::Default] = AllocatorSelector::BumpPointer(0);
+            map[AllocationSemantics::LongLived] = AllocatorSelector::BumpPointer(0);
+            map[AllocationSemantics::ShortLived] = AllocatorSelector::BumpPointer(0);
             map[AllocationSemantics::Immortal] = AllocatorSelector::BumpPointer(1);
             map[AllocationSemantics::Los] = AllocatorSelector::BumpPointer(2);
             map
@@ -72,5 +74,9 @@ pub fn create_nogc_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
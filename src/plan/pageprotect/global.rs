@@ -42,10 +42,9 @@ impl<VM: VMBinding> Plan for PageProtect<VM> {
         &CONSTRAINTS
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.space);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.space);
     }
 
     fn schedule_collection(&'static self, scheduler: &GCWorkScheduler<VM>) {
@@ -102,7 +101,7 @@ impl<VM: VMBinding> PageProtect<VM> {
         let mut heap = HeapMeta::new(&options);
         let global_metadata_specs = SideMetadataContext::new_global_specs(&[]);
 
-        let ret = PageProtect {
+        PageProtect {
             space: LargeObjectSpace::new(
                 "los",
                 true,
@@ -113,6 +112,7 @@ impl<VM: VMBinding> PageProtect<VM> {
                 &mut heap,
                 &CONSTRAINTS,
                 true,
+                false,
             ),
             common: CommonPlan::new(
                 vm_map,
@@ -122,19 +122,6 @@ impl<VM: VMBinding> PageProtect<VM> {
                 &CONSTRAINTS,
                 global_metadata_specs,
             ),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        {
-            use crate::util::metadata::side_metadata::SideMetadataSanity;
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            ret.common
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            ret.space
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
         }
-
-        ret
     }
 }
@@ -29,6 +29,8 @@ lazy_static! {
     pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
         let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
         map[AllocationSemantics::Default] = AllocatorSelector::LargeObject(0);
+        map[AllocationSemantics::LongLived] = AllocatorSelector::LargeObject(0);
+        map[AllocationSemantics::ShortLived] = AllocatorSelector::LargeObject(0);
         map
     };
 }
@@ -57,5 +59,9 @@ pub fn create_pp_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
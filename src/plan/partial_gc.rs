@@ -0,0 +1,114 @@
+//! Partial-heap collections, which collect some spaces of the heap (e.g. only the nursery) without
+//! tracing the others. The references from the spaces that are not collected into the collected
+//! spaces are the roots of such a GC, so a plan can only offer a partial-heap collection of a
+//! scope if it remembers those references, e.g. with a write barrier that records the modified
+//! objects, which are scanned as roots.
+//!
+//! A binding asks for a partial-heap collection with
+//! [`memory_manager::handle_user_partial_collection_request`](crate::memory_manager::handle_user_partial_collection_request).
+//! The plan says whether it supports the scope with
+//! [`Plan::supports_partial_collection`](crate::plan::Plan::supports_partial_collection), and the
+//! requested scope is kept in the `BasePlan` until the end of the GC. The generational plans
+//! support the scope of the nursery, which is a nursery GC, unless the plan has to collect the
+//! whole heap anyway (e.g. because the heap is full). MarkSweep supports the scope of the LOS if it
+//! remembers the references into the LOS (see the option `remember_los_references`), with a write
+//! barrier and a [`RememberedSet`](crate::util::rememberset::RememberedSet) whose
+//! [`scan_packets`](crate::util::rememberset::RememberedSet::scan_packets) are scheduled as roots.
+//!
+//! With the feature `heap_ids`, a scope may also have only the objects of a heap (see
+//! `util::heap_id`) in its spaces, which the binding asks for with
+//! `memory_manager::handle_user_heap_collection_request()`. The objects of the other heaps are not
+//! collected. MarkSweep supports the scopes of the heaps if it remembers the references into each
+//! heap from outside the heap (see the option `remember_heap_references`), in the same remembered
+//! set.
+//!
+//! A plan that collects the whole heap although a scope is requested clears the scope with
+//! `BasePlan::clear_collection_scope()`, as the objects outside the scope of a partial-heap GC that
+//! is not a nursery GC are regarded as live, e.g. by the reference processors.
+
+use super::Plan;
+use crate::mmtk::SFT_MAP;
+use crate::policy::space::{Space, SFT};
+use crate::util::ObjectReference;
+use crate::vm::VMBinding;
+
+/// The spaces collected by a partial-heap GC.
+#[derive(Clone, Debug)]
+pub struct CollectionScope {
+    names: Vec<&'static str>,
+    /// The scope only has the objects of this heap in its spaces (with the feature `heap_ids`).
+    heap_id: Option<u8>,
+}
+
+impl CollectionScope {
+    /// The scope of the spaces of the plan with the given names. Return `None` if a name is not
+    /// the name of a space of the plan.
+    pub fn from_names<P: Plan + ?Sized>(plan: &P, names: &[&str]) -> Option<Self> {
+        let mut scope = CollectionScope {
+            names: vec![],
+            heap_id: None,
+        };
+        plan.for_each_space(&mut |space| {
+            if names.contains(&space.get_name()) {
+                scope.names.push(space.get_name());
+            }
+        });
+        if scope.names.len() == names.len() {
+            Some(scope)
+        } else {
+            None
+        }
+    }
+
+    /// The scope of the objects of a heap in all the spaces of the plan. The objects of the other
+    /// heaps are not collected.
+    #[cfg(feature = "heap_ids")]
+    pub fn of_heap<P: Plan + ?Sized>(plan: &P, heap_id: u8) -> Self {
+        let mut names = vec![];
+        plan.for_each_space(&mut |space| names.push(space.get_name()));
+        CollectionScope {
+            names,
+            heap_id: Some(heap_id),
+        }
+    }
+
+    /// The heap whose objects are in the scope, if the scope only has the objects of a heap.
+    pub fn heap_id(&self) -> Option<u8> {
+        self.heap_id
+    }
+
+    /// The names of the spaces in the scope, in the order of `Plan::for_each_space()`.
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    /// Is the scope exactly all the objects of the spaces with the given names?
+    pub fn is(&self, names: &[&str]) -> bool {
+        self.heap_id().is_none()
+            && self.names.len() == names.len()
+            && names.iter().all(|name| self.names.contains(name))
+    }
+
+    /// Is the space in the scope? The scope may only have the objects of a heap in the space.
+    pub fn includes_space<VM: VMBinding>(&self, space: &dyn Space<VM>) -> bool {
+        self.names.contains(&space.get_name())
+    }
+
+    /// Is the object in the scope? The space of the object is found in the SFT map, which also
+    /// has the chunks of the malloc space.
+    #[inline(always)]
+    pub fn includes(&self, object: ObjectReference) -> bool {
+        if !object.is_in_any_space() {
+            return false;
+        }
+        let space = SFT_MAP.get(object.to_address()).name();
+        if !self.names.iter().any(|name| *name == space) {
+            return false;
+        }
+        #[cfg(feature = "heap_ids")]
+        if let Some(heap_id) = self.heap_id {
+            return crate::util::heap_id::get_heap_id(object) == heap_id;
+        }
+        true
+    }
+}
@@ -33,6 +33,10 @@ pub struct PlanConstraints {
     /// Some policies do object forwarding after the first liveness transitive closure, such as mark compact.
     /// For plans that use those policies, they should set this as true.
     pub needs_forward_after_liveness: bool,
+    /// Can the plan keep any object in place in any GC? If so, the VM can report pinned roots
+    /// (see `RootsWorkFactory::create_process_pinned_roots_work`). Plans that evacuate whole
+    /// spaces (such as copy spaces and mark-compact spaces) cannot.
+    pub supports_pinned_roots: bool,
 }
 
 impl PlanConstraints {
@@ -49,6 +53,7 @@ impl PlanConstraints {
             generate_gc_trace: false,
             may_trace_duplicate_edges: false,
             needs_forward_after_liveness: false,
+            supports_pinned_roots: true,
             needs_log_bit: false,
             barrier: BarrierSelector::NoBarrier,
         }
@@ -14,7 +14,7 @@ use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSanity};
+use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::opaque_pointer::VMWorkerThread;
 use crate::util::options::Options;
 use crate::{plan::global::BasePlan, vm::VMBinding};
@@ -43,6 +43,7 @@ pub const SS_CONSTRAINTS: PlanConstraints = PlanConstraints {
     num_specialized_scans: 1,
     max_non_los_default_alloc_bytes:
         crate::plan::plan_constraints::MAX_NON_LOS_ALLOC_BYTES_COPYING_PLAN,
+    supports_pinned_roots: false,
     ..PlanConstraints::default()
 };
 
@@ -68,11 +69,10 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
         }
     }
 
-    fn get_spaces(&self) -> Vec<&dyn Space<Self::VM>> {
-        let mut ret = self.common.get_spaces();
-        ret.push(&self.copyspace0);
-        ret.push(&self.copyspace1);
-        ret
+    fn for_each_space<'a>(&'a self, func: &mut dyn FnMut(&'a dyn Space<Self::VM>)) {
+        self.common.for_each_space(func);
+        func(&self.copyspace0);
+        func(&self.copyspace1);
     }
 
     fn schedule_collection(&'static self, scheduler: &GCWorkScheduler<VM>) {
@@ -100,13 +100,18 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
     }
 
     fn prepare_worker(&self, worker: &mut GCWorker<VM>) {
-        unsafe { worker.get_copy_context_mut().copy[0].assume_init_mut() }.rebind(self.tospace());
+        worker
+            .get_copy_context_mut()
+            .rebind_semispaces([&self.copyspace0, &self.copyspace1], self.tospace());
     }
 
     fn release(&mut self, tls: VMWorkerThread) {
         self.common.release(tls, true);
         // release the collected region
         self.fromspace().release();
+        // The from-space is not counted as used until the next GC.
+        self.fromspace().set_refill_zeroed_block_pool(false);
+        self.tospace().set_refill_zeroed_block_pool(true);
     }
 
     fn collection_required(&self, space_full: bool, _space: Option<&dyn Space<Self::VM>>) -> bool {
@@ -135,12 +140,13 @@ impl<VM: VMBinding> SemiSpace<VM> {
         let mut heap = HeapMeta::new(&options);
         let global_metadata_specs = SideMetadataContext::new_global_specs(&[]);
 
-        let res = SemiSpace {
+        SemiSpace {
             hi: AtomicBool::new(false),
             copyspace0: CopySpace::new(
                 "copyspace0",
                 false,
                 true,
+                false,
                 VMRequest::discontiguous(),
                 global_metadata_specs.clone(),
                 vm_map,
@@ -151,6 +157,7 @@ impl<VM: VMBinding> SemiSpace<VM> {
                 "copyspace1",
                 true,
                 true,
+                false,
                 VMRequest::discontiguous(),
                 global_metadata_specs.clone(),
                 vm_map,
@@ -165,21 +172,7 @@ impl<VM: VMBinding> SemiSpace<VM> {
                 &SS_CONSTRAINTS,
                 global_metadata_specs,
             ),
-        };
-
-        // Use SideMetadataSanity to check if each spec is valid. This is also needed for check
-        // side metadata in extreme_assertions.
-        {
-            let mut side_metadata_sanity_checker = SideMetadataSanity::new();
-            res.common
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            res.copyspace0
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
-            res.copyspace1
-                .verify_side_metadata_sanity(&mut side_metadata_sanity_checker);
         }
-
-        res
     }
 
     pub fn tospace(&self) -> &CopySpace<VM> {
@@ -44,6 +44,8 @@ lazy_static! {
     pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationSemantics, AllocatorSelector> = {
         let mut map = create_allocator_mapping(RESERVED_ALLOCATORS, true);
         map[AllocationSemantics::Default] = AllocatorSelector::BumpPointer(0);
+        map[AllocationSemantics::LongLived] = AllocatorSelector::BumpPointer(0);
+        map[AllocationSemantics::ShortLived] = AllocatorSelector::BumpPointer(0);
         map
     };
 }
@@ -70,5 +72,9 @@ pub fn create_ss_mutator<VM: VMBinding>(
         mutator_tls,
         config,
         plan,
+        #[cfg(feature = "heap_ids")]
+        heap_id: 0,
+        #[cfg(feature = "analysis")]
+        barrier_counts: Default::default(),
     }
 }
@@ -0,0 +1,94 @@
+//! Build-time plan selection. A binding that only ever uses one plan can build MMTk with one of
+//! the `static_plan_*` features (e.g. `static_plan_immix`). MMTk then only creates that plan, so
+//! the code of the other plans is never instantiated and is left out of the binary, and the plan
+//! is the default of the option `plan`. The hot paths that call the plan (e.g. `poll()` in the
+//! allocation slow path) use [`devirtualize`] to call it with its static type instead of through
+//! `dyn Plan`, so the calls can be inlined.
+//!
+//! Without a static plan, [`devirtualize`] returns the `dyn Plan` as it is.
+
+use crate::plan::Plan;
+use crate::util::options::PlanSelector;
+use crate::vm::VMBinding;
+
+#[cfg(all(
+    feature = "static_plan",
+    not(any(
+        feature = "static_plan_nogc",
+        feature = "static_plan_semispace",
+        feature = "static_plan_gencopy",
+        feature = "static_plan_genimmix",
+        feature = "static_plan_marksweep",
+        feature = "static_plan_pageprotect",
+        feature = "static_plan_immix",
+        feature = "static_plan_markcompact",
+    ))
+))]
+compile_error!("Enable one of the static_plan_* features instead of static_plan.");
+
+// Count the selected plans, so we can tell if more than one is selected.
+const SELECTED_PLANS: usize = cfg!(feature = "static_plan_nogc") as usize
+    + cfg!(feature = "static_plan_semispace") as usize
+    + cfg!(feature = "static_plan_gencopy") as usize
+    + cfg!(feature = "static_plan_genimmix") as usize
+    + cfg!(feature = "static_plan_marksweep") as usize
+    + cfg!(feature = "static_plan_pageprotect") as usize
+    + cfg!(feature = "static_plan_immix") as usize
+    + cfg!(feature = "static_plan_markcompact") as usize;
+const _: () = assert!(
+    SELECTED_PLANS <= 1,
+    "Only one static_plan_* feature can be enabled."
+);
+
+/// The plan selected at build time.
+#[cfg(feature = "static_plan_nogc")]
+pub type StaticPlan<VM> = crate::plan::nogc::NoGC<VM>;
+#[cfg(feature = "static_plan_semispace")]
+pub type StaticPlan<VM> = crate::plan::semispace::SemiSpace<VM>;
+#[cfg(feature = "static_plan_gencopy")]
+pub type StaticPlan<VM> = crate::plan::generational::copying::GenCopy<VM>;
+#[cfg(feature = "static_plan_genimmix")]
+pub type StaticPlan<VM> = crate::plan::generational::immix::GenImmix<VM>;
+#[cfg(feature = "static_plan_marksweep")]
+pub type StaticPlan<VM> = crate::plan::marksweep::MarkSweep<VM>;
+#[cfg(feature = "static_plan_pageprotect")]
+pub type StaticPlan<VM> = crate::plan::pageprotect::PageProtect<VM>;
+#[cfg(feature = "static_plan_immix")]
+pub type StaticPlan<VM> = crate::plan::immix::Immix<VM>;
+#[cfg(feature = "static_plan_markcompact")]
+pub type StaticPlan<VM> = crate::plan::markcompact::MarkCompact<VM>;
+
+/// The default of the option `plan`: the plan selected at build time, or NoGC.
+pub const DEFAULT_PLAN: PlanSelector = if cfg!(feature = "static_plan_semispace") {
+    PlanSelector::SemiSpace
+} else if cfg!(feature = "static_plan_gencopy") {
+    PlanSelector::GenCopy
+} else if cfg!(feature = "static_plan_genimmix") {
+    PlanSelector::GenImmix
+} else if cfg!(feature = "static_plan_marksweep") {
+    PlanSelector::MarkSweep
+} else if cfg!(feature = "static_plan_pageprotect") {
+    PlanSelector::PageProtect
+} else if cfg!(feature = "static_plan_immix") {
+    PlanSelector::Immix
+} else if cfg!(feature = "static_plan_markcompact") {
+    PlanSelector::MarkCompact
+} else {
+    PlanSelector::NoGC
+};
+
+/// The plan with its static type, so calls to it are dispatched statically.
+#[cfg(feature = "static_plan")]
+#[inline(always)]
+pub fn devirtualize<VM: VMBinding>(plan: &dyn Plan<VM = VM>) -> &StaticPlan<VM> {
+    debug_assert!(plan.downcast_ref::<StaticPlan<VM>>().is_some());
+    // Safety: the only plan that MMTk creates with a static plan is of the type StaticPlan.
+    unsafe { &*(plan as *const dyn Plan<VM = VM> as *const StaticPlan<VM>) }
+}
+
+/// The plan as it is, as there is no static plan.
+#[cfg(not(feature = "static_plan"))]
+#[inline(always)]
+pub fn devirtualize<VM: VMBinding>(plan: &dyn Plan<VM = VM>) -> &dyn Plan<VM = VM> {
+    plan
+}
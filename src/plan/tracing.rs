@@ -64,41 +64,111 @@ impl ObjectQueue for VectorObjectQueue {
 /// A transitive closure visitor to collect all the edges of an object.
 pub struct ObjectsClosure<'a, E: ProcessEdgesWork> {
     buffer: Vec<EdgeOf<E>>,
+    /// The number of edges in the buffer that makes a packet. It is between `E::MIN_CAPACITY` and
+    /// `E::CAPACITY`, and is updated from the scheduler whenever a packet is created.
+    capacity: usize,
     worker: &'a mut GCWorker<E::VM>,
+    /// The object whose edges are visited, and its heap, to check that it does not refer to the
+    /// objects of other heaps (see `util::heap_id`).
+    #[cfg(all(feature = "heap_ids", debug_assertions))]
+    source: Option<(ObjectReference, u8)>,
 }
 
 impl<'a, E: ProcessEdgesWork> ObjectsClosure<'a, E> {
     pub fn new(worker: &'a mut GCWorker<E::VM>) -> Self {
+        let capacity = worker
+            .scheduler()
+            .edge_buffer_capacity(E::MIN_CAPACITY, E::CAPACITY);
         Self {
             buffer: vec![],
+            capacity,
             worker,
+            #[cfg(all(feature = "heap_ids", debug_assertions))]
+            source: None,
         }
     }
 
-    fn flush(&mut self) {
+    /// Check the edges visited from now on as the edges of `object`, which must not refer to the
+    /// objects of other heaps (see `util::heap_id`).
+    #[cfg(all(feature = "heap_ids", debug_assertions))]
+    pub fn set_source(&mut self, object: ObjectReference) {
+        self.source = Some((object, crate::util::heap_id::get_heap_id(object)));
+    }
+
+    #[cfg(all(feature = "heap_ids", debug_assertions))]
+    fn check_edge(&self, edge: &EdgeOf<E>) {
+        use crate::vm::edge_shape::Edge;
+        if let Some((object, heap_id)) = self.source {
+            crate::util::heap_id::check_reference(object, heap_id, edge.load());
+        }
+    }
+
+    /// Prepare the buffer for an object that is expected to have `num_edges` edges (see
+    /// `Scanning::edge_count_hint`). If the edges do not fit in the current packet, the current
+    /// packet is created first, so that the edges of the object are not split over two packets
+    /// unnecessarily.
+    #[inline(always)]
+    pub fn reserve_edges(&mut self, num_edges: usize) {
+        if !self.buffer.is_empty() && self.buffer.len() + num_edges > self.capacity {
+            self.create_packet();
+        }
+        if self.buffer.is_empty() {
+            self.buffer.reserve(usize::min(num_edges, self.capacity));
+        }
+    }
+
+    fn create_packet(&mut self) {
         let mut new_edges = Vec::new();
         mem::swap(&mut new_edges, &mut self.buffer);
+        let scheduler = self.worker.scheduler();
+        scheduler.on_edge_packet_created();
+        self.capacity = scheduler.edge_buffer_capacity(E::MIN_CAPACITY, E::CAPACITY);
         self.worker.add_work(
             WorkBucketStage::Closure,
             E::new(new_edges, false, self.worker.mmtk),
         );
     }
+
+    fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        self.create_packet();
+    }
 }
 
 impl<'a, E: ProcessEdgesWork> EdgeVisitor<EdgeOf<E>> for ObjectsClosure<'a, E> {
     #[inline(always)]
     fn visit_edge(&mut self, slot: EdgeOf<E>) {
-        if self.buffer.is_empty() {
-            self.buffer.reserve(E::CAPACITY);
+        #[cfg(all(feature = "heap_ids", debug_assertions))]
+        self.check_edge(&slot);
+        if self.buffer.capacity() == 0 {
+            self.buffer.reserve(self.capacity);
         }
         self.buffer.push(slot);
-        if self.buffer.len() >= E::CAPACITY {
-            let mut new_edges = Vec::new();
-            mem::swap(&mut new_edges, &mut self.buffer);
-            self.worker.add_work(
-                WorkBucketStage::Closure,
-                E::new(new_edges, false, self.worker.mmtk),
-            );
+        if self.buffer.len() >= self.capacity {
+            self.create_packet();
+        }
+    }
+
+    #[inline(always)]
+    fn visit_edges(&mut self, edges: &[EdgeOf<E>]) {
+        #[cfg(all(feature = "heap_ids", debug_assertions))]
+        for edge in edges {
+            self.check_edge(edge);
+        }
+        let mut edges = edges;
+        while !edges.is_empty() {
+            if self.buffer.capacity() == 0 {
+                self.buffer.reserve(self.capacity);
+            }
+            // Fill the current packet with as many edges as it can hold.
+            let len = usize::min(edges.len(), self.capacity.saturating_sub(self.buffer.len()));
+            self.buffer.extend_from_slice(&edges[..len]);
+            edges = &edges[len..];
+            if self.buffer.len() >= self.capacity {
+                self.create_packet();
+            }
         }
     }
 }
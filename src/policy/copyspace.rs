@@ -9,6 +9,7 @@ use crate::util::copy::*;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
 #[cfg(feature = "global_alloc_bit")]
 use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
+use crate::util::heap::zeroed_block_pool::ZeroedBlockPool;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
 use crate::util::heap::{MonotonePageResource, PageResource};
@@ -17,7 +18,7 @@ use crate::util::metadata::{extract_side_metadata, side_metadata, MetadataSpec};
 use crate::util::object_forwarding;
 use crate::util::{Address, ObjectReference};
 use crate::vm::*;
-use libc::{mprotect, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use atomic::Atomic;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 const META_DATA_PAGES_PER_REGION: usize = CARD_META_PAGES_PER_REGION;
@@ -27,6 +28,13 @@ pub struct CopySpace<VM: VMBinding> {
     common: CommonSpace<VM>,
     pr: MonotonePageResource<VM>,
     from_space: AtomicBool,
+    /// Only the objects below this address are in the from-space, if the space is collected in
+    /// part (see `prepare_partial()`). Otherwise this is `Address::MAX`.
+    from_space_limit: Atomic<Address>,
+    /// Should the objects be marked as unlogged when they are allocated? This is used for the
+    /// nursery when it is collected in increments, so the barrier remembers the writes to the
+    /// young objects.
+    unlog_new_objects: bool,
 }
 
 impl<VM: VMBinding> SFT for CopySpace<VM> {
@@ -35,26 +43,39 @@ impl<VM: VMBinding> SFT for CopySpace<VM> {
     }
 
     fn is_live(&self, object: ObjectReference) -> bool {
-        !self.is_from_space() || object_forwarding::is_forwarded::<VM>(object)
+        !self.in_from_space(object) || object_forwarding::is_forwarded::<VM>(object)
     }
 
     fn is_movable(&self) -> bool {
         true
     }
 
+    // All the live objects in a copy space are moved when the space is collected.
+    fn request_relocation(&self, _object: ObjectReference) -> bool {
+        true
+    }
+
+    // The objects are only moved if the space is collected in this GC.
+    fn pin_for_current_gc(&self, object: ObjectReference) -> bool {
+        !self.in_from_space(object)
+    }
+
     #[cfg(feature = "sanity")]
     fn is_sane(&self) -> bool {
         !self.is_from_space()
     }
 
-    fn initialize_object_metadata(&self, _object: ObjectReference, _alloc: bool) {
+    fn initialize_object_metadata(&self, object: ObjectReference, alloc: bool) {
+        if alloc && self.unlog_new_objects {
+            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.mark_as_unlogged::<VM>(object, Ordering::SeqCst);
+        }
         #[cfg(feature = "global_alloc_bit")]
-        crate::util::alloc_bit::set_alloc_bit(_object);
+        crate::util::alloc_bit::set_alloc_bit(object);
     }
 
     #[inline(always)]
     fn get_forwarded_object(&self, object: ObjectReference) -> Option<ObjectReference> {
-        if !self.is_from_space() {
+        if !self.in_from_space(object) {
             return None;
         }
 
@@ -126,22 +147,28 @@ impl<VM: VMBinding> crate::policy::gc_work::PolicyTraceObject<VM> for CopySpace<
 }
 
 impl<VM: VMBinding> CopySpace<VM> {
+    /// Create a copy space. If `track_age` is set, the space holds young objects of a generational
+    /// plan, and keeps their ages in the side metadata (see `Gen::get_age()`).
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &'static str,
         from_space: bool,
         zeroed: bool,
+        track_age: bool,
         vmrequest: VMRequest,
         global_side_metadata_specs: Vec<SideMetadataSpec>,
         vm_map: &'static VMMap,
         mmapper: &'static Mmapper,
         heap: &mut HeapMeta,
     ) -> Self {
-        let local_specs = extract_side_metadata(&[
+        let mut local_specs = extract_side_metadata(&[
             *VM::VMObjectModel::LOCAL_FORWARDING_BITS_SPEC,
             *VM::VMObjectModel::LOCAL_FORWARDING_POINTER_SPEC,
         ]);
-        let common = CommonSpace::new(
+        if track_age {
+            local_specs.push(crate::util::metadata::side_metadata::spec_defs::GEN_OBJECT_AGE);
+        }
+        let mut common = CommonSpace::new(
             SpaceOptions {
                 name,
                 movable: true,
@@ -158,6 +185,12 @@ impl<VM: VMBinding> CopySpace<VM> {
             mmapper,
             heap,
         );
+        if zeroed {
+            // The bump allocators acquire a block at a time.
+            let pool = ZeroedBlockPool::new(BumpAllocator::<VM>::BLOCK_PAGES);
+            pool.set_refillable(!from_space);
+            common.zeroed_block_pool = Some(pool);
+        }
         CopySpace {
             pr: if vmrequest.is_discontiguous() {
                 MonotonePageResource::new_discontiguous(META_DATA_PAGES_PER_REGION, vm_map)
@@ -171,11 +204,29 @@ impl<VM: VMBinding> CopySpace<VM> {
             },
             common,
             from_space: AtomicBool::new(from_space),
+            from_space_limit: Atomic::new(Address::MAX),
+            unlog_new_objects: false,
+        }
+    }
+
+    /// Mark the objects as unlogged when they are allocated. This is only useful in a plan that
+    /// uses the log bit.
+    pub fn set_unlog_new_objects(&mut self) {
+        self.unlog_new_objects = true;
+    }
+
+    /// Set whether the zeroed block pool of the space is refilled after a GC. A plan must only
+    /// refill the pool while it counts the pages of the space as used, i.e. not while the space
+    /// is a from-space that holds no objects until the next GC.
+    pub fn set_refill_zeroed_block_pool(&self, refill: bool) {
+        if let Some(pool) = self.common.zeroed_block_pool.as_ref() {
+            pool.set_refillable(refill);
         }
     }
 
     pub fn prepare(&self, from_space: bool) {
         self.from_space.store(from_space, Ordering::SeqCst);
+        self.from_space_limit.store(Address::MAX, Ordering::Relaxed);
         // Clear the metadata if we are using side forwarding status table. Otherwise
         // objects may inherit forwarding status from the previous GC.
         // TODO: Fix performance.
@@ -190,12 +241,33 @@ impl<VM: VMBinding> CopySpace<VM> {
         }
     }
 
+    /// Prepare to collect the objects below `limit`. The other objects in the space are treated
+    /// like the objects in a to-space: they are neither traced nor moved. The space must be
+    /// contiguous, and its objects must not cross `limit`.
+    pub fn prepare_partial(&self, limit: Address) {
+        debug_assert!(self.common.contiguous);
+        self.prepare(true);
+        self.from_space_limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Finish collecting the objects below the limit given to `prepare_partial()`. Unlike
+    /// `release()`, the pages are kept: the memory of the collected objects is only reclaimed when
+    /// the whole space is released.
+    pub fn release_partial(&self) {
+        self.from_space.store(false, Ordering::SeqCst);
+        self.from_space_limit.store(Address::MAX, Ordering::Relaxed);
+    }
+
     pub fn release(&self) {
         unsafe {
             #[cfg(feature = "global_alloc_bit")]
             self.reset_alloc_bit();
             self.pr.reset();
         }
+        // The blocks in the pool are released with the rest of the space.
+        if let Some(pool) = self.common.zeroed_block_pool.as_ref() {
+            pool.clear();
+        }
         self.common.metadata.reset();
         self.from_space.store(false, Ordering::SeqCst);
     }
@@ -220,6 +292,17 @@ impl<VM: VMBinding> CopySpace<VM> {
         self.from_space.load(Ordering::SeqCst)
     }
 
+    /// Is the object (which is in this space) collected in the current GC?
+    #[inline(always)]
+    pub fn in_from_space(&self, object: ObjectReference) -> bool {
+        self.is_from_space() && object.to_address() < self.from_space_limit.load(Ordering::Relaxed)
+    }
+
+    /// The address below which the pages of the space have been allocated.
+    pub fn cursor(&self) -> Address {
+        self.pr.cursor()
+    }
+
     #[inline(always)]
     pub fn trace_object<Q: ObjectQueue>(
         &self,
@@ -231,7 +314,7 @@ impl<VM: VMBinding> CopySpace<VM> {
         trace!("copyspace.trace_object(, {:?}, {:?})", object, semantics,);
 
         // If this is not from space, we do not need to trace it (the object has been copied to the tosapce)
-        if !self.is_from_space() {
+        if !self.in_from_space(object) {
             // The copy semantics for tospace should be none.
             return object;
         }
@@ -252,8 +335,11 @@ impl<VM: VMBinding> CopySpace<VM> {
         trace!("checking if object is being forwarded");
         if object_forwarding::state_is_forwarded_or_being_forwarded(forwarding_status) {
             trace!("... yes it is");
-            let new_object =
-                object_forwarding::spin_and_get_forwarded_object::<VM>(object, forwarding_status);
+            let new_object = object_forwarding::spin_and_get_forwarded_object::<VM>(
+                object,
+                forwarding_status,
+                &mut worker.get_copy_context_mut().forwarding_contention,
+            );
             trace!("Returning");
             new_object
         } else {
@@ -279,9 +365,8 @@ impl<VM: VMBinding> CopySpace<VM> {
         }
         let start = self.common().start;
         let extent = self.common().extent;
-        unsafe {
-            mprotect(start.to_mut_ptr(), extent, PROT_NONE);
-        }
+        // The protection only helps to catch stale accesses, so a failure is ignored.
+        let _ = crate::util::memory::mprotect(start, extent);
         trace!("Protect {:x} {:x}", start, start + extent);
     }
 
@@ -294,13 +379,8 @@ impl<VM: VMBinding> CopySpace<VM> {
         }
         let start = self.common().start;
         let extent = self.common().extent;
-        unsafe {
-            mprotect(
-                start.to_mut_ptr(),
-                extent,
-                PROT_READ | PROT_WRITE | PROT_EXEC,
-            );
-        }
+        // The protection only helps to catch stale accesses, so a failure is ignored.
+        let _ = crate::util::memory::munprotect(start, extent);
         trace!("Unprotect {:x} {:x}", start, start + extent);
     }
 }
@@ -351,4 +431,10 @@ impl<VM: VMBinding> CopySpaceCopyContext<VM> {
         self.copy_allocator
             .rebind(unsafe { &*{ space as *const _ } });
     }
+
+    /// Does this allocator copy to the space?
+    pub fn copies_to(&self, space: &CopySpace<VM>) -> bool {
+        self.copy_allocator.get_space() as *const dyn Space<VM> as *const ()
+            == space as *const CopySpace<VM> as *const ()
+    }
 }
@@ -1,10 +1,9 @@
-use super::chunk::Chunk;
 use super::defrag::Histogram;
 use super::line::Line;
 use super::ImmixSpace;
 use crate::util::constants::*;
-use crate::util::linear_scan::{Region, RegionIterator};
-use crate::util::metadata::side_metadata::{self, *};
+use crate::util::heap::regions::{Chunk, Region, RegionIterator, RegionState, StatefulRegion};
+use crate::util::metadata::side_metadata::*;
 use crate::util::Address;
 use crate::vm::*;
 use spin::{Mutex, MutexGuard};
@@ -56,6 +55,8 @@ impl From<BlockState> for u8 {
     }
 }
 
+impl RegionState for BlockState {}
+
 impl BlockState {
     /// Test if the block is reuasable.
     pub const fn is_reusable(&self) -> bool {
@@ -87,6 +88,11 @@ impl Region for Block {
     const LOG_BYTES: usize = 15;
 }
 
+impl StatefulRegion for Block {
+    type State = BlockState;
+    const STATE_TABLE: SideMetadataSpec = Self::MARK_TABLE;
+}
+
 impl Block {
     /// Log pages in block
     pub const LOG_PAGES: usize = Self::LOG_BYTES - LOG_BYTES_IN_PAGE as usize;
@@ -105,10 +111,14 @@ impl Block {
     pub const MARK_TABLE: SideMetadataSpec =
         crate::util::metadata::side_metadata::spec_defs::IX_BLOCK_MARK;
 
+    /// Block relocation request table (side)
+    pub const RELOCATION_REQUEST_TABLE: SideMetadataSpec =
+        crate::util::metadata::side_metadata::spec_defs::IX_BLOCK_RELOCATE;
+
     /// Get the chunk containing the block.
     #[inline(always)]
     pub fn chunk(&self) -> Chunk {
-        Chunk::from(Chunk::align(self.0))
+        self.enclosing::<Chunk>()
     }
 
     /// Get the address range of the block's line mark table.
@@ -119,21 +129,6 @@ impl Block {
         MetadataByteArrayRef::<{ Block::LINES }>::new(&Line::MARK_TABLE, self.start(), Self::BYTES)
     }
 
-    /// Get block mark state.
-    #[inline(always)]
-    pub fn get_state(&self) -> BlockState {
-        let byte =
-            side_metadata::load_atomic(&Self::MARK_TABLE, self.start(), Ordering::SeqCst) as u8;
-        byte.into()
-    }
-
-    /// Set block mark state.
-    #[inline(always)]
-    pub fn set_state(&self, state: BlockState) {
-        let state = u8::from(state) as usize;
-        side_metadata::store_atomic(&Self::MARK_TABLE, self.start(), state, Ordering::SeqCst);
-    }
-
     // Defrag byte
 
     const DEFRAG_SOURCE_STATE: u8 = u8::MAX;
@@ -141,9 +136,7 @@ impl Block {
     /// Test if the block is marked for defragmentation.
     #[inline(always)]
     pub fn is_defrag_source(&self) -> bool {
-        let byte =
-            side_metadata::load_atomic(&Self::DEFRAG_STATE_TABLE, self.start(), Ordering::SeqCst)
-                as u8;
+        let byte = self.load_metadata(&Self::DEFRAG_STATE_TABLE, Ordering::SeqCst) as u8;
         debug_assert!(byte == 0 || byte == Self::DEFRAG_SOURCE_STATE);
         byte == Self::DEFRAG_SOURCE_STATE
     }
@@ -152,31 +145,35 @@ impl Block {
     #[inline(always)]
     pub fn set_as_defrag_source(&self, defrag: bool) {
         let byte = if defrag { Self::DEFRAG_SOURCE_STATE } else { 0 };
-        side_metadata::store_atomic(
-            &Self::DEFRAG_STATE_TABLE,
-            self.start(),
-            byte as usize,
-            Ordering::SeqCst,
-        );
+        self.store_metadata(&Self::DEFRAG_STATE_TABLE, byte as usize, Ordering::SeqCst);
+    }
+
+    /// Request to evacuate the block in the next defrag GC.
+    #[inline(always)]
+    pub fn request_relocation(&self) {
+        self.store_metadata(&Self::RELOCATION_REQUEST_TABLE, 1, Ordering::Relaxed);
+    }
+
+    /// Take the relocation request of the block, if any.
+    #[inline(always)]
+    pub fn take_relocation_request(&self) -> bool {
+        if self.load_metadata(&Self::RELOCATION_REQUEST_TABLE, Ordering::Relaxed) == 0 {
+            return false;
+        }
+        self.store_metadata(&Self::RELOCATION_REQUEST_TABLE, 0, Ordering::Relaxed);
+        true
     }
 
     /// Record the number of holes in the block.
     #[inline(always)]
     pub fn set_holes(&self, holes: usize) {
-        side_metadata::store_atomic(
-            &Self::DEFRAG_STATE_TABLE,
-            self.start(),
-            holes,
-            Ordering::SeqCst,
-        );
+        self.store_metadata(&Self::DEFRAG_STATE_TABLE, holes, Ordering::SeqCst);
     }
 
     /// Get the number of holes.
     #[inline(always)]
     pub fn get_holes(&self) -> usize {
-        let byte =
-            side_metadata::load_atomic(&Self::DEFRAG_STATE_TABLE, self.start(), Ordering::SeqCst)
-                as u8;
+        let byte = self.load_metadata(&Self::DEFRAG_STATE_TABLE, Ordering::SeqCst) as u8;
         debug_assert_ne!(byte, Self::DEFRAG_SOURCE_STATE);
         byte as usize
     }
@@ -189,7 +186,8 @@ impl Block {
         } else {
             BlockState::Unmarked
         });
-        side_metadata::store_atomic(&Self::DEFRAG_STATE_TABLE, self.start(), 0, Ordering::SeqCst);
+        self.store_metadata(&Self::DEFRAG_STATE_TABLE, 0, Ordering::SeqCst);
+        self.store_metadata(&Self::RELOCATION_REQUEST_TABLE, 0, Ordering::SeqCst);
     }
 
     /// Deinitalize a block before releasing.
@@ -215,7 +213,7 @@ impl Block {
     #[inline(always)]
     pub fn lines(&self) -> RegionIterator<Line> {
         debug_assert!(!super::BLOCK_ONLY);
-        RegionIterator::<Line>::new(self.start_line(), self.end_line())
+        self.subregions::<Line>()
     }
 
     /// Sweep this block.
@@ -257,6 +255,10 @@ impl Block {
                         holes += 1;
                     }
                     prev_line_is_marked = false;
+                    // The objects in this line are dead. Clear their alloc bits, so the alloc bits
+                    // only describe objects that are not reclaimed yet.
+                    #[cfg(feature = "global_alloc_bit")]
+                    crate::util::alloc_bit::bzero_alloc_bit(line.start(), Line::BYTES);
                 }
             }
 
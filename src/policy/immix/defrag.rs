@@ -4,7 +4,7 @@ use super::{
     ImmixSpace,
 };
 use crate::policy::space::Space;
-use crate::util::linear_scan::Region;
+use crate::util::heap::regions::{Region, StatefulRegion};
 use crate::{util::constants::LOG_BYTES_IN_PAGE, vm::*};
 use spin::Mutex;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
@@ -23,6 +23,9 @@ pub struct Defrag {
     pub defrag_spill_threshold: AtomicUsize,
     /// The number of remaining clean pages in defrag space.
     available_clean_pages_for_defrag: AtomicUsize,
+    /// Is there any relocation request? The requested blocks are recorded in
+    /// `Block::RELOCATION_REQUEST_TABLE`.
+    has_relocation_requests: AtomicBool,
 }
 
 impl Defrag {
@@ -31,6 +34,9 @@ impl Defrag {
     const MIN_SPILL_THRESHOLD: usize = 2;
     const DEFRAG_STRESS: bool = false;
     const DEFRAG_HEADROOM_PERCENT: usize = 2;
+    /// A full heap GC defrags if less than half of the memory used after the last full heap GC
+    /// was in live objects.
+    const DEFRAG_FRAGMENTATION_THRESHOLD: f64 = 0.5;
 
     /// Allocate a new local histogram.
     pub const fn new_histogram(&self) -> Histogram {
@@ -50,6 +56,7 @@ impl Defrag {
     }
 
     /// Determine whether the current GC should do defragmentation.
+    #[allow(clippy::too_many_arguments)]
     pub fn decide_whether_to_defrag(
         &self,
         emergency_collection: bool,
@@ -58,18 +65,36 @@ impl Defrag {
         user_triggered: bool,
         exhausted_reusable_space: bool,
         full_heap_system_gc: bool,
+        fragmentation: Option<f64>,
     ) {
+        let fragmented = fragmentation.map_or(false, |fragmentation| {
+            fragmentation > Self::DEFRAG_FRAGMENTATION_THRESHOLD
+        });
         let in_defrag = super::DEFRAG
             && (emergency_collection
                 || (collection_attempts > 1)
                 || !exhausted_reusable_space
                 || Self::DEFRAG_STRESS
-                || (collect_whole_heap && user_triggered && full_heap_system_gc));
+                || (collect_whole_heap && user_triggered && full_heap_system_gc)
+                || (collect_whole_heap && fragmented)
+                || (collect_whole_heap && self.has_relocation_requests.load(Ordering::Acquire)));
         // println!("Defrag: {}", in_defrag);
         self.in_defrag_collection
             .store(in_defrag, Ordering::Release)
     }
 
+    /// Request to evacuate the block in the next defrag GC.
+    pub fn add_relocation_request(&self, block: Block) {
+        block.request_relocation();
+        self.has_relocation_requests.store(true, Ordering::Release);
+    }
+
+    /// All the pending relocation requests are taken by the blocks in a defrag GC.
+    pub fn clear_relocation_requests(&self) {
+        debug_assert!(self.in_defrag());
+        self.has_relocation_requests.store(false, Ordering::Release);
+    }
+
     /// Get the number of defrag headroom pages.
     pub fn defrag_headroom_pages<VM: VMBinding>(&self, space: &ImmixSpace<VM>) -> usize {
         space.get_page_resource().reserved_pages() * Self::DEFRAG_HEADROOM_PERCENT / 100
@@ -1,22 +1,23 @@
 use super::line::*;
-use super::{
-    block::*,
-    chunk::{Chunk, ChunkMap, ChunkState},
-    defrag::Defrag,
-};
+use super::{block::*, chunk, defrag::Defrag};
+use crate::plan::PlanConstraints;
 use crate::policy::gc_work::TraceKind;
 use crate::policy::space::SpaceOptions;
 use crate::policy::space::*;
 use crate::policy::space::{CommonSpace, Space, SFT};
 use crate::util::copy::*;
+use crate::util::heap::chunk_map::ChunkMap;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
+use crate::util::heap::regions::{
+    Chunk, PageAccountedRegion, Region, RegionIterator, StatefulRegion,
+};
+use crate::util::heap::zeroed_block_pool::ZeroedBlockPool;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::PageResource;
 use crate::util::heap::VMRequest;
-use crate::util::linear_scan::{Region, RegionIterator};
-use crate::util::metadata::side_metadata::{self, *};
+use crate::util::metadata::side_metadata::*;
 use crate::util::metadata::{
-    self, compare_exchange_metadata, load_metadata, store_metadata, MetadataSpec,
+    self, compare_exchange_metadata, load_metadata, mark_bit, store_metadata, MetadataSpec,
 };
 use crate::util::object_forwarding as ForwardingWord;
 use crate::util::{Address, ObjectReference};
@@ -31,7 +32,8 @@ use crate::{
     MMTK,
 };
 use atomic::Ordering;
-use std::sync::{atomic::AtomicU8, Arc};
+use std::collections::HashSet;
+use std::sync::{atomic::AtomicU8, Arc, Mutex};
 
 pub(crate) const TRACE_KIND_FAST: TraceKind = 0;
 pub(crate) const TRACE_KIND_DEFRAG: TraceKind = 1;
@@ -51,8 +53,15 @@ pub struct ImmixSpace<VM: VMBinding> {
     pub(super) defrag: Defrag,
     /// Object mark state
     mark_state: u8,
+    /// The mark bit of the objects in this space (see `ObjectModel::use_side_mark_bit`).
+    mark_bit_spec: MetadataSpec,
     /// Work packet scheduler
     scheduler: Arc<GCWorkScheduler<VM>>,
+    /// Are the log bits of a clean block set in bulk when the block is acquired (see
+    /// `set_unlog_blocks()`)?
+    unlog_blocks: bool,
+    /// The lines pinned in the current GC by `pin_for_current_gc()`.
+    lines_pinned_for_current_gc: Mutex<HashSet<Line>>,
 }
 
 unsafe impl<VM: VMBinding> Sync for ImmixSpace<VM> {}
@@ -69,9 +78,77 @@ impl<VM: VMBinding> SFT for ImmixSpace<VM> {
             self.is_marked(object, self.mark_state) || ForwardingWord::is_forwarded::<VM>(object)
         }
     }
+    fn get_forwarded_object(&self, object: ObjectReference) -> Option<ObjectReference> {
+        // Only objects in defrag source blocks are forwarded. The blocks are released at the end
+        // of the GC, so we never report a forwarding pointer for a freshly allocated object.
+        if super::DEFRAG && ForwardingWord::is_forwarded::<VM>(object) {
+            Some(ForwardingWord::read_forwarding_pointer::<VM>(object))
+        } else {
+            None
+        }
+    }
     fn is_movable(&self) -> bool {
         super::DEFRAG
     }
+    fn request_relocation(&self, object: ObjectReference) -> bool {
+        if !super::DEFRAG || Self::is_pinned(object) {
+            return false;
+        }
+        // The next full heap GC will be a defrag GC, and the block will be a defrag source.
+        self.defrag
+            .add_relocation_request(Block::containing::<VM>(object));
+        true
+    }
+    fn pin_object(&self, object: ObjectReference) -> bool {
+        if !super::DEFRAG {
+            return true;
+        }
+        if super::BLOCK_ONLY {
+            // There are no lines to pin.
+            return false;
+        }
+        // Only the lines of the object are kept in place. The rest of its block can still be
+        // evacuated.
+        for line in Line::lines_for_object::<VM>(object) {
+            line.pin();
+        }
+        true
+    }
+    fn unpin_object(&self, object: ObjectReference) -> bool {
+        if !super::DEFRAG {
+            return true;
+        }
+        if super::BLOCK_ONLY {
+            return false;
+        }
+        for line in Line::lines_for_object::<VM>(object) {
+            line.unpin();
+        }
+        true
+    }
+    fn pin_for_current_gc(&self, object: ObjectReference) -> bool {
+        // Objects are only moved out of defrag source blocks.
+        let block = Block::containing::<VM>(object);
+        if !super::DEFRAG || !block.is_defrag_source() {
+            return true;
+        }
+        if super::BLOCK_ONLY {
+            // There are no lines to pin. The block is no longer a defrag source, so all the
+            // objects in the block are marked in place.
+            block.set_as_defrag_source(false);
+            return true;
+        }
+        // The lines of the object are pinned as with `pin_object()`, so the rest of the block is
+        // still evacuated, and they are unpinned when the space is released. Each line is pinned
+        // once per GC, however many roots point to its objects.
+        let mut lines = self.lines_pinned_for_current_gc.lock().unwrap();
+        for line in Line::lines_for_object::<VM>(object) {
+            if lines.insert(line) {
+                line.pin();
+            }
+        }
+        true
+    }
     #[cfg(feature = "sanity")]
     fn is_sane(&self) -> bool {
         true
@@ -80,6 +157,16 @@ impl<VM: VMBinding> SFT for ImmixSpace<VM> {
         #[cfg(feature = "global_alloc_bit")]
         crate::util::alloc_bit::set_alloc_bit(_object);
     }
+    fn mark_allocated_object(&self, object: ObjectReference) {
+        // Mark the object, and its lines or its block, as if it is traced. The lines are marked
+        // now even if we usually mark lines at scan time, as the object is never scanned.
+        self.attempt_mark(object, self.mark_state);
+        if !super::BLOCK_ONLY {
+            self.mark_lines(object);
+        } else {
+            Block::containing::<VM>(object).set_state(BlockState::Marked);
+        }
+    }
     #[inline(always)]
     fn sft_trace_object(
         &self,
@@ -158,21 +245,24 @@ impl<VM: VMBinding> ImmixSpace<VM> {
     const MARKED_STATE: u8 = 1;
 
     /// Get side metadata specs
-    fn side_metadata_specs() -> Vec<SideMetadataSpec> {
+    fn side_metadata_specs(mark_bit_spec: MetadataSpec) -> Vec<SideMetadataSpec> {
         metadata::extract_side_metadata(&if super::BLOCK_ONLY {
             vec![
                 MetadataSpec::OnSide(Block::DEFRAG_STATE_TABLE),
                 MetadataSpec::OnSide(Block::MARK_TABLE),
-                MetadataSpec::OnSide(ChunkMap::ALLOC_TABLE),
-                *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                MetadataSpec::OnSide(Block::RELOCATION_REQUEST_TABLE),
+                MetadataSpec::OnSide(Chunk::USED_PAGES_TABLE),
+                mark_bit_spec,
             ]
         } else {
             vec![
                 MetadataSpec::OnSide(Line::MARK_TABLE),
+                MetadataSpec::OnSide(Line::PIN_TABLE),
                 MetadataSpec::OnSide(Block::DEFRAG_STATE_TABLE),
                 MetadataSpec::OnSide(Block::MARK_TABLE),
-                MetadataSpec::OnSide(ChunkMap::ALLOC_TABLE),
-                *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                MetadataSpec::OnSide(Block::RELOCATION_REQUEST_TABLE),
+                MetadataSpec::OnSide(Chunk::USED_PAGES_TABLE),
+                mark_bit_spec,
             ]
         })
     }
@@ -184,9 +274,11 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         heap: &mut HeapMeta,
         scheduler: Arc<GCWorkScheduler<VM>>,
         global_side_metadata_specs: Vec<SideMetadataSpec>,
+        constraints: &'static PlanConstraints,
     ) -> Self {
         super::validate_features();
-        let common = CommonSpace::new(
+        let mark_bit_spec = mark_bit::mark_bit_spec_for_space::<VM>(name);
+        let mut common = CommonSpace::new(
             SpaceOptions {
                 name,
                 movable: true,
@@ -195,14 +287,15 @@ impl<VM: VMBinding> ImmixSpace<VM> {
                 vmrequest: VMRequest::discontiguous(),
                 side_metadata_specs: SideMetadataContext {
                     global: global_side_metadata_specs,
-                    local: Self::side_metadata_specs(),
+                    local: Self::side_metadata_specs(mark_bit_spec),
                 },
-                needs_log_bit: false,
+                needs_log_bit: constraints.needs_log_bit,
             },
             vm_map,
             mmapper,
             heap,
         );
+        common.zeroed_block_pool = Some(ZeroedBlockPool::new(Block::PAGES));
         ImmixSpace {
             pr: if common.vmrequest.is_discontiguous() {
                 FreeListPageResource::new_discontiguous(0, vm_map)
@@ -216,10 +309,28 @@ impl<VM: VMBinding> ImmixSpace<VM> {
             reusable_blocks: BlockList::default(),
             defrag: Defrag::default(),
             mark_state: Self::UNMARKED_STATE,
+            mark_bit_spec,
             scheduler,
+            unlog_blocks: false,
+            lines_pinned_for_current_gc: Mutex::new(HashSet::new()),
         }
     }
 
+    /// All the objects in this space are mature, so their log bits are set in bulk for each clean
+    /// block when it is acquired, instead of setting them for each promoted object. This is only
+    /// done if the log bit is in side metadata, and can be updated in bulk. The log bits of the
+    /// young objects copied to this space need to be cleared.
+    pub fn set_unlog_blocks(&mut self) {
+        self.unlog_blocks = self.common.needs_log_bit
+            && VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.supports_bulk_update();
+    }
+
+    /// Are the log bits of the objects in this space set in bulk (see `set_unlog_blocks()`)?
+    #[inline(always)]
+    pub fn unlogs_blocks(&self) -> bool {
+        self.unlog_blocks
+    }
+
     /// Get the number of defrag headroom pages.
     pub fn defrag_headroom_pages(&self) -> usize {
         self.defrag.defrag_headroom_pages(self)
@@ -231,7 +342,8 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         self.defrag.in_defrag()
     }
 
-    /// check if the current GC should do defragmentation.
+    /// check if the current GC should do defragmentation. `fragmentation` is the fraction of the
+    /// used memory that was not in live objects after the last full heap GC, if known.
     pub fn decide_whether_to_defrag(
         &self,
         emergency_collection: bool,
@@ -239,6 +351,7 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         collection_attempts: usize,
         user_triggered_collection: bool,
         full_heap_system_gc: bool,
+        fragmentation: Option<f64>,
     ) -> bool {
         self.defrag.decide_whether_to_defrag(
             emergency_collection,
@@ -247,6 +360,7 @@ impl<VM: VMBinding> ImmixSpace<VM> {
             user_triggered_collection,
             self.reusable_blocks.len() == 0,
             full_heap_system_gc,
+            fragmentation,
         );
         self.defrag.in_defrag()
     }
@@ -257,9 +371,19 @@ impl<VM: VMBinding> ImmixSpace<VM> {
     }
 
     pub fn prepare(&mut self, major_gc: bool) {
+        // A generational plan only prepares and releases this space in full heap GCs, so the
+        // lines pinned in the nursery GCs since the last one are still pinned.
+        self.unpin_lines_pinned_for_gc();
+        // Give the blocks of the zeroed block pool back, so they are not reserved across GCs. The
+        // pool is refilled after the GC.
+        if let Some(pool) = self.common.zeroed_block_pool.as_ref() {
+            for start in pool.drain() {
+                self.pr.release_pages(start);
+            }
+        }
         if major_gc {
             // Update mark_state
-            if VM::VMObjectModel::LOCAL_MARK_BIT_SPEC.is_on_side() {
+            if self.mark_bit_spec.is_on_side() {
                 self.mark_state = Self::MARKED_STATE;
             } else {
                 // For header metadata, we use cyclic mark bits.
@@ -270,7 +394,13 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         // Prepare defrag info
         if super::DEFRAG {
             self.defrag.prepare(self);
+            if self.in_defrag() {
+                // `PrepareBlockState` takes the requests from the blocks.
+                self.defrag.clear_relocation_requests();
+            }
         }
+        // All the chunks need to be swept in this GC.
+        self.chunk_map.prepare_sweep();
         // Prepare each block for GC
         let threshold = self.defrag.defrag_spill_threshold.load(Ordering::Acquire);
         // # Safety: ImmixSpace reference is always valid within this collection cycle.
@@ -314,10 +444,11 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         if !super::BLOCK_ONLY {
             self.reusable_blocks.reset();
         }
+        self.unpin_lines_pinned_for_gc();
         // Sweep chunks and blocks
         // # Safety: ImmixSpace reference is always valid within this collection cycle.
         let space = unsafe { &*(self as *const Self) };
-        let work_packets = self.chunk_map.generate_sweep_tasks(space);
+        let work_packets = chunk::generate_sweep_tasks(space);
         self.scheduler().work_buckets[WorkBucketStage::Release].bulk_add(work_packets);
         if super::DEFRAG {
             self.defrag.release(self);
@@ -325,9 +456,22 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         did_defrag
     }
 
+    /// Unpin the lines pinned by `pin_for_current_gc()`, so they can be evacuated again by the next
+    /// defrag GC.
+    fn unpin_lines_pinned_for_gc(&mut self) {
+        for line in self.lines_pinned_for_current_gc.get_mut().unwrap().drain() {
+            line.unpin();
+        }
+    }
+
     /// Release a block.
     pub fn release_block(&self, block: Block) {
         block.deinit();
+        if self.common.needs_log_bit {
+            // The objects allocated in the block later start logged.
+            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.clear_range(block.start(), Block::BYTES);
+        }
+        block.chunk().release_used_pages(Block::PAGES);
         self.pr.release_pages(block.start());
     }
 
@@ -340,7 +484,13 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         self.defrag.notify_new_clean_block(copy);
         let block = Block::from(block_address);
         block.init(copy);
-        self.chunk_map.set(block.chunk(), ChunkState::Allocated);
+        if self.unlog_blocks {
+            // The objects promoted to the block are unlogged.
+            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC
+                .mark_range_as_unlogged(block.start(), Block::BYTES);
+        }
+        block.chunk().add_used_pages(Block::PAGES);
+        self.chunk_map.set_allocated(block.chunk(), true);
         Some(block)
     }
 
@@ -363,6 +513,36 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         }
     }
 
+    /// Give back a block that a mutator has stopped allocating into before the block is used up,
+    /// e.g. as the mutator is destroyed. The lines before `used_end` are marked unavailable, as
+    /// they may have objects allocated since the last GC, and the block is added to the reusable
+    /// blocks if it has free lines left. The next GC sweeps the block as usual. This must not be
+    /// called during a GC, as the reusable blocks are rebuilt in each GC.
+    pub fn return_block(&self, block: Block, used_end: Address) {
+        if super::BLOCK_ONLY {
+            return;
+        }
+        let unavail_state = self.line_unavail_state.load(Ordering::Acquire);
+        let current_state = self.line_mark_state.load(Ordering::Acquire);
+        let mut unavailable_lines = 0;
+        for line in block.lines() {
+            if line.is_marked(unavail_state) || line.is_marked(current_state) {
+                unavailable_lines += 1;
+            } else if line.start() < used_end {
+                line.mark(unavail_state);
+                unavailable_lines += 1;
+            }
+        }
+        // A block that has not been allocated into at all is left to the next GC, which releases
+        // it, as the block state could not tell it from an unallocated block.
+        if unavailable_lines != 0 && unavailable_lines < Block::LINES {
+            block.set_state(BlockState::Reusable {
+                unavailable_lines: unavailable_lines as _,
+            });
+            self.reusable_blocks.push(block);
+        }
+    }
+
     /// Trace and mark objects without evacuation.
     #[inline(always)]
     pub fn fast_trace_object(
@@ -436,13 +616,16 @@ impl<VM: VMBinding> ImmixSpace<VM> {
             // until the object has been forwarded by the winner. Note that the object may not
             // necessarily get forwarded since Immix opportunistically moves objects.
             #[allow(clippy::let_and_return)]
-            let new_object =
-                ForwardingWord::spin_and_get_forwarded_object::<VM>(object, forwarding_status);
+            let new_object = ForwardingWord::spin_and_get_forwarded_object::<VM>(
+                object,
+                forwarding_status,
+                &mut copy_context.forwarding_contention,
+            );
             #[cfg(debug_assertions)]
             {
                 if new_object == object {
                     debug_assert!(
-                        self.is_marked(object, self.mark_state) || self.defrag.space_exhausted() || Self::is_pinned(object),
+                        self.is_marked(object, self.mark_state) || self.is_copy_space_exhausted() || Self::is_pinned(object),
                         "Forwarded object is the same as original object {} even though it should have been copied",
                         object,
                     );
@@ -461,7 +644,7 @@ impl<VM: VMBinding> ImmixSpace<VM> {
             // We won the forwarding race but the object is already marked so we clear the
             // forwarding status and return the unmoved object
             debug_assert!(
-                self.defrag.space_exhausted() || Self::is_pinned(object),
+                self.is_copy_space_exhausted() || Self::is_pinned(object),
                 "Forwarded object is the same as original object {} even though it should have been copied",
                 object,
             );
@@ -470,7 +653,7 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         } else {
             // We won the forwarding race; actually forward and copy the object if it is not pinned
             // and we have sufficient space in our copy allocator
-            let new_object = if Self::is_pinned(object) || self.defrag.space_exhausted() {
+            let new_object = if Self::is_pinned(object) || self.is_copy_space_exhausted() {
                 self.attempt_mark(object, self.mark_state);
                 ForwardingWord::clear_forwarding_bits::<VM>(object);
                 Block::containing::<VM>(object).set_state(BlockState::Marked);
@@ -490,6 +673,18 @@ impl<VM: VMBinding> ImmixSpace<VM> {
         }
     }
 
+    /// Should we stop evacuating objects in the current defrag GC? This is true if we have used up the
+    /// clean blocks for defrag, or the GC has used up the free pages in the heap (see [`crate::util::gc_stats::CopyReserveUsage`]).
+    /// The objects that are not evacuated yet are marked in place.
+    #[inline(always)]
+    fn is_copy_space_exhausted(&self) -> bool {
+        self.defrag.space_exhausted()
+            || VM::VMActivePlan::global()
+                .base()
+                .gc_stats
+                .is_copy_reserve_exhausted()
+    }
+
     /// Mark all the lines that the given object spans.
     #[allow(clippy::assertions_on_constants)]
     #[inline]
@@ -502,18 +697,15 @@ impl<VM: VMBinding> ImmixSpace<VM> {
     #[inline(always)]
     fn attempt_mark(&self, object: ObjectReference, mark_state: u8) -> bool {
         loop {
-            let old_value = load_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-                object,
-                None,
-                Some(Ordering::SeqCst),
-            ) as u8;
+            let old_value =
+                load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst))
+                    as u8;
             if old_value == mark_state {
                 return false;
             }
 
             if compare_exchange_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                &self.mark_bit_spec,
                 object,
                 old_value as usize,
                 mark_state as usize,
@@ -530,20 +722,19 @@ impl<VM: VMBinding> ImmixSpace<VM> {
     /// Check if an object is marked.
     #[inline(always)]
     fn is_marked(&self, object: ObjectReference, mark_state: u8) -> bool {
-        let old_value = load_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-            object,
-            None,
-            Some(Ordering::SeqCst),
-        ) as u8;
+        let old_value =
+            load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst)) as u8;
         old_value == mark_state
     }
 
-    /// Check if an object is pinned.
+    /// Check if an object is pinned. An object is kept in place if the line of its start is
+    /// pinned, so the objects that share the line with a pinned object are not moved either.
     #[inline(always)]
-    fn is_pinned(_object: ObjectReference) -> bool {
-        // TODO(wenyuzhao): Object pinning not supported yet.
-        false
+    fn is_pinned(object: ObjectReference) -> bool {
+        if super::BLOCK_ONLY {
+            return false;
+        }
+        Line::containing_address(VM::VMObjectModel::object_start_ref(object)).is_pinned()
     }
 
     /// Hole searching.
@@ -609,9 +800,9 @@ pub struct PrepareBlockState<VM: VMBinding> {
 impl<VM: VMBinding> PrepareBlockState<VM> {
     /// Clear object mark table
     #[inline(always)]
-    fn reset_object_mark(chunk: Chunk) {
-        if let MetadataSpec::OnSide(side) = *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC {
-            side_metadata::bzero_metadata(&side, chunk.start(), Chunk::BYTES);
+    fn reset_object_mark(&self) {
+        if let MetadataSpec::OnSide(side) = self.space.mark_bit_spec {
+            self.chunk.bzero_metadata(&side);
         }
     }
 }
@@ -621,16 +812,20 @@ impl<VM: VMBinding> GCWork<VM> for PrepareBlockState<VM> {
     fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
         let defrag_threshold = self.defrag_threshold.unwrap_or(0);
         // Clear object mark table for this chunk
-        Self::reset_object_mark(self.chunk);
+        self.reset_object_mark();
         // Iterate over all blocks in this chunk
-        for block in self.chunk.blocks() {
+        for block in self.chunk.subregions::<Block>() {
             let state = block.get_state();
             // Skip unallocated blocks.
             if state == BlockState::Unallocated {
                 continue;
             }
+            // Evacuate the blocks requested by the binding. Requests wait for a defrag GC.
+            let requested = self.defrag_threshold.is_some() && block.take_relocation_request();
             // Check if this block needs to be defragmented.
-            if super::DEFRAG && defrag_threshold != 0 && block.get_holes() > defrag_threshold {
+            if requested
+                || (super::DEFRAG && defrag_threshold != 0 && block.get_holes() > defrag_threshold)
+            {
                 block.set_as_defrag_source(true);
             } else {
                 block.set_as_defrag_source(false);
@@ -682,16 +877,17 @@ impl<VM: VMBinding> PolicyCopyContext for ImmixCopyContext<VM> {
     #[inline(always)]
     fn post_copy(&mut self, obj: ObjectReference, _bytes: usize) {
         // Mark the object
+        let space = self.get_space();
         store_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+            &space.mark_bit_spec,
             obj,
-            self.get_space().mark_state as usize,
+            space.mark_state as usize,
             None,
             Some(Ordering::SeqCst),
         );
         // Mark the line
         if !super::MARK_LINE_AT_SCAN_TIME {
-            self.get_space().mark_lines(obj);
+            space.mark_lines(obj);
         }
     }
 }
@@ -709,7 +905,7 @@ impl<VM: VMBinding> ImmixCopyContext<VM> {
     }
 
     #[inline(always)]
-    fn get_space(&self) -> &ImmixSpace<VM> {
+    pub(crate) fn get_space(&self) -> &ImmixSpace<VM> {
         // Both copy allocators should point to the same space.
         debug_assert_eq!(
             self.defrag_allocator.immix_space().common().descriptor,
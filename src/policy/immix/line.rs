@@ -1,14 +1,15 @@
 use super::block::Block;
-use crate::util::linear_scan::{Region, RegionIterator};
+use crate::util::heap::regions::{Region, RegionIterator};
 use crate::util::metadata::side_metadata::{self, *};
 use crate::{
     util::{Address, ObjectReference},
     vm::*,
 };
+use std::sync::atomic::Ordering;
 
 /// Data structure to reference a line within an immix block.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq, Hash)]
 pub struct Line(Address);
 
 impl From<Address> for Line {
@@ -41,6 +42,13 @@ impl Line {
     pub const MARK_TABLE: SideMetadataSpec =
         crate::util::metadata::side_metadata::spec_defs::IX_LINE_MARK;
 
+    /// Line pin count table (side)
+    pub const PIN_TABLE: SideMetadataSpec =
+        crate::util::metadata::side_metadata::spec_defs::IX_LINE_PIN;
+
+    /// The maximum number of pins of a line.
+    pub const MAX_PIN_COUNT: u8 = u8::MAX;
+
     /// Get the block containing the line.
     #[inline(always)]
     pub fn block(&self) -> Block {
@@ -71,9 +79,50 @@ impl Line {
         unsafe { side_metadata::load(&Self::MARK_TABLE, self.start()) as u8 == state }
     }
 
-    /// Mark all lines the object is spanned to.
+    /// Record a pin of an object on the line. The count is not changed if it is already at the
+    /// maximum, so it never wraps around.
     #[inline]
-    pub fn mark_lines_for_object<VM: VMBinding>(object: ObjectReference, state: u8) -> usize {
+    pub fn pin(&self) {
+        debug_assert!(!super::BLOCK_ONLY);
+        loop {
+            let old = side_metadata::load_atomic(&Self::PIN_TABLE, self.start(), Ordering::SeqCst);
+            assert!(
+                old < Self::MAX_PIN_COUNT as usize,
+                "Too many pins of the objects on line {:?}",
+                self
+            );
+            if side_metadata::compare_exchange_atomic(
+                &Self::PIN_TABLE,
+                self.start(),
+                old,
+                old + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                return;
+            }
+        }
+    }
+
+    /// Remove a pin of an object on the line.
+    #[inline]
+    pub fn unpin(&self) {
+        debug_assert!(!super::BLOCK_ONLY);
+        let old =
+            side_metadata::fetch_sub_atomic(&Self::PIN_TABLE, self.start(), 1, Ordering::SeqCst);
+        assert!(old != 0, "Line {:?} is not pinned", self);
+    }
+
+    /// Is any object on the line pinned?
+    #[inline(always)]
+    pub fn is_pinned(&self) -> bool {
+        debug_assert!(!super::BLOCK_ONLY);
+        side_metadata::load_atomic(&Self::PIN_TABLE, self.start(), Ordering::SeqCst) != 0
+    }
+
+    /// The lines the object spans.
+    #[inline]
+    pub fn lines_for_object<VM: VMBinding>(object: ObjectReference) -> RegionIterator<Line> {
         debug_assert!(!super::BLOCK_ONLY);
         let start = VM::VMObjectModel::object_start_ref(object);
         let end = start + VM::VMObjectModel::get_current_size(object);
@@ -82,9 +131,15 @@ impl Line {
         if !Line::is_aligned(end) {
             end_line = end_line.next();
         }
+        RegionIterator::<Line>::new(start_line, end_line)
+    }
+
+    /// Mark all lines the object is spanned to.
+    #[inline]
+    pub fn mark_lines_for_object<VM: VMBinding>(object: ObjectReference, state: u8) -> usize {
+        debug_assert!(!super::BLOCK_ONLY);
         let mut marked_lines = 0;
-        let iter = RegionIterator::<Line>::new(start_line, end_line);
-        for line in iter {
+        for line in Self::lines_for_object::<VM>(object) {
             if !line.is_marked(state) {
                 marked_lines += 1;
             }
@@ -93,3 +148,62 @@ impl Line {
         marked_lines
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    fn with_pin_table(f: impl FnOnce(Line) + std::panic::UnwindSafe) {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![Line::PIN_TABLE],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, Block::BYTES)
+                        .unwrap();
+                    f(Line::from(HEAP_START));
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, Block::BYTES);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_pin_count() {
+        with_pin_table(|line| {
+            let next = line.next();
+            assert!(!line.is_pinned());
+            line.pin();
+            line.pin();
+            assert!(line.is_pinned());
+            assert!(!next.is_pinned());
+            line.unpin();
+            assert!(line.is_pinned());
+            line.unpin();
+            assert!(!line.is_pinned());
+        })
+    }
+
+    #[test]
+    fn test_pin_count_does_not_wrap() {
+        with_pin_table(|line| {
+            for _ in 0..Line::MAX_PIN_COUNT {
+                line.pin();
+            }
+            assert!(std::panic::catch_unwind(|| line.pin()).is_err());
+            // The line is still pinned by all the pins before.
+            for _ in 0..Line::MAX_PIN_COUNT {
+                assert!(line.is_pinned());
+                line.unpin();
+            }
+            assert!(!line.is_pinned());
+        })
+    }
+}
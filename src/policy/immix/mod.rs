@@ -7,7 +7,7 @@ pub mod line;
 pub use immixspace::*;
 
 use crate::policy::immix::block::Block;
-use crate::util::linear_scan::Region;
+use crate::util::heap::regions::Region;
 
 /// The max object size for immix: half of a block
 pub const MAX_IMMIX_OBJECT_SIZE: usize = Block::BYTES >> 1;
@@ -5,7 +5,10 @@ use crate::util::address::Address;
 use crate::util::heap::{MonotonePageResource, PageResource, VMRequest};
 
 use crate::util::constants::CARD_META_PAGES_PER_REGION;
-use crate::util::metadata::{compare_exchange_metadata, load_metadata, store_metadata};
+use crate::util::metadata::mark_bit;
+use crate::util::metadata::{
+    compare_exchange_metadata, load_metadata, store_metadata, MetadataSpec,
+};
 use crate::util::{metadata, ObjectReference};
 
 use crate::plan::{ObjectQueue, VectorObjectQueue};
@@ -24,6 +27,8 @@ use crate::vm::{ObjectModel, VMBinding};
 /// actually collect.
 pub struct ImmortalSpace<VM: VMBinding> {
     mark_state: usize,
+    /// The mark bit of the objects in this space (see `ObjectModel::use_side_mark_bit`).
+    mark_bit_spec: MetadataSpec,
     common: CommonSpace<VM>,
     pr: MonotonePageResource<VM>,
 }
@@ -40,12 +45,8 @@ impl<VM: VMBinding> SFT for ImmortalSpace<VM> {
     }
     #[inline(always)]
     fn is_reachable(&self, object: ObjectReference) -> bool {
-        let old_value = load_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-            object,
-            None,
-            Some(Ordering::SeqCst),
-        );
+        let old_value =
+            load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
         old_value == self.mark_state
     }
     fn is_movable(&self) -> bool {
@@ -56,15 +57,11 @@ impl<VM: VMBinding> SFT for ImmortalSpace<VM> {
         true
     }
     fn initialize_object_metadata(&self, object: ObjectReference, _alloc: bool) {
-        let old_value = load_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-            object,
-            None,
-            Some(Ordering::SeqCst),
-        );
+        let old_value =
+            load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
         let new_value = (old_value & GC_MARK_BIT_MASK) | self.mark_state;
         store_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+            &self.mark_bit_spec,
             object,
             new_value,
             None,
@@ -77,6 +74,10 @@ impl<VM: VMBinding> SFT for ImmortalSpace<VM> {
         #[cfg(feature = "global_alloc_bit")]
         crate::util::alloc_bit::set_alloc_bit(object);
     }
+    fn mark_allocated_object(&self, _object: ObjectReference) {
+        // Objects in this space are never reclaimed, and new objects already have the current
+        // mark state (see `initialize_object_metadata()`).
+    }
     #[inline(always)]
     fn sft_trace_object(
         &self,
@@ -143,6 +144,7 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
         heap: &mut HeapMeta,
         constraints: &'static PlanConstraints,
     ) -> Self {
+        let mark_bit_spec = mark_bit::mark_bit_spec_for_space::<VM>(name);
         let common = CommonSpace::new(
             SpaceOptions {
                 name,
@@ -153,9 +155,7 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
                 vmrequest,
                 side_metadata_specs: SideMetadataContext {
                     global: global_side_metadata_specs,
-                    local: metadata::extract_side_metadata(&[
-                        *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-                    ]),
+                    local: metadata::extract_side_metadata(&[mark_bit_spec]),
                 },
             },
             vm_map,
@@ -164,6 +164,7 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
         );
         ImmortalSpace {
             mark_state: 0,
+            mark_bit_spec,
             pr: if vmrequest.is_discontiguous() {
                 MonotonePageResource::new_discontiguous(META_DATA_PAGES_PER_REGION, vm_map)
             } else {
@@ -178,20 +179,16 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
         }
     }
 
-    fn test_and_mark(object: ObjectReference, value: usize) -> bool {
+    fn test_and_mark(&self, object: ObjectReference, value: usize) -> bool {
         loop {
-            let old_value = load_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-                object,
-                None,
-                Some(Ordering::SeqCst),
-            );
+            let old_value =
+                load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
             if old_value == value {
                 return false;
             }
 
             if compare_exchange_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                &self.mark_bit_spec,
                 object,
                 old_value,
                 old_value ^ GC_MARK_BIT_MASK,
@@ -222,7 +219,7 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
             "{:x}: alloc bit not set",
             object
         );
-        if ImmortalSpace::<VM>::test_and_mark(object, self.mark_state) {
+        if self.test_and_mark(object, self.mark_state) {
             queue.enqueue(object);
         }
         object
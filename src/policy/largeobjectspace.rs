@@ -1,12 +1,17 @@
 use atomic::Ordering;
 
+use crate::plan::CollectionScope;
 use crate::plan::ObjectQueue;
 use crate::plan::PlanConstraints;
 use crate::plan::VectorObjectQueue;
+use crate::policy::los_ref_count::{
+    LOSRefCounts, LOS_REF_COUNT_CANDIDATE_SPEC, LOS_REF_COUNT_SPEC,
+};
 use crate::policy::space::SpaceOptions;
 use crate::policy::space::*;
 use crate::policy::space::{CommonSpace, Space, SFT};
-use crate::util::constants::BYTES_IN_PAGE;
+use crate::util::constants::{BYTES_IN_PAGE, LOG_BYTES_IN_PAGE};
+use crate::util::conversions;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
 use crate::util::heap::HeapMeta;
 use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
@@ -36,6 +41,11 @@ pub struct LargeObjectSpace<VM: VMBinding> {
     mark_state: usize,
     in_nursery_gc: bool,
     treadmill: TreadMill,
+    /// The reference counts of the objects, if the option `los_ref_counting` is enabled.
+    ref_counts: Option<LOSRefCounts>,
+    /// Should the objects be marked as unlogged when they are allocated (see
+    /// `set_unlog_new_objects()`)?
+    unlog_new_objects: bool,
 }
 
 impl<VM: VMBinding> SFT for LargeObjectSpace<VM> {
@@ -62,6 +72,9 @@ impl<VM: VMBinding> SFT for LargeObjectSpace<VM> {
         let mut new_value = (old_value & (!LOS_BIT_MASK)) | self.mark_state;
         if alloc {
             new_value |= NURSERY_BIT;
+            if let Some(ref_counts) = self.ref_counts.as_ref() {
+                ref_counts.reset(object);
+            }
         }
         store_metadata::<VM>(
             &VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
@@ -71,15 +84,37 @@ impl<VM: VMBinding> SFT for LargeObjectSpace<VM> {
             Some(Ordering::SeqCst),
         );
 
-        // If this object is freshly allocated, we do not set it as unlogged
-        if !alloc && self.common.needs_log_bit {
+        // If this object is freshly allocated, we do not set it as unlogged, unless the writes to
+        // the young objects need to be remembered.
+        if (!alloc || self.unlog_new_objects) && self.common.needs_log_bit {
             VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.mark_as_unlogged::<VM>(object, Ordering::SeqCst);
         }
 
         #[cfg(feature = "global_alloc_bit")]
         crate::util::alloc_bit::set_alloc_bit(object);
+        self.treadmill.add_to_treadmill(object, alloc);
+    }
+    fn mark_allocated_object(&self, _object: ObjectReference) {
+        // New objects go to the allocation nursery of the treadmill, which is not swept in the
+        // current GC, and they already have the current mark state.
+    }
+    fn resize_object_in_place(
+        &self,
+        object: ObjectReference,
+        old_size: usize,
+        new_size: usize,
+    ) -> bool {
         let cell = VM::VMObjectModel::object_start_ref(object);
-        self.treadmill.add_to_treadmill(cell, alloc);
+        let first_page = get_super_page(cell);
+        if new_size <= old_size {
+            // Give the whole pages after the shrunk object back to the page resource.
+            let keep = conversions::bytes_to_pages_up(cell + new_size - first_page);
+            self.pr.release_tail_pages(first_page, keep);
+            return true;
+        }
+        // We can grow the object as long as it still fits in the pages allocated for it.
+        let pages = self.pr.get_allocated_pages(first_page);
+        cell + new_size <= first_page + (pages << LOG_BYTES_IN_PAGE)
     }
     #[inline(always)]
     fn sft_trace_object(
@@ -148,7 +183,13 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         heap: &mut HeapMeta,
         constraints: &'static PlanConstraints,
         protect_memory_on_release: bool,
+        ref_counting: bool,
     ) -> Self {
+        let mut local_specs =
+            metadata::extract_side_metadata(&[*VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC]);
+        if ref_counting {
+            local_specs.extend_from_slice(&[LOS_REF_COUNT_SPEC, LOS_REF_COUNT_CANDIDATE_SPEC]);
+        }
         let common = CommonSpace::new(
             SpaceOptions {
                 name,
@@ -159,9 +200,7 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
                 vmrequest,
                 side_metadata_specs: SideMetadataContext {
                     global: global_side_metadata_specs,
-                    local: metadata::extract_side_metadata(&[
-                        *VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
-                    ]),
+                    local: local_specs,
                 },
             },
             vm_map,
@@ -180,6 +219,38 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
             mark_state: 0,
             in_nursery_gc: false,
             treadmill: TreadMill::new(),
+            // The counts are kept for the first page of each object.
+            ref_counts: ref_counting
+                .then(|| LOSRefCounts::new(VM::VMObjectModel::object_start_ref)),
+            unlog_new_objects: false,
+        }
+    }
+
+    /// Mark the objects as unlogged when they are allocated, so the barrier remembers the writes to
+    /// the young objects. This is used when the nursery is collected in increments.
+    pub fn set_unlog_new_objects(&mut self) {
+        self.unlog_new_objects = true;
+    }
+
+    /// The reference counts of the objects, if they are counted.
+    pub fn ref_counts(&self) -> Option<&LOSRefCounts> {
+        self.ref_counts.as_ref()
+    }
+
+    /// Keep an object out of the reclamation by reference counting if it is in this space, because
+    /// the finalizer or the reference processors hold it (see [`crate::policy::los_ref_count`]).
+    pub fn keep_out_of_ref_counting(&self, object: ObjectReference) {
+        if let Some(ref_counts) = self.ref_counts.as_ref() {
+            if !object.is_null() && self.in_space(object) {
+                ref_counts.stick(object);
+            }
+        }
+    }
+
+    /// Apply the buffered reference count updates.
+    pub fn apply_ref_counts(&self) {
+        if let Some(ref_counts) = self.ref_counts.as_ref() {
+            ref_counts.apply(|object| !self.is_in_nursery(object));
         }
     }
 
@@ -190,6 +261,10 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         }
         self.treadmill.flip(full_heap);
         self.in_nursery_gc = !full_heap;
+        if let Some(ref_counts) = self.ref_counts.as_ref() {
+            ref_counts.apply(|object| !self.is_in_nursery(object));
+            ref_counts.prepare();
+        }
     }
 
     pub fn release(&mut self, full_heap: bool) {
@@ -198,6 +273,60 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         if full_heap {
             self.sweep_large_pages(false);
         }
+        // The barriers are flushed during the GC, so we apply the rest of the buffers before we
+        // look at the counts.
+        self.apply_ref_counts();
+        if let Some(ref_counts) = self.ref_counts.as_ref() {
+            if full_heap {
+                ref_counts.retain_live(|object| self.test_mark_bit(object, self.mark_state));
+            } else {
+                self.reclaim_dead_candidates();
+            }
+        }
+    }
+
+    /// Keep the objects outside the scope of a partial-heap GC that collects the LOS, as they are
+    /// not traced. They are marked, and moved to the to-space, so the release does not sweep them.
+    /// This must be called after the closure, and before the release of the GC.
+    pub fn keep_out_of_scope(&self, scope: &CollectionScope) {
+        debug_assert!(!self.in_nursery_gc);
+        for object in self.treadmill.keep(|object| !scope.includes(object)) {
+            // The GC threads do not race for the objects, so the mark bits are simply stored.
+            let old_value = load_metadata::<VM>(
+                &VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
+                object,
+                None,
+                Some(Ordering::SeqCst),
+            );
+            store_metadata::<VM>(
+                &VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
+                object,
+                (old_value & (!LOS_BIT_MASK)) | self.mark_state,
+                None,
+                Some(Ordering::SeqCst),
+            );
+        }
+    }
+
+    /// Reclaim the mature objects that have no references from the heap, and are not reached by
+    /// the nursery GC.
+    fn reclaim_dead_candidates(&self) {
+        let dead = self.ref_counts.as_ref().unwrap().take_dead();
+        for object in dead.iter() {
+            let removed = self.treadmill.remove(*object);
+            debug_assert!(
+                removed,
+                "{} is not a mature object in the treadmill",
+                object
+            );
+            self.release_object(*object);
+        }
+        if !dead.is_empty() {
+            debug!(
+                "Reclaimed {} large objects by reference counting",
+                dead.len()
+            );
+        }
     }
     // Allow nested-if for this function to make it clear that test_and_mark() is only executed
     // for the outer condition is met.
@@ -214,17 +343,26 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
             object
         );
         let nursery_object = self.is_in_nursery(object);
+        if self.in_nursery_gc && !nursery_object {
+            if let Some(ref_counts) = self.ref_counts.as_ref() {
+                ref_counts.reach(object);
+            }
+        }
         if !self.in_nursery_gc || nursery_object {
             // Note that test_and_mark() has side effects
             if self.test_and_mark(object, self.mark_state) {
-                let cell = VM::VMObjectModel::object_start_ref(object);
-                self.treadmill.copy(cell, nursery_object);
+                self.treadmill.copy(object, nursery_object);
                 self.clear_nursery(object);
                 // We just moved the object out of the logical nursery, mark it as unlogged.
                 if nursery_object && self.common.needs_log_bit {
                     VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC
                         .mark_as_unlogged::<VM>(object, Ordering::SeqCst);
                 }
+                if let Some(ref_counts) = self.ref_counts.as_ref() {
+                    if nursery_object {
+                        ref_counts.promote(object);
+                    }
+                }
                 queue.enqueue(object);
             }
         }
@@ -236,22 +374,36 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         // didn't call self.release_multiple_pages
         // so the compiler knows I'm borrowing two different fields
         if sweep_nursery {
-            for cell in self.treadmill.collect_nursery() {
-                // println!("- cn {}", cell);
-                #[cfg(feature = "global_alloc_bit")]
-                crate::util::alloc_bit::unset_addr_alloc_bit(cell);
-                self.pr.release_pages(get_super_page(cell));
+            for object in self.treadmill.collect_nursery() {
+                // println!("- cn {}", object);
+                self.release_object(object);
             }
         } else {
-            for cell in self.treadmill.collect() {
-                // println!("- ts {}", cell);
-                #[cfg(feature = "global_alloc_bit")]
-                crate::util::alloc_bit::unset_addr_alloc_bit(cell);
-                self.pr.release_pages(get_super_page(cell));
+            for object in self.treadmill.collect() {
+                // println!("- ts {}", object);
+                self.release_object(object);
             }
         }
     }
 
+    /// Release a dead large object that has been removed from the treadmill.
+    fn release_object(&self, object: ObjectReference) {
+        #[cfg(feature = "global_alloc_bit")]
+        crate::util::alloc_bit::unset_alloc_bit(object);
+        let cell = VM::VMObjectModel::object_start_ref(object);
+        self.release_large_pages(get_super_page(cell));
+    }
+
+    /// Release the pages of a dead large object.
+    fn release_large_pages(&self, start: Address) {
+        if self.common.needs_log_bit {
+            // The next object in the pages starts logged.
+            let bytes = conversions::pages_to_bytes(self.pr.get_allocated_pages(start));
+            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.clear_range(start, bytes);
+        }
+        self.pr.release_pages(start);
+    }
+
     /// Allocate an object
     pub fn allocate_pages(&self, tls: VMThread, pages: usize) -> Address {
         self.acquire(tls, pages)
@@ -300,7 +452,7 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
     }
 
     /// Check if a given object is in nursery
-    fn is_in_nursery(&self, object: ObjectReference) -> bool {
+    pub fn is_in_nursery(&self, object: ObjectReference) -> bool {
         load_metadata::<VM>(
             &VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
             object,
@@ -5,9 +5,11 @@ use crate::util::heap::PageResource;
 use crate::util::ObjectReference;
 
 use crate::policy::space::*;
+use crate::util::constants::BYTES_IN_PAGE;
 use crate::util::conversions;
-use crate::util::heap::layout::vm_layout_constants::{AVAILABLE_BYTES, AVAILABLE_START};
-use crate::util::metadata::side_metadata::SideMetadataSanity;
+use crate::util::heap::layout::vm_layout_constants::{
+    AVAILABLE_BYTES, AVAILABLE_START, BYTES_IN_CHUNK,
+};
 use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSpec};
 use crate::util::opaque_pointer::*;
 use crate::util::options::Options;
@@ -46,7 +48,7 @@ impl<VM: VMBinding> SFT for LockFreeImmortalSpace<VM> {
         self.get_name()
     }
     fn is_live(&self, _object: ObjectReference) -> bool {
-        unimplemented!()
+        true
     }
     fn is_movable(&self) -> bool {
         unimplemented!()
@@ -59,6 +61,9 @@ impl<VM: VMBinding> SFT for LockFreeImmortalSpace<VM> {
         #[cfg(feature = "global_alloc_bit")]
         crate::util::alloc_bit::set_alloc_bit(_object);
     }
+    fn mark_allocated_object(&self, _object: ObjectReference) {
+        // Objects in this space are never reclaimed.
+    }
     fn sft_trace_object(
         &self,
         _queue: &mut VectorObjectQueue,
@@ -87,10 +92,43 @@ impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
         panic!("immortalspace only releases pages enmasse")
     }
 
+    // This space maps its memory directly rather than through the mmapper.
+    fn initialize_address_range(&self) {
+        // Eagerly memory map the entire heap (also zero all the memory)
+        crate::util::memory::dzmmap_noreplace(self.start, self.extent).unwrap();
+        if self
+            .metadata
+            .try_map_metadata_space(self.start, self.extent)
+            .is_err()
+        {
+            // TODO(Javad): handle meta space allocation failure
+            panic!("failed to mmap meta memory");
+        }
+    }
+
     fn initialize_sft(&self) {
         SFT_MAP.update(self.as_sft(), self.start, self.extent);
     }
 
+    // This space maps its memory directly rather than through the mmapper.
+    fn release_address_space(&self, _discontiguous_range: (Address, Address)) {
+        let end = conversions::chunk_align_up(self.start + self.extent);
+        for chunk in (self.start.as_usize()..end.as_usize()).step_by(BYTES_IN_CHUNK) {
+            SFT_MAP.clear(unsafe { Address::from_usize(chunk) });
+        }
+        let bytes = conversions::raw_align_up(self.extent, BYTES_IN_PAGE);
+        self.metadata.release_metadata_space(self.start, bytes);
+        crate::util::memory::munmap(self.start, bytes).unwrap();
+    }
+
+    // The whole extent of this space is mapped when its address range is initialized.
+    fn get_acquired_ranges(&self) -> Vec<(Address, Address)> {
+        vec![(self.start, self.start + self.extent)]
+    }
+
+    // Nothing is released by this space.
+    fn set_decommit_on_release(&self, _decommit: bool) {}
+
     fn reserved_pages(&self) -> usize {
         let cursor = unsafe { Address::from_usize(self.cursor.load(Ordering::Relaxed)) };
         let data_pages = conversions::bytes_to_pages_up(self.limit - cursor);
@@ -120,9 +158,8 @@ impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
 
     /// We have to override the default implementation because
     /// LockFreeImmortalSpace doesn't put metadata in a common space
-    fn verify_side_metadata_sanity(&self, side_metadata_sanity_checker: &mut SideMetadataSanity) {
-        side_metadata_sanity_checker
-            .verify_metadata_context(std::any::type_name::<Self>(), &self.metadata)
+    fn side_metadata(&self) -> &SideMetadataContext {
+        &self.metadata
     }
 }
 
@@ -165,7 +202,7 @@ impl<VM: VMBinding> LockFreeImmortalSpace<VM> {
 
         // FIXME: This space assumes that it can use the entire heap range, which is definitely wrong.
         // https://github.com/mmtk/mmtk-core/issues/314
-        let space = Self {
+        Self {
             name,
             cursor: AtomicUsize::new(AVAILABLE_START.as_usize()),
             limit: AVAILABLE_START + total_bytes,
@@ -177,19 +214,6 @@ impl<VM: VMBinding> LockFreeImmortalSpace<VM> {
                 local: vec![],
             },
             phantom: PhantomData,
-        };
-
-        // Eagerly memory map the entire heap (also zero all the memory)
-        crate::util::memory::dzmmap_noreplace(AVAILABLE_START, total_bytes).unwrap();
-        if space
-            .metadata
-            .try_map_metadata_space(AVAILABLE_START, total_bytes)
-            .is_err()
-        {
-            // TODO(Javad): handle meta space allocation failure
-            panic!("failed to mmap meta memory");
         }
-
-        space
     }
 }
@@ -0,0 +1,384 @@
+//! Reference counting of large objects, so a generational plan can reclaim a dead mature large
+//! object (e.g. a big I/O buffer) in a nursery GC, instead of waiting for the next full-heap GC.
+//! This is enabled with the option `los_ref_counting`.
+//!
+//! The binding reports each update of a reference field with
+//! [`memory_manager::reference_update_barrier`](crate::memory_manager::reference_update_barrier).
+//! The barrier of the mutator buffers an increment for the new referent and a decrement for the
+//! old referent if they are large objects, and the buffers are applied by background packets
+//! between GCs. The count of a large object is the number of reference fields in the heap that
+//! point to it, which may be more than the live references, as the fields of dead objects are
+//! never decremented. It is kept in a byte of side metadata for the first page of the object, and
+//! it sticks at the maximum value if it overflows, or if a decrement finds it zero.
+//!
+//! A mature large object with a zero count is a candidate. The references to it can only be from
+//! the roots, and a nursery GC reaches all the objects referenced from the roots. So a candidate
+//! that is not reached by a nursery GC is dead, and is reclaimed at the end of the GC. The counts
+//! are only exact if the binding reports every reference store into the heap, including the
+//! stores that initialize new objects, and the stores by bulk copies of arrays.
+//!
+//! A nursery GC only processes the young objects of the finalizer and the reference processors,
+//! so it does not reach the mature objects that they hold, although those are not dead. The counts
+//! of the large objects that are registered for finalization, and of the references and referents
+//! that are added to the reference processors, stick (see
+//! `LargeObjectSpace::keep_out_of_ref_counting()`), so they are only reclaimed by full-heap GCs.
+
+use crate::plan::{BarrierSelector, PlanConstraints};
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::options::Options;
+use crate::util::{Address, ObjectReference};
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+pub(crate) const LOS_REF_COUNT_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::LOS_REF_COUNT;
+
+/// The candidate state of each large object, by the first page of the object. The state is kept in
+/// side metadata, so a GC can reach a candidate without a lock.
+pub(crate) const LOS_REF_COUNT_CANDIDATE_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::LOS_REF_COUNT_CANDIDATE;
+
+/// The count sticks at this value.
+const STUCK_COUNT: usize = u8::MAX as usize;
+
+/// Are the references to the large objects counted for a plan? Only the object barrier buffers
+/// the reference updates.
+pub(crate) fn is_enabled(options: &Options, constraints: &PlanConstraints) -> bool {
+    *options.los_ref_counting && constraints.barrier == BarrierSelector::ObjectBarrier
+}
+
+/// The increments and decrements buffered by a barrier.
+#[derive(Default)]
+pub struct RefCountBuffer {
+    pub incs: Vec<ObjectReference>,
+    pub decs: Vec<ObjectReference>,
+}
+
+impl RefCountBuffer {
+    pub fn len(&self) -> usize {
+        self.incs.len() + self.decs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The candidate states of an object.
+mod candidate {
+    /// The object is not a candidate.
+    pub const NONE: usize = 0;
+    /// The count became zero after the GC started. We do not know if the GC reaches it.
+    pub const PENDING: usize = 1;
+    /// The GC has not reached the object (yet).
+    pub const UNREACHED: usize = 2;
+    /// The GC has reached the object.
+    pub const REACHED: usize = 3;
+}
+
+/// The reference counts of a large object space.
+pub struct LOSRefCounts {
+    /// The address of the first page of an object, where its count and state are kept.
+    start_of: fn(ObjectReference) -> Address,
+    /// The buffers flushed by the barriers that are not applied yet.
+    buffers: Mutex<Vec<RefCountBuffer>>,
+    /// The mature objects with a zero count. An object is in the list once, as its state is not
+    /// `NONE`.
+    candidates: Mutex<Vec<ObjectReference>>,
+}
+
+impl LOSRefCounts {
+    /// Count the references to the objects whose first pages are given by `start_of`, e.g.
+    /// `ObjectModel::object_start_ref`.
+    pub fn new(start_of: fn(ObjectReference) -> Address) -> Self {
+        Self {
+            start_of,
+            buffers: Mutex::new(vec![]),
+            candidates: Mutex::new(vec![]),
+        }
+    }
+
+    /// Reset the count and the state of a newly allocated object.
+    pub fn reset(&self, object: ObjectReference) {
+        let start = (self.start_of)(object);
+        side_metadata::store_atomic(&LOS_REF_COUNT_SPEC, start, 0, Ordering::SeqCst);
+        self.set_state(object, candidate::NONE);
+    }
+
+    /// The count of an object.
+    pub fn count(&self, object: ObjectReference) -> usize {
+        side_metadata::load_atomic(
+            &LOS_REF_COUNT_SPEC,
+            (self.start_of)(object),
+            Ordering::SeqCst,
+        )
+    }
+
+    /// Make the count of an object stick, so it is never reclaimed by the counts. Only a full-heap
+    /// GC reclaims it.
+    pub fn stick(&self, object: ObjectReference) {
+        side_metadata::store_atomic(
+            &LOS_REF_COUNT_SPEC,
+            (self.start_of)(object),
+            STUCK_COUNT,
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Update the count of an object, and return the new count.
+    fn update(&self, object: ObjectReference, inc: bool) -> usize {
+        let meta_addr = (self.start_of)(object);
+        loop {
+            let old = side_metadata::load_atomic(&LOS_REF_COUNT_SPEC, meta_addr, Ordering::SeqCst);
+            let new = match (old, inc) {
+                (STUCK_COUNT, _) => return STUCK_COUNT,
+                // We missed an increment, so we cannot trust the count any more.
+                (0, false) => STUCK_COUNT,
+                (_, true) => old + 1,
+                (_, false) => old - 1,
+            };
+            if side_metadata::compare_exchange_atomic(
+                &LOS_REF_COUNT_SPEC,
+                meta_addr,
+                old,
+                new,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                return new;
+            }
+        }
+    }
+
+    fn state(&self, object: ObjectReference) -> usize {
+        side_metadata::load_atomic(
+            &LOS_REF_COUNT_CANDIDATE_SPEC,
+            (self.start_of)(object),
+            Ordering::SeqCst,
+        )
+    }
+
+    fn set_state(&self, object: ObjectReference, state: usize) {
+        side_metadata::store_atomic(
+            &LOS_REF_COUNT_CANDIDATE_SPEC,
+            (self.start_of)(object),
+            state,
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Make an object a candidate, unless it is one already.
+    fn add_candidate(&self, object: ObjectReference) {
+        if side_metadata::compare_exchange_atomic(
+            &LOS_REF_COUNT_CANDIDATE_SPEC,
+            (self.start_of)(object),
+            candidate::NONE,
+            candidate::PENDING,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            self.candidates.lock().unwrap().push(object);
+        }
+    }
+
+    /// Add a buffer flushed by a barrier. It is applied by the next call to `apply()`.
+    pub fn push(&self, buffer: RefCountBuffer) {
+        self.buffers.lock().unwrap().push(buffer);
+    }
+
+    /// Apply the buffered increments and decrements. `is_mature` tells if an object is mature,
+    /// and only mature objects become candidates.
+    pub fn apply(&self, is_mature: impl Fn(ObjectReference) -> bool) {
+        let buffers = std::mem::take(&mut *self.buffers.lock().unwrap());
+        // Apply all the increments first, so the count of an object that is moved from one field
+        // to another does not drop to zero on the way.
+        for object in buffers.iter().flat_map(|buffer| buffer.incs.iter()) {
+            self.update(*object, true);
+        }
+        for object in buffers.iter().flat_map(|buffer| buffer.decs.iter()) {
+            if self.update(*object, false) == 0 && is_mature(*object) {
+                self.add_candidate(*object);
+            }
+        }
+    }
+
+    /// Record an object that is promoted by a nursery GC. It becomes a candidate if no field
+    /// points to it.
+    pub fn promote(&self, object: ObjectReference) {
+        if self.count(object) == 0 {
+            self.add_candidate(object);
+        }
+    }
+
+    /// Start a GC. The candidates are unreached until the GC reaches them.
+    pub fn prepare(&self) {
+        for object in self.candidates.lock().unwrap().iter() {
+            self.set_state(*object, candidate::UNREACHED);
+        }
+    }
+
+    /// Record that a nursery GC reaches a mature object.
+    #[inline(always)]
+    pub fn reach(&self, object: ObjectReference) {
+        if self.state(object) == candidate::UNREACHED {
+            side_metadata::compare_exchange_atomic(
+                &LOS_REF_COUNT_CANDIDATE_SPEC,
+                (self.start_of)(object),
+                candidate::UNREACHED,
+                candidate::REACHED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+
+    /// Remove and return the candidates that are dead at the end of a nursery GC. The buffers
+    /// must be applied before this. The objects that have a non-zero count again are no longer
+    /// candidates.
+    pub fn take_dead(&self) -> Vec<ObjectReference> {
+        let mut dead = vec![];
+        self.candidates.lock().unwrap().retain(|object| {
+            if self.count(*object) != 0 {
+                self.set_state(*object, candidate::NONE);
+                false
+            } else if self.state(*object) == candidate::UNREACHED {
+                self.set_state(*object, candidate::NONE);
+                dead.push(*object);
+                false
+            } else {
+                true
+            }
+        });
+        dead
+    }
+
+    /// Keep the candidates that survive a full-heap GC. The others are reclaimed by the GC.
+    pub fn retain_live(&self, is_live: impl Fn(ObjectReference) -> bool) {
+        self.candidates.lock().unwrap().retain(|object| {
+            let live = is_live(*object);
+            if !live {
+                self.set_state(*object, candidate::NONE);
+            }
+            live
+        });
+    }
+}
+
+/// A background packet that applies the buffers of the reference counts of the LOS.
+pub struct ApplyLOSRefCounts;
+
+impl<VM: VMBinding> GCWork<VM> for ApplyLOSRefCounts {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        mmtk.plan.common().get_los().apply_ref_counts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::vm_layout_constants::{BYTES_IN_CHUNK, HEAP_START};
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    fn with_ref_counts(f: impl FnOnce(&LOSRefCounts, [ObjectReference; 2])) {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![LOS_REF_COUNT_SPEC, LOS_REF_COUNT_CANDIDATE_SPEC],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_CHUNK)
+                        .unwrap();
+                    let ref_counts = LOSRefCounts::new(|object| object.to_address());
+                    let objects = unsafe {
+                        [
+                            HEAP_START.to_object_reference(),
+                            (HEAP_START + 4 * crate::util::constants::BYTES_IN_PAGE)
+                                .to_object_reference(),
+                        ]
+                    };
+                    for object in objects {
+                        ref_counts.reset(object);
+                    }
+                    f(&ref_counts, objects);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_counts() {
+        with_ref_counts(|ref_counts, [a, b]| {
+            ref_counts.push(RefCountBuffer {
+                incs: vec![a, a, b],
+                decs: vec![a],
+            });
+            ref_counts.apply(|_| true);
+            assert_eq!(ref_counts.count(a), 1);
+            assert_eq!(ref_counts.count(b), 1);
+            // A decrement of a zero count makes it stick.
+            ref_counts.push(RefCountBuffer {
+                incs: vec![],
+                decs: vec![b, b],
+            });
+            ref_counts.apply(|_| true);
+            assert_eq!(ref_counts.count(b), STUCK_COUNT);
+            ref_counts.push(RefCountBuffer {
+                incs: vec![],
+                decs: vec![b],
+            });
+            ref_counts.apply(|_| true);
+            assert_eq!(ref_counts.count(b), STUCK_COUNT);
+        })
+    }
+
+    #[test]
+    fn test_candidates_in_nursery_gc() {
+        with_ref_counts(|ref_counts, [a, b]| {
+            // Both objects are mature and have no references.
+            ref_counts.promote(a);
+            ref_counts.promote(b);
+            ref_counts.promote(b);
+            assert_eq!(ref_counts.candidates.lock().unwrap().len(), 2);
+
+            // The GC reaches `b` from a root, so only `a` is dead.
+            ref_counts.prepare();
+            ref_counts.reach(b);
+            assert_eq!(ref_counts.take_dead(), vec![a]);
+
+            // `b` is reclaimed by the next GC that does not reach it, unless a field points to it
+            // again.
+            ref_counts.prepare();
+            ref_counts.push(RefCountBuffer {
+                incs: vec![b],
+                decs: vec![],
+            });
+            ref_counts.apply(|_| true);
+            assert!(ref_counts.take_dead().is_empty());
+            assert!(ref_counts.candidates.lock().unwrap().is_empty());
+        })
+    }
+
+    #[test]
+    fn test_stuck_objects_are_not_reclaimed() {
+        with_ref_counts(|ref_counts, [a, b]| {
+            ref_counts.promote(a);
+            ref_counts.promote(b);
+            // The finalizer knows about `a`.
+            ref_counts.stick(a);
+            ref_counts.prepare();
+            assert_eq!(ref_counts.take_dead(), vec![b]);
+            // A full-heap GC finds `a` dead.
+            ref_counts.retain_live(|_| false);
+            assert!(ref_counts.candidates.lock().unwrap().is_empty());
+        })
+    }
+}
@@ -1,21 +1,24 @@
 use super::metadata::*;
+use crate::plan::static_plan::devirtualize;
+use crate::plan::CollectionScope;
 use crate::plan::ObjectQueue;
 use crate::plan::VectorObjectQueue;
 use crate::policy::space::CommonSpace;
 use crate::policy::space::SFT;
+use crate::util::alloc::object_ref_guard;
 use crate::util::constants::BYTES_IN_PAGE;
+use crate::util::heap::chunk_map::ChunkMap;
+use crate::util::heap::regions::{Chunk, PageAccountedRegion, Region};
 use crate::util::heap::PageResource;
 use crate::util::malloc::malloc_ms_util::*;
-use crate::util::metadata::side_metadata::{
-    bzero_metadata, SideMetadataContext, SideMetadataSanity, SideMetadataSpec,
-};
-use crate::util::metadata::MetadataSpec;
+use crate::util::metadata::side_metadata::{bzero_metadata, SideMetadataContext, SideMetadataSpec};
+use crate::util::metadata::{mark_bit, MetadataSpec};
 use crate::util::opaque_pointer::*;
 use crate::util::Address;
 use crate::util::ObjectReference;
 use crate::util::{conversions, metadata};
 use crate::vm::VMBinding;
-use crate::vm::{ActivePlan, Collection, ObjectModel};
+use crate::vm::{ActivePlan, ObjectModel};
 use crate::{policy::space::Space, util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK};
 use std::marker::PhantomData;
 #[cfg(debug_assertions)]
@@ -36,9 +39,13 @@ const ASSERT_ALLOCATION: bool = false;
 pub struct MallocSpace<VM: VMBinding> {
     phantom: PhantomData<VM>,
     active_bytes: AtomicUsize,
-    pub chunk_addr_min: AtomicUsize, // XXX: have to use AtomicUsize to represent an Address
-    pub chunk_addr_max: AtomicUsize,
+    /// The number of pages marked in the active page metadata, i.e. the pages that have objects.
+    active_pages: AtomicUsize,
+    /// The chunks that have objects allocated by malloc.
+    pub chunk_map: ChunkMap,
     metadata: SideMetadataContext,
+    /// The mark bit of the objects in this space (see `ObjectModel::use_side_mark_bit`).
+    mark_bit_spec: MetadataSpec,
     // Mapping between allocated address and its size - this is used to check correctness.
     // Size will be set to zero when the memory is freed.
     #[cfg(debug_assertions)]
@@ -60,7 +67,7 @@ impl<VM: VMBinding> SFT for MallocSpace<VM> {
     }
 
     fn is_live(&self, object: ObjectReference) -> bool {
-        is_marked::<VM>(object, Some(Ordering::SeqCst))
+        is_marked::<VM>(&self.mark_bit_spec, object, Some(Ordering::SeqCst))
     }
 
     fn is_movable(&self) -> bool {
@@ -89,19 +96,48 @@ impl<VM: VMBinding> SFT for MallocSpace<VM> {
 
     fn initialize_object_metadata(&self, object: ObjectReference, _alloc: bool) {
         trace!("initialize_object_metadata for object {}", object);
-        let page_addr = conversions::page_align_down(object.to_address());
-        set_page_mark(page_addr);
+        // The pages of the object are marked when it is allocated. The object reference may be
+        // past the pages if the binding sets `OBJECT_REF_OFFSET_BEYOND_CELL`.
+        debug_assert!(is_page_marked(conversions::page_align_down(
+            VM::VMObjectModel::object_start_ref(object)
+        )));
         set_alloc_bit(object);
     }
 
+    fn mark_allocated_object(&self, object: ObjectReference) {
+        set_mark_bit::<VM>(&self.mark_bit_spec, object, Some(Ordering::SeqCst));
+    }
+
+    fn resize_object_in_place(
+        &self,
+        object: ObjectReference,
+        old_size: usize,
+        new_size: usize,
+    ) -> bool {
+        if new_size <= old_size {
+            return true;
+        }
+        // malloc may give us more bytes than we asked for. We can grow the object
+        // as long as it still fits in the usable size of the malloc'd memory. The usable size
+        // is already counted in `active_bytes` and marked in the page marks, so we do not need to
+        // update them. For an offset malloc, the object starts after the malloc'd address.
+        let (obj_start, is_offset_malloc, bytes) = Self::get_malloc_addr_size(object);
+        let malloc_end = if is_offset_malloc {
+            offset_malloc_start(obj_start) + bytes
+        } else {
+            obj_start + bytes
+        };
+        obj_start + new_size <= malloc_end
+    }
+
     #[inline(always)]
     fn sft_trace_object(
         &self,
         queue: &mut VectorObjectQueue,
         object: ObjectReference,
-        _worker: GCWorkerMutRef,
+        worker: GCWorkerMutRef,
     ) -> ObjectReference {
-        self.trace_object(queue, object)
+        self.trace_object(queue, object, worker.into_mut::<VM>())
     }
 }
 
@@ -175,16 +211,67 @@ impl<VM: VMBinding> Space<VM> for MallocSpace<VM> {
         "MallocSpace"
     }
 
+    // The memory of MallocSpace comes from malloc, so we free the objects that are still alive
+    // instead of unmapping the memory.
+    fn release_address_space(&self, _discontiguous_range: (Address, Address)) {
+        let chunks: Vec<Chunk> = self.chunk_map.allocated_chunks().collect();
+        for &chunk in chunks.iter() {
+            let chunk_linear_scan = crate::util::linear_scan::ObjectIterator::<
+                VM,
+                MallocObjectSize<VM>,
+                false,
+            >::new(chunk.start(), chunk.end());
+            for object in chunk_linear_scan {
+                let (obj_start, offset_malloc, bytes) = Self::get_malloc_addr_size(object);
+                self.free_internal(obj_start, bytes, offset_malloc);
+            }
+        }
+        // Unmap the side metadata after we have visited all the chunks, as the chunk map is also
+        // side metadata.
+        for chunk in chunks {
+            crate::mmtk::SFT_MAP.clear(chunk.start());
+            self.metadata
+                .release_metadata_space(chunk.start(), BYTES_IN_CHUNK);
+        }
+    }
+
+    // The memory of MallocSpace is given back to the OS by malloc.
+    fn set_decommit_on_release(&self, _decommit: bool) {}
+
     fn reserved_pages(&self) -> usize {
-        // TODO: figure out a better way to get the total number of active pages from the metadata
-        let data_pages = conversions::bytes_to_pages_up(self.active_bytes.load(Ordering::SeqCst));
+        let data_pages = self.active_pages();
         let meta_pages = self.metadata.calculate_reserved_pages(data_pages);
         data_pages + meta_pages
     }
 
-    fn verify_side_metadata_sanity(&self, side_metadata_sanity_checker: &mut SideMetadataSanity) {
-        side_metadata_sanity_checker
-            .verify_metadata_context(std::any::type_name::<Self>(), &self.metadata)
+    // The memory of MallocSpace comes from malloc. We find the chunks that we have seen
+    // objects in by the chunk marks.
+    fn get_acquired_ranges(&self) -> Vec<(Address, Address)> {
+        let mut ranges: Vec<(Address, Address)> = vec![];
+        for chunk in self.chunk_map.allocated_chunks() {
+            match ranges.last_mut() {
+                Some((_, last_end)) if *last_end == chunk.start() => *last_end = chunk.end(),
+                _ => ranges.push((chunk.start(), chunk.end())),
+            }
+        }
+        ranges
+    }
+
+    // MallocSpace does not have a common space, and its memory is not in a fixed range.
+    fn get_space_info(&self) -> SpaceInfo {
+        SpaceInfo {
+            name: self.get_name(),
+            policy: self.get_policy_name(),
+            movable: false,
+            immortal: false,
+            bounds: None,
+            reserved_pages: self.reserved_pages(),
+            zeroed_block_pool_pages: 0,
+        }
+    }
+
+    fn side_metadata(&self) -> &SideMetadataContext {
+        &self.metadata
     }
 }
 
@@ -198,9 +285,9 @@ impl<VM: VMBinding> crate::policy::gc_work::PolicyTraceObject<VM> for MallocSpac
         queue: &mut Q,
         object: ObjectReference,
         _copy: Option<CopySemantics>,
-        _worker: &mut GCWorker<VM>,
+        worker: &mut GCWorker<VM>,
     ) -> ObjectReference {
-        self.trace_object(queue, object)
+        self.trace_object(queue, object, worker)
     }
 
     #[inline(always)]
@@ -211,19 +298,26 @@ impl<VM: VMBinding> crate::policy::gc_work::PolicyTraceObject<VM> for MallocSpac
 
 impl<VM: VMBinding> MallocSpace<VM> {
     pub fn new(global_side_metadata_specs: Vec<SideMetadataSpec>) -> Self {
+        let mark_bit_spec = mark_bit::mark_bit_spec_for_space::<VM>("MallocSpace");
         MallocSpace {
             phantom: PhantomData,
             active_bytes: AtomicUsize::new(0),
-            chunk_addr_min: AtomicUsize::new(usize::max_value()), // XXX: have to use AtomicUsize to represent an Address
-            chunk_addr_max: AtomicUsize::new(0),
+            active_pages: AtomicUsize::new(0),
+            chunk_map: ChunkMap::new(),
             metadata: SideMetadataContext {
                 global: global_side_metadata_specs,
                 local: metadata::extract_side_metadata(&[
                     MetadataSpec::OnSide(ACTIVE_PAGE_METADATA_SPEC),
+                    MetadataSpec::OnSide(CONTINUATION_PAGE_METADATA_SPEC),
                     MetadataSpec::OnSide(OFFSET_MALLOC_METADATA_SPEC),
-                    *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                    MetadataSpec::OnSide(CHUNK_ALLOC_EPOCH_METADATA_SPEC),
+                    MetadataSpec::OnSide(CHUNK_LIVE_OBJECTS_METADATA_SPEC),
+                    MetadataSpec::OnSide(CHUNK_MARKED_OBJECTS_METADATA_SPEC),
+                    MetadataSpec::OnSide(Chunk::USED_PAGES_TABLE),
+                    mark_bit_spec,
                 ]),
             },
+            mark_bit_spec,
             #[cfg(debug_assertions)]
             active_mem: Mutex::new(HashMap::new()),
             #[cfg(debug_assertions)]
@@ -237,9 +331,15 @@ impl<VM: VMBinding> MallocSpace<VM> {
 
     pub fn alloc(&self, tls: VMThread, size: usize, align: usize, offset: isize) -> Address {
         // TODO: Should refactor this and Space.acquire()
-        if VM::VMActivePlan::global().poll(false, Some(self)) {
+        if crate::util::alloc::allocator::is_no_gc_on_failure() {
+            if VM::VMActivePlan::global().collection_required(false, Some(self)) {
+                return unsafe { Address::zero() };
+            }
+        } else if devirtualize(VM::VMActivePlan::global()).poll(false, Some(self)) {
             assert!(VM::VMActivePlan::is_mutator(tls), "Polling in GC worker");
-            VM::VMCollection::block_for_gc(VMMutatorThread(tls));
+            VM::VMActivePlan::global()
+                .base()
+                .block_for_gc(VMMutatorThread(tls));
             return unsafe { Address::zero() };
         }
 
@@ -247,14 +347,24 @@ impl<VM: VMBinding> MallocSpace<VM> {
         if !address.is_zero() {
             let actual_size = get_malloc_usable_size(address, is_offset_malloc);
 
-            // If the side metadata for the address has not yet been mapped, we will map all the side metadata for the range [address, address + actual_size).
-            if !is_meta_space_mapped(address, actual_size) {
+            // If the side metadata for the address has not yet been mapped, we will map all the
+            // side metadata for the range in which the object reference may be. It may be in the
+            // next chunk if the object has a large header.
+            let ref_extent = object_ref_guard::object_ref_extent::<VM>(actual_size);
+            if !is_meta_space_mapped(address, ref_extent) {
                 // Map the metadata space for the associated chunk
-                self.map_metadata_and_update_bound(address, actual_size);
+                map_meta_space(&self.metadata, &self.chunk_map, address, ref_extent);
                 // Update SFT
-                crate::mmtk::SFT_MAP.update(self, address, actual_size);
+                crate::mmtk::SFT_MAP.update(self, address, ref_extent);
             }
             self.active_bytes.fetch_add(actual_size, Ordering::SeqCst);
+            self.mark_pages(address, address + actual_size);
+            set_continuation_pages(address, actual_size);
+            let gc_stats = &VM::VMActivePlan::global().base().gc_stats;
+            gc_stats.add_allocated_bytes(actual_size);
+            // The sweeping cannot skip the chunk in the next GC.
+            let chunk_start = conversions::chunk_align_down(address);
+            set_chunk_alloc_epoch(chunk_start, gc_stats.gc_count() as u32);
 
             if is_offset_malloc {
                 set_offset_malloc_bit(address);
@@ -270,6 +380,24 @@ impl<VM: VMBinding> MallocSpace<VM> {
         address
     }
 
+    /// The number of pages that have objects. A page shared by many small objects is counted
+    /// once, and a large object counts the pages it spans.
+    pub fn active_pages(&self) -> usize {
+        self.active_pages.load(Ordering::SeqCst)
+    }
+
+    /// Mark the pages that `[start, end)` overlaps as active.
+    fn mark_pages(&self, start: Address, end: Address) {
+        let marked = mark_pages(start, end);
+        self.active_pages.fetch_add(marked, Ordering::SeqCst);
+    }
+
+    /// Unmark the active pages in `[start, end)`.
+    fn unmark_pages(&self, start: Address, end: Address) {
+        let unmarked = unmark_pages(start, end);
+        self.active_pages.fetch_sub(unmarked, Ordering::SeqCst);
+    }
+
     pub fn free(&self, addr: Address) {
         let offset_malloc_bit = is_offset_malloc(addr);
         let bytes = get_malloc_usable_size(addr, offset_malloc_bit);
@@ -279,6 +407,11 @@ impl<VM: VMBinding> MallocSpace<VM> {
     // XXX optimize: We pass the bytes in to free as otherwise there were multiple
     // indirect call instructions in the generated assembly
     fn free_internal(&self, addr: Address, bytes: usize, offset_malloc_bit: bool) {
+        // Unmark the pages that only this memory is on before freeing it. These may be in other
+        // chunks than the one being swept, if the memory spans chunks.
+        let unmarked = unmark_covered_pages(addr, bytes);
+        self.active_pages.fetch_sub(unmarked, Ordering::SeqCst);
+
         if offset_malloc_bit {
             trace!("Free memory {:x}", addr);
             offset_free(addr);
@@ -292,6 +425,7 @@ impl<VM: VMBinding> MallocSpace<VM> {
         }
 
         self.active_bytes.fetch_sub(bytes, Ordering::SeqCst);
+        clear_continuation_pages(addr, bytes);
 
         #[cfg(debug_assertions)]
         if ASSERT_ALLOCATION {
@@ -304,6 +438,7 @@ impl<VM: VMBinding> MallocSpace<VM> {
         &self,
         queue: &mut Q,
         object: ObjectReference,
+        worker: &mut GCWorker<VM>,
     ) -> ObjectReference {
         if object.is_null() {
             return object;
@@ -316,70 +451,32 @@ impl<VM: VMBinding> MallocSpace<VM> {
             address,
         );
 
-        if !is_marked::<VM>(object, None) {
-            let chunk_start = conversions::chunk_align_down(address);
-            set_mark_bit::<VM>(object, Some(Ordering::SeqCst));
-            set_chunk_mark(chunk_start);
+        // Mark the object atomically, so each marked object is counted only once for its chunk.
+        if !is_marked::<VM>(&self.mark_bit_spec, object, None)
+            && try_set_mark_bit::<VM>(&self.mark_bit_spec, object)
+        {
+            let chunk = Chunk::containing_address(address);
+            self.chunk_map.set_allocated(chunk, true);
+            worker.malloc_marked_objects.inc(chunk.start());
             queue.enqueue(object);
         }
 
         object
     }
 
-    fn map_metadata_and_update_bound(&self, addr: Address, size: usize) {
-        // Map the metadata space for the range [addr, addr + size)
-        map_meta_space(&self.metadata, addr, size);
-
-        // Update the bounds of the max and min chunk addresses seen -- this is used later in the sweep
-        // Lockless compare-and-swap loops perform better than a locking variant
-
-        // Update chunk_addr_min, basing on the start of the allocation: addr.
-        {
-            let min_chunk_start = conversions::chunk_align_down(addr);
-            let min_chunk_usize = min_chunk_start.as_usize();
-            let mut min = self.chunk_addr_min.load(Ordering::Relaxed);
-            while min_chunk_usize < min {
-                match self.chunk_addr_min.compare_exchange_weak(
-                    min,
-                    min_chunk_usize,
-                    Ordering::AcqRel,
-                    Ordering::Relaxed,
-                ) {
-                    Ok(_) => break,
-                    Err(x) => min = x,
-                }
-            }
-        }
-
-        // Update chunk_addr_max, basing on the end of the allocation: addr + size.
-        {
-            let max_chunk_start = conversions::chunk_align_down(addr + size);
-            let max_chunk_usize = max_chunk_start.as_usize();
-            let mut max = self.chunk_addr_max.load(Ordering::Relaxed);
-            while max_chunk_usize > max {
-                match self.chunk_addr_max.compare_exchange_weak(
-                    max,
-                    max_chunk_usize,
-                    Ordering::AcqRel,
-                    Ordering::Relaxed,
-                ) {
-                    Ok(_) => break,
-                    Err(x) => max = x,
-                }
-            }
-        }
-    }
-
-    pub fn sweep_chunk(&self, chunk_start: Address) {
+    /// Sweep a chunk. If the GC is a partial-heap GC, the unmarked objects outside its `scope` are
+    /// not collected, so they are kept.
+    pub fn sweep_chunk(&self, chunk: Chunk, scope: Option<&CollectionScope>) {
         // Call the relevant sweep function depending on the location of the mark bits
-        match *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC {
+        match self.mark_bit_spec {
             MetadataSpec::OnSide(local_mark_bit_side_spec) => {
-                self.sweep_chunk_mark_on_side(chunk_start, local_mark_bit_side_spec);
+                self.sweep_chunk_mark_on_side(chunk.start(), local_mark_bit_side_spec, scope);
             }
             _ => {
-                self.sweep_chunk_mark_in_header(chunk_start);
+                self.sweep_chunk_mark_in_header(chunk.start(), scope);
             }
         }
+        self.chunk_map.set_swept(chunk);
     }
 
     /// Given an object in MallocSpace, return its malloc address, whether it is an offset malloc, and malloc size
@@ -388,23 +485,37 @@ impl<VM: VMBinding> MallocSpace<VM> {
         let obj_start = VM::VMObjectModel::object_start_ref(object);
         let offset_malloc_bit = is_offset_malloc(obj_start);
         let bytes = get_malloc_usable_size(obj_start, offset_malloc_bit);
+        object_ref_guard::debug_check_object_start::<VM>(object, obj_start, bytes);
         (obj_start, offset_malloc_bit, bytes)
     }
 
     /// Clean up for an empty chunk
     fn clean_up_empty_chunk(&self, chunk_start: Address) {
-        // Since the chunk mark metadata is a byte, we don't need synchronization
-        unsafe { unset_chunk_mark_unsafe(chunk_start) };
+        self.chunk_map
+            .set_allocated(Chunk::from(chunk_start), false);
         // Clear the SFT entry
         crate::mmtk::SFT_MAP.clear(chunk_start);
     }
 
+    /// Is the object dead after the closure? An unmarked object outside the scope of a
+    /// partial-heap GC is not collected.
+    #[inline(always)]
+    fn is_dead(&self, object: ObjectReference, scope: Option<&CollectionScope>) -> bool {
+        !is_marked::<VM>(&self.mark_bit_spec, object, None)
+            && scope.map_or(true, |scope| scope.includes(object))
+    }
+
     /// Sweep an object if it is dead, and unset page marks for empty pages before this object.
     /// Return true if the object is swept.
-    fn sweep_object(&self, object: ObjectReference, empty_page_start: &mut Address) -> bool {
+    fn sweep_object(
+        &self,
+        object: ObjectReference,
+        empty_page_start: &mut Address,
+        scope: Option<&CollectionScope>,
+    ) -> bool {
         let (obj_start, offset_malloc, bytes) = Self::get_malloc_addr_size(object);
 
-        if !is_marked::<VM>(object, None) {
+        if self.is_dead(object, scope) {
             // Dead object
             trace!("Object {} has been allocated but not marked", object);
 
@@ -415,18 +526,14 @@ impl<VM: VMBinding> MallocSpace<VM> {
 
             true
         } else {
-            // Live object that we have marked
+            // Live object that we have marked, or that the GC does not collect
 
             // Unset marks for free pages and update last_object_end
             if !empty_page_start.is_zero() {
-                // unset marks for pages since last object
-                let current_page = object.to_address().align_down(BYTES_IN_PAGE);
-
-                let mut page = *empty_page_start;
-                while page < current_page {
-                    unsafe { unset_page_mark_unsafe(page) };
-                    page += BYTES_IN_PAGE;
-                }
+                // unset marks for pages since last object. The object reference may be on a later
+                // page than the start of the object.
+                let current_page = obj_start.align_down(BYTES_IN_PAGE);
+                self.unmark_pages(*empty_page_start, current_page);
             }
 
             // Update last_object_end
@@ -436,6 +543,17 @@ impl<VM: VMBinding> MallocSpace<VM> {
         }
     }
 
+    /// Unset marks for the pages from `empty_page_start` to the end of the chunk, or for all the
+    /// pages of the chunk if `empty_page_start` is zero, i.e. there is no live object in the chunk.
+    fn unmark_free_pages_at_chunk_end(&self, chunk_start: Address, empty_page_start: Address) {
+        let start = if empty_page_start.is_zero() {
+            chunk_start
+        } else {
+            empty_page_start
+        };
+        self.unmark_pages(start, chunk_start + BYTES_IN_CHUNK);
+    }
+
     /// Used when each chunk is done. Only called in debug build.
     #[cfg(debug_assertions)]
     fn debug_sweep_chunk_done(&self, live_bytes_in_the_chunk: usize) {
@@ -469,7 +587,12 @@ impl<VM: VMBinding> MallocSpace<VM> {
     /// This function uses non-atomic accesses to side metadata (although these
     /// non-atomic accesses should not have race conditions associated with them)
     /// as well as calls libc functions (`malloc_usable_size()`, `free()`)
-    fn sweep_chunk_mark_on_side(&self, chunk_start: Address, mark_bit_spec: SideMetadataSpec) {
+    fn sweep_chunk_mark_on_side(
+        &self,
+        chunk_start: Address,
+        mark_bit_spec: SideMetadataSpec,
+        scope: Option<&CollectionScope>,
+    ) {
         #[cfg(debug_assertions)]
         let mut live_bytes = 0;
 
@@ -477,6 +600,8 @@ impl<VM: VMBinding> MallocSpace<VM> {
         let mut address = chunk_start;
         let chunk_end = chunk_start + BYTES_IN_CHUNK;
 
+        // This is also checked for the binding by `MMTKBuilder::build()` (see `vm::checks`),
+        // but not when the MMTk instance is created with `MMTK::new()`.
         debug_assert!(
             crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC.log_bytes_in_region
                 == mark_bit_spec.log_bytes_in_region,
@@ -489,13 +614,30 @@ impl<VM: VMBinding> MallocSpace<VM> {
             128 * (1 << crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC.log_bytes_in_region);
 
         // The start of a possibly empty page. This will be updated during the sweeping, and always points to the next page of last live objects.
-        let mut empty_page_start = Address::ZERO;
+        // The first pages of the chunk are not empty if they are covered by a live object from the previous chunk.
+        let mut empty_page_start = if is_continuation_page(chunk_start) {
+            skip_continuation_pages(chunk_start, chunk_end)
+        } else {
+            Address::ZERO
+        };
+
+        // If nothing has been allocated in the chunk since the last GC, and all the objects that
+        // were live after the last sweep have been marked, there is nothing to sweep. The count of
+        // the marked objects does not tell that in a partial-heap GC.
+        let live_objects = unsafe { get_chunk_live_objects_unsafe(chunk_start) };
+        let skip =
+            scope.is_none() && self.is_chunk_unchanged_and_fully_marked(chunk_start, live_objects);
+        if skip {
+            address = chunk_end;
+        }
+        let mut marked_objects = 0;
 
         // Scan the chunk by every 'bulk_load_size' region.
         while address < chunk_end {
             let alloc_128: u128 =
                 unsafe { load128(&crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC, address) };
             let mark_128: u128 = unsafe { load128(&mark_bit_spec, address) };
+            marked_objects += mark_128.count_ones() as usize;
 
             // Check if there are dead objects in the bulk loaded region
             if alloc_128 ^ mark_128 != 0 {
@@ -509,13 +651,21 @@ impl<VM: VMBinding> MallocSpace<VM> {
                     false,
                 >::new(address, end);
                 for object in bulk_load_scan {
-                    self.sweep_object(object, &mut empty_page_start);
+                    if !self.sweep_object(object, &mut empty_page_start, scope)
+                        && !is_marked::<VM>(&self.mark_bit_spec, object, None)
+                    {
+                        // The object is kept by a partial-heap GC, and is counted as marked.
+                        marked_objects += 1;
+                    }
                 }
             } else {
-                // TODO we aren't actually accounting for the case where an object is alive and spans
-                // a page boundary as we don't know what the object sizes are/what is alive in the bulk region
+                // All the objects in the region are live. We do not know their sizes, but the last
+                // of them may span the pages after the region, which are then continuation pages.
                 if alloc_128 != 0 {
-                    empty_page_start = address + bulk_load_size;
+                    empty_page_start = skip_continuation_pages(
+                        (address + bulk_load_size).align_up(BYTES_IN_PAGE),
+                        chunk_end,
+                    );
                 }
             }
 
@@ -551,7 +701,7 @@ impl<VM: VMBinding> MallocSpace<VM> {
                 }
 
                 debug_assert!(
-                    is_marked::<VM>(object, None),
+                    !self.is_dead(object, scope),
                     "Dead object = {} found after sweep",
                     object
                 );
@@ -563,34 +713,65 @@ impl<VM: VMBinding> MallocSpace<VM> {
         // Clear all the mark bits
         bzero_metadata(&mark_bit_spec, chunk_start, BYTES_IN_CHUNK);
 
-        // If we never updated empty_page_start, the entire chunk is empty.
-        if empty_page_start.is_zero() {
-            self.clean_up_empty_chunk(chunk_start);
+        if skip {
+            debug!("Skip sweeping unchanged chunk {:?}", chunk_start);
+            marked_objects = live_objects;
+        } else {
+            // Unset marks for the free pages after the last live object.
+            self.unmark_free_pages_at_chunk_end(chunk_start, empty_page_start);
+
+            // If we never updated empty_page_start, the entire chunk is empty.
+            if empty_page_start.is_zero() {
+                self.clean_up_empty_chunk(chunk_start);
+            }
         }
+        unsafe { set_chunk_live_objects_unsafe(chunk_start, marked_objects) };
 
         #[cfg(debug_assertions)]
         self.debug_sweep_chunk_done(live_bytes);
     }
 
+    /// Can the sweeping skip a chunk? This is the case if no object has been allocated in the
+    /// chunk since the last GC, and the objects marked in the chunk in the current GC are all the
+    /// `live_objects` that the last sweep of the chunk left. Objects freed explicitly since then
+    /// make the count of the marked objects smaller, so the chunk is swept as usual.
+    fn is_chunk_unchanged_and_fully_marked(
+        &self,
+        chunk_start: Address,
+        live_objects: usize,
+    ) -> bool {
+        let gc_count = VM::VMActivePlan::global().base().gc_stats.gc_count() as u32;
+        live_objects != 0
+            && get_chunk_alloc_epoch(chunk_start) != gc_count
+            && unsafe { get_chunk_marked_objects_unsafe(chunk_start) } == live_objects
+    }
+
     /// This sweep function is called when the mark bit sits in the object header
     ///
     /// This function uses non-atomic accesses to side metadata (although these
     /// non-atomic accesses should not have race conditions associated with them)
     /// as well as calls libc functions (`malloc_usable_size()`, `free()`)
-    fn sweep_chunk_mark_in_header(&self, chunk_start: Address) {
+    fn sweep_chunk_mark_in_header(&self, chunk_start: Address, scope: Option<&CollectionScope>) {
         #[cfg(debug_assertions)]
         let mut live_bytes = 0;
 
         debug!("Check active chunk {:?}", chunk_start);
 
         // The start of a possibly empty page. This will be updated during the sweeping, and always points to the next page of last live objects.
-        let mut empty_page_start = Address::ZERO;
+        // The first pages of the chunk are not empty if they are covered by a live object from the previous chunk.
+        let chunk_end = chunk_start + BYTES_IN_CHUNK;
+        let mut empty_page_start = if is_continuation_page(chunk_start) {
+            skip_continuation_pages(chunk_start, chunk_end)
+        } else {
+            Address::ZERO
+        };
+        let mut live_objects = 0;
 
         let chunk_linear_scan = crate::util::linear_scan::ObjectIterator::<
             VM,
             MallocObjectSize<VM>,
             false,
-        >::new(chunk_start, chunk_start + BYTES_IN_CHUNK);
+        >::new(chunk_start, chunk_end);
         for object in chunk_linear_scan {
             #[cfg(debug_assertions)]
             if ASSERT_ALLOCATION {
@@ -608,10 +789,11 @@ impl<VM: VMBinding> MallocSpace<VM> {
                 );
             }
 
-            let live = !self.sweep_object(object, &mut empty_page_start);
+            let live = !self.sweep_object(object, &mut empty_page_start, scope);
             if live {
                 // Live object. Unset mark bit
-                unset_mark_bit::<VM>(object, None);
+                unset_mark_bit::<VM>(&self.mark_bit_spec, object, None);
+                live_objects += 1;
 
                 #[cfg(debug_assertions)]
                 {
@@ -622,10 +804,16 @@ impl<VM: VMBinding> MallocSpace<VM> {
             }
         }
 
+        // Unset marks for the free pages after the last live object.
+        self.unmark_free_pages_at_chunk_end(chunk_start, empty_page_start);
+
         // If we never updated empty_page_start, the entire chunk is empty.
         if empty_page_start.is_zero() {
             self.clean_up_empty_chunk(chunk_start);
         }
+        // The mark bits are not on the side, so the chunk is never skipped. We still keep the
+        // counts up to date.
+        unsafe { set_chunk_live_objects_unsafe(chunk_start, live_objects) };
 
         #[cfg(debug_assertions)]
         self.debug_sweep_chunk_done(live_bytes);
@@ -1,14 +1,20 @@
 use crate::util::alloc_bit;
+use crate::util::constants::BYTES_IN_PAGE;
 use crate::util::conversions;
+use crate::util::heap::chunk_map::{ChunkMap, ChunkState};
 use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
+use crate::util::heap::regions::{Chunk, PageAccountedRegion, Region, StatefulRegion};
+use crate::util::metadata::compare_exchange_metadata;
 use crate::util::metadata::load_metadata;
 use crate::util::metadata::side_metadata;
 use crate::util::metadata::side_metadata::SideMetadataContext;
 use crate::util::metadata::side_metadata::SideMetadataSpec;
+use crate::util::metadata::side_metadata::TypedSideMetadataSpec;
 use crate::util::metadata::store_metadata;
+use crate::util::metadata::MetadataSpec;
 use crate::util::Address;
 use crate::util::ObjectReference;
-use crate::vm::{ObjectModel, VMBinding};
+use crate::vm::VMBinding;
 use std::sync::atomic::Ordering;
 use std::sync::Mutex;
 
@@ -20,24 +26,19 @@ lazy_static! {
 
     /// Lock to synchronize the mapping of side metadata for a newly allocated chunk by malloc
     static ref CHUNK_MAP_LOCK: Mutex<()> = Mutex::new(());
-    /// Maximum metadata address for the ACTIVE_CHUNK_METADATA_SPEC which is used to check bounds
-    pub static ref MAX_METADATA_ADDRESS: Address = ACTIVE_CHUNK_METADATA_SPEC.upper_bound_address_for_contiguous();
 }
 
 /// Metadata spec for the active chunk byte
 ///
-/// The active chunk metadata is used to track what chunks have been allocated by `malloc()`
+/// MallocSpace uses the chunk map to track what chunks have been allocated by `malloc()`
 /// which is out of our control. We use this metadata later to generate sweep tasks for only
-/// the chunks which have live objects in them.
+/// the chunks which have live objects in them. The memory from `malloc()` never overlaps
+/// with the chunks of other spaces, so a chunk with malloc objects is always allocated to MallocSpace
+/// in the chunk map.
 ///
-/// This metadata is mapped eagerly (as opposed to lazily like the others),
+/// For MallocSpace, this metadata is mapped eagerly (as opposed to lazily like the others),
 /// hence a separate `SideMetadata` instance is required.
-///
-/// This is a global side metadata spec even though it is used only by MallocSpace as
-/// we require its space to be contiguous and mapped only once. Otherwise we risk
-/// overwriting the previous mapping.
-pub(crate) const ACTIVE_CHUNK_METADATA_SPEC: SideMetadataSpec =
-    crate::util::metadata::side_metadata::spec_defs::MS_ACTIVE_CHUNK;
+pub(crate) const ACTIVE_CHUNK_METADATA_SPEC: SideMetadataSpec = ChunkMap::STATE_TABLE;
 
 /// Metadata spec for the active page byte
 ///
@@ -52,9 +53,44 @@ pub(crate) const ACTIVE_CHUNK_METADATA_SPEC: SideMetadataSpec =
 pub(crate) const ACTIVE_PAGE_METADATA_SPEC: SideMetadataSpec =
     crate::util::metadata::side_metadata::spec_defs::MS_ACTIVE_PAGE;
 
+/// Metadata spec for the continuation page byte
+///
+/// A page is a continuation page if it is covered by an object that starts on an earlier page.
+/// Nothing else tells the sweeping that such a page is in use if no object starts on it, e.g. for
+/// the later pages of a live object that spans the end of a bulk loaded region or of a chunk, so
+/// the sweeping checks this before it unmarks the pages. The bytes are set when the object is
+/// allocated and cleared when it is freed. Like the active page metadata, we use a byte per page.
+pub(crate) const CONTINUATION_PAGE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::MS_CONTINUATION_PAGE;
+
 pub(crate) const OFFSET_MALLOC_METADATA_SPEC: SideMetadataSpec =
     crate::util::metadata::side_metadata::spec_defs::MS_OFFSET_MALLOC;
 
+/// Metadata spec for the allocation epoch of a chunk
+///
+/// The allocation epoch is the (truncated) number of finished GCs when an object was last
+/// allocated in the chunk. Together with the live and marked object counts of the chunk, it lets
+/// the sweeping skip a chunk that has not been allocated in since the previous GC, and whose
+/// objects are all marked in the current GC.
+pub(crate) const CHUNK_ALLOC_EPOCH_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::MS_CHUNK_ALLOC_EPOCH;
+
+/// Metadata spec for the number of live objects in a chunk after it was last swept
+pub(crate) const CHUNK_LIVE_OBJECTS_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::MS_CHUNK_LIVE_OBJECTS;
+
+/// Metadata spec for the number of objects marked in a chunk in the current GC
+pub(crate) const CHUNK_MARKED_OBJECTS_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::MS_CHUNK_MARKED_OBJECTS;
+
+// The typed views of the chunk counters. They are 32 bits, and accessed as `u32`.
+const CHUNK_ALLOC_EPOCH: TypedSideMetadataSpec<u32> =
+    TypedSideMetadataSpec::<u32>::new(CHUNK_ALLOC_EPOCH_METADATA_SPEC);
+const CHUNK_LIVE_OBJECTS: TypedSideMetadataSpec<u32> =
+    TypedSideMetadataSpec::<u32>::new(CHUNK_LIVE_OBJECTS_METADATA_SPEC);
+const CHUNK_MARKED_OBJECTS: TypedSideMetadataSpec<u32> =
+    TypedSideMetadataSpec::<u32>::new(CHUNK_MARKED_OBJECTS_METADATA_SPEC);
+
 /// Check if metadata is mapped for a range [addr, addr + size). Metadata is mapped per chunk,
 /// we will go through all the chunks for [address, address + size), and check if they are mapped.
 /// If any of the chunks is not mapped, return false. Otherwise return true.
@@ -110,7 +146,12 @@ fn map_active_chunk_metadata(chunk_start: Address) {
 /// We map the active chunk metadata (if not previously mapped), as well as the alloc bit metadata
 /// and active page metadata here. Note that if [addr, addr + size) crosses multiple chunks, we
 /// will map for each chunk.
-pub fn map_meta_space(metadata: &SideMetadataContext, addr: Address, size: usize) {
+pub fn map_meta_space(
+    metadata: &SideMetadataContext,
+    chunk_map: &ChunkMap,
+    addr: Address,
+    size: usize,
+) {
     // In order to prevent race conditions, we synchronize on the lock first and then
     // check if we need to map the active chunk metadata for `chunk_start`
     let _lock = CHUNK_MAP_LOCK.lock().unwrap();
@@ -143,7 +184,7 @@ pub fn map_meta_space(metadata: &SideMetadataContext, addr: Address, size: usize
         // Set the chunk mark at the end. So if we have chunk mark set, we know we have mapped side metadata
         // for the chunk.
         trace!("set chunk mark bit for {}", start);
-        set_chunk_mark(start);
+        chunk_map.set_allocated(Chunk::from(start), true);
     };
 
     // Go through each chunk, and map for them.
@@ -167,13 +208,12 @@ pub fn has_object_alloced_by_malloc(addr: Address) -> bool {
     is_meta_space_mapped_for_address(addr) && alloc_bit::is_alloced_object(addr)
 }
 
-pub fn is_marked<VM: VMBinding>(object: ObjectReference, ordering: Option<Ordering>) -> bool {
-    load_metadata::<VM>(
-        &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-        object,
-        None,
-        ordering,
-    ) == 1
+pub fn is_marked<VM: VMBinding>(
+    mark_bit_spec: &MetadataSpec,
+    object: ObjectReference,
+    ordering: Option<Ordering>,
+) -> bool {
+    load_metadata::<VM>(mark_bit_spec, object, None, ordering) == 1
 }
 
 #[allow(unused)]
@@ -187,38 +227,23 @@ pub(super) unsafe fn is_page_marked_unsafe(page_addr: Address) -> bool {
 }
 
 pub fn is_chunk_mapped(chunk_start: Address) -> bool {
-    // Since `address_to_meta_address` will translate a data address to a metadata address without caring
-    // if it goes across metadata boundaries, we have to check if we have accidentally gone over the bounds
-    // of the active chunk metadata spec before we check if the metadata has been mapped or not
-    let meta_address =
-        side_metadata::address_to_meta_address(&ACTIVE_CHUNK_METADATA_SPEC, chunk_start);
-    if meta_address < *MAX_METADATA_ADDRESS {
-        meta_address.is_mapped()
-    } else {
-        false
-    }
+    ChunkMap::is_mapped(Chunk::from(chunk_start))
 }
 
 pub fn is_chunk_marked(chunk_start: Address) -> bool {
-    side_metadata::load_atomic(&ACTIVE_CHUNK_METADATA_SPEC, chunk_start, Ordering::SeqCst) == 1
-}
-
-pub unsafe fn is_chunk_marked_unsafe(chunk_start: Address) -> bool {
-    side_metadata::load(&ACTIVE_CHUNK_METADATA_SPEC, chunk_start) == 1
+    Chunk::from(chunk_start).get_state() != ChunkState::Free
 }
 
 pub fn set_alloc_bit(object: ObjectReference) {
     alloc_bit::set_alloc_bit(object);
 }
 
-pub fn set_mark_bit<VM: VMBinding>(object: ObjectReference, ordering: Option<Ordering>) {
-    store_metadata::<VM>(
-        &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-        object,
-        1,
-        None,
-        ordering,
-    );
+pub fn set_mark_bit<VM: VMBinding>(
+    mark_bit_spec: &MetadataSpec,
+    object: ObjectReference,
+    ordering: Option<Ordering>,
+) {
+    store_metadata::<VM>(mark_bit_spec, object, 1, None, ordering);
 }
 
 #[allow(unused)]
@@ -226,17 +251,213 @@ pub fn unset_alloc_bit(object: ObjectReference) {
     alloc_bit::unset_alloc_bit(object);
 }
 
-pub(super) fn set_page_mark(page_addr: Address) {
-    side_metadata::store_atomic(&ACTIVE_PAGE_METADATA_SPEC, page_addr, 1, Ordering::SeqCst);
+/// Mark an object. Return false if the object is already marked.
+pub(super) fn try_set_mark_bit<VM: VMBinding>(
+    mark_bit_spec: &MetadataSpec,
+    object: ObjectReference,
+) -> bool {
+    compare_exchange_metadata::<VM>(
+        mark_bit_spec,
+        object,
+        0,
+        1,
+        None,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    )
+}
+
+/// Record that an object has been allocated in a chunk in the given epoch.
+pub(super) fn set_chunk_alloc_epoch(chunk_start: Address, epoch: u32) {
+    CHUNK_ALLOC_EPOCH.store_atomic(chunk_start, epoch, Ordering::Relaxed);
 }
 
-pub(super) fn set_chunk_mark(chunk_start: Address) {
-    side_metadata::store_atomic(
-        &ACTIVE_CHUNK_METADATA_SPEC,
-        chunk_start,
+pub(super) fn get_chunk_alloc_epoch(chunk_start: Address) -> u32 {
+    CHUNK_ALLOC_EPOCH.load_atomic(chunk_start, Ordering::Relaxed)
+}
+
+/// Count the objects marked in a chunk.
+fn add_chunk_marked_objects(chunk_start: Address, objects: usize) {
+    CHUNK_MARKED_OBJECTS.fetch_add_atomic(chunk_start, objects as u32, Ordering::Relaxed);
+}
+
+/// The objects that a GC worker has marked in a chunk, and not yet added to the count of the
+/// chunk. The worker adds them when it marks objects in another chunk, or when it finishes a work
+/// packet, so the counts of a chunk are not updated by all the workers for each object.
+pub(crate) struct ChunkMarkedObjects {
+    chunk_start: Address,
+    objects: usize,
+}
+
+impl ChunkMarkedObjects {
+    pub(crate) const fn new() -> Self {
+        Self {
+            chunk_start: Address::ZERO,
+            objects: 0,
+        }
+    }
+
+    /// Count an object marked in a chunk.
+    #[inline(always)]
+    pub(super) fn inc(&mut self, chunk_start: Address) {
+        if self.chunk_start != chunk_start {
+            self.flush();
+            self.chunk_start = chunk_start;
+        }
+        self.objects += 1;
+    }
+
+    /// Add the objects counted so far to the count of their chunk.
+    #[inline(always)]
+    pub(crate) fn flush(&mut self) {
+        if self.objects != 0 {
+            add_chunk_marked_objects(self.chunk_start, self.objects);
+            self.objects = 0;
+        }
+    }
+}
+
+/// The number of objects marked in a chunk in the current GC. This is only called by the sweeping
+/// of the chunk, so it accesses the metadata non-atomically.
+pub(super) unsafe fn get_chunk_marked_objects_unsafe(chunk_start: Address) -> usize {
+    CHUNK_MARKED_OBJECTS.load(chunk_start) as usize
+}
+
+/// The number of live objects in a chunk after it was last swept.
+pub(super) unsafe fn get_chunk_live_objects_unsafe(chunk_start: Address) -> usize {
+    CHUNK_LIVE_OBJECTS.load(chunk_start) as usize
+}
+
+/// Record the number of live objects in a chunk at the end of its sweeping, and reset its count
+/// of marked objects for the next GC.
+pub(super) unsafe fn set_chunk_live_objects_unsafe(chunk_start: Address, live_objects: usize) {
+    CHUNK_LIVE_OBJECTS.store(chunk_start, live_objects as u32);
+    CHUNK_MARKED_OBJECTS.store(chunk_start, 0);
+}
+
+/// Mark a page. Return false if the page is already marked.
+pub(super) fn try_set_page_mark(page_addr: Address) -> bool {
+    side_metadata::compare_exchange_atomic(
+        &ACTIVE_PAGE_METADATA_SPEC,
+        page_addr,
+        0,
         1,
         Ordering::SeqCst,
-    );
+        Ordering::SeqCst,
+    )
+}
+
+/// Unmark a page. Return false if the page is not marked.
+fn try_unset_page_mark(page_addr: Address) -> bool {
+    side_metadata::compare_exchange_atomic(
+        &ACTIVE_PAGE_METADATA_SPEC,
+        page_addr,
+        1,
+        0,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    )
+}
+
+/// Update the marks of the pages in `[start, end)` with `update`, which returns true if it changes
+/// the mark of a page. The changed pages are accounted in their chunks with `account`, which is
+/// called once for each chunk that the range covers. Return the number of changed pages.
+fn update_page_marks(
+    start: Address,
+    end: Address,
+    update: impl Fn(Address) -> bool,
+    account: impl Fn(Chunk, usize),
+) -> usize {
+    debug_assert!(start.is_aligned_to(BYTES_IN_PAGE));
+    let mut changed = 0;
+    let mut chunk = Chunk::containing_address(start);
+    let mut changed_in_chunk = 0;
+    let mut page = start;
+    while page < end {
+        if page >= chunk.end() {
+            account(chunk, changed_in_chunk);
+            chunk = Chunk::containing_address(page);
+            changed_in_chunk = 0;
+        }
+        if update(page) {
+            changed += 1;
+            changed_in_chunk += 1;
+        }
+        page += BYTES_IN_PAGE;
+    }
+    if changed_in_chunk != 0 {
+        account(chunk, changed_in_chunk);
+    }
+    changed
+}
+
+/// Mark the pages that `[start, end)` overlaps as active, and count the newly marked pages as used
+/// in their chunks. Return the number of newly marked pages.
+pub(super) fn mark_pages(start: Address, end: Address) -> usize {
+    update_page_marks(
+        conversions::page_align_down(start),
+        end,
+        try_set_page_mark,
+        |chunk, pages| chunk.add_used_pages(pages),
+    )
+}
+
+/// Unmark the active pages in `[start, end)`, and count them as free in their chunks. `start` must
+/// be page aligned. Return the number of unmarked pages.
+pub(super) fn unmark_pages(start: Address, end: Address) -> usize {
+    update_page_marks(start, end, try_unset_page_mark, |chunk, pages| {
+        chunk.release_used_pages(pages);
+    })
+}
+
+/// Unmark the pages that the memory in `[start, start + bytes)` fully covers, and count them as
+/// free in their chunks. No other memory can be on these pages, while the pages that the memory
+/// partially covers may be shared with other memory, and are left to the sweeping. This needs to be
+/// called before the memory is freed. Otherwise, the pages may be reused and marked again. Return
+/// the number of unmarked pages.
+pub(super) fn unmark_covered_pages(start: Address, bytes: usize) -> usize {
+    unmark_pages(
+        start.align_up(BYTES_IN_PAGE),
+        (start + bytes).align_down(BYTES_IN_PAGE),
+    )
+}
+
+/// Mark the pages after the first page of the object in `[start, start + bytes)` as continuation
+/// pages.
+pub(super) fn set_continuation_pages(start: Address, bytes: usize) {
+    let mut page = conversions::page_align_down(start) + BYTES_IN_PAGE;
+    while page < start + bytes {
+        side_metadata::store_atomic(&CONTINUATION_PAGE_METADATA_SPEC, page, 1, Ordering::SeqCst);
+        page += BYTES_IN_PAGE;
+    }
+}
+
+/// Unmark the continuation pages of the object in `[start, start + bytes)` when it is freed.
+pub(super) fn clear_continuation_pages(start: Address, bytes: usize) {
+    let mut page = conversions::page_align_down(start) + BYTES_IN_PAGE;
+    while page < start + bytes {
+        side_metadata::store_atomic(&CONTINUATION_PAGE_METADATA_SPEC, page, 0, Ordering::SeqCst);
+        page += BYTES_IN_PAGE;
+    }
+}
+
+/// Is the page covered by an object that starts on an earlier page?
+pub(super) fn is_continuation_page(page_addr: Address) -> bool {
+    side_metadata::load_atomic(
+        &CONTINUATION_PAGE_METADATA_SPEC,
+        page_addr,
+        Ordering::SeqCst,
+    ) == 1
+}
+
+/// Skip the continuation pages from `page`, and return the first page before `chunk_end` that
+/// is not covered by an object that starts on an earlier page.
+pub(super) fn skip_continuation_pages(page: Address, chunk_end: Address) -> Address {
+    let mut page = page;
+    while page < chunk_end && is_continuation_page(page) {
+        page += BYTES_IN_PAGE;
+    }
+    page
 }
 
 pub(super) fn is_offset_malloc(address: Address) -> bool {
@@ -256,22 +477,12 @@ pub unsafe fn unset_alloc_bit_unsafe(object: ObjectReference) {
 }
 
 #[allow(unused)]
-pub fn unset_mark_bit<VM: VMBinding>(object: ObjectReference, ordering: Option<Ordering>) {
-    store_metadata::<VM>(
-        &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-        object,
-        0,
-        None,
-        ordering,
-    );
-}
-
-pub(super) unsafe fn unset_page_mark_unsafe(page_addr: Address) {
-    side_metadata::store(&ACTIVE_PAGE_METADATA_SPEC, page_addr, 0);
-}
-
-pub(super) unsafe fn unset_chunk_mark_unsafe(chunk_start: Address) {
-    side_metadata::store(&ACTIVE_CHUNK_METADATA_SPEC, chunk_start, 0);
+pub fn unset_mark_bit<VM: VMBinding>(
+    mark_bit_spec: &MetadataSpec,
+    object: ObjectReference,
+    ordering: Option<Ordering>,
+) {
+    store_metadata::<VM>(mark_bit_spec, object, 0, None, ordering);
 }
 
 /// Load u128 bits of side metadata
@@ -286,3 +497,187 @@ pub(super) unsafe fn load128(metadata_spec: &SideMetadataSpec, data_addr: Addres
 
     meta_addr.load::<u128>() as u128
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    #[test]
+    fn test_page_marks_across_chunks() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![ACTIVE_PAGE_METADATA_SPEC, Chunk::USED_PAGES_TABLE],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK)
+                        .unwrap();
+                    let first = Chunk::from(HEAP_START);
+                    let second = Chunk::from(HEAP_START + BYTES_IN_CHUNK);
+                    let boundary = second.start();
+
+                    // Memory from 1.5 pages before the chunk boundary to 2.5 pages after it.
+                    let start = boundary - BYTES_IN_PAGE - BYTES_IN_PAGE / 2;
+                    let bytes = 4 * BYTES_IN_PAGE;
+                    assert_eq!(mark_pages(start, start + bytes), 5);
+                    assert_eq!(first.used_pages(), 2);
+                    assert_eq!(second.used_pages(), 3);
+                    // A page is counted once, even if more memory is on it.
+                    let next = start + bytes;
+                    assert_eq!(mark_pages(next, next + BYTES_IN_PAGE), 1);
+                    assert_eq!(second.used_pages(), 4);
+
+                    // Freeing the memory unmarks the pages that only it is on.
+                    assert_eq!(unmark_covered_pages(start, bytes), 3);
+                    assert_eq!(first.used_pages(), 1);
+                    assert_eq!(second.used_pages(), 1);
+                    assert!(is_page_marked(conversions::page_align_down(start)));
+                    assert!(!is_page_marked(boundary));
+                    assert!(is_page_marked(conversions::page_align_down(next)));
+
+                    // The rest is unmarked by the sweeping.
+                    assert_eq!(unmark_pages(first.start(), second.end()), 2);
+                    assert_eq!(unmark_pages(first.start(), second.end()), 0);
+                    assert_eq!(first.used_pages(), 0);
+                    assert_eq!(second.used_pages(), 0);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_continuation_pages_of_spanning_object() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![CONTINUATION_PAGE_METADATA_SPEC],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK)
+                        .unwrap();
+                    let boundary = HEAP_START + BYTES_IN_CHUNK;
+                    let chunk_end = boundary + BYTES_IN_CHUNK;
+
+                    // A live object that starts in the middle of a page, covers the rest of the
+                    // chunk and the first two pages of the next chunk and stops in the third.
+                    let start = boundary - 2 * BYTES_IN_PAGE - BYTES_IN_PAGE / 2;
+                    let bytes = 5 * BYTES_IN_PAGE;
+                    set_continuation_pages(start, bytes);
+                    assert!(!is_continuation_page(conversions::page_align_down(start)));
+                    assert!(is_continuation_page(boundary - BYTES_IN_PAGE));
+                    assert!(is_continuation_page(boundary + 2 * BYTES_IN_PAGE));
+                    assert!(!is_continuation_page(boundary + 3 * BYTES_IN_PAGE));
+
+                    // If a bulk loaded region of live objects ends in the object, the pages up to
+                    // the last page of the object are not empty.
+                    let region_end = conversions::page_align_down(start) + BYTES_IN_PAGE;
+                    assert_eq!(skip_continuation_pages(region_end, boundary), boundary);
+                    // The sweeping of the next chunk starts after the object.
+                    assert_eq!(
+                        skip_continuation_pages(boundary, chunk_end),
+                        boundary + 3 * BYTES_IN_PAGE
+                    );
+
+                    // Once the object is freed, the pages may be empty.
+                    clear_continuation_pages(start, bytes);
+                    assert_eq!(skip_continuation_pages(region_end, boundary), region_end);
+                    assert_eq!(skip_continuation_pages(boundary, chunk_end), boundary);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_unmark_covered_pages_in_one_page() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![ACTIVE_PAGE_METADATA_SPEC, Chunk::USED_PAGES_TABLE],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_CHUNK)
+                        .unwrap();
+                    let chunk = Chunk::from(HEAP_START);
+                    let start = HEAP_START + 16usize;
+                    assert_eq!(mark_pages(start, start + 32usize), 1);
+                    // The page may be shared with other memory.
+                    assert_eq!(unmark_covered_pages(start, 32), 0);
+                    assert_eq!(chunk.used_pages(), 1);
+                    // A whole page is unmarked.
+                    assert_eq!(unmark_covered_pages(HEAP_START, BYTES_IN_PAGE), 1);
+                    assert_eq!(chunk.used_pages(), 0);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_chunk_marked_objects() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![],
+                local: vec![
+                    CHUNK_LIVE_OBJECTS_METADATA_SPEC,
+                    CHUNK_MARKED_OBJECTS_METADATA_SPEC,
+                ],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK)
+                        .unwrap();
+                    let first = HEAP_START;
+                    let second = HEAP_START + BYTES_IN_CHUNK;
+                    let marked =
+                        |chunk_start| unsafe { get_chunk_marked_objects_unsafe(chunk_start) };
+
+                    let mut counter = ChunkMarkedObjects::new();
+                    counter.inc(first);
+                    counter.inc(first);
+                    // Nothing is added until the worker moves to another chunk.
+                    assert_eq!(marked(first), 0);
+                    counter.inc(second);
+                    assert_eq!(marked(first), 2);
+                    assert_eq!(marked(second), 0);
+                    // Or finishes the packet.
+                    counter.flush();
+                    assert_eq!(marked(second), 1);
+                    counter.flush();
+                    assert_eq!(marked(second), 1);
+
+                    // Other workers add to the same counts.
+                    let mut other = ChunkMarkedObjects::new();
+                    other.inc(first);
+                    other.flush();
+                    assert_eq!(marked(first), 3);
+
+                    unsafe {
+                        set_chunk_live_objects_unsafe(first, 3);
+                        set_chunk_live_objects_unsafe(second, 1);
+                    }
+                    assert_eq!(marked(first), 0);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, 2 * BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+}
@@ -8,12 +8,15 @@ use crate::util::constants::LOG_BYTES_IN_WORD;
 use crate::util::copy::CopySemantics;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
 use crate::util::heap::{HeapMeta, MonotonePageResource, PageResource, VMRequest};
-use crate::util::metadata::load_metadata;
 use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSpec};
 use crate::util::metadata::{compare_exchange_metadata, extract_side_metadata};
+use crate::util::metadata::{load_metadata, mark_bit, MetadataSpec};
 use crate::util::{alloc_bit, Address, ObjectReference};
 use crate::{vm::*, ObjectQueue};
 use atomic::Ordering;
+use std::ops::Range;
+use std::sync::atomic::AtomicUsize;
+use std::sync::RwLock;
 
 pub(crate) const TRACE_KIND_MARK: TraceKind = 0;
 pub(crate) const TRACE_KIND_FORWARD: TraceKind = 1;
@@ -21,6 +24,11 @@ pub(crate) const TRACE_KIND_FORWARD: TraceKind = 1;
 pub struct MarkCompactSpace<VM: VMBinding> {
     common: CommonSpace<VM>,
     pr: MonotonePageResource<VM>,
+    /// The compaction regions of the current GC, in address order. They are computed while
+    /// calculating forwarding pointers, and each is compacted by a work packet.
+    compaction_regions: RwLock<Vec<CompactionRegion>>,
+    /// The mark bit of the objects in this space (see `ObjectModel::use_side_mark_bit`).
+    mark_bit_spec: MetadataSpec,
 }
 
 const GC_MARK_BIT_MASK: usize = 1;
@@ -31,6 +39,97 @@ const GC_MARK_BIT_MASK: usize = 1;
 pub const GC_EXTRA_HEADER_WORD: usize = 1;
 const GC_EXTRA_HEADER_BYTES: usize = GC_EXTRA_HEADER_WORD << LOG_BYTES_IN_WORD;
 
+/// The live objects are grouped into compaction regions by address windows of this size. A window
+/// without live objects has no compaction region, so compaction skips dead memory in bulk.
+const LOG_BYTES_IN_COMPACTION_WINDOW: usize = 18;
+
+/// The live objects of one address window, and where they move to.
+struct CompactionRegion {
+    /// The first live object of the region.
+    first_object: ObjectReference,
+    /// The memory of the live objects before compaction. Compacting the region reads it.
+    from: Range<Address>,
+    /// The memory of the live objects after compaction. Compacting the region writes it.
+    to: Range<Address>,
+    /// True if the region is a single object that moves to a higher start or end address. An
+    /// object may move up if it grows when it is copied (see `ObjectModel::get_size_when_copied`).
+    /// Such an object is compacted on its own, as its copy may overwrite the objects after it.
+    moves_up: bool,
+    /// The regions whose `to` overlaps the `from` of this region. They wait for this region to be
+    /// compacted, otherwise they may overwrite live objects that have not been copied yet.
+    dependents: Range<usize>,
+    /// The number of regions whose `from` overlaps the `to` of this region, and that have not been
+    /// compacted.
+    pending_dependencies: AtomicUsize,
+}
+
+impl CompactionRegion {
+    fn new(first_object: ObjectReference, from: Range<Address>, to: Range<Address>) -> Self {
+        Self {
+            first_object,
+            from,
+            to,
+            moves_up: false,
+            dependents: 0..0,
+            pending_dependencies: AtomicUsize::new(0),
+        }
+    }
+
+    /// Does the region depend on `other`, i.e. does it overwrite the memory that `other` reads?
+    fn depends_on(&self, other: &CompactionRegion) -> bool {
+        self.to.start < other.from.end && other.from.start < self.to.end
+    }
+}
+
+/// Compute the dependencies of the compaction regions, and return the indices of the regions
+/// that can be compacted right away. Both the `from` and the `to` ranges of the regions are
+/// sorted and disjoint, so the dependencies and the dependents of a region are contiguous ranges
+/// of regions. Objects usually move down, but a region may also depend on the regions after it
+/// if its objects grow when they are copied.
+fn resolve_compaction_dependencies(regions: &mut [CompactionRegion]) -> Vec<usize> {
+    let mut ready = vec![];
+    for i in 0..regions.len() {
+        // The regions whose `from` overlaps the `to` of this region.
+        let first = regions.partition_point(|r| r.from.end <= regions[i].to.start);
+        let last = regions.partition_point(|r| r.from.start < regions[i].to.end);
+        let pending = (first..last).filter(|&j| j != i).count();
+        *regions[i].pending_dependencies.get_mut() = pending;
+        if pending == 0 {
+            ready.push(i);
+        }
+        // The regions whose `to` overlaps the `from` of this region.
+        let first = regions.partition_point(|r| r.to.end <= regions[i].from.start);
+        let last = regions.partition_point(|r| r.to.start < regions[i].from.end);
+        regions[i].dependents = first..last;
+    }
+    debug_assert!(
+        compaction_dependencies_are_acyclic(regions, &ready),
+        "The compaction regions depend on each other"
+    );
+    ready
+}
+
+/// Check that all the regions can be compacted after the `ready` regions are compacted.
+fn compaction_dependencies_are_acyclic(regions: &[CompactionRegion], ready: &[usize]) -> bool {
+    let mut pending: Vec<usize> = regions
+        .iter()
+        .map(|r| r.pending_dependencies.load(Ordering::Relaxed))
+        .collect();
+    let mut ready = ready.to_vec();
+    let mut compacted = 0;
+    while let Some(index) = ready.pop() {
+        compacted += 1;
+        for i in regions[index].dependents.clone().filter(|&i| i != index) {
+            debug_assert!(regions[i].depends_on(&regions[index]));
+            pending[i] -= 1;
+            if pending[i] == 0 {
+                ready.push(i);
+            }
+        }
+    }
+    compacted == regions.len()
+}
+
 impl<VM: VMBinding> SFT for MarkCompactSpace<VM> {
     fn name(&self) -> &str {
         self.get_name()
@@ -49,7 +148,7 @@ impl<VM: VMBinding> SFT for MarkCompactSpace<VM> {
     fn is_live(&self, object: ObjectReference) -> bool {
         // Sanity checker cannot use this method to do the verification
         // since the mark bit will be cleared during the second trace(update forwarding pointer)
-        Self::is_marked(object)
+        self.is_marked(object)
     }
 
     fn is_movable(&self) -> bool {
@@ -193,7 +292,8 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
         mmapper: &'static Mmapper,
         heap: &mut HeapMeta,
     ) -> Self {
-        let local_specs = extract_side_metadata(&[*VM::VMObjectModel::LOCAL_MARK_BIT_SPEC]);
+        let mark_bit_spec = mark_bit::mark_bit_spec_for_space::<VM>(name);
+        let local_specs = extract_side_metadata(&[mark_bit_spec]);
         let common = CommonSpace::new(
             SpaceOptions {
                 name,
@@ -218,6 +318,8 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
                 MonotonePageResource::new_contiguous(common.start, common.extent, 0, vm_map)
             },
             common,
+            compaction_regions: RwLock::new(vec![]),
+            mark_bit_spec,
         }
     }
 
@@ -235,7 +337,7 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
             "{:x}: alloc bit not set",
             object
         );
-        if MarkCompactSpace::<VM>::test_and_mark(object) {
+        if self.test_and_mark(object) {
             queue.enqueue(object);
         }
         object
@@ -253,27 +355,23 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
         );
         // from this stage and onwards, mark bit is no longer needed
         // therefore, it can be reused to save one extra bit in metadata
-        if MarkCompactSpace::<VM>::test_and_clear_mark(object) {
+        if self.test_and_clear_mark(object) {
             queue.enqueue(object);
         }
 
         Self::get_header_forwarding_pointer(object)
     }
 
-    pub fn test_and_mark(object: ObjectReference) -> bool {
+    pub fn test_and_mark(&self, object: ObjectReference) -> bool {
         loop {
-            let old_value = load_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-                object,
-                None,
-                Some(Ordering::SeqCst),
-            );
+            let old_value =
+                load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
             let mark_bit = old_value & GC_MARK_BIT_MASK;
             if mark_bit != 0 {
                 return false;
             }
             if compare_exchange_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                &self.mark_bit_spec,
                 object,
                 old_value,
                 1,
@@ -287,21 +385,17 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
         true
     }
 
-    pub fn test_and_clear_mark(object: ObjectReference) -> bool {
+    pub fn test_and_clear_mark(&self, object: ObjectReference) -> bool {
         loop {
-            let old_value = load_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-                object,
-                None,
-                Some(Ordering::SeqCst),
-            );
+            let old_value =
+                load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
             let mark_bit = old_value & GC_MARK_BIT_MASK;
             if mark_bit == 0 {
                 return false;
             }
 
             if compare_exchange_metadata::<VM>(
-                &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                &self.mark_bit_spec,
                 object,
                 old_value,
                 0,
@@ -315,40 +409,66 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
         true
     }
 
-    pub fn is_marked(object: ObjectReference) -> bool {
-        let old_value = load_metadata::<VM>(
-            &VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
-            object,
-            None,
-            Some(Ordering::SeqCst),
-        );
+    pub fn is_marked(&self, object: ObjectReference) -> bool {
+        let old_value =
+            load_metadata::<VM>(&self.mark_bit_spec, object, None, Some(Ordering::SeqCst));
         let mark_bit = old_value & GC_MARK_BIT_MASK;
         mark_bit != 0
     }
 
-    pub fn to_be_compacted(object: ObjectReference) -> bool {
-        Self::is_marked(object)
+    pub fn to_be_compacted(&self, object: ObjectReference) -> bool {
+        self.is_marked(object)
     }
 
+    /// Calculate the forwarding pointers of the live objects, and group them into compaction
+    /// regions. The alloc bits of dead objects are cleared here, so that compaction only visits
+    /// live objects.
     pub fn calculate_forwarding_pointer(&self) {
         let start = self.common.start;
         let end = self.pr.cursor();
         let mut to = start;
+        let mut regions: Vec<CompactionRegion> = vec![];
+        let mut current_window = usize::MAX;
 
         let linear_scan =
             crate::util::linear_scan::ObjectIterator::<VM, MarkCompactObjectSize<VM>, true>::new(
                 start, end,
             );
-        for obj in linear_scan.filter(|obj| Self::to_be_compacted(*obj)) {
-            let copied_size =
-                VM::VMObjectModel::get_size_when_copied(obj) + Self::HEADER_RESERVED_IN_BYTES;
+        for obj in linear_scan {
+            if !self.to_be_compacted(obj) {
+                alloc_bit::unset_addr_alloc_bit(obj.to_address());
+                continue;
+            }
             let align = VM::VMObjectModel::get_align_when_copied(obj);
             let offset = VM::VMObjectModel::get_align_offset_when_copied(obj);
-            to = align_allocation_no_fill::<VM>(to, align, offset);
+            let from_start =
+                VM::VMObjectModel::object_start_ref(obj) - Self::HEADER_RESERVED_IN_BYTES;
+            let from_end =
+                VM::VMObjectModel::object_start_ref(obj) + VM::VMObjectModel::get_current_size(obj);
+            let to_start = align_allocation_no_fill::<VM>(to, align, offset);
             let new_obj = VM::VMObjectModel::get_reference_when_copied_to(
                 obj,
-                to + Self::HEADER_RESERVED_IN_BYTES,
+                to_start + Self::HEADER_RESERVED_IN_BYTES,
             );
+            // An object that stays in place is not copied, so it keeps its current size. Otherwise
+            // it may grow when copied, e.g. for the word of its hash code.
+            let copied_size = if new_obj == obj {
+                from_end - from_start
+            } else {
+                VM::VMObjectModel::get_size_when_copied(obj) + Self::HEADER_RESERVED_IN_BYTES
+            };
+            // An object that moves up starts a region of its own, and the object after it starts
+            // another region.
+            let moves_up = to_start > from_start || to_start + copied_size > from_end;
+            let after_moving_up = regions.last().map_or(false, |r| r.moves_up);
+            let window = obj.to_address().as_usize() >> LOG_BYTES_IN_COMPACTION_WINDOW;
+            if window != current_window || moves_up || after_moving_up {
+                current_window = window;
+                let mut region = CompactionRegion::new(obj, from_start..from_start, to..to);
+                region.moves_up = moves_up;
+                regions.push(region);
+            }
+            to = to_start;
 
             Self::store_header_forwarding_pointer(obj, new_obj);
 
@@ -361,45 +481,111 @@ impl<VM: VMBinding> MarkCompactSpace<VM> {
             );
 
             to += copied_size;
+            let region = regions.last_mut().unwrap();
+            region.from.end = from_end;
+            region.to.end = to;
         }
-        debug!("Calculate forward end: to = {}", to);
+        debug!(
+            "Calculate forward end: to = {}, {} compaction regions",
+            to,
+            regions.len()
+        );
+        *self.compaction_regions.write().unwrap() = regions;
     }
 
-    pub fn compact(&self) {
-        let start = self.common.start;
-        let end = self.pr.cursor();
-        let mut to = end;
+    /// Prepare for compaction, and return the indices of the compaction regions that can be
+    /// compacted first. The others become ready as their dependencies are compacted
+    /// (see [`MarkCompactSpace::compact_region`]).
+    pub fn prepare_compaction(&self) -> Vec<usize> {
+        let mut regions = self.compaction_regions.write().unwrap();
+        let ready = resolve_compaction_dependencies(&mut regions);
+        let to = regions
+            .last()
+            .map_or(self.common.start, |region| region.to.end);
+        debug!("Compact end: to = {}", to);
+        // reset the bump pointer. No mutator allocates until the compaction is done.
+        self.pr.reset_cursor(to);
+        ready
+    }
 
-        let linear_scan =
-            crate::util::linear_scan::ObjectIterator::<VM, MarkCompactObjectSize<VM>, true>::new(
-                start, end,
-            );
-        for obj in linear_scan {
+    /// Compact the live objects of a compaction region, and return the indices of the regions
+    /// that become ready to be compacted.
+    pub fn compact_region(&self, index: usize) -> Vec<usize> {
+        let regions = self.compaction_regions.read().unwrap();
+        let region = &regions[index];
+        debug_assert_eq!(region.pending_dependencies.load(Ordering::SeqCst), 0);
+        if region.moves_up {
+            // Do not scan the alloc bits of the region: the copy of the object may set an alloc
+            // bit after the object.
+            self.compact_objects(std::iter::once(region.first_object));
+        } else {
+            self.compact_objects(crate::util::linear_scan::ObjectIterator::<
+                VM,
+                MarkCompactObjectSize<VM>,
+                true,
+            >::new(
+                region.first_object.to_address(), region.from.end
+            ));
+        }
+
+        let mut ready = vec![];
+        for i in region.dependents.clone().filter(|&i| i != index) {
+            if regions[i]
+                .pending_dependencies
+                .fetch_sub(1, Ordering::SeqCst)
+                == 1
+            {
+                ready.push(i);
+            }
+        }
+        ready
+    }
+
+    /// Move the live objects to their forwarding pointers.
+    fn compact_objects(&self, objects: impl Iterator<Item = ObjectReference>) {
+        #[cfg(feature = "graph_export")]
+        let exporter = &VM::VMActivePlan::global().base().graph_exporter;
+        #[cfg(feature = "graph_export")]
+        let mut graph = crate::util::graph_export::GraphBuffer::default();
+        for obj in objects {
             // clear the alloc bit
             alloc_bit::unset_addr_alloc_bit(obj.to_address());
 
             let forwarding_pointer = Self::get_header_forwarding_pointer(obj);
 
             trace!("Compact {} to {}", obj, forwarding_pointer);
-            if !forwarding_pointer.is_null() {
-                let copied_size = VM::VMObjectModel::get_size_when_copied(obj);
-                let new_object = forwarding_pointer;
-                Self::clear_header_forwarding_pointer(new_object);
-
-                // copy object
-                trace!(" copy from {} to {}", obj, new_object);
-                let end_of_new_object = VM::VMObjectModel::copy_to(obj, new_object, Address::ZERO);
-                // update alloc_bit,
-                alloc_bit::set_alloc_bit(new_object);
-                to = new_object.to_address() + copied_size;
-                debug_assert_eq!(end_of_new_object, to);
+            debug_assert!(!forwarding_pointer.is_null());
+            let new_object = forwarding_pointer;
+            Self::clear_header_forwarding_pointer(new_object);
+            if new_object == obj {
+                // The object stays in place. It is not copied, so it keeps its size and its hash
+                // state (see `calculate_forwarding_pointer`).
+                alloc_bit::set_alloc_bit(obj);
+                continue;
             }
+            let copied_size = VM::VMObjectModel::get_size_when_copied(obj);
+
+            // copy object
+            trace!(" copy from {} to {}", obj, new_object);
+            let end_of_new_object = VM::VMObjectModel::copy_to(obj, new_object, Address::ZERO);
+            // update alloc_bit,
+            alloc_bit::set_alloc_bit(new_object);
+            #[cfg(feature = "object_age")]
+            crate::util::object_age::copy_birth_epoch(obj, new_object);
+            #[cfg(feature = "object_hash")]
+            crate::util::object_hash::fixup_after_copy::<VM>(obj, new_object);
+            #[cfg(feature = "object_user_data")]
+            crate::util::object_user_data::copy_user_data(obj, new_object);
+            #[cfg(feature = "heap_ids")]
+            crate::util::heap_id::copy_heap_id(obj, new_object);
+            #[cfg(feature = "immutable_objects")]
+            crate::util::immutable_object::copy_state(obj, new_object);
+            #[cfg(feature = "graph_export")]
+            exporter.object_moved(&mut graph, obj, new_object);
+            debug_assert_eq!(end_of_new_object, new_object.to_address() + copied_size);
         }
-
-        debug!("Compact end: to = {}", to);
-
-        // reset the bump pointer
-        self.pr.reset_cursor(to);
+        #[cfg(feature = "graph_export")]
+        exporter.flush(&mut graph);
     }
 }
 
@@ -410,3 +596,63 @@ impl<VM: VMBinding> crate::util::linear_scan::LinearScanObjectSize for MarkCompa
         VM::VMObjectModel::get_current_size(object)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(from: Range<usize>, to: Range<usize>) -> CompactionRegion {
+        let addr = |a: usize| unsafe { Address::from_usize(a) };
+        CompactionRegion::new(
+            unsafe { addr(from.start).to_object_reference() },
+            addr(from.start)..addr(from.end),
+            addr(to.start)..addr(to.end),
+        )
+    }
+
+    #[test]
+    fn test_compaction_dependencies() {
+        let mut regions = vec![
+            // does not move
+            region(0x1000..0x2000, 0x1000..0x2000),
+            // moves into the free memory after the first region
+            region(0x8000..0x9000, 0x2000..0x3000),
+            // overlaps the from range of the second region
+            region(0x9000..0xa000, 0x3000..0x8800),
+            // overlaps the from ranges of the second and the third regions
+            region(0xa000..0xb000, 0x8800..0x9800),
+        ];
+        let ready = resolve_compaction_dependencies(&mut regions);
+        assert_eq!(ready, vec![0, 1]);
+        let dependents: Vec<Range<usize>> = regions.iter().map(|r| r.dependents.clone()).collect();
+        assert_eq!(dependents, vec![0..1, 2..4, 3..4, 4..4]);
+        let pending: Vec<usize> = regions
+            .iter()
+            .map(|r| r.pending_dependencies.load(Ordering::SeqCst))
+            .collect();
+        assert_eq!(pending, vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_compaction_dependencies_moving_up() {
+        let mut regions = vec![
+            // moves down
+            region(0x2000..0x3000, 0x1000..0x2000),
+            // grows when copied, and overwrites the start of the next region
+            region(0x3000..0x3010, 0x2000..0x4008),
+            // moves up in place
+            region(0x4000..0x5000, 0x4008..0x5000),
+        ];
+        // The second region waits for the first region, and for the third region that it
+        // overwrites.
+        let ready = resolve_compaction_dependencies(&mut regions);
+        assert_eq!(ready, vec![0, 2]);
+        let dependents: Vec<Range<usize>> = regions.iter().map(|r| r.dependents.clone()).collect();
+        assert_eq!(dependents, vec![1..2, 1..2, 1..3]);
+        let pending: Vec<usize> = regions
+            .iter()
+            .map(|r| r.pending_dependencies.load(Ordering::SeqCst))
+            .collect();
+        assert_eq!(pending, vec![0, 2, 0]);
+    }
+}
@@ -23,5 +23,8 @@ pub mod immix;
 pub mod immortalspace;
 pub mod largeobjectspace;
 pub mod lockfreeimmortalspace;
+pub mod los_ref_count;
 pub mod mallocspace;
 pub mod markcompactspace;
+#[cfg(feature = "raw_memory_space")]
+pub mod rawmemoryspace;
@@ -0,0 +1,244 @@
+//! A space for the raw memory that a binding allocates for its own metadata (e.g. type information
+//! or bytecode) with [`memory_manager::alloc_raw`](crate::memory_manager::alloc_raw). The memory
+//! is not an object heap: it is never traced or scanned, and it is only freed explicitly with
+//! [`memory_manager::free_raw`](crate::memory_manager::free_raw). But it is allocated from the
+//! same virtual memory and page budget as the rest of the heap, so it triggers GCs when the heap
+//! is full, and its pages show up in the statistics of the spaces.
+//!
+//! A request of more than half a page gets its own pages, which are returned to the page resource
+//! when it is freed. Smaller requests are rounded up to a power of two size class, and are carved
+//! out of pages of the size class. A freed cell is reused for the next request of the same size
+//! class, and the pages of the cells are never released.
+
+use crate::plan::PlanConstraints;
+use crate::plan::VectorObjectQueue;
+use crate::policy::space::SpaceOptions;
+use crate::policy::space::*;
+use crate::policy::space::{CommonSpace, Space, SFT};
+use crate::util::alloc::allocator;
+use crate::util::alloc::AllocationError;
+use crate::util::constants::{BYTES_IN_PAGE, LOG_BYTES_IN_PAGE};
+use crate::util::conversions;
+use crate::util::error::{self, MMTKError};
+use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
+use crate::util::metadata::side_metadata::SideMetadataContext;
+use crate::util::metadata::side_metadata::SideMetadataSpec;
+use crate::util::opaque_pointer::*;
+use crate::util::{Address, ObjectReference};
+use crate::vm::Collection;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// log2 of the smallest size class.
+const LOG_MIN_CELL_BYTES: usize = 4;
+/// The number of size classes. The largest size class is half a page.
+const SIZE_CLASSES: usize = LOG_BYTES_IN_PAGE as usize - LOG_MIN_CELL_BYTES;
+
+/// A space for raw memory that is allocated and freed explicitly by the binding.
+pub struct RawMemorySpace<VM: VMBinding> {
+    common: CommonSpace<VM>,
+    pr: FreeListPageResource<VM>,
+    /// The free cells of each size class.
+    free_cells: Vec<Mutex<Vec<Address>>>,
+    /// The bytes that are allocated and not freed, including the rounding up to the size classes
+    /// and the pages.
+    used_bytes: AtomicUsize,
+}
+
+impl<VM: VMBinding> SFT for RawMemorySpace<VM> {
+    fn name(&self) -> &str {
+        self.get_name()
+    }
+    fn is_live(&self, _object: ObjectReference) -> bool {
+        // The memory is only freed by the binding.
+        true
+    }
+    fn is_movable(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "sanity")]
+    fn is_sane(&self) -> bool {
+        true
+    }
+    fn initialize_object_metadata(&self, object: ObjectReference, _alloc: bool) {
+        panic!(
+            "The raw memory space does not have objects (object {})",
+            object
+        )
+    }
+    #[inline(always)]
+    fn sft_trace_object(
+        &self,
+        _queue: &mut VectorObjectQueue,
+        object: ObjectReference,
+        _worker: GCWorkerMutRef,
+    ) -> ObjectReference {
+        panic!("The raw memory space is not traced (object {})", object)
+    }
+}
+
+impl<VM: VMBinding> Space<VM> for RawMemorySpace<VM> {
+    fn as_space(&self) -> &dyn Space<VM> {
+        self
+    }
+    fn as_sft(&self) -> &(dyn SFT + Sync + 'static) {
+        self
+    }
+    fn get_page_resource(&self) -> &dyn PageResource<VM> {
+        &self.pr
+    }
+
+    fn initialize_sft(&self) {
+        self.common().initialize_sft(self.as_sft())
+    }
+
+    fn common(&self) -> &CommonSpace<VM> {
+        &self.common
+    }
+
+    fn release_multiple_pages(&mut self, start: Address) {
+        self.pr.release_pages(start);
+    }
+}
+
+impl<VM: VMBinding> RawMemorySpace<VM> {
+    pub fn new(
+        name: &'static str,
+        vmrequest: VMRequest,
+        global_side_metadata_specs: Vec<SideMetadataSpec>,
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        heap: &mut HeapMeta,
+        constraints: &'static PlanConstraints,
+    ) -> Self {
+        let common = CommonSpace::new(
+            SpaceOptions {
+                name,
+                movable: false,
+                immortal: false,
+                zeroed: false,
+                needs_log_bit: constraints.needs_log_bit,
+                vmrequest,
+                side_metadata_specs: SideMetadataContext {
+                    global: global_side_metadata_specs,
+                    local: vec![],
+                },
+            },
+            vm_map,
+            mmapper,
+            heap,
+        );
+        let pr = if vmrequest.is_discontiguous() {
+            FreeListPageResource::new_discontiguous(0, vm_map)
+        } else {
+            FreeListPageResource::new_contiguous(common.start, common.extent, 0, vm_map)
+        };
+        RawMemorySpace {
+            common,
+            pr,
+            free_cells: (0..SIZE_CLASSES).map(|_| Mutex::new(vec![])).collect(),
+            used_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// The size class of a request, or `None` if the request gets its own pages.
+    fn size_class(bytes: usize, align: usize) -> Option<usize> {
+        let cell_bytes = bytes
+            .max(align)
+            .max(1 << LOG_MIN_CELL_BYTES)
+            .next_power_of_two();
+        if cell_bytes >= BYTES_IN_PAGE {
+            None
+        } else {
+            Some(cell_bytes.trailing_zeros() as usize - LOG_MIN_CELL_BYTES)
+        }
+    }
+
+    /// Allocate `bytes` of memory aligned to `align`, which is a power of two and at most a page.
+    /// The memory is not zeroed. Return zero if the heap is out of memory.
+    pub fn alloc(&self, tls: VMThread, bytes: usize, align: usize) -> Address {
+        assert!(
+            align.is_power_of_two() && align <= BYTES_IN_PAGE,
+            "Raw memory can only be aligned to a power of two of at most a page, not {}",
+            align
+        );
+        match Self::size_class(bytes, align) {
+            Some(class) => self.alloc_cell(tls, class),
+            None => {
+                let pages = conversions::bytes_to_pages_up(bytes);
+                let start = self.acquire_or_fail(tls, pages);
+                if !start.is_zero() {
+                    self.used_bytes
+                        .fetch_add(conversions::pages_to_bytes(pages), Ordering::Relaxed);
+                }
+                start
+            }
+        }
+    }
+
+    fn alloc_cell(&self, tls: VMThread, class: usize) -> Address {
+        let cell_bytes = 1 << (class + LOG_MIN_CELL_BYTES);
+        if let Some(cell) = self.free_cells[class].lock().unwrap().pop() {
+            self.used_bytes.fetch_add(cell_bytes, Ordering::Relaxed);
+            return cell;
+        }
+        // Do not hold the lock while acquiring the page, as we may block for a GC.
+        let page = self.acquire_or_fail(tls, 1);
+        if page.is_zero() {
+            return page;
+        }
+        self.free_cells[class]
+            .lock()
+            .unwrap()
+            .extend((1..BYTES_IN_PAGE / cell_bytes).map(|i| page + i * cell_bytes));
+        self.used_bytes.fetch_add(cell_bytes, Ordering::Relaxed);
+        page
+    }
+
+    /// Free the memory at `addr` that was allocated with the same `bytes` and `align`.
+    pub fn free(&self, addr: Address, bytes: usize, align: usize) {
+        debug_assert!(self.address_in_space(addr));
+        match Self::size_class(bytes, align) {
+            Some(class) => {
+                let cell_bytes = 1 << (class + LOG_MIN_CELL_BYTES);
+                debug_assert!(addr.is_aligned_to(cell_bytes));
+                self.free_cells[class].lock().unwrap().push(addr);
+                self.used_bytes.fetch_sub(cell_bytes, Ordering::Relaxed);
+            }
+            None => {
+                debug_assert!(addr.is_aligned_to(BYTES_IN_PAGE));
+                let pages = self.pr.get_allocated_pages(addr);
+                debug_assert_eq!(pages, conversions::bytes_to_pages_up(bytes));
+                self.pr.release_pages(addr);
+                self.used_bytes
+                    .fetch_sub(conversions::pages_to_bytes(pages), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The bytes that are allocated in the space and not freed.
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Acquire pages from the space. If the heap is full, the current thread is blocked for a GC,
+    /// and we try once more after the GC. If that fails too, the heap is out of memory.
+    fn acquire_or_fail(&self, tls: VMThread, pages: usize) -> Address {
+        for _ in 0..2 {
+            let start = self.acquire(tls, pages);
+            if !start.is_zero() || allocator::is_no_gc_on_failure() || error::has_allocation_error()
+            {
+                return start;
+            }
+        }
+        if error::is_returning_allocation_errors() {
+            error::set_allocation_error(MMTKError::HeapOutOfMemory);
+        } else {
+            VM::VMCollection::out_of_memory(tls, AllocationError::HeapOutOfMemory);
+        }
+        unsafe { Address::zero() }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::plan::static_plan::devirtualize;
 use crate::plan::VectorObjectQueue;
 use crate::util::conversions::*;
 use crate::util::metadata::side_metadata::{SideMetadataContext, SideMetadataSanity};
@@ -7,7 +8,7 @@ use crate::util::ObjectReference;
 use crate::util::heap::layout::vm_layout_constants::{AVAILABLE_BYTES, LOG_BYTES_IN_CHUNK};
 use crate::util::heap::layout::vm_layout_constants::{AVAILABLE_END, AVAILABLE_START};
 use crate::util::heap::{PageResource, VMRequest};
-use crate::vm::{ActivePlan, Collection, ObjectModel};
+use crate::vm::{ActivePlan, ObjectModel};
 
 use crate::util::constants::LOG_BYTES_IN_MBYTE;
 use crate::util::conversions;
@@ -15,7 +16,9 @@ use crate::util::opaque_pointer::*;
 
 use crate::mmtk::SFT_MAP;
 use crate::scheduler::GCWorker;
+use crate::util::alloc::allocator;
 use crate::util::copy::*;
+use crate::util::error::MMTKError;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::map::Map;
@@ -23,6 +26,7 @@ use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
 use crate::util::heap::layout::vm_layout_constants::MAX_CHUNKS;
 use crate::util::heap::layout::Mmapper as IMmapper;
 use crate::util::heap::space_descriptor::SpaceDescriptor;
+use crate::util::heap::zeroed_block_pool::{self, ZeroedBlockPool};
 use crate::util::heap::HeapMeta;
 use crate::util::memory;
 
@@ -31,6 +35,7 @@ use crate::util::alloc_bit;
 
 use crate::vm::VMBinding;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
 use downcast_rs::Downcast;
@@ -103,6 +108,61 @@ pub trait SFT {
     }
     /// Initialize object metadata (in the header, or in the side metadata).
     fn initialize_object_metadata(&self, object: ObjectReference, alloc: bool);
+    /// Mark a newly allocated object as live in the current GC (black allocation), so a
+    /// concurrent GC does not reclaim the objects allocated while it is marking, even though it
+    /// never traces them. This is called after `initialize_object_metadata()` when black
+    /// allocation is enabled (see `BasePlan::set_black_allocation()`).
+    fn mark_allocated_object(&self, object: ObjectReference) {
+        panic!(
+            "{} does not support black allocation (object {})",
+            self.name(),
+            object
+        )
+    }
+    /// Can the object be resized from `old_size` bytes to `new_size` bytes without moving it?
+    /// If this returns true, the policy has updated its own accounting for the object, and the
+    /// binding should report `new_size` as the object size from now on.
+    /// By default, we allow shrinking but not growing an object. Most policies get the object
+    /// size from the binding when they need it (e.g. when tracing or copying the object),
+    /// so the bytes after the shrunk object are simply reclaimed along with the object.
+    #[inline(always)]
+    fn resize_object_in_place(
+        &self,
+        _object: ObjectReference,
+        old_size: usize,
+        new_size: usize,
+    ) -> bool {
+        new_size <= old_size
+    }
+    /// Request the policy to move the object in the next GC that collects this space.
+    /// Return true if the policy accepts the request. It is up to the policy how the object
+    /// is moved, and the new location is found through the usual forwarding mechanism.
+    /// By default, the request is rejected.
+    #[inline(always)]
+    fn request_relocation(&self, _object: ObjectReference) -> bool {
+        false
+    }
+    /// Pin the object, so GCs do not move it until it is unpinned with `unpin_object()`. Pins are
+    /// counted, so an object pinned twice needs to be unpinned twice. Return false if the policy
+    /// cannot pin objects. By default, only policies that never move objects can, trivially.
+    #[inline(always)]
+    fn pin_object(&self, _object: ObjectReference) -> bool {
+        !self.is_movable()
+    }
+    /// Remove a pin of the object added with `pin_object()`. Return false if the policy cannot pin
+    /// objects.
+    #[inline(always)]
+    fn unpin_object(&self, _object: ObjectReference) -> bool {
+        !self.is_movable()
+    }
+    /// Keep the object in place in the current GC, because it is pointed by a pinned root. This is
+    /// called after the spaces are prepared for the GC, and before any object is traced.
+    /// Return false if the policy cannot keep the object in place.
+    /// By default, only policies that never move objects can keep objects in place.
+    #[inline(always)]
+    fn pin_for_current_gc(&self, _object: ObjectReference) -> bool {
+        !self.is_movable()
+    }
     /// Trace objects through SFT. This along with [`SFTProcessEdges`](mmtk/scheduler/gc_work/SFTProcessEdges)
     /// provides an easy way for most plans to trace objects without the need to implement any plan-specific
     /// code. However, tracing objects for some policies are more complicated, and they do not provide an
@@ -130,7 +190,7 @@ const DEBUG_SFT: bool = cfg!(debug_assertions) && false;
 #[derive(Debug)]
 struct EmptySpaceSFT {}
 
-const EMPTY_SFT_NAME: &str = "empty";
+pub(crate) const EMPTY_SFT_NAME: &str = "empty";
 
 impl SFT for EmptySpaceSFT {
     fn name(&self) -> &str {
@@ -174,6 +234,14 @@ impl SFT for EmptySpaceSFT {
             object
         )
     }
+    fn resize_object_in_place(
+        &self,
+        _object: ObjectReference,
+        _old_size: usize,
+        _new_size: usize,
+    ) -> bool {
+        false
+    }
 
     fn sft_trace_object(
         &self,
@@ -383,6 +451,13 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
     fn as_sft(&self) -> &(dyn SFT + Sync + 'static);
     fn get_page_resource(&self) -> &dyn PageResource<VM>;
 
+    /// Insert the address range of the space to the VM map, and map or reserve the metadata for
+    /// the range. This is called by `create_plan()` once the side metadata layout of the plan is
+    /// checked, so a plan with conflicting side metadata does not map any metadata.
+    fn initialize_address_range(&self) {
+        self.common().initialize_address_range();
+    }
+
     /// Initialize entires in SFT map for the space. This is called when the Space object
     /// has a non-moving address, as we will use the address to set sft.
     /// Currently after we create a boxed plan, spaces in the plan have a non-moving address.
@@ -393,8 +468,17 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         // Should we poll to attempt to GC?
         // - If tls is collector, we cannot attempt a GC.
         // - If gc is disabled, we cannot attempt a GC.
+        // - Without GC threads, the GC runs on the mutator thread, which cannot attempt a GC when
+        //   the GC allocates to copy objects.
+        // - If we are refilling a zeroed block pool, we only take the free memory of the heap.
         let should_poll = VM::VMActivePlan::is_mutator(tls)
-            && VM::VMActivePlan::global().should_trigger_gc_when_heap_is_full();
+            && VM::VMActivePlan::global().should_trigger_gc_when_heap_is_full()
+            && !(VM::VMActivePlan::global()
+                .base()
+                .options
+                .is_single_threaded()
+                && VM::VMActivePlan::global().base().gc_in_progress())
+            && !zeroed_block_pool::is_refilling();
         // Is a GC allowed here? If we should poll but are not allowed to poll, we will panic.
         // initialize_collection() has to be called so we know GC is initialized.
         let allow_gc = should_poll && VM::VMActivePlan::global().is_initialized();
@@ -405,15 +489,60 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         trace!("Pages reserved");
         trace!("Polling ..");
 
-        if should_poll && VM::VMActivePlan::global().poll(false, Some(self.as_space())) {
+        // If the space has grown beyond its maximum size, or has grown too much since the last
+        // GC, we treat the space as full.
+        let space_full = should_poll && {
+            let base = VM::VMActivePlan::global().base();
+            base.is_space_over_limit(self.as_space())
+                || base.is_space_over_growth_trigger(self.as_space())
+        };
+        // Should we fail the allocation rather than triggering a GC (see `memory_manager::try_alloc()`)?
+        let no_gc_on_failure = allocator::is_no_gc_on_failure();
+
+        if should_poll
+            && no_gc_on_failure
+            && VM::VMActivePlan::global().collection_required(space_full, Some(self.as_space()))
+        {
+            debug!("Collection required, but the allocation fails without triggering a GC");
+            pr.clear_request(pages_reserved);
+            unsafe { Address::zero() }
+        } else if should_poll
+            && !no_gc_on_failure
+            && devirtualize(VM::VMActivePlan::global()).poll(space_full, Some(self.as_space()))
+        {
             debug!("Collection required");
             assert!(allow_gc, "GC is not allowed here: collection is not initialized (did you call initialize_collection()?).");
             pr.clear_request(pages_reserved);
-            VM::VMCollection::block_for_gc(VMMutatorThread(tls)); // We have checked that this is mutator
+            // We have checked that this is mutator
+            VM::VMActivePlan::global()
+                .base()
+                .block_for_gc(VMMutatorThread(tls));
             unsafe { Address::zero() }
         } else {
             debug!("Collection not required");
 
+            // Take a block that was acquired and zeroed ahead of time, if the space has one.
+            if let Some(start) = self
+                .common()
+                .zeroed_block_pool
+                .as_ref()
+                .and_then(|pool| pool.take(pages))
+            {
+                pr.clear_request(pages_reserved);
+                record_acquired_pages::<VM>(tls, pages);
+                #[cfg(feature = "chunk_compression")]
+                if let Some(chunk_compression) =
+                    VM::VMActivePlan::global().base().chunk_compression.as_ref()
+                {
+                    chunk_compression.touch(start, conversions::pages_to_bytes(pages));
+                }
+                debug!(
+                    "Space.acquire(), returned = {} from the zeroed block pool",
+                    start
+                );
+                return start;
+            }
+
             // We need this lock: Othrewise, it is possible that one thread acquires pages in a new chunk, but not yet
             // set SFT for it (in grow_space()), and another thread acquires pages in the same chunk, which is not
             // a new chunk so grow_space() won't be called on it. The second thread could return a result in the chunk before
@@ -434,6 +563,10 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
                     );
                     let bytes = conversions::pages_to_bytes(res.pages);
                     self.grow_space(res.start, bytes, res.new_chunk);
+                    // The pages of a zeroed block pool are counted when they are taken.
+                    if !zeroed_block_pool::is_refilling() {
+                        record_acquired_pages::<VM>(tls, res.pages);
+                    }
 
                     // Once we finish grow_space, we can drop the lock.
                     drop(lock);
@@ -450,9 +583,28 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
                                 .try_map_metadata_space(res.start, bytes),
                         )
                     {
+                        if crate::util::error::is_returning_allocation_errors()
+                            && memory::is_mmap_out_of_memory(&mmap_error)
+                        {
+                            // Return the error to the caller (see `memory_manager::alloc_checked`).
+                            // Give the pages back to the page resource, so the failure does not
+                            // shrink the heap.
+                            pr.release_unmapped_pages(res.start, res.pages);
+                            crate::util::error::set_allocation_error(MMTKError::MmapFailed(
+                                mmap_error,
+                            ));
+                            return unsafe { Address::zero() };
+                        }
                         memory::handle_mmap_error::<VM>(mmap_error, tls);
                     }
 
+                    #[cfg(feature = "chunk_compression")]
+                    if let Some(chunk_compression) =
+                        VM::VMActivePlan::global().base().chunk_compression.as_ref()
+                    {
+                        chunk_compression.touch(res.start, bytes);
+                    }
+
                     // TODO: Concurrent zeroing
                     if self.common().zeroed {
                         memory::zero(res.start, bytes);
@@ -490,16 +642,28 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
                 Err(_) => {
                     drop(lock); // drop the lock immediately
 
+                    if no_gc_on_failure {
+                        pr.clear_request(pages_reserved);
+                        return unsafe { Address::zero() };
+                    }
+
                     // We thought we had memory to allocate, but somehow failed the allocation. Will force a GC.
                     assert!(
                         allow_gc,
                         "Physical allocation failed when GC is not allowed!"
                     );
 
-                    let gc_performed = VM::VMActivePlan::global().poll(true, Some(self.as_space()));
-                    debug_assert!(gc_performed, "GC not performed when forced.");
+                    let plan = VM::VMActivePlan::global();
+                    let gc_performed = devirtualize(plan).poll(true, Some(self.as_space()));
+                    debug_assert!(
+                        gc_performed || plan.base().gc_requester.is_shutting_down(),
+                        "GC not performed when forced."
+                    );
                     pr.clear_request(pages_reserved);
-                    VM::VMCollection::block_for_gc(VMMutatorThread(tls)); // We asserted that this is mutator.
+                    if gc_performed {
+                        // We asserted that this is mutator.
+                        plan.base().block_for_gc(VMMutatorThread(tls));
+                    }
                     unsafe { Address::zero() }
                 }
             }
@@ -581,6 +745,37 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         self.common()
             .mmapper
             .mark_as_mapped(self.common().start, self.common().extent);
+        self.common()
+            .externally_mapped
+            .store(true, Ordering::Relaxed);
+    }
+
+    /// Unmap the memory of this space and its side metadata, and clear its entries in the SFT
+    /// map. This is called when the MMTk instance shuts down, after which the space is no longer
+    /// used. The memory of a space that is mapped by the VM (see `ensure_mapped()`) is not
+    /// unmapped.
+    ///
+    /// Arguments:
+    /// * `discontiguous_range`: The address range shared by the discontiguous spaces. A
+    ///   discontiguous space releases the entire range.
+    fn release_address_space(&self, discontiguous_range: (Address, Address)) {
+        let common = self.common();
+        let (start, end) = if common.contiguous {
+            (common.start, common.start + common.extent)
+        } else {
+            discontiguous_range
+        };
+        let (start, end) = (chunk_align_down(start), chunk_align_up(end));
+        if start >= end {
+            return;
+        }
+        for chunk in (start.as_usize()..end.as_usize()).step_by(BYTES_IN_CHUNK) {
+            SFT_MAP.clear(unsafe { Address::from_usize(chunk) });
+        }
+        common.metadata.release_metadata_space(start, end - start);
+        if !common.externally_mapped.load(Ordering::Relaxed) {
+            common.mmapper.unmap(start, bytes_to_pages(end - start));
+        }
     }
 
     fn reserved_pages(&self) -> usize {
@@ -589,6 +784,14 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         data_pages + meta_pages
     }
 
+    /// Set whether the pages released by this space are given back to the OS (see
+    /// `memory_manager::set_heap_size()`).
+    fn set_decommit_on_release(&self, decommit: bool) {
+        self.get_page_resource()
+            .common()
+            .set_decommit_on_release(decommit);
+    }
+
     /// Return the number of physical pages available.
     fn available_physical_pages(&self) -> usize {
         self.get_page_resource().get_available_physical_pages()
@@ -598,6 +801,91 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         self.common().name
     }
 
+    /// Get the name of the policy of this space, e.g. `CopySpace`.
+    fn get_policy_name(&self) -> &'static str {
+        // Strip the module path and the type arguments, e.g. `mmtk::policy::copyspace::CopySpace<VM>` -> `CopySpace`.
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Get a description of this space.
+    fn get_space_info(&self) -> SpaceInfo {
+        let common = self.common();
+        SpaceInfo {
+            name: common.name,
+            policy: self.get_policy_name(),
+            movable: common.movable,
+            immortal: common.immortal,
+            bounds: if common.contiguous {
+                Some((common.start, common.start + common.extent))
+            } else {
+                None
+            },
+            reserved_pages: self.reserved_pages(),
+            zeroed_block_pool_pages: common
+                .zeroed_block_pool
+                .as_ref()
+                .map_or(0, |pool| pool.pages()),
+        }
+    }
+
+    /// Get the address ranges of the memory acquired by this space. Adjacent chunks are merged
+    /// into one range. The ranges are sorted by address. Only the chunks that are mapped are included.
+    fn get_acquired_ranges(&self) -> Vec<(Address, Address)> {
+        let common = self.common();
+        let mut regions = vec![];
+        if common.contiguous {
+            regions.push((common.start, common.start + common.extent));
+        } else {
+            let mut a = self
+                .get_page_resource()
+                .common()
+                .get_head_discontiguous_region();
+            while !a.is_zero() {
+                regions.push((a, a + common.vm_map().get_contiguous_region_size(a)));
+                a = common.vm_map().get_next_contiguous_region(a);
+            }
+            regions.sort_by_key(|(start, _)| *start);
+        }
+
+        let mut ranges: Vec<(Address, Address)> = vec![];
+        for (start, end) in regions {
+            let mut chunk = conversions::chunk_align_down(start);
+            while chunk < end {
+                // When a chunk is mapped, the side metadata (including the alloc bit) for the chunk
+                // is mapped as well.
+                if chunk.is_mapped() {
+                    match ranges.last_mut() {
+                        Some((_, last_end)) if *last_end == chunk => *last_end += BYTES_IN_CHUNK,
+                        _ => ranges.push((chunk, chunk + BYTES_IN_CHUNK)),
+                    }
+                }
+                chunk += BYTES_IN_CHUNK;
+            }
+        }
+        ranges
+    }
+
+    /// Iterate over the objects in this space in address order. The objects are found by the alloc bit,
+    /// so this includes all the objects that are allocated in the space but not yet reclaimed by a GC.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that there is no allocation or GC in this space while the iterator is used,
+    /// i.e. the mutators are stopped at a safepoint and no GC is in progress.
+    #[cfg(feature = "global_alloc_bit")]
+    unsafe fn iterate_objects(&self) -> Box<dyn Iterator<Item = ObjectReference>> {
+        use crate::util::linear_scan::{DefaultObjectSize, ObjectIterator};
+        Box::new(
+            self.get_acquired_ranges()
+                .into_iter()
+                .flat_map(|(start, end)| {
+                    ObjectIterator::<VM, DefaultObjectSize<VM>, true>::new(start, end)
+                }),
+        )
+    }
+
     fn common(&self) -> &CommonSpace<VM>;
 
     fn release_multiple_pages(&mut self, start: Address);
@@ -664,12 +952,53 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
     /// * `side_metadata_sanity_checker`: The `SideMetadataSanity` object instantiated in the calling plan.
     fn verify_side_metadata_sanity(&self, side_metadata_sanity_checker: &mut SideMetadataSanity) {
         side_metadata_sanity_checker
-            .verify_metadata_context(std::any::type_name::<Self>(), &self.common().metadata)
+            .verify_metadata_context(std::any::type_name::<Self>(), self.side_metadata())
+    }
+
+    /// The side metadata specs of this space.
+    fn side_metadata(&self) -> &SideMetadataContext {
+        &self.common().metadata
     }
 }
 
 impl_downcast!(Space<VM> where VM: VMBinding);
 
+/// A description of a space. This allows bindings and tools to query the configuration of MMTk at run time.
+#[derive(Clone, Debug)]
+pub struct SpaceInfo {
+    /// The name of the space.
+    pub name: &'static str,
+    /// The policy of the space, e.g. `CopySpace`.
+    pub policy: &'static str,
+    /// Can objects in the space be moved?
+    pub movable: bool,
+    /// Are objects in the space immortal?
+    pub immortal: bool,
+    /// The address range of the space if the space is contiguous. `None` for a discontiguous space.
+    pub bounds: Option<(Address, Address)>,
+    /// The number of pages reserved by the space, including its side metadata.
+    pub reserved_pages: usize,
+    /// The number of pages in the zeroed block pool of the space (see the option
+    /// `zeroed_block_pool`). They are included in `reserved_pages`.
+    pub zeroed_block_pool_pages: usize,
+}
+
+/// Count the pages acquired by `Space::acquire()` in the GC statistics, as allocated bytes for a
+/// mutator, or as copy pages for a GC thread.
+fn record_acquired_pages<VM: VMBinding>(tls: VMThread, pages: usize) {
+    if VM::VMActivePlan::is_mutator(tls) {
+        VM::VMActivePlan::global()
+            .base()
+            .gc_stats
+            .add_allocated_bytes(conversions::pages_to_bytes(pages));
+    } else {
+        VM::VMActivePlan::global()
+            .base()
+            .gc_stats
+            .add_copy_pages(pages);
+    }
+}
+
 pub struct CommonSpace<VM: VMBinding> {
     pub name: &'static str,
     pub descriptor: SpaceDescriptor,
@@ -700,6 +1029,12 @@ pub struct CommonSpace<VM: VMBinding> {
     /// A lock used during acquire() to make sure only one thread can allocate.
     pub acquire_lock: Mutex<()>,
 
+    /// Is the memory of this space mapped by the VM? See `Space::ensure_mapped()`.
+    pub externally_mapped: AtomicBool,
+
+    /// The blocks that were acquired and zeroed ahead of time, if the space keeps a pool of them.
+    pub zeroed_block_pool: Option<ZeroedBlockPool>,
+
     p: PhantomData<VM>,
 }
 
@@ -741,6 +1076,8 @@ impl<VM: VMBinding> CommonSpace<VM> {
             metadata: opt.side_metadata_specs,
             p: PhantomData,
             acquire_lock: Mutex::new(()),
+            externally_mapped: AtomicBool::new(false),
+            zeroed_block_pool: None,
         };
 
         let vmrequest = opt.vmrequest;
@@ -793,17 +1130,8 @@ impl<VM: VMBinding> CommonSpace<VM> {
         // FIXME
         rtn.descriptor = SpaceDescriptor::create_descriptor_from_heap_range(start, start + extent);
         // VM.memory.setHeapRange(index, start, start.plus(extent));
-        vm_map.insert(start, extent, rtn.descriptor);
-
-        // For contiguous space, we know its address range so we reserve metadata memory for its range.
-        if rtn
-            .metadata
-            .try_map_metadata_address_range(rtn.start, rtn.extent)
-            .is_err()
-        {
-            // TODO(Javad): handle meta space allocation failure
-            panic!("failed to mmap meta memory");
-        }
+        // The range is inserted to the VM map, and its metadata memory is reserved, after the side
+        // metadata layout is checked. See `initialize_address_range()`.
 
         if DEBUG_SPACE {
             println!(
@@ -818,6 +1146,26 @@ impl<VM: VMBinding> CommonSpace<VM> {
         rtn
     }
 
+    /// Insert the address range of a contiguous space to the VM map, and reserve the metadata
+    /// memory for the range. See `Space::initialize_address_range()`.
+    pub fn initialize_address_range(&self) {
+        if !self.contiguous {
+            return;
+        }
+        self.vm_map.insert(self.start, self.extent, self.descriptor);
+
+        // For contiguous space, we know its address range so we reserve metadata memory for its
+        // range.
+        if self
+            .metadata
+            .try_map_metadata_address_range(self.start, self.extent)
+            .is_err()
+        {
+            // TODO(Javad): handle meta space allocation failure
+            panic!("failed to mmap meta memory");
+        }
+    }
+
     pub fn initialize_sft(&self, sft: &(dyn SFT + Sync + 'static)) {
         // For contiguous space, we eagerly initialize SFT map based on its address range.
         if self.contiguous {
@@ -0,0 +1,116 @@
+//! Background work packets. These are low-priority housekeeping packets, such as lazy sweeping,
+//! zeroing or releasing memory, that plans and bindings want to run on the GC workers between GCs,
+//! instead of spawning their own threads. A worker only executes a background packet when it has
+//! no other work and no GC is in progress. When a GC is triggered, the GC controller waits for the
+//! background packets being executed to finish before the GC starts, and background packets that
+//! have not started yet are held until the GC is finished. Without GC workers (the option
+//! `threads` is 0), the background packets are executed on the thread that did the GC, after the
+//! GC is finished.
+
+use super::*;
+use crate::mmtk::MMTK;
+use crate::vm::VMBinding;
+use crossbeam::deque::{Injector, Steal};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+pub(super) struct BackgroundWork<VM: VMBinding> {
+    /// The background packets that have not been started.
+    queue: Injector<Box<dyn GCWork<VM>>>,
+    /// Is a GC in progress? Workers do not start background packets during a GC.
+    gc_in_progress: AtomicBool,
+    /// The number of background packets being executed.
+    running: AtomicUsize,
+    /// The GC controller waits on this for the running packets to finish when a GC starts.
+    finished: (Mutex<()>, Condvar),
+}
+
+impl<VM: VMBinding> BackgroundWork<VM> {
+    pub fn new() -> Self {
+        Self {
+            queue: Injector::new(),
+            gc_in_progress: AtomicBool::new(false),
+            running: AtomicUsize::new(0),
+            finished: (Mutex::new(()), Condvar::new()),
+        }
+    }
+
+    pub fn add(&self, work: Box<dyn GCWork<VM>>) {
+        self.queue.push(work);
+    }
+
+    /// Are there background packets to run now?
+    #[inline(always)]
+    pub fn is_runnable(&self) -> bool {
+        !self.gc_in_progress.load(Ordering::SeqCst) && !self.queue.is_empty()
+    }
+
+    /// Take a background packet to execute, unless a GC is in progress. The packet is wrapped so
+    /// that the GC controller knows when it is finished.
+    #[inline]
+    pub fn poll(&self) -> Option<Box<dyn GCWork<VM>>> {
+        if !self.is_runnable() {
+            return None;
+        }
+        // Count the packet as running before checking for a GC again, so either the controller
+        // sees the packet and waits for it, or we see the GC and do not start the packet.
+        self.running.fetch_add(1, Ordering::SeqCst);
+        if self.gc_in_progress.load(Ordering::SeqCst) {
+            self.finish_running();
+            return None;
+        }
+        loop {
+            match self.queue.steal() {
+                Steal::Success(work) => return Some(Box::new(BackgroundPacket { work })),
+                Steal::Retry => continue,
+                Steal::Empty => {
+                    self.finish_running();
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// A GC is starting. Hold the background packets that have not been started, and wait for
+    /// the ones being executed to finish.
+    pub fn on_gc_start(&self) {
+        self.gc_in_progress.store(true, Ordering::SeqCst);
+        let mut guard = self.finished.0.lock().unwrap();
+        while self.running.load(Ordering::SeqCst) != 0 {
+            guard = self.finished.1.wait(guard).unwrap();
+        }
+    }
+
+    /// A packet counted as running is finished (or not started after all). Wake up the GC
+    /// controller if it waits for the last running packet.
+    fn finish_running(&self) {
+        if self.running.fetch_sub(1, Ordering::SeqCst) == 1
+            && self.gc_in_progress.load(Ordering::SeqCst)
+        {
+            // Notify with the lock held, so the controller either sees the count or waits.
+            let _guard = self.finished.0.lock().unwrap();
+            self.finished.1.notify_all();
+        }
+    }
+
+    /// The GC is finished. Background packets can run again.
+    pub fn on_gc_end(&self) {
+        self.gc_in_progress.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A background packet that a worker is executing.
+struct BackgroundPacket<VM: VMBinding> {
+    work: Box<dyn GCWork<VM>>,
+}
+
+impl<VM: VMBinding> GCWork<VM> for BackgroundPacket<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        self.work.do_work(worker, mmtk);
+        worker.scheduler().background_work.finish_running();
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        self.work.get_type_name()
+    }
+}
@@ -3,18 +3,19 @@
 //! MMTk has many GC threads.  There are many GC worker threads and one GC controller thread.
 //! The GC controller thread responds to GC requests and coordinates the workers to perform GC.
 
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::Arc;
 
-use crate::plan::gc_requester::GCRequester;
+use crate::plan::gc_requester::{GCRequestResult, GCRequester};
 use crate::scheduler::gc_work::{EndOfGC, ScheduleCollection};
 use crate::scheduler::CoordinatorMessage;
 use crate::util::VMWorkerThread;
-use crate::vm::VMBinding;
+use crate::vm::{Collection, VMBinding};
 use crate::MMTK;
 use atomic::Ordering;
 
-use super::{GCWork, GCWorkScheduler, GCWorker};
+use super::watchdog::Watchdog;
+use super::{do_work_or_fail, GCWorkScheduler, GCWorker};
 
 /// The thread local struct for the GC controller, the counterpart of `GCWorker`.
 pub struct GCController<VM: VMBinding> {
@@ -28,6 +29,11 @@ pub struct GCController<VM: VMBinding> {
     receiver: Receiver<CoordinatorMessage<VM>>,
     /// The `GCWorker` is used to execute packets. The controller is also a `GCWorker`.
     coordinator_worker: GCWorker<VM>,
+    /// The watchdog that reports a GC that makes no progress.
+    watchdog: Watchdog,
+    /// The only GC worker if there are no GC threads, which runs on the same thread as the
+    /// controller.
+    worker: Option<Box<GCWorker<VM>>>,
 }
 
 impl<VM: VMBinding> GCController<VM> {
@@ -44,17 +50,54 @@ impl<VM: VMBinding> GCController<VM> {
             scheduler,
             receiver,
             coordinator_worker,
+            watchdog: Watchdog::new(
+                *mmtk.options.gc_watchdog_timeout,
+                *mmtk.options.gc_watchdog_abort,
+            ),
+            worker: None,
         })
     }
 
+    /// Set the worker to use without GC threads. `tls` is the thread that initializes the
+    /// collection.
+    pub(crate) fn set_worker(&mut self, mut worker: Box<GCWorker<VM>>, tls: VMWorkerThread) {
+        worker.init(tls, self.mmtk);
+        self.worker = Some(worker);
+    }
+
+    /// Do the requested GC, if any, on the current thread. Without GC threads, this is called
+    /// instead of `Collection::block_for_gc()`, on the mutator thread that blocks for the GC.
+    pub(crate) fn run_requested_gc(&mut self, tls: VMWorkerThread) {
+        match self.requester.poll_request() {
+            Some(GCRequestResult::Collect) => self.do_gc_on_current_thread(tls),
+            Some(GCRequestResult::Cancelled) => VM::VMCollection::gc_cancelled(tls),
+            Some(GCRequestResult::Exit) | None => {}
+        }
+    }
+
     pub fn run(&mut self, tls: VMWorkerThread) {
         // Initialize the GC worker for coordinator. We are not using the run() method from
         // GCWorker so we manually initialize the worker here.
         self.coordinator_worker.tls = tls;
+        self.scheduler.on_gc_thread_start(true);
 
         loop {
             debug!("[STWController: Waiting for request...]");
-            self.requester.wait_for_request();
+            match self.requester.wait_for_request() {
+                GCRequestResult::Collect => {}
+                GCRequestResult::Cancelled => {
+                    // The VM is shutting down. The mutators have not been stopped for the GC, so
+                    // we only need to release the mutators that are blocked for it.
+                    debug!("[STWController: Request cancelled.]");
+                    VM::VMCollection::gc_cancelled(tls);
+                    continue;
+                }
+                GCRequestResult::Exit => {
+                    debug!("[STWController: Exiting.]");
+                    self.scheduler.on_gc_thread_exit(true);
+                    return;
+                }
+            }
             debug!("[STWController: Request recieved.]");
 
             // For heap growth logic
@@ -73,7 +116,7 @@ impl<VM: VMBinding> GCController<VM> {
         let mmtk = self.mmtk;
         match message {
             CoordinatorMessage::Work(mut work) => {
-                work.do_work_with_stat(worker, mmtk);
+                do_work_or_fail(work.as_mut(), worker, mmtk);
                 let old_count = self
                     .scheduler
                     .pending_coordinator_packets
@@ -100,33 +143,98 @@ impl<VM: VMBinding> GCController<VM> {
 
     /// Coordinate workers to perform GC in response to a GC request.
     pub fn do_gc_until_completion(&mut self) {
+        self.scheduler.on_gc_start();
+        self.watchdog.start(&self.scheduler);
+
         // Schedule collection.
-        ScheduleCollection.do_work_with_stat(&mut self.coordinator_worker, self.mmtk);
+        do_work_or_fail(
+            &mut ScheduleCollection,
+            &mut self.coordinator_worker,
+            self.mmtk,
+        );
+        self.scheduler.on_gc_scheduled();
 
         // Drain the message queue and execute coordinator work.
         loop {
-            let message = self.receiver.recv().unwrap();
+            let message = match self.watchdog.check_interval() {
+                Some(interval) => match self.receiver.recv_timeout(interval) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.watchdog.check(&self.scheduler);
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        panic!("The GC workers have disconnected from the controller")
+                    }
+                },
+                None => self.receiver.recv().unwrap(),
+            };
             let finished = self.process_message(message);
             if finished {
                 break;
             }
         }
         debug_assert!(!self.scheduler.worker_group.has_designated_work());
-        // Sometimes multiple finish messages will be sent. Skip them.
+        // Sometimes multiple finish messages will be sent. Skip them. No more messages are sent
+        // for this GC once the scheduler knows it is finished.
+        self.scheduler.on_gc_finished();
         for message in self.receiver.try_iter() {
             match message {
                 CoordinatorMessage::Work(_) => unreachable!(),
                 CoordinatorMessage::Finish => {}
             }
         }
+        self.finish_gc();
+    }
+
+    /// Do a GC on the current thread, with the worker to use without GC threads. The packets are
+    /// executed one by one, and the next buckets are opened when the open ones are drained, as the
+    /// last parked worker would do.
+    fn do_gc_on_current_thread(&mut self, tls: VMWorkerThread) {
+        self.coordinator_worker.tls = tls;
+        self.worker.as_mut().expect("The GC worker is not set").tls = tls;
+        self.scheduler.on_gc_start();
+
+        // Schedule collection.
+        do_work_or_fail(
+            &mut ScheduleCollection,
+            &mut self.coordinator_worker,
+            self.mmtk,
+        );
+
+        loop {
+            // Execute the coordinator work first. The packets may wait for it before the next
+            // buckets can open.
+            while let Ok(message) = self.receiver.try_recv() {
+                self.process_message(message);
+            }
+            let worker = self.worker.as_mut().unwrap();
+            if let Some(mut work) = worker.poll_without_waiting() {
+                do_work_or_fail(work.as_mut(), worker, self.mmtk);
+            } else if !self.scheduler.open_buckets_on_current_thread() {
+                break;
+            }
+        }
+        self.finish_gc();
+
+        // There are no workers to run the background packets between GCs, so run them now.
+        let worker = self.worker.as_mut().unwrap();
+        while let Some(mut work) = self.scheduler.background_work.poll() {
+            do_work_or_fail(work.as_mut(), worker, self.mmtk);
+        }
+    }
+
+    /// Finish the GC after all the buckets are drained.
+    fn finish_gc(&mut self) {
         self.scheduler.deactivate_all();
         // Finalization: Resume mutators, reset gc states
         // Note: Resume-mutators must happen after all work buckets are closed.
         //       Otherwise, for generational GCs, workers will receive and process
         //       newly generated remembered-sets from those open buckets.
         //       But these remsets should be preserved until next GC.
-        EndOfGC.do_work_with_stat(&mut self.coordinator_worker, self.mmtk);
+        do_work_or_fail(&mut EndOfGC, &mut self.coordinator_worker, self.mmtk);
 
         self.scheduler.debug_assert_all_buckets_deactivated();
+        self.scheduler.on_gc_end();
     }
 }
@@ -0,0 +1,109 @@
+//! Panics in work packets. If a work packet panics, the panic would kill the GC thread, and the
+//! GC would hang forever waiting for the work buckets to drain. Instead, MMTk catches the panic,
+//! marks the GC as failed, reports the failure to the binding (see `Collection::gc_failed`),
+//! and aborts the process. The GC cannot continue, as the heap may be in an inconsistent state.
+
+use super::*;
+use crate::mmtk::MMTK;
+use crate::util::platform::platform;
+use crate::util::VMWorkerThread;
+use crate::vm::{Collection, VMBinding};
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// A GC failed because a work packet panicked.
+#[derive(Debug)]
+pub struct GCFailure {
+    /// The type of the work packet that panicked.
+    pub packet_type: &'static str,
+    /// The ordinal of the worker that executed the packet, or `None` if the packet was executed
+    /// by the GC controller thread.
+    pub worker_ordinal: Option<usize>,
+    /// The thread that executed the packet.
+    pub tls: VMWorkerThread,
+    /// The panic message.
+    pub message: String,
+}
+
+impl fmt::Display for GCFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.worker_ordinal {
+            Some(ordinal) => write!(f, "GC worker {}", ordinal)?,
+            None => write!(f, "GC controller")?,
+        }
+        write!(
+            f,
+            " panicked in work packet {}: {}",
+            self.packet_type, self.message
+        )
+    }
+}
+
+/// Execute a work packet. If the packet panics, report the failure to the binding and abort.
+pub(crate) fn do_work_or_fail<VM: VMBinding, W: GCWork<VM> + ?Sized>(
+    work: &mut W,
+    worker: &mut GCWorker<VM>,
+    mmtk: &'static MMTK<VM>,
+) {
+    let packet_type = work.get_type_name();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        work.do_work_with_stat(worker, mmtk);
+        worker.flush_packet_counts();
+    }));
+    if let Err(payload) = result {
+        let failure = GCFailure {
+            packet_type,
+            worker_ordinal: if worker.is_coordinator() {
+                None
+            } else {
+                Some(worker.ordinal)
+            },
+            tls: worker.tls,
+            message: panic_message(payload.as_ref()),
+        };
+        fail::<VM>(worker.scheduler(), failure);
+    }
+}
+
+/// Mark the GC as failed, let the binding know, and abort.
+fn fail<VM: VMBinding>(scheduler: &GCWorkScheduler<VM>, failure: GCFailure) -> ! {
+    if !scheduler.mark_failed() {
+        // Another GC thread has already failed, and it will abort the process once the
+        // binding has seen its failure. Do not report it twice.
+        loop {
+            platform().sleep(std::time::Duration::from_secs(1));
+        }
+    }
+    // The logger may be compiled out in release builds, so we print the failure directly.
+    eprintln!("[MMTk] GC failed: {}", failure);
+    VM::VMCollection::gc_failed(&failure);
+    eprintln!("[MMTk] Aborting, as the heap may be in an inconsistent state.");
+    std::process::abort();
+}
+
+/// Get the message from the payload of a panic.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(no message)".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let payload = panic::catch_unwind(|| panic!("plain")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "plain");
+        let payload = panic::catch_unwind(|| panic!("formatted {}", 42)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "formatted 42");
+        let payload = panic::catch_unwind(|| panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "(no message)");
+    }
+}
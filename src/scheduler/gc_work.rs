@@ -3,19 +3,27 @@ use super::*;
 use crate::plan::GcStatus;
 use crate::plan::ObjectsClosure;
 use crate::plan::VectorObjectQueue;
+use crate::util::gc_stats::WorkerTracingStats;
+use crate::util::heap::zeroed_block_pool::{RefillZeroedBlock, RefillZeroedBlockPools};
 use crate::util::metadata::*;
+use crate::util::mutator_assist::PerformMutatorAssists;
 use crate::util::*;
 use crate::vm::edge_shape::Edge;
 use crate::vm::*;
 use crate::*;
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 pub struct ScheduleCollection;
 
 impl<VM: VMBinding> GCWork<VM> for ScheduleCollection {
     fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        // End the concurrent phase of the mutator assists. The quanta that are left refill the
+        // zeroed block pools, which are refilled again after this GC.
+        mmtk.plan.base().mutator_assist.end();
         mmtk.plan.schedule_collection(worker.scheduler());
     }
 }
@@ -114,6 +122,14 @@ impl<C: GCWorkContext + 'static> GCWork<C::VM> for Release<C> {
         // We assume this is the only running work packet that accesses plan at the point of execution
         #[allow(clippy::cast_ref_to_mut)]
         let plan_mut: &mut C::PlanType = unsafe { &mut *(self.plan as *const _ as *mut _) };
+        #[cfg(feature = "analysis")]
+        mmtk.plan.base().analysis_manager.release_hook(mmtk);
+        // The deferred co-location groups are weak, so drop the dead ones before the spaces
+        // release their objects. A nursery GC does not know the liveness of the mature objects.
+        if !mmtk.plan.is_current_gc_nursery() {
+            let scope = mmtk.plan.base().collection_scope();
+            mmtk.colocation.release_deferred(scope.as_deref());
+        }
         plan_mut.release(worker.tls);
 
         for mutator in <C::VM as VMBinding>::VMActivePlan::mutators() {
@@ -152,9 +168,23 @@ impl<VM: VMBinding> GCWork<VM> for ReleaseMutator<VM> {
 pub struct ReleaseCollector;
 
 impl<VM: VMBinding> GCWork<VM> for ReleaseCollector {
-    fn do_work(&mut self, worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
         trace!("Release Collector");
         worker.get_copy_context_mut().release();
+        let copied_bytes = worker.get_copy_context_mut().take_copied_bytes();
+        mmtk.plan.base().gc_stats.add_copied_bytes(copied_bytes);
+        let promoted_bytes = worker.get_copy_context_mut().take_promoted_bytes();
+        mmtk.plan.base().gc_stats.add_promoted_bytes(promoted_bytes);
+        let contention = worker.get_copy_context_mut().take_forwarding_contention();
+        mmtk.plan.base().record_forwarding_contention(contention);
+        let tracing = WorkerTracingStats {
+            copied_bytes: copied_bytes as u64,
+            ..worker.take_tracing_stats()
+        };
+        mmtk.plan
+            .base()
+            .gc_stats
+            .add_worker_tracing(worker.ordinal, tracing);
     }
 }
 
@@ -182,12 +212,31 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for StopMutators<E> {
             return;
         }
 
+        // Wait for the mutators in GC-critical regions before stopping them.
+        mmtk.gc_critical_regions.block_and_wait();
         trace!("stop_all_mutators start");
         mmtk.plan.base().prepare_for_stack_scanning();
         <E::VM as VMBinding>::VMCollection::stop_all_mutators(worker.tls, |mutator| {
             mmtk.scheduler.work_buckets[WorkBucketStage::Prepare].add(ScanStackRoot::<E>(mutator));
         });
         trace!("stop_all_mutators end");
+        // All the mutators have passed a safepoint.
+        mmtk.epochs.advance();
+        mmtk.plan
+            .base()
+            .gc_stats
+            .record_gc_start(mmtk.plan.get_used_pages());
+        mmtk.plan.base().gc_stats.record_copy_reserve(
+            mmtk.plan.get_collection_reserved_pages(),
+            mmtk.plan.get_available_pages(),
+        );
+        #[cfg(feature = "graph_export")]
+        mmtk.plan
+            .base()
+            .graph_exporter
+            .gc_start(mmtk.plan.base().gc_stats.gc_count());
+        // The root scanning packets are in the Prepare bucket, which is not open yet.
+        <E::VM as VMBinding>::VMScanning::pre_scan_roots(worker.tls);
         mmtk.scheduler.notify_mutators_paused(mmtk);
         if <E::VM as VMBinding>::VMScanning::SCAN_MUTATORS_IN_SAFEPOINT {
             // Prepare mutators if necessary
@@ -213,6 +262,31 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for StopMutators<E> {
             }
         }
         mmtk.scheduler.work_buckets[WorkBucketStage::Prepare].add(ScanVMSpecificRoots::<E>::new());
+        mmtk.scheduler.work_buckets[WorkBucketStage::Closure].add(ProcessCodeRoots::<E>::new());
+        // The PinningRootsTrace bucket opens once all the roots are scanned, and the closure,
+        // which may move objects, does not start until the bucket is drained.
+        mmtk.scheduler.work_buckets[WorkBucketStage::PinningRootsTrace]
+            .add(PostScanRoots::<E::VM>::new());
+        // The roots of the pinning regions
+        #[cfg(feature = "transitive_pinning")]
+        {
+            let roots: Vec<ObjectReference> = mmtk
+                .transitive_pinning
+                .roots()
+                .into_iter()
+                .filter(|root| mmtk.transitive_pinning.try_pin(*root))
+                .collect();
+            mmtk.transitive_pinning.add_pinned(roots.clone());
+            if !roots.is_empty() {
+                mmtk.scheduler.work_buckets[WorkBucketStage::PinningRootsTrace]
+                    .add(ProcessPinnedRoots::<E>::new(roots.clone()));
+                mmtk.scheduler.work_buckets[WorkBucketStage::PinningRootsTrace].add(PinClosure::<
+                    E::VM,
+                >::new(
+                    roots
+                ));
+            }
+        }
     }
 }
 
@@ -231,17 +305,83 @@ impl<VM: VMBinding> GCWork<VM> for EndOfGC {
             mmtk.edge_logger.reset();
         }
 
+        // The objects are only pinned for this GC.
+        #[cfg(feature = "transitive_pinning")]
+        mmtk.transitive_pinning.reset();
+
         if <VM as VMBinding>::VMCollection::COORDINATOR_ONLY_STW {
             assert!(worker.is_coordinator(),
                     "VM only allows coordinator to resume mutators, but the current worker is not the coordinator.");
         }
 
+        if let Some(pause) = mmtk.plan.base().current_gc_elapsed() {
+            mmtk.plan.base().gc_stats.record_gc_end(
+                mmtk.plan.get_used_pages(),
+                pause,
+                mmtk.plan.last_collection_full_heap(),
+            );
+        }
+        mmtk.plan.end_of_gc(worker.tls);
+        // The memory released in this GC after the heap is shrunk is given back to the OS.
+        mmtk.plan
+            .for_each_space(&mut |space| space.set_decommit_on_release(false));
+        mmtk.plan
+            .for_each_space(&mut |space| mmtk.plan.base().record_space_pages_after_gc(space));
+        if *mmtk.options.heap_timeline {
+            let gc = mmtk.plan.base().gc_stats.gc_count();
+            mmtk.plan.base().heap_timeline.record(
+                gc,
+                mmtk.plan.get_spaces(),
+                *mmtk.options.heap_timeline_resident,
+            );
+        }
+
+        #[cfg(feature = "graph_export")]
+        mmtk.plan.base().graph_exporter.gc_end();
+
         mmtk.plan.base().set_gc_status(GcStatus::NotInGC);
 
+        // Zero the blocks for the allocations after the GC while the workers are idle, and on the
+        // allocation slow path of the mutators if they assist.
+        if *mmtk.options.zeroed_block_pool > 0 {
+            if *mmtk.options.mutator_assist_bytes > 0 {
+                RefillZeroedBlock::add_quanta(mmtk);
+                // Without GC workers, only the mutators perform the quanta.
+                if !mmtk.options.is_single_threaded() {
+                    mmtk.scheduler
+                        .add_background_work(Box::new(PerformMutatorAssists));
+                }
+            } else {
+                mmtk.scheduler
+                    .add_background_work(Box::new(RefillZeroedBlockPools));
+            }
+        }
+
+        // Run the callbacks deferred until the mutators passed the safepoint of this GC.
+        if mmtk.epochs.has_ripe() {
+            mmtk.scheduler
+                .add_background_work(Box::new(crate::util::epoch::RunDeferredCallbacks));
+        }
+
+        // Compress the chunks that are cold after the GC.
+        #[cfg(feature = "chunk_compression")]
+        if let Some(chunk_compression) = mmtk.plan.base().chunk_compression.as_ref() {
+            chunk_compression.gc_end(mmtk.plan.base().gc_stats.gc_count());
+            mmtk.scheduler.add_background_work(Box::new(
+                crate::util::heap::chunk_compression::CompressColdChunks,
+            ));
+        }
+
         // Reset the triggering information.
         mmtk.plan.base().reset_collection_trigger();
+        mmtk.gc_critical_regions.unblock();
 
         <VM as VMBinding>::VMCollection::resume_mutators(worker.tls);
+        // Release the threads that wait for this GC with request_gc_blocking().
+        mmtk.plan
+            .base()
+            .gc_requester
+            .finish_gc(mmtk.plan.last_collection_full_heap());
     }
 }
 
@@ -331,6 +471,24 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanVMSpecificRoots<E> {
     }
 }
 
+/// Let the binding know that all the roots have been scanned, with
+/// [`Scanning::post_scan_roots`](crate::vm::Scanning::post_scan_roots).
+#[derive(Default)]
+pub struct PostScanRoots<VM: VMBinding>(PhantomData<VM>);
+
+impl<VM: VMBinding> PostScanRoots<VM> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for PostScanRoots<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+        trace!("PostScanRoots");
+        <VM as VMBinding>::VMScanning::post_scan_roots(worker.tls);
+    }
+}
+
 pub struct ProcessEdgesBase<VM: VMBinding> {
     pub edges: Vec<VM::VMEdge>,
     pub nodes: VectorObjectQueue,
@@ -339,6 +497,9 @@ pub struct ProcessEdgesBase<VM: VMBinding> {
     // Because a copying gc will dereference this pointer at least once for every object copy.
     worker: *mut GCWorker<VM>,
     pub roots: bool,
+    /// How far ahead to prefetch when processing the edges. 0 means no prefetching.
+    #[cfg(feature = "prefetch")]
+    pub prefetch_distance: usize,
 }
 
 unsafe impl<VM: VMBinding> Send for ProcessEdgesBase<VM> {}
@@ -360,6 +521,8 @@ impl<VM: VMBinding> ProcessEdgesBase<VM> {
             mmtk,
             worker: std::ptr::null_mut(),
             roots,
+            #[cfg(feature = "prefetch")]
+            prefetch_distance: *mmtk.options.prefetch_distance,
         }
     }
     pub fn set_worker(&mut self, worker: &mut GCWorker<VM>) {
@@ -408,7 +571,12 @@ pub trait ProcessEdgesWork:
     /// The work packet type for scanning objects when using this ProcessEdgesWork.
     type ScanObjectsWorkType: ScanObjectsWork<Self::VM>;
 
+    /// The maximum number of edges in a packet created while tracing objects.
     const CAPACITY: usize = 4096;
+    /// The number of edges in the packets created while tracing objects at the beginning of a GC.
+    /// The packets are small at first to spread the work among the workers, and grow to
+    /// `CAPACITY` as the closure proceeds. Set this to `CAPACITY` to always use full packets.
+    const MIN_CAPACITY: usize = 256;
     const OVERWRITE_REFERENCE: bool = true;
     const SCAN_OBJECTS_IMMEDIATELY: bool = true;
 
@@ -458,15 +626,40 @@ pub trait ProcessEdgesWork:
 
     /// Flush the nodes in ProcessEdgesBase, and create a ScanObjects work packet for it. If the node set is empty,
     /// this method will simply return with no work packet created.
+    /// If the pending packets of the closure take more than the option `max_tracing_memory`, the nodes are
+    /// scanned later instead (see `util::tracing_overflow`).
     #[cold]
     fn flush(&mut self) {
         if self.nodes.is_empty() {
             return;
         }
         let nodes = self.pop_nodes();
+        #[cfg(feature = "tracing_overflow")]
+        if self.is_tracing_memory_full() {
+            let mmtk = self.mmtk;
+            let scan_work = Arc::new(move |nodes: Vec<ObjectReference>| {
+                let work = Self::new(vec![], false, mmtk).create_scan_work(nodes, false);
+                Box::new(work) as Box<dyn GCWork<Self::VM>>
+            });
+            self.mmtk
+                .scheduler
+                .tracing_overflow
+                .overflow(&nodes, scan_work);
+            return;
+        }
         self.start_or_dispatch_scan_work(self.create_scan_work(nodes, false));
     }
 
+    /// Do the pending packets of the closure take more than the option `max_tracing_memory`? Each
+    /// packet is counted as a full edge buffer.
+    #[cfg(feature = "tracing_overflow")]
+    fn is_tracing_memory_full(&self) -> bool {
+        let max_bytes = *self.mmtk.options.max_tracing_memory;
+        let bytes_per_packet = Self::CAPACITY * std::mem::size_of::<EdgeOf<Self>>();
+        max_bytes != 0
+            && self.mmtk.scheduler.pending_closure_packets() * bytes_per_packet > max_bytes
+    }
+
     #[inline]
     fn process_edge(&mut self, slot: EdgeOf<Self>) {
         let object = slot.load();
@@ -478,16 +671,59 @@ pub trait ProcessEdgesWork:
 
     #[inline]
     fn process_edges(&mut self) {
+        #[cfg(feature = "prefetch")]
+        if self.prefetch_distance != 0 {
+            self.process_edges_with_prefetch();
+            return;
+        }
         for i in 0..self.edges.len() {
             self.process_edge(self.edges[i])
         }
     }
+
+    /// Process the edges, and prefetch the slots and the objects of the edges ahead.
+    #[cfg(feature = "prefetch")]
+    #[inline]
+    fn process_edges_with_prefetch(&mut self) {
+        let distance = self.prefetch_distance;
+        let len = self.edges.len();
+        for i in 0..len {
+            // Prefetch the slot that we load from `distance` edges later.
+            if i + 2 * distance < len {
+                self.edges[i + 2 * distance].prefetch_load();
+            }
+            // Prefetch the object that we trace `distance` edges later, and its mark bit. A space
+            // may use `LOCAL_MARK_BIT_SIDE_SPEC` instead, in which case this is only a wasted hint.
+            if i + distance < len {
+                let object = self.edges[i + distance].load();
+                if !object.is_null() {
+                    object.to_address().prefetch();
+                    crate::util::metadata::prefetch_metadata(
+                        &<Self::VM as VMBinding>::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+                        object,
+                    );
+                }
+            }
+            self.process_edge(self.edges[i])
+        }
+    }
 }
 
 impl<E: ProcessEdgesWork> GCWork<E::VM> for E {
     #[inline]
     fn do_work(&mut self, worker: &mut GCWorker<E::VM>, _mmtk: &'static MMTK<E::VM>) {
         trace!("ProcessEdgesWork");
+        #[cfg(feature = "graph_export")]
+        if self.roots {
+            let exporter = &_mmtk.plan.base().graph_exporter;
+            if exporter.is_active() {
+                let mut graph = crate::util::graph_export::GraphBuffer::default();
+                for edge in self.edges.iter() {
+                    exporter.root(&mut graph, edge.load());
+                }
+                exporter.flush(&mut graph);
+            }
+        }
         self.set_worker(worker);
         self.process_edges();
         if !self.nodes.is_empty() {
@@ -547,23 +783,248 @@ impl<VM: VMBinding> ProcessEdgesWork for SFTProcessEdges<VM> {
     }
 }
 
+/// Trace the objects pointed by pinned roots, and keep them in place in this GC.
+///
+/// The packets run in the `PinningRootsTrace` stage, after the spaces are prepared and before the
+/// closure, so no other edge can move the objects before they are pinned.
+pub struct ProcessPinnedRoots<E: ProcessEdgesWork> {
+    nodes: Vec<ObjectReference>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ProcessPinnedRoots<E> {
+    pub fn new(nodes: Vec<ObjectReference>) -> Self {
+        Self {
+            nodes,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessPinnedRoots<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ProcessPinnedRoots");
+        let mut process_edges_work = E::new(vec![], true, mmtk);
+        process_edges_work.set_worker(worker);
+        for object in self
+            .nodes
+            .iter()
+            .copied()
+            .filter(|object| !object.is_null())
+        {
+            pin_for_current_gc(mmtk, object);
+            let new_object = process_edges_work.trace_object(object);
+            debug_assert_eq!(new_object, object, "A pinned root {} is moved", object);
+        }
+        // Do not scan the objects in this stage (as `flush()` may do), otherwise their children
+        // may be moved before the other pinned roots are pinned.
+        if !process_edges_work.nodes.is_empty() {
+            let nodes = process_edges_work.pop_nodes();
+            let work = process_edges_work.create_scan_work(nodes, true);
+            mmtk.scheduler.work_buckets[WorkBucketStage::Closure].add(work);
+        }
+    }
+}
+
+/// Trace the objects of the code roots (see [`crate::util::code_roots`]) as strong roots, and
+/// record their new addresses. The code roots are updated later by [`UpdateCodeRoots`].
+#[derive(Default)]
+pub struct ProcessCodeRoots<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> ProcessCodeRoots<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessCodeRoots<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ProcessCodeRoots");
+        let mut process_edges_work = E::new(vec![], true, mmtk);
+        process_edges_work.set_worker(worker);
+        mmtk.code_roots
+            .trace(|object| process_edges_work.trace_object(object));
+        process_edges_work.flush();
+    }
+}
+
+/// Call the updaters of the code roots whose objects have moved in this GC.
+#[derive(Default)]
+pub struct UpdateCodeRoots;
+
+impl<VM: VMBinding> GCWork<VM> for UpdateCodeRoots {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        trace!("UpdateCodeRoots");
+        mmtk.code_roots.update();
+    }
+}
+
+/// Copy the objects of each group requested by
+/// [`memory_manager::request_colocation`](crate::memory_manager::request_colocation) one after
+/// another with the copy allocator of one worker, so they are placed next to each other. This runs
+/// before the closure, so the objects are not copied elsewhere first. See
+/// [`crate::util::colocation`].
+#[derive(Default)]
+pub struct ColocateObjects<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> ColocateObjects<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ColocateObjects<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ColocateObjects");
+        let groups = mmtk.colocation.take_groups();
+        if groups.is_empty() {
+            return;
+        }
+        let constraints = mmtk.plan.constraints();
+        if !constraints.moves_objects || constraints.needs_forward_after_liveness {
+            mmtk.colocation.drop_groups(groups);
+            return;
+        }
+        // The groups are held weakly by the GCs that do not copy them.
+        if !mmtk.plan.current_gc_copies_objects() {
+            mmtk.colocation.defer(groups);
+            return;
+        }
+        let mut process_edges_work = E::new(vec![], true, mmtk);
+        process_edges_work.set_worker(worker);
+        let traced: Vec<Vec<ObjectReference>> = groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|object| process_edges_work.trace_object(*object))
+                    .collect()
+            })
+            .collect();
+        mmtk.colocation.record_attempts(&traced);
+        // Scan the objects in the closure, so their children are not copied between the objects
+        // of the groups.
+        if !process_edges_work.nodes.is_empty() {
+            let nodes = process_edges_work.pop_nodes();
+            let work = process_edges_work.create_scan_work(nodes, true);
+            mmtk.scheduler.work_buckets[WorkBucketStage::Closure].add(work);
+        }
+    }
+}
+
+/// Keep the object in place in the current GC. Panic if the policy of the object cannot do so.
+fn pin_for_current_gc<VM: VMBinding>(mmtk: &'static MMTK<VM>, object: ObjectReference) {
+    let sft = crate::mmtk::SFT_MAP.get(object.to_address());
+    assert!(
+        sft.pin_for_current_gc(object),
+        "Attempted to pin {} in {}, which cannot keep it in place in this GC.  Plan: {:?}",
+        object,
+        sft.name(),
+        *mmtk.options.plan
+    );
+}
+
+/// Propagate the pinning from the roots of the pinning regions to all the objects reachable from
+/// them (see [`crate::memory_manager::pin_transitively`]).  Like `ProcessPinnedRoots`, the packets
+/// run in the `PinningRootsTrace` stage, so the objects are pinned before the closure may move them.
+/// The objects are only pinned here, and they are traced by the closure as usual.
+#[cfg(feature = "transitive_pinning")]
+pub struct PinClosure<VM: VMBinding> {
+    /// The pinned objects that have not been scanned.
+    objects: Vec<ObjectReference>,
+    phantom: PhantomData<VM>,
+}
+
+#[cfg(feature = "transitive_pinning")]
+impl<VM: VMBinding> PinClosure<VM> {
+    /// The number of objects to scan in a packet. More objects are given to a new packet.
+    const CAPACITY: usize = 4096;
+
+    /// Create a packet to scan the given objects. The objects must have been pinned.
+    pub fn new(objects: Vec<ObjectReference>) -> Self {
+        Self {
+            objects,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "transitive_pinning")]
+impl<VM: VMBinding> GCWork<VM> for PinClosure<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        trace!("PinClosure");
+        let tls = worker.tls;
+        // The objects newly marked in this packet. They are handed over once at the end.
+        let mut pinned = vec![];
+        while let Some(object) = self.objects.pop() {
+            pin_for_current_gc(mmtk, object);
+            let objects = &mut self.objects;
+            let pinned = &mut pinned;
+            let mut visit = |child: ObjectReference| {
+                if !child.is_null() && mmtk.transitive_pinning.try_pin(child) {
+                    objects.push(child);
+                    pinned.push(child);
+                }
+            };
+            if VM::VMScanning::support_edge_enqueuing(tls, object) {
+                VM::VMScanning::scan_object(tls, object, &mut |edge: VM::VMEdge| {
+                    visit(edge.load())
+                });
+            } else {
+                VM::VMScanning::scan_object_and_trace_edges(
+                    tls,
+                    object,
+                    &mut |child: ObjectReference| {
+                        visit(child);
+                        child
+                    },
+                );
+            }
+            if self.objects.len() > Self::CAPACITY {
+                let half = self.objects.split_off(self.objects.len() / 2);
+                worker.add_work(
+                    WorkBucketStage::PinningRootsTrace,
+                    PinClosure::<VM>::new(half),
+                );
+            }
+        }
+        mmtk.transitive_pinning.add_pinned(pinned);
+    }
+}
+
+/// The factory that MMTk core gives to the VM for root scanning. It batches the root edges into
+/// work packets of `E::CAPACITY` edges, and filters out the duplicate edges in each packet. The
+/// remaining edges are put into a packet when the factory is dropped.
 struct ProcessEdgesWorkRootsWorkFactory<E: ProcessEdgesWork> {
     mmtk: &'static MMTK<E::VM>,
+    /// The root edges that have not been put into a packet.
+    edges: Vec<EdgeOf<E>>,
 }
 
 impl<E: ProcessEdgesWork> Clone for ProcessEdgesWorkRootsWorkFactory<E> {
     fn clone(&self) -> Self {
-        Self { mmtk: self.mmtk }
+        Self {
+            mmtk: self.mmtk,
+            edges: vec![],
+        }
     }
 }
 
 impl<E: ProcessEdgesWork> RootsWorkFactory<EdgeOf<E>> for ProcessEdgesWorkRootsWorkFactory<E> {
-    fn create_process_edge_roots_work(&mut self, edges: Vec<EdgeOf<E>>) {
-        crate::memory_manager::add_work_packet(
-            self.mmtk,
-            WorkBucketStage::Closure,
-            E::new(edges, true, self.mmtk),
-        );
+    fn create_process_edge_roots_work(&mut self, mut edges: Vec<EdgeOf<E>>) {
+        if self.edges.is_empty() {
+            self.edges = edges;
+        } else {
+            self.edges.append(&mut edges);
+        }
+        if self.edges.len() >= E::CAPACITY {
+            let edges = std::mem::take(&mut self.edges);
+            let mut chunks = edges.chunks_exact(E::CAPACITY);
+            for chunk in &mut chunks {
+                self.create_packet(chunk.to_vec());
+            }
+            self.edges = chunks.remainder().to_vec();
+        }
     }
 
     fn create_process_node_roots_work(&mut self, nodes: Vec<ObjectReference>) {
@@ -581,11 +1042,49 @@ impl<E: ProcessEdgesWork> RootsWorkFactory<EdgeOf<E>> for ProcessEdgesWorkRootsW
         let work = process_edges_work.create_scan_work(nodes, true);
         crate::memory_manager::add_work_packet(self.mmtk, WorkBucketStage::Closure, work);
     }
+
+    fn create_process_pinned_roots_work(&mut self, nodes: Vec<ObjectReference>) {
+        assert!(
+            self.mmtk.plan.constraints().supports_pinned_roots,
+            "Attempted to add pinned roots with a plan that cannot pin objects.  Plan: {:?}",
+            *self.mmtk.options.plan
+        );
+        crate::memory_manager::add_work_packet(
+            self.mmtk,
+            WorkBucketStage::PinningRootsTrace,
+            ProcessPinnedRoots::<E>::new(nodes),
+        );
+    }
 }
 
 impl<E: ProcessEdgesWork> ProcessEdgesWorkRootsWorkFactory<E> {
     fn new(mmtk: &'static MMTK<E::VM>) -> Self {
-        Self { mmtk }
+        Self {
+            mmtk,
+            edges: vec![],
+        }
+    }
+
+    /// Put the edges into a packet. The VM may report a root edge more than once (e.g. an edge
+    /// reachable from two root sets), so the duplicates in the packet are removed. Duplicates in
+    /// different packets are harmless, as tracing an object twice returns the same object.
+    fn create_packet(&self, mut edges: Vec<EdgeOf<E>>) {
+        let mut seen = HashSet::with_capacity(edges.len());
+        edges.retain(|edge| seen.insert(*edge));
+        crate::memory_manager::add_work_packet(
+            self.mmtk,
+            WorkBucketStage::Closure,
+            E::new(edges, true, self.mmtk),
+        );
+    }
+}
+
+impl<E: ProcessEdgesWork> Drop for ProcessEdgesWorkRootsWorkFactory<E> {
+    fn drop(&mut self) {
+        if !self.edges.is_empty() {
+            let edges = std::mem::take(&mut self.edges);
+            self.create_packet(edges);
+        }
     }
 }
 
@@ -628,6 +1127,17 @@ pub trait ScanObjectsWork<VM: VMBinding>: GCWork<VM> + Sized {
         mmtk: &'static MMTK<<Self::E as ProcessEdgesWork>::VM>,
     ) {
         let tls = worker.tls;
+        // The graph found by this packet, passed to the exporter at the end of the packet.
+        #[cfg(feature = "graph_export")]
+        let exporter = &mmtk.plan.base().graph_exporter;
+        #[cfg(feature = "graph_export")]
+        let mut graph = crate::util::graph_export::GraphBuffer::default();
+        #[cfg(feature = "graph_export")]
+        if self.roots() && exporter.is_active() {
+            for object in buffer.iter().copied() {
+                exporter.root(&mut graph, object);
+            }
+        }
 
         // If this is a root packet, the objects in this packet will have not been traced, yet.
         //
@@ -662,11 +1172,107 @@ pub trait ScanObjectsWork<VM: VMBinding>: GCWork<VM> + Sized {
 
         // Then scan those objects for edges.
         let mut scan_later = vec![];
+        let mut large_arrays = vec![];
+        let mut traced_bytes = 0;
         {
+            #[cfg(feature = "prefetch")]
+            let prefetch_distance = *mmtk.options.prefetch_distance;
+            let count_traced_bytes = *mmtk.options.count_traced_bytes;
+            #[cfg(feature = "immutable_objects")]
+            let is_nursery_gc = mmtk.plan.is_current_gc_nursery();
+            // The chunks of the scanned objects are live, so they are not cold.
+            #[cfg(feature = "chunk_compression")]
+            let chunk_compression = mmtk.plan.base().chunk_compression.as_ref();
             let mut closure = ObjectsClosure::<Self::E>::new(worker);
-            for object in objects_to_scan.iter().copied() {
+            for (_i, object) in objects_to_scan.iter().copied().enumerate() {
+                if count_traced_bytes {
+                    traced_bytes += <VM as VMBinding>::VMObjectModel::get_current_size(object);
+                }
+                #[cfg(feature = "chunk_compression")]
+                if let Some(chunk_compression) = chunk_compression {
+                    chunk_compression.mark_touched(object.to_address());
+                }
+                // Prefetch the object that we scan `prefetch_distance` objects later.
+                #[cfg(feature = "prefetch")]
+                if prefetch_distance != 0 {
+                    if let Some(next) = objects_to_scan.get(_i + prefetch_distance) {
+                        next.to_address().prefetch();
+                    }
+                }
                 if <VM as VMBinding>::VMScanning::support_edge_enqueuing(tls, object) {
                     // If an object supports edge-enqueuing, we enqueue its edges.
+                    #[cfg(all(feature = "heap_ids", debug_assertions))]
+                    closure.set_source(object);
+                    let layout =
+                        <VM as VMBinding>::VMScanning::LAYOUT_TYPE_ID_OFFSET.and_then(|offset| {
+                            let type_id = unsafe { (object.to_address() + offset).load::<usize>() };
+                            mmtk.object_layouts.get(type_id)
+                        });
+                    #[cfg(feature = "graph_export")]
+                    let layout = layout.filter(|_| !exporter.is_active());
+                    if let Some(layout) = layout {
+                        // The layout of the type is cached. Enqueue the edges without calling
+                        // back to the VM.
+                        closure.reserve_edges(layout.num_fields());
+                        for fields in layout.ref_field_bitmaps(object) {
+                            for field in fields.field_addresses() {
+                                let edge = <VM as VMBinding>::VMScanning::edge_for_field(field);
+                                closure.visit_edge(edge);
+                            }
+                        }
+                        self.post_scan_object(object);
+                        continue;
+                    }
+                    #[cfg(feature = "immutable_objects")]
+                    if crate::util::immutable_object::skip_scanning(object, is_nursery_gc) {
+                        // The children of the object are mature, and stay where they are.
+                        self.post_scan_object(object);
+                        continue;
+                    }
+                    if let Some(num_edges) =
+                        <VM as VMBinding>::VMScanning::edge_count_hint(tls, object)
+                    {
+                        closure.reserve_edges(num_edges);
+                    }
+                    #[cfg(feature = "graph_export")]
+                    if exporter.is_active() {
+                        <VM as VMBinding>::VMScanning::scan_object(
+                            tls,
+                            object,
+                            &mut |edge: <VM as VMBinding>::VMEdge| {
+                                exporter.edge(&mut graph, object, edge.load());
+                                closure.visit_edge(edge);
+                            },
+                        );
+                        self.post_scan_object(object);
+                        continue;
+                    }
+                    if let Some(array) = <VM as VMBinding>::VMScanning::reference_array(tls, object)
+                    {
+                        if array.len > Self::E::CAPACITY {
+                            // Large arrays are scanned in chunks by other packets.
+                            large_arrays.push((object, array));
+                        } else {
+                            for element in array.element_addresses() {
+                                let edge = <VM as VMBinding>::VMScanning::edge_for_field(element);
+                                closure.visit_edge(edge);
+                            }
+                        }
+                        self.post_scan_object(object);
+                        continue;
+                    }
+                    if let Some(fields) =
+                        <VM as VMBinding>::VMScanning::reference_field_bitmap(tls, object)
+                    {
+                        // The VM describes the reference fields with a bitmap. Enqueue the edges
+                        // without calling back to the VM for each field.
+                        for field in fields.field_addresses() {
+                            let edge = <VM as VMBinding>::VMScanning::edge_for_field(field);
+                            closure.visit_edge(edge);
+                        }
+                        self.post_scan_object(object);
+                        continue;
+                    }
                     <VM as VMBinding>::VMScanning::scan_object(tls, object, &mut closure);
                     self.post_scan_object(object);
                 } else {
@@ -680,15 +1286,28 @@ pub trait ScanObjectsWork<VM: VMBinding>: GCWork<VM> + Sized {
                 }
             }
         }
+        worker.add_traced_objects(objects_to_scan.len(), traced_bytes);
+
+        // Create work packets to scan the elements of large reference arrays.
+        for (_object, array) in large_arrays {
+            let chunk = ScanRefArrayChunk::<Self::E>::new(array);
+            #[cfg(all(feature = "heap_ids", debug_assertions))]
+            let chunk = chunk.with_source(Some(_object));
+            worker.add_work(WorkBucketStage::Closure, chunk);
+        }
 
         // If any object does not support edge-enqueuing, we process them now.
         if !scan_later.is_empty() {
             // We create an instance of E to use its `trace_object` method and its object queue.
             let mut process_edges_work = Self::E::new(vec![], false, mmtk);
-            let mut closure = |object| process_edges_work.trace_object(object);
 
             // Scan objects and trace their edges at the same time.
             for object in scan_later.iter().copied() {
+                let mut closure = |target| {
+                    #[cfg(feature = "graph_export")]
+                    exporter.edge(&mut graph, object, target);
+                    process_edges_work.trace_object(target)
+                };
                 <VM as VMBinding>::VMScanning::scan_object_and_trace_edges(
                     tls,
                     object,
@@ -716,6 +1335,9 @@ pub trait ScanObjectsWork<VM: VMBinding>: GCWork<VM> + Sized {
                 }
             }
         }
+
+        #[cfg(feature = "graph_export")]
+        exporter.flush(&mut graph);
     }
 }
 
@@ -770,6 +1392,59 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanObjects<E> {
     }
 }
 
+/// Scan a part of a reference array (see `Scanning::reference_array`), and enqueue the edges of
+/// its elements.  A packet with more elements than `E::CAPACITY` splits off half of its elements
+/// to another packet until it is small enough, so the elements of a large array are scanned by
+/// multiple workers in parallel.
+pub struct ScanRefArrayChunk<E: ProcessEdgesWork> {
+    array: RefArray,
+    /// The array object, whose elements are checked for references to other heaps.
+    #[cfg(all(feature = "heap_ids", debug_assertions))]
+    source: Option<ObjectReference>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ScanRefArrayChunk<E> {
+    pub fn new(array: RefArray) -> Self {
+        Self {
+            array,
+            #[cfg(all(feature = "heap_ids", debug_assertions))]
+            source: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Check the elements as the references of `object` (see `util::heap_id`).
+    #[cfg(all(feature = "heap_ids", debug_assertions))]
+    fn with_source(mut self, object: Option<ObjectReference>) -> Self {
+        self.source = object;
+        self
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanRefArrayChunk<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, _mmtk: &'static MMTK<E::VM>) {
+        let mut array = self.array;
+        while array.len > E::CAPACITY {
+            let (lower, upper) = array.split_at(array.len / 2);
+            let chunk = Self::new(upper);
+            #[cfg(all(feature = "heap_ids", debug_assertions))]
+            let chunk = chunk.with_source(self.source);
+            worker.add_work(WorkBucketStage::Closure, chunk);
+            array = lower;
+        }
+        let mut closure = ObjectsClosure::<E>::new(worker);
+        #[cfg(all(feature = "heap_ids", debug_assertions))]
+        if let Some(object) = self.source {
+            closure.set_source(object);
+        }
+        for element in array.element_addresses() {
+            let edge = <E::VM as VMBinding>::VMScanning::edge_for_field(element);
+            closure.visit_edge(edge);
+        }
+    }
+}
+
 pub struct ProcessModBuf<E: ProcessEdgesWork> {
     modbuf: Vec<ObjectReference>,
     phantom: PhantomData<E>,
@@ -789,17 +1464,34 @@ impl<E: ProcessEdgesWork> ProcessModBuf<E> {
 impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessModBuf<E> {
     #[inline(always)]
     fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        // Only the generational plans use the mod buffers. If the young objects are logged, the
+        // ones that are collected in this GC are neither scanned nor unlogged here: their copies
+        // are scanned if they are reachable, and the promoted copies are unlogged.
+        let gen = mmtk.plan.generational();
+        if gen.logs_young_objects() {
+            self.modbuf
+                .retain(|obj| !(gen.nursery.in_space(*obj) && gen.nursery.in_from_space(*obj)));
+        }
         if !self.modbuf.is_empty() {
+            #[cfg(feature = "chunk_compression")]
+            let chunk_compression = mmtk.plan.base().chunk_compression.as_ref();
             for obj in &self.modbuf {
                 store_metadata::<E::VM>(&self.meta, *obj, 1, None, Some(Ordering::SeqCst));
+                // The object was written by the mutator, so its chunk is not cold.
+                #[cfg(feature = "chunk_compression")]
+                if let Some(chunk_compression) = chunk_compression {
+                    chunk_compression.mark_touched(obj.to_address());
+                }
             }
         }
         if mmtk.plan.is_current_gc_nursery() {
             if !self.modbuf.is_empty() {
                 let mut modbuf = vec![];
                 ::std::mem::swap(&mut modbuf, &mut self.modbuf);
+                // Use the scan work packet of the plan, so the plan can see the objects in the mod buffer.
+                let process_edges_work = E::new(vec![], false, mmtk);
                 GCWork::do_work(
-                    &mut ScanObjects::<E>::new(modbuf, false, false),
+                    &mut process_edges_work.create_scan_work(modbuf, false),
                     worker,
                     mmtk,
                 )
@@ -817,6 +1509,16 @@ use crate::policy::gc_work::TraceKind;
 
 /// This provides an implementation of [`ProcessEdgesWork`](scheduler/gc_work/ProcessEdgesWork). A plan that implements
 /// `PlanTraceObject` can use this work packet for tracing objects.
+///
+/// The packet is monomorphized for each combination of the plan `P` and the trace kind `KIND`. `trace_object` finds the
+/// policy of an object with the statically generated checks of the plan's spaces, and calls the policy method
+/// specialized for `KIND`, so tracing does not use dynamic dispatch through the SFT (unlike `SFTProcessEdges`). The
+/// object-scanning packets it creates (`PlanScanObjects`) find the policy for `post_scan_object` in the same way.
+///
+/// A binding does not name these types. The plan selects its `ProcessEdgesWork` for each kind of GC through its
+/// [`GCWorkContext`], and the scheduler passes it to the binding as the type parameter of the generic
+/// callbacks, e.g. the `RootsWorkFactory` in `Scanning::scan_thread_roots()`. A binding that creates its own work
+/// packets should be generic over `ProcessEdgesWork` in the same way, so that it uses the specialized types.
 pub struct PlanProcessEdges<
     VM: VMBinding,
     P: Plan<VM = VM> + PlanTraceObject<VM>,
@@ -14,7 +14,18 @@ pub use work::GCWork;
 pub(crate) use work::GCWorkContext;
 
 mod work_bucket;
-pub use work_bucket::WorkBucketStage;
+pub use work_bucket::{GCPhase, StageOpenHook, WorkBucketStage};
+
+mod watchdog;
+
+mod background;
+
+mod vm_tasks;
+pub use vm_tasks::{VMTaskBuilder, VMTaskId};
+
+mod failure;
+pub(crate) use failure::do_work_or_fail;
+pub use failure::GCFailure;
 
 mod worker;
 pub use worker::GCWorker;
@@ -1,19 +1,29 @@
+use super::background::BackgroundWork;
 use super::stat::SchedulerStat;
+use super::watchdog::WorkerActivity;
 use super::work_bucket::*;
 use super::worker::{GCWorker, GCWorkerShared, WorkerGroup};
 use super::*;
 use crate::mmtk::MMTK;
+use crate::plan::gc_requester::GCRequester;
 use crate::util::opaque_pointer::*;
-use crate::vm::Collection;
-use crate::vm::{GCThreadContext, VMBinding};
+use crate::vm::VMBinding;
+use crate::vm::{Collection, GCThreadContext};
 use crossbeam::deque::{self, Steal};
 use enum_map::Enum;
 use enum_map::{enum_map, EnumMap};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Condvar, Mutex};
 
+/// The number of GC threads that are in their main loop.
+#[derive(Default)]
+struct RunningGCThreads {
+    controller: usize,
+    workers: usize,
+}
+
 pub enum CoordinatorMessage<VM: VMBinding> {
     /// Send a work-packet to the coordinator thread/
     Work(Box<dyn CoordinatorWork<VM>>),
@@ -44,6 +54,33 @@ pub struct GCWorkScheduler<VM: VMBinding> {
     closure_end: Mutex<Option<Box<dyn Send + Fn() -> bool>>>,
     /// Counter for pending coordinator messages.
     pub(super) pending_coordinator_packets: AtomicUsize,
+    /// The number of edge-processing work packets created while tracing objects in the current GC.
+    /// It decides the size of the edge buffers (see `ProcessEdgesWork::MIN_CAPACITY`).
+    closure_edge_packets: AtomicUsize,
+    /// Do the GC threads record the work packets they execute for the GC watchdog?
+    watchdog_enabled: AtomicBool,
+    /// Do all the pending packets stay in the buckets, where they are counted, instead of the
+    /// local queues of the workers? This is the case for the GC watchdog, and for the bounded
+    /// memory for tracing (see the option `max_tracing_memory`).
+    counts_pending_packets: AtomicBool,
+    /// Has a work packet panicked? See `scheduler::failure`.
+    failed: AtomicBool,
+    /// Low-priority packets that are only executed when no GC is in progress.
+    pub(super) background_work: BackgroundWork<VM>,
+    /// Have the work packets of the current GC been scheduled? The workers only tell the
+    /// controller that the GC is finished while this is set, so a message sent between GCs, e.g.
+    /// after a background packet, cannot finish the next GC early.
+    gc_scheduled: AtomicBool,
+    /// Should the workers exit? This is set when the MMTk instance shuts down.
+    workers_exiting: AtomicBool,
+    /// The number of the running controller and worker threads, i.e. the threads that have
+    /// started their main loop and not yet exited it, and the condvar notified when one exits.
+    running_gc_threads: (Mutex<RunningGCThreads>, Condvar),
+    /// Are the GCs done on the mutator threads, without GC threads?
+    single_threaded: bool,
+    /// The objects whose scanning is deferred as the tracing memory is full.
+    #[cfg(feature = "tracing_overflow")]
+    pub(crate) tracing_overflow: crate::util::tracing_overflow::TracingOverflow<VM>,
 }
 
 // FIXME: GCWorkScheduler should be naturally Sync, but we cannot remove this `impl` yet.
@@ -52,7 +89,9 @@ pub struct GCWorkScheduler<VM: VMBinding> {
 unsafe impl<VM: VMBinding> Sync for GCWorkScheduler<VM> {}
 
 impl<VM: VMBinding> GCWorkScheduler<VM> {
-    pub fn new(num_workers: usize) -> Arc<Self> {
+    /// Create a scheduler with `num_workers` workers. If `single_threaded` is true, the GCs are
+    /// done on the mutator threads that trigger them, and no GC thread is created.
+    pub fn new(num_workers: usize, single_threaded: bool) -> Arc<Self> {
         let worker_monitor: Arc<(Mutex<()>, Condvar)> = Default::default();
         let worker_group = WorkerGroup::new(num_workers);
 
@@ -60,6 +99,8 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
         let mut work_buckets = enum_map! {
             WorkBucketStage::Unconstrained => WorkBucket::new(true, worker_monitor.clone(), worker_group.clone()),
             WorkBucketStage::Prepare => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
+            WorkBucketStage::PinningRootsTrace => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
+            WorkBucketStage::Colocation => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
             WorkBucketStage::Closure => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
             WorkBucketStage::SoftRefClosure => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
             WorkBucketStage::WeakRefClosure => WorkBucket::new(false, worker_monitor.clone(), worker_group.clone()),
@@ -116,6 +157,17 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
             worker_monitor,
             closure_end: Mutex::new(None),
             pending_coordinator_packets: AtomicUsize::new(0),
+            closure_edge_packets: AtomicUsize::new(0),
+            watchdog_enabled: AtomicBool::new(false),
+            counts_pending_packets: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+            background_work: BackgroundWork::new(),
+            gc_scheduled: AtomicBool::new(false),
+            workers_exiting: AtomicBool::new(false),
+            running_gc_threads: (Mutex::new(RunningGCThreads::default()), Condvar::new()),
+            single_threaded,
+            #[cfg(feature = "tracing_overflow")]
+            tracing_overflow: crate::util::tracing_overflow::TracingOverflow::new(),
         })
     }
 
@@ -126,6 +178,16 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
 
     /// Create GC threads, including the controller thread and all workers.
     pub fn spawn_gc_threads(self: &Arc<Self>, mmtk: &'static MMTK<VM>, tls: VMThread) {
+        let watchdog_enabled = *mmtk.options.gc_watchdog_timeout != 0;
+        self.watchdog_enabled
+            .store(watchdog_enabled, Ordering::Relaxed);
+        let counts_pending_packets = watchdog_enabled || *mmtk.options.max_tracing_memory != 0;
+        self.counts_pending_packets
+            .store(counts_pending_packets, Ordering::Relaxed);
+        for (_, bucket) in self.work_buckets.iter() {
+            bucket.set_watched(counts_pending_packets);
+        }
+
         // Create the communication channel.
         let (sender, receiver) = channel::<CoordinatorMessage<VM>>();
 
@@ -146,9 +208,80 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
             receiver,
             coordinator_worker,
         );
-        VM::VMCollection::spawn_gc_thread(tls, GCThreadContext::<VM>::Controller(gc_controller));
+        if self.single_threaded {
+            // Without GC threads, the controller and the only worker run on the mutator thread
+            // that blocks for a GC. The GC requester keeps them until then.
+            let mut gc_controller = gc_controller;
+            let mut workers = self.worker_group.create_workers(mmtk, sender);
+            debug_assert_eq!(workers.len(), 1);
+            gc_controller.set_worker(workers.pop().unwrap(), VMWorkerThread(tls));
+            mmtk.plan.base().gc_requester.set_controller(gc_controller);
+        } else {
+            VM::VMCollection::spawn_gc_thread(
+                tls,
+                GCThreadContext::<VM>::Controller(gc_controller),
+            );
+            self.worker_group.spawn(mmtk, sender, tls)
+        }
+    }
 
-        self.worker_group.spawn(mmtk, sender, tls)
+    /// Open the next buckets when the open buckets are drained, if there are no GC threads. Return
+    /// false if no bucket has packets, i.e. the GC is finished.
+    pub(super) fn open_buckets_on_current_thread(&self) -> bool {
+        debug_assert!(!self.worker_group.has_designated_work());
+        self.update_buckets()
+    }
+
+    /// Let the GC threads exit, and wait until they have exited. This is called when the MMTk
+    /// instance shuts down. The controller exits once it has finished the current GC, if any, and
+    /// only then the workers exit, so a GC is never interrupted.
+    ///
+    /// Only the threads that have already started are waited for, as the VM may not have started
+    /// some (or any) of the threads it was asked to spawn. A thread that starts later sees the
+    /// exit flags and leaves its main loop immediately.
+    pub fn shut_down_gc_threads(&self, requester: &GCRequester<VM>) {
+        requester.exit();
+        if self.single_threaded {
+            // There are no GC threads.
+            return;
+        }
+        self.wait_for_gc_threads(|running| running.controller == 0);
+        {
+            let _guard = self.worker_monitor.0.lock().unwrap();
+            self.workers_exiting.store(true, Ordering::SeqCst);
+            self.worker_monitor.1.notify_all();
+        }
+        self.wait_for_gc_threads(|running| running.workers == 0);
+    }
+
+    fn wait_for_gc_threads(&self, done: impl Fn(&RunningGCThreads) -> bool) {
+        let (lock, cvar) = &self.running_gc_threads;
+        let mut running = lock.lock().unwrap();
+        while !done(&running) {
+            running = cvar.wait(running).unwrap();
+        }
+    }
+
+    /// Called by a GC thread when it enters its main loop.
+    pub(super) fn on_gc_thread_start(&self, is_controller: bool) {
+        let mut running = self.running_gc_threads.0.lock().unwrap();
+        if is_controller {
+            running.controller += 1;
+        } else {
+            running.workers += 1;
+        }
+    }
+
+    /// Called by a GC thread when it exits its main loop.
+    pub(super) fn on_gc_thread_exit(&self, is_controller: bool) {
+        let (lock, cvar) = &self.running_gc_threads;
+        let mut running = lock.lock().unwrap();
+        if is_controller {
+            running.controller -= 1;
+        } else {
+            running.workers -= 1;
+        }
+        cvar.notify_all();
     }
 
     /// Schedule all the common work packets
@@ -167,6 +300,12 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
 
         // Release global/collectors/mutators
         self.work_buckets[WorkBucketStage::Release].add(Release::<C>::new(plan));
+        // The objects of the code roots have been forwarded by the end of the closures.
+        self.work_buckets[WorkBucketStage::Release].add(UpdateCodeRoots);
+
+        // Co-locate the groups of objects requested by the binding.
+        self.work_buckets[WorkBucketStage::Colocation]
+            .add(ColocateObjects::<C::ProcessEdgesWorkType>::new());
 
         // Analysis GC work
         #[cfg(feature = "analysis")]
@@ -231,6 +370,38 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
         buckets.iter().all(|&b| self.work_buckets[b].is_drained())
     }
 
+    /// Add a hook that schedules work packets for a stage when its bucket opens in each GC, i.e.
+    /// after the buckets of all the previous stages have been drained. A plan can use this to
+    /// insert its own phases between the common ones without changing the common work packets,
+    /// e.g. to select the regions to evacuate once the marking closure is finished. The hooks of
+    /// a stage are called in the order they are added.
+    ///
+    /// A hook is called by a GC worker while it holds the worker monitor. The hook must return
+    /// the packets rather than adding them to a bucket, which would notify the workers.
+    pub fn on_stage_open(&self, stage: WorkBucketStage, hook: StageOpenHook<VM>) {
+        assert_ne!(
+            stage,
+            WorkBucketStage::Unconstrained,
+            "The Unconstrained bucket is always open"
+        );
+        self.work_buckets[stage].add_open_hook(hook);
+    }
+
+    /// Insert a phase into each GC after the stage `after`, and after the phases already added
+    /// for the stage. Once the bucket of the stage is drained, the packets of the phase are
+    /// added to the bucket, and the next stage only opens after they are drained, too. A plan can
+    /// use this to add its own phases without changing the common work packets.
+    ///
+    /// The phases should be added before the first GC.
+    pub fn add_phase(&self, after: WorkBucketStage, phase: Box<dyn GCPhase<VM>>) {
+        assert_ne!(
+            after,
+            WorkBucketStage::Unconstrained,
+            "The Unconstrained bucket is never finished"
+        );
+        self.work_buckets[after].add_phase(phase);
+    }
+
     pub fn on_closure_end(&self, f: Box<dyn Send + Fn() -> bool>) {
         *self.closure_end.lock().unwrap() = Some(f);
     }
@@ -246,6 +417,15 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
     ///
     /// Return true if there're any non-empty buckets updated.
     fn update_buckets(&self) -> bool {
+        // Scan the overflowed objects before the next stage opens.
+        #[cfg(feature = "tracing_overflow")]
+        if self.schedule_tracing_overflow() {
+            return true;
+        }
+        // Run the phases inserted after the last open stage before the next stage opens.
+        if self.schedule_next_phase() {
+            return true;
+        }
         let mut buckets_updated = false;
         let mut new_packets = false;
         for i in 0..WorkBucketStage::LENGTH {
@@ -257,7 +437,9 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
             let bucket_opened = bucket.update(self);
             buckets_updated = buckets_updated || bucket_opened;
             if bucket_opened {
-                new_packets = new_packets || !bucket.is_drained();
+                // Run the phases after an empty stage before the next stage opens.
+                new_packets =
+                    new_packets || !bucket.is_drained() || bucket.schedule_next_phase(self);
                 // Quit the loop. There'are already new packets in the newly opened buckets.
                 if new_packets {
                     break;
@@ -267,6 +449,51 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
         buckets_updated && new_packets
     }
 
+    /// Schedule the next phase after the last open stage, if it has any (see `add_phase`).
+    /// Return true if there are packets for it. Like `update_buckets()`, this does not notify
+    /// the workers.
+    fn schedule_next_phase(&self) -> bool {
+        let last_open_stage = (0..WorkBucketStage::LENGTH)
+            .map(WorkBucketStage::from_usize)
+            .filter(|id| *id != WorkBucketStage::Unconstrained)
+            .filter(|id| self.work_buckets[*id].is_activated())
+            .last();
+        match last_open_stage {
+            Some(stage) if self.work_buckets[stage].is_drained() => {
+                self.work_buckets[stage].schedule_next_phase(self)
+            }
+            _ => false,
+        }
+    }
+
+    /// Add the packets that scan the overflowed objects to the last open stage. Return true if
+    /// there are any. Like `update_buckets()`, this does not notify the workers.
+    #[cfg(feature = "tracing_overflow")]
+    fn schedule_tracing_overflow(&self) -> bool {
+        let packets = self.tracing_overflow.take_rescan_work();
+        if packets.is_empty() {
+            return false;
+        }
+        let stage = (0..WorkBucketStage::LENGTH)
+            .map(WorkBucketStage::from_usize)
+            .filter(|id| *id != WorkBucketStage::Unconstrained)
+            .filter(|id| self.work_buckets[*id].is_activated())
+            .last()
+            .unwrap();
+        self.work_buckets[stage].bulk_add_without_notify(packets);
+        true
+    }
+
+    /// The number of packets pending in the closure stages. With the option `max_tracing_memory`,
+    /// all the pending packets stay in the buckets (see `counts_pending_packets()`), so this only
+    /// leaves out the packets that the workers are executing.
+    #[cfg(feature = "tracing_overflow")]
+    pub fn pending_closure_packets(&self) -> usize {
+        (WorkBucketStage::Closure as usize..=LAST_CLOSURE_BUCKET as usize)
+            .map(|i| self.work_buckets[WorkBucketStage::from_usize(i)].pending_packets())
+            .sum()
+    }
+
     pub fn deactivate_all(&self) {
         self.work_buckets.iter().for_each(|(id, bkt)| {
             if id != WorkBucketStage::Unconstrained {
@@ -341,6 +568,12 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
                 _ => {}
             }
         }
+        // Run background work only if there is nothing else to do.
+        if !should_retry {
+            if let Some(w) = self.background_work.poll() {
+                return Steal::Success(w);
+            }
+        }
         if should_retry {
             Steal::Retry
         } else {
@@ -350,7 +583,10 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
 
     /// Get a schedulable work packet.
     #[inline]
-    fn poll_schedulable_work(&self, worker: &GCWorker<VM>) -> Option<Box<dyn GCWork<VM>>> {
+    pub(super) fn poll_schedulable_work(
+        &self,
+        worker: &GCWorker<VM>,
+    ) -> Option<Box<dyn GCWork<VM>>> {
         // Loop until we successfully get a packet.
         loop {
             match self.poll_schedulable_work_once(worker) {
@@ -358,7 +594,7 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
                     return Some(w);
                 }
                 Steal::Retry => {
-                    std::thread::yield_now();
+                    crate::util::platform::platform().yield_now();
                     continue;
                 }
                 Steal::Empty => {
@@ -371,19 +607,25 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
     /// Called by workers to get a schedulable work packet.
     /// Park the worker if there're no available packets.
     #[inline]
-    pub fn poll(&self, worker: &GCWorker<VM>) -> Box<dyn GCWork<VM>> {
+    /// Get a work packet for the worker, and wait if there is none. Return `None` if the worker
+    /// should exit, as the MMTk instance shuts down.
+    pub fn poll(&self, worker: &GCWorker<VM>) -> Option<Box<dyn GCWork<VM>>> {
         self.poll_schedulable_work(worker)
-            .unwrap_or_else(|| self.poll_slow(worker))
+            .or_else(|| self.poll_slow(worker))
     }
 
     #[cold]
-    fn poll_slow(&self, worker: &GCWorker<VM>) -> Box<dyn GCWork<VM>> {
+    fn poll_slow(&self, worker: &GCWorker<VM>) -> Option<Box<dyn GCWork<VM>>> {
         // Note: The lock is released during `wait` in the loop.
         let mut guard = self.worker_monitor.0.lock().unwrap();
         loop {
+            // The workers only exit after the controller has exited, so no GC is in progress.
+            if self.workers_exiting.load(Ordering::SeqCst) {
+                return None;
+            }
             // Retry polling
             if let Some(work) = self.poll_schedulable_work(worker) {
-                return work;
+                return Some(work);
             }
             // Prepare to park this worker
             let all_parked = self.worker_group.inc_parked_workers();
@@ -410,11 +652,13 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
                             self.worker_monitor.1.notify_all();
                         }
                         // Return this packet and execute it.
-                        return work;
+                        return Some(work);
                     }
                     debug_assert!(!self.worker_group.has_designated_work());
                     // The current pause is finished if we can't open more buckets.
-                    worker.sender.send(CoordinatorMessage::Finish).unwrap();
+                    if self.gc_scheduled.load(Ordering::SeqCst) {
+                        worker.sender.send(CoordinatorMessage::Finish).unwrap();
+                    }
                 }
                 // Otherwise, if there is still pending coordinator work, the last parked
                 // worker will wait on the monitor, too.  The coordinator will notify a
@@ -428,6 +672,73 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
         }
     }
 
+    /// Should the GC threads record the work packets they execute for the GC watchdog?
+    #[inline(always)]
+    pub fn is_watchdog_enabled(&self) -> bool {
+        self.watchdog_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Do all the pending packets stay in the buckets, where they are counted? If so, the workers
+    /// do not keep packets in their local queues.
+    #[inline(always)]
+    pub fn counts_pending_packets(&self) -> bool {
+        self.counts_pending_packets.load(Ordering::Relaxed)
+    }
+
+    /// Mark the GC as failed because a work packet panicked. Return true if this is the first
+    /// failure, i.e. the caller should report it.
+    pub(super) fn mark_failed(&self) -> bool {
+        !self.failed.swap(true, Ordering::SeqCst)
+    }
+
+    /// The work packet the embedded worker of the controller thread is executing.
+    pub(super) fn coordinator_activity(&self) -> &WorkerActivity {
+        &self.coordinator_worker_shared.activity
+    }
+
+    /// Add a background packet, which is executed by a worker when the worker is idle and no GC
+    /// is in progress.
+    pub fn add_background_work(&self, work: Box<dyn GCWork<VM>>) {
+        self.background_work.add(work);
+        if self.background_work.is_runnable() && self.worker_group.parked_workers() > 0 {
+            let _guard = self.worker_monitor.0.lock().unwrap();
+            self.worker_monitor.1.notify_one();
+        }
+    }
+
+    /// Called by the GC controller before a GC starts. This waits for the background packets
+    /// being executed to finish, and holds the others until the GC is finished.
+    pub(super) fn on_gc_start(&self) {
+        self.background_work.on_gc_start();
+    }
+
+    /// Called by the GC controller after it has scheduled the work packets of a GC. From now on,
+    /// the last parked worker tells the controller when the GC is finished. The workers are woken
+    /// up, as the last parked worker may have drained the buckets before.
+    pub(super) fn on_gc_scheduled(&self) {
+        let _guard = self.worker_monitor.0.lock().unwrap();
+        self.gc_scheduled.store(true, Ordering::SeqCst);
+        self.worker_monitor.1.notify_all();
+    }
+
+    /// Called by the GC controller once the workers have finished the GC. The workers decide
+    /// whether to send `CoordinatorMessage::Finish` while they hold the monitor lock, so no
+    /// message is sent for the GC after this returns.
+    pub(super) fn on_gc_finished(&self) {
+        let _guard = self.worker_monitor.0.lock().unwrap();
+        self.gc_scheduled.store(false, Ordering::SeqCst);
+    }
+
+    /// Called by the GC controller after a GC is finished. This wakes up the workers if there
+    /// are background packets.
+    pub(super) fn on_gc_end(&self) {
+        self.background_work.on_gc_end();
+        if self.background_work.is_runnable() {
+            let _guard = self.worker_monitor.0.lock().unwrap();
+            self.worker_monitor.1.notify_all();
+        }
+    }
+
     pub fn enable_stat(&self) {
         for worker in &self.worker_group.workers_shared {
             let worker_stat = worker.borrow_stat();
@@ -448,12 +759,70 @@ impl<VM: VMBinding> GCWorkScheduler<VM> {
         summary.harness_stat()
     }
 
+    /// The capacity of the next edge buffer for tracing, between `min` and `max` edges. The buffers
+    /// start small at the beginning of a GC so that there is soon enough work for all the workers,
+    /// and grow as more edge-processing packets are created, to reduce the per-packet overhead.
+    #[inline]
+    pub fn edge_buffer_capacity(&self, min: usize, max: usize) -> usize {
+        let packets = self.closure_edge_packets.load(Ordering::Relaxed);
+        adaptive_edge_buffer_capacity(packets, self.num_workers(), min, max)
+    }
+
+    /// Record that an edge-processing work packet has been created while tracing objects.
+    #[inline]
+    pub fn on_edge_packet_created(&self) {
+        self.closure_edge_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn notify_mutators_paused(&self, mmtk: &'static MMTK<VM>) {
         mmtk.plan.base().gc_requester.clear_request();
+        self.closure_edge_packets.store(0, Ordering::Relaxed);
         let first_stw_bucket = &self.work_buckets[WorkBucketStage::first_stw_stage()];
         debug_assert!(!first_stw_bucket.is_activated());
-        first_stw_bucket.activate();
+        first_stw_bucket.open(self);
         let _guard = self.worker_monitor.0.lock().unwrap();
         self.worker_monitor.1.notify_all();
     }
 }
+
+/// The edge buffer capacity after `packets` edge-processing packets have been created in a GC. The
+/// capacity starts at `min`, and doubles every time each of the `num_workers` workers could have
+/// got a packet, until it reaches `max`.
+fn adaptive_edge_buffer_capacity(
+    packets: usize,
+    num_workers: usize,
+    min: usize,
+    max: usize,
+) -> usize {
+    let min = usize::min(min, max).max(1);
+    let doublings = packets / num_workers.max(1);
+    if doublings >= (max / min).next_power_of_two().trailing_zeros() as usize {
+        max
+    } else {
+        usize::min(min << doublings, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_edge_buffer_capacity() {
+        // The buffers start small.
+        assert_eq!(adaptive_edge_buffer_capacity(0, 4, 256, 4096), 256);
+        assert_eq!(adaptive_edge_buffer_capacity(3, 4, 256, 4096), 256);
+        // They double after each round of packets for the workers.
+        assert_eq!(adaptive_edge_buffer_capacity(4, 4, 256, 4096), 512);
+        assert_eq!(adaptive_edge_buffer_capacity(12, 4, 256, 4096), 2048);
+        // They do not grow beyond the maximum.
+        assert_eq!(adaptive_edge_buffer_capacity(16, 4, 256, 4096), 4096);
+        assert_eq!(
+            adaptive_edge_buffer_capacity(usize::MAX, 4, 256, 4096),
+            4096
+        );
+        assert_eq!(adaptive_edge_buffer_capacity(0, 4, 4096, 4096), 4096);
+        assert_eq!(adaptive_edge_buffer_capacity(0, 4, 100, 300), 100);
+        assert_eq!(adaptive_edge_buffer_capacity(8, 4, 100, 300), 300);
+    }
+}
@@ -0,0 +1,184 @@
+//! Parallel tasks of the binding. A binding may have its own work to do in a GC, such as string
+//! deduplication, sweeping the code cache, or cleaning up the symbol table. Instead of running the
+//! work on its own threads, the binding can describe the work as a set of work packets with a
+//! [`VMTaskBuilder`], and MMTk executes the packets on the GC workers in the given stage. A packet
+//! may depend on other packets in the same builder, in which case it is only added to the work
+//! bucket once all the packets it depends on have finished.
+
+use super::*;
+use crate::mmtk::MMTK;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Identify a task added to a [`VMTaskBuilder`], so later tasks can depend on it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct VMTaskId(usize);
+
+/// Build a set of tasks for a GC stage. For example:
+///
+/// ```ignore
+/// let mut tasks = VMTaskBuilder::new(WorkBucketStage::WeakRefClosure);
+/// let dedup = tasks.add(DeduplicateStrings);
+/// tasks.add_after(CleanSymbolTable, &[dedup]);
+/// tasks.add(SweepCodeCache);
+/// memory_manager::schedule_vm_tasks(mmtk, tasks);
+/// ```
+pub struct VMTaskBuilder<VM: VMBinding> {
+    stage: WorkBucketStage,
+    packets: Vec<Box<dyn GCWork<VM>>>,
+    dependencies: Dependencies,
+}
+
+impl<VM: VMBinding> VMTaskBuilder<VM> {
+    /// Create a builder for tasks that are executed in the given stage.
+    pub fn new(stage: WorkBucketStage) -> Self {
+        Self {
+            stage,
+            packets: vec![],
+            dependencies: Dependencies::default(),
+        }
+    }
+
+    /// Add a task that can be executed as soon as the stage opens.
+    pub fn add<W: GCWork<VM>>(&mut self, packet: W) -> VMTaskId {
+        self.add_after(packet, &[])
+    }
+
+    /// Add a task that is executed after all the tasks in `dependencies` have finished. The
+    /// dependencies must have been added to this builder.
+    pub fn add_after<W: GCWork<VM>>(&mut self, packet: W, dependencies: &[VMTaskId]) -> VMTaskId {
+        self.packets.push(Box::new(packet));
+        VMTaskId(self.dependencies.add(dependencies))
+    }
+
+    /// Add the tasks that have no dependencies to the work bucket of the stage. The other tasks
+    /// are added once their dependencies have finished.
+    pub(crate) fn schedule(self, scheduler: &GCWorkScheduler<VM>) {
+        let graph = Arc::new(TaskGraph {
+            stage: self.stage,
+            packets: self
+                .packets
+                .into_iter()
+                .map(|packet| spin::Mutex::new(Some(packet)))
+                .collect(),
+            pending: self
+                .dependencies
+                .counts
+                .iter()
+                .map(|&count| AtomicUsize::new(count))
+                .collect(),
+            dependents: self.dependencies.dependents,
+        });
+        let counts = self.dependencies.counts;
+        let roots = (0..counts.len())
+            .filter(|&index| counts[index] == 0)
+            .map(|index| graph.take(index))
+            .collect();
+        scheduler.work_buckets[self.stage].bulk_add(roots);
+    }
+}
+
+/// The dependencies between the tasks of a builder. A task may only depend on the tasks added
+/// before it, so the dependencies cannot form a cycle.
+#[derive(Default)]
+struct Dependencies {
+    /// The number of tasks each task depends on.
+    counts: Vec<usize>,
+    /// The tasks that depend on each task.
+    dependents: Vec<Vec<usize>>,
+}
+
+impl Dependencies {
+    /// Add a task with the given dependencies, and return its index.
+    fn add(&mut self, dependencies: &[VMTaskId]) -> usize {
+        let index = self.counts.len();
+        let mut count = 0;
+        for &VMTaskId(dependency) in dependencies {
+            assert!(
+                dependency < index,
+                "A task can only depend on the tasks added before it to the same builder"
+            );
+            if !self.dependents[dependency].contains(&index) {
+                self.dependents[dependency].push(index);
+                count += 1;
+            }
+        }
+        self.counts.push(count);
+        self.dependents.push(vec![]);
+        index
+    }
+}
+
+/// The tasks of a builder that has been scheduled.
+struct TaskGraph<VM: VMBinding> {
+    stage: WorkBucketStage,
+    /// The packets that have not been added to the bucket.
+    packets: Vec<spin::Mutex<Option<Box<dyn GCWork<VM>>>>>,
+    /// The number of unfinished tasks each task depends on.
+    pending: Vec<AtomicUsize>,
+    /// The tasks that depend on each task.
+    dependents: Vec<Vec<usize>>,
+}
+
+impl<VM: VMBinding> TaskGraph<VM> {
+    /// Take the packet of a task that is ready to run.
+    fn take(self: &Arc<Self>, index: usize) -> Box<dyn GCWork<VM>> {
+        let work = self.packets[index].lock().take().unwrap();
+        Box::new(VMTask {
+            graph: self.clone(),
+            index,
+            work,
+        })
+    }
+}
+
+/// A task that is ready to run. Once it finishes, the tasks that depend on it and have no other
+/// unfinished dependencies are added to the bucket.
+struct VMTask<VM: VMBinding> {
+    graph: Arc<TaskGraph<VM>>,
+    index: usize,
+    work: Box<dyn GCWork<VM>>,
+}
+
+impl<VM: VMBinding> GCWork<VM> for VMTask<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        self.work.do_work(worker, mmtk);
+        // The dependents are added before this packet finishes, so the bucket is not drained
+        // before they run.
+        let ready: Vec<_> = self.graph.dependents[self.index]
+            .iter()
+            .filter(|&&dependent| self.graph.pending[dependent].fetch_sub(1, Ordering::SeqCst) == 1)
+            .map(|&dependent| self.graph.take(dependent))
+            .collect();
+        mmtk.scheduler.work_buckets[self.graph.stage].bulk_add(ready);
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        self.work.get_type_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependencies() {
+        let mut dependencies = Dependencies::default();
+        let a = VMTaskId(dependencies.add(&[]));
+        let b = VMTaskId(dependencies.add(&[a]));
+        // A duplicated dependency is only counted once.
+        let c = VMTaskId(dependencies.add(&[a, b, b]));
+        assert_eq!(c, VMTaskId(2));
+        assert_eq!(dependencies.counts, vec![0, 1, 2]);
+        assert_eq!(dependencies.dependents, vec![vec![1, 2], vec![2], vec![]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_forward_dependency() {
+        let mut dependencies = Dependencies::default();
+        dependencies.add(&[VMTaskId(0)]);
+    }
+}
@@ -0,0 +1,247 @@
+//! The GC watchdog reports a GC that gets stuck, e.g. because the binding deadlocks in
+//! `Scanning::scan_object`. It runs on the GC controller thread, which is otherwise idle while it
+//! waits for messages from the workers. If the workers have not finished any work packet within
+//! the timeout (the option `gc_watchdog_timeout`), the watchdog prints the state of the work
+//! buckets and the work packet each GC thread is executing, and optionally aborts the process.
+//!
+//! While the watchdog is enabled, the work buckets count their pending packets of each type, and
+//! the workers take the packets from the buckets one at a time instead of in batches, so the
+//! report can list the types of the pending packets.
+
+use super::*;
+use crate::util::VMWorkerThread;
+use crate::vm::{Collection, VMBinding};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// What a GC thread is doing. The thread updates this around each work packet if the watchdog is
+/// enabled, and the watchdog reads it when it reports a stuck GC.
+#[derive(Default)]
+pub(super) struct WorkerActivity {
+    /// The number of work packets this thread has finished.
+    finished_packets: AtomicUsize,
+    /// The work packet the thread is executing.
+    current: spin::Mutex<CurrentPacket>,
+}
+
+#[derive(Default)]
+struct CurrentPacket {
+    /// The thread that executes the packets. It is `None` until the thread executes a packet.
+    tls: Option<VMWorkerThread>,
+    /// The type of the packet and when the thread started executing it, or `None` if the thread is
+    /// not executing a packet.
+    packet: Option<(&'static str, Instant)>,
+}
+
+impl WorkerActivity {
+    /// Record that the thread `tls` starts executing a packet of the given type.
+    #[inline]
+    pub fn begin(&self, tls: VMWorkerThread, packet_type: &'static str) {
+        let mut current = self.current.lock();
+        current.tls = Some(tls);
+        current.packet = Some((packet_type, Instant::now()));
+    }
+
+    /// Record that the thread has finished the packet it was executing.
+    #[inline]
+    pub fn end(&self) {
+        self.current.lock().packet = None;
+        self.finished_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn finished_packets(&self) -> usize {
+        self.finished_packets.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) struct Watchdog {
+    /// How long the workers may not finish any work packet before we report the GC as stuck.
+    /// The watchdog is disabled if this is `None`.
+    timeout: Option<Duration>,
+    /// Should we abort the process after reporting a stuck GC?
+    abort: bool,
+    /// The number of work packets that had been finished when we last saw progress.
+    last_finished_packets: usize,
+    /// When we last saw progress.
+    last_progress: Instant,
+}
+
+impl Watchdog {
+    /// Create a watchdog with the timeout in seconds. A timeout of 0 disables the watchdog.
+    pub fn new(timeout_secs: usize, abort: bool) -> Self {
+        Self {
+            timeout: if timeout_secs == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(timeout_secs as u64))
+            },
+            abort,
+            last_finished_packets: 0,
+            last_progress: Instant::now(),
+        }
+    }
+
+    /// How long the controller should wait for a message before it checks the GC again, or `None`
+    /// if the watchdog is disabled.
+    pub fn check_interval(&self) -> Option<Duration> {
+        self.timeout.map(|timeout| timeout / 4)
+    }
+
+    /// Start watching a new GC.
+    pub fn start<VM: VMBinding>(&mut self, scheduler: &GCWorkScheduler<VM>) {
+        self.last_finished_packets = scheduler.finished_packets();
+        self.last_progress = Instant::now();
+    }
+
+    /// Check if the GC has made progress. Report the GC if it has not made any progress within
+    /// the timeout, and abort if the watchdog is configured to do so.
+    pub fn check<VM: VMBinding>(&mut self, scheduler: &GCWorkScheduler<VM>) {
+        let now = Instant::now();
+        let stalled = match self.stalled_for(scheduler.finished_packets(), now) {
+            Some(stalled) => stalled,
+            None => return,
+        };
+        // The logger may be compiled out in release builds, so we print the report directly.
+        eprintln!("{}", report(scheduler, stalled, now));
+        for tls in scheduler.gc_threads() {
+            VM::VMCollection::print_gc_thread_backtrace(tls);
+        }
+        if self.abort {
+            eprintln!("[GC watchdog] Aborting the stuck GC (gc_watchdog_abort is set).");
+            std::process::abort();
+        }
+        // Report again if the GC is still stuck after another timeout.
+        self.last_progress = now;
+    }
+
+    /// Return how long the GC has made no progress if that exceeds the timeout, given the number
+    /// of work packets that have been finished so far.
+    fn stalled_for(&mut self, finished_packets: usize, now: Instant) -> Option<Duration> {
+        let timeout = self.timeout?;
+        if finished_packets != self.last_finished_packets {
+            self.last_finished_packets = finished_packets;
+            self.last_progress = now;
+            return None;
+        }
+        let stalled = now.saturating_duration_since(self.last_progress);
+        if stalled >= timeout {
+            Some(stalled)
+        } else {
+            None
+        }
+    }
+}
+
+/// Describe the state of a stuck GC: the work buckets with pending packets, and what each GC thread
+/// is doing.
+fn report<VM: VMBinding>(
+    scheduler: &GCWorkScheduler<VM>,
+    stalled: Duration,
+    now: Instant,
+) -> String {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "[GC watchdog] No work packet has finished for {:.1}s. The GC may be stuck.",
+        stalled.as_secs_f64()
+    )
+    .unwrap();
+    for (stage, bucket) in scheduler.work_buckets.iter() {
+        let pending = bucket.pending_packets();
+        if bucket.is_activated() || pending != 0 {
+            writeln!(
+                report,
+                "  bucket {:?}: {}, {} pending packets",
+                stage,
+                if bucket.is_activated() {
+                    "open"
+                } else {
+                    "closed"
+                },
+                pending
+            )
+            .unwrap();
+            for (packet_type, count) in bucket.pending_packet_types() {
+                writeln!(report, "    {} x {}", packet_type, count).unwrap();
+            }
+        }
+    }
+    writeln!(
+        report,
+        "  {} of {} workers parked, {} pending coordinator packets",
+        scheduler.worker_group.parked_workers(),
+        scheduler.worker_group.worker_count(),
+        scheduler.pending_coordinator_packets.load(Ordering::SeqCst)
+    )
+    .unwrap();
+    let threads = std::iter::once(("coordinator".to_string(), scheduler.coordinator_activity()))
+        .chain(
+            scheduler
+                .worker_group
+                .workers_shared
+                .iter()
+                .enumerate()
+                .map(|(ordinal, shared)| (format!("worker {}", ordinal), &shared.activity)),
+        );
+    for (name, activity) in threads {
+        let state = match activity.current.lock().packet {
+            Some((packet_type, start)) => format!(
+                "executing {} for {:.1}s",
+                packet_type,
+                now.saturating_duration_since(start).as_secs_f64()
+            ),
+            None => "idle".to_string(),
+        };
+        writeln!(report, "  {}: {}", name, state).unwrap();
+    }
+    report
+}
+
+impl<VM: VMBinding> GCWorkScheduler<VM> {
+    /// The number of work packets all the GC threads have finished. This is only counted if the
+    /// watchdog is enabled.
+    fn finished_packets(&self) -> usize {
+        self.coordinator_activity().finished_packets()
+            + self
+                .worker_group
+                .workers_shared
+                .iter()
+                .map(|shared| shared.activity.finished_packets())
+                .sum::<usize>()
+    }
+
+    /// The GC threads that have executed work packets.
+    fn gc_threads(&self) -> Vec<VMWorkerThread> {
+        std::iter::once(self.coordinator_activity())
+            .chain(self.worker_group.workers_shared.iter().map(|s| &s.activity))
+            .filter_map(|activity| activity.current.lock().tls)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_stall_detection() {
+        let mut watchdog = Watchdog::new(10, false);
+        let start = watchdog.last_progress;
+        let secs = |s: u64| start + Duration::from_secs(s);
+        // No progress, but within the timeout.
+        assert_eq!(watchdog.stalled_for(0, secs(5)), None);
+        // Progress resets the timer.
+        assert_eq!(watchdog.stalled_for(3, secs(9)), None);
+        assert_eq!(watchdog.stalled_for(3, secs(18)), None);
+        // No progress for the timeout.
+        assert_eq!(
+            watchdog.stalled_for(3, secs(19)),
+            Some(Duration::from_secs(10))
+        );
+        // A disabled watchdog never reports.
+        let mut disabled = Watchdog::new(0, false);
+        assert_eq!(disabled.check_interval(), None);
+        assert_eq!(disabled.stalled_for(0, secs(1000)), None);
+    }
+}
@@ -16,6 +16,11 @@ pub trait GCWork<VM: VMBinding>: 'static + Send {
     /// Usually `do_work_with_stat()` should be used.
     fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>);
 
+    /// Get the type name of the work packet, e.g. to report a work packet that panicked.
+    fn get_type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
     /// Do work and collect statistics. This internally calls `do_work()`. In most cases,
     /// this should be called rather than `do_work()` so that MMTk can correctly collect
     /// statistics for the work packets.
@@ -33,9 +38,27 @@ pub trait GCWork<VM: VMBinding>: 'static + Send {
             worker_stat.measure_work(TypeId::of::<Self>(), type_name::<Self>(), mmtk)
         };
 
+        // Let the GC watchdog know what this thread is doing.
+        let watched = worker.scheduler().is_watchdog_enabled();
+        if watched {
+            let packet_type = std::any::type_name::<Self>();
+            worker.shared.activity.begin(worker.tls, packet_type);
+        }
+
         // Do the actual work
         self.do_work(worker, mmtk);
 
+        // Pass the objects moved by this packet to the graph exporter.
+        #[cfg(feature = "graph_export")]
+        mmtk.plan
+            .base()
+            .graph_exporter
+            .flush(&mut worker.get_copy_context_mut().graph_buffer);
+
+        if watched {
+            worker.shared.activity.end();
+        }
+
         #[cfg(feature = "work_packet_stats")]
         // Finish collecting statistics
         {
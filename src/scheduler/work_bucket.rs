@@ -3,17 +3,22 @@ use super::*;
 use crate::vm::VMBinding;
 use crossbeam::deque::{Injector, Steal, Worker};
 use enum_map::Enum;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 
 struct BucketQueue<VM: VMBinding> {
     queue: Injector<Box<dyn GCWork<VM>>>,
+    /// The number of packets of each type in the queue. This is only counted while the bucket is
+    /// watched (see `WorkBucket::set_watched`).
+    pending_types: spin::Mutex<HashMap<&'static str, usize>>,
 }
 
 impl<VM: VMBinding> BucketQueue<VM> {
     fn new() -> Self {
         Self {
             queue: Injector::new(),
+            pending_types: spin::Mutex::new(HashMap::new()),
         }
     }
 
@@ -22,25 +27,81 @@ impl<VM: VMBinding> BucketQueue<VM> {
         self.queue.is_empty()
     }
 
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+
     #[inline(always)]
     fn steal_batch_and_pop(
         &self,
         dest: &Worker<Box<dyn GCWork<VM>>>,
+        watched: bool,
     ) -> Steal<Box<dyn GCWork<VM>>> {
-        self.queue.steal_batch_and_pop(dest)
+        if !watched {
+            return self.queue.steal_batch_and_pop(dest);
+        }
+        // Take one packet at a time, so all the pending packets stay in the queue where they
+        // are counted.
+        let stolen = self.queue.steal();
+        if let Steal::Success(w) = &stolen {
+            let mut pending_types = self.pending_types.lock();
+            if let Some(count) = pending_types.get_mut(w.get_type_name()) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        stolen
     }
 
     #[inline(always)]
-    fn push(&self, w: Box<dyn GCWork<VM>>) {
+    fn push(&self, w: Box<dyn GCWork<VM>>, watched: bool) {
+        if watched {
+            *self
+                .pending_types
+                .lock()
+                .entry(w.get_type_name())
+                .or_insert(0) += 1;
+        }
         self.queue.push(w);
     }
 
     #[inline(always)]
-    fn push_all(&self, ws: Vec<Box<dyn GCWork<VM>>>) {
+    fn push_all(&self, ws: Vec<Box<dyn GCWork<VM>>>, watched: bool) {
         for w in ws {
-            self.queue.push(w);
+            self.push(w, watched);
         }
     }
+
+    /// The types of the packets in the queue and the number of packets of each type, if the
+    /// packets are counted.
+    fn pending_types(&self) -> Vec<(&'static str, usize)> {
+        self.pending_types
+            .lock()
+            .iter()
+            .filter(|(_, count)| **count != 0)
+            .map(|(name, count)| (*name, *count))
+            .collect()
+    }
+}
+
+/// A hook that is called when a work bucket opens (see `GCWorkScheduler::on_stage_open`). It
+/// returns the work packets to add to the bucket.
+pub type StageOpenHook<VM> = Box<dyn (Fn(&GCWorkScheduler<VM>) -> Vec<Box<dyn GCWork<VM>>>) + Send>;
+
+/// A phase that a plan inserts into each GC after a stage (see `GCWorkScheduler::add_phase`),
+/// e.g. to process the reference count decrements or to select the regions to evacuate. Unlike
+/// the packets of a `StageOpenHook`, which run alongside the other packets of their stage, the
+/// packets of a phase run after the stage is finished, and the next stage (or phase) waits until
+/// they are finished.
+pub trait GCPhase<VM: VMBinding>: 'static + Send {
+    /// The name of the phase, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Return the work packets of the phase. This is called once in each GC, once the stage that
+    /// the phase follows and the phases added before it are finished. The packets are executed in
+    /// the bucket of that stage, so the packets they create for the stage are part of the phase.
+    ///
+    /// Like a `StageOpenHook`, this is called by a GC worker while it holds the worker monitor.
+    fn schedule(&self, scheduler: &GCWorkScheduler<VM>) -> Vec<Box<dyn GCWork<VM>>>;
 }
 
 pub struct WorkBucket<VM: VMBinding> {
@@ -49,7 +110,15 @@ pub struct WorkBucket<VM: VMBinding> {
     prioritized_queue: Option<BucketQueue<VM>>,
     monitor: Arc<(Mutex<()>, Condvar)>,
     can_open: Option<Box<dyn (Fn(&GCWorkScheduler<VM>) -> bool) + Send>>,
+    /// The hooks that schedule work packets for this bucket each time it opens.
+    open_hooks: Mutex<Vec<StageOpenHook<VM>>>,
+    /// The phases that run in this bucket after the stage is finished, in order.
+    phases: Mutex<Vec<Box<dyn GCPhase<VM>>>>,
+    /// The number of the phases that have been scheduled in the current GC.
+    scheduled_phases: AtomicUsize,
     group: Arc<WorkerGroup<VM>>,
+    /// Do we count the pending packets of each type for the GC watchdog?
+    watched: AtomicBool,
 }
 
 impl<VM: VMBinding> WorkBucket<VM> {
@@ -64,7 +133,11 @@ impl<VM: VMBinding> WorkBucket<VM> {
             prioritized_queue: None,
             monitor,
             can_open: None,
+            open_hooks: Mutex::new(vec![]),
+            phases: Mutex::new(vec![]),
+            scheduled_phases: AtomicUsize::new(0),
             group,
+            watched: AtomicBool::new(false),
         }
     }
 
@@ -115,6 +188,41 @@ impl<VM: VMBinding> WorkBucket<VM> {
                 .unwrap_or(true)
     }
 
+    /// The number of packets in the bucket. This is only meant for diagnostics, as other
+    /// threads may add or take packets concurrently.
+    pub fn pending_packets(&self) -> usize {
+        self.queue.len() + self.prioritized_queue.as_ref().map_or(0, |q| q.len())
+    }
+
+    /// Count the pending packets of each type for the GC watchdog. The workers then take one
+    /// packet at a time from the bucket, so all the pending packets stay in the bucket (see
+    /// `pending_packet_types`, and `GCWorkScheduler::counts_pending_packets`).
+    pub(super) fn set_watched(&self, watched: bool) {
+        self.watched.store(watched, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn is_watched(&self) -> bool {
+        self.watched.load(Ordering::Relaxed)
+    }
+
+    /// The types of the packets in the bucket and the number of packets of each type, sorted by
+    /// the type names. This is only meant for diagnostics, as other threads may add or take
+    /// packets concurrently, and it is only counted if the bucket is watched (see `set_watched`).
+    pub(super) fn pending_packet_types(&self) -> Vec<(&'static str, usize)> {
+        let mut types = self.queue.pending_types();
+        if let Some(prioritized_queue) = self.prioritized_queue.as_ref() {
+            for (name, count) in prioritized_queue.pending_types() {
+                match types.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, c)) => *c += count,
+                    None => types.push((name, count)),
+                }
+            }
+        }
+        types.sort_unstable();
+        types
+    }
+
     #[inline(always)]
     pub fn is_drained(&self) -> bool {
         self.is_activated() && self.is_empty()
@@ -124,27 +232,31 @@ impl<VM: VMBinding> WorkBucket<VM> {
     pub fn deactivate(&self) {
         debug_assert!(self.queue.is_empty(), "Bucket not drained before close");
         self.active.store(false, Ordering::Relaxed);
+        self.scheduled_phases.store(0, Ordering::Relaxed);
     }
 
     /// Add a work packet to this bucket
     /// Panic if this bucket cannot receive prioritized packets.
     #[inline(always)]
     pub fn add_prioritized(&self, work: Box<dyn GCWork<VM>>) {
-        self.prioritized_queue.as_ref().unwrap().push(work);
+        self.prioritized_queue
+            .as_ref()
+            .unwrap()
+            .push(work, self.is_watched());
         self.notify_one_worker();
     }
 
     /// Add a work packet to this bucket
     #[inline(always)]
     pub fn add<W: GCWork<VM>>(&self, work: W) {
-        self.queue.push(Box::new(work));
+        self.queue.push(Box::new(work), self.is_watched());
         self.notify_one_worker();
     }
 
     /// Add a work packet to this bucket
     #[inline(always)]
     pub fn add_boxed(&self, work: Box<dyn GCWork<VM>>) {
-        self.queue.push(work);
+        self.queue.push(work, self.is_watched());
         self.notify_one_worker();
     }
 
@@ -152,7 +264,10 @@ impl<VM: VMBinding> WorkBucket<VM> {
     /// Panic if this bucket cannot receive prioritized packets.
     #[inline(always)]
     pub fn bulk_add_prioritized(&self, work_vec: Vec<Box<dyn GCWork<VM>>>) {
-        self.prioritized_queue.as_ref().unwrap().push_all(work_vec);
+        self.prioritized_queue
+            .as_ref()
+            .unwrap()
+            .push_all(work_vec, self.is_watched());
         if self.is_activated() {
             self.notify_all_workers();
         }
@@ -164,24 +279,31 @@ impl<VM: VMBinding> WorkBucket<VM> {
         if work_vec.is_empty() {
             return;
         }
-        self.queue.push_all(work_vec);
+        self.queue.push_all(work_vec, self.is_watched());
         if self.is_activated() {
             self.notify_all_workers();
         }
     }
 
+    /// Add multiple packets without notifying the workers. Like `open()`, this is for the scheduler
+    /// while it updates the buckets, when all the workers are parked.
+    pub(super) fn bulk_add_without_notify(&self, work_vec: Vec<Box<dyn GCWork<VM>>>) {
+        self.queue.push_all(work_vec, self.is_watched());
+    }
+
     /// Get a work packet from this bucket
     #[inline(always)]
     pub fn poll(&self, worker: &Worker<Box<dyn GCWork<VM>>>) -> Steal<Box<dyn GCWork<VM>>> {
         if !self.is_activated() || self.is_empty() {
             return Steal::Empty;
         }
+        let watched = self.is_watched();
         if let Some(prioritized_queue) = self.prioritized_queue.as_ref() {
             prioritized_queue
-                .steal_batch_and_pop(worker)
-                .or_else(|| self.queue.steal_batch_and_pop(worker))
+                .steal_batch_and_pop(worker, watched)
+                .or_else(|| self.queue.steal_batch_and_pop(worker, watched))
         } else {
-            self.queue.steal_batch_and_pop(worker)
+            self.queue.steal_batch_and_pop(worker, watched)
         }
     }
 
@@ -192,11 +314,47 @@ impl<VM: VMBinding> WorkBucket<VM> {
         self.can_open = Some(Box::new(pred));
     }
 
+    /// Add a hook that schedules work packets for this bucket each time it opens.
+    pub fn add_open_hook(&self, hook: StageOpenHook<VM>) {
+        self.open_hooks.lock().unwrap().push(hook);
+    }
+
+    /// Add a phase that runs in this bucket after the stage is finished.
+    pub fn add_phase(&self, phase: Box<dyn GCPhase<VM>>) {
+        self.phases.lock().unwrap().push(phase);
+    }
+
+    /// Schedule the packets of the next phase that has not run in the current GC, if there is
+    /// one with any packets. Return true if packets are added. Like `open()`, this does not
+    /// notify the workers, and it should only be called once the bucket is drained.
+    pub(super) fn schedule_next_phase(&self, scheduler: &GCWorkScheduler<VM>) -> bool {
+        let phases = self.phases.lock().unwrap();
+        while let Some(phase) = phases.get(self.scheduled_phases.load(Ordering::Relaxed)) {
+            self.scheduled_phases.fetch_add(1, Ordering::Relaxed);
+            let packets = phase.schedule(scheduler);
+            debug!("GC phase {}: {} packets", phase.name(), packets.len());
+            if !packets.is_empty() {
+                self.queue.push_all(packets, self.is_watched());
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Add the work packets from the open hooks, and activate the bucket. Like `update()`, this
+    /// does not notify the workers.
+    pub fn open(&self, scheduler: &GCWorkScheduler<VM>) {
+        for hook in self.open_hooks.lock().unwrap().iter() {
+            self.queue.push_all(hook(scheduler), self.is_watched());
+        }
+        self.activate();
+    }
+
     #[inline(always)]
     pub fn update(&self, scheduler: &GCWorkScheduler<VM>) -> bool {
         if let Some(can_open) = self.can_open.as_ref() {
             if !self.is_activated() && can_open(scheduler) {
-                self.activate();
+                self.open(scheduler);
                 return true;
             }
         }
@@ -204,21 +362,48 @@ impl<VM: VMBinding> WorkBucket<VM> {
     }
 }
 
+/// The stages of a GC. Each stage has a work bucket. Except `Unconstrained`, the buckets open in
+/// the order of the stages: a bucket opens after all the buckets of the previous stages have
+/// been drained. Plans schedule their work packets for a GC into these buckets (usually in
+/// `Plan::schedule_collection`), and can also add hooks that schedule packets when a stage opens
+/// (see `GCWorkScheduler::on_stage_open`), e.g. to act on the result of the previous stages, or
+/// insert their own phases between the stages (see `GCWorkScheduler::add_phase`).
 #[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
 pub enum WorkBucketStage {
+    /// Work that can run at any time, including when the mutators are running, e.g. stopping the
+    /// mutators and scanning their stacks.
     Unconstrained,
+    /// Prepare the plan, the spaces and the mutators for the GC. This opens once all the
+    /// mutators are stopped.
     Prepare,
+    /// Trace the pinned roots and the pinning regions, before the closure may move any object.
+    PinningRootsTrace,
+    /// Copy the groups of objects that the binding wants to be co-located, before the closure
+    /// copies them.
+    Colocation,
+    /// The transitive closure from the roots.
     Closure,
+    /// Process soft references, and the closure from the referents that are retained.
     SoftRefClosure,
+    /// Process weak references and VM-specific weak tables.
     WeakRefClosure,
+    /// Process finalizable objects, and the closure from the objects to be finalized.
     FinalRefClosure,
+    /// Process phantom references.
     PhantomRefClosure,
+    /// Compute the new locations of the live objects (MarkCompact).
     CalculateForwarding,
+    /// Trace the roots again to update the references to the moved objects (MarkCompact).
     SecondRoots,
+    /// Update the reference objects to the moved objects.
     RefForwarding,
+    /// Update the finalizable objects to the moved objects.
     FinalizableForwarding,
+    /// Move the objects to their new locations (MarkCompact).
     Compact,
+    /// Release the plan, the spaces and the mutators after the GC.
     Release,
+    /// The last stage of a GC, e.g. for the sanity GC.
     Final,
 }
 
@@ -1,8 +1,11 @@
 use super::stat::WorkerLocalStat;
+use super::watchdog::WorkerActivity;
 use super::work_bucket::*;
 use super::*;
 use crate::mmtk::MMTK;
+use crate::policy::mallocspace::metadata::ChunkMarkedObjects;
 use crate::util::copy::GCWorkerCopyContext;
+use crate::util::gc_stats::WorkerTracingStats;
 use crate::util::opaque_pointer::*;
 use crate::vm::{Collection, GCThreadContext, VMBinding};
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
@@ -25,6 +28,8 @@ pub struct GCWorkerShared<VM: VMBinding> {
     pub designated_work: ArrayQueue<Box<dyn GCWork<VM>>>,
     /// Handle for stealing packets from the current worker
     pub stealer: Option<Stealer<Box<dyn GCWork<VM>>>>,
+    /// The work packet the worker is executing. This is only updated if the GC watchdog is enabled.
+    pub(super) activity: WorkerActivity,
 }
 
 impl<VM: VMBinding> GCWorkerShared<VM> {
@@ -33,6 +38,7 @@ impl<VM: VMBinding> GCWorkerShared<VM> {
             stat: Default::default(),
             designated_work: ArrayQueue::new(16),
             stealer,
+            activity: WorkerActivity::default(),
         }
     }
 }
@@ -49,6 +55,8 @@ pub struct GCWorker<VM: VMBinding> {
     scheduler: Arc<GCWorkScheduler<VM>>,
     /// The copy context, used to implement copying GC.
     copy: GCWorkerCopyContext<VM>,
+    /// The objects traced by this worker since the last `take_tracing_stats()`.
+    tracing: WorkerTracingStats,
     /// The sending end of the channel to send message to the controller thread.
     pub sender: Sender<CoordinatorMessage<VM>>,
     /// The reference to the MMTk instance.
@@ -60,6 +68,8 @@ pub struct GCWorker<VM: VMBinding> {
     pub shared: Arc<GCWorkerShared<VM>>,
     /// Local work packet queue.
     pub local_work_buffer: deque::Worker<Box<dyn GCWork<VM>>>,
+    /// The objects marked by this worker in a chunk of MallocSpace in the current work packet.
+    pub(crate) malloc_marked_objects: ChunkMarkedObjects,
 }
 
 unsafe impl<VM: VMBinding> Sync for GCWorkerShared<VM> {}
@@ -94,12 +104,14 @@ impl<VM: VMBinding> GCWorker<VM> {
             ordinal,
             // We will set this later
             copy: GCWorkerCopyContext::new_non_copy(),
+            tracing: WorkerTracingStats::default(),
             sender,
             scheduler,
             mmtk,
             is_coordinator,
             shared,
             local_work_buffer,
+            malloc_marked_objects: ChunkMarkedObjects::new(),
         }
     }
 
@@ -108,10 +120,13 @@ impl<VM: VMBinding> GCWorker<VM> {
     /// Add a work packet to the work queue and mark it with a higher priority.
     /// If the bucket is activated, the packet will be pushed to the local queue, otherwise it will be
     /// pushed to the global bucket with a higher priority.
+    /// While the pending packets are counted (see `GCWorkScheduler::counts_pending_packets()`), the
+    /// packet always goes to the bucket, where it is counted.
     #[inline]
     pub fn add_work_prioritized(&mut self, bucket: WorkBucketStage, work: impl GCWork<VM>) {
         if !self.scheduler().work_buckets[bucket].is_activated()
             || self.local_work_buffer.len() >= Self::LOCALLY_CACHED_WORK_PACKETS
+            || self.scheduler().counts_pending_packets()
         {
             self.scheduler.work_buckets[bucket].add_prioritized(Box::new(work));
             return;
@@ -122,10 +137,13 @@ impl<VM: VMBinding> GCWorker<VM> {
     /// Add a work packet to the work queue.
     /// If the bucket is activated, the packet will be pushed to the local queue, otherwise it will be
     /// pushed to the global bucket.
+    /// While the pending packets are counted (see `GCWorkScheduler::counts_pending_packets()`), the
+    /// packet always goes to the bucket, where it is counted.
     #[inline]
     pub fn add_work(&mut self, bucket: WorkBucketStage, work: impl GCWork<VM>) {
         if !self.scheduler().work_buckets[bucket].is_activated()
             || self.local_work_buffer.len() >= Self::LOCALLY_CACHED_WORK_PACKETS
+            || self.scheduler().counts_pending_packets()
         {
             self.scheduler.work_buckets[bucket].add(work);
             return;
@@ -145,8 +163,27 @@ impl<VM: VMBinding> GCWorker<VM> {
         &mut self.copy
     }
 
+    /// Record objects traced (scanned) by this worker.
+    #[inline]
+    pub(crate) fn add_traced_objects(&mut self, objects: usize, bytes: usize) {
+        self.tracing.traced_objects += objects as u64;
+        self.tracing.traced_bytes += bytes as u64;
+    }
+
+    /// Flush the counts that this worker keeps for the current work packet.
+    #[inline]
+    pub(crate) fn flush_packet_counts(&mut self) {
+        self.malloc_marked_objects.flush();
+    }
+
+    /// Take the objects traced by this worker. The copied bytes are in the copy context.
+    pub(crate) fn take_tracing_stats(&mut self) -> WorkerTracingStats {
+        std::mem::take(&mut self.tracing)
+    }
+
     pub fn do_work(&'static mut self, mut work: impl GCWork<VM>) {
         work.do_work(self, self.mmtk);
+        self.flush_packet_counts();
     }
 
     /// Poll a ready-to-execute work packet in the following order:
@@ -155,27 +192,46 @@ impl<VM: VMBinding> GCWorker<VM> {
     /// 2. Poll from the local work queue.
     /// 3. Poll from activated global work-buckets
     /// 4. Steal from other workers
-    fn poll(&self) -> Box<dyn GCWork<VM>> {
+    /// Get a work packet to execute. Return `None` if the worker should exit, as the MMTk instance
+    /// shuts down.
+    fn poll(&self) -> Option<Box<dyn GCWork<VM>>> {
         self.shared
             .designated_work
             .pop()
             .or_else(|| self.local_work_buffer.pop())
-            .unwrap_or_else(|| self.scheduler().poll(self))
+            .or_else(|| self.scheduler().poll(self))
     }
 
     pub fn do_boxed_work(&'static mut self, mut work: Box<dyn GCWork<VM>>) {
         work.do_work(self, self.mmtk);
+        self.flush_packet_counts();
+    }
+
+    /// Get a work packet without waiting, for the worker that runs on the mutator thread if there
+    /// are no GC threads. Return `None` if there is no packet in the open buckets.
+    pub(super) fn poll_without_waiting(&self) -> Option<Box<dyn GCWork<VM>>> {
+        self.shared
+            .designated_work
+            .pop()
+            .or_else(|| self.local_work_buffer.pop())
+            .or_else(|| self.scheduler().poll_schedulable_work(self))
+    }
+
+    /// Initialize the worker for the thread that runs it.
+    pub(super) fn init(&mut self, tls: VMWorkerThread, mmtk: &'static MMTK<VM>) {
+        self.tls = tls;
+        self.copy = crate::plan::create_gc_worker_context(tls, mmtk);
     }
 
     /// Entry of the worker thread.
     /// Each worker will keep polling and executing work packets in a loop.
     pub fn run(&mut self, tls: VMWorkerThread, mmtk: &'static MMTK<VM>) {
-        self.tls = tls;
-        self.copy = crate::plan::create_gc_worker_context(tls, mmtk);
-        loop {
-            let mut work = self.poll();
-            work.do_work_with_stat(self, mmtk);
+        self.init(tls, mmtk);
+        self.scheduler().on_gc_thread_start(false);
+        while let Some(mut work) = self.poll() {
+            do_work_or_fail(work.as_mut(), self, mmtk);
         }
+        self.scheduler().on_gc_thread_exit(false);
     }
 }
 
@@ -209,6 +265,33 @@ impl<VM: VMBinding> WorkerGroup<VM> {
         })
     }
 
+    /// Create all the workers.
+    pub fn create_workers(
+        &self,
+        mmtk: &'static MMTK<VM>,
+        sender: Sender<CoordinatorMessage<VM>>,
+    ) -> Vec<Box<GCWorker<VM>>> {
+        let mut unspawned_local_work_queues = self.unspawned_local_work_queues.lock().unwrap();
+        let workers = self
+            .workers_shared
+            .iter()
+            .enumerate()
+            .map(|(ordinal, shared)| {
+                Box::new(GCWorker::new(
+                    mmtk,
+                    ordinal,
+                    mmtk.scheduler.clone(),
+                    false,
+                    sender.clone(),
+                    shared.clone(),
+                    unspawned_local_work_queues.pop().unwrap(),
+                ))
+            })
+            .collect();
+        debug_assert!(unspawned_local_work_queues.is_empty());
+        workers
+    }
+
     /// Spawn all the worker threads
     pub fn spawn(
         &self,
@@ -216,21 +299,10 @@ impl<VM: VMBinding> WorkerGroup<VM> {
         sender: Sender<CoordinatorMessage<VM>>,
         tls: VMThread,
     ) {
-        let mut unspawned_local_work_queues = self.unspawned_local_work_queues.lock().unwrap();
         // Spawn each worker thread.
-        for (ordinal, shared) in self.workers_shared.iter().enumerate() {
-            let worker = Box::new(GCWorker::new(
-                mmtk,
-                ordinal,
-                mmtk.scheduler.clone(),
-                false,
-                sender.clone(),
-                shared.clone(),
-                unspawned_local_work_queues.pop().unwrap(),
-            ));
+        for worker in self.create_workers(mmtk, sender) {
             VM::VMCollection::spawn_gc_thread(tls, GCThreadContext::<VM>::Worker(worker));
         }
-        debug_assert!(unspawned_local_work_queues.is_empty());
     }
 
     /// Get the number of workers in the group
@@ -327,6 +327,17 @@ impl Address {
         conversions::address_to_chunk_index(self)
     }
 
+    /// prefetches the cache line of the address for a read. This never faults, even if the address is not mapped,
+    /// and is a no-op on architectures where we do not support prefetching.
+    #[inline(always)]
+    pub fn prefetch(self) {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch::<_MM_HINT_T0>(self.to_ptr::<i8>());
+        }
+    }
+
     /// return true if the referenced memory is mapped
     pub fn is_mapped(self) -> bool {
         if self.0 == 0 {
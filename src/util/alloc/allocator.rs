@@ -4,7 +4,9 @@ use std::sync::atomic::Ordering;
 use crate::plan::Plan;
 use crate::policy::space::Space;
 use crate::util::constants::*;
+use crate::util::error::MMTKError;
 use crate::util::opaque_pointer::*;
+use crate::util::platform::with_thread_state;
 use crate::vm::VMBinding;
 use crate::vm::{ActivePlan, Collection};
 use downcast_rs::Downcast;
@@ -20,6 +22,20 @@ pub enum AllocationError {
     MmapOutOfMemory,
 }
 
+/// Run `f` with allocation on the current thread failing (returning a zero address) instead of
+/// triggering a GC when the heap is full. This is used by `memory_manager::try_alloc()`.
+pub(crate) fn with_no_gc_on_failure<T>(f: impl FnOnce() -> T) -> T {
+    let old = with_thread_state(|state| state.no_gc_on_failure.replace(true));
+    let result = f();
+    with_thread_state(|state| state.no_gc_on_failure.set(old));
+    result
+}
+
+/// Should allocation on the current thread fail instead of triggering a GC?
+pub(crate) fn is_no_gc_on_failure() -> bool {
+    with_thread_state(|state| state.no_gc_on_failure.get())
+}
+
 #[inline(always)]
 pub fn align_allocation_no_fill<VM: VMBinding>(
     region: Address,
@@ -138,6 +154,12 @@ pub trait Allocator<VM: VMBinding>: Downcast {
         unimplemented!()
     }
 
+    /// The mutator that owns this allocator is being destroyed, and will not allocate with it any
+    /// more. An allocator that keeps a thread local buffer should give the unused part of the
+    /// buffer back to its space here, if the space can reuse it before the next GC. MMTk calls this
+    /// while no GC is in progress.
+    fn on_mutator_destroy(&mut self) {}
+
     /// An allocation attempt. The implementation of this function depends on the allocator used.
     /// If an allocator supports thread local allocations, then the allocation will be serviced
     /// from its TLAB, otherwise it will default to using the slowpath, i.e. [`alloc_slow`](Allocator::alloc_slow).
@@ -244,16 +266,52 @@ pub trait Allocator<VM: VMBinding>: Downcast {
                     // This is the allocation hook for the analysis trait. If you want to call
                     // an analysis counter specific allocation hook, then here is the place to do so
                     #[cfg(feature = "analysis")]
-                    if _allocation_bytes > *plan.options.analysis_factor {
+                    if _allocation_bytes > plan.options.analysis_factor.get() {
                         trace!(
                             "Analysis: allocation_bytes = {} more than analysis_factor = {}",
                             _allocation_bytes,
-                            *plan.options.analysis_factor
+                            plan.options.analysis_factor.get()
                         );
                         plan.analysis_manager.alloc_hook(size, align, offset);
                     }
                 }
 
+                // Pay the allocation tax to the plan's concurrent phase, if there is one.
+                if is_mutator && plan.mutator_assist.is_active() {
+                    let allocated_size = if self.does_thread_local_allocation() {
+                        crate::util::conversions::raw_align_up(
+                            size,
+                            self.get_thread_local_buffer_granularity(),
+                        )
+                    } else {
+                        size
+                    };
+                    plan.mutator_assist.on_allocation(tls, allocated_size);
+                }
+
+                return result;
+            }
+
+            // The allocation failed without triggering a GC, as requested by the caller.
+            if is_no_gc_on_failure() {
+                return result;
+            }
+
+            // The allocation failed with an error that is returned to the caller.
+            if crate::util::error::has_allocation_error() {
+                return result;
+            }
+
+            // No GC is done once the VM is shutting down (see
+            // `memory_manager::prepare_for_shutdown`), so the allocation fails for good if it
+            // failed again after the first retry.
+            if previous_result_zero && plan.gc_requester.is_shutting_down() {
+                trace!("Throw HeapOutOfMemory while shutting down!");
+                if crate::util::error::is_returning_allocation_errors() {
+                    crate::util::error::set_allocation_error(MMTKError::HeapOutOfMemory);
+                } else {
+                    VM::VMCollection::out_of_memory(tls, AllocationError::HeapOutOfMemory);
+                }
                 return result;
             }
 
@@ -273,7 +331,11 @@ pub trait Allocator<VM: VMBinding>: Downcast {
                 if fail_with_oom {
                     // Note that we throw a `HeapOutOfMemory` error here and return a null ptr back to the VM
                     trace!("Throw HeapOutOfMemory!");
-                    VM::VMCollection::out_of_memory(tls, AllocationError::HeapOutOfMemory);
+                    if crate::util::error::is_returning_allocation_errors() {
+                        crate::util::error::set_allocation_error(MMTKError::HeapOutOfMemory);
+                    } else {
+                        VM::VMCollection::out_of_memory(tls, AllocationError::HeapOutOfMemory);
+                    }
                     plan.allocation_success.swap(false, Ordering::SeqCst);
                     return result;
                 }
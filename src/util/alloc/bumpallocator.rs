@@ -29,6 +29,9 @@ pub struct BumpAllocator<VM: VMBinding> {
 }
 
 impl<VM: VMBinding> BumpAllocator<VM> {
+    /// The pages that the allocator acquires at a time, unless an object needs more.
+    pub const BLOCK_PAGES: usize = BLOCK_SIZE / BYTES_IN_PAGE;
+
     pub fn set_limit(&mut self, cursor: Address, limit: Address) {
         self.cursor = cursor;
         self.limit = adjust_thread_local_buffer_limit::<VM>(limit);
@@ -1,11 +1,12 @@
 use super::allocator::{align_allocation_no_fill, fill_alignment_gap};
 use super::object_ref_guard::adjust_thread_local_buffer_limit;
 use crate::plan::Plan;
+use crate::policy::immix::block::Block;
 use crate::policy::immix::line::*;
 use crate::policy::immix::ImmixSpace;
 use crate::policy::space::Space;
 use crate::util::alloc::Allocator;
-use crate::util::linear_scan::Region;
+use crate::util::heap::regions::Region;
 use crate::util::opaque_pointer::VMThread;
 use crate::util::rust_util::unlikely;
 use crate::util::Address;
@@ -167,6 +168,30 @@ impl<VM: VMBinding> Allocator<VM> for ImmixAllocator<VM> {
     fn get_tls(&self) -> VMThread {
         self.tls
     }
+
+    /// Give the blocks that this allocator is allocating into back to the space, so other mutators
+    /// can allocate into their free lines before the next GC.
+    fn on_mutator_destroy(&mut self) {
+        // Turn the fake limits of the precise stress test back into addresses.
+        self.restore_limit_for_stress();
+        // The end of the memory allocated into in the current block. If the current hole is used
+        // up, the block still has free lines from the hole-searching cursor, if any.
+        let used_end = if self.cursor < self.limit {
+            Some(self.cursor)
+        } else {
+            self.line.map(|line| line.start())
+        };
+        let large_used_end = if self.large_cursor < self.large_limit {
+            Some(self.large_cursor)
+        } else {
+            None
+        };
+        for used_end in [used_end, large_used_end].iter().flatten() {
+            let block = Block::from(Block::align(*used_end));
+            self.immix_space().return_block(block, *used_end);
+        }
+        self.reset();
+    }
 }
 
 impl<VM: VMBinding> ImmixAllocator<VM> {
@@ -7,7 +7,7 @@ pub use allocator::AllocationError;
 pub use allocator::Allocator;
 
 /// Functions to ensure an object reference for an allocation has valid metadata.
-mod object_ref_guard;
+pub(crate) mod object_ref_guard;
 
 /// A list of all the allocators, embedded in Mutator
 pub(crate) mod allocators;
@@ -17,6 +17,7 @@
 
 use crate::util::heap::layout::vm_layout_constants::{BYTES_IN_CHUNK, CHUNK_MASK};
 use crate::util::Address;
+use crate::util::ObjectReference;
 use crate::vm::ObjectModel;
 use crate::vm::VMBinding;
 
@@ -63,3 +64,42 @@ pub fn object_ref_may_cross_chunk<VM: VMBinding>(addr: Address) -> bool {
     (addr & CHUNK_MASK) + VM::VMObjectModel::OBJECT_REF_OFFSET_BEYOND_CELL.unwrap()
         >= BYTES_IN_CHUNK
 }
+
+/// The number of bytes from the start of an allocation of `bytes` bytes in which its object
+/// reference may be, i.e. the object reference is in `[cell, cell + object_ref_extent(bytes))`. A
+/// policy that sets per-object metadata for an allocation without knowing where the object
+/// reference will be (e.g. the malloc space) should map the metadata for this range.
+pub fn object_ref_extent<VM: VMBinding>(bytes: usize) -> usize {
+    match VM::VMObjectModel::OBJECT_REF_OFFSET_BEYOND_CELL {
+        Some(offset) => bytes.max(offset + 1),
+        None => bytes,
+    }
+}
+
+/// Check that `start`, the value of `ObjectModel::object_start_ref` for `object`, is consistent
+/// with the object reference: the object may not start after its reference, and the reference
+/// may not be further from the start than the size of the object (`bytes`), or
+/// `OBJECT_REF_OFFSET_BEYOND_CELL` if that is larger. The linear scan and the sweeping rely on this
+/// to find the start of each object from the per-object metadata. This is a no-op in a release
+/// build.
+#[inline(always)]
+pub fn debug_check_object_start<VM: VMBinding>(
+    object: ObjectReference,
+    start: Address,
+    bytes: usize,
+) {
+    debug_assert!(
+        start <= object.to_address(),
+        "The object {} starts after its reference at {}",
+        object,
+        start
+    );
+    debug_assert!(
+        object.to_address() - start < object_ref_extent::<VM>(bytes),
+        "The object {} of {} bytes starting at {} is too far from its reference (OBJECT_REF_OFFSET_BEYOND_CELL = {:?})",
+        object,
+        bytes,
+        start,
+        VM::VMObjectModel::OBJECT_REF_OFFSET_BEYOND_CELL
+    );
+}
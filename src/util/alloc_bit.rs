@@ -30,6 +30,8 @@ pub fn set_alloc_bit(object: ObjectReference) {
         1,
         Ordering::SeqCst,
     );
+    #[cfg(feature = "object_start_map")]
+    crate::util::object_start_map::record_object_start(object.to_address());
 }
 
 pub fn unset_addr_alloc_bit(address: Address) {
@@ -78,4 +80,6 @@ pub unsafe fn is_alloced_object_unsafe(address: Address) -> bool {
 
 pub fn bzero_alloc_bit(start: Address, size: usize) {
     side_metadata::bzero_metadata(&ALLOC_SIDE_METADATA_SPEC, start, size);
+    #[cfg(feature = "object_start_map")]
+    crate::util::object_start_map::clear_range(start, size);
 }
@@ -0,0 +1,150 @@
+use crate::plan::BarrierOutcome;
+use crate::util::analysis::RtAnalysis;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/**
+ * This file implements an analysis routine that profiles the write barrier at each call site.
+ * The binding identifies a call site with an arbitrary number (e.g. the bytecode index of a
+ * field store), and calls `memory_manager::post_write_barrier_at_site()` instead of
+ * `memory_manager::post_write_barrier()`. For each site, we count how many times the barrier
+ * took its fast path, how many times it went to its slow path and logged the object, and how
+ * many times it went to its slow path but found the object already logged by another thread
+ * (a redundant log). Sites that rarely leave the fast path are candidates for barrier elision.
+ *
+ * Each mutator counts the outcomes locally, and adds the counts to the profile every few thousand
+ * barriers and whenever the mutator is flushed (e.g. in each GC). The profile at the end of the
+ * harness does not include the outcomes that are still buffered in mutators.
+ *
+ * The profile is written as CSV at the end of the harness, to the file specified by the option
+ * `barrier_profile_file`, or to stdout if the option is not set.
+ */
+#[derive(Default)]
+pub struct BarrierProfiler {
+    running: bool,
+    /// The counts of the fast paths, the slow paths and the redundant logs, keyed by the call site.
+    sites: BTreeMap<usize, [usize; 3]>,
+}
+
+impl BarrierProfiler {
+    pub fn new(running: bool) -> Self {
+        Self {
+            running,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    /// Add the counts recorded by a mutator to the profile, and clear them.
+    pub fn add(&mut self, counts: &mut BarrierSiteCounts) {
+        if self.running {
+            for (site, counts) in counts.sites.iter() {
+                let total = self.sites.entry(*site).or_insert([0; 3]);
+                for (total, count) in total.iter_mut().zip(counts.iter()) {
+                    *total += count;
+                }
+            }
+        }
+        counts.sites.clear();
+        counts.records = 0;
+    }
+
+    fn write_csv(&self, w: &mut impl Write) -> std::io::Result<()> {
+        writeln!(w, "site,fast_path,slow_path,redundant_log")?;
+        for (site, [fast, slow, redundant]) in self.sites.iter() {
+            writeln!(w, "{},{},{},{}", site, fast, slow, redundant)?;
+        }
+        Ok(())
+    }
+}
+
+/// The barrier outcomes recorded by one mutator. They are added to the [`BarrierProfiler`] in
+/// batches, when the mutator is flushed or has recorded enough outcomes, so the mutators do not
+/// contend on the profiler in their barriers.
+#[derive(Default)]
+pub struct BarrierSiteCounts {
+    sites: BTreeMap<usize, [usize; 3]>,
+    /// The number of outcomes recorded since the last flush.
+    records: usize,
+}
+
+impl BarrierSiteCounts {
+    /// Flush the counts to the profiler after this many records.
+    const FLUSH_THRESHOLD: usize = 4096;
+
+    /// Record an outcome of the barrier at the site. Return true if the counts should be flushed.
+    #[inline]
+    pub fn record(&mut self, site: usize, outcome: BarrierOutcome) -> bool {
+        let index = match outcome {
+            BarrierOutcome::FastPath => 0,
+            BarrierOutcome::SlowPath => 1,
+            BarrierOutcome::RedundantLog => 2,
+        };
+        self.sites.entry(site).or_insert([0; 3])[index] += 1;
+        self.records += 1;
+        self.records >= Self::FLUSH_THRESHOLD
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+}
+
+impl<VM: VMBinding> RtAnalysis<VM> for BarrierProfiler {
+    fn harness_end_hook(&mut self, mmtk: &'static MMTK<VM>) {
+        if self.sites.is_empty() {
+            return;
+        }
+        let file = &*mmtk.options.barrier_profile_file;
+        let result = if file.is_empty() {
+            self.write_csv(&mut std::io::stdout())
+        } else {
+            std::fs::File::create(file).and_then(|mut f| self.write_csv(&mut f))
+        };
+        if let Err(e) = result {
+            warn!("Failed to write the write barrier profile: {}", e);
+        }
+    }
+
+    fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let mut profiler = BarrierProfiler::new(true);
+        let mut counts = BarrierSiteCounts::default();
+        counts.record(7, BarrierOutcome::FastPath);
+        counts.record(7, BarrierOutcome::FastPath);
+        counts.record(3, BarrierOutcome::SlowPath);
+        profiler.add(&mut counts);
+        assert!(counts.is_empty());
+        counts.record(7, BarrierOutcome::RedundantLog);
+        profiler.add(&mut counts);
+        profiler.running = false;
+        counts.record(3, BarrierOutcome::SlowPath);
+        profiler.add(&mut counts);
+
+        let mut out = vec![];
+        profiler.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "site,fast_path,slow_path,redundant_log\n3,0,1,0\n7,2,0,1\n"
+        );
+    }
+
+    #[test]
+    fn test_flush_threshold() {
+        let mut counts = BarrierSiteCounts::default();
+        for _ in 0..BarrierSiteCounts::FLUSH_THRESHOLD - 1 {
+            assert!(!counts.record(1, BarrierOutcome::FastPath));
+        }
+        assert!(counts.record(1, BarrierOutcome::FastPath));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::util::opaque_pointer::VMWorkerThread;
+use crate::util::ObjectReference;
+use crate::vm::edge_shape::Edge;
+use crate::vm::{ObjectModel, Scanning, VMBinding};
+use std::collections::HashMap;
+use std::io::Write;
+
+/**
+ * This file implements an offline analysis that computes the dominator tree and the retained
+ * sizes of the objects reachable from a set of roots. An object A dominates an object B if every
+ * path from the roots to B goes through A. The retained size of A is the total size of the objects
+ * that A dominates (including A itself), i.e. the bytes that would be freed if A became unreachable.
+ *
+ * The analysis builds the object graph with `Scanning::scan_object`, so it must run at a safepoint
+ * (no mutator can change the object graph, and no GC can move the objects).
+ */
+
+/// The index of the virtual root node in the graph. The roots given by the binding are its successors.
+const ROOT: usize = 0;
+/// The immediate dominator of a node that is not reachable from the root.
+const UNDEFINED: usize = usize::MAX;
+
+/// The object graph. Node 0 is the virtual root, and node `i` (i > 0) is `objects[i - 1]`.
+struct HeapGraph {
+    objects: Vec<ObjectReference>,
+    sizes: Vec<usize>,
+    successors: Vec<Vec<usize>>,
+}
+
+impl HeapGraph {
+    fn build<VM: VMBinding>(tls: VMWorkerThread, roots: &[ObjectReference]) -> Self {
+        let mut graph = HeapGraph {
+            objects: vec![],
+            sizes: vec![0],
+            successors: vec![vec![]],
+        };
+        let mut index: HashMap<ObjectReference, usize> = HashMap::new();
+        let mut worklist: Vec<usize> = vec![];
+
+        let mut node_for = |graph: &mut HeapGraph, worklist: &mut Vec<usize>, object| {
+            *index.entry(object).or_insert_with(|| {
+                graph.objects.push(object);
+                graph
+                    .sizes
+                    .push(VM::VMObjectModel::get_current_size(object));
+                graph.successors.push(vec![]);
+                worklist.push(graph.objects.len());
+                graph.objects.len()
+            })
+        };
+
+        for root in roots.iter().filter(|o| !o.is_null() && o.is_in_any_space()) {
+            let node = node_for(&mut graph, &mut worklist, *root);
+            graph.successors[ROOT].push(node);
+        }
+        while let Some(node) = worklist.pop() {
+            let mut children = vec![];
+            VM::VMScanning::scan_object(tls, graph.objects[node - 1], &mut |edge: VM::VMEdge| {
+                let child = edge.load();
+                if !child.is_null() && child.is_in_any_space() {
+                    children.push(child);
+                }
+            });
+            for child in children {
+                let child = node_for(&mut graph, &mut worklist, child);
+                graph.successors[node].push(child);
+            }
+        }
+        graph
+    }
+}
+
+/// Return the nodes reachable from the root in reverse postorder.
+fn reverse_postorder(successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; successors.len()];
+    let mut postorder = Vec::with_capacity(successors.len());
+    // Each entry is a node and the index of the next successor to visit.
+    let mut stack = vec![(ROOT, 0)];
+    visited[ROOT] = true;
+    while let Some((node, next)) = stack.pop() {
+        if next < successors[node].len() {
+            stack.push((node, next + 1));
+            let succ = successors[node][next];
+            if !visited[succ] {
+                visited[succ] = true;
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(node);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// Compute the immediate dominator of each node, with the iterative algorithm from
+/// Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm".
+/// Return the immediate dominators and the reverse postorder of the reachable nodes.
+/// The immediate dominator of the root is the root itself.
+fn compute_dominators(successors: &[Vec<usize>]) -> (Vec<usize>, Vec<usize>) {
+    let order = reverse_postorder(successors);
+    let mut rpo_number = vec![UNDEFINED; successors.len()];
+    for (i, node) in order.iter().enumerate() {
+        rpo_number[*node] = i;
+    }
+    let mut predecessors = vec![vec![]; successors.len()];
+    for (node, succs) in successors.iter().enumerate() {
+        for succ in succs {
+            predecessors[*succ].push(node);
+        }
+    }
+
+    let mut idom = vec![UNDEFINED; successors.len()];
+    idom[ROOT] = ROOT;
+    let intersect = |idom: &[usize], mut a: usize, mut b: usize| {
+        while a != b {
+            while rpo_number[a] > rpo_number[b] {
+                a = idom[a];
+            }
+            while rpo_number[b] > rpo_number[a] {
+                b = idom[b];
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in order.iter().skip(1) {
+            let mut new_idom = UNDEFINED;
+            for pred in predecessors[*node].iter() {
+                if idom[*pred] == UNDEFINED {
+                    continue;
+                }
+                new_idom = if new_idom == UNDEFINED {
+                    *pred
+                } else {
+                    intersect(&idom, *pred, new_idom)
+                };
+            }
+            if idom[*node] != new_idom {
+                idom[*node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    (idom, order)
+}
+
+/// Compute the retained size of each node, i.e. the total size of the nodes in its dominator subtree.
+fn retained_sizes(idom: &[usize], order: &[usize], sizes: &[usize]) -> Vec<usize> {
+    let mut retained = sizes.to_vec();
+    // Visit the nodes in postorder, so a node is visited after all the nodes it dominates.
+    for node in order.iter().skip(1).rev() {
+        retained[idom[*node]] += retained[*node];
+    }
+    retained
+}
+
+/// Compute the dominator tree for the objects reachable from `roots`, and write a report with the
+/// `max_entries` objects that have the largest retained sizes.
+pub fn write_dominator_report<VM: VMBinding>(
+    tls: VMWorkerThread,
+    roots: &[ObjectReference],
+    max_entries: usize,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    let graph = HeapGraph::build::<VM>(tls, roots);
+    let (idom, order) = compute_dominators(&graph.successors);
+    let retained = retained_sizes(&idom, &order, &graph.sizes);
+
+    writeln!(
+        out,
+        "Reachable objects: {}, reachable bytes: {}",
+        graph.objects.len(),
+        retained[ROOT]
+    )?;
+    let mut nodes: Vec<usize> = order.iter().skip(1).copied().collect();
+    nodes.sort_by(|a, b| retained[*b].cmp(&retained[*a]));
+    writeln!(
+        out,
+        "object,shallow_bytes,retained_bytes,immediate_dominator"
+    )?;
+    for node in nodes.into_iter().take(max_entries) {
+        let dominator = if idom[node] == ROOT {
+            "root".to_string()
+        } else {
+            format!("{}", graph.objects[idom[node] - 1])
+        };
+        writeln!(
+            out,
+            "{},{},{},{}",
+            graph.objects[node - 1],
+            graph.sizes[node],
+            retained[node],
+            dominator
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dominators_diamond() {
+        // root -> 1 -> {2, 3} -> 4
+        let successors = vec![vec![1], vec![2, 3], vec![4], vec![4], vec![]];
+        let (idom, _) = compute_dominators(&successors);
+        assert_eq!(idom, vec![0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_dominators_multiple_roots() {
+        // root -> {1, 2}, 1 -> 3, 2 -> 3, 3 -> 4, and 5 is unreachable.
+        let successors = vec![vec![1, 2], vec![3], vec![3], vec![4], vec![], vec![4]];
+        let (idom, order) = compute_dominators(&successors);
+        assert_eq!(idom, vec![0, 0, 0, 0, 3, UNDEFINED]);
+        assert_eq!(order.len(), 5);
+    }
+
+    #[test]
+    fn test_retained_sizes() {
+        // root -> 1 -> {2, 3} -> 4, and 3 -> 1 (a cycle).
+        let successors = vec![vec![1], vec![2, 3], vec![4], vec![4, 1], vec![]];
+        let sizes = vec![0, 10, 20, 30, 40];
+        let (idom, order) = compute_dominators(&successors);
+        let retained = retained_sizes(&idom, &order, &sizes);
+        assert_eq!(retained, vec![100, 100, 20, 30, 40]);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::util::analysis::RtAnalysis;
+use crate::util::object_age;
+use crate::util::options::PlanSelector;
+use crate::vm::{ObjectModel, VMBinding};
+use crate::MMTK;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/**
+ * This file implements an analysis routine that builds a histogram of object lifetimes.
+ * For each object that is found dead in a GC, we record the epoch in which the object was
+ * allocated (its birth epoch, recorded by the `object_age` feature), and the epoch in which
+ * the object died (the current GC). An epoch is the number of GCs finished before it.
+ *
+ * Dead objects are found by iterating over the objects in each space (by alloc bits) in the
+ * release phase of each GC, before any space is released, and checking their liveness. For
+ * generational plans, a mature object is only found dead in the full heap GC that reclaims it.
+ * MarkCompact is not supported, as it clears the mark bits before the release phase.
+ *
+ * The histogram is written as CSV at the end of the harness, to the file specified by the
+ * option `lifetime_histogram_file`, or to stdout if the option is not set.
+ */
+#[derive(Default)]
+pub struct LifetimeHistogram {
+    running: bool,
+    /// The number of dead objects and their bytes, keyed by (birth epoch, death epoch).
+    histogram: BTreeMap<(u64, u64), (usize, usize)>,
+}
+
+impl LifetimeHistogram {
+    pub fn new(running: bool) -> Self {
+        Self {
+            running,
+            histogram: BTreeMap::new(),
+        }
+    }
+
+    fn record_death(&mut self, birth: u64, death: u64, bytes: usize) {
+        let entry = self.histogram.entry((birth, death)).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += bytes;
+    }
+
+    fn write_csv(&self, w: &mut impl Write) -> std::io::Result<()> {
+        writeln!(w, "birth_epoch,death_epoch,objects,bytes")?;
+        for ((birth, death), (objects, bytes)) in self.histogram.iter() {
+            writeln!(w, "{},{},{},{}", birth, death, objects, bytes)?;
+        }
+        Ok(())
+    }
+}
+
+impl<VM: VMBinding> RtAnalysis<VM> for LifetimeHistogram {
+    fn release_hook(&mut self, mmtk: &'static MMTK<VM>) {
+        if !self.running || matches!(*mmtk.options.plan, PlanSelector::MarkCompact) {
+            return;
+        }
+
+        // The current GC has not finished yet, so its epoch is the number of finished GCs.
+        let death = mmtk.plan.base().gc_stats.gc_count();
+        for space in mmtk.plan.get_spaces() {
+            // Safety: The release hook is called before any space is released, and no other work packet is running.
+            for object in unsafe { space.iterate_objects() } {
+                if !object.is_live() {
+                    // The birth epoch is only recorded modulo 256, so we get the full epoch from the age.
+                    let age = object_age::age(object_age::get_birth_epoch(object), death);
+                    let size = VM::VMObjectModel::get_current_size(object);
+                    self.record_death(death.saturating_sub(age as u64), death, size);
+                }
+            }
+        }
+    }
+
+    fn harness_end_hook(&mut self, mmtk: &'static MMTK<VM>) {
+        let file = &*mmtk.options.lifetime_histogram_file;
+        let result = if file.is_empty() {
+            self.write_csv(&mut std::io::stdout())
+        } else {
+            std::fs::File::create(file).and_then(|mut f| self.write_csv(&mut f))
+        };
+        if let Err(e) = result {
+            warn!("Failed to write the object lifetime histogram: {}", e);
+        }
+    }
+
+    fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let mut histogram = LifetimeHistogram::new(true);
+        histogram.record_death(0, 1, 16);
+        histogram.record_death(0, 1, 32);
+        histogram.record_death(1, 3, 24);
+
+        let mut out = vec![];
+        histogram.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "birth_epoch,death_epoch,objects,bytes\n0,1,2,48\n1,3,1,24\n"
+        );
+    }
+}
@@ -4,10 +4,15 @@ use crate::vm::VMBinding;
 use crate::MMTK;
 use std::sync::{Arc, Mutex};
 
+pub mod barrier_profile;
+pub mod dominators;
 pub mod gc_count;
+#[cfg(all(feature = "object_age", feature = "global_alloc_bit"))]
+pub mod lifetime;
 pub mod obj_num;
 pub mod obj_size;
 
+use self::barrier_profile::{BarrierProfiler, BarrierSiteCounts};
 use self::gc_count::GcCounter;
 use self::obj_num::ObjectCounter;
 use self::obj_size::PerSizeClassObjectCounter;
@@ -25,6 +30,10 @@ use self::obj_size::PerSizeClassObjectCounter;
 pub trait RtAnalysis<VM: VMBinding> {
     fn alloc_hook(&mut self, _size: usize, _align: usize, _offset: isize) {}
     fn gc_hook(&mut self, _mmtk: &'static MMTK<VM>) {}
+    /// Called in the release phase of each GC, before any space is released.
+    fn release_hook(&mut self, _mmtk: &'static MMTK<VM>) {}
+    /// Called at the end of the harness.
+    fn harness_end_hook(&mut self, _mmtk: &'static MMTK<VM>) {}
     fn set_running(&mut self, running: bool);
 }
 
@@ -43,12 +52,16 @@ impl<VM: VMBinding> GCWork<VM> for GcHookWork {
 #[derive(Default)]
 pub struct AnalysisManager<VM: VMBinding> {
     routines: Mutex<Vec<Arc<Mutex<dyn RtAnalysis<VM> + Send>>>>,
+    /// The barrier profiler is also in `routines`. We keep a reference to it for
+    /// `flush_barrier_counts()`.
+    barrier_profiler: Arc<Mutex<BarrierProfiler>>,
 }
 
 impl<VM: VMBinding> AnalysisManager<VM> {
     pub fn new(stats: &Stats) -> Self {
         let mut manager = AnalysisManager {
             routines: Mutex::new(vec![]),
+            barrier_profiler: Arc::new(Mutex::new(BarrierProfiler::new(true))),
         };
         manager.initialize_routines(stats);
         manager
@@ -65,6 +78,12 @@ impl<VM: VMBinding> AnalysisManager<VM> {
         self.add_analysis_routine(obj_num);
         self.add_analysis_routine(gc_count);
         self.add_analysis_routine(obj_size);
+        self.add_analysis_routine(self.barrier_profiler.clone());
+        #[cfg(all(feature = "object_age", feature = "global_alloc_bit"))]
+        {
+            let lifetime = Arc::new(Mutex::new(lifetime::LifetimeHistogram::new(true)));
+            self.add_analysis_routine(lifetime);
+        }
     }
 
     pub fn add_analysis_routine(&mut self, routine: Arc<Mutex<dyn RtAnalysis<VM> + Send>>) {
@@ -85,4 +104,25 @@ impl<VM: VMBinding> AnalysisManager<VM> {
             r.lock().unwrap().gc_hook(mmtk);
         }
     }
+
+    /// Add the barrier outcomes recorded by a mutator to the barrier profile.
+    pub fn flush_barrier_counts(&self, counts: &mut BarrierSiteCounts) {
+        if !counts.is_empty() {
+            self.barrier_profiler.lock().unwrap().add(counts);
+        }
+    }
+
+    pub fn release_hook(&self, mmtk: &'static MMTK<VM>) {
+        let routines = self.routines.lock().unwrap();
+        for r in &*routines {
+            r.lock().unwrap().release_hook(mmtk);
+        }
+    }
+
+    pub fn harness_end_hook(&self, mmtk: &'static MMTK<VM>) {
+        let routines = self.routines.lock().unwrap();
+        for r in &*routines {
+            r.lock().unwrap().harness_end_hook(mmtk);
+        }
+    }
 }
@@ -0,0 +1,244 @@
+//! Detect the memory limit imposed by cgroups (e.g. when running in a container), and
+//! listen to memory pressure notifications from the kernel (PSI).
+
+use crate::util::constants::BYTES_IN_PAGE;
+use crate::util::platform::PlatformThread;
+use crate::vm::VMBinding;
+use crate::MMTK;
+
+/// The default heap size if there is no memory limit for the process.
+pub const DEFAULT_HEAP_SIZE: usize = 512 << 20;
+
+/// The file that describes the cgroups of the current process.
+const PROC_SELF_CGROUP: &str = "/proc/self/cgroup";
+/// Where the cgroup file systems are usually mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// The system-wide memory pressure file. This is used if we cannot find one for the cgroup.
+const PROC_PRESSURE_MEMORY: &str = "/proc/pressure/memory";
+/// The PSI trigger we register: notify us if some tasks are stalled on memory
+/// for 150ms in total within a 1s window.
+const PSI_TRIGGER: &[u8] = b"some 150000 1000000\0";
+
+/// Return the default heap size. If the process is limited by a cgroup memory limit,
+/// the default heap size is half of the limit (but no more than `DEFAULT_HEAP_SIZE`),
+/// so the rest of the process still has some room. Otherwise, it is `DEFAULT_HEAP_SIZE`.
+pub fn default_heap_size() -> usize {
+    match memory_limit() {
+        Some(limit) => usize::max(usize::min(DEFAULT_HEAP_SIZE, limit / 2), BYTES_IN_PAGE),
+        None => DEFAULT_HEAP_SIZE,
+    }
+}
+
+/// Return the memory limit (in bytes) imposed by cgroup v2 or cgroup v1 on the current process.
+/// Return `None` if there is no limit, or we cannot find the limit.
+#[cfg(target_os = "linux")]
+pub fn memory_limit() -> Option<usize> {
+    let cgroups = std::fs::read_to_string(PROC_SELF_CGROUP).ok()?;
+    let limit = cgroup_v2_limit(&cgroups).or_else(|| cgroup_v1_limit(&cgroups))?;
+    // cgroup v1 reports a very large number if there is no limit. We treat any limit that is
+    // not smaller than the physical memory as no limit.
+    match physical_memory() {
+        Some(phys) if limit >= phys => None,
+        _ => Some(limit),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn memory_limit() -> Option<usize> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn physical_memory() -> Option<usize> {
+    let pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if pages <= 0 || page_size <= 0 {
+        None
+    } else {
+        Some(pages as usize * page_size as usize)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn cgroup_v2_limit(cgroups: &str) -> Option<usize> {
+    let path = cgroup_path(cgroups, None)?;
+    read_limit(&[
+        format!("{}{}/memory.max", CGROUP_ROOT, path),
+        // In a container, the cgroup of the process is usually mounted at the root.
+        format!("{}/memory.max", CGROUP_ROOT),
+    ])
+}
+
+#[cfg(target_os = "linux")]
+fn cgroup_v1_limit(cgroups: &str) -> Option<usize> {
+    let path = cgroup_path(cgroups, Some("memory"))?;
+    read_limit(&[
+        format!("{}/memory{}/memory.limit_in_bytes", CGROUP_ROOT, path),
+        format!("{}/memory/memory.limit_in_bytes", CGROUP_ROOT),
+    ])
+}
+
+/// Read the limit from the first file in `files` that we can read.
+#[cfg(target_os = "linux")]
+fn read_limit(files: &[String]) -> Option<usize> {
+    files
+        .iter()
+        .find_map(|f| std::fs::read_to_string(f).ok())
+        .and_then(|content| parse_limit(&content))
+}
+
+/// Find the cgroup path for the given controller from the content of `/proc/self/cgroup`.
+/// Each line is formatted as `hierarchy-ID:controller-list:cgroup-path`. For cgroup v2
+/// (`controller` is `None`), the hierarchy ID is 0 and the controller list is empty.
+fn cgroup_path<'a>(cgroups: &'a str, controller: Option<&str>) -> Option<&'a str> {
+    cgroups.lines().find_map(|line| {
+        let mut parts = line.splitn(3, ':');
+        let id = parts.next()?;
+        let controllers = parts.next()?;
+        let path = parts.next()?;
+        let matched = match controller {
+            None => id == "0" && controllers.is_empty(),
+            Some(c) => controllers.split(',').any(|x| x == c),
+        };
+        if matched {
+            Some(path)
+        } else {
+            None
+        }
+    })
+}
+
+/// Parse the content of a cgroup memory limit file. `max` means there is no limit.
+fn parse_limit(content: &str) -> Option<usize> {
+    let content = content.trim();
+    if content == "max" {
+        None
+    } else {
+        content.parse().ok()
+    }
+}
+
+/// Spawn a thread that listens to memory pressure notifications (PSI) from the kernel,
+/// and triggers a GC when the process is stalled on memory. We use the memory pressure
+/// file of the cgroup if there is one, otherwise we use the system-wide one.
+#[cfg(target_os = "linux")]
+pub fn spawn_memory_pressure_listener<VM: VMBinding>(
+    mmtk: &'static MMTK<VM>,
+) -> Option<Box<dyn PlatformThread>> {
+    use crate::util::platform::platform;
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    let cgroups = std::fs::read_to_string(PROC_SELF_CGROUP).unwrap_or_default();
+    let mut candidates = vec![];
+    if let Some(path) = cgroup_path(&cgroups, None) {
+        candidates.push(format!("{}{}/memory.pressure", CGROUP_ROOT, path));
+    }
+    candidates.push(PROC_PRESSURE_MEMORY.to_string());
+
+    let file = candidates.iter().find_map(|f| {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(f)
+            .ok()
+    });
+    let mut file = match file {
+        Some(file) => file,
+        None => {
+            warn!("Cannot open any memory pressure file. Memory pressure GC is disabled.");
+            return None;
+        }
+    };
+    if let Err(e) = file.write_all(PSI_TRIGGER) {
+        warn!(
+            "Failed to register the memory pressure trigger: {}. Memory pressure GC is disabled.",
+            e
+        );
+        return None;
+    }
+
+    let listen = move || {
+        let mut fds = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        loop {
+            if mmtk.plan.base().gc_requester.is_shutting_down() {
+                return;
+            }
+            let ret = unsafe { libc::poll(&mut fds, 1, SHUTDOWN_CHECK_INTERVAL_MS) };
+            if ret == 0 {
+                // Timed out. Check if the VM is shutting down.
+                continue;
+            }
+            if ret < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                warn!("Failed to poll the memory pressure file: {}", err);
+                return;
+            }
+            if fds.revents & libc::POLLERR != 0 {
+                warn!("The memory pressure file is no longer available.");
+                return;
+            }
+            if fds.revents & libc::POLLPRI != 0 {
+                let plan = mmtk.get_plan();
+                if plan.should_trigger_gc_when_heap_is_full() && !plan.base().gc_in_progress() {
+                    info!("Memory pressure detected, triggering collection");
+                    plan.base().trigger_internal_collection_request();
+                }
+            }
+        }
+    };
+    let thread = platform()
+        .spawn_thread("MMTk Memory Pressure Listener", Box::new(listen))
+        .expect("Failed to spawn the memory pressure listener thread");
+    Some(thread)
+}
+
+/// How often the memory pressure listener checks if the VM is shutting down, in milliseconds.
+#[cfg(target_os = "linux")]
+const SHUTDOWN_CHECK_INTERVAL_MS: i32 = 100;
+
+#[cfg(not(target_os = "linux"))]
+pub fn spawn_memory_pressure_listener<VM: VMBinding>(
+    _mmtk: &'static MMTK<VM>,
+) -> Option<Box<dyn PlatformThread>> {
+    warn!("Memory pressure GC is only supported on Linux.");
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_v2_path() {
+        let cgroups = "0::/user.slice/user-1000.slice\n";
+        assert_eq!(
+            cgroup_path(cgroups, None),
+            Some("/user.slice/user-1000.slice")
+        );
+        assert_eq!(cgroup_path(cgroups, Some("memory")), None);
+    }
+
+    #[test]
+    fn test_cgroup_v1_path() {
+        let cgroups =
+            "12:cpu,cpuacct:/docker/abc\n11:memory:/docker/abc\n1:name=systemd:/docker/abc\n";
+        assert_eq!(cgroup_path(cgroups, Some("memory")), Some("/docker/abc"));
+        assert_eq!(cgroup_path(cgroups, Some("cpu")), Some("/docker/abc"));
+        assert_eq!(cgroup_path(cgroups, None), None);
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("max\n"), None);
+        assert_eq!(parse_limit("268435456\n"), Some(268435456));
+        assert_eq!(parse_limit(""), None);
+    }
+}
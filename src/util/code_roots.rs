@@ -0,0 +1,183 @@
+//! Code roots are references embedded in compiled code, e.g. in the immediates of instructions.
+//! MMTk cannot load or store such a reference as an edge, as its encoding depends on the
+//! instruction. Instead, the binding registers each slot in the code with the object it refers
+//! to and an updater, and the updater re-encodes the reference into the slot once the object has
+//! moved.
+//!
+//! In each GC, the objects of the code roots are traced as strong roots in the closure (and again
+//! with the forwarding closure if the plan computes the new addresses later, as MarkCompact
+//! does). The updaters of the objects that have moved are called in the Release stage, once the
+//! new addresses of all the objects are known, and before the mutators are resumed.
+
+use crate::util::{Address, ObjectReference};
+use crossbeam::queue::SegQueue;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Re-encode the reference to `object` into the slot at `slot` in compiled code. MMTk calls this
+/// while the mutators are stopped.
+pub type CodeRootUpdater = fn(slot: Address, object: ObjectReference);
+
+struct CodeRoot {
+    /// The object that the slot refers to.
+    object: ObjectReference,
+    /// The address of the object after the current GC.
+    new_object: ObjectReference,
+    updater: CodeRootUpdater,
+}
+
+/// A registration or an unregistration that is not yet applied to the code roots.
+enum PendingChange {
+    Register(Address, CodeRoot),
+    Unregister(Address),
+}
+
+/// The code roots of an MMTk instance.
+pub(crate) struct CodeRoots {
+    /// The changes by the binding since the last GC. The binding may register and unregister code
+    /// roots often (e.g. whenever it compiles or frees code), so it only pushes to a lock-free
+    /// queue, and the GC applies the changes in order.
+    pending: SegQueue<PendingChange>,
+    /// The code roots, by their slots. This is only accessed by the GC.
+    roots: Mutex<HashMap<Address, CodeRoot>>,
+}
+
+impl CodeRoots {
+    pub fn new() -> Self {
+        Self {
+            pending: SegQueue::new(),
+            roots: Default::default(),
+        }
+    }
+
+    /// Register a slot that refers to `object`. This replaces the previous registration of the
+    /// slot, if any.
+    pub fn register(&self, slot: Address, object: ObjectReference, updater: CodeRootUpdater) {
+        debug_assert!(!object.is_null());
+        let root = CodeRoot {
+            object,
+            new_object: object,
+            updater,
+        };
+        self.pending.push(PendingChange::Register(slot, root));
+    }
+
+    /// Unregister a slot, e.g. when its code is freed. This does nothing if the slot is not
+    /// registered.
+    pub fn unregister(&self, slot: Address) {
+        self.pending.push(PendingChange::Unregister(slot));
+    }
+
+    /// Apply the pending changes to the code roots.
+    fn apply_pending(&self, roots: &mut HashMap<Address, CodeRoot>) {
+        while let Some(change) = self.pending.pop() {
+            match change {
+                PendingChange::Register(slot, root) => {
+                    roots.insert(slot, root);
+                }
+                PendingChange::Unregister(slot) => {
+                    roots.remove(&slot);
+                }
+            }
+        }
+    }
+
+    /// Trace the objects of the code roots with `trace`, and record the addresses it returns as
+    /// the addresses of the objects after the current GC.
+    pub fn trace(&self, mut trace: impl FnMut(ObjectReference) -> ObjectReference) {
+        let mut roots = self.roots.lock().unwrap();
+        self.apply_pending(&mut roots);
+        for root in roots.values_mut() {
+            root.new_object = trace(root.object);
+        }
+    }
+
+    /// Call the updaters of the slots whose objects have moved in the current GC. Return the
+    /// number of updated slots.
+    pub fn update(&self) -> usize {
+        let mut roots = self.roots.lock().unwrap();
+        // Do not update the slots that have been unregistered since the tracing.
+        self.apply_pending(&mut roots);
+        let mut updated = 0;
+        for (&slot, root) in roots.iter_mut() {
+            if root.new_object != root.object {
+                (root.updater)(slot, root.new_object);
+                root.object = root.new_object;
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// The number of code roots, including the pending changes.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        let mut roots = self.roots.lock().unwrap();
+        self.apply_pending(&mut roots);
+        roots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static UPDATED: AtomicUsize = AtomicUsize::new(0);
+
+    fn updater(slot: Address, object: ObjectReference) {
+        assert_eq!(slot.as_usize(), 0x100);
+        assert_eq!(object.to_address().as_usize(), 0x3000);
+        UPDATED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn object(address: usize) -> ObjectReference {
+        unsafe { Address::from_usize(address).to_object_reference() }
+    }
+
+    #[test]
+    fn test_update_moved() {
+        let roots = CodeRoots::new();
+        let slot = unsafe { Address::from_usize(0x100) };
+        let other = unsafe { Address::from_usize(0x200) };
+        roots.register(slot, object(0x1000), updater);
+        roots.register(other, object(0x2000), updater);
+        // Only the moved object is updated.
+        roots.trace(|o| {
+            if o == object(0x1000) {
+                object(0x3000)
+            } else {
+                o
+            }
+        });
+        assert_eq!(roots.update(), 1);
+        assert_eq!(UPDATED.load(Ordering::SeqCst), 1);
+        // The slot refers to the new address from now on.
+        let mut traced = vec![];
+        roots.trace(|o| {
+            traced.push(o);
+            o
+        });
+        assert!(traced.contains(&object(0x3000)));
+        assert_eq!(roots.update(), 0);
+
+        roots.unregister(other);
+        roots.unregister(other);
+        assert_eq!(roots.len(), 1);
+    }
+
+    #[test]
+    fn test_unregister_before_update() {
+        let roots = CodeRoots::new();
+        let slot = unsafe { Address::from_usize(0x300) };
+        roots.register(slot, object(0x1000), updater);
+        roots.trace(|_| object(0x4000));
+        // The slot is unregistered after the tracing, so its updater is not called.
+        roots.unregister(slot);
+        assert_eq!(roots.update(), 0);
+        // A slot registered again replaces the previous registration.
+        roots.register(slot, object(0x1000), updater);
+        roots.register(slot, object(0x2000), updater);
+        assert_eq!(roots.len(), 1);
+    }
+}
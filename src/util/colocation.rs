@@ -0,0 +1,159 @@
+//! Groups of objects that the binding wants to be placed together, e.g. the data that is
+//! accessed together by a hot call site, or by the threads of a NUMA node. The binding requests a
+//! group with [`memory_manager::request_colocation`](crate::memory_manager::request_colocation),
+//! and the next GC that copies objects as it traces them (a copying GC or a nursery GC, or a
+//! defrag GC of Immix) copies the objects of each group next to each other before the closure
+//! starts, so they usually end up in the same block.
+//!
+//! The requests only keep their objects alive in the GC that co-locates them, which traces them
+//! as roots. The other GCs hold the pending groups weakly: at the end of a full-heap GC, a group
+//! is dropped if one of its objects died, and the objects of the other groups are forwarded. The
+//! plans that do not copy objects as they trace them (e.g. MarkSweep and MarkCompact) drop the
+//! requests in their next GC.
+
+use crate::plan::CollectionScope;
+use crate::util::ObjectReference;
+use std::sync::Mutex;
+
+/// log2 of the bytes of a region. The objects of a group are co-located if they are in the same
+/// region. This is the size of an Immix block.
+pub const LOG_BYTES_IN_REGION: usize = 15;
+/// The bytes of a region.
+pub const BYTES_IN_REGION: usize = 1 << LOG_BYTES_IN_REGION;
+
+/// The statistics of the co-location requests.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColocationStats {
+    /// The groups requested by the binding.
+    pub requested_groups: usize,
+    /// The groups that a GC has tried to co-locate.
+    pub attempted_groups: usize,
+    /// The groups whose objects are all in the same region after the GC.
+    pub colocated_groups: usize,
+    /// The groups dropped by the plans that cannot co-locate objects.
+    pub dropped_groups: usize,
+    /// The groups dropped because one of their objects died before a GC could co-locate them.
+    pub dead_groups: usize,
+}
+
+/// The co-location requests of an MMTk instance.
+pub(crate) struct Colocation {
+    /// The groups that are not co-located yet.
+    groups: Mutex<Vec<Vec<ObjectReference>>>,
+    stats: Mutex<ColocationStats>,
+}
+
+impl Colocation {
+    pub fn new() -> Self {
+        Self {
+            groups: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Request a group of objects to be co-located.
+    pub fn request(&self, objects: Vec<ObjectReference>) {
+        debug_assert!(objects.iter().all(|object| !object.is_null()));
+        if objects.is_empty() {
+            return;
+        }
+        self.groups.lock().unwrap().push(objects);
+        self.stats.lock().unwrap().requested_groups += 1;
+    }
+
+    /// Take the pending groups for the current GC.
+    pub fn take_groups(&self) -> Vec<Vec<ObjectReference>> {
+        std::mem::take(&mut *self.groups.lock().unwrap())
+    }
+
+    /// Put back the groups that the current GC does not try to co-locate. They are held weakly
+    /// until the end of the GC (see [`Colocation::release_deferred`]).
+    pub fn defer(&self, groups: Vec<Vec<ObjectReference>>) {
+        self.groups.lock().unwrap().extend(groups);
+    }
+
+    /// Drop the deferred groups that have an object that died in the current GC, and forward the
+    /// objects of the other groups. This must be called after the closure of a GC that is not a
+    /// nursery GC, before the spaces are released. The objects outside `scope` are not collected
+    /// by the GC, so they are live.
+    pub fn release_deferred(&self, scope: Option<&CollectionScope>) {
+        let is_live = |object: &ObjectReference| {
+            object.is_reachable() || scope.map_or(false, |scope| !scope.includes(*object))
+        };
+        let mut groups = self.groups.lock().unwrap();
+        let before = groups.len();
+        *groups = std::mem::take(&mut *groups)
+            .into_iter()
+            .filter(|group| group.iter().all(is_live))
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|object| object.get_forwarded_object().unwrap_or(object))
+                    .collect()
+            })
+            .collect();
+        self.stats.lock().unwrap().dead_groups += before - groups.len();
+    }
+
+    /// Drop the groups, as the plan cannot co-locate them.
+    pub fn drop_groups(&self, groups: Vec<Vec<ObjectReference>>) {
+        self.stats.lock().unwrap().dropped_groups += groups.len();
+    }
+
+    /// Record the addresses of the objects of the groups that the current GC has tried to
+    /// co-locate.
+    pub fn record_attempts(&self, groups: &[Vec<ObjectReference>]) {
+        let colocated = groups.iter().filter(|group| is_colocated(group)).count();
+        let mut stats = self.stats.lock().unwrap();
+        stats.attempted_groups += groups.len();
+        stats.colocated_groups += colocated;
+    }
+
+    pub fn stats(&self) -> ColocationStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+/// Are the objects all in the same region?
+fn is_colocated(group: &[ObjectReference]) -> bool {
+    let region = |object: &ObjectReference| object.to_address().align_down(BYTES_IN_REGION);
+    let first = region(&group[0]);
+    group.iter().all(|object| region(object) == first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Address;
+
+    fn object(address: usize) -> ObjectReference {
+        unsafe { Address::from_usize(address).to_object_reference() }
+    }
+
+    #[test]
+    fn test_record_attempts() {
+        let colocation = Colocation::new();
+        colocation.request(vec![object(0x1000_0000), object(0x2000_0000)]);
+        colocation.request(vec![object(0x3000_0000)]);
+        colocation.request(vec![]);
+        let groups = colocation.take_groups();
+        assert_eq!(groups.len(), 2);
+        assert!(colocation.take_groups().is_empty());
+
+        // The first group is copied into one region, and the second into two.
+        let copied = vec![
+            vec![
+                object(0x4000_0000),
+                object(0x4000_0000 + BYTES_IN_REGION - 8),
+            ],
+            vec![object(0x4000_0000), object(0x4000_0000 + BYTES_IN_REGION)],
+        ];
+        colocation.record_attempts(&copied);
+        colocation.drop_groups(groups);
+        let stats = colocation.stats();
+        assert_eq!(stats.requested_groups, 2);
+        assert_eq!(stats.attempted_groups, 2);
+        assert_eq!(stats.colocated_groups, 1);
+        assert_eq!(stats.dropped_groups, 2);
+    }
+}
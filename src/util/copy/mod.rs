@@ -18,7 +18,7 @@ use std::sync::atomic::Ordering;
 use enum_map::Enum;
 use enum_map::EnumMap;
 
-const MAX_COPYSPACE_COPY_ALLOCATORS: usize = 1;
+const MAX_COPYSPACE_COPY_ALLOCATORS: usize = 3;
 const MAX_IMMIX_COPY_ALLOCATORS: usize = 2;
 
 type CopySpaceMapping<VM> = Vec<(CopySelector, &'static dyn Space<VM>)>;
@@ -29,13 +29,57 @@ type CopySpaceMapping<VM> = Vec<(CopySelector, &'static dyn Space<VM>)>;
 pub struct CopyConfig<VM: VMBinding> {
     /// Mapping CopySemantics to the actual copying allocators (CopySelector)
     pub copy_mapping: EnumMap<CopySemantics, CopySelector>,
-    /// Mapping copying allocators with space
+    /// Mapping copying allocators with space. Multiple copying allocators may copy to the same space.
+    /// If different copy semantics use different allocators, a worker keeps a copy buffer for each of
+    /// them, and copying with one semantics does not use up or break the buffer of the others (e.g.
+    /// promoting nursery objects and defragmenting mature objects in the same GC).
     pub space_mapping: CopySpaceMapping<VM>,
     /// A reference to the plan constraints.
     /// GCWorkerCopyContext may have plan-specific behaviors dependson the plan constraints.
     pub constraints: &'static PlanConstraints,
 }
 
+impl<VM: VMBinding> CopyConfig<VM> {
+    /// Check that the config is valid, and panic if it is not. Each copying allocator used by
+    /// a copy semantics needs a space, and each copying allocator in the space mapping needs to
+    /// be within the max number of allocators, and to match the policy of its space.
+    pub fn validate(&self) {
+        for (semantics, selector) in self.copy_mapping.iter() {
+            assert!(
+                matches!(selector, CopySelector::Unused)
+                    || self.space_mapping.iter().any(|(s, _)| s == selector),
+                "No space is mapped for the copying allocator {:?} of {:?}",
+                selector,
+                semantics
+            );
+        }
+        for (i, &(selector, space)) in self.space_mapping.iter().enumerate() {
+            assert!(
+                self.space_mapping[..i].iter().all(|(s, _)| *s != selector),
+                "The copying allocator {:?} is mapped to more than one space",
+                selector
+            );
+            let valid = match selector {
+                CopySelector::CopySpace(index) => {
+                    (index as usize) < MAX_COPYSPACE_COPY_ALLOCATORS
+                        && space.downcast_ref::<CopySpace<VM>>().is_some()
+                }
+                CopySelector::Immix(index) => {
+                    (index as usize) < MAX_IMMIX_COPY_ALLOCATORS
+                        && space.downcast_ref::<ImmixSpace<VM>>().is_some()
+                }
+                CopySelector::Unused => false,
+            };
+            assert!(
+                valid,
+                "The copying allocator {:?} cannot copy to the space {}",
+                selector,
+                space.get_name()
+            );
+        }
+    }
+}
+
 impl<VM: VMBinding> Default for CopyConfig<VM> {
     fn default() -> Self {
         CopyConfig {
@@ -55,6 +99,15 @@ pub struct GCWorkerCopyContext<VM: VMBinding> {
     pub immix: [MaybeUninit<ImmixCopyContext<VM>>; MAX_IMMIX_COPY_ALLOCATORS],
     /// The config for the plan
     config: CopyConfig<VM>,
+    /// The bytes copied by this worker since the last `take_copied_bytes()`.
+    copied_bytes: usize,
+    /// The bytes promoted to the mature space by this worker since the last `take_promoted_bytes()`.
+    promoted_bytes: usize,
+    /// The forwarding contention of this worker since the last `take_forwarding_contention()`.
+    pub(crate) forwarding_contention: object_forwarding::ForwardingContention,
+    /// The objects moved by this worker that have not been passed to the graph exporter.
+    #[cfg(feature = "graph_export")]
+    pub(crate) graph_buffer: crate::util::graph_export::GraphBuffer,
 }
 
 impl<VM: VMBinding> GCWorkerCopyContext<VM> {
@@ -82,6 +135,10 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
                 bytes, self.config.constraints.max_non_los_default_alloc_bytes
             );
         }
+        self.copied_bytes += bytes;
+        if matches!(semantics, CopySemantics::PromoteToMature) {
+            self.promoted_bytes += bytes;
+        }
         match self.config.copy_mapping[semantics] {
             CopySelector::CopySpace(index) => {
                 unsafe { self.copy[index as usize].assume_init_mut() }
@@ -103,9 +160,20 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
         // Clear forwarding bits.
         object_forwarding::clear_forwarding_bits::<VM>(object);
         // If we are copying objects in mature space, we would need to mark the object as mature.
-        if semantics.is_mature() && self.config.constraints.needs_log_bit {
-            // If the plan uses unlogged bit, we set the unlogged bit (the object is unlogged/mature)
-            VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC.mark_as_unlogged::<VM>(object, Ordering::SeqCst);
+        if self.config.constraints.needs_log_bit {
+            let unlogged_in_bulk = self.is_unlogged_in_bulk(semantics);
+            if semantics.is_mature() {
+                // If the plan uses unlogged bit, we set the unlogged bit (the object is
+                // unlogged/mature), unless the space has set the bits of its blocks.
+                if !unlogged_in_bulk {
+                    VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC
+                        .mark_as_unlogged::<VM>(object, Ordering::SeqCst);
+                }
+            } else if unlogged_in_bulk {
+                // The space has set the bits of its blocks, but the object is young.
+                VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC
+                    .mark_as_logged::<VM>(object, Ordering::SeqCst);
+            }
         }
         // Policy specific post copy.
         match self.config.copy_mapping[semantics] {
@@ -119,6 +187,18 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
         }
     }
 
+    /// Does the space that the copy semantics copy to set the log bits of its blocks in bulk (see
+    /// `ImmixSpace::set_unlog_blocks()`)?
+    #[inline(always)]
+    fn is_unlogged_in_bulk(&self, semantics: CopySemantics) -> bool {
+        match self.config.copy_mapping[semantics] {
+            CopySelector::Immix(index) => unsafe { self.immix[index as usize].assume_init_ref() }
+                .get_space()
+                .unlogs_blocks(),
+            _ => false,
+        }
+    }
+
     /// Prepare the copying allocators.
     pub fn prepare(&mut self) {
         // Delegate to prepare() for each policy copy context
@@ -151,6 +231,21 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
         }
     }
 
+    /// Rebind all the copying allocators for CopySpace that copy to either of the two semi-spaces
+    /// to `tospace`, which is the to-space of the two in this GC. The plans that flip their
+    /// semi-spaces call this in `prepare_worker`, so the copying allocators that the binding adds
+    /// (see `Collection::customize_copy_config`) follow the flip as well.
+    pub fn rebind_semispaces(&mut self, semispaces: [&CopySpace<VM>; 2], tospace: &CopySpace<VM>) {
+        for &(selector, _) in self.config.space_mapping.iter() {
+            if let CopySelector::CopySpace(index) = selector {
+                let copy = unsafe { self.copy[index as usize].assume_init_mut() };
+                if semispaces.iter().any(|space| copy.copies_to(space)) {
+                    copy.rebind(tospace);
+                }
+            }
+        }
+    }
+
     /// Create a GCWorkerCopyContext based on the configuration for a copying plan.
     ///
     /// Arguments:
@@ -162,10 +257,18 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
         plan: &'static dyn Plan<VM = VM>,
         config: CopyConfig<VM>,
     ) -> Self {
+        // The config may have been customized by the binding (see
+        // `Collection::customize_copy_config`).
+        config.validate();
         let mut ret = GCWorkerCopyContext {
             copy: unsafe { MaybeUninit::uninit().assume_init() },
             immix: unsafe { MaybeUninit::uninit().assume_init() },
             config,
+            copied_bytes: 0,
+            promoted_bytes: 0,
+            forwarding_contention: Default::default(),
+            #[cfg(feature = "graph_export")]
+            graph_buffer: Default::default(),
         };
 
         // Initiate the copy context for each policy based on the space mapping.
@@ -198,8 +301,28 @@ impl<VM: VMBinding> GCWorkerCopyContext<VM> {
             copy: unsafe { MaybeUninit::uninit().assume_init() },
             immix: unsafe { MaybeUninit::uninit().assume_init() },
             config: CopyConfig::default(),
+            copied_bytes: 0,
+            promoted_bytes: 0,
+            forwarding_contention: Default::default(),
+            #[cfg(feature = "graph_export")]
+            graph_buffer: Default::default(),
         }
     }
+
+    /// Return the bytes copied by this worker since the last call, and reset the count.
+    pub fn take_copied_bytes(&mut self) -> usize {
+        std::mem::replace(&mut self.copied_bytes, 0)
+    }
+
+    /// Return the bytes promoted to the mature space by this worker since the last call, and reset the count.
+    pub fn take_promoted_bytes(&mut self) -> usize {
+        std::mem::replace(&mut self.promoted_bytes, 0)
+    }
+
+    /// Return the forwarding contention of this worker since the last call, and reset the counts.
+    pub(crate) fn take_forwarding_contention(&mut self) -> object_forwarding::ForwardingContention {
+        std::mem::take(&mut self.forwarding_contention)
+    }
 }
 
 /// CopySemantics describes the copying operation. It depends on
@@ -226,7 +349,7 @@ impl CopySemantics {
 }
 
 #[repr(C, u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CopySelector {
     CopySpace(u8),
     Immix(u8),
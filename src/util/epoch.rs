@@ -0,0 +1,127 @@
+//! Epochs of the safepoints of the mutators, so a binding can free the nodes of its lock-free data
+//! structures without running another epoch-based reclamation scheme (e.g. crossbeam-epoch)
+//! alongside MMTk.
+//!
+//! The epoch advances each time a GC stops all the mutators. A mutator that unlinks a node from a
+//! lock-free structure calls
+//! [`memory_manager::defer_until_safepoint`](crate::memory_manager::defer_until_safepoint) with a
+//! callback that frees the node. As the mutators must not hold a pointer to a node across a
+//! safepoint, all the mutators that might have seen the node drop their pointers before the world
+//! is stopped the next time, so MMTk runs the callback after that. The callbacks are run by a
+//! background packet on a GC worker after the GC, while the mutators are running again. The stop
+//! of the world is a full fence for all the mutators, so the nodes need no other fences than
+//! the release store that unlinks them.
+//!
+//! The epoch only advances with GCs, so a callback may wait for a long time if GCs are rare, and
+//! the callbacks are never run with NoGC.
+
+use crate::mmtk::MMTK;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A callback that is run once all the mutators have passed a safepoint.
+pub type DeferredCallback = Box<dyn FnOnce() + Send>;
+
+pub(crate) struct Epochs {
+    /// The number of times the world is stopped.
+    current: AtomicUsize,
+    /// The callbacks, with the epoch in which each of them was deferred.
+    deferred: Mutex<Vec<(usize, DeferredCallback)>>,
+}
+
+impl Epochs {
+    pub fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            deferred: Mutex::new(vec![]),
+        }
+    }
+
+    /// The current epoch.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    /// All the mutators are stopped.
+    pub fn advance(&self) {
+        self.current.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Run `callback` once all the mutators have passed a safepoint.
+    pub fn defer(&self, callback: DeferredCallback) {
+        let mut deferred = self.deferred.lock().unwrap();
+        // Read the epoch with the lock, so a callback is never deferred in an older epoch than
+        // the callbacks before it.
+        deferred.push((self.current(), callback));
+    }
+
+    /// Are there callbacks that can be run?
+    pub fn has_ripe(&self) -> bool {
+        let current = self.current();
+        let deferred = self.deferred.lock().unwrap();
+        deferred
+            .first()
+            .map_or(false, |(epoch, _)| *epoch < current)
+    }
+
+    /// Take the callbacks that can be run, i.e. those that were deferred before the current epoch.
+    pub fn take_ripe(&self) -> Vec<DeferredCallback> {
+        let current = self.current();
+        let mut deferred = self.deferred.lock().unwrap();
+        // The callbacks are in the order of their epochs.
+        let ripe = deferred.partition_point(|(epoch, _)| *epoch < current);
+        deferred
+            .drain(..ripe)
+            .map(|(_, callback)| callback)
+            .collect()
+    }
+}
+
+/// A background packet that runs the deferred callbacks whose mutators have all passed a
+/// safepoint.
+pub struct RunDeferredCallbacks;
+
+impl<VM: VMBinding> GCWork<VM> for RunDeferredCallbacks {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        for callback in mmtk.epochs.take_ripe() {
+            callback();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_take_ripe() {
+        let epochs = Epochs::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let callback = || {
+            let count = count.clone();
+            Box::new(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }) as DeferredCallback
+        };
+        epochs.defer(callback());
+        assert!(!epochs.has_ripe());
+        assert!(epochs.take_ripe().is_empty());
+
+        epochs.advance();
+        epochs.defer(callback());
+        assert!(epochs.has_ripe());
+        // Only the callback before the stop of the world can run.
+        for callback in epochs.take_ripe() {
+            callback();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!epochs.has_ripe());
+
+        epochs.advance();
+        assert_eq!(epochs.take_ripe().len(), 1);
+        assert_eq!(epochs.current(), 2);
+    }
+}
@@ -0,0 +1,128 @@
+//! Errors returned by the public API. The fallible entry points (e.g. `MMTKBuilder::build`,
+//! `memory_manager::try_bind_mutator` and `memory_manager::alloc_checked`) return an [`MMTKError`]
+//! for the errors the binding can recover from or report to its users, such as an invalid option or
+//! an exhausted heap. MMTk still panics if its own invariants are violated, as it cannot continue.
+
+use crate::util::options::OptionError;
+use crate::util::platform::with_thread_state;
+use std::fmt;
+
+/// An error that the binding can handle, e.g. by reporting it to the user of the VM.
+#[derive(Debug)]
+pub enum MMTKError {
+    /// There is no option of the name.
+    UnknownOption(String),
+    /// The option cannot be set this way, e.g. it cannot be set from the command line.
+    OptionNotSettable(String),
+    /// The value cannot be parsed for the option, or is not valid for it.
+    InvalidOptionValue(OptionError),
+    /// The option string is not a white space separated list of `key=value` pairs.
+    MalformedOptions(String),
+    /// Some values from env vars or the command line could not be set (see `Options::diagnostics`).
+    InvalidOptionValues(Vec<OptionError>),
+    /// The options are not valid together (see `Options::validate`).
+    InvalidOptions(String),
+    /// The side metadata specs of the plan, the policies and the binding overlap, or do not fit
+    /// in the address range for side metadata. The message includes the layout of the specs.
+    SideMetadataConflict(String),
+    /// The constants of the binding (e.g. the metadata specs of its `ObjectModel`) cannot work
+    /// with MMTk, or with the selected plan.
+    IncompatibleBinding(String),
+    /// The heap is exhausted: an allocation failed even after an emergency GC.
+    HeapOutOfMemory,
+    /// The OS failed to map memory for the heap or its side metadata.
+    MmapFailed(std::io::Error),
+    /// The MMTk instance is shutting down, and does not accept new mutators.
+    ShuttingDown,
+}
+
+impl fmt::Display for MMTKError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MMTKError::UnknownOption(name) => write!(f, "Unknown MMTk option: {}", name),
+            MMTKError::OptionNotSettable(name) => {
+                write!(f, "The MMTk option {} cannot be set this way", name)
+            }
+            MMTKError::InvalidOptionValue(e) => write!(f, "Invalid MMTk option value: {}", e),
+            MMTKError::MalformedOptions(options) => {
+                write!(f, "Malformed MMTk options: {:?}", options)
+            }
+            MMTKError::InvalidOptionValues(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Invalid MMTk option values: {}", errors.join("; "))
+            }
+            MMTKError::InvalidOptions(e) => write!(f, "Invalid MMTk options: {}", e),
+            MMTKError::IncompatibleBinding(e) => write!(f, "{}", e),
+            MMTKError::SideMetadataConflict(e) => write!(f, "{}", e),
+            MMTKError::HeapOutOfMemory => write!(f, "The heap is out of memory"),
+            MMTKError::MmapFailed(e) => write!(f, "Failed to map memory: {}", e),
+            MMTKError::ShuttingDown => write!(f, "The MMTk instance is shutting down"),
+        }
+    }
+}
+
+impl std::error::Error for MMTKError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MMTKError::MmapFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// The error from the allocation on the current thread in `memory_manager::alloc_checked()` is kept
+// in `ThreadState::allocation_error`. The outer `Option` is `None` if the allocation errors on the
+// current thread are not returned to the caller, but reported to the binding with
+// `Collection::out_of_memory`.
+
+/// Run `f` with the allocation errors on the current thread returned instead of reported to the
+/// binding. Return the result of `f`, and the error, if any.
+pub(crate) fn with_allocation_errors_returned<T>(f: impl FnOnce() -> T) -> (T, Option<MMTKError>) {
+    let old = with_thread_state(|state| state.allocation_error.replace(Some(None)));
+    let result = f();
+    let error = with_thread_state(|state| state.allocation_error.replace(old)).flatten();
+    (result, error)
+}
+
+/// Are the allocation errors on the current thread returned to the caller?
+pub(crate) fn is_returning_allocation_errors() -> bool {
+    with_thread_state(|state| state.allocation_error.borrow().is_some())
+}
+
+/// Record an allocation error to return to the caller. This must only be called if
+/// `is_returning_allocation_errors()` is true.
+pub(crate) fn set_allocation_error(e: MMTKError) {
+    with_thread_state(|state| {
+        let mut error = state.allocation_error.borrow_mut();
+        debug_assert!(
+            error.is_some(),
+            "Allocation errors are not returned on this thread"
+        );
+        *error = Some(Some(e));
+    });
+}
+
+/// Has an allocation error been recorded for the current thread?
+pub(crate) fn has_allocation_error() -> bool {
+    with_thread_state(|state| matches!(*state.allocation_error.borrow(), Some(Some(_))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocation_errors_returned() {
+        assert!(!is_returning_allocation_errors());
+        let ((), error) = with_allocation_errors_returned(|| {
+            assert!(is_returning_allocation_errors());
+            assert!(!has_allocation_error());
+            set_allocation_error(MMTKError::HeapOutOfMemory);
+            assert!(has_allocation_error());
+        });
+        assert!(matches!(error, Some(MMTKError::HeapOutOfMemory)));
+        assert!(!is_returning_allocation_errors());
+        let ((), error) = with_allocation_errors_returned(|| {});
+        assert!(error.is_none());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::plan::ObjectGeneration;
 use crate::scheduler::gc_work::ProcessEdgesWork;
 use crate::scheduler::{GCWork, GCWorker};
 use crate::util::ObjectReference;
@@ -49,13 +50,21 @@ impl<F: Finalizable> FinalizableProcessor<F> {
         self.candidates.append(&mut self.ready_for_finalize);
         debug_assert!(self.ready_for_finalize.is_empty());
 
+        // The live candidates that are still young after a nursery GC (e.g. the objects that an
+        // incremental nursery GC does not collect) are checked again by the next nursery GC.
+        let mut young = vec![];
         for mut f in self.candidates.drain(start..).collect::<Vec<F>>() {
             let reff = f.get_reference();
             trace!("Pop {:?} for finalization", reff);
             if reff.is_live() {
                 FinalizableProcessor::<F>::forward_finalizable_reference(e, &mut f);
                 trace!("{:?} is live, push {:?} back to candidates", reff, f);
-                self.candidates.push(f);
+                let generation = e.plan().object_generation(f.get_reference());
+                if nursery && matches!(generation, Some(ObjectGeneration::Young { .. })) {
+                    young.push(f);
+                } else {
+                    self.candidates.push(f);
+                }
                 continue;
             }
 
@@ -72,6 +81,7 @@ impl<F: Finalizable> FinalizableProcessor<F> {
         self.forward_finalizable(e, nursery);
 
         self.nursery_index = self.candidates.len();
+        self.candidates.append(&mut young);
 
         <<E as ProcessEdgesWork>::VM as VMBinding>::VMCollection::schedule_finalization(tls);
     }
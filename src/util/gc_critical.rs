@@ -0,0 +1,170 @@
+//! GC-critical regions. A mutator may enter a critical region to keep the GC from stopping the
+//! world for a short while, e.g. while it holds a raw pointer into an object across a native
+//! operation. A GC that is triggered while some mutators are in critical regions waits for all
+//! the regions to exit before it stops the mutators. Meanwhile, a mutator that is not yet in a
+//! critical region cannot enter one until the GC is done, so the GC cannot be delayed forever by
+//! mutators entering new regions. A mutator that is already in a critical region can enter nested
+//! regions, as it would otherwise wait for itself.
+//!
+//! If the regions do not exit within the option `gc_critical_region_timeout` seconds, MMTk
+//! reports the mutators that are still in critical regions, and keeps waiting.
+
+use crate::util::VMMutatorThread;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct CriticalState {
+    /// The mutators in critical regions, with their nesting depths. There are usually only a few.
+    depths: Vec<(VMMutatorThread, usize)>,
+    /// Is a GC waiting for the critical regions to exit? Set until the GC is done.
+    gc_waiting: bool,
+}
+
+pub(crate) struct GCCriticalRegions {
+    state: Mutex<CriticalState>,
+    /// Notified when the last critical region exits.
+    all_exited: Condvar,
+    /// How long a GC waits for the critical regions before it reports them.
+    timeout: Option<Duration>,
+}
+
+impl GCCriticalRegions {
+    /// Create the critical regions with the timeout in seconds. A timeout of 0 disables the
+    /// reports.
+    pub fn new(timeout_secs: usize) -> Self {
+        Self {
+            state: Mutex::new(CriticalState::default()),
+            all_exited: Condvar::new(),
+            timeout: if timeout_secs == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(timeout_secs as u64))
+            },
+        }
+    }
+
+    /// Try to enter a critical region for the mutator `tls`. Return false if a GC is waiting or in
+    /// progress, and the mutator is not in a critical region yet. The mutator then needs to
+    /// block for the GC before it tries again.
+    pub fn try_enter(&self, tls: VMMutatorThread) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, depth)) = state.depths.iter_mut().find(|(t, _)| *t == tls) {
+            *depth += 1;
+            return true;
+        }
+        if state.gc_waiting {
+            return false;
+        }
+        state.depths.push((tls, 1));
+        true
+    }
+
+    /// Exit a critical region of the mutator `tls`.
+    pub fn exit(&self, tls: VMMutatorThread) {
+        let mut state = self.state.lock().unwrap();
+        let index = state
+            .depths
+            .iter()
+            .position(|(t, _)| *t == tls)
+            .unwrap_or_else(|| panic!("{:?} is not in a GC-critical region", tls));
+        state.depths[index].1 -= 1;
+        if state.depths[index].1 == 0 {
+            state.depths.swap_remove(index);
+            if state.depths.is_empty() {
+                self.all_exited.notify_all();
+            }
+        }
+    }
+
+    /// Is the mutator `tls` in a critical region?
+    pub fn is_in_critical_region(&self, tls: VMMutatorThread) -> bool {
+        let state = self.state.lock().unwrap();
+        state.depths.iter().any(|(t, _)| *t == tls)
+    }
+
+    /// Keep mutators from entering new critical regions, and wait for the current ones to exit.
+    /// This is called by a GC before it stops the mutators. Return how many times the timeout
+    /// passed before the regions exited.
+    pub fn block_and_wait(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.gc_waiting = true;
+        let mut reports = 0;
+        let mut deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        while !state.depths.is_empty() {
+            state = match deadline {
+                None => self.all_exited.wait(state).unwrap(),
+                Some(time) => {
+                    let now = Instant::now();
+                    if now >= time {
+                        // The logger may be compiled out in release builds, so we print the
+                        // report directly.
+                        eprintln!(
+                            "[MMTk] A GC has waited for {:?} for the GC-critical regions of {:?}",
+                            self.timeout.unwrap() * (reports + 1) as u32,
+                            state.depths.iter().map(|(t, _)| *t).collect::<Vec<_>>()
+                        );
+                        reports += 1;
+                        deadline = Some(now + self.timeout.unwrap());
+                        continue;
+                    }
+                    self.all_exited.wait_timeout(state, time - now).unwrap().0
+                }
+            };
+        }
+        reports
+    }
+
+    /// The GC is done. Mutators may enter critical regions again.
+    pub fn unblock(&self) {
+        self.state.lock().unwrap().gc_waiting = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::opaque_pointer::{OpaquePointer, VMThread};
+    use crate::util::Address;
+    use std::sync::Arc;
+
+    fn mutator(id: usize) -> VMMutatorThread {
+        let pointer = OpaquePointer::from_address(unsafe { Address::from_usize(id) });
+        VMMutatorThread(VMThread(pointer))
+    }
+
+    #[test]
+    fn test_nested_regions() {
+        let regions = GCCriticalRegions::new(0);
+        assert!(regions.try_enter(mutator(1)));
+        assert!(regions.try_enter(mutator(1)));
+        regions.exit(mutator(1));
+        assert!(regions.is_in_critical_region(mutator(1)));
+        regions.exit(mutator(1));
+        assert!(!regions.is_in_critical_region(mutator(1)));
+        // No region to wait for.
+        assert_eq!(regions.block_and_wait(), 0);
+        // Mutators cannot enter new regions until the GC is done.
+        assert!(!regions.try_enter(mutator(2)));
+        regions.unblock();
+        assert!(regions.try_enter(mutator(2)));
+    }
+
+    #[test]
+    fn test_wait_for_exit() {
+        let regions = Arc::new(GCCriticalRegions::new(1));
+        assert!(regions.try_enter(mutator(1)));
+        let gc = {
+            let regions = regions.clone();
+            std::thread::spawn(move || regions.block_and_wait())
+        };
+        // The mutator in the region can still enter nested regions while the GC waits.
+        while regions.try_enter(mutator(2)) {
+            regions.exit(mutator(2));
+        }
+        assert!(regions.try_enter(mutator(1)));
+        regions.exit(mutator(1));
+        regions.exit(mutator(1));
+        assert_eq!(gc.join().unwrap(), 0);
+    }
+}
@@ -0,0 +1,734 @@
+//! Cumulative GC statistics. Unlike the statistics that are gathered inside the harness,
+//! these counters are always on and cheap to maintain, so a runtime can use them to implement
+//! its language's standard GC stats APIs (such as `GC.stat`).
+//!
+//! The statistics can also be measured in named windows (see [`StatsWindows`]), so a harness can
+//! measure several phases of a run, such as warmup and steady state, in the same process.
+//! A monitoring agent can instead take a [`GCStatsEpoch`] and later ask for the statistics since
+//! then, to compute the GC metrics of each interval.
+//!
+//! What each GC worker traced and copied in the last GC is kept in [`GCTracingStats`], so the
+//! tracing bandwidth and the liveness of the heap are known, and not only its page counts.
+
+use std::collections::HashMap;
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::util::constants::LOG_BYTES_IN_PAGE;
+
+/// A snapshot of the cumulative GC statistics. It can be retrieved by
+/// [`memory_manager::gc_stats`](crate::memory_manager::gc_stats) at any time
+/// without stopping mutators.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GCStats {
+    /// The number of GCs.
+    pub gc_count: u64,
+    /// The number of nursery GCs. This is always 0 for non-generational plans.
+    pub nursery_gc_count: u64,
+    /// The number of full heap GCs.
+    pub full_heap_gc_count: u64,
+    /// The total time of GC pauses in nanoseconds.
+    pub total_pause_ns: u64,
+    /// The total bytes allocated by mutators. This is counted when spaces acquire pages for mutators,
+    /// so it is at the granularity of pages (or thread local buffers).
+    pub total_allocated_bytes: u64,
+    /// The total bytes of objects copied by GC.
+    pub total_copied_bytes: u64,
+    /// The total bytes of objects promoted from the nursery to the mature space by GC.
+    /// This is always 0 for non-generational plans.
+    pub total_promoted_bytes: u64,
+    /// The total bytes freed by GC.
+    pub total_freed_bytes: u64,
+    /// The current heap size in bytes.
+    pub heap_size_bytes: u64,
+    /// The bytes currently used by MMTk.
+    pub used_bytes: u64,
+    /// The total number of objects traced (scanned) by GC.
+    pub total_traced_objects: u64,
+    /// The total bytes of objects traced (scanned) by GC. This is 0 unless the option
+    /// `count_traced_bytes` is set.
+    pub total_traced_bytes: u64,
+}
+
+impl GCStats {
+    /// The statistics between an earlier snapshot and this one. The counters are the differences
+    /// between the two snapshots, and the heap size and usage are those of this snapshot.
+    pub fn since(&self, earlier: &GCStats) -> GCStats {
+        GCStats {
+            gc_count: self.gc_count - earlier.gc_count,
+            nursery_gc_count: self.nursery_gc_count - earlier.nursery_gc_count,
+            full_heap_gc_count: self.full_heap_gc_count - earlier.full_heap_gc_count,
+            total_pause_ns: self.total_pause_ns - earlier.total_pause_ns,
+            total_allocated_bytes: self.total_allocated_bytes - earlier.total_allocated_bytes,
+            total_copied_bytes: self.total_copied_bytes - earlier.total_copied_bytes,
+            total_promoted_bytes: self.total_promoted_bytes - earlier.total_promoted_bytes,
+            total_freed_bytes: self.total_freed_bytes - earlier.total_freed_bytes,
+            total_traced_objects: self.total_traced_objects - earlier.total_traced_objects,
+            total_traced_bytes: self.total_traced_bytes - earlier.total_traced_bytes,
+            heap_size_bytes: self.heap_size_bytes,
+            used_bytes: self.used_bytes,
+        }
+    }
+
+    /// Add the counters of a later period (see [`GCStats::since`]) to these, and take its heap
+    /// size and usage.
+    fn accumulate(&mut self, later: &GCStats) {
+        self.gc_count += later.gc_count;
+        self.nursery_gc_count += later.nursery_gc_count;
+        self.full_heap_gc_count += later.full_heap_gc_count;
+        self.total_pause_ns += later.total_pause_ns;
+        self.total_allocated_bytes += later.total_allocated_bytes;
+        self.total_copied_bytes += later.total_copied_bytes;
+        self.total_promoted_bytes += later.total_promoted_bytes;
+        self.total_freed_bytes += later.total_freed_bytes;
+        self.total_traced_objects += later.total_traced_objects;
+        self.total_traced_bytes += later.total_traced_bytes;
+        self.heap_size_bytes = later.heap_size_bytes;
+        self.used_bytes = later.used_bytes;
+    }
+}
+
+/// An opaque token for the GC statistics at some point in time. It can be retrieved by
+/// [`memory_manager::gc_stats_epoch`](crate::memory_manager::gc_stats_epoch), and passed to
+/// [`memory_manager::gc_stats_since`](crate::memory_manager::gc_stats_since) later to get the
+/// statistics of the interval in between.
+#[derive(Copy, Clone, Debug)]
+pub struct GCStatsEpoch {
+    time: Instant,
+    stats: GCStats,
+}
+
+impl GCStatsEpoch {
+    pub(crate) fn new(stats: GCStats) -> Self {
+        Self {
+            time: Instant::now(),
+            stats,
+        }
+    }
+
+    /// The statistics between this epoch and `now`.
+    pub(crate) fn delta(&self, now: &GCStats) -> GCStatsDelta {
+        GCStatsDelta {
+            elapsed_ns: self.time.elapsed().as_nanos() as u64,
+            stats: now.since(&self.stats),
+        }
+    }
+}
+
+/// The GC statistics of an interval since a [`GCStatsEpoch`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GCStatsDelta {
+    /// The wall-clock time of the interval in nanoseconds.
+    pub elapsed_ns: u64,
+    /// The counters are what happened in the interval, and the heap size and usage are the
+    /// current ones. See [`GCStats::since`].
+    pub stats: GCStats,
+}
+
+/// The name of the window measured between `memory_manager::harness_begin` and
+/// `memory_manager::harness_end`.
+pub const HARNESS_STATS_WINDOW: &str = "harness";
+
+/// The statistics of a measurement window. It can be retrieved by
+/// [`memory_manager::stats_window`](crate::memory_manager::stats_window).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WindowStats {
+    /// Whether the window is running.
+    pub running: bool,
+    /// The wall-clock time in nanoseconds that the window has been running for.
+    pub elapsed_ns: u64,
+    /// The GC statistics of the time the window has been running for. The counters only include
+    /// what happened while the window was running. The heap size and usage are the current ones
+    /// if the window is running, or the ones when it was last stopped.
+    pub stats: GCStats,
+}
+
+/// A measurement window. It can be started and stopped many times, and accumulates the
+/// statistics of all the periods in which it was running.
+#[derive(Default)]
+struct StatsWindow {
+    /// The statistics of the periods that have finished.
+    accumulated: GCStats,
+    elapsed: Duration,
+    /// When the current period started, and the statistics at that time.
+    started: Option<(Instant, GCStats)>,
+}
+
+impl StatsWindow {
+    fn get(&self, now: &GCStats) -> WindowStats {
+        let mut stats = self.accumulated;
+        let mut elapsed = self.elapsed;
+        if let Some((start_time, start_stats)) = &self.started {
+            stats.accumulate(&now.since(start_stats));
+            elapsed += start_time.elapsed();
+        }
+        WindowStats {
+            running: self.started.is_some(),
+            elapsed_ns: elapsed.as_nanos() as u64,
+            stats,
+        }
+    }
+}
+
+/// Named measurement windows. Each window measures the GC statistics between the times it is
+/// started and stopped. Windows are independent of each other, and can overlap.
+#[derive(Default)]
+pub struct StatsWindows {
+    windows: Mutex<HashMap<String, StatsWindow>>,
+}
+
+impl StatsWindows {
+    /// Start (or continue) measuring the window with the given label. The window is created if it
+    /// does not exist. Return false if the window is already running.
+    pub(crate) fn start(&self, label: &str, now: GCStats) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows.entry(label.to_string()).or_default();
+        if window.started.is_some() {
+            return false;
+        }
+        window.started = Some((Instant::now(), now));
+        true
+    }
+
+    /// Stop measuring the window with the given label, and return its statistics. Return `None` if
+    /// the window does not exist.
+    pub(crate) fn stop(&self, label: &str, now: GCStats) -> Option<WindowStats> {
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows.get_mut(label)?;
+        let stats = window.get(&now);
+        window.accumulated = stats.stats;
+        window.elapsed = Duration::from_nanos(stats.elapsed_ns);
+        window.started = None;
+        Some(WindowStats {
+            running: false,
+            ..stats
+        })
+    }
+
+    /// Clear the statistics of the window with the given label. A running window keeps running,
+    /// and measures from now on. Return false if the window does not exist.
+    pub(crate) fn reset(&self, label: &str, now: GCStats) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        match windows.get_mut(label) {
+            Some(window) => {
+                let running = window.started.is_some();
+                *window = StatsWindow::default();
+                if running {
+                    window.started = Some((Instant::now(), now));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The statistics of the window with the given label, or `None` if the window does not exist.
+    pub(crate) fn get(&self, label: &str, now: GCStats) -> Option<WindowStats> {
+        let windows = self.windows.lock().unwrap();
+        windows.get(label).map(|window| window.get(&now))
+    }
+}
+
+/// How much memory a GC used for copying, compared to what the plan reserved for copying.
+/// It can be retrieved by [`memory_manager::copy_reserve_usage`](crate::memory_manager::copy_reserve_usage).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopyReserveUsage {
+    /// The pages that the plan reserved for copying when the GC started.
+    pub reserved_pages: usize,
+    /// The pages that GC workers can acquire before the heap exceeds its size, i.e. the copy
+    /// reserve plus the available pages (that are not reserved for anything) when the GC started.
+    pub limit_pages: usize,
+    /// The pages that GC workers acquired from spaces in the GC.
+    pub used_pages: usize,
+    /// Whether the GC used up `limit_pages`. If so, the spaces that are able to keep objects in place stop
+    /// evacuating objects for the rest of the GC (e.g. defrag in Immix). Other spaces still have to copy
+    /// their live objects, so the heap may temporarily exceed its size.
+    pub exhausted: bool,
+}
+
+/// What a GC worker traced and copied in a GC.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkerTracingStats {
+    /// The number of objects that the worker traced (scanned).
+    pub traced_objects: u64,
+    /// The bytes of the objects that the worker traced. This is 0 unless the option
+    /// `count_traced_bytes` is set.
+    pub traced_bytes: u64,
+    /// The bytes of the objects that the worker copied.
+    pub copied_bytes: u64,
+}
+
+impl WorkerTracingStats {
+    fn add(&mut self, other: &WorkerTracingStats) {
+        self.traced_objects += other.traced_objects;
+        self.traced_bytes += other.traced_bytes;
+        self.copied_bytes += other.copied_bytes;
+    }
+}
+
+/// What the GC workers traced and copied in a GC. It can be retrieved by
+/// [`memory_manager::gc_tracing_stats`](crate::memory_manager::gc_tracing_stats).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GCTracingStats {
+    /// The number of the GC, i.e. `GCStats::gc_count` after the GC.
+    pub gc: u64,
+    /// Whether the GC was a full heap GC.
+    pub full_heap: bool,
+    /// The pause time of the GC in nanoseconds.
+    pub pause_ns: u64,
+    /// The pages used by MMTk after the GC.
+    pub used_pages_after_gc: usize,
+    /// Whether the bytes of the traced objects are counted (see the option `count_traced_bytes`).
+    pub traced_bytes_counted: bool,
+    /// The statistics of each GC worker, indexed by the ordinal of the worker.
+    pub workers: Vec<WorkerTracingStats>,
+}
+
+impl GCTracingStats {
+    /// The statistics of all the workers.
+    pub fn total(&self) -> WorkerTracingStats {
+        let mut total = WorkerTracingStats::default();
+        for worker in &self.workers {
+            total.add(worker);
+        }
+        total
+    }
+
+    /// The bytes traced per second of the pause, or `None` if the pause is unknown or the traced
+    /// bytes are not counted.
+    pub fn tracing_bandwidth(&self) -> Option<f64> {
+        if self.pause_ns == 0 || !self.traced_bytes_counted {
+            return None;
+        }
+        Some(self.total().traced_bytes as f64 * 1e9 / self.pause_ns as f64)
+    }
+
+    /// The fraction of the used memory after the GC that is not in live objects, i.e. the bytes
+    /// of the traced objects compared to the used pages. This is only meaningful for a full heap
+    /// GC, which traces all the live objects. Return `None` if no memory is used or the traced
+    /// bytes are not counted.
+    pub fn fragmentation(&self) -> Option<f64> {
+        let used_bytes = self.used_pages_after_gc << LOG_BYTES_IN_PAGE;
+        if used_bytes == 0 || !self.traced_bytes_counted {
+            return None;
+        }
+        let live = self.total().traced_bytes.min(used_bytes as u64);
+        Some(1.0 - live as f64 / used_bytes as f64)
+    }
+}
+
+/// The counters behind [`GCStats`].
+#[derive(Default)]
+pub struct CumulativeGCStats {
+    /// Incremented before and after a GC updates the counters at the end of the GC, so it is odd
+    /// while the counters are being updated. A snapshot is retried if the sequence changed while
+    /// it was taken, so it never sees the counters of a GC half updated.
+    sequence: AtomicUsize,
+    gc_count: AtomicU64,
+    nursery_gc_count: AtomicU64,
+    full_heap_gc_count: AtomicU64,
+    total_pause_ns: AtomicU64,
+    total_allocated_bytes: AtomicU64,
+    total_copied_bytes: AtomicU64,
+    total_promoted_bytes: AtomicU64,
+    total_freed_bytes: AtomicU64,
+    total_traced_objects: AtomicU64,
+    total_traced_bytes: AtomicU64,
+    /// Are the bytes of the traced objects counted? See the option `count_traced_bytes`.
+    count_traced_bytes: bool,
+    /// What the workers traced and copied in the current GC.
+    gc_tracing: Mutex<Vec<WorkerTracingStats>>,
+    /// What the workers traced and copied in the last GC, and in the last full heap GC.
+    last_gc_tracing: Mutex<GCTracingStats>,
+    last_full_heap_gc_tracing: Mutex<Option<GCTracingStats>>,
+    /// The bytes copied and promoted in the current (or the last) GC.
+    gc_copied_bytes: AtomicUsize,
+    gc_promoted_bytes: AtomicUsize,
+    /// The used pages when the current GC started.
+    used_pages_at_gc_start: AtomicUsize,
+    /// The copy reserve of the current (or the last) GC. See [`CopyReserveUsage`].
+    copy_reserved_pages: AtomicUsize,
+    copy_limit_pages: AtomicUsize,
+    copy_used_pages: AtomicUsize,
+    copy_reserve_exhausted: AtomicBool,
+    /// The total pause time of the full heap GCs, to estimate how long a full heap GC takes.
+    full_heap_pause_ns: AtomicU64,
+    /// `total_allocated_bytes` at the end of the last GC.
+    allocated_bytes_at_gc_end: AtomicU64,
+}
+
+impl CumulativeGCStats {
+    pub(crate) fn new(count_traced_bytes: bool) -> Self {
+        CumulativeGCStats {
+            count_traced_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// A GC has stopped all the mutators, and is about to start.
+    pub(crate) fn record_gc_start(&self, used_pages: usize) {
+        self.used_pages_at_gc_start
+            .store(used_pages, Ordering::Relaxed);
+        self.gc_copied_bytes.store(0, Ordering::Relaxed);
+        self.gc_promoted_bytes.store(0, Ordering::Relaxed);
+        self.gc_tracing.lock().unwrap().clear();
+    }
+
+    /// Reset the copy reserve accounting for a GC that is about to start. The GC can use its copy
+    /// reserve (`reserved_pages`) and the pages that are not reserved for anything
+    /// (`available_pages`, see `Plan::get_available_pages()`).
+    pub(crate) fn record_copy_reserve(&self, reserved_pages: usize, available_pages: usize) {
+        self.copy_reserved_pages
+            .store(reserved_pages, Ordering::Relaxed);
+        self.copy_limit_pages
+            .store(reserved_pages + available_pages, Ordering::Relaxed);
+        self.copy_used_pages.store(0, Ordering::Relaxed);
+        self.copy_reserve_exhausted.store(false, Ordering::Release);
+    }
+
+    /// GC workers acquired pages from a space.
+    pub(crate) fn add_copy_pages(&self, pages: usize) {
+        let used = self.copy_used_pages.fetch_add(pages, Ordering::Relaxed) + pages;
+        if used >= self.copy_limit_pages.load(Ordering::Relaxed) {
+            self.copy_reserve_exhausted.store(true, Ordering::Release);
+        }
+    }
+
+    /// Has the current GC used up the memory it can use for copying?
+    #[inline(always)]
+    pub(crate) fn is_copy_reserve_exhausted(&self) -> bool {
+        self.copy_reserve_exhausted.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn copy_reserve_usage(&self) -> CopyReserveUsage {
+        CopyReserveUsage {
+            reserved_pages: self.copy_reserved_pages.load(Ordering::Relaxed),
+            limit_pages: self.copy_limit_pages.load(Ordering::Relaxed),
+            used_pages: self.copy_used_pages.load(Ordering::Relaxed),
+            exhausted: self.is_copy_reserve_exhausted(),
+        }
+    }
+
+    /// A GC has finished, and mutators are about to be resumed.
+    pub(crate) fn record_gc_end(&self, used_pages: usize, pause: Duration, full_heap: bool) {
+        self.sequence.fetch_add(1, Ordering::SeqCst);
+        self.gc_count.fetch_add(1, Ordering::Relaxed);
+        if full_heap {
+            self.full_heap_gc_count.fetch_add(1, Ordering::Relaxed);
+            self.full_heap_pause_ns
+                .fetch_add(pause.as_nanos() as u64, Ordering::Relaxed);
+        } else {
+            self.nursery_gc_count.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_pause_ns
+            .fetch_add(pause.as_nanos() as u64, Ordering::Relaxed);
+        let freed_pages = self
+            .used_pages_at_gc_start
+            .load(Ordering::Relaxed)
+            .saturating_sub(used_pages);
+        self.total_freed_bytes
+            .fetch_add((freed_pages << LOG_BYTES_IN_PAGE) as u64, Ordering::Relaxed);
+        self.allocated_bytes_at_gc_end.store(
+            self.total_allocated_bytes.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        let tracing = GCTracingStats {
+            gc: self.gc_count.load(Ordering::Relaxed),
+            full_heap,
+            pause_ns: pause.as_nanos() as u64,
+            used_pages_after_gc: used_pages,
+            traced_bytes_counted: self.count_traced_bytes,
+            workers: std::mem::take(&mut *self.gc_tracing.lock().unwrap()),
+        };
+        if full_heap {
+            *self.last_full_heap_gc_tracing.lock().unwrap() = Some(tracing.clone());
+        }
+        *self.last_gc_tracing.lock().unwrap() = tracing;
+        self.sequence.fetch_add(1, Ordering::Release);
+    }
+
+    /// A GC worker has finished tracing and copying objects in the current GC. This is called
+    /// when the worker releases its copy context.
+    pub(crate) fn add_worker_tracing(&self, ordinal: usize, stats: WorkerTracingStats) {
+        self.total_traced_objects
+            .fetch_add(stats.traced_objects, Ordering::Relaxed);
+        self.total_traced_bytes
+            .fetch_add(stats.traced_bytes, Ordering::Relaxed);
+        let mut workers = self.gc_tracing.lock().unwrap();
+        if workers.len() <= ordinal {
+            workers.resize(ordinal + 1, WorkerTracingStats::default());
+        }
+        workers[ordinal].add(&stats);
+    }
+
+    /// What the workers traced and copied in the last GC.
+    pub(crate) fn last_gc_tracing(&self) -> GCTracingStats {
+        self.last_gc_tracing.lock().unwrap().clone()
+    }
+
+    /// What the workers traced and copied in the last full heap GC, if any.
+    pub(crate) fn last_full_heap_gc_tracing(&self) -> Option<GCTracingStats> {
+        self.last_full_heap_gc_tracing.lock().unwrap().clone()
+    }
+
+    /// The fragmentation of the heap after the last full heap GC (see
+    /// [`GCTracingStats::fragmentation`]), if any.
+    pub(crate) fn fragmentation_after_full_heap_gc(&self) -> Option<f64> {
+        self.last_full_heap_gc_tracing
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|tracing| tracing.fragmentation())
+    }
+
+    /// The bytes allocated by mutators since the end of the last GC.
+    pub(crate) fn allocated_bytes_since_gc(&self) -> u64 {
+        let total = self.total_allocated_bytes.load(Ordering::Relaxed);
+        total.saturating_sub(self.allocated_bytes_at_gc_end.load(Ordering::Relaxed))
+    }
+
+    /// The average pause time of all the GCs and of the full heap GCs, or `None` if there has
+    /// been no such GC.
+    pub(crate) fn average_pauses(&self) -> (Option<Duration>, Option<Duration>) {
+        let average = |pause_ns: &AtomicU64, count: &AtomicU64| {
+            let count = count.load(Ordering::Relaxed);
+            if count == 0 {
+                None
+            } else {
+                Some(Duration::from_nanos(
+                    pause_ns.load(Ordering::Relaxed) / count,
+                ))
+            }
+        };
+        (
+            average(&self.total_pause_ns, &self.gc_count),
+            average(&self.full_heap_pause_ns, &self.full_heap_gc_count),
+        )
+    }
+
+    /// The number of GCs that have finished.
+    pub(crate) fn gc_count(&self) -> u64 {
+        self.gc_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_allocated_bytes(&self, bytes: usize) {
+        self.total_allocated_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_copied_bytes(&self, bytes: usize) {
+        self.total_copied_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.gc_copied_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_promoted_bytes(&self, bytes: usize) {
+        self.total_promoted_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.gc_promoted_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// The bytes copied and the bytes promoted in the current GC. The numbers are complete once
+    /// all the GC workers have released their copy contexts, i.e. at the end of the GC.
+    pub(crate) fn gc_copied_and_promoted_bytes(&self) -> (usize, usize) {
+        (
+            self.gc_copied_bytes.load(Ordering::Relaxed),
+            self.gc_promoted_bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Take a snapshot of the counters, with the current heap size and usage.
+    pub(crate) fn snapshot(&self, total_pages: usize, used_pages: usize) -> GCStats {
+        loop {
+            let sequence = self.sequence.load(Ordering::Acquire);
+            if sequence % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let stats = self.read(total_pages, used_pages);
+            fence(Ordering::Acquire);
+            if self.sequence.load(Ordering::Relaxed) == sequence {
+                return stats;
+            }
+        }
+    }
+
+    fn read(&self, total_pages: usize, used_pages: usize) -> GCStats {
+        GCStats {
+            gc_count: self.gc_count.load(Ordering::Relaxed),
+            nursery_gc_count: self.nursery_gc_count.load(Ordering::Relaxed),
+            full_heap_gc_count: self.full_heap_gc_count.load(Ordering::Relaxed),
+            total_pause_ns: self.total_pause_ns.load(Ordering::Relaxed),
+            total_allocated_bytes: self.total_allocated_bytes.load(Ordering::Relaxed),
+            total_copied_bytes: self.total_copied_bytes.load(Ordering::Relaxed),
+            total_promoted_bytes: self.total_promoted_bytes.load(Ordering::Relaxed),
+            total_freed_bytes: self.total_freed_bytes.load(Ordering::Relaxed),
+            total_traced_objects: self.total_traced_objects.load(Ordering::Relaxed),
+            total_traced_bytes: self.total_traced_bytes.load(Ordering::Relaxed),
+            heap_size_bytes: (total_pages << LOG_BYTES_IN_PAGE) as u64,
+            used_bytes: (used_pages << LOG_BYTES_IN_PAGE) as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_reserve_exhausted() {
+        let stats = CumulativeGCStats::default();
+        // The GC can use the 4 reserved pages and the 4 available pages.
+        stats.record_copy_reserve(4, 4);
+        stats.add_copy_pages(4);
+        assert!(!stats.is_copy_reserve_exhausted());
+        stats.add_copy_pages(3);
+        assert!(!stats.is_copy_reserve_exhausted());
+        stats.add_copy_pages(1);
+        assert_eq!(
+            stats.copy_reserve_usage(),
+            CopyReserveUsage {
+                reserved_pages: 4,
+                limit_pages: 8,
+                used_pages: 8,
+                exhausted: true,
+            }
+        );
+        // The next GC starts with a fresh reserve.
+        stats.record_copy_reserve(4, 4);
+        assert!(!stats.is_copy_reserve_exhausted());
+    }
+
+    #[test]
+    fn test_per_gc_copied_bytes() {
+        let stats = CumulativeGCStats::default();
+        stats.record_gc_start(0);
+        stats.add_copied_bytes(100);
+        stats.add_promoted_bytes(40);
+        assert_eq!(stats.gc_copied_and_promoted_bytes(), (100, 40));
+        // The per-GC numbers are reset when the next GC starts, but the totals are not.
+        stats.record_gc_start(0);
+        stats.add_copied_bytes(8);
+        assert_eq!(stats.gc_copied_and_promoted_bytes(), (8, 0));
+        let snapshot = stats.snapshot(0, 0);
+        assert_eq!(snapshot.total_copied_bytes, 108);
+        assert_eq!(snapshot.total_promoted_bytes, 40);
+    }
+
+    #[test]
+    fn test_gc_tracing() {
+        let stats = CumulativeGCStats::new(true);
+        let traced = |objects, bytes, copied| WorkerTracingStats {
+            traced_objects: objects,
+            traced_bytes: bytes,
+            copied_bytes: copied,
+        };
+        stats.record_gc_start(8);
+        stats.add_worker_tracing(1, traced(2, 64, 32));
+        stats.add_worker_tracing(0, traced(1, 16, 0));
+        stats.add_worker_tracing(1, traced(1, 16, 16));
+        stats.record_gc_end(4, Duration::from_nanos(1000), true);
+        let tracing = stats.last_gc_tracing();
+        assert_eq!(tracing.gc, 1);
+        assert_eq!(tracing.workers, vec![traced(1, 16, 0), traced(3, 80, 48)]);
+        assert_eq!(tracing.total(), traced(4, 96, 48));
+        assert!((tracing.tracing_bandwidth().unwrap() - 96e6).abs() < f64::EPSILON);
+        let live = 96.0 / (4 << LOG_BYTES_IN_PAGE) as f64;
+        assert!((tracing.fragmentation().unwrap() - (1.0 - live)).abs() < f64::EPSILON);
+        assert_eq!(stats.last_full_heap_gc_tracing(), Some(tracing));
+
+        // A nursery GC does not replace the last full heap GC.
+        stats.record_gc_start(8);
+        stats.record_gc_end(8, Duration::from_nanos(10), false);
+        assert!(stats.last_gc_tracing().workers.is_empty());
+        assert_eq!(stats.last_full_heap_gc_tracing().unwrap().gc, 1);
+        let snapshot = stats.snapshot(0, 0);
+        assert_eq!(snapshot.total_traced_objects, 4);
+        assert_eq!(snapshot.total_traced_bytes, 96);
+    }
+
+    #[test]
+    fn test_gc_tracing_without_bytes() {
+        let stats = CumulativeGCStats::default();
+        stats.record_gc_start(8);
+        stats.add_worker_tracing(
+            0,
+            WorkerTracingStats {
+                traced_objects: 2,
+                ..Default::default()
+            },
+        );
+        stats.record_gc_end(4, Duration::from_nanos(1000), true);
+        let tracing = stats.last_gc_tracing();
+        assert!(!tracing.traced_bytes_counted);
+        assert_eq!(tracing.total().traced_objects, 2);
+        assert_eq!(tracing.tracing_bandwidth(), None);
+        assert_eq!(tracing.fragmentation(), None);
+        assert_eq!(stats.fragmentation_after_full_heap_gc(), None);
+    }
+
+    #[test]
+    fn test_epoch_delta() {
+        let stats = CumulativeGCStats::default();
+        stats.add_allocated_bytes(100);
+        let epoch = GCStatsEpoch::new(stats.snapshot(0, 0));
+        stats.add_allocated_bytes(10);
+        stats.record_gc_start(4);
+        stats.record_gc_end(1, Duration::from_nanos(5), true);
+        let delta = epoch.delta(&stats.snapshot(16, 1));
+        assert_eq!(delta.stats.gc_count, 1);
+        assert_eq!(delta.stats.full_heap_gc_count, 1);
+        assert_eq!(delta.stats.total_pause_ns, 5);
+        assert_eq!(delta.stats.total_allocated_bytes, 10);
+        assert_eq!(delta.stats.total_freed_bytes, 3 << LOG_BYTES_IN_PAGE);
+        assert_eq!(delta.stats.used_bytes, 1 << LOG_BYTES_IN_PAGE);
+        // The sequence is even once the GC has updated the counters.
+        assert_eq!(stats.sequence.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_stats_windows() {
+        let stats = CumulativeGCStats::default();
+        let windows = StatsWindows::default();
+        assert!(windows.start("a", stats.snapshot(0, 0)));
+        assert!(!windows.start("a", stats.snapshot(0, 0)));
+        stats.add_allocated_bytes(100);
+        assert!(windows.start("b", stats.snapshot(0, 0)));
+        stats.add_allocated_bytes(10);
+
+        let a = windows.stop("a", stats.snapshot(0, 0)).unwrap();
+        assert!(!a.running);
+        assert_eq!(a.stats.total_allocated_bytes, 110);
+        // A stopped window does not count what happens after it stopped.
+        stats.add_allocated_bytes(1000);
+        let b = windows.get("b", stats.snapshot(8, 4)).unwrap();
+        assert!(b.running);
+        assert_eq!(b.stats.total_allocated_bytes, 1010);
+        assert_eq!(b.stats.heap_size_bytes, 8 << LOG_BYTES_IN_PAGE);
+        let a = windows.get("a", stats.snapshot(0, 0)).unwrap();
+        assert_eq!(a.stats.total_allocated_bytes, 110);
+
+        // A restarted window accumulates.
+        windows.start("a", stats.snapshot(0, 0));
+        stats.add_allocated_bytes(1);
+        let a = windows.stop("a", stats.snapshot(0, 0)).unwrap();
+        assert_eq!(a.stats.total_allocated_bytes, 111);
+
+        // Resetting a running window measures from now on.
+        assert!(windows.reset("b", stats.snapshot(0, 0)));
+        stats.add_allocated_bytes(2);
+        let b = windows.get("b", stats.snapshot(0, 0)).unwrap();
+        assert!(b.running);
+        assert_eq!(b.stats.total_allocated_bytes, 2);
+
+        assert!(windows.get("c", stats.snapshot(0, 0)).is_none());
+        assert!(windows.stop("c", stats.snapshot(0, 0)).is_none());
+        assert!(!windows.reset("c", stats.snapshot(0, 0)));
+    }
+}
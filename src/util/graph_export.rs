@@ -0,0 +1,248 @@
+//! Export the object graph traced by a GC to a sink registered by the binding, in a streaming way.
+//! The edges are reported while the objects are scanned in the closure phase, so this does not
+//! need an extra trace of the heap.
+
+use crate::util::ObjectReference;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// A sink that receives the object graph during GCs. The methods are called by GC workers in parallel.
+///
+/// In a GC, the sink sees the roots (`root()`), the edges between objects (`edge()`), and the objects
+/// that are moved (`object_moved()`). The target of an edge is the object reference in the field before
+/// the GC updates the field. If the target is moved by the GC, `object_moved()` is called for it, and the
+/// sources of edges are always the objects at their new locations.
+///
+/// Each GC worker buffers the graph locally, and passes it to the sink in batches
+/// (`roots()`, `edges()` and `objects_moved()`). By default, the batch methods call the methods
+/// for each element.
+pub trait GraphSink: Send + Sync {
+    /// A GC is about to start tracing. Return true if the graph should be exported in this GC,
+    /// so a sink can sample GCs.
+    fn gc_start(&self, _gc_count: u64) -> bool {
+        true
+    }
+    /// The object is pointed to by a root.
+    fn root(&self, object: ObjectReference);
+    /// The object `source` has a field that points to `target`.
+    fn edge(&self, source: ObjectReference, target: ObjectReference);
+    /// The object is moved from `from` to `to`.
+    fn object_moved(&self, _from: ObjectReference, _to: ObjectReference) {}
+    /// A batch of roots.
+    fn roots(&self, objects: &[ObjectReference]) {
+        for object in objects.iter() {
+            self.root(*object);
+        }
+    }
+    /// A batch of edges, as `(source, target)`.
+    fn edges(&self, edges: &[(ObjectReference, ObjectReference)]) {
+        for (source, target) in edges.iter() {
+            self.edge(*source, *target);
+        }
+    }
+    /// A batch of moved objects, as `(from, to)`.
+    fn objects_moved(&self, moved: &[(ObjectReference, ObjectReference)]) {
+        for (from, to) in moved.iter() {
+            self.object_moved(*from, *to);
+        }
+    }
+    /// The GC that exports the graph has finished.
+    fn gc_end(&self) {}
+}
+
+/// The part of the graph recorded by one GC worker that has not been passed to the sink yet.
+#[derive(Default)]
+pub(crate) struct GraphBuffer {
+    roots: Vec<ObjectReference>,
+    edges: Vec<(ObjectReference, ObjectReference)>,
+    moved: Vec<(ObjectReference, ObjectReference)>,
+}
+
+impl GraphBuffer {
+    /// Flush a buffer when it has this many entries.
+    const CAPACITY: usize = 4096;
+
+    fn is_full(&self) -> bool {
+        self.roots.len() >= Self::CAPACITY
+            || self.edges.len() >= Self::CAPACITY
+            || self.moved.len() >= Self::CAPACITY
+    }
+
+    fn is_empty(&self) -> bool {
+        self.roots.is_empty() && self.edges.is_empty() && self.moved.is_empty()
+    }
+}
+
+/// Forwards the graph to the registered sink, if the sink wants the graph for the current GC.
+#[derive(Default)]
+pub struct GraphExporter {
+    sink: RwLock<Option<Box<dyn GraphSink>>>,
+    active: AtomicBool,
+}
+
+impl GraphExporter {
+    pub fn set_sink(&self, sink: Option<Box<dyn GraphSink>>) {
+        *self.sink.write().unwrap() = sink;
+    }
+
+    /// Is the graph being exported in the current GC?
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn gc_start(&self, gc_count: u64) {
+        let active = self
+            .sink
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |sink| sink.gc_start(gc_count));
+        self.active.store(active, Ordering::Relaxed);
+    }
+
+    pub(crate) fn gc_end(&self) {
+        if self.is_active() {
+            self.active.store(false, Ordering::Relaxed);
+            if let Some(sink) = self.sink.read().unwrap().as_ref() {
+                sink.gc_end();
+            }
+        }
+    }
+
+    /// Pass the buffered graph to the sink, and empty the buffer.
+    pub(crate) fn flush(&self, buffer: &mut GraphBuffer) {
+        if buffer.is_empty() {
+            return;
+        }
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            if !buffer.roots.is_empty() {
+                sink.roots(&buffer.roots);
+            }
+            if !buffer.edges.is_empty() {
+                sink.edges(&buffer.edges);
+            }
+            if !buffer.moved.is_empty() {
+                sink.objects_moved(&buffer.moved);
+            }
+        }
+        buffer.roots.clear();
+        buffer.edges.clear();
+        buffer.moved.clear();
+    }
+
+    #[inline]
+    fn flush_if_full(&self, buffer: &mut GraphBuffer) {
+        if buffer.is_full() {
+            self.flush(buffer);
+        }
+    }
+
+    pub(crate) fn root(&self, buffer: &mut GraphBuffer, object: ObjectReference) {
+        if self.is_active() && !object.is_null() {
+            buffer.roots.push(object);
+            self.flush_if_full(buffer);
+        }
+    }
+
+    pub(crate) fn edge(
+        &self,
+        buffer: &mut GraphBuffer,
+        source: ObjectReference,
+        target: ObjectReference,
+    ) {
+        if self.is_active() && !target.is_null() {
+            buffer.edges.push((source, target));
+            self.flush_if_full(buffer);
+        }
+    }
+
+    pub(crate) fn object_moved(
+        &self,
+        buffer: &mut GraphBuffer,
+        from: ObjectReference,
+        to: ObjectReference,
+    ) {
+        if self.is_active() {
+            buffer.moved.push((from, to));
+            self.flush_if_full(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Address;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingSink {
+        export_gc: u64,
+        edges: Arc<Mutex<Vec<(ObjectReference, ObjectReference)>>>,
+    }
+
+    impl GraphSink for RecordingSink {
+        fn gc_start(&self, gc_count: u64) -> bool {
+            gc_count == self.export_gc
+        }
+        fn root(&self, _object: ObjectReference) {}
+        fn edge(&self, source: ObjectReference, target: ObjectReference) {
+            self.edges.lock().unwrap().push((source, target));
+        }
+    }
+
+    #[test]
+    fn test_sample_gc() {
+        let a = unsafe { Address::from_usize(0x1000).to_object_reference() };
+        let b = unsafe { Address::from_usize(0x2000).to_object_reference() };
+        let edges = Arc::new(Mutex::new(vec![]));
+        let exporter = GraphExporter::default();
+        exporter.set_sink(Some(Box::new(RecordingSink {
+            export_gc: 1,
+            edges: edges.clone(),
+        })));
+
+        let mut buffer = GraphBuffer::default();
+
+        // Not exported in GC 0
+        exporter.gc_start(0);
+        exporter.edge(&mut buffer, a, b);
+        exporter.flush(&mut buffer);
+        exporter.gc_end();
+        assert!(edges.lock().unwrap().is_empty());
+
+        // Exported in GC 1. Null targets are skipped. The edges reach the sink when the buffer is
+        // flushed.
+        exporter.gc_start(1);
+        exporter.edge(&mut buffer, a, b);
+        exporter.edge(&mut buffer, a, ObjectReference::NULL);
+        assert!(edges.lock().unwrap().is_empty());
+        exporter.flush(&mut buffer);
+        exporter.gc_end();
+        assert_eq!(*edges.lock().unwrap(), vec![(a, b)]);
+        assert!(!exporter.is_active());
+    }
+
+    #[test]
+    fn test_flush_when_full() {
+        let a = unsafe { Address::from_usize(0x1000).to_object_reference() };
+        let b = unsafe { Address::from_usize(0x2000).to_object_reference() };
+        let edges = Arc::new(Mutex::new(vec![]));
+        let exporter = GraphExporter::default();
+        exporter.set_sink(Some(Box::new(RecordingSink {
+            export_gc: 0,
+            edges: edges.clone(),
+        })));
+        let mut buffer = GraphBuffer::default();
+
+        exporter.gc_start(0);
+        for _ in 0..GraphBuffer::CAPACITY - 1 {
+            exporter.edge(&mut buffer, a, b);
+        }
+        assert!(edges.lock().unwrap().is_empty());
+        exporter.edge(&mut buffer, a, b);
+        assert_eq!(edges.lock().unwrap().len(), GraphBuffer::CAPACITY);
+        assert!(buffer.is_empty());
+        exporter.gc_end();
+    }
+}
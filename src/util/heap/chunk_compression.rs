@@ -0,0 +1,662 @@
+//! Compression of cold chunks (experimental). If the option `chunk_compression_gcs` is set, MMTk
+//! records the last GC in which each chunk of the heap was touched, and after each GC, a
+//! background packet compresses the chunks that have not been touched for that many GCs into a
+//! side store, protects the chunks, and gives their pages back to the OS. When the program (or
+//! the GC) accesses a compressed chunk, the access faults, and the fault handler decompresses the
+//! chunk in place. This trades CPU time for memory in memory-constrained deployments, where a
+//! large part of the heap is long-lived, rarely accessed data.
+//!
+//! A chunk is marked as touched when a space acquires pages in it, when a barrier logs an object
+//! in it, when the GC scans an object in it, and when it is decompressed. The background packet
+//! turns the marks into the last GC in which each chunk was touched. Chunks that are not
+//! acquired by a space (e.g. the malloc space and the VM space) are never compressed.
+//!
+//! The kernel does not fault when a system call accesses a compressed chunk, but fails the call
+//! with `EFAULT`, so a binding must call `memory_manager::ensure_uncompressed()` before it passes
+//! heap memory to the kernel.
+//!
+//! The fault handler is async-signal-safe: it finds the chunk in a table that is mapped from the
+//! OS, takes no lock, and does not allocate or free memory. A fault that is not in a compressed
+//! chunk is forwarded to the handler that was installed before ours.
+//!
+//! This requires the feature `chunk_compression`, and is only supported on Linux, as it installs
+//! a SIGSEGV handler and maps memory with the OS directly. It cannot be used with the plans that
+//! protect memory themselves (PageProtect), and only one MMTk instance can compress chunks at a
+//! time.
+
+use crate::mmtk::MMTK;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::constants::BYTES_IN_WORD;
+use crate::util::conversions;
+use crate::util::heap::layout::vm_layout_constants::{
+    BYTES_IN_CHUNK, HEAP_END, HEAP_START, LOG_BYTES_IN_CHUNK,
+};
+use crate::util::Address;
+use crate::vm::VMBinding;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+
+/// The words in a chunk.
+const WORDS_IN_CHUNK: usize = BYTES_IN_CHUNK / BYTES_IN_WORD;
+/// A run of zeros shorter than this is stored as literals, as a run costs two words.
+const MIN_ZERO_RUN: usize = 4;
+/// A chunk is only compressed if it compresses to this fraction of its size or less.
+const MAX_COMPRESSED_FRACTION: usize = 2;
+/// The chunks in the heap range, each of which has an entry in the chunk table.
+const CHUNKS_IN_HEAP: usize = (HEAP_END.as_usize() - HEAP_START.as_usize()) >> LOG_BYTES_IN_CHUNK;
+
+/// Compress the words of a chunk. The compressed data is a sequence of runs, each of which is the
+/// number of zero words, the number of literal words, and the literal words. This is fast, and
+/// works well for the sparse data and the zeroed free memory that are common in cold chunks.
+pub fn compress(words: &[usize]) -> Vec<usize> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let zeros_start = i;
+        while i < words.len() && words[i] == 0 {
+            i += 1;
+        }
+        let literals_start = i;
+        while i < words.len() {
+            if words[i] != 0 {
+                i += 1;
+                continue;
+            }
+            // Keep a short run of zeros in the literals.
+            let mut j = i;
+            while j < words.len() && words[j] == 0 {
+                j += 1;
+            }
+            if j - i >= MIN_ZERO_RUN || j == words.len() {
+                break;
+            }
+            i = j;
+        }
+        out.push(literals_start - zeros_start);
+        out.push(i - literals_start);
+        out.extend_from_slice(&words[literals_start..i]);
+    }
+    out
+}
+
+/// Decompress the data from [`compress`] into `out`, which must be zeroed, and as long as the
+/// original words. This does not allocate, so it can be called from the fault handler.
+pub fn decompress(data: &[usize], out: &mut [usize]) {
+    let mut cursor = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = data[i];
+        let literals = data[i + 1];
+        cursor += zeros;
+        out[cursor..cursor + literals].copy_from_slice(&data[i + 2..i + 2 + literals]);
+        cursor += literals;
+        i += 2 + literals;
+    }
+    debug_assert!(cursor <= out.len());
+}
+
+/// The states of a chunk.
+mod state {
+    /// The chunk is not acquired by a space.
+    pub const UNUSED: u8 = 0;
+    /// The chunk is accessible.
+    pub const UNCOMPRESSED: u8 = 1;
+    /// The chunk is read-only while it is being compressed. A write to the chunk waits until the
+    /// chunk is compressed, and then decompresses it.
+    pub const COMPRESSING: u8 = 2;
+    /// The chunk is protected, and its data is in the side store.
+    pub const COMPRESSED: u8 = 3;
+    /// A thread is decompressing the chunk, or freeing its compressed data.
+    pub const RESTORING: u8 = 4;
+    /// The chunk is accessible again after it was decompressed. The fault handler cannot free the
+    /// compressed data, as it cannot call the allocator, so the data is freed after the next GC.
+    pub const RESTORED: u8 = 5;
+}
+
+/// The entry of a chunk in the chunk table. The table is zeroed memory from the OS, so an entry
+/// starts as an unused chunk.
+struct ChunkEntry {
+    state: AtomicU8,
+    /// Is the chunk touched since the background packet last looked at it?
+    touched: AtomicBool,
+    /// The last GC in which the chunk was touched.
+    last_touched: AtomicUsize,
+    /// The number of times the chunk was made accessible after it was protected. An access that
+    /// faults in a protected chunk may only be handled after another thread has made the chunk
+    /// accessible again, so the handler finds it uncompressed.
+    unprotections: AtomicUsize,
+    /// The compressed data, or null if the chunk is not compressed. The first word is the length
+    /// of the data that follows it.
+    data: AtomicPtr<usize>,
+}
+
+/// The statistics of chunk compression.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkCompressionStats {
+    /// The number of chunks that are compressed.
+    pub compressed_chunks: usize,
+    /// The bytes of the compressed data of the compressed chunks.
+    pub compressed_bytes: usize,
+}
+
+/// The chunk compression of an MMTk instance.
+pub struct ChunkCompression {
+    /// The entries of the chunks in the heap range, indexed by the chunk.
+    table: *mut ChunkEntry,
+    /// The chunks that are acquired by the spaces. The fault handler does not use this.
+    chunks: Mutex<Vec<Address>>,
+    /// The number of GCs so far.
+    current_gc: AtomicUsize,
+    /// The number of chunks that are compressed.
+    compressed_chunks: AtomicUsize,
+    /// The bytes of the compressed data of the compressed chunks.
+    compressed_bytes: AtomicUsize,
+}
+
+// The table is only accessed through atomics.
+unsafe impl Send for ChunkCompression {}
+unsafe impl Sync for ChunkCompression {}
+
+/// The chunk compression that the fault handler uses, or null if no MMTk instance compresses
+/// chunks.
+static ACTIVE: AtomicPtr<ChunkCompression> = AtomicPtr::new(std::ptr::null_mut());
+
+impl ChunkCompression {
+    const TABLE_BYTES: usize = CHUNKS_IN_HEAP * std::mem::size_of::<ChunkEntry>();
+
+    /// Map the chunk table, and make this the chunk compression that the fault handler uses.
+    pub(crate) fn new() -> Box<Self> {
+        // The table is only backed by memory for the parts of the heap that are used.
+        let table = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                Self::TABLE_BYTES,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(table, libc::MAP_FAILED, "Failed to map the chunk table");
+        let mut this = Box::new(ChunkCompression {
+            table: table as *mut ChunkEntry,
+            chunks: Mutex::new(vec![]),
+            current_gc: AtomicUsize::new(0),
+            compressed_chunks: AtomicUsize::new(0),
+            compressed_bytes: AtomicUsize::new(0),
+        });
+        let ptr: *mut ChunkCompression = &mut *this;
+        ACTIVE
+            .compare_exchange(
+                std::ptr::null_mut(),
+                ptr,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .expect("Only one MMTk instance can compress chunks at a time");
+        this
+    }
+
+    /// The entry of the chunk of `address`, or `None` if it is outside the heap range.
+    fn entry(&self, address: Address) -> Option<&ChunkEntry> {
+        if address < HEAP_START || address >= HEAP_END {
+            return None;
+        }
+        let index = (address - HEAP_START) >> LOG_BYTES_IN_CHUNK;
+        Some(unsafe { &*self.table.add(index) })
+    }
+
+    /// The current statistics of chunk compression.
+    pub fn stats(&self) -> ChunkCompressionStats {
+        ChunkCompressionStats {
+            compressed_chunks: self.compressed_chunks.load(Ordering::SeqCst),
+            compressed_bytes: self.compressed_bytes.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Mark the chunks of `[start, start + bytes)` as touched, and decompress them if they are
+    /// compressed. This must be called before MMTk writes to memory that may be in a compressed
+    /// chunk without faulting (e.g. zeroing it), and before the kernel accesses the memory.
+    pub(crate) fn touch(&self, start: Address, bytes: usize) {
+        let mut chunk = conversions::chunk_align_down(start);
+        let end = start + bytes;
+        while chunk < end {
+            if let Some(entry) = self.entry(chunk) {
+                self.touch_chunk(chunk, entry);
+            }
+            chunk += BYTES_IN_CHUNK;
+        }
+    }
+
+    fn touch_chunk(&self, chunk: Address, entry: &ChunkEntry) {
+        entry.touched.store(true, Ordering::SeqCst);
+        loop {
+            match entry.state.load(Ordering::SeqCst) {
+                state::UNUSED => {
+                    if Self::transition(entry, state::UNUSED, state::UNCOMPRESSED) {
+                        self.chunks.lock().unwrap().push(chunk);
+                        return;
+                    }
+                }
+                state::COMPRESSED => {
+                    if Self::transition(entry, state::COMPRESSED, state::RESTORING) {
+                        assert!(self.restore(chunk, entry), "Failed to unprotect {}", chunk);
+                        return;
+                    }
+                }
+                // Wait for another thread to compress or decompress the chunk.
+                state::COMPRESSING | state::RESTORING => std::thread::yield_now(),
+                _ => return,
+            }
+        }
+    }
+
+    /// Mark the chunk of `address` as touched, without decompressing it. This is for the objects
+    /// that the GC scans, which are accessible.
+    pub(crate) fn mark_touched(&self, address: Address) {
+        if let Some(entry) = self.entry(address) {
+            // Avoid the store, so the table is only written once per chunk and cycle.
+            if !entry.touched.load(Ordering::Relaxed) {
+                entry.touched.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn transition(entry: &ChunkEntry, from: u8, to: u8) -> bool {
+        entry
+            .state
+            .compare_exchange(from, to, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Decompress a chunk in place, after the caller moved it from compressed to restoring. This
+    /// does not take a lock, allocate, free memory or panic, so it can be called from the fault
+    /// handler. Return false, and leave the chunk compressed, if the chunk cannot be unprotected.
+    fn restore(&self, chunk: Address, entry: &ChunkEntry) -> bool {
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        if unsafe { libc::mprotect(chunk.to_mut_ptr(), BYTES_IN_CHUNK, prot) } != 0 {
+            entry.state.store(state::COMPRESSED, Ordering::SeqCst);
+            return false;
+        }
+        let data = unsafe { compressed_data(entry.data.load(Ordering::SeqCst)) };
+        // The pages were given back to the OS, so they are zeroed.
+        let words = unsafe { std::slice::from_raw_parts_mut(chunk.to_mut_ptr(), WORDS_IN_CHUNK) };
+        decompress(data, words);
+        entry.unprotections.fetch_add(1, Ordering::SeqCst);
+        self.compressed_chunks.fetch_sub(1, Ordering::SeqCst);
+        self.compressed_bytes
+            .fetch_sub(data.len() * BYTES_IN_WORD, Ordering::SeqCst);
+        entry.touched.store(true, Ordering::SeqCst);
+        entry.state.store(state::RESTORED, Ordering::SeqCst);
+        true
+    }
+
+    /// Free the compressed data of a restored chunk.
+    fn free_restored(entry: &ChunkEntry) {
+        if Self::transition(entry, state::RESTORED, state::RESTORING) {
+            let data = entry.data.swap(std::ptr::null_mut(), Ordering::SeqCst);
+            unsafe { free_compressed_data(data) };
+            entry.state.store(state::UNCOMPRESSED, Ordering::SeqCst);
+        }
+    }
+
+    /// Start a new GC cycle at the end of GC `gc_count`.
+    pub(crate) fn gc_end(&self, gc_count: usize) {
+        self.current_gc.store(gc_count, Ordering::Relaxed);
+    }
+
+    /// Free the compressed data of the chunks that were decompressed, record the chunks that were
+    /// touched in the last GC cycle, and compress the chunks that have not been touched for
+    /// `min_idle_gcs` GCs.
+    pub(crate) fn compress_cold_chunks(&self, min_idle_gcs: usize) {
+        let current = self.current_gc.load(Ordering::Relaxed);
+        let chunks = self.chunks.lock().unwrap().clone();
+        for chunk in chunks {
+            let entry = self.entry(chunk).unwrap();
+            Self::free_restored(entry);
+            if entry.touched.swap(false, Ordering::SeqCst) {
+                entry.last_touched.store(current, Ordering::SeqCst);
+            }
+            let last_touched = entry.last_touched.load(Ordering::SeqCst);
+            if current.saturating_sub(last_touched) >= min_idle_gcs
+                && entry.state.load(Ordering::SeqCst) == state::UNCOMPRESSED
+            {
+                install_fault_handler();
+                self.compress_chunk(chunk, entry, current);
+            }
+        }
+    }
+
+    fn compress_chunk(&self, chunk: Address, entry: &ChunkEntry, current: usize) {
+        if !Self::transition(entry, state::UNCOMPRESSED, state::COMPRESSING) {
+            return;
+        }
+        // Make the chunk read-only, so a write to the chunk after this waits for the compression
+        // in the fault handler.
+        let ret = unsafe { libc::mprotect(chunk.to_mut_ptr(), BYTES_IN_CHUNK, libc::PROT_READ) };
+        assert_eq!(ret, 0, "Failed to protect chunk {}", chunk);
+
+        // Give up if the chunk is touched since we found it, or if it is not worth it. In the
+        // latter case, do not try the chunk again until it has been idle for another period.
+        let words = unsafe { std::slice::from_raw_parts(chunk.to_ptr(), WORDS_IN_CHUNK) };
+        let data = (!entry.touched.load(Ordering::SeqCst))
+            .then(|| compress(words))
+            .filter(|data| data.len() <= WORDS_IN_CHUNK / MAX_COMPRESSED_FRACTION);
+        let data = match data {
+            Some(data) => data,
+            None => {
+                let prot = libc::PROT_READ | libc::PROT_WRITE;
+                let ret = unsafe { libc::mprotect(chunk.to_mut_ptr(), BYTES_IN_CHUNK, prot) };
+                assert_eq!(ret, 0, "Failed to unprotect chunk {}", chunk);
+                entry.unprotections.fetch_add(1, Ordering::SeqCst);
+                entry.last_touched.store(current, Ordering::SeqCst);
+                entry.state.store(state::UNCOMPRESSED, Ordering::SeqCst);
+                return;
+            }
+        };
+
+        let bytes = data.len() * BYTES_IN_WORD;
+        entry
+            .data
+            .store(leak_compressed_data(data), Ordering::SeqCst);
+        let ret = unsafe { libc::mprotect(chunk.to_mut_ptr(), BYTES_IN_CHUNK, libc::PROT_NONE) };
+        assert_eq!(ret, 0, "Failed to protect chunk {}", chunk);
+        let ret = unsafe { libc::madvise(chunk.to_mut_ptr(), BYTES_IN_CHUNK, libc::MADV_DONTNEED) };
+        assert_eq!(ret, 0, "Failed to discard the pages of chunk {}", chunk);
+        self.compressed_chunks.fetch_add(1, Ordering::SeqCst);
+        self.compressed_bytes.fetch_add(bytes, Ordering::SeqCst);
+        debug!("Compressed chunk {} to {} bytes", chunk, bytes);
+        entry.state.store(state::COMPRESSED, Ordering::SeqCst);
+    }
+
+    /// Handle a fault at `address`. Return true if the fault is in a compressed chunk, or in a
+    /// chunk that may have been unprotected after the access faulted, and the access can be
+    /// retried.
+    fn handle_fault_at(&self, address: Address) -> bool {
+        let entry = match self.entry(address) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        // Sample the count before the state, so an accessible state is not older than the count.
+        let unprotections = entry.unprotections.load(Ordering::SeqCst);
+        loop {
+            match entry.state.load(Ordering::SeqCst) {
+                state::COMPRESSED => {
+                    if Self::transition(entry, state::COMPRESSED, state::RESTORING) {
+                        return self.restore(conversions::chunk_align_down(address), entry);
+                    }
+                }
+                // Another thread is compressing or decompressing the chunk. Retry the access,
+                // which faults again until the chunk is accessible.
+                state::COMPRESSING | state::RESTORING => {
+                    unsafe { libc::sched_yield() };
+                    return true;
+                }
+                // Another thread restored the chunk after our access faulted.
+                state::RESTORED => return true,
+                // The chunk is accessible. If it was protected when our access faulted, another
+                // thread has unprotected it since (e.g. it gave up compressing the chunk, or freed
+                // the data of the restored chunk), and the access can be retried. We cannot tell,
+                // so we retry the access once for each unprotection, and a fault that is not ours
+                // is forwarded when the access faults again.
+                _ => {
+                    let chunk = conversions::chunk_align_down(address);
+                    return unprotections != 0 && retry_once(chunk, unprotections);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ChunkCompression {
+    fn drop(&mut self) {
+        // Decompress the chunks, as the memory of the heap may outlive this instance.
+        let chunks = std::mem::take(self.chunks.get_mut().unwrap());
+        for chunk in chunks {
+            let entry = self.entry(chunk).unwrap();
+            self.touch_chunk(chunk, entry);
+            Self::free_restored(entry);
+        }
+        let ptr: *mut ChunkCompression = self;
+        let _ = ACTIVE.compare_exchange(
+            ptr,
+            std::ptr::null_mut(),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        unsafe { libc::munmap(self.table as *mut libc::c_void, Self::TABLE_BYTES) };
+    }
+}
+
+/// Move the compressed data to the side store, after its length.
+fn leak_compressed_data(data: Vec<usize>) -> *mut usize {
+    let mut words = Vec::with_capacity(data.len() + 1);
+    words.push(data.len());
+    words.extend_from_slice(&data);
+    Box::into_raw(words.into_boxed_slice()) as *mut usize
+}
+
+/// The compressed data in the side store at `ptr`.
+unsafe fn compressed_data(ptr: *const usize) -> &'static [usize] {
+    std::slice::from_raw_parts(ptr.add(1), *ptr)
+}
+
+/// Free the compressed data in the side store at `ptr`.
+unsafe fn free_compressed_data(ptr: *mut usize) {
+    let len = *ptr + 1;
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+thread_local! {
+    /// The chunk and its count of unprotections, for the last fault in an accessible chunk that
+    /// the thread retried.
+    static LAST_RETRY: Cell<(Address, usize)> = const { Cell::new((Address::ZERO, 0)) };
+}
+
+/// Should the thread retry an access that faulted in an accessible chunk? This is only true the
+/// first time for each unprotection of the chunk. A `const` thread-local without a destructor is
+/// async-signal-safe.
+fn retry_once(chunk: Address, unprotections: usize) -> bool {
+    LAST_RETRY.with(|last| last.replace((chunk, unprotections)) != (chunk, unprotections))
+}
+
+/// The SIGSEGV handler before ours.
+static mut PREVIOUS_HANDLER: std::mem::MaybeUninit<libc::sigaction> =
+    std::mem::MaybeUninit::uninit();
+
+extern "C" fn handle_sigsegv(sig: libc::c_int, info: *mut libc::siginfo_t, ctx: *mut libc::c_void) {
+    unsafe {
+        // A fault has a positive code. A signal sent by a process (e.g. with `kill()`) does not
+        // have a fault address.
+        if (*info).si_code > 0 {
+            let active = ACTIVE.load(Ordering::SeqCst);
+            // Keep errno, as the handler interrupts code that may read it.
+            let errno = *libc::__errno_location();
+            let handled = !active.is_null()
+                && (*active).handle_fault_at(Address::from_mut_ptr((*info).si_addr()));
+            *libc::__errno_location() = errno;
+            if handled {
+                return;
+            }
+        }
+        forward_signal(sig, info, ctx);
+    }
+}
+
+/// Forward a signal that is not ours to the previous handler. Our handler is never uninstalled,
+/// as the other threads may still fault in the compressed chunks.
+unsafe fn forward_signal(sig: libc::c_int, info: *mut libc::siginfo_t, ctx: *mut libc::c_void) {
+    let previous = &*PREVIOUS_HANDLER.as_ptr();
+    let is_fault = (*info).si_code > 0;
+    if previous.sa_sigaction == libc::SIG_IGN && !is_fault {
+        return;
+    }
+    if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+        // The default action terminates the process, and a fault cannot be ignored. Terminate the
+        // process with `abort()`, which is async-signal-safe, instead of restoring the default
+        // action, which would uninstall our handler.
+        const MESSAGE: &[u8] = b"Unhandled SIGSEGV\n";
+        libc::write(
+            libc::STDERR_FILENO,
+            MESSAGE.as_ptr() as *const libc::c_void,
+            MESSAGE.len(),
+        );
+        libc::abort();
+    } else if previous.sa_flags & libc::SA_SIGINFO != 0 {
+        let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+            std::mem::transmute(previous.sa_sigaction);
+        handler(sig, info, ctx);
+    } else {
+        let handler: extern "C" fn(libc::c_int) = std::mem::transmute(previous.sa_sigaction);
+        handler(sig);
+    }
+}
+
+/// Install the SIGSEGV handler, if it is not installed yet. The handler runs on the alternate
+/// signal stack if the thread has one, so a stack overflow still reaches the previous handler.
+fn install_fault_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigsegv as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        let ret = libc::sigaction(libc::SIGSEGV, &action, PREVIOUS_HANDLER.as_mut_ptr());
+        assert_eq!(ret, 0, "Failed to install the SIGSEGV handler");
+    });
+}
+
+/// A background packet that compresses the chunks that have not been touched for the number of
+/// GCs set by the option `chunk_compression_gcs`.
+pub struct CompressColdChunks;
+
+impl<VM: VMBinding> GCWork<VM> for CompressColdChunks {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        if let Some(chunk_compression) = mmtk.plan.base().chunk_compression.as_ref() {
+            chunk_compression.compress_cold_chunks(*mmtk.options.chunk_compression_gcs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::memory;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    fn round_trip(words: &[usize]) -> Vec<usize> {
+        let data = compress(words);
+        let mut out = vec![0; words.len()];
+        decompress(&data, &mut out);
+        assert_eq!(out, words);
+        data
+    }
+
+    #[test]
+    fn test_zeros() {
+        let data = round_trip(&[0; 1024]);
+        assert_eq!(data, vec![1024, 0]);
+    }
+
+    #[test]
+    fn test_literals() {
+        let words: Vec<usize> = (1..100).collect();
+        let data = round_trip(&words);
+        assert_eq!(data.len(), words.len() + 2);
+    }
+
+    #[test]
+    fn test_mixed() {
+        let mut words = vec![0usize; 4096];
+        for (i, word) in words.iter_mut().enumerate() {
+            // Short and long runs of zeros between the literals.
+            if i % 7 == 0 || ((i / 512) % 2 == 0 && i % 3 == 0) {
+                *word = i * 31 + 1;
+            }
+        }
+        words[4095] = 0;
+        round_trip(&words);
+        round_trip(&words[1..]);
+        round_trip(&[]);
+        round_trip(&[0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_compress_and_fault() {
+        serial_test(|| {
+            let chunk = HEAP_END - BYTES_IN_CHUNK;
+            with_cleanup(
+                || {
+                    memory::dzmmap_noreplace(chunk, BYTES_IN_CHUNK).unwrap();
+                    let word = |i: usize| chunk + i * BYTES_IN_WORD;
+                    for i in (0..WORDS_IN_CHUNK).step_by(1000) {
+                        unsafe { word(i).store(i + 1) };
+                    }
+                    let chunk_compression = ChunkCompression::new();
+                    let compress_after_gc = |gc_count| {
+                        chunk_compression.gc_end(gc_count);
+                        chunk_compression.compress_cold_chunks(1);
+                        chunk_compression.stats().compressed_chunks
+                    };
+
+                    // The chunk is touched when it is acquired.
+                    chunk_compression.touch(chunk, BYTES_IN_CHUNK);
+                    assert_eq!(compress_after_gc(1), 0);
+                    assert_eq!(compress_after_gc(2), 1);
+                    let compressed_bytes = chunk_compression.stats().compressed_bytes;
+                    assert!(compressed_bytes > 0 && compressed_bytes < BYTES_IN_CHUNK / 2);
+
+                    // A read faults, and decompresses the chunk.
+                    for i in (0..WORDS_IN_CHUNK).step_by(1000) {
+                        assert_eq!(unsafe { word(i).load::<usize>() }, i + 1);
+                    }
+                    assert_eq!(chunk_compression.stats(), ChunkCompressionStats::default());
+
+                    // So does a write. The chunk is cold again after it is not touched in a cycle.
+                    assert_eq!(compress_after_gc(3), 0);
+                    // The data of the restored chunk has been freed, so the chunk is uncompressed.
+                    // An access that faulted before that is retried, but only once.
+                    assert!(chunk_compression.handle_fault_at(word(3)));
+                    assert!(!chunk_compression.handle_fault_at(word(3)));
+                    assert_eq!(compress_after_gc(4), 1);
+                    unsafe { word(1).store(42usize) };
+                    assert_eq!(unsafe { word(1).load::<usize>() }, 42);
+                    assert_eq!(chunk_compression.stats().compressed_chunks, 0);
+
+                    // A system call does not fault, but fails, unless the chunk is decompressed
+                    // first.
+                    assert_eq!(compress_after_gc(5), 0);
+                    assert_eq!(compress_after_gc(6), 1);
+                    let mut fds = [0; 2];
+                    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+                    let write = || unsafe { libc::write(fds[1], chunk.to_ptr(), BYTES_IN_WORD) };
+                    assert_eq!(write(), -1);
+                    assert_eq!(unsafe { *libc::__errno_location() }, libc::EFAULT);
+                    chunk_compression.touch(chunk, BYTES_IN_WORD);
+                    assert_eq!(write(), BYTES_IN_WORD as isize);
+                    let mut read = 0usize;
+                    let buf = &mut read as *mut usize as *mut libc::c_void;
+                    assert_eq!(
+                        unsafe { libc::read(fds[0], buf, BYTES_IN_WORD) },
+                        BYTES_IN_WORD as isize
+                    );
+                    assert_eq!(read, 1);
+                    unsafe {
+                        libc::close(fds[0]);
+                        libc::close(fds[1]);
+                    }
+
+                    // The chunks are decompressed when the instance is dropped.
+                    assert_eq!(compress_after_gc(7), 0);
+                    assert_eq!(compress_after_gc(8), 1);
+                    drop(chunk_compression);
+                    assert_eq!(unsafe { word(1000).load::<usize>() }, 1001);
+                },
+                || {
+                    memory::munmap(chunk, BYTES_IN_CHUNK).unwrap();
+                },
+            )
+        })
+    }
+}
@@ -0,0 +1,320 @@
+//! The chunk map records the state of each chunk in a byte of global side metadata: whether the
+//! chunk is free, or which space it is allocated to and whether it needs to be swept. The state
+//! table is shared by all the spaces, and each space that manages its memory in chunks (e.g.
+//! `ImmixSpace` and `MallocSpace`) owns a [`ChunkMap`] that updates the table for its chunks,
+//! remembers the range of its chunks, and generates the per-chunk work packets (such as sweeping)
+//! for the space.
+
+use crate::scheduler::GCWork;
+use crate::util::heap::regions::{Chunk, Region, RegionIterator, RegionState, StatefulRegion};
+use crate::util::metadata::side_metadata::{address_to_meta_address, SideMetadataSpec};
+use crate::vm::VMBinding;
+use spin::Mutex;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The state of a chunk in the chunk map.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChunkState {
+    /// The chunk is not allocated to any space.
+    Free,
+    /// The chunk is allocated to the space with the given index (see [`ChunkMap::space_index`]).
+    /// `needs_sweep` is set when the space prepares for a GC (or when the chunk is allocated), and
+    /// cleared when the chunk is swept.
+    Allocated { space: u8, needs_sweep: bool },
+}
+
+impl ChunkState {
+    /// Private constant
+    const ALLOCATED_BIT: u8 = 0x80;
+    /// Private constant
+    const NEEDS_SWEEP_BIT: u8 = 0x40;
+    /// Private constant
+    const SPACE_MASK: u8 = Self::NEEDS_SWEEP_BIT - 1;
+    /// The number of distinct space indices that can be recorded.
+    const MAX_SPACES: usize = Self::SPACE_MASK as usize + 1;
+}
+
+impl From<u8> for ChunkState {
+    #[inline(always)]
+    fn from(state: u8) -> Self {
+        if state & Self::ALLOCATED_BIT == 0 {
+            debug_assert_eq!(state, 0);
+            ChunkState::Free
+        } else {
+            ChunkState::Allocated {
+                space: state & Self::SPACE_MASK,
+                needs_sweep: state & Self::NEEDS_SWEEP_BIT != 0,
+            }
+        }
+    }
+}
+
+impl From<ChunkState> for u8 {
+    #[inline(always)]
+    fn from(state: ChunkState) -> Self {
+        match state {
+            ChunkState::Free => 0,
+            ChunkState::Allocated { space, needs_sweep } => {
+                debug_assert!(space <= ChunkState::SPACE_MASK);
+                let sweep_bit = if needs_sweep {
+                    ChunkState::NEEDS_SWEEP_BIT
+                } else {
+                    0
+                };
+                ChunkState::ALLOCATED_BIT | sweep_bit | space
+            }
+        }
+    }
+}
+
+impl RegionState for ChunkState {}
+
+impl StatefulRegion for Chunk {
+    type State = ChunkState;
+    const STATE_TABLE: SideMetadataSpec = ChunkMap::STATE_TABLE;
+}
+
+/// A bit for each space index that is used by a live chunk map. The index of a chunk map is
+/// released when it is dropped (e.g. with its MMTk instance), so no two live chunk maps share an
+/// index.
+static USED_SPACE_INDICES: AtomicU64 = AtomicU64::new(0);
+
+/// Take the lowest space index that is not used by a live chunk map.
+fn acquire_space_index() -> u8 {
+    // The indices are recorded in a u64.
+    debug_assert!(ChunkState::MAX_SPACES <= 64);
+    let mut used = USED_SPACE_INDICES.load(Ordering::Relaxed);
+    loop {
+        let index = (!used).trailing_zeros() as usize;
+        assert!(
+            index < ChunkState::MAX_SPACES,
+            "Too many spaces use the chunk map (at most {} at a time)",
+            ChunkState::MAX_SPACES
+        );
+        match USED_SPACE_INDICES.compare_exchange_weak(
+            used,
+            used | (1 << index),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => return index as u8,
+            Err(current) => used = current,
+        }
+    }
+}
+
+fn release_space_index(index: u8) {
+    let old = USED_SPACE_INDICES.fetch_and(!(1 << index), Ordering::Relaxed);
+    debug_assert!(old & (1 << index) != 0);
+}
+
+/// The view of a space on the chunk map.
+pub struct ChunkMap {
+    /// The index of the space that is recorded in the chunk map for its chunks.
+    space_index: u8,
+    /// The range that covers all the chunks that have been allocated to the space.
+    chunk_range: Mutex<Range<Chunk>>,
+}
+
+impl ChunkMap {
+    /// The chunk state table. This is a global side metadata spec, so every space maps it for its chunks.
+    pub const STATE_TABLE: SideMetadataSpec =
+        crate::util::metadata::side_metadata::spec_defs::CHUNK_MAP;
+
+    pub fn new() -> Self {
+        Self {
+            space_index: acquire_space_index(),
+            chunk_range: Mutex::new(Chunk::ZERO..Chunk::ZERO),
+        }
+    }
+
+    /// The index that identifies the space in the chunk map.
+    pub fn space_index(&self) -> u8 {
+        self.space_index
+    }
+
+    /// Record the chunk as allocated to this space, or as free. A newly allocated chunk needs to be
+    /// swept in the current or the next GC. Allocating a chunk that is already allocated to this
+    /// space keeps its sweep state.
+    pub fn set_allocated(&self, chunk: Chunk, allocated: bool) {
+        // Do nothing if the chunk is already in the expected state.
+        if self.is_allocated(chunk) == allocated {
+            return;
+        }
+        let state = if allocated {
+            debug_assert_eq!(
+                chunk.get_state(),
+                ChunkState::Free,
+                "{:?} is owned by another space",
+                chunk
+            );
+            ChunkState::Allocated {
+                space: self.space_index,
+                needs_sweep: true,
+            }
+        } else {
+            ChunkState::Free
+        };
+        chunk.set_state(state);
+        // If this is a newly allocated chunk, then expand the chunk range.
+        if allocated {
+            debug_assert!(!chunk.start().is_zero());
+            let mut range = self.chunk_range.lock();
+            if range.start == Chunk::ZERO {
+                range.start = chunk;
+                range.end = chunk.next();
+            } else if chunk < range.start {
+                range.start = chunk;
+            } else if range.end <= chunk {
+                range.end = chunk.next();
+            }
+        }
+    }
+
+    /// Is the chunk allocated to this space?
+    pub fn is_allocated(&self, chunk: Chunk) -> bool {
+        match chunk.get_state() {
+            ChunkState::Allocated { space, .. } => space == self.space_index,
+            ChunkState::Free => false,
+        }
+    }
+
+    /// Is the chunk allocated to this space, and not yet swept since the space prepared for the GC?
+    pub fn needs_sweep(&self, chunk: Chunk) -> bool {
+        chunk.get_state()
+            == ChunkState::Allocated {
+                space: self.space_index,
+                needs_sweep: true,
+            }
+    }
+
+    /// Mark all the chunks of this space as needing to be swept. A space calls this when it
+    /// prepares for a GC.
+    pub fn prepare_sweep(&self) {
+        for chunk in self.allocated_chunks() {
+            chunk.set_state(ChunkState::Allocated {
+                space: self.space_index,
+                needs_sweep: true,
+            });
+        }
+    }
+
+    /// Record that the chunk has been swept. This does nothing if the sweep has freed the chunk.
+    pub fn set_swept(&self, chunk: Chunk) {
+        let from = ChunkState::Allocated {
+            space: self.space_index,
+            needs_sweep: true,
+        };
+        let to = ChunkState::Allocated {
+            space: self.space_index,
+            needs_sweep: false,
+        };
+        chunk.transition_state(from, to);
+    }
+
+    /// Is the chunk map mapped for the chunk? A space always maps the chunk map before it uses a chunk,
+    /// but the chunks that are not used by any space may be unmapped.
+    pub fn is_mapped(chunk: Chunk) -> bool {
+        // `address_to_meta_address` does not check the bounds of the spec. An address far away
+        // from the heap (e.g. from malloc) may be translated to an address beyond the chunk map.
+        let meta_address = address_to_meta_address(&Self::STATE_TABLE, chunk.start());
+        meta_address < Self::STATE_TABLE.upper_bound_address_for_contiguous()
+            && meta_address.is_mapped()
+    }
+
+    /// Return the index of the space that the chunk is allocated to, or `None` if the chunk is free.
+    pub fn owner(chunk: Chunk) -> Option<u8> {
+        if !Self::is_mapped(chunk) {
+            return None;
+        }
+        match chunk.get_state() {
+            ChunkState::Free => None,
+            ChunkState::Allocated { space, .. } => Some(space),
+        }
+    }
+
+    /// A range of all the chunks that have been allocated to this space. Some of them may have been freed.
+    pub fn all_chunks(&self) -> RegionIterator<Chunk> {
+        let chunk_range = self.chunk_range.lock();
+        RegionIterator::<Chunk>::new(chunk_range.start, chunk_range.end)
+    }
+
+    /// The chunks that are currently allocated to this space.
+    pub fn allocated_chunks(&self) -> impl Iterator<Item = Chunk> + '_ {
+        self.all_chunks()
+            .filter(move |c| Self::is_mapped(*c) && self.is_allocated(*c))
+    }
+
+    /// Helper function to create per-chunk processing work packets, for each chunk that is allocated to this space.
+    pub fn generate_tasks<VM: VMBinding>(
+        &self,
+        func: impl Fn(Chunk) -> Box<dyn GCWork<VM>>,
+    ) -> Vec<Box<dyn GCWork<VM>>> {
+        self.allocated_chunks().map(func).collect()
+    }
+
+    /// Helper function to create per-chunk sweeping work packets, for each chunk of this space
+    /// that needs to be swept. The work packet should call [`ChunkMap::set_swept`] for the chunk.
+    pub fn generate_sweep_tasks<VM: VMBinding>(
+        &self,
+        func: impl Fn(Chunk) -> Box<dyn GCWork<VM>>,
+    ) -> Vec<Box<dyn GCWork<VM>>> {
+        self.all_chunks()
+            .filter(|c| Self::is_mapped(*c) && self.needs_sweep(*c))
+            .map(func)
+            .collect()
+    }
+}
+
+impl Drop for ChunkMap {
+    fn drop(&mut self) {
+        release_space_index(self.space_index);
+    }
+}
+
+impl Default for ChunkMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_state_encoding() {
+        for state in [
+            ChunkState::Free,
+            ChunkState::Allocated {
+                space: 0,
+                needs_sweep: false,
+            },
+            ChunkState::Allocated {
+                space: 5,
+                needs_sweep: true,
+            },
+            ChunkState::Allocated {
+                space: (ChunkState::MAX_SPACES - 1) as u8,
+                needs_sweep: true,
+            },
+        ] {
+            let byte: u8 = state.into();
+            assert_eq!(ChunkState::from(byte), state);
+        }
+        // A zeroed chunk map means all the chunks are free.
+        assert_eq!(ChunkState::from(0), ChunkState::Free);
+    }
+
+    #[test]
+    fn test_space_indices_are_unique() {
+        let maps: Vec<ChunkMap> = (0..8).map(|_| ChunkMap::new()).collect();
+        let mut indices: Vec<u8> = maps.iter().map(|m| m.space_index()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        assert_eq!(indices.len(), maps.len());
+        let used = USED_SPACE_INDICES.load(Ordering::Relaxed);
+        assert!(maps.iter().all(|m| used & (1 << m.space_index()) != 0));
+    }
+}
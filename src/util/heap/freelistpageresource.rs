@@ -76,6 +76,17 @@ impl<VM: VMBinding> PageResource<VM> for FreeListPageResource<VM> {
         &mut self.common
     }
 
+    fn release_unmapped_pages(&self, start: Address, pages: usize) {
+        if self.protect_memory_on_release {
+            // Pages on the free list are expected to be mapped and protected, so we cannot
+            // allocate them again.
+            self.common.accounting.release(pages);
+            return;
+        }
+        debug_assert_eq!(self.get_allocated_pages(start), pages);
+        self.free_pages(start);
+    }
+
     fn get_available_physical_pages(&self) -> usize {
         let mut rtn = self.sync.lock().unwrap().pages_currently_on_freelist;
         if !self.common.contiguous {
@@ -358,6 +369,53 @@ impl<VM: VMBinding> FreeListPageResource<VM> {
         }
     }
 
+    /// Return the number of pages in the allocation that starts at `first`.
+    pub fn get_allocated_pages(&self, first: Address) -> usize {
+        debug_assert!(conversions::is_page_aligned(first));
+        let page_offset = conversions::bytes_to_pages(first - self.start);
+        self.free_list.size(page_offset as _) as usize
+    }
+
+    /// Release the pages after the first `keep` pages of the allocation that starts at `first`,
+    /// and keep the first `keep` pages allocated. The allocation then only has `keep` pages.
+    pub fn release_tail_pages(&self, first: Address, keep: usize) {
+        debug_assert!(conversions::is_page_aligned(first));
+        debug_assert!(keep > 0);
+        let page_offset = conversions::bytes_to_pages(first - self.start);
+        let pages = self.free_list.size(page_offset as _) as usize;
+        if keep >= pages {
+            return;
+        }
+        let tail = first + conversions::pages_to_bytes(keep);
+        let tail_pages = pages - keep;
+
+        self.common.on_release(tail, tail_pages);
+        if self.protect_memory_on_release {
+            self.mprotect(tail, tail_pages);
+        }
+
+        // FIXME
+        #[allow(clippy::cast_ref_to_mut)]
+        let me = unsafe { &mut *(self as *const _ as *mut Self) };
+        let freed = {
+            let mut sync = self.sync.lock().unwrap();
+            self.common.accounting.release(tail_pages);
+            // Split the allocation into the pages we keep and the tail, and free the tail.
+            let tail_offset = (page_offset + keep) as i32;
+            me.free_list.set_size(page_offset as _, keep as _);
+            me.free_list.set_free(page_offset as _, false);
+            me.free_list.set_size(tail_offset, tail_pages as _);
+            me.free_list.set_free(tail_offset, false);
+            let freed = me.free_list.free(tail_offset, true);
+            sync.pages_currently_on_freelist += tail_pages;
+            freed
+        };
+        if !self.common.contiguous {
+            // only discontiguous spaces use chunks
+            me.release_free_chunks(tail, freed as _);
+        }
+    }
+
     pub fn release_pages(&self, first: Address) {
         debug_assert!(conversions::is_page_aligned(first));
         let page_offset = conversions::bytes_to_pages(first - self.start);
@@ -366,10 +424,17 @@ impl<VM: VMBinding> FreeListPageResource<VM> {
         //     VM.memory.zero(false, first, Conversions.pagesToBytes(pages));
         debug_assert!(pages as usize <= self.common.accounting.get_committed_pages());
 
+        self.common.on_release(first, pages as _);
         if self.protect_memory_on_release {
             self.mprotect(first, pages as _);
         }
+        self.free_pages(first);
+    }
 
+    /// Put the pages of the allocation that starts at `first` back to the free list.
+    fn free_pages(&self, first: Address) {
+        let page_offset = conversions::bytes_to_pages(first - self.start);
+        let pages = self.free_list.size(page_offset as _);
         // FIXME
         #[allow(clippy::cast_ref_to_mut)]
         let me = unsafe { &mut *(self as *const _ as *mut Self) };
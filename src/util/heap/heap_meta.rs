@@ -2,11 +2,12 @@ use crate::util::conversions;
 use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
 use crate::util::options::Options;
 use crate::util::Address;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct HeapMeta {
     pub heap_cursor: Address,
     pub heap_limit: Address,
-    pub total_pages: usize,
+    total_pages: AtomicUsize,
 }
 
 impl HeapMeta {
@@ -14,7 +15,7 @@ impl HeapMeta {
         HeapMeta {
             heap_cursor: HEAP_START,
             heap_limit: HEAP_END,
-            total_pages: conversions::bytes_to_pages(*options.heap_size),
+            total_pages: AtomicUsize::new(conversions::bytes_to_pages(*options.heap_size)),
         }
     }
 
@@ -48,6 +49,12 @@ impl HeapMeta {
     }
 
     pub fn get_total_pages(&self) -> usize {
-        self.total_pages
+        self.total_pages.load(Ordering::Relaxed)
+    }
+
+    /// Change the heap bound. The new bound is checked against the reserved pages
+    /// at the next GC poll, so shrinking below the current usage will trigger a GC.
+    pub fn set_total_pages(&self, pages: usize) {
+        self.total_pages.store(pages, Ordering::Relaxed);
     }
 }
@@ -114,6 +114,17 @@ impl Mmapper for ByteMapMmapper {
             MapState::transition_to_protected(&self.mapped[chunk], mmap_start).unwrap();
         }
     }
+
+    fn unmap(&self, start: Address, pages: usize) {
+        let start_chunk = Self::address_to_mmap_chunks_down(start);
+        let end_chunk = Self::address_to_mmap_chunks_up(start + pages_to_bytes(pages));
+        let _guard = self.lock.lock().unwrap();
+
+        for chunk in start_chunk..end_chunk {
+            let mmap_start = Self::mmap_chunks_to_address(chunk);
+            MapState::transition_to_unmapped(&self.mapped[chunk], mmap_start).unwrap();
+        }
+    }
 }
 
 impl ByteMapMmapper {
@@ -323,6 +334,42 @@ mod tests {
         })
     }
 
+    #[test]
+    fn unmap() {
+        serial_test(|| {
+            with_cleanup(
+                || {
+                    // map 2 chunks, and protect 1 of them
+                    let mmapper = ByteMapMmapper::new();
+                    let pages_per_chunk = MMAP_CHUNK_BYTES >> LOG_BYTES_IN_PAGE as usize;
+                    mmapper
+                        .ensure_mapped(FIXED_ADDRESS, pages_per_chunk * 2)
+                        .unwrap();
+                    mmapper.protect(FIXED_ADDRESS, pages_per_chunk);
+
+                    // unmap both chunks
+                    mmapper.unmap(FIXED_ADDRESS, pages_per_chunk * 2);
+                    let chunk = ByteMapMmapper::address_to_mmap_chunks_down(FIXED_ADDRESS);
+                    for c in chunk..chunk + 2 {
+                        assert_eq!(
+                            mmapper.mapped[c].load(Ordering::Relaxed),
+                            MapState::Unmapped
+                        );
+                    }
+
+                    // the chunks can be mapped again
+                    mmapper
+                        .ensure_mapped(FIXED_ADDRESS, pages_per_chunk * 2)
+                        .unwrap();
+                    assert!(mmapper.is_mapped_address(FIXED_ADDRESS));
+                },
+                || {
+                    memory::munmap(FIXED_ADDRESS, MAX_SIZE).unwrap();
+                },
+            )
+        })
+    }
+
     #[test]
     fn ensure_mapped_on_protected_chunks() {
         serial_test(|| {
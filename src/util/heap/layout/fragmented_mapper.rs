@@ -186,6 +186,31 @@ impl Mmapper for FragmentedMapper {
             start = high;
         }
     }
+
+    fn unmap(&self, mut start: Address, pages: usize) {
+        let end = start + conversions::pages_to_bytes(pages);
+        let _guard = self.lock.lock().unwrap();
+        // Iterate over the slabs covered
+        while start < end {
+            let base = Self::slab_align_down(start);
+            let high = if end > Self::slab_limit(start) && !Self::slab_limit(start).is_zero() {
+                Self::slab_limit(start)
+            } else {
+                end
+            };
+
+            // Nothing in a slab is mapped if the slab has not been allocated.
+            if let Some(mapped) = self.slab_table(start) {
+                let start_chunk = Self::chunk_index(base, start);
+                let end_chunk = Self::chunk_index(base, conversions::mmap_chunk_align_up(high));
+                for (chunk, entry) in mapped.iter().enumerate().take(end_chunk).skip(start_chunk) {
+                    let mmap_start = Self::chunk_index_to_address(base, chunk);
+                    MapState::transition_to_unmapped(entry, mmap_start).unwrap();
+                }
+            }
+            start = high;
+        }
+    }
 }
 
 impl FragmentedMapper {
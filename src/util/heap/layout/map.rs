@@ -55,4 +55,15 @@ pub trait Map: Sized {
     fn get_descriptor_for_address(&self, address: Address) -> SpaceDescriptor;
 
     fn add_to_cumulative_committed_pages(&self, pages: usize);
+
+    /// Forget all the spaces, so a new MMTk instance can create its spaces in the address range.
+    /// This is only called when an MMTk instance shuts down, after which its spaces are no longer
+    /// used.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure no other thread accesses the map during the reset, and that no
+    /// space, page resource or free list created with the map is used afterwards, as they still
+    /// refer to the old state.
+    unsafe fn reset(&self);
 }
@@ -250,6 +250,23 @@ impl Map for Map32 {
         self.cumulative_committed_pages
             .fetch_add(pages, Ordering::Relaxed);
     }
+
+    unsafe fn reset(&self) {
+        // Keep the lock, and only replace the state it protects, so the mutex is never
+        // overwritten while it is held.
+        let (_sync, self_mut) = self.mut_self_with_sync();
+        let fresh = Self::new();
+        self_mut.prev_link = fresh.prev_link;
+        self_mut.next_link = fresh.next_link;
+        self_mut.region_map = fresh.region_map;
+        self_mut.global_page_map = fresh.global_page_map;
+        self_mut.shared_discontig_fl_count = fresh.shared_discontig_fl_count;
+        self_mut.shared_fl_map = fresh.shared_fl_map;
+        self_mut.total_available_discontiguous_chunks = fresh.total_available_discontiguous_chunks;
+        self_mut.finalized = fresh.finalized;
+        self_mut.descriptor_map = fresh.descriptor_map;
+        self_mut.cumulative_committed_pages = fresh.cumulative_committed_pages;
+    }
 }
 
 impl Map32 {
@@ -216,6 +216,24 @@ impl Map for Map64 {
         self.cumulative_committed_pages
             .fetch_add(pages, Ordering::Relaxed);
     }
+
+    unsafe fn reset(&self) {
+        // The caller guarantees that no one else is using the map. As in Map32, only the state is
+        // replaced, in place, so the map itself (and the vectors it owns) is never overwritten.
+        let self_mut: &mut Self = self.mut_self();
+        for i in 0..MAX_SPACES {
+            let base = Address::from_usize(i << LOG_SPACE_SIZE_64);
+            self_mut.high_water[i] = base;
+            self_mut.base_address[i] = base;
+        }
+        self_mut.descriptor_map.fill(SpaceDescriptor::UNINITIALIZED);
+        self_mut.fl_page_resources.fill(None);
+        self_mut.fl_map.fill(None);
+        self_mut.finalized = false;
+        self_mut
+            .cumulative_committed_pages
+            .store(0, Ordering::Relaxed);
+    }
 }
 
 impl Map64 {
@@ -57,6 +57,15 @@ pub trait Mmapper {
     /// * `start`: Address of the first page to be protected
     /// * `pages`: Number of pages to be protected
     fn protect(&self, start: Address, pages: usize);
+
+    /// Unmap a range of pages that have been mapped, quarantined or protected, and mark them as
+    /// unmapped, so they can be mapped again. This is used when an MMTk instance shuts down.
+    /// Note that unmapping occurs at chunk granularity.
+    ///
+    /// Arguments:
+    /// * `start`: Address of the first page to be unmapped
+    /// * `pages`: Number of pages to be unmapped
+    fn unmap(&self, start: Address, pages: usize);
 }
 
 /// The mmap state of a mmap chunk.
@@ -122,6 +131,27 @@ impl MapState {
         res
     }
 
+    /// Check the current MapState of the chunk, and transition the chunk to MapState::Unmapped.
+    /// The caller should hold a lock before invoking this method.
+    pub(super) fn transition_to_unmapped(
+        state: &Atomic<MapState>,
+        mmap_start: Address,
+    ) -> Result<()> {
+        trace!(
+            "Trying to unmap {} - {}",
+            mmap_start,
+            mmap_start + MMAP_CHUNK_BYTES
+        );
+        let res = match state.load(Ordering::Relaxed) {
+            MapState::Unmapped => Ok(()),
+            _ => munmap(mmap_start, MMAP_CHUNK_BYTES),
+        };
+        if res.is_ok() {
+            state.store(MapState::Unmapped, Ordering::Relaxed);
+        }
+        res
+    }
+
     /// Check the current MapState of the chunk, and transition the chunk to MapState::Protected.
     /// The caller should hold a lock before invoking this method.
     pub(super) fn transition_to_protected(
@@ -1,12 +1,17 @@
 mod accounting;
+#[cfg(feature = "chunk_compression")]
+pub mod chunk_compression;
+pub mod chunk_map;
 #[macro_use]
 pub mod layout;
 pub mod freelistpageresource;
 mod heap_meta;
 pub mod monotonepageresource;
 pub mod pageresource;
+pub mod regions;
 pub mod space_descriptor;
 mod vmrequest;
+pub mod zeroed_block_pool;
 
 pub use self::accounting::PageAccounting;
 pub use self::freelistpageresource::FreeListPageResource;
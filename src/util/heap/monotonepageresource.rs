@@ -307,10 +307,12 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
     unsafe fn release_pages(&self, guard: &mut MutexGuard<MonotonePageResourceSync>) {
         // TODO: concurrent zeroing
         if self.common().contiguous {
-            guard.cursor = match guard.conditional {
+            let start = match guard.conditional {
                 MonotonePageResourceConditional::Contiguous { start: _start, .. } => _start,
                 _ => unreachable!(),
             };
+            self.release_pages_extent(start, guard.cursor - start);
+            guard.cursor = start;
         } else if !guard.cursor.is_zero() {
             let bytes = guard.cursor - guard.current_chunk;
             self.release_pages_extent(guard.current_chunk, bytes);
@@ -326,9 +328,10 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
         }
     }
 
-    fn release_pages_extent(&self, _first: Address, bytes: usize) {
+    fn release_pages_extent(&self, first: Address, bytes: usize) {
         let pages = crate::util::conversions::bytes_to_pages(bytes);
         debug_assert!(bytes == crate::util::conversions::pages_to_bytes(pages));
+        self.common.on_release(first, pages);
         // FIXME ZERO_PAGES_ON_RELEASE
         // FIXME Options.protectOnRelease
         // FIXME VM.events.tracePageReleased
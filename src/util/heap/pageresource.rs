@@ -1,7 +1,9 @@
 use crate::util::address::Address;
 use crate::util::conversions;
+use crate::util::memory;
 use crate::util::opaque_pointer::*;
 use crate::vm::ActivePlan;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
 use super::layout::map::Map;
@@ -83,6 +85,13 @@ pub trait PageResource<VM: VMBinding>: 'static {
         }
     }
 
+    /// Give back the pages of an allocation from `start` whose memory could not be mapped, so they
+    /// are no longer committed. The default implementation only releases the pages from the page
+    /// accounting, and their addresses are not allocated again.
+    fn release_unmapped_pages(&self, _start: Address, pages: usize) {
+        self.common().accounting.release(pages);
+    }
+
     fn reserved_pages(&self) -> usize {
         self.common().accounting.get_reserved_pages()
     }
@@ -127,6 +136,9 @@ pub struct CommonPageResource {
 
     pub vm_map: &'static VMMap,
     head_discontiguous_region: Mutex<Address>,
+    /// Give the memory of the pages released by this resource back to the OS, e.g. in the GC
+    /// after the heap is shrunk.
+    decommit_on_release: AtomicBool,
 }
 
 impl CommonPageResource {
@@ -139,6 +151,24 @@ impl CommonPageResource {
             vm_map,
 
             head_discontiguous_region: Mutex::new(Address::ZERO),
+            decommit_on_release: AtomicBool::new(false),
+        }
+    }
+
+    /// Set whether the pages released by this resource are given back to the OS.
+    pub fn set_decommit_on_release(&self, decommit: bool) {
+        self.decommit_on_release.store(decommit, Ordering::Relaxed);
+    }
+
+    /// Pages are released by this resource. Give their memory back to the OS if it is requested.
+    /// This must be called before the pages can be allocated again.
+    pub fn on_release(&self, start: Address, pages: usize) {
+        if pages == 0 || !self.decommit_on_release.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(e) = memory::decommit(start, conversions::pages_to_bytes(pages)) {
+            // The pages are still usable, we just keep their memory.
+            warn!("Failed to decommit {} pages at {}: {:?}", pages, start, e);
         }
     }
 
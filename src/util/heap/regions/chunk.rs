@@ -0,0 +1,38 @@
+use super::{PageAccountedRegion, Region};
+use crate::util::heap::layout::vm_layout_constants::LOG_BYTES_IN_CHUNK;
+use crate::util::metadata::side_metadata::SideMetadataSpec;
+use crate::util::Address;
+
+/// Data structure to reference a MMTk 4 MB chunk.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
+pub struct Chunk(Address);
+
+impl From<Address> for Chunk {
+    #[inline(always)]
+    fn from(address: Address) -> Chunk {
+        debug_assert!(address.is_aligned_to(Self::BYTES));
+        Self(address)
+    }
+}
+
+impl From<Chunk> for Address {
+    #[inline(always)]
+    fn from(chunk: Chunk) -> Address {
+        chunk.0
+    }
+}
+
+impl Region for Chunk {
+    const LOG_BYTES: usize = LOG_BYTES_IN_CHUNK;
+}
+
+impl PageAccountedRegion for Chunk {
+    const USED_PAGES_TABLE: SideMetadataSpec =
+        crate::util::metadata::side_metadata::spec_defs::CHUNK_USED_PAGES;
+}
+
+impl Chunk {
+    /// Chunk constant with zero address
+    pub const ZERO: Self = Self(Address::ZERO);
+}
@@ -0,0 +1,338 @@
+//! Regions are aligned memory ranges of a fixed size, such as chunks, blocks and lines. Region-based
+//! policies describe their regions with the [`Region`] trait, and get the address arithmetic, the
+//! iteration over regions and sub-regions, and the access to per-region side metadata from it.
+//! A region with a per-region state machine (e.g. a block that is unallocated, marked or reusable)
+//! can implement [`StatefulRegion`] to keep its state in a byte of side metadata. A region that
+//! policies allocate pages in (e.g. a chunk) can implement [`PageAccountedRegion`] to count its
+//! used pages, so a policy knows how many pages are free in the region without scanning it.
+
+mod chunk;
+
+pub use self::chunk::Chunk;
+
+use crate::util::constants::LOG_BYTES_IN_PAGE;
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::Address;
+use crate::util::ObjectReference;
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+use std::fmt::Debug;
+use std::sync::atomic::Ordering;
+
+/// Region represents a memory region with a properly aligned address as its start and a fixed size for the region.
+/// Region provides a set of utility methods, along with a RegionIterator that linearly scans at the step of a region.
+pub trait Region: Copy + PartialEq + PartialOrd + From<Address> + Into<Address> {
+    const LOG_BYTES: usize;
+    const BYTES: usize = 1 << Self::LOG_BYTES;
+
+    /// Align the address to the region.
+    #[inline(always)]
+    fn align(address: Address) -> Address {
+        address.align_down(Self::BYTES)
+    }
+    /// Check if an address is aligned to the region.
+    #[inline(always)]
+    fn is_aligned(address: Address) -> bool {
+        address.is_aligned_to(Self::BYTES)
+    }
+    /// Return the region that contains the address.
+    #[inline(always)]
+    fn containing_address(address: Address) -> Self {
+        Self::from(Self::align(address))
+    }
+    /// Return the start address of the region.
+    #[inline(always)]
+    fn start(&self) -> Address {
+        (*self).into()
+    }
+    /// Return the end address of the region. Note that the end address is not in the region.
+    #[inline(always)]
+    fn end(&self) -> Address {
+        self.start() + Self::BYTES
+    }
+    /// Return the next region after this one.
+    #[inline(always)]
+    fn next(&self) -> Self {
+        self.next_nth(1)
+    }
+    /// Return the next nth region after this one.
+    #[inline(always)]
+    fn next_nth(&self, n: usize) -> Self {
+        debug_assert!(self.start().as_usize() < usize::MAX - (n << Self::LOG_BYTES));
+        Self::from(self.start() + (n << Self::LOG_BYTES))
+    }
+    /// Return the region that contains the object (by its cell address).
+    #[inline(always)]
+    fn containing<VM: VMBinding>(object: ObjectReference) -> Self {
+        Self::from(VM::VMObjectModel::ref_to_address(object).align_down(Self::BYTES))
+    }
+    /// Return the larger region (e.g. the chunk of a block) that contains this region.
+    #[inline(always)]
+    fn enclosing<R: Region>(&self) -> R {
+        debug_assert!(R::LOG_BYTES >= Self::LOG_BYTES);
+        R::containing_address(self.start())
+    }
+    /// Iterate over the smaller regions (e.g. the blocks of a chunk) in this region.
+    #[inline(always)]
+    fn subregions<R: Region>(&self) -> RegionIterator<R> {
+        debug_assert!(R::LOG_BYTES <= Self::LOG_BYTES);
+        RegionIterator::new(R::from(self.start()), R::from(self.end()))
+    }
+
+    // Per-region side metadata. The spec should have one entry for each region, i.e. its
+    // `log_bytes_in_region` is `Self::LOG_BYTES`.
+
+    /// Load the side metadata of this region.
+    #[inline(always)]
+    fn load_metadata(&self, spec: &SideMetadataSpec, order: Ordering) -> usize {
+        debug_assert_eq!(spec.log_bytes_in_region, Self::LOG_BYTES);
+        side_metadata::load_atomic(spec, self.start(), order)
+    }
+    /// Store the side metadata of this region.
+    #[inline(always)]
+    fn store_metadata(&self, spec: &SideMetadataSpec, value: usize, order: Ordering) {
+        debug_assert_eq!(spec.log_bytes_in_region, Self::LOG_BYTES);
+        side_metadata::store_atomic(spec, self.start(), value, order)
+    }
+    /// Clear the side metadata for all the data in this region. Unlike the other metadata methods,
+    /// the spec does not need to be per region, e.g. this can clear the line marks in a block.
+    #[inline(always)]
+    fn bzero_metadata(&self, spec: &SideMetadataSpec) {
+        side_metadata::bzero_metadata(spec, self.start(), Self::BYTES)
+    }
+}
+
+pub struct RegionIterator<R: Region> {
+    current: R,
+    end: R,
+}
+
+impl<R: Region> RegionIterator<R> {
+    pub fn new(start: R, end: R) -> Self {
+        Self {
+            current: start,
+            end,
+        }
+    }
+}
+
+impl<R: Region> Iterator for RegionIterator<R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if self.current < self.end {
+            let ret = self.current;
+            self.current = self.current.next();
+            Some(ret)
+        } else {
+            None
+        }
+    }
+}
+
+/// The state of a region. It is encoded in a byte.
+pub trait RegionState: Copy + PartialEq + Debug + From<u8> + Into<u8> {}
+
+/// A region with a state, which is stored in a byte of side metadata for each region.
+pub trait StatefulRegion: Region {
+    type State: RegionState;
+
+    /// The side metadata spec for the region state. It needs 8 bits for each region.
+    const STATE_TABLE: SideMetadataSpec;
+
+    /// Get the state of the region.
+    #[inline(always)]
+    fn get_state(&self) -> Self::State {
+        let byte = self.load_metadata(&Self::STATE_TABLE, Ordering::SeqCst) as u8;
+        byte.into()
+    }
+
+    /// Set the state of the region.
+    #[inline(always)]
+    fn set_state(&self, state: Self::State) {
+        let byte: u8 = state.into();
+        self.store_metadata(&Self::STATE_TABLE, byte as usize, Ordering::SeqCst);
+    }
+
+    /// Atomically change the state of the region from `from` to `to`. Return false if the region
+    /// was not in the state `from`, e.g. another thread has changed the state.
+    #[inline(always)]
+    fn transition_state(&self, from: Self::State, to: Self::State) -> bool {
+        debug_assert_eq!(Self::STATE_TABLE.log_bytes_in_region, Self::LOG_BYTES);
+        let (from, to): (u8, u8) = (from.into(), to.into());
+        side_metadata::compare_exchange_atomic(
+            &Self::STATE_TABLE,
+            self.start(),
+            from as usize,
+            to as usize,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+    }
+}
+
+/// A region that counts its used pages in side metadata. The policies that allocate pages in the
+/// region (e.g. the blocks of Immix, or the pages of malloc'd objects in MallocSpace) report the
+/// pages they start and stop using.
+pub trait PageAccountedRegion: Region {
+    /// The side metadata spec for the page count. It needs enough bits to count all the pages in
+    /// a region.
+    const USED_PAGES_TABLE: SideMetadataSpec;
+
+    /// The number of pages in the region.
+    const PAGES: usize = Self::BYTES >> LOG_BYTES_IN_PAGE;
+
+    /// Get the number of used pages in the region.
+    #[inline(always)]
+    fn used_pages(&self) -> usize {
+        self.load_metadata(&Self::USED_PAGES_TABLE, Ordering::Relaxed)
+    }
+
+    /// Get the number of free pages in the region.
+    #[inline(always)]
+    fn free_pages(&self) -> usize {
+        Self::PAGES - self.used_pages()
+    }
+
+    /// Some pages in the region are used.
+    #[inline(always)]
+    fn add_used_pages(&self, pages: usize) {
+        debug_assert_eq!(Self::USED_PAGES_TABLE.log_bytes_in_region, Self::LOG_BYTES);
+        let old = side_metadata::fetch_add_atomic(
+            &Self::USED_PAGES_TABLE,
+            self.start(),
+            pages,
+            Ordering::Relaxed,
+        );
+        debug_assert!(old + pages <= Self::PAGES);
+    }
+
+    /// Some used pages in the region are free again. Return the number of used pages after this.
+    #[inline(always)]
+    fn release_used_pages(&self, pages: usize) -> usize {
+        debug_assert_eq!(Self::USED_PAGES_TABLE.log_bytes_in_region, Self::LOG_BYTES);
+        let old = side_metadata::fetch_sub_atomic(
+            &Self::USED_PAGES_TABLE,
+            self.start(),
+            pages,
+            Ordering::Relaxed,
+        );
+        debug_assert!(old >= pages);
+        old - pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::LOG_BYTES_IN_PAGE;
+
+    const PAGE_SIZE: usize = 1 << LOG_BYTES_IN_PAGE;
+
+    #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+    struct Page(Address);
+
+    impl From<Address> for Page {
+        #[inline(always)]
+        fn from(address: Address) -> Page {
+            debug_assert!(address.is_aligned_to(Self::BYTES));
+            Self(address)
+        }
+    }
+
+    impl From<Page> for Address {
+        #[inline(always)]
+        fn from(page: Page) -> Address {
+            page.0
+        }
+    }
+
+    impl Region for Page {
+        const LOG_BYTES: usize = LOG_BYTES_IN_PAGE as usize;
+    }
+
+    #[test]
+    fn test_region_methods() {
+        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
+        let addr4k1 = unsafe { Address::from_usize(PAGE_SIZE + 1) };
+
+        // align
+        debug_assert_eq!(Page::align(addr4k), addr4k);
+        debug_assert_eq!(Page::align(addr4k1), addr4k);
+        debug_assert!(Page::is_aligned(addr4k));
+        debug_assert!(!Page::is_aligned(addr4k1));
+
+        let page = Page::from(addr4k);
+        // start/end
+        debug_assert_eq!(page.start(), addr4k);
+        debug_assert_eq!(page.end(), addr4k + PAGE_SIZE);
+        // next
+        debug_assert_eq!(page.next().start(), addr4k + PAGE_SIZE);
+        debug_assert_eq!(page.next_nth(1).start(), addr4k + PAGE_SIZE);
+        debug_assert_eq!(page.next_nth(2).start(), addr4k + 2 * PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_region_iterator_normal() {
+        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
+        let page = Page::from(addr4k);
+        let end_page = page.next_nth(5);
+
+        let mut results = vec![];
+        let iter = RegionIterator::new(page, end_page);
+        for p in iter {
+            results.push(p);
+        }
+        debug_assert_eq!(
+            results,
+            vec![
+                page,
+                page.next_nth(1),
+                page.next_nth(2),
+                page.next_nth(3),
+                page.next_nth(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_region_iterator_same_start_end() {
+        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
+        let page = Page::from(addr4k);
+
+        let mut results = vec![];
+        let iter = RegionIterator::new(page, page);
+        for p in iter {
+            results.push(p);
+        }
+        debug_assert_eq!(results, vec![]);
+    }
+
+    #[test]
+    fn test_region_iterator_smaller_end() {
+        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
+        let page = Page::from(addr4k);
+        let end = Page::from(Address::ZERO);
+
+        let mut results = vec![];
+        let iter = RegionIterator::new(page, end);
+        for p in iter {
+            results.push(p);
+        }
+        debug_assert_eq!(results, vec![]);
+    }
+
+    #[test]
+    fn test_subregions_and_enclosing() {
+        let chunk = Chunk::from(unsafe { Address::from_usize(Chunk::BYTES) });
+        let pages: Vec<Page> = chunk.subregions::<Page>().collect();
+        debug_assert_eq!(pages.len(), Chunk::BYTES / PAGE_SIZE);
+        debug_assert_eq!(pages[0].start(), chunk.start());
+        debug_assert_eq!(pages.last().unwrap().end(), chunk.end());
+        debug_assert!(pages.iter().all(|p| p.enclosing::<Chunk>() == chunk));
+        debug_assert_eq!(
+            Page::containing_address(chunk.start() + PAGE_SIZE + 1),
+            pages[1]
+        );
+    }
+}
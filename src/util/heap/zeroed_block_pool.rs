@@ -0,0 +1,226 @@
+//! Pools of blocks that are acquired and zeroed ahead of time. A space that zeroes the memory it
+//! acquires (e.g. an Immix space or a copy space) zeroes each block on the allocation path, or in
+//! the pause if the block is acquired to copy objects into. If the option `zeroed_block_pool` is
+//! set, such a space keeps a pool of that many blocks, and `Space::acquire()` takes a block from
+//! the pool before it asks the page resource for new pages. The pools of all the spaces are
+//! refilled by a background packet on the GC workers after each GC (see
+//! [`RefillZeroedBlockPools`]), so the zeroing is done while the workers are otherwise idle. If the
+//! option `mutator_assist_bytes` is set, the blocks are zeroed one at a time by assist quanta
+//! instead (see [`RefillZeroedBlock`]), which the mutators perform as they allocate, and the idle
+//! workers perform too.
+//!
+//! The blocks in a pool are reserved and committed by their space. A pool is only refilled while
+//! the plan counts the pages of its space as used memory (a copy space that is a from-space is
+//! not refilled), and while the heap has room for the blocks. A pool does not keep its blocks
+//! once its space is collected: they are released with the space, or given back to its page
+//! resource.
+
+use crate::mmtk::MMTK;
+use crate::policy::space::Space;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::mutator_assist::AssistWork;
+use crate::util::opaque_pointer::VMThread;
+use crate::util::platform::with_thread_state;
+use crate::util::Address;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Is the current thread refilling a pool? The pages it acquires go to the pool, so they must not
+/// be taken from the pool, or counted as allocated.
+pub(crate) fn is_refilling() -> bool {
+    with_thread_state(|state| state.refilling_zeroed_block_pool.get())
+}
+
+fn set_refilling(refilling: bool) {
+    with_thread_state(|state| state.refilling_zeroed_block_pool.set(refilling))
+}
+
+/// The pre-zeroed blocks of a space.
+pub struct ZeroedBlockPool {
+    /// The pages of a block. Only the acquisitions of this many pages are served by the pool.
+    block_pages: usize,
+    blocks: Mutex<Vec<Address>>,
+    /// Is the pool refilled after a GC?
+    refillable: AtomicBool,
+}
+
+impl ZeroedBlockPool {
+    pub fn new(block_pages: usize) -> Self {
+        Self {
+            block_pages,
+            blocks: Mutex::new(vec![]),
+            refillable: AtomicBool::new(true),
+        }
+    }
+
+    /// Take a block from the pool for an acquisition of `pages` pages, if the pool has a block of
+    /// that size.
+    #[inline]
+    pub fn take(&self, pages: usize) -> Option<Address> {
+        if pages != self.block_pages || is_refilling() {
+            return None;
+        }
+        self.blocks.lock().unwrap().pop()
+    }
+
+    /// The number of blocks in the pool.
+    pub fn len(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+
+    /// The number of pages in the pool.
+    pub fn pages(&self) -> usize {
+        self.len() * self.block_pages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget the blocks in the pool. This must be called when the space releases all its pages
+    /// at once, as the blocks are released with them.
+    pub fn clear(&self) {
+        self.blocks.lock().unwrap().clear();
+    }
+
+    /// Take all the blocks from the pool, so the space can give their pages back.
+    pub fn drain(&self) -> Vec<Address> {
+        std::mem::take(&mut *self.blocks.lock().unwrap())
+    }
+
+    /// Set whether the pool is refilled after a GC. The pool must only be refilled while the
+    /// plan counts the pages of its space as used, otherwise the blocks exceed the heap size.
+    pub fn set_refillable(&self, refillable: bool) {
+        self.refillable.store(refillable, Ordering::Relaxed);
+    }
+
+    /// Is the pool refilled after a GC?
+    pub fn is_refillable(&self) -> bool {
+        self.refillable.load(Ordering::Relaxed)
+    }
+
+    /// Acquire blocks from the space until the pool has `target` blocks, or the heap has no room
+    /// for another block. Nothing is acquired if the pool is not refillable.
+    pub fn refill<VM: VMBinding>(
+        &self,
+        space: &dyn Space<VM>,
+        mmtk: &MMTK<VM>,
+        tls: VMThread,
+        target: usize,
+    ) {
+        if !self.is_refillable() {
+            return;
+        }
+        set_refilling(true);
+        while self.len() < target && self.heap_has_room(mmtk) {
+            let start = space.acquire(tls, self.block_pages);
+            if start.is_zero() {
+                break;
+            }
+            self.blocks.lock().unwrap().push(start);
+        }
+        set_refilling(false);
+    }
+
+    /// Does the heap have room for another block, without triggering a GC?
+    fn heap_has_room<VM: VMBinding>(&self, mmtk: &MMTK<VM>) -> bool {
+        let reserved = mmtk.plan.get_reserved_pages() + self.block_pages;
+        reserved < mmtk.plan.get_total_pages()
+    }
+}
+
+/// A background packet that refills the zeroed block pools of all the spaces to the size set by
+/// the option `zeroed_block_pool`.
+pub struct RefillZeroedBlockPools;
+
+impl<VM: VMBinding> GCWork<VM> for RefillZeroedBlockPools {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let target = *mmtk.options.zeroed_block_pool;
+        mmtk.plan.for_each_space(&mut |space| {
+            if let Some(pool) = space.common().zeroed_block_pool.as_ref() {
+                pool.refill(space, mmtk, worker.tls.0, target);
+            }
+        });
+    }
+}
+
+/// An assist quantum that acquires and zeroes one block for the pool of a space (see
+/// `util::mutator_assist`).
+pub struct RefillZeroedBlock<VM: VMBinding> {
+    mmtk: &'static MMTK<VM>,
+    /// The index of the space in the order of `Plan::for_each_space()`.
+    space_index: usize,
+}
+
+impl<VM: VMBinding> RefillZeroedBlock<VM> {
+    /// Add a quantum for each block that is missing from the pools after a GC, and start the
+    /// concurrent phase in which the mutators perform them.
+    pub fn add_quanta(mmtk: &'static MMTK<VM>) {
+        let target = *mmtk.options.zeroed_block_pool;
+        let assist = &mmtk.plan.base().mutator_assist;
+        let mut space_index = 0;
+        mmtk.plan.for_each_space(&mut |space| {
+            if let Some(pool) = space.common().zeroed_block_pool.as_ref() {
+                if pool.is_refillable() {
+                    for _ in pool.len()..target {
+                        assist.add(Box::new(RefillZeroedBlock { mmtk, space_index }));
+                    }
+                }
+            }
+            space_index += 1;
+        });
+        assist.begin(*mmtk.options.mutator_assist_bytes);
+    }
+}
+
+impl<VM: VMBinding> AssistWork for RefillZeroedBlock<VM> {
+    fn do_work(&mut self, tls: VMThread) {
+        let target = *self.mmtk.options.zeroed_block_pool;
+        let mut space_index = 0;
+        self.mmtk.plan.for_each_space(&mut |space| {
+            if space_index == self.space_index {
+                if let Some(pool) = space.common().zeroed_block_pool.as_ref() {
+                    pool.refill(space, self.mmtk, tls, usize::min(pool.len() + 1, target));
+                }
+            }
+            space_index += 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let pool = ZeroedBlockPool::new(8);
+        let block = unsafe { Address::from_usize(0x1000_0000) };
+        pool.blocks.lock().unwrap().push(block);
+        // Only an acquisition of a block is served by the pool.
+        assert_eq!(pool.take(16), None);
+        // The refilling thread does not take blocks from the pool.
+        set_refilling(true);
+        assert_eq!(pool.take(8), None);
+        set_refilling(false);
+        assert_eq!(pool.take(8), Some(block));
+        assert!(pool.is_empty());
+        assert_eq!(pool.take(8), None);
+    }
+
+    #[test]
+    fn test_drain() {
+        let pool = ZeroedBlockPool::new(8);
+        let blocks = unsafe {
+            [
+                Address::from_usize(0x1000_0000),
+                Address::from_usize(0x1000_8000),
+            ]
+        };
+        pool.blocks.lock().unwrap().extend_from_slice(&blocks);
+        assert_eq!(pool.drain(), blocks.to_vec());
+        assert!(pool.is_empty());
+        assert_eq!(pool.take(8), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Snapshots of the alloc bits and the mark bits of an address range, for external tools such as
+//! debuggers and memory visualizers. A tool can render the occupancy of the heap from a snapshot
+//! without walking the objects. A snapshot can be serialized to a compact byte format with
+//! [`HeapBitmap::to_bytes`], and read back with [`HeapBitmap::from_bytes`].
+//!
+//! The format is little-endian:
+//! ```text
+//! magic:                 8 bytes, "MMTKHBM1"
+//! start:                 u64, the start address of the range
+//! log_bytes_in_granule:  u64
+//! granules:              u64, the number of granules in the range
+//! alloc bits:            (granules + 7) / 8 bytes
+//! mark bits:             (granules + 7) / 8 bytes
+//! ```
+//! Bit `i % 8` of byte `i / 8` of each bitmap is for the granule at
+//! `start + (i << log_bytes_in_granule)`.
+
+use crate::mmtk::SFT_MAP;
+use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
+use crate::util::Address;
+
+const MAGIC: &[u8; 8] = b"MMTKHBM1";
+const HEADER_BYTES: usize = MAGIC.len() + 3 * 8;
+
+/// A snapshot of the alloc bits and the mark bits of an address range. Each bit is for a granule
+/// of the range, which is the minimum object size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeapBitmap {
+    /// The start address of the range.
+    pub start: Address,
+    /// log2 of the bytes of a granule.
+    pub log_bytes_in_granule: usize,
+    /// The number of granules in the range.
+    pub granules: usize,
+    /// Is there an object at each granule?
+    alloc_bits: Vec<u8>,
+    /// Did the last GC find the object at each granule live? This is only set for granules with
+    /// objects.
+    mark_bits: Vec<u8>,
+}
+
+impl HeapBitmap {
+    /// Create an empty snapshot. The range is aligned to granules.
+    fn new(start: Address, end: Address, log_bytes_in_granule: usize) -> Self {
+        let granule: usize = 1 << log_bytes_in_granule;
+        let start = start.align_down(granule);
+        let end = end.align_up(granule);
+        let granules = (end - start) >> log_bytes_in_granule;
+        let bytes = (granules + 7) / 8;
+        Self {
+            start,
+            log_bytes_in_granule,
+            granules,
+            alloc_bits: vec![0; bytes],
+            mark_bits: vec![0; bytes],
+        }
+    }
+
+    /// Take a snapshot of the range. The chunks that are not in any space are skipped.
+    pub(crate) fn capture(start: Address, end: Address) -> Self {
+        let mut bitmap = Self::new(
+            start,
+            end,
+            crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC.log_bytes_in_region,
+        );
+        let granule: usize = 1 << bitmap.log_bytes_in_granule;
+        let mut index = 0;
+        while index < bitmap.granules {
+            let address = bitmap.address_of(index);
+            let chunk_end = (address + 1usize).align_up(BYTES_IN_CHUNK);
+            let chunk_granules = (chunk_end - address) >> bitmap.log_bytes_in_granule;
+            let next = usize::min(index + chunk_granules, bitmap.granules);
+            let sft = SFT_MAP.get(address);
+            if sft.name() != crate::policy::space::EMPTY_SFT_NAME {
+                let mut cursor = address;
+                for i in index..next {
+                    if sft.is_mmtk_object(cursor) {
+                        let object = unsafe { cursor.to_object_reference() };
+                        bitmap.set(i, true, sft.is_reachable(object));
+                    }
+                    cursor += granule;
+                }
+            }
+            index = next;
+        }
+        bitmap
+    }
+
+    /// The address of a granule.
+    pub fn address_of(&self, index: usize) -> Address {
+        self.start + (index << self.log_bytes_in_granule)
+    }
+
+    fn set(&mut self, index: usize, allocated: bool, marked: bool) {
+        let bit = 1 << (index % 8);
+        if allocated {
+            self.alloc_bits[index / 8] |= bit;
+        }
+        if marked {
+            self.mark_bits[index / 8] |= bit;
+        }
+    }
+
+    /// Is there an object at the granule?
+    pub fn is_allocated(&self, index: usize) -> bool {
+        self.alloc_bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Did the last GC find the object at the granule live?
+    pub fn is_marked(&self, index: usize) -> bool {
+        self.mark_bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Serialize the snapshot in the format described in the module documentation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_BYTES + self.alloc_bits.len() * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.start.as_usize() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.log_bytes_in_granule as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.granules as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.alloc_bits);
+        bytes.extend_from_slice(&self.mark_bits);
+        bytes
+    }
+
+    /// Read a snapshot serialized by [`HeapBitmap::to_bytes`]. Return `None` if the bytes are not
+    /// a valid snapshot.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_BYTES || &bytes[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let read_u64 = |offset: usize| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(word) as usize
+        };
+        let start = unsafe { Address::from_usize(read_u64(MAGIC.len())) };
+        let log_bytes_in_granule = read_u64(MAGIC.len() + 8);
+        let granules = read_u64(MAGIC.len() + 16);
+        let bitmap_bytes = (granules + 7) / 8;
+        if bytes.len() != HEADER_BYTES + bitmap_bytes * 2 {
+            return None;
+        }
+        let bitmaps = &bytes[HEADER_BYTES..];
+        Some(Self {
+            start,
+            log_bytes_in_granule,
+            granules,
+            alloc_bits: bitmaps[..bitmap_bytes].to_vec(),
+            mark_bits: bitmaps[bitmap_bytes..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let start = unsafe { Address::from_usize(0x1000_0004) };
+        let end = start + 108usize;
+        let mut bitmap = HeapBitmap::new(start, end, 3);
+        assert_eq!(bitmap.start, unsafe { Address::from_usize(0x1000_0000) });
+        assert_eq!(bitmap.granules, 14);
+        bitmap.set(0, true, true);
+        bitmap.set(9, true, false);
+        bitmap.set(13, true, true);
+
+        let bytes = bitmap.to_bytes();
+        assert_eq!(bytes.len(), HEADER_BYTES + 4);
+        let read = HeapBitmap::from_bytes(&bytes).unwrap();
+        assert_eq!(read, bitmap);
+        assert!(read.is_allocated(9) && !read.is_marked(9));
+        assert!(read.is_allocated(13) && read.is_marked(13));
+        assert!(!read.is_allocated(1));
+        assert_eq!(read.address_of(9), bitmap.start + 72usize);
+
+        assert!(HeapBitmap::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(HeapBitmap::from_bytes(b"not a bitmap").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+//! Heap ids for runtimes that run several isolates (e.g. JavaScript or Dart isolates) on one MMTk
+//! instance. Each mutator allocates into a heap, which is heap 0 unless the binding sets another
+//! with [`memory_manager::set_mutator_heap_id`](crate::memory_manager::set_mutator_heap_id).
+//! `post_alloc` tags each object with the heap of its mutator in side metadata, and the tag is kept
+//! when the object is moved. The binding can read it with
+//! [`memory_manager::object_heap_id`](crate::memory_manager::object_heap_id). Like the other
+//! per-object metadata, the tag is only set for objects whose allocation is followed by
+//! `post_alloc`.
+//!
+//! Heap 0 is the shared heap: its objects may refer to the objects of any heap, and be referred to
+//! by them. The objects of two other heaps must not refer to each other. Debug builds check the
+//! edges of each object that a GC scans as they are enqueued, and panic if an object of a heap
+//! refers to an object of another heap. The check loads every edge, so it is not done in release
+//! builds.
+//!
+//! The heaps share the spaces of the plan, and a GC usually collects all the heaps. A binding can
+//! ask for a GC of only the objects of a heap other than the shared heap with
+//! [`memory_manager::handle_user_heap_collection_request`](crate::memory_manager::handle_user_heap_collection_request),
+//! if the plan supports it (see [`crate::plan::CollectionScope::of_heap`]). The objects of the
+//! other heaps are not collected, and their references into the heap are roots, so the plan must
+//! remember them: MarkSweep does with the option `remember_heap_references`, which needs the
+//! binding to report the stores of references with the write barrier. Such a GC still stops the
+//! mutators of all the heaps.
+
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use std::sync::atomic::Ordering;
+
+pub(crate) const HEAP_ID_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::HEAP_ID;
+
+/// The shared heap. Its objects may refer to, and be referred to by, the objects of any heap.
+pub const SHARED_HEAP: u8 = 0;
+
+/// Tag a newly allocated object with its heap.
+#[inline(always)]
+pub fn set_heap_id(object: ObjectReference, heap_id: u8) {
+    side_metadata::store_atomic(
+        &HEAP_ID_SIDE_METADATA_SPEC,
+        object.to_address(),
+        heap_id as usize,
+        Ordering::Relaxed,
+    );
+}
+
+/// Get the heap of an object.
+#[inline(always)]
+pub fn get_heap_id(object: ObjectReference) -> u8 {
+    side_metadata::load_atomic(
+        &HEAP_ID_SIDE_METADATA_SPEC,
+        object.to_address(),
+        Ordering::Relaxed,
+    ) as u8
+}
+
+/// Keep the heap of an object when the object is moved.
+#[inline(always)]
+pub fn copy_heap_id(from: ObjectReference, to: ObjectReference) {
+    set_heap_id(to, get_heap_id(from));
+}
+
+/// May an object of heap `from` refer to an object of heap `to`?
+#[inline(always)]
+pub fn may_refer(from: u8, to: u8) -> bool {
+    from == to || from == SHARED_HEAP || to == SHARED_HEAP
+}
+
+/// Check that `object` of heap `heap_id` may refer to `target`. In debug builds, this is done for
+/// the edges of each object that a GC scans (see `ObjectsClosure::set_source()`).
+#[cfg(debug_assertions)]
+pub(crate) fn check_reference(object: ObjectReference, heap_id: u8, target: ObjectReference) {
+    if target.is_null() || !target.is_in_any_space() {
+        return;
+    }
+    let target_heap_id = get_heap_id(target);
+    assert!(
+        may_refer(heap_id, target_heap_id),
+        "Object {} of heap {} refers to object {} of heap {}",
+        object,
+        heap_id,
+        target,
+        target_heap_id
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::BYTES_IN_PAGE;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+    use crate::util::Address;
+
+    fn object(addr: usize) -> ObjectReference {
+        unsafe { Address::from_usize(addr).to_object_reference() }
+    }
+
+    #[test]
+    fn test_heap_id() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![HEAP_ID_SIDE_METADATA_SPEC],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_PAGE)
+                        .unwrap();
+                    let shared = object(HEAP_START.as_usize());
+                    let isolate = object(HEAP_START.as_usize() + 16);
+                    // The objects are in the shared heap until they are tagged.
+                    assert_eq!(get_heap_id(shared), SHARED_HEAP);
+                    set_heap_id(isolate, 3);
+                    assert_eq!(get_heap_id(isolate), 3);
+                    assert_eq!(get_heap_id(shared), SHARED_HEAP);
+
+                    // The tag moves with the object.
+                    let moved = object(HEAP_START.as_usize() + 32);
+                    copy_heap_id(isolate, moved);
+                    assert_eq!(get_heap_id(moved), 3);
+                    // Each object keeps its own tag, up to the largest heap id.
+                    set_heap_id(shared, u8::MAX);
+                    assert_eq!(get_heap_id(shared), u8::MAX);
+                    assert_eq!(get_heap_id(moved), 3);
+                },
+                || metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_PAGE),
+            )
+        })
+    }
+
+    #[test]
+    fn test_may_refer() {
+        assert!(may_refer(1, 1));
+        assert!(may_refer(1, SHARED_HEAP));
+        assert!(may_refer(SHARED_HEAP, 2));
+        assert!(!may_refer(1, 2));
+    }
+}
@@ -0,0 +1,213 @@
+//! A timeline of the heap occupancy. If the option `heap_timeline` is set, MMTk samples the
+//! committed and the used bytes of each space at the end of every GC, so a runtime can chart the
+//! heap usage over time. Only the samples of the latest GCs are kept (see the option
+//! `heap_timeline_gcs`), so the timeline does not grow without bound. The samples can be
+//! retrieved with [`memory_manager::heap_timeline`](crate::memory_manager::heap_timeline), or
+//! written as CSV with
+//! [`memory_manager::write_heap_timeline_csv`](crate::memory_manager::write_heap_timeline_csv).
+//!
+//! If the option `heap_timeline_resident` is also set, the samples include the bytes of each space
+//! that are resident in physical memory, which are found with `mincore()` (see
+//! [`Platform::resident_bytes`](crate::util::platform::Platform::resident_bytes)). This tells how
+//! much of the RSS of the process is the heap, and which spaces it is in, e.g. when the committed
+//! heap is much larger than the used heap, and the pages that are no longer used are still
+//! resident.
+
+use crate::policy::space::Space;
+use crate::util::constants::LOG_BYTES_IN_PAGE;
+use crate::util::memory;
+use crate::vm::VMBinding;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The occupancy of a space at the end of a GC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeapTimelineSample {
+    /// The number of GCs that have finished, including the GC at the end of which the sample was
+    /// taken.
+    pub gc: u64,
+    /// The time of the sample, in nanoseconds since the MMTk instance was created.
+    pub time_ns: u64,
+    /// The name of the space.
+    pub space: &'static str,
+    /// The bytes of the chunks that the space has acquired and mapped.
+    pub committed_bytes: u64,
+    /// The bytes that the space uses, including its side metadata.
+    pub used_bytes: u64,
+    /// The bytes of the committed chunks that are resident in physical memory. This is `None` if
+    /// the option `heap_timeline_resident` is not set, or the platform cannot tell.
+    pub resident_bytes: Option<u64>,
+}
+
+impl HeapTimelineSample {
+    /// The fraction of the committed bytes that are not used, between 0 and 1.
+    pub fn fragmentation(&self) -> f64 {
+        if self.committed_bytes == 0 {
+            0.0
+        } else {
+            self.committed_bytes.saturating_sub(self.used_bytes) as f64
+                / self.committed_bytes as f64
+        }
+    }
+}
+
+/// The samples of the heap occupancy taken at the end of the latest GCs.
+pub struct HeapTimeline {
+    start: Instant,
+    /// The number of the latest GCs whose samples are kept.
+    max_gcs: u64,
+    samples: Mutex<VecDeque<HeapTimelineSample>>,
+}
+
+impl HeapTimeline {
+    pub fn new(max_gcs: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            max_gcs: max_gcs as u64,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sample the spaces at the end of the `gc`-th GC. The resident bytes are sampled if
+    /// `resident` is true.
+    pub(crate) fn record<VM: VMBinding>(
+        &self,
+        gc: u64,
+        spaces: Vec<&dyn Space<VM>>,
+        resident: bool,
+    ) {
+        let time_ns = self.start.elapsed().as_nanos() as u64;
+        let new_samples = spaces.iter().map(|space| {
+            let ranges = space.get_acquired_ranges();
+            HeapTimelineSample {
+                gc,
+                time_ns,
+                space: space.get_name(),
+                committed_bytes: ranges
+                    .iter()
+                    .map(|(start, end)| (*end - *start) as u64)
+                    .sum(),
+                used_bytes: (space.reserved_pages() << LOG_BYTES_IN_PAGE) as u64,
+                resident_bytes: if resident {
+                    ranges
+                        .iter()
+                        .map(|(start, end)| {
+                            memory::resident_bytes(*start, *end - *start).map(|b| b as u64)
+                        })
+                        .sum()
+                } else {
+                    None
+                },
+            }
+        });
+        let mut samples = self.samples.lock().unwrap();
+        samples.extend(new_samples);
+        self.discard_old_samples(&mut samples, gc);
+    }
+
+    /// Discard the samples of the GCs before the latest `max_gcs` GCs.
+    fn discard_old_samples(&self, samples: &mut VecDeque<HeapTimelineSample>, gc: u64) {
+        while samples
+            .front()
+            .map_or(false, |sample| sample.gc + self.max_gcs <= gc)
+        {
+            samples.pop_front();
+        }
+    }
+
+    /// The samples kept so far, in the order they were taken.
+    pub(crate) fn samples(&self) -> Vec<HeapTimelineSample> {
+        self.samples.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Write the samples as CSV, with a header line.
+    pub(crate) fn write_csv(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "gc,time_ns,space,committed_bytes,used_bytes,fragmentation,resident_bytes"
+        )?;
+        for sample in self.samples.lock().unwrap().iter() {
+            // The resident bytes are left empty if they were not sampled.
+            let resident_bytes = sample
+                .resident_bytes
+                .map_or(String::new(), |bytes| bytes.to_string());
+            writeln!(
+                out,
+                "{},{},{},{},{},{:.4},{}",
+                sample.gc,
+                sample.time_ns,
+                sample.space,
+                sample.committed_bytes,
+                sample.used_bytes,
+                sample.fragmentation(),
+                resident_bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let timeline = HeapTimeline::new(2);
+        timeline
+            .samples
+            .lock()
+            .unwrap()
+            .push_back(HeapTimelineSample {
+                gc: 1,
+                time_ns: 100,
+                space: "immix",
+                committed_bytes: 4096,
+                used_bytes: 1024,
+                resident_bytes: None,
+            });
+        timeline
+            .samples
+            .lock()
+            .unwrap()
+            .push_back(HeapTimelineSample {
+                gc: 2,
+                time_ns: 200,
+                space: "immix",
+                committed_bytes: 4096,
+                used_bytes: 2048,
+                resident_bytes: Some(3072),
+            });
+        assert_eq!(timeline.samples()[0].fragmentation(), 0.75);
+        let mut out = vec![];
+        timeline.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "gc,time_ns,space,committed_bytes,used_bytes,fragmentation,resident_bytes\n1,100,immix,4096,1024,0.7500,\n2,200,immix,4096,2048,0.5000,3072\n"
+        );
+    }
+
+    #[test]
+    fn test_discard_old_samples() {
+        let timeline = HeapTimeline::new(2);
+        let sample = |gc| HeapTimelineSample {
+            gc,
+            time_ns: 0,
+            space: "immix",
+            committed_bytes: 0,
+            used_bytes: 0,
+            resident_bytes: None,
+        };
+        let mut samples = timeline.samples.lock().unwrap();
+        for gc in 1..=5 {
+            samples.push_back(sample(gc));
+            samples.push_back(sample(gc));
+            timeline.discard_old_samples(&mut samples, gc);
+            // Only the samples of the latest two GCs are kept.
+            assert_eq!(samples.len(), 2 * std::cmp::min(gc, 2) as usize);
+            assert!(samples.iter().all(|s| s.gc + 2 > gc));
+        }
+    }
+}
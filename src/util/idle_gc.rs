@@ -0,0 +1,86 @@
+//! Idle-time GC scheduling. A binding that knows it is going to be idle for a while (e.g. an event
+//! loop waiting for the next frame or request) can tell MMTk with
+//! [`memory_manager::notify_idle`](crate::memory_manager::notify_idle). MMTk then uses the idle
+//! period for a GC if the GC is expected to fit in it, so a GC is less likely to interrupt the
+//! busy periods later. The expected pause is the average pause of the previous GCs: a full heap GC
+//! is done if the period is long enough for one, otherwise the plan decides what kind of GC to do
+//! (e.g. a nursery GC for a generational plan). MMTk does not collect if few objects have been
+//! allocated since the last GC, as the GC would not find much garbage.
+//!
+//! Low-priority background work (e.g. lazy sweeping, see
+//! `memory_manager::add_background_work_packet`) already runs whenever the GC workers are idle, so
+//! it does not need the notification.
+
+use std::time::Duration;
+
+/// A GC is only done in an idle period if the mutators have allocated at least this fraction of
+/// the heap size since the last GC.
+const MIN_ALLOCATION_FRACTION: f64 = 1.0 / 16.0;
+
+/// What MMTk does in an idle period.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IdleGCAction {
+    /// Nothing. The period is too short for a GC, there is not enough garbage, or a GC cannot be
+    /// triggered now.
+    Nothing,
+    /// A GC is triggered, and the plan decides what kind of GC to do.
+    Collection,
+    /// A full heap GC is triggered.
+    FullHeapCollection,
+}
+
+/// What the GC statistics tell about the next GC.
+pub(crate) struct IdleGCEstimate {
+    /// The average pause of the previous GCs, if any.
+    pub average_pause: Option<Duration>,
+    /// The average pause of the previous full heap GCs, if any.
+    pub average_full_heap_pause: Option<Duration>,
+    /// The bytes allocated since the last GC.
+    pub allocated_bytes: u64,
+    /// The heap size in bytes.
+    pub heap_size_bytes: u64,
+}
+
+impl IdleGCEstimate {
+    /// Decide what to do in an idle period of the given length.
+    pub fn decide(&self, idle: Duration) -> IdleGCAction {
+        if (self.allocated_bytes as f64) < self.heap_size_bytes as f64 * MIN_ALLOCATION_FRACTION {
+            return IdleGCAction::Nothing;
+        }
+        let fits = |pause: Option<Duration>| pause.map_or(false, |pause| pause <= idle);
+        if fits(self.average_full_heap_pause) {
+            IdleGCAction::FullHeapCollection
+        } else if fits(self.average_pause) {
+            IdleGCAction::Collection
+        } else {
+            IdleGCAction::Nothing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let ms = Duration::from_millis;
+        let mut estimate = IdleGCEstimate {
+            average_pause: Some(ms(2)),
+            average_full_heap_pause: Some(ms(10)),
+            allocated_bytes: 1 << 20,
+            heap_size_bytes: 1 << 24,
+        };
+        assert_eq!(estimate.decide(ms(1)), IdleGCAction::Nothing);
+        assert_eq!(estimate.decide(ms(5)), IdleGCAction::Collection);
+        assert_eq!(estimate.decide(ms(10)), IdleGCAction::FullHeapCollection);
+        // Not enough allocation since the last GC.
+        estimate.allocated_bytes = (1 << 20) - 1;
+        assert_eq!(estimate.decide(ms(10)), IdleGCAction::Nothing);
+        // No GC so far: the pause is unknown.
+        estimate.allocated_bytes = 1 << 20;
+        estimate.average_pause = None;
+        estimate.average_full_heap_pause = None;
+        assert_eq!(estimate.decide(ms(100)), IdleGCAction::Nothing);
+    }
+}
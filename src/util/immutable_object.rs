@@ -0,0 +1,145 @@
+//! Objects that the binding declares immutable with
+//! [`memory_manager::set_object_immutable`](crate::memory_manager::set_object_immutable), once
+//! their reference fields are initialized. The reference fields of an immutable object never
+//! change, so once a GC has scanned the object, its children have been traced, and, in a
+//! generational plan, promoted to the mature space. The object will not refer to a nursery object
+//! again, so the nursery GCs after that skip scanning it, e.g. when it is in the modified object
+//! buffer because it was written before it became immutable, or when a nursery GC scans the mature
+//! objects pointed to by the roots.
+//!
+//! The immutable objects are still scanned in each full-heap GC, as their children are only kept
+//! alive by being traced. The state of an object is kept in two bits of side metadata per
+//! `MIN_OBJECT_SIZE` bytes, which `post_alloc` clears and which are kept when the object is moved.
+
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use std::sync::atomic::Ordering;
+
+pub(crate) const IMMUTABLE_OBJECT_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::IMMUTABLE_OBJECT;
+
+/// The object is immutable.
+const IMMUTABLE: u8 = 0b01;
+/// The object has been scanned by a GC since it became immutable.
+const SCANNED: u8 = 0b10;
+
+#[inline(always)]
+fn get_state(object: ObjectReference) -> u8 {
+    side_metadata::load_atomic(
+        &IMMUTABLE_OBJECT_SIDE_METADATA_SPEC,
+        object.to_address(),
+        Ordering::Relaxed,
+    ) as u8
+}
+
+#[inline(always)]
+fn set_state(object: ObjectReference, state: u8) {
+    side_metadata::store_atomic(
+        &IMMUTABLE_OBJECT_SIDE_METADATA_SPEC,
+        object.to_address(),
+        state as usize,
+        Ordering::Relaxed,
+    );
+}
+
+/// Clear the state of a newly allocated object, which is mutable.
+#[inline(always)]
+pub fn clear_state(object: ObjectReference) {
+    set_state(object, 0);
+}
+
+/// Declare the object immutable.
+#[inline(always)]
+pub fn set_immutable(object: ObjectReference) {
+    if get_state(object) & IMMUTABLE == 0 {
+        set_state(object, IMMUTABLE);
+    }
+}
+
+/// Is the object immutable?
+#[inline(always)]
+pub fn is_immutable(object: ObjectReference) -> bool {
+    get_state(object) & IMMUTABLE != 0
+}
+
+/// Keep the state of an object when the object is moved.
+#[inline(always)]
+pub fn copy_state(from: ObjectReference, to: ObjectReference) {
+    set_state(to, get_state(from));
+}
+
+/// Called before a GC scans an object. Return true if the scanning can be skipped, i.e. the
+/// object is immutable, it has been scanned in an earlier GC, and this is a nursery GC. Otherwise,
+/// the object is scanned, and an immutable object is recorded as scanned.
+#[inline(always)]
+pub(crate) fn skip_scanning(object: ObjectReference, is_nursery_gc: bool) -> bool {
+    let state = get_state(object);
+    if state & IMMUTABLE == 0 {
+        return false;
+    }
+    if state & SCANNED != 0 {
+        return is_nursery_gc;
+    }
+    // Only one of the workers that may scan the object at the same time needs to set the bit.
+    set_state(object, IMMUTABLE | SCANNED);
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::BYTES_IN_PAGE;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+    use crate::util::Address;
+
+    fn object(addr: usize) -> ObjectReference {
+        unsafe { Address::from_usize(addr).to_object_reference() }
+    }
+
+    #[test]
+    fn test_skip_scanning() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![IMMUTABLE_OBJECT_SIDE_METADATA_SPEC],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_PAGE)
+                        .unwrap();
+                    let mutable = object(HEAP_START.as_usize());
+                    let immutable = object(HEAP_START.as_usize() + 16);
+                    clear_state(mutable);
+                    clear_state(immutable);
+                    set_immutable(immutable);
+                    assert!(!is_immutable(mutable));
+                    assert!(is_immutable(immutable));
+
+                    // Mutable objects are always scanned.
+                    assert!(!skip_scanning(mutable, false));
+                    assert!(!skip_scanning(mutable, true));
+                    // An immutable object is scanned the first time, even in a nursery GC.
+                    assert!(!skip_scanning(immutable, true));
+                    // Later, only the full-heap GCs scan it.
+                    assert!(skip_scanning(immutable, true));
+                    assert!(!skip_scanning(immutable, false));
+                    // Declaring it immutable again does not forget that it has been scanned.
+                    set_immutable(immutable);
+                    assert!(skip_scanning(immutable, true));
+
+                    // The state moves with the object.
+                    let moved = object(HEAP_START.as_usize() + 32);
+                    copy_state(immutable, moved);
+                    assert!(skip_scanning(moved, true));
+                    // A new object at the address is mutable.
+                    clear_state(immutable);
+                    assert!(!is_immutable(immutable));
+                },
+                || metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_PAGE),
+            )
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use crate::util::alloc::object_ref_guard;
 use crate::util::alloc_bit;
 use crate::util::Address;
 use crate::util::ObjectReference;
@@ -5,6 +6,10 @@ use crate::vm::ObjectModel;
 use crate::vm::VMBinding;
 use std::marker::PhantomData;
 
+// The regions have moved to `util::heap::regions`. They are still available here for the code
+// that uses the old paths.
+pub use crate::util::heap::regions::{Region, RegionIterator};
+
 /// Iterate over an address range, and find each object by alloc bit.
 /// ATOMIC_LOAD_ALLOC_BIT can be set to false if it is known that loading alloc bit
 /// non-atomically is correct (e.g. a single thread is scanning this address range, and
@@ -48,7 +53,12 @@ impl<VM: VMBinding, S: LinearScanObjectSize, const ATOMIC_LOAD_ALLOC_BIT: bool>
 
             if is_object {
                 let object = unsafe { self.cursor.to_object_reference() };
-                self.cursor += S::size(object);
+                // The object reference may point past the start of the object, and the offset may
+                // differ between objects, so the next object may only be after the end of this one.
+                let start = VM::VMObjectModel::object_start_ref(object);
+                let size = S::size(object);
+                object_ref_guard::debug_check_object_start::<VM>(object, start, size);
+                self.cursor = (start + size).max(self.cursor + VM::MIN_ALIGNMENT);
                 return Some(object);
             } else {
                 self.cursor += VM::MIN_ALIGNMENT;
@@ -60,7 +70,8 @@ impl<VM: VMBinding, S: LinearScanObjectSize, const ATOMIC_LOAD_ALLOC_BIT: bool>
 }
 
 /// Describe object size for linear scan. Different policies may have
-/// different object sizes (e.g. extra metadata, etc)
+/// different object sizes (e.g. extra metadata, etc). The size is counted from
+/// `ObjectModel::object_start_ref`, not from the object reference.
 pub trait LinearScanObjectSize {
     fn size(object: ObjectReference) -> usize;
 }
@@ -73,176 +84,3 @@ impl<VM: VMBinding> LinearScanObjectSize for DefaultObjectSize<VM> {
         VM::VMObjectModel::get_current_size(object)
     }
 }
-
-/// Region represents a memory region with a properly aligned address as its start and a fixed size for the region.
-/// Region provides a set of utility methods, along with a RegionIterator that linearly scans at the step of a region.
-pub trait Region: Copy + PartialEq + PartialOrd + From<Address> + Into<Address> {
-    const LOG_BYTES: usize;
-    const BYTES: usize = 1 << Self::LOG_BYTES;
-
-    /// Align the address to the region.
-    #[inline(always)]
-    fn align(address: Address) -> Address {
-        address.align_down(Self::BYTES)
-    }
-    /// Check if an address is aligned to the region.
-    #[inline(always)]
-    fn is_aligned(address: Address) -> bool {
-        address.is_aligned_to(Self::BYTES)
-    }
-    /// Return the start address of the region.
-    #[inline(always)]
-    fn start(&self) -> Address {
-        (*self).into()
-    }
-    /// Return the end address of the region. Note that the end address is not in the region.
-    #[inline(always)]
-    fn end(&self) -> Address {
-        self.start() + Self::BYTES
-    }
-    /// Return the next region after this one.
-    #[inline(always)]
-    fn next(&self) -> Self {
-        self.next_nth(1)
-    }
-    /// Return the next nth region after this one.
-    #[inline(always)]
-    fn next_nth(&self, n: usize) -> Self {
-        debug_assert!(self.start().as_usize() < usize::MAX - (n << Self::LOG_BYTES));
-        Self::from(self.start() + (n << Self::LOG_BYTES))
-    }
-    /// Return the region that contains the object (by its cell address).
-    #[inline(always)]
-    fn containing<VM: VMBinding>(object: ObjectReference) -> Self {
-        Self::from(VM::VMObjectModel::ref_to_address(object).align_down(Self::BYTES))
-    }
-}
-
-pub struct RegionIterator<R: Region> {
-    current: R,
-    end: R,
-}
-
-impl<R: Region> RegionIterator<R> {
-    pub fn new(start: R, end: R) -> Self {
-        Self {
-            current: start,
-            end,
-        }
-    }
-}
-
-impl<R: Region> Iterator for RegionIterator<R> {
-    type Item = R;
-
-    fn next(&mut self) -> Option<R> {
-        if self.current < self.end {
-            let ret = self.current;
-            self.current = self.current.next();
-            Some(ret)
-        } else {
-            None
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::util::constants::LOG_BYTES_IN_PAGE;
-
-    const PAGE_SIZE: usize = 1 << LOG_BYTES_IN_PAGE;
-
-    #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
-    struct Page(Address);
-
-    impl From<Address> for Page {
-        #[inline(always)]
-        fn from(address: Address) -> Page {
-            debug_assert!(address.is_aligned_to(Self::BYTES));
-            Self(address)
-        }
-    }
-
-    impl From<Page> for Address {
-        #[inline(always)]
-        fn from(page: Page) -> Address {
-            page.0
-        }
-    }
-
-    impl Region for Page {
-        const LOG_BYTES: usize = LOG_BYTES_IN_PAGE as usize;
-    }
-
-    #[test]
-    fn test_region_methods() {
-        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
-        let addr4k1 = unsafe { Address::from_usize(PAGE_SIZE + 1) };
-
-        // align
-        debug_assert_eq!(Page::align(addr4k), addr4k);
-        debug_assert_eq!(Page::align(addr4k1), addr4k);
-        debug_assert!(Page::is_aligned(addr4k));
-        debug_assert!(!Page::is_aligned(addr4k1));
-
-        let page = Page::from(addr4k);
-        // start/end
-        debug_assert_eq!(page.start(), addr4k);
-        debug_assert_eq!(page.end(), addr4k + PAGE_SIZE);
-        // next
-        debug_assert_eq!(page.next().start(), addr4k + PAGE_SIZE);
-        debug_assert_eq!(page.next_nth(1).start(), addr4k + PAGE_SIZE);
-        debug_assert_eq!(page.next_nth(2).start(), addr4k + 2 * PAGE_SIZE);
-    }
-
-    #[test]
-    fn test_region_iterator_normal() {
-        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
-        let page = Page::from(addr4k);
-        let end_page = page.next_nth(5);
-
-        let mut results = vec![];
-        let iter = RegionIterator::new(page, end_page);
-        for p in iter {
-            results.push(p);
-        }
-        debug_assert_eq!(
-            results,
-            vec![
-                page,
-                page.next_nth(1),
-                page.next_nth(2),
-                page.next_nth(3),
-                page.next_nth(4)
-            ]
-        );
-    }
-
-    #[test]
-    fn test_region_iterator_same_start_end() {
-        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
-        let page = Page::from(addr4k);
-
-        let mut results = vec![];
-        let iter = RegionIterator::new(page, page);
-        for p in iter {
-            results.push(p);
-        }
-        debug_assert_eq!(results, vec![]);
-    }
-
-    #[test]
-    fn test_region_iterator_smaller_end() {
-        let addr4k = unsafe { Address::from_usize(PAGE_SIZE) };
-        let page = Page::from(addr4k);
-        let end = Page::from(Address::ZERO);
-
-        let mut results = vec![];
-        let iter = RegionIterator::new(page, end);
-        for p in iter {
-            results.push(p);
-        }
-        debug_assert_eq!(results, vec![]);
-    }
-}
@@ -43,6 +43,12 @@ pub fn offset_malloc_usable_size(address: Address) -> usize {
     unsafe { malloc_usable_size(malloc_res) }
 }
 
+/// get the address returned by malloc for an address that is allocated with some offset
+pub fn offset_malloc_start(address: Address) -> Address {
+    let malloc_res_ptr: *mut usize = (address - BYTES_IN_ADDRESS).to_mut_ptr();
+    unsafe { Address::from_usize(*malloc_res_ptr) }
+}
+
 /// free an address that is allocated with some offset
 pub fn offset_free(address: Address) {
     let malloc_res_ptr: *mut usize = (address - BYTES_IN_ADDRESS).to_mut_ptr();
@@ -1,20 +1,24 @@
 use crate::util::alloc::AllocationError;
 use crate::util::opaque_pointer::*;
+use crate::util::platform::platform;
 use crate::util::Address;
 use crate::vm::{Collection, VMBinding};
-use libc::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
 use std::io::{Error, Result};
 
 pub fn result_is_mapped(result: Result<()>) -> bool {
     match result {
         Ok(_) => false,
-        Err(err) => err.raw_os_error().unwrap() == libc::EEXIST,
+        Err(err) => err.kind() == std::io::ErrorKind::AlreadyExists,
     }
 }
 
 pub fn zero(start: Address, len: usize) {
-    let ptr = start.to_mut_ptr();
-    wrap_libc_call(&|| unsafe { libc::memset(ptr, 0, len) }, ptr).unwrap()
+    set(start, 0, len);
+}
+
+/// Set every byte in the range to the value.
+pub fn set(start: Address, val: u8, len: usize) {
+    unsafe { std::ptr::write_bytes(start.to_mut_ptr::<u8>(), val, len) }
 }
 
 /// Demand-zero mmap:
@@ -26,33 +30,15 @@ pub fn zero(start: Address, len: usize) {
 /// This function WILL overwrite existing memory mapping if there is any. So only use this function if you know
 /// the memory has been reserved by mmtk (e.g. after the use of mmap_noreserve()). Otherwise using this function
 /// may corrupt others' data.
-#[allow(clippy::let_and_return)] // Zeroing is not neceesary for some OS/s
 pub unsafe fn dzmmap(start: Address, size: usize) -> Result<()> {
-    let prot = PROT_READ | PROT_WRITE | PROT_EXEC;
-    let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED;
-    let ret = mmap_fixed(start, size, prot, flags);
-    // We do not need to explicitly zero for Linux (memory is guaranteed to be zeroed)
-    #[cfg(not(target_os = "linux"))]
-    if ret.is_ok() {
-        zero(start, size)
-    }
-    ret
+    platform().map_zeroed(start, size, true)
 }
 
 /// Demand-zero mmap (no replace):
 /// This function mmaps the memory and guarantees to zero all mapped memory.
 /// This function will not overwrite existing memory mapping, and it will result Err if there is an existing mapping.
-#[allow(clippy::let_and_return)] // Zeroing is not neceesary for some OS/s
 pub fn dzmmap_noreplace(start: Address, size: usize) -> Result<()> {
-    let prot = PROT_READ | PROT_WRITE | PROT_EXEC;
-    let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE;
-    let ret = mmap_fixed(start, size, prot, flags);
-    // We do not need to explicitly zero for Linux (memory is guaranteed to be zeroed)
-    #[cfg(not(target_os = "linux"))]
-    if ret.is_ok() {
-        zero(start, size)
-    }
-    ret
+    platform().map_zeroed(start, size, false)
 }
 
 /// mmap with no swap space reserve:
@@ -60,102 +46,69 @@ pub fn dzmmap_noreplace(start: Address, size: usize) -> Result<()> {
 /// mapping can always be successful. In case of out of physical memory, one may get a segfault for writing to the mapping.
 /// We can use this to reserve the address range, and then later overwrites the mapping with dzmmap().
 pub fn mmap_noreserve(start: Address, size: usize) -> Result<()> {
-    let prot = PROT_NONE;
-    let flags =
-        libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE | libc::MAP_NORESERVE;
-    mmap_fixed(start, size, prot, flags)
+    platform().reserve(start, size)
 }
 
-pub fn mmap_fixed(
-    start: Address,
-    size: usize,
-    prot: libc::c_int,
-    flags: libc::c_int,
-) -> Result<()> {
-    let ptr = start.to_mut_ptr();
-    wrap_libc_call(
-        &|| unsafe { libc::mmap(start.to_mut_ptr(), size, prot, flags, -1, 0) },
-        ptr,
-    )
+pub fn munmap(start: Address, size: usize) -> Result<()> {
+    platform().unmap(start, size)
 }
 
-pub fn munmap(start: Address, size: usize) -> Result<()> {
-    wrap_libc_call(&|| unsafe { libc::munmap(start.to_mut_ptr(), size) }, 0)
+/// Give the physical memory of the range back to the OS. The range stays mapped, and is zero
+/// when it is touched again.
+pub fn decommit(start: Address, size: usize) -> Result<()> {
+    platform().decommit(start, size)
 }
 
 /// Properly handle errors from a mmap Result, including invoking the binding code in the case of
 /// an OOM error.
 pub fn handle_mmap_error<VM: VMBinding>(error: Error, tls: VMThread) -> ! {
+    if is_mmap_out_of_memory(&error) {
+        // Signal `MmapOutOfMemory`. Expect the VM to abort immediately.
+        trace!("Signal MmapOutOfMemory!");
+        VM::VMCollection::out_of_memory(tls, AllocationError::MmapOutOfMemory);
+        unreachable!()
+    }
+    if error.kind() == std::io::ErrorKind::AlreadyExists {
+        panic!("Failed to mmap, the address is already mapped. Should MMTk quanrantine the address range first?");
+    }
+    panic!("Unexpected mmap failure: {:?}", error)
+}
+
+/// Did a mmap fail because the OS is out of memory?
+pub fn is_mmap_out_of_memory(error: &Error) -> bool {
     use std::io::ErrorKind;
 
     match error.kind() {
         // From Rust nightly 2021-05-12, we started to see Rust added this ErrorKind.
-        ErrorKind::OutOfMemory => {
-            // Signal `MmapOutOfMemory`. Expect the VM to abort immediately.
-            trace!("Signal MmapOutOfMemory!");
-            VM::VMCollection::out_of_memory(tls, AllocationError::MmapOutOfMemory);
-            unreachable!()
-        }
+        ErrorKind::OutOfMemory => true,
         // Before Rust had ErrorKind::OutOfMemory, this is how we capture OOM from OS calls.
         // TODO: We may be able to remove this now.
-        ErrorKind::Other => {
-            // further check the error
-            if let Some(os_errno) = error.raw_os_error() {
-                // If it is OOM, we invoke out_of_memory() through the VM interface.
-                if os_errno == libc::ENOMEM {
-                    // Signal `MmapOutOfMemory`. Expect the VM to abort immediately.
-                    trace!("Signal MmapOutOfMemory!");
-                    VM::VMCollection::out_of_memory(tls, AllocationError::MmapOutOfMemory);
-                    unreachable!()
-                }
-            }
-        }
-        ErrorKind::AlreadyExists => panic!("Failed to mmap, the address is already mapped. Should MMTk quanrantine the address range first?"),
-        _ => {}
+        ErrorKind::Other => error.raw_os_error() == Some(libc::ENOMEM),
+        _ => false,
     }
-    panic!("Unexpected mmap failure: {:?}", error)
 }
 
 /// Checks if the memory has already been mapped. If not, we panic.
-// Note that the checking has a side effect that it will map the memory if it was unmapped. So we panic if it was unmapped.
-// Be very careful about using this function.
+// Note that with the OS platform, the checking has a side effect that it will map the memory if it
+// was unmapped. So we panic if it was unmapped. Be very careful about using this function.
 pub fn panic_if_unmapped(start: Address, size: usize) {
-    let prot = PROT_READ | PROT_WRITE;
-    // MAP_FIXED_NOREPLACE returns EEXIST if already mapped
-    let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE;
-    match mmap_fixed(start, size, prot, flags) {
-        Ok(_) => panic!("{} of size {} is not mapped", start, size),
-        Err(e) => {
-            assert!(
-                e.kind() == std::io::ErrorKind::AlreadyExists,
-                "Failed to check mapped: {:?}",
-                e
-            );
-        }
+    if !platform().is_mapped(start, size) {
+        panic!("{} of size {} is not mapped", start, size);
     }
 }
 
-pub fn munprotect(start: Address, size: usize) -> Result<()> {
-    wrap_libc_call(
-        &|| unsafe { libc::mprotect(start.to_mut_ptr(), size, PROT_READ | PROT_WRITE | PROT_EXEC) },
-        0,
-    )
+/// The bytes of the mapped memory that are resident in physical memory, or `None` if the platform
+/// cannot tell.
+pub fn resident_bytes(start: Address, size: usize) -> Option<usize> {
+    platform().resident_bytes(start, size)
 }
 
-pub fn mprotect(start: Address, size: usize) -> Result<()> {
-    wrap_libc_call(
-        &|| unsafe { libc::mprotect(start.to_mut_ptr(), size, PROT_NONE) },
-        0,
-    )
+pub fn munprotect(start: Address, size: usize) -> Result<()> {
+    platform().unprotect(start, size)
 }
 
-fn wrap_libc_call<T: PartialEq>(f: &dyn Fn() -> T, expect: T) -> Result<()> {
-    let ret = f();
-    if ret == expect {
-        Ok(())
-    } else {
-        Err(std::io::Error::last_os_error())
-    }
+pub fn mprotect(start: Address, size: usize) -> Result<()> {
+    platform().protect(start, size)
 }
 
 /// Get the memory maps for the process. The returned string is a multi-line string.
@@ -201,6 +154,49 @@ mod tests {
         });
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_resident_bytes() {
+        serial_test(|| {
+            with_cleanup(
+                || {
+                    let size = 4 * BYTES_IN_PAGE;
+                    assert!(dzmmap_noreplace(START, size).is_ok());
+                    // The pages are not touched yet.
+                    assert_eq!(resident_bytes(START, size), Some(0));
+                    unsafe { START.store(1usize) };
+                    let resident = resident_bytes(START, size).unwrap();
+                    assert!((BYTES_IN_PAGE..=size).contains(&resident));
+                },
+                || {
+                    assert!(munmap(START, 4 * BYTES_IN_PAGE).is_ok());
+                },
+            );
+        });
+    }
+
+    #[test]
+    fn test_decommit() {
+        serial_test(|| {
+            with_cleanup(
+                || {
+                    assert!(dzmmap_noreplace(START, BYTES_IN_PAGE).is_ok());
+                    unsafe { START.store(42usize) };
+                    assert!(decommit(START, BYTES_IN_PAGE).is_ok());
+                    // The page is no longer resident. Check it before the load below, which
+                    // maps the zero page.
+                    #[cfg(target_os = "linux")]
+                    assert_eq!(resident_bytes(START, BYTES_IN_PAGE), Some(0));
+                    // The memory is still mapped, and is zero again.
+                    assert_eq!(unsafe { START.load::<usize>() }, 0);
+                },
+                || {
+                    assert!(munmap(START, BYTES_IN_PAGE).is_ok());
+                },
+            )
+        })
+    }
+
     #[test]
     fn test_munmap() {
         serial_test(|| {
@@ -1,4 +1,12 @@
+//! The log bit of objects, used by the object barrier of the generational plans to remember the
+//! mature objects that are modified. The policies maintain the bit with the functions here: they
+//! mark an object unlogged when it becomes mature, and clear the bits of the memory they release.
+//! For a side log bit, the bits of a whole range (e.g. a block or the pages of a large object) are
+//! updated in bulk instead of object by object.
+
+use crate::util::metadata::side_metadata;
 use crate::util::metadata::*;
+use crate::util::Address;
 use crate::util::ObjectReference;
 use crate::vm::VMBinding;
 use crate::vm::VMGlobalLogBitSpec;
@@ -9,4 +17,43 @@ impl VMGlobalLogBitSpec {
     pub fn mark_as_unlogged<VM: VMBinding>(&self, object: ObjectReference, order: Ordering) {
         store_metadata::<VM>(self, object, 1, None, Some(order))
     }
+
+    /// Mark the log bit as logged (0 means logged)
+    pub fn mark_as_logged<VM: VMBinding>(&self, object: ObjectReference, order: Ordering) {
+        store_metadata::<VM>(self, object, 0, None, Some(order))
+    }
+
+    /// Check if the object is unlogged (1 means unlogged). The metadata for the object must be mapped.
+    #[inline(always)]
+    pub fn is_unlogged<VM: VMBinding>(&self, object: ObjectReference, order: Ordering) -> bool {
+        load_metadata::<VM>(self, object, None, Some(order)) == 1
+    }
+
+    /// Can the bits of a range of memory be updated in bulk? This is true for a side log bit. An
+    /// in-header log bit can only be updated object by object.
+    pub fn supports_bulk_update(&self) -> bool {
+        self.as_spec().is_on_side()
+    }
+
+    /// Mark all the objects in the page aligned range `[start, start + size)` as unlogged, e.g.
+    /// when all the objects in a block are promoted. This panics with an in-header log bit (see
+    /// `supports_bulk_update()`).
+    pub fn mark_range_as_unlogged(&self, start: Address, size: usize) {
+        match self.as_spec() {
+            MetadataSpec::OnSide(spec) => side_metadata::bset_metadata(spec, start, size),
+            MetadataSpec::InHeader(_) => {
+                panic!("An in-header log bit cannot be set in bulk. Set it for each object.")
+            }
+        }
+    }
+
+    /// Clear the log bits for the page aligned range `[start, start + size)` when the memory is
+    /// released, so the objects allocated later in the memory do not inherit the unlogged state
+    /// of the dead objects. An in-header log bit is part of the object header, which the binding
+    /// initializes for each new object, so nothing needs to be done for it.
+    pub fn clear_range(&self, start: Address, size: usize) {
+        if let MetadataSpec::OnSide(spec) = self.as_spec() {
+            side_metadata::bzero_metadata(spec, start, size);
+        }
+    }
 }
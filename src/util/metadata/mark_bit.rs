@@ -0,0 +1,24 @@
+//! The mark bit of objects. A VM provides `LOCAL_MARK_BIT_SPEC`, and optionally
+//! `LOCAL_MARK_BIT_SIDE_SPEC` on the side, and each space that marks objects decides which of the
+//! two it uses when it is created. A space keeps the spec it uses, and accesses its mark bits with
+//! it. The log bit and the forwarding bits are still the same for all the spaces, as the barriers
+//! and the forwarding functions check them before they know the space of the object.
+
+use crate::util::metadata::MetadataSpec;
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+
+/// The mark bit spec for the space of the given name.
+pub(crate) fn mark_bit_spec_for_space<VM: VMBinding>(space_name: &str) -> MetadataSpec {
+    match VM::VMObjectModel::LOCAL_MARK_BIT_SIDE_SPEC {
+        Some(side_spec) if VM::VMObjectModel::use_side_mark_bit(space_name) => {
+            assert!(
+                side_spec.is_on_side(),
+                "LOCAL_MARK_BIT_SIDE_SPEC must be a side metadata spec"
+            );
+            debug!("Space {} keeps its mark bits on the side", space_name);
+            *side_spec
+        }
+        _ => *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+    }
+}
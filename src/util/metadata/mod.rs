@@ -223,5 +223,6 @@ pub mod header_metadata;
 pub mod side_metadata;
 
 pub(crate) mod log_bit;
+pub(crate) mod mark_bit;
 
 pub use global::*;
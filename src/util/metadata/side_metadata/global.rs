@@ -38,6 +38,18 @@ impl SideMetadataSpec {
         !self.is_absolute_offset()
     }
 
+    /// Does this spec start at or after the given offset? The offset must be of the same kind
+    /// (absolute or relative) as the offset of this spec.
+    pub(crate) fn starts_at_or_after(&self, offset: SideMetadataOffset) -> bool {
+        unsafe {
+            if self.is_absolute_offset() {
+                self.offset.addr >= offset.addr
+            } else {
+                self.offset.rel_offset >= offset.rel_offset
+            }
+        }
+    }
+
     #[inline(always)]
     pub const fn get_absolute_offset(&self) -> Address {
         debug_assert!(self.is_absolute_offset());
@@ -174,17 +186,30 @@ pub struct SideMetadataContext {
 }
 
 impl SideMetadataContext {
-    #[cfg(not(feature = "global_alloc_bit"))]
     pub fn new_global_specs(specs: &[SideMetadataSpec]) -> Vec<SideMetadataSpec> {
-        let mut ret = vec![];
-        ret.extend_from_slice(specs);
-        ret
-    }
-
-    #[cfg(feature = "global_alloc_bit")]
-    pub fn new_global_specs(specs: &[SideMetadataSpec]) -> Vec<SideMetadataSpec> {
-        let mut ret = vec![];
+        let mut ret = vec![crate::util::heap::chunk_map::ChunkMap::STATE_TABLE];
+        #[cfg(feature = "global_alloc_bit")]
         ret.extend_from_slice(&[ALLOC_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "object_age")]
+        ret.extend_from_slice(&[crate::util::object_age::OBJECT_AGE_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "object_hash")]
+        ret.extend_from_slice(&[crate::util::object_hash::HASH_STATE_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "object_start_map")]
+        ret.extend_from_slice(&[crate::util::object_start_map::OBJECT_START_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "object_user_data")]
+        ret.extend_from_slice(&[crate::util::object_user_data::USER_DATA_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "tracing_overflow")]
+        ret.extend_from_slice(&[
+            crate::util::tracing_overflow::TRACING_OVERFLOW_SIDE_METADATA_SPEC,
+        ]);
+        #[cfg(feature = "heap_ids")]
+        ret.extend_from_slice(&[crate::util::heap_id::HEAP_ID_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "transitive_pinning")]
+        ret.extend_from_slice(&[crate::util::transitive_pin::TRANSITIVE_PIN_SIDE_METADATA_SPEC]);
+        #[cfg(feature = "immutable_objects")]
+        ret.extend_from_slice(&[
+            crate::util::immutable_object::IMMUTABLE_OBJECT_SIDE_METADATA_SPEC,
+        ]);
         ret.extend_from_slice(specs);
         ret
     }
@@ -309,6 +334,37 @@ impl SideMetadataContext {
         Ok(())
     }
 
+    /// Unmap the metadata space mapped by `try_map_metadata_space` or
+    /// `try_map_metadata_address_range` for the data address range, and tell the mmapper about
+    /// it, so a later MMTk instance can map the range again. This is used when an MMTk instance
+    /// shuts down. Like the mmapper, this works at mmap chunk granularity, so it must only be
+    /// called if no other space uses metadata in the same mmap chunks.
+    pub fn release_metadata_space(&self, start: Address, size: usize) {
+        debug!("release_metadata_space({}, 0x{:x})", start, size);
+        for spec in self.global.iter() {
+            munmap_contiguous_metadata_space(start, size, spec);
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        let mut lsize: usize = 0;
+
+        for spec in self.local.iter() {
+            #[cfg(target_pointer_width = "64")]
+            {
+                munmap_contiguous_metadata_space(start, size, spec);
+            }
+            #[cfg(target_pointer_width = "32")]
+            {
+                lsize += metadata_bytes_per_chunk(spec.log_bytes_in_region, spec.log_num_of_bits);
+            }
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        if lsize > 0 {
+            munmap_per_chunk_metadata_space(start, size, lsize);
+        }
+    }
+
     /// Unmap the corresponding metadata space or panic.
     ///
     /// Note-1: This function is only used for test and debug right now.
@@ -836,9 +892,39 @@ pub fn bzero_metadata(metadata_spec: &SideMetadataSpec, start: Address, size: us
     #[cfg(feature = "extreme_assertions")]
     sanity::verify_bzero(metadata_spec, start, size);
 
+    bulk_update_metadata(metadata_spec, start, size, &memory::zero);
+}
+
+/// Bulk-set a specific metadata for a range of data: every bit of the metadata for the range is
+/// set to 1. This is the counterpart of [`bzero_metadata`], and has the same alignment
+/// requirement.
+pub fn bset_metadata(metadata_spec: &SideMetadataSpec, start: Address, size: usize) {
+    #[cfg(feature = "extreme_assertions")]
+    let _lock = sanity::SANITY_LOCK.lock().unwrap();
+
+    debug_assert!(
+        start.is_aligned_to(BYTES_IN_PAGE) && meta_byte_lshift(metadata_spec, start) == 0
+    );
+
+    #[cfg(feature = "extreme_assertions")]
+    sanity::verify_bset(metadata_spec, start, size);
+
+    bulk_update_metadata(metadata_spec, start, size, &|meta_start, meta_size| {
+        memory::set(meta_start, 0xff, meta_size)
+    });
+}
+
+/// Apply `update` to the metadata bytes for the data range. `update` is called with the start and
+/// the size of each contiguous metadata range.
+fn bulk_update_metadata(
+    metadata_spec: &SideMetadataSpec,
+    start: Address,
+    size: usize,
+    update: &impl Fn(Address, usize),
+) {
     let meta_start = address_to_meta_address(metadata_spec, start);
     if cfg!(target_pointer_width = "64") || metadata_spec.is_global {
-        memory::zero(
+        update(
             meta_start,
             address_to_meta_address(metadata_spec, start + size) - meta_start,
         );
@@ -850,28 +936,28 @@ pub fn bzero_metadata(metadata_spec: &SideMetadataSpec, start: Address, size: us
             - start.align_down(BYTES_IN_CHUNK))
             / BYTES_IN_CHUNK;
         if chunk_num == 0 {
-            memory::zero(
+            update(
                 meta_start,
                 address_to_meta_address(metadata_spec, start + size) - meta_start,
             );
         } else {
             let second_data_chunk = start.align_up(BYTES_IN_CHUNK);
-            // bzero the first sub-chunk
-            memory::zero(
+            // update the first sub-chunk
+            update(
                 meta_start,
                 address_to_meta_address(metadata_spec, second_data_chunk) - meta_start,
             );
             let last_data_chunk = (start + size).align_down(BYTES_IN_CHUNK);
             let last_meta_chunk = address_to_meta_address(metadata_spec, last_data_chunk);
-            // bzero the last sub-chunk
-            memory::zero(
+            // update the last sub-chunk
+            update(
                 last_meta_chunk,
                 address_to_meta_address(metadata_spec, start + size) - last_meta_chunk,
             );
             let mut next_data_chunk = second_data_chunk;
-            // bzero all chunks in the middle
+            // update all chunks in the middle
             while next_data_chunk != last_data_chunk {
-                memory::zero(
+                update(
                     address_to_meta_address(metadata_spec, next_data_chunk),
                     metadata_bytes_per_chunk(
                         metadata_spec.log_bytes_in_region,
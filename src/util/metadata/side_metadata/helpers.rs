@@ -88,6 +88,23 @@ pub(crate) fn try_mmap_contiguous_metadata_space(
     }
 }
 
+/// Unmaps the metadata space (`spec`) for the specified data address range (`start` and `size`)
+/// through the mmapper, so the range can be mapped again. This is the reverse of
+/// `try_mmap_contiguous_metadata_space`. Note that the mmapper unmaps at mmap chunk granularity.
+pub(crate) fn munmap_contiguous_metadata_space(
+    start: Address,
+    size: usize,
+    spec: &SideMetadataSpec,
+) {
+    let metadata_start = address_to_meta_address(spec, start);
+    let mmap_start = metadata_start.align_down(BYTES_IN_PAGE);
+    let metadata_size = (size + ((1 << addr_rshift(spec)) - 1)) >> addr_rshift(spec);
+    let mmap_size = (metadata_start + metadata_size).align_up(BYTES_IN_PAGE) - mmap_start;
+    if mmap_size > 0 {
+        MMAPPER.unmap(mmap_start, mmap_size >> LOG_BYTES_IN_PAGE);
+    }
+}
+
 /// Performs the translation of data address (`data_addr`) to metadata address for the specified metadata (`metadata_spec`).
 #[inline(always)]
 pub(crate) fn address_to_meta_address(
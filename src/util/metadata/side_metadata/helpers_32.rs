@@ -167,6 +167,19 @@ pub fn try_map_per_chunk_metadata_space(
     Ok(total_mapped)
 }
 
+/// Unmaps the per chunk metadata for the chunks in the data address range (`start` and `size`)
+/// through the mmapper, so the range can be mapped again. This is the reverse of
+/// `try_map_per_chunk_metadata_space`.
+pub fn munmap_per_chunk_metadata_space(start: Address, size: usize, local_per_chunk: usize) {
+    let mut aligned_start = start.align_down(BYTES_IN_CHUNK);
+    let aligned_end = (start + size).align_up(BYTES_IN_CHUNK);
+    let pages = crate::util::conversions::bytes_to_pages_up(local_per_chunk);
+    while aligned_start < aligned_end {
+        MMAPPER.unmap(address_to_meta_chunk_addr(aligned_start), pages);
+        aligned_start += BYTES_IN_CHUNK;
+    }
+}
+
 // Try to map side metadata for the chunk starting at `start`
 pub fn try_mmap_metadata_chunk(
     start: Address,
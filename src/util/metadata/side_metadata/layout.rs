@@ -0,0 +1,255 @@
+//! The layout of the side metadata of an MMTk instance. The layout of all the specs of the plan,
+//! the policies and the binding is computed when the MMTk instance is built, so overlapping specs,
+//! and specs that do not fit in the address range for side metadata, are reported before any
+//! metadata is mapped. The layout can be printed for debugging with
+//! [`memory_manager::side_metadata_layout`](crate::memory_manager::side_metadata_layout).
+
+use super::constants::*;
+use super::SideMetadataContext;
+use super::SideMetadataSpec;
+#[cfg(target_pointer_width = "64")]
+use crate::util::heap::layout::vm_layout_constants::LOG_ADDRESS_SPACE;
+use std::fmt;
+
+/// A side metadata spec in the layout.
+#[derive(Clone, Debug)]
+pub struct SideMetadataLayoutEntry {
+    pub spec: SideMetadataSpec,
+    /// The names of the spaces that use the spec.
+    pub spaces: Vec<&'static str>,
+    /// The start of the metadata. This is an address for contiguous metadata, and an offset in
+    /// each metadata chunk for chunked metadata (local metadata on 32 bits).
+    pub start: usize,
+    /// The end of the metadata (exclusive), like `start`.
+    pub end: usize,
+}
+
+impl SideMetadataLayoutEntry {
+    fn new(spec: &SideMetadataSpec, space: &'static str) -> Self {
+        let (start, size) = if spec.is_absolute_offset() {
+            (
+                spec.get_absolute_offset().as_usize(),
+                super::metadata_address_range_size(spec),
+            )
+        } else {
+            Self::chunked_range(spec)
+        };
+        SideMetadataLayoutEntry {
+            spec: *spec,
+            spaces: vec![space],
+            start,
+            end: start + size,
+        }
+    }
+
+    /// The offset and the size of chunked metadata in each metadata chunk.
+    #[cfg(target_pointer_width = "32")]
+    fn chunked_range(spec: &SideMetadataSpec) -> (usize, usize) {
+        let size = super::metadata_bytes_per_chunk(spec.log_bytes_in_region, spec.log_num_of_bits);
+        (spec.get_rel_offset(), size)
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    fn chunked_range(_spec: &SideMetadataSpec) -> (usize, usize) {
+        unreachable!("Side metadata is contiguous on 64 bits")
+    }
+}
+
+impl fmt::Display for SideMetadataLayoutEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[0x{:x}, 0x{:x}) {} ({} bytes, used by {})",
+            self.start,
+            self.end,
+            self.spec.name,
+            self.end - self.start,
+            self.spaces.join(", ")
+        )
+    }
+}
+
+/// The layout of the side metadata of an MMTk instance. The entries are sorted by their start.
+#[derive(Clone, Debug, Default)]
+pub struct SideMetadataLayout {
+    pub global: Vec<SideMetadataLayoutEntry>,
+    pub local: Vec<SideMetadataLayoutEntry>,
+}
+
+impl SideMetadataLayout {
+    /// Compute the layout of the side metadata of the given spaces, with the name and the metadata
+    /// context of each space. Returns an error that describes all the conflicts and the layout, if
+    /// there is any conflict.
+    pub(crate) fn compute<'a>(
+        spaces: impl IntoIterator<Item = (&'static str, &'a SideMetadataContext)>,
+    ) -> Result<Self, String> {
+        let mut layout = SideMetadataLayout::default();
+        for (space, context) in spaces {
+            for spec in &context.global {
+                Self::add(&mut layout.global, spec, space);
+            }
+            for spec in &context.local {
+                Self::add(&mut layout.local, spec, space);
+            }
+        }
+        layout.global.sort_by_key(|entry| entry.start);
+        layout.local.sort_by_key(|entry| entry.start);
+
+        let mut errors = vec![];
+        Self::check_overlaps(&layout.global, &mut errors);
+        Self::check_overlaps(&layout.local, &mut errors);
+        // The global metadata is followed by the local metadata.
+        if let Some(entry) = layout.global.iter().max_by_key(|entry| entry.end) {
+            if entry.end > LOCAL_SIDE_METADATA_BASE_ADDRESS.as_usize() {
+                errors.push(format!(
+                    "{} ends at 0x{:x}, beyond the global side metadata, which ends at {}",
+                    entry.spec.name, entry.end, LOCAL_SIDE_METADATA_BASE_ADDRESS
+                ));
+            }
+        }
+        #[cfg(target_pointer_width = "64")]
+        for entry in &layout.local {
+            let max_size = 1usize << (LOG_ADDRESS_SPACE - LOG_LOCAL_SIDE_METADATA_WORST_CASE_RATIO);
+            if entry.end - entry.start > max_size {
+                errors.push(format!(
+                    "{} needs {} bytes, but a local side metadata can have {} bytes at most",
+                    entry.spec.name,
+                    entry.end - entry.start,
+                    max_size
+                ));
+            }
+        }
+        #[cfg(target_pointer_width = "32")]
+        if let Some(entry) = layout.local.iter().max_by_key(|entry| entry.end) {
+            if entry.end > LOCAL_SIDE_METADATA_PER_CHUNK {
+                errors.push(format!(
+                    "{} ends at offset 0x{:x} of a metadata chunk, which has 0x{:x} bytes",
+                    entry.spec.name, entry.end, LOCAL_SIDE_METADATA_PER_CHUNK
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(layout)
+        } else {
+            Err(format!(
+                "Side metadata conflicts: {}\n{}",
+                errors.join("; "),
+                layout
+            ))
+        }
+    }
+
+    /// Add a spec used by a space. A spec used by several spaces is only added once.
+    fn add(
+        entries: &mut Vec<SideMetadataLayoutEntry>,
+        spec: &SideMetadataSpec,
+        space: &'static str,
+    ) {
+        match entries.iter_mut().find(|entry| entry.spec == *spec) {
+            Some(entry) => {
+                if !entry.spaces.contains(&space) {
+                    entry.spaces.push(space);
+                }
+            }
+            None => entries.push(SideMetadataLayoutEntry::new(spec, space)),
+        }
+    }
+
+    /// Report the entries that overlap. The entries are sorted by their start.
+    fn check_overlaps(entries: &[SideMetadataLayoutEntry], errors: &mut Vec<String>) {
+        for (i, entry_1) in entries.iter().enumerate() {
+            for entry_2 in entries[i + 1..]
+                .iter()
+                .take_while(|e| e.start < entry_1.end)
+            {
+                errors.push(format!(
+                    "{} (used by {}) overlaps {} (used by {})",
+                    entry_1.spec.name,
+                    entry_1.spaces.join(", "),
+                    entry_2.spec.name,
+                    entry_2.spaces.join(", ")
+                ));
+            }
+        }
+    }
+}
+
+impl fmt::Display for SideMetadataLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Global side metadata:")?;
+        for entry in &self.global {
+            writeln!(f, "  {}", entry)?;
+        }
+        if cfg!(target_pointer_width = "32") {
+            writeln!(f, "Local side metadata (offsets in each metadata chunk):")?;
+        } else {
+            writeln!(f, "Local side metadata:")?;
+        }
+        for entry in &self.local {
+            writeln!(f, "  {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::metadata::side_metadata::SideMetadataOffset;
+
+    const SPEC_1: SideMetadataSpec = SideMetadataSpec {
+        name: "SPEC_1",
+        is_global: false,
+        offset: LOCAL_SIDE_METADATA_VM_BASE_OFFSET,
+        log_num_of_bits: 0,
+        log_bytes_in_region: 3,
+    };
+    const SPEC_2: SideMetadataSpec = SideMetadataSpec {
+        name: "SPEC_2",
+        is_global: false,
+        offset: SideMetadataOffset::layout_after(&SPEC_1),
+        log_num_of_bits: 1,
+        log_bytes_in_region: 3,
+    };
+    const SPEC_3: SideMetadataSpec = SideMetadataSpec {
+        name: "SPEC_3",
+        is_global: false,
+        offset: LOCAL_SIDE_METADATA_VM_BASE_OFFSET,
+        log_num_of_bits: 1,
+        log_bytes_in_region: 3,
+    };
+
+    fn context(local: Vec<SideMetadataSpec>) -> SideMetadataContext {
+        SideMetadataContext {
+            global: SideMetadataContext::new_global_specs(&[]),
+            local,
+        }
+    }
+
+    #[test]
+    fn test_compute_layout() {
+        let space_1 = context(vec![SPEC_1]);
+        let space_2 = context(vec![SPEC_1, SPEC_2]);
+        let layout = SideMetadataLayout::compute(vec![("a", &space_1), ("b", &space_2)]).unwrap();
+        assert_eq!(layout.local.len(), 2);
+        assert_eq!(layout.local[0].spec, SPEC_1);
+        assert_eq!(layout.local[0].spaces, vec!["a", "b"]);
+        assert_eq!(layout.local[0].end, layout.local[1].start);
+        assert_eq!(layout.local[1].spaces, vec!["b"]);
+        // The global specs of the spaces are the same.
+        assert_eq!(layout.global.len(), space_1.global.len());
+        assert!(layout.to_string().contains("SPEC_2"));
+    }
+
+    #[test]
+    fn test_compute_layout_overlap() {
+        let space_1 = context(vec![SPEC_1]);
+        let space_2 = context(vec![SPEC_3]);
+        let error = SideMetadataLayout::compute(vec![("a", &space_1), ("b", &space_2)])
+            .err()
+            .unwrap();
+        assert!(error.contains("overlaps"));
+        assert!(error.contains("SPEC_1") && error.contains("SPEC_3"));
+    }
+}
@@ -4,13 +4,18 @@ mod helpers;
 mod helpers_32;
 
 mod global;
+mod layout;
 mod sanity;
 mod side_metadata_tests;
 pub(crate) mod spec_defs;
+mod typed;
 
 pub use constants::*;
 pub use global::*;
 pub use helpers::*;
 #[cfg(target_pointer_width = "32")]
 pub use helpers_32::*;
+pub use layout::{SideMetadataLayout, SideMetadataLayoutEntry};
+pub(crate) use sanity::verify_no_overlap_local;
 pub use sanity::SideMetadataSanity;
+pub use typed::*;
@@ -177,6 +177,30 @@ fn verify_no_overlap_chunked(spec_1: &SideMetadataSpec, spec_2: &SideMetadataSpe
     Ok(())
 }
 
+/// Checks whether two local specifications overlap, with the layout of local metadata on this
+/// architecture (contiguous on 64 bits, and chunked on 32 bits).
+///
+/// Returns `Err` if overlap is detected.
+#[cfg(target_pointer_width = "64")]
+pub(crate) fn verify_no_overlap_local(
+    spec_1: &SideMetadataSpec,
+    spec_2: &SideMetadataSpec,
+) -> Result<()> {
+    verify_no_overlap_contiguous(spec_1, spec_2)
+}
+
+/// Checks whether two local specifications overlap, with the layout of local metadata on this
+/// architecture (contiguous on 64 bits, and chunked on 32 bits).
+///
+/// Returns `Err` if overlap is detected.
+#[cfg(target_pointer_width = "32")]
+pub(crate) fn verify_no_overlap_local(
+    spec_1: &SideMetadataSpec,
+    spec_2: &SideMetadataSpec,
+) -> Result<()> {
+    verify_no_overlap_chunked(spec_1, spec_2)
+}
+
 /// Checks whether a slice of global specifications fit within the memory limits and don't overlap.
 ///
 /// Returns `Ok` if no issue is detected, or otherwise an `Err` explaining the issue.
@@ -244,10 +268,7 @@ impl SideMetadataSanity {
         for spec_1 in &local_specs {
             for spec_2 in &local_specs {
                 if spec_1 != spec_2 {
-                    #[cfg(target_pointer_width = "64")]
-                    verify_no_overlap_contiguous(spec_1, spec_2)?;
-                    #[cfg(target_pointer_width = "32")]
-                    verify_no_overlap_chunked(spec_1, spec_2)?;
+                    verify_no_overlap_local(spec_1, spec_2)?;
                 }
             }
         }
@@ -430,6 +451,37 @@ pub fn verify_bzero(metadata_spec: &SideMetadataSpec, start: Address, size: usiz
     }
 }
 
+/// Commits a side metadata bulk set operation (bset) to the sanity side metadata memory.
+/// Every region in the range is recorded with all its bits set.
+///
+/// Arguments:
+/// * `metadata_spec`: the metadata spec to perform the bulk set on
+/// * `start`: the starting address of the source data
+/// * `size`: size of the source data
+///
+#[cfg(feature = "extreme_assertions")]
+pub fn verify_bset(metadata_spec: &SideMetadataSpec, start: Address, size: usize) {
+    let sanity_map = &mut CONTENT_SANITY_MAP.write().unwrap();
+    let bits = 1usize << metadata_spec.log_num_of_bits;
+    let max_value = if bits >= usize::BITS as usize {
+        usize::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    match sanity_map.get_mut(metadata_spec) {
+        Some(spec_sanity_map) => {
+            let mut data_addr = start;
+            while data_addr < start + size {
+                spec_sanity_map.insert(data_addr, max_value);
+                data_addr += 1usize << metadata_spec.log_bytes_in_region;
+            }
+        }
+        None => {
+            panic!("Invalid Metadata Spec!");
+        }
+    }
+}
+
 /// Ensures a side metadata load operation returns the correct side metadata content.
 /// Panics if:
 /// 1 - the metadata spec is not valid,
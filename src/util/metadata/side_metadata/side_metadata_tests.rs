@@ -522,4 +522,78 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_side_metadata_bset_metadata() {
+        serial_test(|| {
+            with_cleanup(
+                || {
+                    let data_addr = vm_layout_constants::HEAP_START
+                        + (vm_layout_constants::BYTES_IN_CHUNK << 2);
+
+                    #[cfg(target_pointer_width = "64")]
+                    let metadata_1_spec = SideMetadataSpec {
+                        name: "metadata_1_spec",
+                        is_global: false,
+                        offset: SideMetadataOffset::addr(LOCAL_SIDE_METADATA_BASE_ADDRESS),
+                        log_num_of_bits: 0,
+                        log_bytes_in_region: 3,
+                    };
+                    #[cfg(target_pointer_width = "32")]
+                    let metadata_1_spec = SideMetadataSpec {
+                        name: "metadata_1_spec",
+                        is_global: false,
+                        offset: SideMetadataOffset::rel(0),
+                        log_num_of_bits: 0,
+                        log_bytes_in_region: 3,
+                    };
+                    let metadata_2_spec = SideMetadataSpec {
+                        name: "metadata_2_spec",
+                        is_global: false,
+                        offset: SideMetadataOffset::layout_after(&metadata_1_spec),
+                        log_num_of_bits: 3,
+                        log_bytes_in_region: 7,
+                    };
+
+                    let metadata = SideMetadataContext {
+                        global: vec![],
+                        local: vec![metadata_1_spec, metadata_2_spec],
+                    };
+
+                    let mut metadata_sanity = SideMetadataSanity::new();
+                    metadata_sanity.verify_metadata_context("NoPolicy", &metadata);
+
+                    assert!(metadata
+                        .try_map_metadata_space(data_addr, constants::BYTES_IN_PAGE,)
+                        .is_ok());
+
+                    bset_metadata(&metadata_1_spec, data_addr, constants::BYTES_IN_PAGE);
+
+                    let one = load_atomic(&metadata_1_spec, data_addr, Ordering::SeqCst);
+                    assert_eq!(one, 1);
+                    let one = load_atomic(
+                        &metadata_1_spec,
+                        data_addr + constants::BYTES_IN_PAGE - 8usize,
+                        Ordering::SeqCst,
+                    );
+                    assert_eq!(one, 1);
+                    // The other spec is not affected.
+                    let zero = load_atomic(&metadata_2_spec, data_addr, Ordering::SeqCst);
+                    assert_eq!(zero, 0);
+
+                    bzero_metadata(&metadata_1_spec, data_addr, constants::BYTES_IN_PAGE);
+
+                    let zero = load_atomic(&metadata_1_spec, data_addr, Ordering::SeqCst);
+                    assert_eq!(zero, 0);
+
+                    metadata.ensure_unmap_metadata_space(data_addr, constants::BYTES_IN_PAGE);
+
+                    metadata_sanity.reset();
+                },
+                || {
+                    sanity::reset();
+                },
+            );
+        });
+    }
 }
@@ -1,6 +1,6 @@
 use crate::util::constants::*;
 use crate::util::heap::layout::vm_layout_constants::*;
-use crate::util::linear_scan::Region;
+use crate::util::heap::regions::Region;
 use crate::util::metadata::side_metadata::constants::{
     GLOBAL_SIDE_METADATA_BASE_OFFSET, LOCAL_SIDE_METADATA_BASE_OFFSET,
 };
@@ -52,29 +52,153 @@ macro_rules! define_side_metadata_specs {
 }
 
 // This defines all GLOBAL side metadata used by mmtk-core.
+#[cfg(not(any(feature = "object_age", feature = "object_hash")))]
 define_side_metadata_specs!(
-    last_spec_as LAST_GLOBAL_SIDE_METADATA_SPEC,
+    last_spec_as LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC,
     // Mark the start of an object
     ALLOC_BIT       = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
-    // Track chunks used by (malloc) marksweep
-    MS_ACTIVE_CHUNK = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Record the state and the owner space of each chunk
+    CHUNK_MAP       = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
 );
 
+// The same as above, plus the specs for the enabled features (the birth epoch of objects, and/or
+// the hash state of objects). We only lay out those specs if the features are enabled, as they
+// take a lot of the address space for global side metadata.
+#[cfg(all(feature = "object_age", not(feature = "object_hash")))]
+define_side_metadata_specs!(
+    last_spec_as LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC,
+    // Mark the start of an object
+    ALLOC_BIT       = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+    // Record the state and the owner space of each chunk
+    CHUNK_MAP       = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Record the GC epoch in which an object is allocated
+    OBJECT_AGE      = (global: true, log_num_of_bits: 3, log_bytes_in_region: crate::util::object_age::LOG_BYTES_IN_REGION),
+);
+
+#[cfg(all(feature = "object_hash", not(feature = "object_age")))]
+define_side_metadata_specs!(
+    last_spec_as LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC,
+    // Mark the start of an object
+    ALLOC_BIT       = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+    // Record the state and the owner space of each chunk
+    CHUNK_MAP       = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Record whether the hash code of an object has been taken, and whether it has moved since
+    HASH_STATE      = (global: true, log_num_of_bits: 1, log_bytes_in_region: crate::util::object_hash::LOG_BYTES_IN_REGION),
+);
+
+#[cfg(all(feature = "object_age", feature = "object_hash"))]
+define_side_metadata_specs!(
+    last_spec_as LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC,
+    // Mark the start of an object
+    ALLOC_BIT       = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+    // Record the state and the owner space of each chunk
+    CHUNK_MAP       = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Record the GC epoch in which an object is allocated
+    OBJECT_AGE      = (global: true, log_num_of_bits: 3, log_bytes_in_region: crate::util::object_age::LOG_BYTES_IN_REGION),
+    // Record whether the hash code of an object has been taken, and whether it has moved since
+    HASH_STATE      = (global: true, log_num_of_bits: 1, log_bytes_in_region: crate::util::object_hash::LOG_BYTES_IN_REGION),
+);
+
+// The object start map is laid out after the fixed global specs, if the feature is enabled.
+#[cfg(feature = "object_start_map")]
+define_side_metadata_specs!(
+    @prev_spec LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC as LAST_START_MAP_GLOBAL_SIDE_METADATA_SPEC,
+    // Record the offset of the first object that starts in each card
+    OBJECT_START    = (global: true, log_num_of_bits: 3, log_bytes_in_region: crate::util::object_start_map::LOG_BYTES_IN_CARD),
+);
+#[cfg(not(feature = "object_start_map"))]
+pub const LAST_START_MAP_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_FIXED_GLOBAL_SIDE_METADATA_SPEC;
+
+// The user data of objects is laid out after the other global specs, if the feature is enabled.
+#[cfg(feature = "object_user_data")]
+define_side_metadata_specs!(
+    @prev_spec LAST_START_MAP_GLOBAL_SIDE_METADATA_SPEC as LAST_USER_DATA_GLOBAL_SIDE_METADATA_SPEC,
+    // Record a value for each object on behalf of the binding
+    OBJECT_USER_DATA = (global: true, log_num_of_bits: crate::util::object_user_data::LOG_BITS_IN_USER_DATA, log_bytes_in_region: crate::util::object_user_data::LOG_BYTES_IN_REGION),
+);
+#[cfg(not(feature = "object_user_data"))]
+pub const LAST_USER_DATA_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_START_MAP_GLOBAL_SIDE_METADATA_SPEC;
+
+// The tracing overflow bits are laid out after the other global specs, if the feature is enabled.
+#[cfg(feature = "tracing_overflow")]
+define_side_metadata_specs!(
+    @prev_spec LAST_USER_DATA_GLOBAL_SIDE_METADATA_SPEC as LAST_TRACING_OVERFLOW_GLOBAL_SIDE_METADATA_SPEC,
+    // Mark the objects whose scanning is deferred because the tracing memory is full
+    TRACING_OVERFLOW = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+);
+#[cfg(not(feature = "tracing_overflow"))]
+pub const LAST_TRACING_OVERFLOW_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_USER_DATA_GLOBAL_SIDE_METADATA_SPEC;
+
+// The heap ids of objects are laid out after the other global specs, if the feature is enabled.
+#[cfg(feature = "heap_ids")]
+define_side_metadata_specs!(
+    @prev_spec LAST_TRACING_OVERFLOW_GLOBAL_SIDE_METADATA_SPEC as LAST_HEAP_ID_GLOBAL_SIDE_METADATA_SPEC,
+    // Record the heap (e.g. the isolate) that each object belongs to
+    HEAP_ID         = (global: true, log_num_of_bits: 3, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+);
+#[cfg(not(feature = "heap_ids"))]
+pub const LAST_HEAP_ID_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_TRACING_OVERFLOW_GLOBAL_SIDE_METADATA_SPEC;
+
+// The transitive pin bits are laid out after the other global specs, if the feature is enabled.
+#[cfg(feature = "transitive_pinning")]
+define_side_metadata_specs!(
+    @prev_spec LAST_HEAP_ID_GLOBAL_SIDE_METADATA_SPEC as LAST_TRANSITIVE_PIN_GLOBAL_SIDE_METADATA_SPEC,
+    // Mark the objects that are transitively pinned in the current GC
+    TRANSITIVE_PIN  = (global: true, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+);
+#[cfg(not(feature = "transitive_pinning"))]
+pub const LAST_TRANSITIVE_PIN_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_HEAP_ID_GLOBAL_SIDE_METADATA_SPEC;
+
+// The states of the immutable objects are laid out after the other global specs, if the feature is
+// enabled.
+#[cfg(feature = "immutable_objects")]
+define_side_metadata_specs!(
+    @prev_spec LAST_TRANSITIVE_PIN_GLOBAL_SIDE_METADATA_SPEC as LAST_GLOBAL_SIDE_METADATA_SPEC,
+    // Record whether each object is immutable, and whether it has been scanned since then
+    IMMUTABLE_OBJECT = (global: true, log_num_of_bits: 1, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+);
+#[cfg(not(feature = "immutable_objects"))]
+pub const LAST_GLOBAL_SIDE_METADATA_SPEC: SideMetadataSpec =
+    LAST_TRANSITIVE_PIN_GLOBAL_SIDE_METADATA_SPEC;
+
 // This defines all LOCAL side metadata used by mmtk-core.
 define_side_metadata_specs!(
     last_spec_as LAST_LOCAL_SIDE_METADATA_SPEC,
     // Mark pages by (malloc) marksweep
     MS_ACTIVE_PAGE  = (global: false, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_PAGE as usize),
+    // Record the pages covered by (malloc) marksweep objects that start on an earlier page
+    MS_CONTINUATION_PAGE = (global: false, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_PAGE as usize),
     // Record objects allocated with some offset
     MS_OFFSET_MALLOC = (global: false, log_num_of_bits: 0, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+    // Record the last GC epoch in which malloc marksweep allocated in a chunk
+    MS_CHUNK_ALLOC_EPOCH = (global: false, log_num_of_bits: 5, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Count the live objects in a chunk after the last sweep of malloc marksweep
+    MS_CHUNK_LIVE_OBJECTS = (global: false, log_num_of_bits: 5, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Count the objects marked in a chunk in the current GC by malloc marksweep
+    MS_CHUNK_MARKED_OBJECTS = (global: false, log_num_of_bits: 5, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
     // Mark lines by immix
     IX_LINE_MARK    = (global: false, log_num_of_bits: 3, log_bytes_in_region: crate::policy::immix::line::Line::LOG_BYTES),
+    // Count the pins of the objects on each immix line
+    IX_LINE_PIN     = (global: false, log_num_of_bits: 3, log_bytes_in_region: crate::policy::immix::line::Line::LOG_BYTES),
     // Record defrag state for immix blocks
     IX_BLOCK_DEFRAG = (global: false, log_num_of_bits: 3, log_bytes_in_region: crate::policy::immix::block::Block::LOG_BYTES),
     // Mark blocks by immix
     IX_BLOCK_MARK   = (global: false, log_num_of_bits: 3, log_bytes_in_region: crate::policy::immix::block::Block::LOG_BYTES),
-    // Mark chunks by immix
-    IX_CHUNK_MARK   = (global: false, log_num_of_bits: 3, log_bytes_in_region: crate::policy::immix::chunk::Chunk::LOG_BYTES),
+    // Record the immix blocks that the binding requested to evacuate
+    IX_BLOCK_RELOCATE = (global: false, log_num_of_bits: 0, log_bytes_in_region: crate::policy::immix::block::Block::LOG_BYTES),
+    // Count the used pages in each chunk of region-based policies (see `util::heap::regions`)
+    CHUNK_USED_PAGES = (global: false, log_num_of_bits: 4, log_bytes_in_region: LOG_BYTES_IN_CHUNK as usize),
+    // Record the number of nursery GCs survived by young objects in generational plans
+    GEN_OBJECT_AGE  = (global: false, log_num_of_bits: 2, log_bytes_in_region: LOG_MIN_OBJECT_SIZE as usize),
+    // Count the references to each large object, by the first page of the object
+    LOS_REF_COUNT   = (global: false, log_num_of_bits: 3, log_bytes_in_region: LOG_BYTES_IN_PAGE as usize),
+    // Record the candidate state of each large object for the reference counts
+    LOS_REF_COUNT_CANDIDATE = (global: false, log_num_of_bits: 1, log_bytes_in_region: LOG_BYTES_IN_PAGE as usize),
 );
 
 #[cfg(test)]
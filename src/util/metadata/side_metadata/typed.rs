@@ -0,0 +1,215 @@
+//! Side metadata specs typed with the integer type of their values. A `TypedSideMetadataSpec<T>`
+//! can only be created from a spec whose values are `T` (a spec of less than 8 bits is read and
+//! written as `u8`), and its accessors take and return `T` instead of `usize`. If the specs are
+//! defined as constants, a type that does not match the bits of the spec is a compile time error,
+//! so a policy cannot read or write its metadata with a wrong width.
+
+use super::global::{self, SideMetadataSpec};
+use crate::util::Address;
+use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
+
+/// An integer type for the values of side metadata.
+pub trait MetadataValue: Copy + Send + Sync + 'static {
+    /// log2 of the bits of the type.
+    const LOG_BITS: usize;
+    /// Convert a metadata value loaded as `usize`. The value fits in the type.
+    fn from_usize(value: usize) -> Self;
+    /// Convert the value to `usize` to store it.
+    fn to_usize(self) -> usize;
+}
+
+/// A side metadata spec whose values are `T`.
+#[derive(Clone, Copy, Debug)]
+pub struct TypedSideMetadataSpec<T> {
+    spec: SideMetadataSpec,
+    _value: PhantomData<T>,
+}
+
+macro_rules! impl_metadata_value {
+    ($($t: ty),*) => {
+        $(
+            impl MetadataValue for $t {
+                const LOG_BITS: usize = (<$t>::BITS as usize).trailing_zeros() as usize;
+
+                #[inline(always)]
+                fn from_usize(value: usize) -> Self {
+                    value as $t
+                }
+
+                #[inline(always)]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+            }
+
+            impl TypedSideMetadataSpec<$t> {
+                #[doc = concat!("Create a typed spec whose values are `", stringify!($t), "`.")]
+                #[doc = ""]
+                #[doc = "This panics (or fails to compile in a constant) if the values of the spec"]
+                #[doc = "are of a different width."]
+                pub const fn new(spec: SideMetadataSpec) -> Self {
+                    let log_bits = if spec.log_num_of_bits < 3 {
+                        3
+                    } else {
+                        spec.log_num_of_bits
+                    };
+                    assert!(
+                        log_bits == <$t as MetadataValue>::LOG_BITS,
+                        "The type does not match the bits of the side metadata spec"
+                    );
+                    TypedSideMetadataSpec {
+                        spec,
+                        _value: PhantomData,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_metadata_value!(u8, u16, u32, usize);
+
+impl<T> TypedSideMetadataSpec<T> {
+    /// The untyped spec.
+    pub const fn spec(&self) -> &SideMetadataSpec {
+        &self.spec
+    }
+}
+
+impl<T: MetadataValue> TypedSideMetadataSpec<T> {
+    /// Check that a value fits in the bits of the spec. Storing a larger value in a spec of less
+    /// than 8 bits would corrupt the metadata of the neighbouring regions.
+    #[inline(always)]
+    fn debug_check_value(&self, value: T) {
+        let bits = 1usize << self.spec.log_num_of_bits;
+        debug_assert!(
+            bits >= usize::BITS as usize || value.to_usize() >> bits == 0,
+            "The value {:#x} does not fit in the {} bits of {}",
+            value.to_usize(),
+            bits,
+            self.spec.name
+        );
+    }
+
+    #[inline(always)]
+    pub fn load_atomic(&self, data_addr: Address, order: Ordering) -> T {
+        T::from_usize(global::load_atomic(&self.spec, data_addr, order))
+    }
+
+    #[inline(always)]
+    pub fn store_atomic(&self, data_addr: Address, value: T, order: Ordering) {
+        self.debug_check_value(value);
+        global::store_atomic(&self.spec, data_addr, value.to_usize(), order)
+    }
+
+    #[inline(always)]
+    pub fn compare_exchange_atomic(
+        &self,
+        data_addr: Address,
+        old_value: T,
+        new_value: T,
+        success_order: Ordering,
+        failure_order: Ordering,
+    ) -> bool {
+        self.debug_check_value(new_value);
+        global::compare_exchange_atomic(
+            &self.spec,
+            data_addr,
+            old_value.to_usize(),
+            new_value.to_usize(),
+            success_order,
+            failure_order,
+        )
+    }
+
+    /// Add to the value, and return the old value. This wraps around on overflow.
+    #[inline(always)]
+    pub fn fetch_add_atomic(&self, data_addr: Address, value: T, order: Ordering) -> T {
+        T::from_usize(global::fetch_add_atomic(
+            &self.spec,
+            data_addr,
+            value.to_usize(),
+            order,
+        ))
+    }
+
+    /// Subtract from the value, and return the old value. This wraps around on overflow.
+    #[inline(always)]
+    pub fn fetch_sub_atomic(&self, data_addr: Address, value: T, order: Ordering) -> T {
+        T::from_usize(global::fetch_sub_atomic(
+            &self.spec,
+            data_addr,
+            value.to_usize(),
+            order,
+        ))
+    }
+
+    /// Load the value non-atomically.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`load`](super::load).
+    #[inline(always)]
+    pub unsafe fn load(&self, data_addr: Address) -> T {
+        T::from_usize(global::load(&self.spec, data_addr))
+    }
+
+    /// Store the value non-atomically.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`store`](super::store).
+    #[inline(always)]
+    pub unsafe fn store(&self, data_addr: Address, value: T) {
+        self.debug_check_value(value);
+        global::store(&self.spec, data_addr, value.to_usize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::metadata::side_metadata::{
+        SideMetadataOffset, GLOBAL_SIDE_METADATA_BASE_ADDRESS,
+    };
+
+    const fn spec(log_num_of_bits: usize) -> SideMetadataSpec {
+        SideMetadataSpec {
+            name: "typed_spec",
+            is_global: true,
+            offset: SideMetadataOffset::addr(GLOBAL_SIDE_METADATA_BASE_ADDRESS),
+            log_num_of_bits,
+            log_bytes_in_region: 3,
+        }
+    }
+
+    // A mismatched type in a constant would not compile.
+    const BIT_SPEC: TypedSideMetadataSpec<u8> = TypedSideMetadataSpec::<u8>::new(spec(0));
+    const U32_SPEC: TypedSideMetadataSpec<u32> = TypedSideMetadataSpec::<u32>::new(spec(5));
+
+    #[test]
+    fn test_matching_types() {
+        assert_eq!(BIT_SPEC.spec().log_num_of_bits, 0);
+        assert_eq!(U32_SPEC.spec().log_num_of_bits, 5);
+        assert_eq!(
+            TypedSideMetadataSpec::<u16>::new(spec(4))
+                .spec()
+                .log_num_of_bits,
+            4
+        );
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            TypedSideMetadataSpec::<usize>::new(spec(6))
+                .spec()
+                .log_num_of_bits,
+            6
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The type does not match the bits of the side metadata spec")]
+    fn test_mismatched_type() {
+        TypedSideMetadataSpec::<u8>::new(spec(4));
+    }
+}
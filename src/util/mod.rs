@@ -11,22 +11,43 @@ pub mod address;
 /// Allocators
 // This module is made public so the binding could implement allocator slowpaths if they would like to.
 pub mod alloc;
+/// References embedded in compiled code, which are updated by callbacks of the binding.
+pub mod code_roots;
+/// Groups of objects that the binding wants to be placed together by the next copying GC.
+pub mod colocation;
 /// Constants used in MMTk
 pub mod constants;
 /// Calculation, conversion and rounding for memory related numbers.
 pub mod conversions;
 /// The copy allocators for a GC worker.
 pub mod copy;
+/// Epochs of the safepoints of the mutators, to defer freeing the nodes of lock-free structures.
+pub mod epoch;
+/// Errors returned by the public API.
+pub mod error;
+/// Cumulative GC statistics that are always collected.
+pub mod gc_stats;
 /// Linear scan through a heap range
 pub mod linear_scan;
 /// Wrapper functions for memory syscalls such as mmap, mprotect, etc.
 pub mod memory;
+/// Mutators performing GC work on the allocation slow path during a concurrent phase.
+pub mod mutator_assist;
+/// The layouts of the objects registered by the binding, with which MMTk core scans the objects.
+pub mod object_layout;
 /// Opaque pointers used in MMTk, e.g. VMThread.
 pub mod opaque_pointer;
 /// MMTk command line options.
 pub mod options;
+/// The OS services used by MMTk, which a binding may provide itself.
+pub mod platform;
 /// Reference processing implementation.
 pub mod reference_processor;
+/// Remembered sets of the references between regions, for GCs that collect part of the heap.
+pub mod rememberset;
+/// Transitively pinning the objects reachable from an object.
+#[cfg(feature = "transitive_pinning")]
+pub mod transitive_pin;
 
 // The following modules are only public in the mmtk crate. They should only be used in MMTk core.
 /// Alloc bit
@@ -34,6 +55,8 @@ pub(crate) mod alloc_bit;
 /// An analysis framework for collecting data and profiling in GC.
 #[cfg(feature = "analysis")]
 pub(crate) mod analysis;
+/// Cgroup memory limit detection and memory pressure notification.
+pub(crate) mod cgroup;
 /// Logging edges to check duplicated edges in GC.
 #[cfg(feature = "extreme_assertions")]
 pub(crate) mod edge_logger;
@@ -41,8 +64,25 @@ pub(crate) mod edge_logger;
 pub(crate) mod erase_vm;
 /// Finalization implementation.
 pub(crate) mod finalizable_processor;
+/// GC-critical regions, in which mutators keep the GC from stopping the world.
+pub(crate) mod gc_critical;
+#[cfg(feature = "graph_export")]
+pub mod graph_export;
 /// Heap implementation, including page resource, mmapper, etc.
 pub(crate) mod heap;
+/// Snapshots of the alloc bits and the mark bits of the heap for external tools.
+#[cfg(feature = "heap_bitmap")]
+pub mod heap_bitmap;
+/// The heaps of the isolates that share an MMTk instance.
+#[cfg(feature = "heap_ids")]
+pub mod heap_id;
+/// A timeline of the heap occupancy, sampled at every GC.
+pub mod heap_timeline;
+/// Idle-time GC scheduling (see `memory_manager::notify_idle`).
+pub mod idle_gc;
+/// Objects that the binding declares immutable, which nursery GCs do not scan again.
+#[cfg(feature = "immutable_objects")]
+pub mod immutable_object;
 #[cfg(feature = "is_mmtk_object")]
 pub mod is_mmtk_object;
 /// Logger initialization
@@ -51,18 +91,36 @@ pub(crate) mod logger;
 pub mod malloc;
 /// Metadata (OnSide or InHeader) implementation.
 pub mod metadata;
+/// Record the birth epoch of objects to tell their ages.
+#[cfg(feature = "object_age")]
+pub(crate) mod object_age;
 /// Forwarding word in object copying.
 pub(crate) mod object_forwarding;
+/// Keep the hash state of objects for address-based identity hash codes.
+#[cfg(feature = "object_hash")]
+pub mod object_hash;
+/// Record the first object that starts in each card.
+#[cfg(feature = "object_start_map")]
+pub mod object_start_map;
+/// Keep a user data value for each object on behalf of the binding.
+#[cfg(feature = "object_user_data")]
+pub mod object_user_data;
+/// The thread that triggers GCs periodically (see the option `periodic_gc_ms`).
+pub(crate) mod periodic_gc;
 /// Utilities funcitons for Rust
 pub(crate) mod rust_util;
 /// Sanity checker for GC.
 #[cfg(feature = "sanity")]
 pub(crate) mod sanity;
-/// Utils for collecting statistics.
-pub(crate) mod statistics;
+/// Utils for collecting statistics. Bindings can add their own counters to the statistics
+/// (see `memory_manager::new_event_counter`).
+pub mod statistics;
 /// Test utilities.
 #[cfg(test)]
 pub(crate) mod test_util;
+/// Defer the scanning of objects when the tracing memory is full.
+#[cfg(feature = "tracing_overflow")]
+pub(crate) mod tracing_overflow;
 /// A treadmill implementation.
 pub(crate) mod treadmill;
 
@@ -0,0 +1,229 @@
+//! Mutator assists. During a concurrent phase, a plan can hand small quanta of GC work (e.g.
+//! tracing a few objects or sweeping a block) to the mutators, so the collection keeps up with the
+//! allocation without dedicating more GC worker threads to it. The mutators are taxed for their
+//! allocation: for every `bytes_per_quantum` bytes a mutator allocates in the allocation slow path,
+//! it performs one quantum of the assist work before it returns to the VM.
+//!
+//! A plan starts a concurrent phase with [`MutatorAssist::begin`], adds the work with
+//! [`MutatorAssist::add`], and ends the phase with [`MutatorAssist::end`], which returns the quanta
+//! that the mutators have not performed, so the plan can finish them in its next pause. The GC
+//! workers can take the quanta as well (see [`PerformMutatorAssists`]), so the work is shared by
+//! the mutators that allocate and the workers that are idle.
+//!
+//! The concurrent phase that all the plans have is the time between two GCs. If the options
+//! `mutator_assist_bytes` and `zeroed_block_pool` are both set, the zeroed block pools are refilled
+//! by assists after each GC (see `zeroed_block_pool::RefillZeroedBlock`), and the phase ends when
+//! the next GC starts.
+
+use crate::mmtk::MMTK;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::VMThread;
+use crate::vm::VMBinding;
+use crossbeam::deque::{Injector, Steal};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A quantum of GC work that a mutator performs on the allocation slow path. A quantum should be
+/// small, as it delays the allocation. It may be executed by a mutator thread, so it must not
+/// allocate in the MMTk heap or block for a GC.
+pub trait AssistWork: 'static + Send {
+    /// Perform the work.
+    ///
+    /// Arguments:
+    /// * `tls`: The mutator thread or the GC worker thread that performs the work.
+    fn do_work(&mut self, tls: VMThread);
+}
+
+pub struct MutatorAssist {
+    /// The quanta that have not been performed.
+    queue: Injector<Box<dyn AssistWork>>,
+    /// Is a concurrent phase in progress? Mutators only perform assist work during the phase.
+    active: AtomicBool,
+    /// The allocation tax.
+    tax: AllocationTax,
+}
+
+impl MutatorAssist {
+    pub fn new() -> Self {
+        Self {
+            queue: Injector::new(),
+            active: AtomicBool::new(false),
+            tax: AllocationTax::default(),
+        }
+    }
+
+    /// Start a concurrent phase. Mutators perform one quantum for every `bytes_per_quantum` bytes
+    /// that they allocate from now on.
+    pub fn begin(&self, bytes_per_quantum: usize) {
+        debug_assert!(bytes_per_quantum > 0);
+        self.tax.reset(bytes_per_quantum);
+        self.active.store(true, Ordering::SeqCst);
+    }
+
+    /// End the concurrent phase. Return the quanta that the mutators have not performed.
+    /// Some mutators may still be finishing the quanta that they have taken.
+    pub fn end(&self) -> Vec<Box<dyn AssistWork>> {
+        self.active.store(false, Ordering::SeqCst);
+        let mut remaining = vec![];
+        while let Some(work) = self.take() {
+            remaining.push(work);
+        }
+        remaining
+    }
+
+    /// Add a quantum of assist work.
+    pub fn add(&self, work: Box<dyn AssistWork>) {
+        self.queue.push(work);
+    }
+
+    /// Is a concurrent phase in progress?
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Take a quantum that has not been performed.
+    fn take(&self) -> Option<Box<dyn AssistWork>> {
+        loop {
+            match self.queue.steal() {
+                Steal::Success(work) => return Some(work),
+                Steal::Retry => continue,
+                Steal::Empty => return None,
+            }
+        }
+    }
+
+    /// Called on the allocation slow path of a mutator after it allocated `bytes`. The mutator
+    /// pays its allocation tax by performing the quanta it owes. If there are not enough
+    /// quanta, the rest of the tax is waived.
+    pub fn on_allocation(&self, tls: VMThread, bytes: usize) {
+        if !self.is_active() {
+            return;
+        }
+        let owed = self.tax.charge(bytes);
+        for _ in 0..owed {
+            match self.take() {
+                Some(mut work) => work.do_work(tls),
+                None => break,
+            }
+        }
+    }
+}
+
+impl Default for MutatorAssist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The allocation tax of all the mutators. The bytes that have been allocated but not paid for
+/// are carried over to the next allocation.
+#[derive(Default)]
+struct AllocationTax {
+    /// How many bytes a mutator may allocate for each quantum it performs.
+    bytes_per_quantum: AtomicUsize,
+    /// The bytes allocated that have not been paid for.
+    debt: AtomicUsize,
+}
+
+impl AllocationTax {
+    fn reset(&self, bytes_per_quantum: usize) {
+        self.bytes_per_quantum
+            .store(bytes_per_quantum, Ordering::Relaxed);
+        self.debt.store(0, Ordering::Relaxed);
+    }
+
+    /// Charge the tax for allocating `bytes`. Return the number of quanta that need to be
+    /// performed.
+    fn charge(&self, bytes: usize) -> usize {
+        let bytes_per_quantum = self.bytes_per_quantum.load(Ordering::Relaxed);
+        if bytes_per_quantum == 0 {
+            return 0;
+        }
+        let mut owed = 0;
+        let _ = self
+            .debt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |debt| {
+                let debt = debt + bytes;
+                owed = debt / bytes_per_quantum;
+                Some(debt % bytes_per_quantum)
+            });
+        owed
+    }
+}
+
+/// A background packet that performs the assist quanta that the mutators have not taken yet, while
+/// the GC workers are idle.
+pub struct PerformMutatorAssists;
+
+impl<VM: VMBinding> GCWork<VM> for PerformMutatorAssists {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let assist = &mmtk.plan.base().mutator_assist;
+        while assist.is_active() {
+            match assist.take() {
+                Some(mut work) => work.do_work(worker.tls.0),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    struct CountQuantum(Arc<AtomicUsize>);
+
+    impl AssistWork for CountQuantum {
+        fn do_work(&mut self, _tls: VMThread) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_allocation_tax() {
+        let tax = AllocationTax::default();
+        // No tax outside a concurrent phase.
+        assert_eq!(tax.charge(1 << 20), 0);
+        tax.reset(1000);
+        assert_eq!(tax.charge(600), 0);
+        // The debt is carried over.
+        assert_eq!(tax.charge(600), 1);
+        assert_eq!(tax.charge(2800), 3);
+        assert_eq!(tax.debt.load(Ordering::Relaxed), 0);
+        // Resetting the tax clears the debt.
+        assert_eq!(tax.charge(999), 0);
+        tax.reset(500);
+        assert_eq!(tax.charge(499), 0);
+    }
+
+    #[test]
+    fn test_mutator_assist() {
+        let assist = MutatorAssist::new();
+        let performed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..5 {
+            assist.add(Box::new(CountQuantum(performed.clone())));
+        }
+        // No quanta are performed outside a concurrent phase.
+        assist.on_allocation(VMThread::UNINITIALIZED, 1 << 20);
+        assert_eq!(performed.load(Ordering::SeqCst), 0);
+
+        assist.begin(1000);
+        assist.on_allocation(VMThread::UNINITIALIZED, 500);
+        assert_eq!(performed.load(Ordering::SeqCst), 0);
+        // The mutator pays for the bytes of both allocations.
+        assist.on_allocation(VMThread::UNINITIALIZED, 2500);
+        assert_eq!(performed.load(Ordering::SeqCst), 3);
+        // The tax for more quanta than there are is waived.
+        assist.on_allocation(VMThread::UNINITIALIZED, 10_000);
+        assert_eq!(performed.load(Ordering::SeqCst), 5);
+        assert!(assist.end().is_empty());
+
+        // The quanta that are not performed in the phase are returned when it ends.
+        assist.begin(1000);
+        assist.add(Box::new(CountQuantum(performed.clone())));
+        assert_eq!(assist.end().len(), 1);
+        assert!(!assist.is_active());
+        assert_eq!(performed.load(Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,73 @@
+//! Record the GC epoch in which each object is allocated (its birth epoch), so we can tell
+//! the age of an object, i.e. the number of GCs it has survived.
+//!
+//! The epoch is the number of GCs that have finished. It is stored in a side metadata byte per
+//! object, so it wraps around every 256 GCs, and so does the age of an object.
+
+use crate::util::constants::LOG_MIN_OBJECT_SIZE;
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use std::sync::atomic::Ordering;
+
+/// The birth epoch is recorded per `1 << LOG_BYTES_IN_REGION` bytes. On 32 bits, we use 16-byte regions
+/// (instead of the min object size) to keep the side metadata within the space reserved for global side metadata.
+/// Objects that are smaller than 16 bytes and share a region share the birth epoch.
+#[cfg(target_pointer_width = "64")]
+pub(crate) const LOG_BYTES_IN_REGION: usize = LOG_MIN_OBJECT_SIZE as usize;
+#[cfg(target_pointer_width = "32")]
+pub(crate) const LOG_BYTES_IN_REGION: usize = 4;
+
+pub(crate) const OBJECT_AGE_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::OBJECT_AGE;
+
+/// Record the birth epoch for a newly allocated object.
+#[inline(always)]
+pub fn set_birth_epoch(object: ObjectReference, epoch: u64) {
+    side_metadata::store_atomic(
+        &OBJECT_AGE_SIDE_METADATA_SPEC,
+        object.to_address(),
+        epoch as u8 as usize,
+        Ordering::Relaxed,
+    );
+}
+
+/// Get the birth epoch (modulo 256) of an object.
+#[inline(always)]
+pub fn get_birth_epoch(object: ObjectReference) -> u8 {
+    side_metadata::load_atomic(
+        &OBJECT_AGE_SIDE_METADATA_SPEC,
+        object.to_address(),
+        Ordering::Relaxed,
+    ) as u8
+}
+
+/// Keep the birth epoch of an object when the object is moved.
+#[inline(always)]
+pub fn copy_birth_epoch(from: ObjectReference, to: ObjectReference) {
+    side_metadata::store_atomic(
+        &OBJECT_AGE_SIDE_METADATA_SPEC,
+        to.to_address(),
+        get_birth_epoch(from) as usize,
+        Ordering::Relaxed,
+    );
+}
+
+/// Get the age of an object with the given birth epoch, at the given epoch.
+#[inline(always)]
+pub fn age(birth_epoch: u8, current_epoch: u64) -> u8 {
+    (current_epoch as u8).wrapping_sub(birth_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age() {
+        assert_eq!(age(0, 0), 0);
+        assert_eq!(age(3, 10), 7);
+        // The epoch wraps around every 256 GCs.
+        assert_eq!(age(250, 260), 10);
+        assert_eq!(age(0, 256), 0);
+    }
+}
@@ -21,6 +21,23 @@ const FORWARDING_POINTER_MASK: usize = 0x00ff_ffff_ffff_fff8;
 #[cfg(target_pointer_width = "32")]
 const FORWARDING_POINTER_MASK: usize = 0xffff_fffc;
 
+/// The number of times a thread polls the forwarding bits of an object that is being forwarded by
+/// another thread before it starts to yield between the polls. Copying an object is usually short,
+/// so the thread first spins, and only yields the CPU if the forwarding thread is slow (e.g. it
+/// is descheduled, or it copies a large object).
+const SPINS_BEFORE_YIELD: usize = 1 << 10;
+
+/// The contention on forwarding objects. Each GC worker counts its contention in its copy context
+/// (see `GCWorkerCopyContext::take_forwarding_contention()`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ForwardingContention {
+    /// The number of times a thread found that another thread was forwarding the object, and had
+    /// to wait for it.
+    pub lost_races: usize,
+    /// The number of times a waiting thread yielded the CPU.
+    pub yields: usize,
+}
+
 /// Attempt to become the worker thread who will forward the object.
 /// The successful worker will set the object forwarding bits to BEING_FORWARDED, preventing other workers from forwarding the same object.
 pub fn attempt_to_forward<VM: VMBinding>(object: ObjectReference) -> usize {
@@ -42,22 +59,36 @@ pub fn attempt_to_forward<VM: VMBinding>(object: ObjectReference) -> usize {
     }
 }
 
-/// Spin-wait for the object's forwarding to become complete and then read the forwarding pointer to the new object.
+/// Wait for the object's forwarding to become complete and then read the forwarding pointer to the new object.
+/// The thread spins for a while (see `SPINS_BEFORE_YIELD`), and then yields between the polls of the forwarding bits.
 ///
 /// # Arguments:
 ///
 /// * `object`: the forwarded/being_forwarded object.
 /// * `forwarding_bits`: the last state of the forwarding bits before calling this function.
+/// * `contention`: the forwarding contention of the calling GC worker, which counts the wait.
 ///
 /// Returns a reference to the new object.
 ///
 pub fn spin_and_get_forwarded_object<VM: VMBinding>(
     object: ObjectReference,
     forwarding_bits: usize,
+    contention: &mut ForwardingContention,
 ) -> ObjectReference {
     let mut forwarding_bits = forwarding_bits;
-    while forwarding_bits == BEING_FORWARDED {
-        forwarding_bits = get_forwarding_status::<VM>(object);
+    if forwarding_bits == BEING_FORWARDED {
+        contention.lost_races += 1;
+        let mut spins = 0;
+        while forwarding_bits == BEING_FORWARDED {
+            if spins < SPINS_BEFORE_YIELD {
+                spins += 1;
+                std::hint::spin_loop();
+            } else {
+                contention.yields += 1;
+                crate::util::platform::platform().yield_now();
+            }
+            forwarding_bits = get_forwarding_status::<VM>(object);
+        }
     }
 
     if forwarding_bits == FORWARDED {
@@ -84,6 +115,21 @@ pub fn forward_object<VM: VMBinding>(
     let new_object = VM::VMObjectModel::copy(object, semantics, copy_context);
     #[cfg(feature = "global_alloc_bit")]
     crate::util::alloc_bit::set_alloc_bit(new_object);
+    #[cfg(feature = "object_age")]
+    crate::util::object_age::copy_birth_epoch(object, new_object);
+    #[cfg(feature = "object_hash")]
+    crate::util::object_hash::fixup_after_copy::<VM>(object, new_object);
+    #[cfg(feature = "object_user_data")]
+    crate::util::object_user_data::copy_user_data(object, new_object);
+    #[cfg(feature = "heap_ids")]
+    crate::util::heap_id::copy_heap_id(object, new_object);
+    #[cfg(feature = "immutable_objects")]
+    crate::util::immutable_object::copy_state(object, new_object);
+    #[cfg(feature = "graph_export")]
+    <VM::VMActivePlan as crate::vm::ActivePlan<VM>>::global()
+        .base()
+        .graph_exporter
+        .object_moved(&mut copy_context.graph_buffer, object, new_object);
     if let Some(shift) = forwarding_bits_offset_in_forwarding_pointer::<VM>() {
         store_metadata::<VM>(
             &VM::VMObjectModel::LOCAL_FORWARDING_POINTER_SPEC,
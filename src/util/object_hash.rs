@@ -0,0 +1,150 @@
+//! Address-based identity hash codes that survive copying.
+//!
+//! The hash code of an object is its address when the hash code is first taken. MMTk keeps a hash
+//! state for each object in side metadata, so the VM does not need header bits for it:
+//!
+//! * `Unhashed`: The hash code has not been taken.
+//! * `Hashed`: The hash code has been taken, and the object has not moved since, so the hash code
+//!   is the address of the object.
+//! * `HashedAndMoved`: The object has moved since its hash code was taken. When a `Hashed` object
+//!   is copied, MMTk stores its old address in an extra word of the copy (see
+//!   `ObjectModel::hash_word_address`), and the hash code is read from that word from then on.
+//!
+//! The VM must account for the extra word in the object size (see `hash_extra_bytes` and
+//! `hash_extra_bytes_when_copied`).
+
+use crate::util::constants::BYTES_IN_WORD;
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+use std::sync::atomic::Ordering;
+
+/// The hash state is recorded per `1 << LOG_BYTES_IN_REGION` bytes, so objects need to be at least
+/// 8 bytes apart to have their own hash states.
+pub(crate) const LOG_BYTES_IN_REGION: usize = 3;
+
+pub(crate) const HASH_STATE_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::HASH_STATE;
+
+/// The hash state of an object.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashState {
+    Unhashed = 0,
+    Hashed = 1,
+    HashedAndMoved = 2,
+}
+
+impl From<usize> for HashState {
+    #[inline(always)]
+    fn from(value: usize) -> Self {
+        match value {
+            0 => HashState::Unhashed,
+            1 => HashState::Hashed,
+            2 => HashState::HashedAndMoved,
+            _ => unreachable!("Invalid hash state {}", value),
+        }
+    }
+}
+
+/// Get the hash state of an object.
+#[inline(always)]
+pub fn get_hash_state(object: ObjectReference) -> HashState {
+    side_metadata::load_atomic(
+        &HASH_STATE_SIDE_METADATA_SPEC,
+        object.to_address(),
+        Ordering::SeqCst,
+    )
+    .into()
+}
+
+#[inline(always)]
+fn set_hash_state(object: ObjectReference, state: HashState) {
+    side_metadata::store_atomic(
+        &HASH_STATE_SIDE_METADATA_SPEC,
+        object.to_address(),
+        state as usize,
+        Ordering::SeqCst,
+    );
+}
+
+/// Reset the hash state for a newly allocated object. The memory may have been used by an object
+/// that was hashed.
+#[inline(always)]
+pub fn clear_hash_state(object: ObjectReference) {
+    set_hash_state(object, HashState::Unhashed);
+}
+
+/// Get the identity hash code of an object, and mark the object as hashed if it was not.
+#[inline(always)]
+pub fn identity_hash<VM: VMBinding>(object: ObjectReference) -> usize {
+    if get_hash_state(object) == HashState::Unhashed {
+        // Another thread may have hashed the object already, which is fine.
+        side_metadata::compare_exchange_atomic(
+            &HASH_STATE_SIDE_METADATA_SPEC,
+            object.to_address(),
+            HashState::Unhashed as usize,
+            HashState::Hashed as usize,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+    match get_hash_state(object) {
+        HashState::Unhashed => unreachable!(),
+        HashState::Hashed => object.to_address().as_usize(),
+        HashState::HashedAndMoved => unsafe {
+            VM::VMObjectModel::hash_word_address(object).load::<usize>()
+        },
+    }
+}
+
+/// The extra bytes that the current copy of the object has for the hash code.
+#[inline(always)]
+pub fn hash_extra_bytes(object: ObjectReference) -> usize {
+    match get_hash_state(object) {
+        HashState::HashedAndMoved => BYTES_IN_WORD,
+        _ => 0,
+    }
+}
+
+/// The extra bytes that a new copy of the object needs for the hash code.
+#[inline(always)]
+pub fn hash_extra_bytes_when_copied(object: ObjectReference) -> usize {
+    match get_hash_state(object) {
+        HashState::Unhashed => 0,
+        _ => BYTES_IN_WORD,
+    }
+}
+
+/// Update the hash state of the new copy of an object, and store the hash code of the object in
+/// the copy if the object is moved for the first time since it was hashed.
+#[inline(always)]
+pub fn fixup_after_copy<VM: VMBinding>(from: ObjectReference, to: ObjectReference) {
+    match get_hash_state(from) {
+        HashState::Hashed => {
+            // Set the state first, so the VM sees the extra word in the object size.
+            set_hash_state(to, HashState::HashedAndMoved);
+            unsafe {
+                VM::VMObjectModel::hash_word_address(to).store(from.to_address().as_usize());
+            }
+        }
+        state => set_hash_state(to, state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_state_encoding() {
+        for state in [
+            HashState::Unhashed,
+            HashState::Hashed,
+            HashState::HashedAndMoved,
+        ] {
+            assert_eq!(HashState::from(state as usize), state);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Object layouts cached by MMTk core. A binding may register the layout of each type of objects
+//! whose reference fields are at fixed offsets, keyed by a type id that MMTk core can read from
+//! the object header (see `Scanning::LAYOUT_TYPE_ID_OFFSET`). MMTk core then enqueues the edges of
+//! the objects of the registered types from the layouts, without calling back to the binding for
+//! each object. This is mostly useful for bindings whose callbacks cross a language boundary.
+//!
+//! The layouts are immutable once registered. They are usually registered when the types are
+//! loaded, before any object of the types is allocated.
+
+use crate::util::constants::{BITS_IN_WORD, BYTES_IN_WORD};
+use crate::util::ObjectReference;
+use crate::vm::RefFieldBitmap;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// The reference fields of a type of objects, described as a [`RefFieldBitmap`] for each
+/// `BITS_IN_WORD` words from `offset` bytes from the object reference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectLayout {
+    /// The offset of the first word described by the bitmaps, from the object reference.
+    offset: isize,
+    bitmaps: Vec<usize>,
+}
+
+impl ObjectLayout {
+    /// Create a layout from the byte offsets of the reference fields from the object reference.
+    /// The offsets must be word-aligned.
+    pub fn from_field_offsets(offsets: &[isize]) -> Self {
+        let offset = offsets.iter().copied().min().unwrap_or(0);
+        let mut bitmaps = vec![];
+        for field in offsets {
+            let distance = (field - offset) as usize;
+            assert!(
+                distance % BYTES_IN_WORD == 0,
+                "The reference field at offset {} is not word-aligned",
+                field
+            );
+            let index = distance / BYTES_IN_WORD;
+            if bitmaps.len() <= index / RefFieldBitmap::MAX_WORDS {
+                bitmaps.resize(index / RefFieldBitmap::MAX_WORDS + 1, 0);
+            }
+            bitmaps[index / RefFieldBitmap::MAX_WORDS] |= 1 << (index % RefFieldBitmap::MAX_WORDS);
+        }
+        Self { offset, bitmaps }
+    }
+
+    /// The number of reference fields.
+    pub fn num_fields(&self) -> usize {
+        self.bitmaps.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// The reference fields of an object, as the bitmaps that MMTk core also uses for the objects
+    /// described by `Scanning::reference_field_bitmap`.
+    #[inline(always)]
+    pub fn ref_field_bitmaps(
+        &self,
+        object: ObjectReference,
+    ) -> impl Iterator<Item = RefFieldBitmap> + '_ {
+        let start = object.to_address() + self.offset;
+        self.bitmaps
+            .iter()
+            .enumerate()
+            .filter(|(_, &bitmap)| bitmap != 0)
+            .map(move |(i, &bitmap)| {
+                RefFieldBitmap::new(start + i * BITS_IN_WORD * BYTES_IN_WORD, bitmap)
+            })
+    }
+}
+
+/// A slot in the table of the layouts. A slot is claimed for a type id once, and never freed.
+struct LayoutSlot {
+    /// The type id, or 0 if the slot is free.
+    type_id: AtomicUsize,
+    /// The layout of the type, or null if it is being registered.
+    layout: AtomicPtr<ObjectLayout>,
+}
+
+/// The layouts registered by the binding, by their type ids. The objects are scanned in parallel,
+/// and each scanned object looks up its layout, so the layouts are kept in a lock-free open
+/// addressing hash table, whose size is fixed when the MMTk instance is created. The table is
+/// at most half full, as at most `max_object_layouts` layouts can be registered.
+pub(crate) struct ObjectLayouts {
+    slots: Box<[LayoutSlot]>,
+    /// The log of the number of slots.
+    log_slots: u32,
+    /// The maximum number of layouts (the option `max_object_layouts`).
+    max_layouts: usize,
+    registered: AtomicUsize,
+}
+
+impl ObjectLayouts {
+    /// Create the table for at most `max_layouts` layouts. No memory is used if `max_layouts` is
+    /// 0, e.g. if the binding does not register layouts.
+    pub fn new(max_layouts: usize) -> Self {
+        let num_slots = if max_layouts == 0 {
+            0
+        } else {
+            (max_layouts * 2).next_power_of_two()
+        };
+        Self {
+            slots: (0..num_slots)
+                .map(|_| LayoutSlot {
+                    type_id: AtomicUsize::new(0),
+                    layout: AtomicPtr::new(std::ptr::null_mut()),
+                })
+                .collect(),
+            log_slots: num_slots.trailing_zeros(),
+            max_layouts,
+            registered: AtomicUsize::new(0),
+        }
+    }
+
+    /// The first slot to probe for a type id: the high bits of a multiplicative hash.
+    #[inline(always)]
+    fn first_slot(&self, type_id: usize) -> usize {
+        const MULTIPLIER: u64 = 0x9E37_79B9_7F4A_7C15;
+        let hash = (type_id as u64).wrapping_mul(MULTIPLIER);
+        (hash >> (u64::BITS - self.log_slots)) as usize
+    }
+
+    /// Register the layout of a type. Panic if the type already has a different layout, as the
+    /// objects of the type may be being scanned with the old layout.
+    pub fn register(&self, type_id: usize, layout: ObjectLayout) {
+        assert!(type_id != 0, "The type id 0 cannot have a layout");
+        assert!(
+            !self.slots.is_empty(),
+            "Object layouts are not enabled (see Scanning::LAYOUT_TYPE_ID_OFFSET)"
+        );
+        let mask = self.slots.len() - 1;
+        let mut index = self.first_slot(type_id);
+        for _ in 0..self.slots.len() {
+            let slot = &self.slots[index];
+            let current =
+                match slot
+                    .type_id
+                    .compare_exchange(0, type_id, Ordering::AcqRel, Ordering::Acquire)
+                {
+                    Ok(_) => {
+                        let registered = self.registered.fetch_add(1, Ordering::Relaxed) + 1;
+                        assert!(
+                        registered <= self.max_layouts,
+                        "More than {} layouts are registered (see the option max_object_layouts)",
+                        self.max_layouts
+                    );
+                        type_id
+                    }
+                    Err(current) => current,
+                };
+            if current == type_id {
+                let new = Box::into_raw(Box::new(layout));
+                if let Err(old) = slot.layout.compare_exchange(
+                    std::ptr::null_mut(),
+                    new,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    let new = unsafe { Box::from_raw(new) };
+                    assert!(
+                        unsafe { &*old } == &*new,
+                        "Type {:#x} already has a different layout",
+                        type_id
+                    );
+                }
+                return;
+            }
+            index = (index + 1) & mask;
+        }
+        unreachable!("The table of the object layouts is full");
+    }
+
+    /// The layout of a type, if it is registered.
+    #[inline(always)]
+    pub fn get(&self, type_id: usize) -> Option<&ObjectLayout> {
+        if self.slots.is_empty() || type_id == 0 {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        let mut index = self.first_slot(type_id);
+        // The table is never full, so there is always a free slot to end the probing.
+        loop {
+            let slot = &self.slots[index];
+            match slot.type_id.load(Ordering::Acquire) {
+                0 => return None,
+                id if id == type_id => {
+                    return unsafe { slot.layout.load(Ordering::Acquire).as_ref() };
+                }
+                _ => index = (index + 1) & mask,
+            }
+        }
+    }
+}
+
+impl Drop for ObjectLayouts {
+    fn drop(&mut self) {
+        for slot in self.slots.iter() {
+            let layout = slot.layout.load(Ordering::Relaxed);
+            if !layout.is_null() {
+                drop(unsafe { Box::from_raw(layout) });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Address;
+
+    #[test]
+    fn test_field_addresses() {
+        let word = BYTES_IN_WORD as isize;
+        let offsets = [-word, 2 * word, (BITS_IN_WORD as isize + 1) * word];
+        let layout = ObjectLayout::from_field_offsets(&offsets);
+        assert_eq!(layout.num_fields(), 3);
+        let object = unsafe { Address::from_usize(0x1000).to_object_reference() };
+        let fields: Vec<Address> = layout
+            .ref_field_bitmaps(object)
+            .flat_map(|bitmap| bitmap.field_addresses())
+            .collect();
+        let expected: Vec<Address> = offsets
+            .iter()
+            .map(|&offset| object.to_address() + offset)
+            .collect();
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_register_and_get() {
+        let layouts = ObjectLayouts::new(4);
+        assert!(layouts.get(0x1000).is_none());
+        for type_id in 1..=4 {
+            layouts.register(type_id * 0x1000, ObjectLayout::from_field_offsets(&[0]));
+        }
+        // Registering the same layout again is fine.
+        layouts.register(0x1000, ObjectLayout::from_field_offsets(&[0]));
+        for type_id in 1..=4 {
+            assert_eq!(
+                layouts.get(type_id * 0x1000),
+                Some(&ObjectLayout::from_field_offsets(&[0]))
+            );
+        }
+        assert!(layouts.get(0x5000).is_none());
+        assert!(layouts.get(0).is_none());
+
+        // There are no layouts without a table.
+        assert!(ObjectLayouts::new(0).get(0x1000).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "already has a different layout")]
+    fn test_register_different_layout() {
+        let layouts = ObjectLayouts::new(1);
+        layouts.register(1, ObjectLayout::from_field_offsets(&[0]));
+        layouts.register(1, ObjectLayout::from_field_offsets(&[8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_object_layouts")]
+    fn test_register_too_many() {
+        let layouts = ObjectLayouts::new(1);
+        layouts.register(1, ObjectLayout::from_field_offsets(&[0]));
+        layouts.register(2, ObjectLayout::from_field_offsets(&[0]));
+    }
+}
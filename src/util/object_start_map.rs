@@ -0,0 +1,280 @@
+//! An object start map, which records the first object that starts in each card of 512 bytes.
+//! The map lets card scanning start from the first object in a card, and conservative pointer
+//! resolution find the object before an interior pointer, without scanning the alloc bits from the
+//! start of the chunk.
+//!
+//! The map is a side metadata byte per card, which is an offset in the card (in units of the
+//! minimum object size) plus one, or zero if no object starts in the card. No object starts in
+//! the card before the offset, so the first object is found with one load of the alloc bits of
+//! the card from the offset. An entry is only lowered, with a compare and swap, when an alloc bit
+//! is set, and clearing an alloc bit leaves the entry as it is: a racing update can never raise
+//! an entry over an object. The entries are only reset to zero for the cards in a range whose
+//! alloc bits are cleared at once, in which no object is allocated concurrently.
+
+use crate::util::alloc_bit;
+use crate::util::constants::{BITS_IN_WORD, BYTES_IN_WORD, LOG_MIN_OBJECT_SIZE};
+use crate::util::heap::layout::Mmapper;
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::{Address, ObjectReference};
+use crate::MMAPPER;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// log2 of the bytes of a card.
+pub const LOG_BYTES_IN_CARD: usize = 9;
+/// The bytes of a card.
+pub const BYTES_IN_CARD: usize = 1 << LOG_BYTES_IN_CARD;
+
+/// The alloc bits of a card, a bit per minimum object size.
+const ALLOC_BITS_IN_CARD: usize = BYTES_IN_CARD >> LOG_MIN_OBJECT_SIZE;
+/// The words of the alloc bits of a card.
+const ALLOC_WORDS_IN_CARD: usize = ALLOC_BITS_IN_CARD / BITS_IN_WORD;
+
+pub(crate) const OBJECT_START_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::OBJECT_START;
+
+/// The entry of the card of `address` if the first object in the card starts at `address`.
+#[inline(always)]
+fn entry_for(address: Address) -> usize {
+    ((address - address.align_down(BYTES_IN_CARD)) >> LOG_MIN_OBJECT_SIZE) + 1
+}
+
+/// The address of the alloc bit `index` of the card.
+#[inline(always)]
+fn address_of(card: Address, index: usize) -> Address {
+    card + (index << LOG_MIN_OBJECT_SIZE)
+}
+
+/// Load the alloc bits of a card. Bit `i` of the result is the alloc bit of the address `i`
+/// minimum object sizes into the card.
+#[inline(always)]
+fn load_alloc_bits(card: Address) -> [usize; ALLOC_WORDS_IN_CARD] {
+    // A card covers whole words of the alloc bits.
+    let meta_addr =
+        side_metadata::address_to_meta_address(&alloc_bit::ALLOC_SIDE_METADATA_SPEC, card);
+    let mut words = [0; ALLOC_WORDS_IN_CARD];
+    for (i, word) in words.iter_mut().enumerate() {
+        // The bits are stored from the lowest bit of the first byte.
+        *word = usize::from_le(unsafe {
+            (meta_addr + i * BYTES_IN_WORD).atomic_load::<AtomicUsize>(Ordering::SeqCst)
+        });
+    }
+    words
+}
+
+/// The index of the first alloc bit that is set at or after the index `from`.
+fn first_alloc_bit(bits: &[usize; ALLOC_WORDS_IN_CARD], from: usize) -> Option<usize> {
+    (from / BITS_IN_WORD..ALLOC_WORDS_IN_CARD).find_map(|i| {
+        let word = if i == from / BITS_IN_WORD {
+            bits[i] & (usize::MAX << (from % BITS_IN_WORD))
+        } else {
+            bits[i]
+        };
+        (word != 0).then(|| i * BITS_IN_WORD + word.trailing_zeros() as usize)
+    })
+}
+
+/// The index of the last alloc bit that is set at or before the index `to`.
+fn last_alloc_bit(bits: &[usize; ALLOC_WORDS_IN_CARD], to: usize) -> Option<usize> {
+    (0..=to / BITS_IN_WORD).rev().find_map(|i| {
+        let word = if i == to / BITS_IN_WORD {
+            bits[i] & (usize::MAX >> (BITS_IN_WORD - 1 - to % BITS_IN_WORD))
+        } else {
+            bits[i]
+        };
+        (word != 0).then(|| i * BITS_IN_WORD + BITS_IN_WORD - 1 - word.leading_zeros() as usize)
+    })
+}
+
+/// Record an object that starts at `address`, i.e. whose alloc bit is set at `address`.
+#[inline(always)]
+pub(crate) fn record_object_start(address: Address) {
+    let entry = entry_for(address);
+    let spec = &OBJECT_START_SIDE_METADATA_SPEC;
+    // Objects are usually allocated in the order of their addresses, so the card usually has a
+    // smaller entry already.
+    let mut old = side_metadata::load_atomic(spec, address, Ordering::SeqCst);
+    while old == 0 || old > entry {
+        if side_metadata::compare_exchange_atomic(
+            spec,
+            address,
+            old,
+            entry,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            return;
+        }
+        old = side_metadata::load_atomic(spec, address, Ordering::SeqCst);
+    }
+}
+
+/// Update the cards of `[start, start + size)` after the alloc bits of the range are cleared.
+/// Nothing may be allocated in the range concurrently.
+pub(crate) fn clear_range(start: Address, size: usize) {
+    // No object starts in the cards that are entirely in the range. The entries of the cards at
+    // the ends of the range are still at or before their first objects.
+    let full_start = start.align_up(BYTES_IN_CARD);
+    let full_end = (start + size).align_down(BYTES_IN_CARD);
+    if full_start < full_end {
+        side_metadata::bzero_metadata(
+            &OBJECT_START_SIDE_METADATA_SPEC,
+            full_start,
+            full_end - full_start,
+        );
+    }
+}
+
+/// Is the object start map of the card mapped? The map is mapped with the other global side
+/// metadata of a chunk when a space acquires the chunk.
+fn is_card_mapped(card: Address) -> bool {
+    let meta_addr = side_metadata::address_to_meta_address(&OBJECT_START_SIDE_METADATA_SPEC, card);
+    MMAPPER.is_mapped_address(meta_addr)
+}
+
+/// The first object that starts in the card of `address`, or `None` if no object starts in the
+/// card, or the card is not in the heap.
+pub fn first_object_in_card(address: Address) -> Option<ObjectReference> {
+    let card = address.align_down(BYTES_IN_CARD);
+    if !is_card_mapped(card) {
+        return None;
+    }
+    let entry =
+        side_metadata::load_atomic(&OBJECT_START_SIDE_METADATA_SPEC, card, Ordering::SeqCst);
+    if entry == 0 {
+        return None;
+    }
+    first_alloc_bit(&load_alloc_bits(card), entry - 1)
+        .map(|index| unsafe { address_of(card, index).to_object_reference() })
+}
+
+/// Find the object with the highest address at or before `address`, searching back at most
+/// `max_search_bytes`. Return `None` if there is no such object in the search range. The object
+/// is the only object that may contain `address`, but it does not necessarily contain it: the
+/// caller checks the size of the object.
+pub fn find_object_before(address: Address, max_search_bytes: usize) -> Option<ObjectReference> {
+    let limit = address.as_usize().saturating_sub(max_search_bytes);
+    let mut card = address.align_down(BYTES_IN_CARD);
+    let mut to = (address - card) >> LOG_MIN_OBJECT_SIZE;
+    loop {
+        if is_card_mapped(card) {
+            let entry = side_metadata::load_atomic(
+                &OBJECT_START_SIDE_METADATA_SPEC,
+                card,
+                Ordering::SeqCst,
+            );
+            // The card may have an object at or before `to`.
+            if entry != 0 && entry - 1 <= to {
+                if let Some(index) = last_alloc_bit(&load_alloc_bits(card), to) {
+                    let object = address_of(card, index);
+                    return if object.as_usize() >= limit {
+                        Some(unsafe { object.to_object_reference() })
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+        if card.as_usize() <= limit || card.as_usize() < BYTES_IN_CARD {
+            return None;
+        }
+        card -= BYTES_IN_CARD;
+        to = ALLOC_BITS_IN_CARD - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::MIN_OBJECT_SIZE;
+    use crate::util::heap::layout::vm_layout_constants::{BYTES_IN_CHUNK, HEAP_START};
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    #[test]
+    fn test_entry() {
+        let card = unsafe { Address::from_usize(0x1000_0000) };
+        assert_eq!(entry_for(card), 1);
+        for offset in [MIN_OBJECT_SIZE, BYTES_IN_CARD - MIN_OBJECT_SIZE] {
+            let entry = entry_for(card + offset);
+            assert_eq!(address_of(card, entry - 1), card + offset);
+        }
+        // The entries fit in the byte of a card.
+        assert!(entry_for(card + (BYTES_IN_CARD - MIN_OBJECT_SIZE)) <= u8::MAX as usize);
+    }
+
+    #[test]
+    fn test_find_alloc_bits() {
+        let mut bits = [0; ALLOC_WORDS_IN_CARD];
+        let last = ALLOC_BITS_IN_CARD - 1;
+        assert_eq!(first_alloc_bit(&bits, 0), None);
+        assert_eq!(last_alloc_bit(&bits, last), None);
+        bits[0] = 0b1010;
+        bits[ALLOC_WORDS_IN_CARD - 1] = 1 << (BITS_IN_WORD - 1);
+        assert_eq!(first_alloc_bit(&bits, 0), Some(1));
+        assert_eq!(first_alloc_bit(&bits, 2), Some(3));
+        assert_eq!(first_alloc_bit(&bits, 4), Some(last));
+        assert_eq!(last_alloc_bit(&bits, last), Some(last));
+        assert_eq!(last_alloc_bit(&bits, last - 1), Some(3));
+        assert_eq!(last_alloc_bit(&bits, 2), Some(1));
+        assert_eq!(last_alloc_bit(&bits, 0), None);
+    }
+
+    #[test]
+    fn test_entry_stays_before_objects() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![
+                    alloc_bit::ALLOC_SIDE_METADATA_SPEC,
+                    OBJECT_START_SIDE_METADATA_SPEC,
+                ],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_CHUNK)
+                        .unwrap();
+                    let card = HEAP_START + BYTES_IN_CARD;
+                    let first_object = || {
+                        let entry = side_metadata::load_atomic(
+                            &OBJECT_START_SIDE_METADATA_SPEC,
+                            card,
+                            Ordering::SeqCst,
+                        );
+                        if entry == 0 {
+                            return None;
+                        }
+                        first_alloc_bit(&load_alloc_bits(card), entry - 1)
+                            .map(|index| address_of(card, index))
+                    };
+                    let a = unsafe { (card + MIN_OBJECT_SIZE).to_object_reference() };
+                    let b = unsafe { (card + 3 * MIN_OBJECT_SIZE).to_object_reference() };
+                    alloc_bit::set_alloc_bit(b);
+                    alloc_bit::set_alloc_bit(a);
+                    assert_eq!(first_object(), Some(a.to_address()));
+                    // Clearing the alloc bit of the first object does not raise the entry, so the
+                    // next object is still found, even if it was recorded concurrently.
+                    alloc_bit::unset_alloc_bit(a);
+                    assert_eq!(first_object(), Some(b.to_address()));
+                    alloc_bit::unset_alloc_bit(b);
+                    assert_eq!(first_object(), None);
+
+                    // The cards that are entirely in a cleared range are reset.
+                    alloc_bit::set_alloc_bit(b);
+                    alloc_bit::bzero_alloc_bit(card, BYTES_IN_CARD);
+                    assert_eq!(
+                        side_metadata::load_atomic(
+                            &OBJECT_START_SIDE_METADATA_SPEC,
+                            card,
+                            Ordering::SeqCst
+                        ),
+                        0
+                    );
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+}
@@ -0,0 +1,139 @@
+//! A user data value for each object, kept by MMTk in side metadata, so a binding can attach
+//! auxiliary data to objects (e.g. profiling ids or lock words) without growing the object header.
+//! MMTk copies the value with the object when the object is moved, and clears it for the objects
+//! allocated through `post_alloc`.
+//!
+//! The value is 32 bits by default. The features `object_user_data_16` and `object_user_data_8`
+//! make it 16 or 8 bits, which takes less address space for side metadata.
+
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use std::sync::atomic::Ordering;
+
+/// log2 of the bits of a user data value.
+#[cfg(not(any(feature = "object_user_data_8", feature = "object_user_data_16")))]
+pub const LOG_BITS_IN_USER_DATA: usize = 5;
+#[cfg(feature = "object_user_data_16")]
+pub const LOG_BITS_IN_USER_DATA: usize = 4;
+#[cfg(all(feature = "object_user_data_8", not(feature = "object_user_data_16")))]
+pub const LOG_BITS_IN_USER_DATA: usize = 3;
+
+/// The bits of a user data value.
+pub const BITS_IN_USER_DATA: usize = 1 << LOG_BITS_IN_USER_DATA;
+
+/// The user data is recorded per `1 << LOG_BYTES_IN_REGION` bytes. The regions are larger than the
+/// min object size to keep the side metadata within the space reserved for global side metadata.
+#[cfg(target_pointer_width = "64")]
+pub const LOG_BYTES_IN_REGION: usize = 4;
+#[cfg(target_pointer_width = "32")]
+pub const LOG_BYTES_IN_REGION: usize = 6;
+
+/// The min size of an object that uses the user data. Two objects that are closer than this share
+/// one value, so the binding needs to allocate the objects whose user data it uses with at least
+/// this size. `post_alloc` asserts this in debug builds.
+pub const MIN_OBJECT_SIZE: usize = 1 << LOG_BYTES_IN_REGION;
+
+pub(crate) const USER_DATA_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::OBJECT_USER_DATA;
+
+/// Get the user data of an object.
+#[inline(always)]
+pub fn get_user_data(object: ObjectReference) -> usize {
+    side_metadata::load_atomic(
+        &USER_DATA_SIDE_METADATA_SPEC,
+        object.to_address(),
+        Ordering::Relaxed,
+    )
+}
+
+/// Set the user data of an object. The value must fit in `BITS_IN_USER_DATA` bits.
+#[inline(always)]
+pub fn set_user_data(object: ObjectReference, value: usize) {
+    debug_assert!(
+        BITS_IN_USER_DATA >= usize::BITS as usize || value >> BITS_IN_USER_DATA == 0,
+        "The user data {:#x} does not fit in {} bits",
+        value,
+        BITS_IN_USER_DATA
+    );
+    side_metadata::store_atomic(
+        &USER_DATA_SIDE_METADATA_SPEC,
+        object.to_address(),
+        value,
+        Ordering::Relaxed,
+    );
+}
+
+/// Clear the user data of a new object of `bytes` bytes.
+#[inline(always)]
+pub(crate) fn clear_user_data(object: ObjectReference, bytes: usize) {
+    debug_assert!(
+        bytes >= MIN_OBJECT_SIZE,
+        "The object {} of {} bytes is smaller than the min object size for user data ({})",
+        object,
+        bytes,
+        MIN_OBJECT_SIZE
+    );
+    set_user_data(object, 0);
+}
+
+/// Keep the user data of an object when the object is moved.
+#[inline(always)]
+pub fn copy_user_data(from: ObjectReference, to: ObjectReference) {
+    set_user_data(to, get_user_data(from));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::BYTES_IN_PAGE;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+    use crate::util::Address;
+
+    fn object(addr: usize) -> ObjectReference {
+        unsafe { Address::from_usize(addr).to_object_reference() }
+    }
+
+    #[test]
+    fn test_neighbouring_objects() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![USER_DATA_SIDE_METADATA_SPEC],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_PAGE)
+                        .unwrap();
+                    // Objects of the min size at unaligned addresses still have their own values.
+                    let base = HEAP_START.as_usize() + MIN_OBJECT_SIZE / 2;
+                    let objects: Vec<ObjectReference> =
+                        (0..4).map(|i| object(base + i * MIN_OBJECT_SIZE)).collect();
+                    for o in objects.iter() {
+                        clear_user_data(*o, MIN_OBJECT_SIZE);
+                    }
+                    for (i, o) in objects.iter().enumerate() {
+                        set_user_data(*o, i + 1);
+                    }
+                    for (i, o) in objects.iter().enumerate() {
+                        assert_eq!(get_user_data(*o), i + 1);
+                    }
+
+                    copy_user_data(objects[0], objects[3]);
+                    assert_eq!(get_user_data(objects[3]), 1);
+                    assert_eq!(get_user_data(objects[2]), 3);
+                },
+                || metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_PAGE),
+            )
+        })
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_object_too_small() {
+        clear_user_data(object(HEAP_START.as_usize()), MIN_OBJECT_SIZE / 2);
+    }
+}
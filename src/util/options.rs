@@ -1,8 +1,11 @@
 use crate::util::constants::DEFAULT_STRESS_FACTOR;
 use crate::util::constants::LOG_BYTES_IN_MBYTE;
+use crate::util::error::MMTKError;
 use std::default::Default;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use strum_macros::EnumString;
 
 #[derive(Copy, Clone, EnumString, Debug)]
@@ -97,6 +100,151 @@ fn always_valid<T>(_: &T) -> bool {
     true
 }
 
+/// Where an option is set from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionSource {
+    /// An env var, such as `MMTK_THREADS`.
+    EnvVar,
+    /// The command line or the API, such as `MMTKBuilder::set_option()`.
+    CommandLine,
+}
+
+/// Why a value is rejected for an option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionErrorKind {
+    /// The value cannot be parsed as the type of the option.
+    Unparsable {
+        /// The type of the option.
+        expected: &'static str,
+        /// The error from parsing the value.
+        reason: String,
+    },
+    /// The value is parsed, but the validator of the option rejects it.
+    Invalid,
+}
+
+/// A value that cannot be set for an option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptionError {
+    /// The name of the option.
+    pub name: String,
+    /// The value as it is given.
+    pub value: String,
+    /// Where the value is from.
+    pub source: OptionSource,
+    /// Why the value is rejected.
+    pub kind: OptionErrorKind,
+    /// The values that the option accepts, if they are more restricted than its type.
+    pub accepted: Option<&'static str>,
+    /// The option is left with its default value. Otherwise, it keeps the value that was set
+    /// before.
+    pub default_applied: bool,
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.source {
+            OptionSource::EnvVar => write!(
+                f,
+                "MMTK_{}={:?} (env var)",
+                self.name.to_uppercase(),
+                self.value
+            )?,
+            OptionSource::CommandLine => write!(f, "{}={:?}", self.name, self.value)?,
+        }
+        match &self.kind {
+            OptionErrorKind::Unparsable { expected, reason } => {
+                write!(f, " cannot be parsed as {}: {}", expected, reason)?
+            }
+            OptionErrorKind::Invalid => write!(f, " is not a valid value")?,
+        }
+        if let Some(accepted) = self.accepted {
+            write!(f, " (accepted: {})", accepted)?;
+        }
+        if self.default_applied {
+            write!(f, ". The default value is used")
+        } else {
+            write!(f, ". The value set before is kept")
+        }
+    }
+}
+
+/// A scalar type that a live option can have.
+pub trait LiveScalar: Copy + Debug + FromStr {
+    fn to_bits(self) -> usize;
+    fn from_bits(bits: usize) -> Self;
+}
+
+impl LiveScalar for bool {
+    fn to_bits(self) -> usize {
+        self as usize
+    }
+    fn from_bits(bits: usize) -> Self {
+        bits != 0
+    }
+}
+
+impl LiveScalar for usize {
+    fn to_bits(self) -> usize {
+        self
+    }
+    fn from_bits(bits: usize) -> Self {
+        bits
+    }
+}
+
+/// The value of a live option. A live option can be changed with `MMTK::tune_option()` while other
+/// threads read it, so its value is kept in an atomic, and is read with `get()`, e.g.
+/// `options.stress_factor.get()`.
+pub struct LiveValue<T: LiveScalar> {
+    bits: AtomicUsize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: LiveScalar> LiveValue<T> {
+    pub fn new(value: T) -> Self {
+        LiveValue {
+            bits: AtomicUsize::new(value.to_bits()),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the current value.
+    pub fn get(&self) -> T {
+        T::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: T) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl<T: LiveScalar> From<T> for LiveValue<T> {
+    fn from(value: T) -> Self {
+        LiveValue::new(value)
+    }
+}
+
+impl<T: LiveScalar> Clone for LiveValue<T> {
+    fn clone(&self) -> Self {
+        LiveValue::new(self.get())
+    }
+}
+
+impl<T: LiveScalar> Debug for LiveValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl<T: LiveScalar> FromStr for LiveValue<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<T>().map(LiveValue::new)
+    }
+}
+
 /// An MMTk option of a given type.
 /// This type allows us to store some metadata for the option. To get the value of an option,
 /// you can simply dereference it (for example, *options.threads).
@@ -110,6 +258,13 @@ pub struct MMTKOption<T: Debug + Clone> {
     from_env_var: bool,
     /// Can we set this option through command line options/API?
     from_command_line: bool,
+    /// Can we change this option after the MMTk instance is created (through `MMTK::tune_option()`)?
+    live: bool,
+    /// Is the option still set to its default value?
+    is_default: bool,
+    /// The error from the last time the option failed to be set from an env var or the command
+    /// line, if it has not been set successfully since then.
+    error: Option<OptionError>,
 }
 
 impl<T: Debug + Clone> MMTKOption<T> {
@@ -119,6 +274,7 @@ impl<T: Debug + Clone> MMTKOption<T> {
         validator: fn(&T) -> bool,
         from_env_var: bool,
         from_command_line: bool,
+        live: bool,
     ) -> Self {
         // FIXME: We should enable the following check to make sure the initial value is valid.
         // However, we cannot enable it now. For options like perf events, the validator checks
@@ -137,6 +293,9 @@ impl<T: Debug + Clone> MMTKOption<T> {
             validator,
             from_env_var,
             from_command_line,
+            live,
+            is_default: true,
+            error: None,
         }
     }
 
@@ -144,10 +303,28 @@ impl<T: Debug + Clone> MMTKOption<T> {
     pub fn set(&mut self, value: T) -> bool {
         if (self.validator)(&value) {
             self.value = value;
+            self.is_default = false;
+            self.error = None;
             return true;
         }
         false
     }
+
+    /// Is the option still set to its default value?
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    /// The error from the last time the option failed to be set from an env var or the command
+    /// line, if it has not been set successfully since then.
+    pub fn error(&self) -> Option<&OptionError> {
+        self.error.as_ref()
+    }
+
+    /// Can this option be changed after the MMTk instance is created?
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
 }
 
 // Dereference an option to get its value.
@@ -167,26 +344,124 @@ macro_rules! options {
             _ => panic!("Invalid Options key: {}", $key)
         }
     };
+    // Change a live option of `$self` to the value of the option in `$new`.
+    (@set_live(true, $self: expr, $new: expr, $name: ident)) => {{
+        $self.$name.value.set($new.$name.value.get());
+        Ok(())
+    }};
+    (@set_live(false, $self: expr, $new: expr, $name: ident)) => {
+        Err(MMTKError::OptionNotSettable(stringify!($name).to_string()))
+    };
+    // The description of the values that the validator accepts, if any.
+    (@accepted()) => { None };
+    (@accepted($accepted: expr)) => { Some($accepted) };
 
-    ($($name:ident: $type:ty[env_var: $env_var:expr, command_line: $command_line:expr][$validator:expr] = $default:expr),*,) => [
-        options!($($name: $type[env_var: $env_var, command_line: $command_line, mutable: $mutable][$validator] = $default),*);
+    ($($(#[$attr:meta])* $name:ident: $type:ty[env_var: $env_var:expr, command_line: $command_line:expr, live: $live:tt][$validator:expr $(, accepted: $accepted:expr)?] = $default:expr),*,) => [
+        options!($($(#[$attr])* $name: $type[env_var: $env_var, command_line: $command_line, live: $live][$validator $(, accepted: $accepted)?] = $default),*);
     ];
-    ($($name:ident: $type:ty[env_var: $env_var:expr, command_line: $command_line:expr][$validator:expr] = $default:expr),*) => [
+    ($($(#[$attr:meta])* $name:ident: $type:ty[env_var: $env_var:expr, command_line: $command_line:expr, live: $live:tt][$validator:expr $(, accepted: $accepted:expr)?] = $default:expr),*) => [
         #[derive(Clone)]
         pub struct Options {
-            $(pub $name: MMTKOption<$type>),*
+            $(
+                $(#[$attr])*
+                #[doc = ""]
+                #[doc = concat!("Default: `", stringify!($default), "`")]
+                pub $name: MMTKOption<$type>
+            ),*
+        }
+
+        /// A typed builder for [`Options`]. Each method sets an option to a typed value. The builder
+        /// records the options that are not set successfully, and the errors are reported by `done()`,
+        /// along with the errors from the cross-option validation.
+        pub struct OptionsBuilder<'a> {
+            options: &'a mut Options,
+            errors: Vec<String>,
+        }
+
+        impl<'a> OptionsBuilder<'a> {
+            pub(crate) fn new(options: &'a mut Options) -> Self {
+                OptionsBuilder {
+                    options,
+                    errors: vec![],
+                }
+            }
+
+            $(
+                #[doc = concat!("Set the option `", stringify!($name), "`.")]
+                pub fn $name(mut self, value: impl Into<$type>) -> Self {
+                    if !self.options.$name.from_command_line {
+                        self.errors.push(format!("cannot set option {} (not from_command_line)", stringify!($name)));
+                    } else if !self.options.$name.set(value.into()) {
+                        self.errors.push(format!("invalid value for option {}", stringify!($name)));
+                    }
+                    self
+                }
+            )*
+
+            /// Finish setting options. Returns an error that describes all the invalid options, if there is any.
+            pub fn done(self) -> Result<(), String> {
+                let mut errors = self.errors;
+                if let Err(e) = self.options.validate() {
+                    errors.push(e);
+                }
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(errors.join("; "))
+                }
+            }
         }
+
         impl Options {
             /// Set an option from env var
             pub fn set_from_env_var(&mut self, s: &str, val: &str) -> bool {
                 options!(@verify_set_from(self, s, from_env_var, $($name),*));
-                self.set_inner(s, val)
+                self.set_inner(s, val, OptionSource::EnvVar)
             }
 
             /// Set an option from command line
             pub fn set_from_command_line(&mut self, s: &str, val: &str) -> bool {
                 options!(@verify_set_from(self, s, from_command_line, $($name),*));
-                self.set_inner(s, val)
+                self.set_inner(s, val, OptionSource::CommandLine)
+            }
+
+            /// Set an option from command line. Unlike `set_from_command_line()`, this returns
+            /// an error instead of panicking if there is no such option, or if the option
+            /// cannot be set from command line.
+            pub fn try_set_from_command_line(
+                &mut self,
+                s: &str,
+                val: &str,
+            ) -> Result<(), MMTKError> {
+                let from_command_line = match s {
+                    $(stringify!($name) => self.$name.from_command_line,)*
+                    _ => return Err(MMTKError::UnknownOption(s.to_string())),
+                };
+                if !from_command_line {
+                    return Err(MMTKError::OptionNotSettable(s.to_string()));
+                }
+                self.try_set_inner(s, val, OptionSource::CommandLine)
+                    .map_err(MMTKError::InvalidOptionValue)
+            }
+
+            /// Bulk process options, and return the error of the first option that cannot be
+            /// set. The options before it are set.
+            ///
+            /// Arguments:
+            /// * `options`: a string that is key value pairs separated by white spaces, e.g.
+            ///   "threads=1 stress_factor=4096"
+            pub fn try_set_bulk_from_command_line(
+                &mut self,
+                options: &str,
+            ) -> Result<(), MMTKError> {
+                for opt in options.split_ascii_whitespace() {
+                    let kv_pair: Vec<&str> = opt.split('=').collect();
+                    if kv_pair.len() != 2 {
+                        return Err(MMTKError::MalformedOptions(opt.to_string()));
+                    }
+                    self.try_set_from_command_line(kv_pair[0], kv_pair[1])?;
+                }
+                Ok(())
             }
 
             /// Bulk process options. Returns true if all the options are processed successfully.
@@ -211,28 +486,98 @@ macro_rules! options {
                 true
             }
 
-            /// Set an option and run its validator for its value.
-            fn set_inner(&mut self, s: &str, val: &str) -> bool {
+            /// Set a live option for an MMTk instance that is already created, while other threads
+            /// may read the options. Returns an error if there is no such option, the option is not
+            /// live, the value is invalid, or the options are not valid after the change.
+            pub fn set_live(&self, s: &str, val: &str) -> Result<(), MMTKError> {
+                let is_live = match s {
+                    $(stringify!($name) => self.$name.live,)*
+                    _ => return Err(MMTKError::UnknownOption(s.to_string())),
+                };
+                if !is_live {
+                    return Err(MMTKError::OptionNotSettable(s.to_string()));
+                }
+                // Try the change on a copy first, so we do not leave the options in an invalid state.
+                let mut new_options = self.clone();
+                new_options
+                    .try_set_inner(s, val, OptionSource::CommandLine)
+                    .map_err(|e| MMTKError::InvalidOptionValues(vec![e]))?;
+                new_options.validate().map_err(MMTKError::InvalidOptions)?;
                 match s {
-                    // Parse the given value from str (by env vars or by calling process()) to the right type
-                    $(stringify!($name) => if let Ok(typed_val) = val.parse::<$type>() {
-                        let is_set = self.$name.set(typed_val);
-                        if !is_set {
-                            eprintln!("Warn: unable to set {}={:?}. Invalid value. Default value will be used.", s, val);
+                    $(stringify!($name) => options!(@set_live($live, self, new_options, $name)),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            /// The values from env vars and the command line that could not be set, and have not
+            /// been replaced by a valid value since then, in the order of the options.
+            /// `MMTKBuilder::build()` returns them as an error.
+            pub fn diagnostics(&self) -> Vec<OptionError> {
+                let errors = [$(self.$name.error()),*];
+                errors.iter().flatten().map(|e| (*e).clone()).collect()
+            }
+
+            /// Set an option and run its validator for its value. If the value cannot be set, this
+            /// warns, and records the error for `diagnostics()`.
+            fn set_inner(&mut self, s: &str, val: &str, source: OptionSource) -> bool {
+                match self.try_set_inner(s, val, source) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        eprintln!("Warn: unable to set {}", e);
+                        match s {
+                            $(stringify!($name) => self.$name.error = Some(e),)*
+                            _ => unreachable!()
                         }
-                        is_set
-                    } else {
-                        eprintln!("Warn: unable to set {}={:?}. Can't parse value. Default value will be used.", s, val);
                         false
-                    })*
+                    }
+                }
+            }
+
+            /// Set an option and run its validator for its value. Returns the error if the value
+            /// cannot be set.
+            fn try_set_inner(
+                &mut self,
+                s: &str,
+                val: &str,
+                source: OptionSource,
+            ) -> Result<(), OptionError> {
+                let (kind, default_applied) = match s {
+                    // Parse the given value from str (by env vars or by calling process()) to the right type
+                    $(stringify!($name) => match val.parse::<$type>() {
+                        Ok(typed_val) => if self.$name.set(typed_val) {
+                            return Ok(());
+                        } else {
+                            (OptionErrorKind::Invalid, self.$name.is_default)
+                        },
+                        Err(e) => (OptionErrorKind::Unparsable {
+                            expected: std::any::type_name::<$type>(),
+                            reason: e.to_string(),
+                        }, self.$name.is_default),
+                    },)*
                     _ => panic!("Invalid Options key: {}", s)
+                };
+                Err(OptionError {
+                    name: s.to_string(),
+                    value: val.to_string(),
+                    source,
+                    kind,
+                    accepted: Self::accepted_values(s),
+                    default_applied,
+                })
+            }
+
+            /// The values accepted by the option, if its validator rejects some values of its type.
+            fn accepted_values(s: &str) -> Option<&'static str> {
+                match s {
+                    $(stringify!($name) => options!(@accepted($($accepted)?)),)*
+                    _ => None,
                 }
             }
         }
         impl Default for Options {
             fn default() -> Self {
                 let mut options = Options {
-                    $($name: MMTKOption::new($default, $validator, $env_var,$command_line)),*
+                    $($name: MMTKOption::new($default.into(), $validator, $env_var, $command_line, $live)),*
                 };
 
                 // If we have env vars that start with MMTK_ and match any option (such as MMTK_STRESS_FACTOR),
@@ -254,22 +599,26 @@ macro_rules! options {
     ]
 }
 
-#[derive(Copy, Clone, EnumString, Debug)]
+#[derive(Copy, Clone, EnumString, Debug, PartialEq, Eq)]
 /// Different nursery types.
 pub enum NurseryKind {
     /// A Bounded nursery has different upper and lower bounds. The size only controls the upper
     /// bound. Hence, it is considered to be a "variable size" nursery. By default, a Bounded
     /// nursery has a lower bound of 2 MB and an upper bound of 32 MB for 32-bit systems and 1 TB
-    /// for 64-bit systems.
+    /// for 64-bit systems. Within the bounds, the nursery uses the free space in the heap (Appel-style),
+    /// so it shrinks as the mature space grows.
     Bounded,
     /// A Fixed nursery has the same upper and lower bounds. The size controls both the upper and
     /// lower bounds. Note that this is considered less performant than a Bounded nursery since a
     /// Fixed nursery size can be too restrictive and cause more GCs.
     Fixed,
+    /// A Proportional nursery is a fixed proportion (in percentage) of the heap size. It uses the
+    /// default lower and upper bounds of a Bounded nursery.
+    Proportional,
 }
 
 #[derive(Copy, Clone, Debug)]
-/// An option that provides a min/max interface to MMTk and a Bounded/Fixed interface to the
+/// An option that provides a min/max interface to MMTk and a Bounded/Fixed/Proportional interface to the
 /// user/VM.
 pub struct NurserySize {
     /// The nursery type
@@ -278,6 +627,8 @@ pub struct NurserySize {
     pub min: usize,
     /// Maximum nursery size (in bytes)
     pub max: usize,
+    /// The nursery size as a percentage of the heap size. This is only used for a Proportional nursery.
+    pub percent: usize,
 }
 
 impl NurserySize {
@@ -287,27 +638,55 @@ impl NurserySize {
                 kind,
                 min: DEFAULT_MIN_NURSERY,
                 max: value,
+                percent: 0,
             },
             NurseryKind::Fixed => NurserySize {
                 kind,
                 min: value,
                 max: value,
+                percent: 0,
+            },
+            NurseryKind::Proportional => NurserySize {
+                kind,
+                min: DEFAULT_MIN_NURSERY,
+                max: DEFAULT_MAX_NURSERY,
+                percent: value,
             },
         }
     }
 
     /// Returns a NurserySize or String containing error. Expects nursery size to be formatted as
     /// "<NurseryKind>:<size in bytes>". For example, "Fixed:8192" creates a Fixed nursery of size
-    /// 8192 bytes.
+    /// 8192 bytes. A Bounded nursery can also be given both bounds as "Bounded:<min>:<max>", and
+    /// a Proportional nursery is given a percentage of the heap size, such as "Proportional:25".
     pub fn parse(s: &str) -> Result<NurserySize, String> {
         let ns: Vec<&str> = s.split(':').into_iter().collect();
         let kind = ns[0].parse::<NurseryKind>().map_err(|_| {
-            String::from("Please specify one of \"Bounded\" or \"Fixed\" nursery type")
+            String::from(
+                "Please specify one of \"Bounded\", \"Fixed\" or \"Proportional\" nursery type",
+            )
         })?;
-        let value = ns[1]
-            .parse()
-            .map_err(|_| String::from("Failed to parse size"))?;
-        Ok(NurserySize::new(kind, value))
+        let parse_size = |v: &str| -> Result<usize, String> {
+            v.parse().map_err(|_| String::from("Failed to parse size"))
+        };
+        match (kind, ns.len()) {
+            (_, 2) => Ok(NurserySize::new(kind, parse_size(ns[1])?)),
+            (NurseryKind::Bounded, 3) => Ok(NurserySize {
+                kind,
+                min: parse_size(ns[1])?,
+                max: parse_size(ns[2])?,
+                percent: 0,
+            }),
+            _ => Err(String::from("Invalid nursery size")),
+        }
+    }
+
+    /// Is the nursery size valid?
+    fn is_valid(&self) -> bool {
+        self.min > 0
+            && self.max > 0
+            && self.max >= self.min
+            && (self.kind != NurseryKind::Proportional || (self.percent > 0 && self.percent <= 100))
     }
 }
 
@@ -329,75 +708,323 @@ impl Options {
     pub fn get_min_nursery(&self) -> usize {
         self.nursery.min
     }
+
+    /// Are the GCs done on the mutator threads that trigger them, without GC threads? This is the
+    /// case with the `single_thread` feature, or if `threads` is 0.
+    pub fn is_single_threaded(&self) -> bool {
+        cfg!(feature = "single_thread") || *self.threads == 0
+    }
+}
+
+/// The size of a space, either in bytes, or as a percentage of the heap size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpaceSize {
+    /// A size in bytes.
+    Bytes(usize),
+    /// A percentage of the heap size.
+    Percent(usize),
 }
 
+impl SpaceSize {
+    /// Return the size in pages for a heap of the given number of pages.
+    pub fn to_pages(&self, heap_pages: usize) -> usize {
+        match *self {
+            SpaceSize::Bytes(bytes) => crate::util::conversions::bytes_to_pages_up(bytes),
+            SpaceSize::Percent(percent) => heap_pages / 100 * percent,
+        }
+    }
+}
+
+/// Sizes for specific spaces. The sizes are formatted as a comma separated list of
+/// `<space name>:<size>`, where the size is either a number of bytes, or a percentage of the heap size,
+/// e.g. "los:25%,nursery:8388608".
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpaceSizes {
+    pub sizes: Vec<(String, SpaceSize)>,
+}
+
+impl SpaceSizes {
+    /// Return the size for the space of the given name, if there is one.
+    pub fn get(&self, space_name: &str) -> Option<SpaceSize> {
+        self.sizes
+            .iter()
+            .find(|(name, _)| name == space_name)
+            .map(|(_, size)| *size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// A percentage cannot be more than 100.
+    fn is_valid(&self) -> bool {
+        self.sizes.iter().all(|(_, size)| match size {
+            SpaceSize::Percent(percent) => *percent <= 100,
+            SpaceSize::Bytes(_) => true,
+        })
+    }
+}
+
+impl FromStr for SpaceSizes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sizes = s
+            .split(',')
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                let e: Vec<&str> = e.split(':').collect();
+                if e.len() != 2 || e[0].is_empty() {
+                    return Err("Please supply <space name>:<size>".to_string());
+                }
+                let size = if let Some(percent) = e[1].strip_suffix('%') {
+                    SpaceSize::Percent(
+                        percent
+                            .parse()
+                            .map_err(|_| String::from("Failed to parse percentage"))?,
+                    )
+                } else {
+                    SpaceSize::Bytes(
+                        e[1].parse()
+                            .map_err(|_| String::from("Failed to parse size"))?,
+                    )
+                };
+                Ok((e[0].to_string(), size))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(SpaceSizes { sizes })
+    }
+}
+
+/// The values accepted by the options of the sizes of spaces.
+const SPACE_SIZES: &str =
+    "a comma separated list of <space name>:<bytes> or <space name>:<percent>%, \
+                           where the percents are not larger than 100";
+
 // Currently we allow all the options to be set by env var for the sake of convenience.
 // At some point, we may disallow this and all the options can only be set by command line.
 options! {
-    // The plan to use.
-    plan:                  PlanSelector         [env_var: true, command_line: true] [always_valid] = PlanSelector::NoGC,
-    // Number of GC worker threads. (There is always one GC controller thread.)
+    /// The plan to use.
+    plan:                  PlanSelector         [env_var: true, command_line: true, live: false] [always_valid] = crate::plan::static_plan::DEFAULT_PLAN,
+    /// Number of GC worker threads. (There is always one GC controller thread.) If this is 0, MMTk does not create GC threads,
+    /// and a GC is done on the mutator thread that triggers it, which runs all the work packets after stopping the other
+    /// mutators, as with the `single_thread` feature.
     // FIXME: Currently we create GCWorkScheduler when MMTK is created, which is usually static.
     // To allow this as a command-line option, we need to refactor the creation fo the `MMTK` instance.
     // See: https://github.com/mmtk/mmtk-core/issues/532
-    threads:               usize                [env_var: true, command_line: true] [|v: &usize| *v > 0]    = num_cpus::get(),
-    // Heap size. Default to 512MB.
-    // TODO: We should have a default heap size related to the max physical memory.
-    heap_size:             usize                [env_var: true, command_line: true] [|v: &usize| *v > 0]    = 512 << 20,
-    // Enable an optimization that only scans the part of the stack that has changed since the last GC (not supported)
-    use_short_stack_scans: bool                 [env_var: true, command_line: true]  [always_valid] = false,
-    // Enable a return barrier (not supported)
-    use_return_barrier:    bool                 [env_var: true, command_line: true]  [always_valid] = false,
-    // Should we eagerly finish sweeping at the start of a collection? (not supported)
-    eager_complete_sweep:  bool                 [env_var: true, command_line: true]  [always_valid] = false,
-    // Should we ignore GCs requested by the user (e.g. java.lang.System.gc)?
-    ignore_system_gc:      bool                 [env_var: true, command_line: true]  [always_valid] = false,
+    threads:               usize                [env_var: true, command_line: true, live: false] [always_valid]    = num_cpus::get(),
+    /// Heap size. Default to 512MB. If the process has a cgroup memory limit (e.g. in a container), the default is
+    /// half of the limit if that is smaller than 512MB.
+    heap_size:             usize                [env_var: true, command_line: true, live: false] [|v: &usize| *v > 0, accepted: "a number of bytes larger than 0"]
+        = crate::util::cgroup::default_heap_size(),
+    /// Should we trigger a GC when the kernel reports memory pressure (PSI)? This is only supported on Linux.
+    memory_pressure_gc:    bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Trigger a GC if no GC has happened in this many milliseconds, e.g. to return memory in an idle process. The plan
+    /// decides what kind of GC to do, as for a GC triggered by memory pressure. 0 disables the periodic GCs.
+    periodic_gc_ms:        usize                [env_var: true, command_line: true, live: false]  [always_valid] = 0,
+    /// Enable an optimization that only scans the part of the stack that has changed since the last GC (not supported)
+    use_short_stack_scans: bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Enable a return barrier (not supported)
+    use_return_barrier:    bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Should we eagerly finish sweeping at the start of a collection? (not supported)
+    eager_complete_sweep:  bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Should we ignore GCs requested by the user (e.g. java.lang.System.gc)?
+    ignore_system_gc:      LiveValue<bool>      [env_var: true, command_line: true, live: true]  [always_valid] = false,
     // FIXME: This is not a good way to have conflicting options -- we should refactor this
-    // The nursery size for generational plans. It can be one of Bounded or Fixed. The size for a
-    // Bounded nursery only controls the upper bound, whereas the size for a Fixed nursery controls
-    // both the upper and lower bounds. The nursery size can be set like "Fixed:8192", for example,
-    // to have a Fixed nursery size of 8192 bytes
-    nursery:               NurserySize          [env_var: true, command_line: true]  [|v: &NurserySize| v.min > 0 && v.max > 0 && v.max >= v.min]
-        = NurserySize { kind: NurseryKind::Bounded, min: DEFAULT_MIN_NURSERY, max: DEFAULT_MAX_NURSERY },
-    // Should a major GC be performed when a system GC is required?
-    full_heap_system_gc:   bool                 [env_var: true, command_line: true]  [always_valid] = false,
-    // Should we shrink/grow the heap to adjust to application working set? (not supported)
-    variable_size_heap:    bool                 [env_var: true, command_line: true]  [always_valid] = true,
-    // Should finalization be disabled?
-    no_finalizer:          bool                 [env_var: true, command_line: true]  [always_valid] = false,
-    // Should reference type processing be disabled?
-    // If reference type processing is disabled, no weak reference processing work is scheduled,
-    // and we expect a binding to treat weak references as strong references.
-    // We disable weak reference processing by default, as we are still working on it. This will be changed to `false`
-    // once weak reference processing is implemented properly.
-    no_reference_types:    bool                 [env_var: true, command_line: true]  [always_valid] = true,
-    // The zeroing approach to use for new object allocations. Affects each plan differently. (not supported)
-    nursery_zeroing:       NurseryZeroingOptions[env_var: true, command_line: true]  [always_valid] = NurseryZeroingOptions::Temporal,
-    // How frequent (every X bytes) should we do a stress GC?
-    stress_factor:         usize                [env_var: true, command_line: true]  [always_valid] = DEFAULT_STRESS_FACTOR,
-    // How frequent (every X bytes) should we run analysis (a STW event that collects data)
-    analysis_factor:       usize                [env_var: true, command_line: true]  [always_valid] = DEFAULT_STRESS_FACTOR,
-    // Precise stress test. Trigger stress GCs exactly at X bytes if this is true. This is usually used to test the GC correctness
-    // and will significantly slow down the mutator performance. If this is false, stress GCs will only be triggered when an allocation reaches
-    // the slow path. This means we may have allocated more than X bytes or fewer than X bytes when we actually trigger a stress GC.
-    // But this should have no obvious mutator overhead, and can be used to test GC performance along with a larger stress
-    // factor (e.g. tens of metabytes).
-    precise_stress:        bool                 [env_var: true, command_line: true]  [always_valid] = true,
-    // The size of vmspace.
+    /// The nursery size for generational plans. It can be one of Bounded, Fixed or Proportional. The size for a
+    /// Bounded nursery only controls the upper bound (or both bounds, like "Bounded:2097152:33554432"), whereas the
+    /// size for a Fixed nursery controls both the upper and lower bounds. The nursery size can be set like "Fixed:8192",
+    /// for example, to have a Fixed nursery size of 8192 bytes. A Proportional nursery is a percentage of the heap
+    /// size, like "Proportional:25".
+    nursery:               NurserySize          [env_var: true, command_line: true, live: false]  [|v: &NurserySize| v.is_valid(), accepted:
+        "Bounded:<max>, Bounded:<min>:<max>, Fixed:<size> or Proportional:<percent>, where the sizes are larger than 0, min is not \
+         larger than max, and the percent is from 1 to 100"]
+        = NurserySize { kind: NurseryKind::Bounded, min: DEFAULT_MIN_NURSERY, max: DEFAULT_MAX_NURSERY, percent: 0 },
+    /// The target pause time (in milliseconds) for nursery GCs in generational plans. If this is not 0, the nursery
+    /// is shrunk when a nursery GC takes longer than this, so the nursery work is split into more but shorter pauses,
+    /// with a smaller allocation budget between them. With `incremental_nursery`, the nursery keeps its size, and this
+    /// bounds the increments instead. A single GC may still take longer than this, e.g. if the roots are large.
+    max_pause_ms:          LiveValue<usize>     [env_var: true, command_line: true, live: true]  [always_valid] = 0,
+    /// Collect the nursery of generational plans in increments when `max_pause_ms` is set. Each incremental nursery GC
+    /// promotes the survivors of the objects allocated before the previous GC, and leaves the objects allocated since then
+    /// to the next GC, so the pause only copies the survivors of the pages allocated between two GCs. The budget between
+    /// the GCs is adapted to `max_pause_ms`. The memory of the nursery is reclaimed by a GC that collects the whole nursery,
+    /// when the nursery is full. The young objects are logged by the write barrier like mature objects, so the binding must
+    /// call `post_alloc()` before it stores references into a new object, and must not elide the barriers for such stores.
+    /// This cannot be used with survivor spaces (`survivor_age_threshold` larger than 1).
+    incremental_nursery:   bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// The number of nursery GCs an object has to survive before it is promoted to the mature space in GenCopy.
+    /// With the default value 1, objects are promoted in the first GC they survive. With a larger value, the objects
+    /// that survive a nursery GC are copied to a survivor space, and stay in the young generation until they have survived
+    /// this many nursery GCs. A full heap GC always promotes all the surviving objects. The maximum value is 15.
+    survivor_age_threshold: usize               [env_var: true, command_line: true, live: false]  [|v: &usize| *v >= 1 && *v <= 15, accepted: "1 to 15"] = 1,
+    /// Adapt the young generation to the survival of nursery objects. After each nursery GC, the nursery is grown if many
+    /// nursery objects survived (so they get more time to die), and shrunk if very few survived. In GenCopy, the survivor age
+    /// threshold is also lowered when the survivor space overflows and raised again (up to `survivor_age_threshold`) when it
+    /// has room. This has no effect on the nursery size with a fixed nursery.
+    nursery_feedback:      LiveValue<bool>      [env_var: true, command_line: true, live: true]  [always_valid] = false,
+    /// The maximum sizes for specific spaces, so one space cannot starve the others, e.g. "los:25%". When a space
+    /// reaches its maximum size, a GC is triggered.
+    space_max_sizes:       SpaceSizes           [env_var: true, command_line: true, live: false]  [|v: &SpaceSizes| v.is_valid(), accepted: SPACE_SIZES]
+        = SpaceSizes::default(),
+    /// Growth triggers for specific spaces, e.g. "los:10%". When a space has grown by this size since the end of the last GC,
+    /// a GC is triggered, as if the space is full (for generational plans, it is a full heap GC unless the space is the nursery).
+    /// This is useful when most of the garbage is in one space, such as large objects.
+    space_growth_triggers: SpaceSizes           [env_var: true, command_line: true, live: false]  [|v: &SpaceSizes| v.is_valid(), accepted: SPACE_SIZES]
+        = SpaceSizes::default(),
+    /// The reserved sizes for specific spaces, e.g. "nursery:8388608". The reserved pages that are not yet
+    /// used by the space are counted as reserved pages for the plan, so other spaces cannot use them.
+    space_reservations:    SpaceSizes           [env_var: true, command_line: true, live: false]  [|v: &SpaceSizes| v.is_valid(), accepted: SPACE_SIZES]
+        = SpaceSizes::default(),
+    /// The number of blocks that each Immix space and copy space acquires and zeroes ahead of time, on the GC workers after
+    /// each GC, so the allocations after the GC take zeroed blocks instead of zeroing them. The blocks in the pools count as
+    /// used memory, and the pool of a copy space is only refilled while it is a to-space. 0 disables the pools.
+    zeroed_block_pool:     usize                [env_var: true, command_line: true, live: false]  [always_valid] = 0,
+    /// The bytes that a mutator may allocate in the allocation slow path for each quantum of GC work it performs between
+    /// GCs (see `util::mutator_assist`). With `zeroed_block_pool`, the mutators then zero the blocks of the pools, one block
+    /// for a quantum, together with the idle GC workers, or by themselves without GC workers. 0 disables the assists.
+    mutator_assist_bytes:  usize                [env_var: true, command_line: true, live: false]  [always_valid] = 0,
+    /// Count the references to large objects in the generational plans, so a nursery GC can reclaim the mature large objects
+    /// that have no references, instead of waiting for a full-heap GC. The binding must report every update of a reference
+    /// field with `memory_manager::reference_update_barrier()`. This is ignored by the other plans.
+    los_ref_counting:      bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Remember the references from the other spaces into the LOS in MarkSweep, so the binding can ask for a collection of
+    /// only the LOS with `memory_manager::handle_user_partial_collection_request()`. The binding must report every store of
+    /// a reference with `memory_manager::reference_write_barrier()`. The remembered objects are found by their alloc bits, so
+    /// this requires the feature `global_alloc_bit`. This is ignored by the other plans.
+    remember_los_references: bool               [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Remember the references into the objects of each heap from outside the heap in MarkSweep (see `util::heap_id`), so
+    /// the binding can ask for a collection of only the objects of a heap with
+    /// `memory_manager::handle_user_heap_collection_request()`. The binding must report every store of a reference with
+    /// `memory_manager::reference_write_barrier()`. This requires the features `heap_ids` and `global_alloc_bit`.
+    /// This is ignored by the other plans.
+    remember_heap_references: bool              [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Compress the chunks that have not been touched for this many GCs, and decompress them when they are accessed again.
+    /// 0 disables compression. This requires the feature `chunk_compression`, and is ignored by the PageProtect plan.
+    chunk_compression_gcs: usize                [env_var: true, command_line: true, live: false]  [always_valid] = 0,
+    /// The bytes that the pending work packets of the transitive closure may take, or 0 for no bound. Once the packets
+    /// take more, the objects that are marked are scanned after the packets are drained, instead of by new packets.
+    /// This requires the feature `tracing_overflow`.
+    max_tracing_memory:    usize                [env_var: true, command_line: true, live: false]  [always_valid] = 0,
+    /// Should a major GC be performed when a system GC is required?
+    full_heap_system_gc:   LiveValue<bool>      [env_var: true, command_line: true, live: true]  [always_valid] = false,
+    /// Should we shrink/grow the heap to adjust to application working set? (not supported)
+    variable_size_heap:    bool                 [env_var: true, command_line: true, live: false]  [always_valid] = true,
+    /// Should finalization be disabled?
+    no_finalizer:          bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Should reference type processing be disabled?
+    /// If reference type processing is disabled, no weak reference processing work is scheduled,
+    /// and we expect a binding to treat weak references as strong references.
+    /// We disable weak reference processing by default, as we are still working on it. This will be changed to `false`
+    /// once weak reference processing is implemented properly.
+    no_reference_types:    bool                 [env_var: true, command_line: true, live: false]  [always_valid] = true,
+    /// The zeroing approach to use for new object allocations. Affects each plan differently. (not supported)
+    nursery_zeroing:       NurseryZeroingOptions[env_var: true, command_line: true, live: false]  [always_valid] = NurseryZeroingOptions::Temporal,
+    /// How frequent (every X bytes) should we do a stress GC?
+    stress_factor:         LiveValue<usize>     [env_var: true, command_line: true, live: true]  [always_valid] = DEFAULT_STRESS_FACTOR,
+    /// How frequent (every X bytes) should we run analysis (a STW event that collects data)
+    analysis_factor:       LiveValue<usize>     [env_var: true, command_line: true, live: true]  [always_valid] = DEFAULT_STRESS_FACTOR,
+    /// The file to write the object lifetime histogram (as CSV) to at the end of the harness. The histogram is printed to stdout
+    /// if this is empty. This requires the features `analysis`, `object_age` and `global_alloc_bit`.
+    lifetime_histogram_file: String             [env_var: true, command_line: true, live: false]  [always_valid] = String::new(),
+    /// Count the bytes of the objects that the GC workers trace, for `GCStats::total_traced_bytes` and
+    /// `memory_manager::gc_tracing_stats()`. Immix then also defrags in a full heap GC if the heap was fragmented after the
+    /// last full heap GC. This asks the binding for the size of every traced object.
+    count_traced_bytes:    bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// Sample the committed and the used bytes of each space at the end of every GC, so the runtime can retrieve the
+    /// heap occupancy over time with `memory_manager::heap_timeline()`. The samples are kept in memory.
+    heap_timeline:         bool                 [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// The number of the latest GCs whose samples are kept in the heap timeline. The samples of the earlier GCs are discarded.
+    heap_timeline_gcs:     usize                [env_var: true, command_line: true, live: false]  [|v: &usize| *v > 0, accepted: "a number larger than 0"] = 1024,
+    /// Also sample the bytes of each space that are resident in physical memory in the heap timeline. This requires
+    /// `heap_timeline`. It is more expensive than the other samples, as it queries the OS (with `mincore()`) for each page.
+    heap_timeline_resident: bool                [env_var: true, command_line: true, live: false]  [always_valid] = false,
+    /// The file to write the write barrier profile (as CSV) to at the end of the harness. The profile is printed to stdout
+    /// if this is empty. This requires the feature `analysis`, and the binding needs to use `memory_manager::post_write_barrier_at_site()`.
+    barrier_profile_file: String                [env_var: true, command_line: true, live: false]  [always_valid] = String::new(),
+    /// Precise stress test. Trigger stress GCs exactly at X bytes if this is true. This is usually used to test the GC correctness
+    /// and will significantly slow down the mutator performance. If this is false, stress GCs will only be triggered when an allocation reaches
+    /// the slow path. This means we may have allocated more than X bytes or fewer than X bytes when we actually trigger a stress GC.
+    /// But this should have no obvious mutator overhead, and can be used to test GC performance along with a larger stress
+    /// factor (e.g. tens of metabytes).
+    precise_stress:        LiveValue<bool>      [env_var: true, command_line: true, live: true]  [always_valid] = true,
+    /// How far ahead the tracing loops prefetch, in edges or objects. When processing an edge, we prefetch the object that
+    /// the edge `prefetch_distance` edges later points to and its mark bit, and the slot of the edge twice as far ahead.
+    /// When scanning an object, we prefetch the object `prefetch_distance` objects later. 0 disables prefetching. A
+    /// non-zero value requires the feature prefetch.
+    prefetch_distance:     usize                [env_var: true, command_line: true, live: false] [always_valid] = 0,
+    /// The maximum number of object layouts that the binding can register with `memory_manager::register_object_layout()`. The table of
+    /// the layouts is allocated when MMTk is created if `Scanning::LAYOUT_TYPE_ID_OFFSET` is set, with two words for each layout.
+    max_object_layouts:    usize                [env_var: true, command_line: true, live: false] [always_valid] = 65536,
+    /// Report a GC in which no work packet has finished for this many seconds, e.g. because the binding deadlocks while
+    /// scanning an object. The report lists the open work buckets with the number of pending packets, and the packet each
+    /// GC thread is executing, and MMTk asks the binding to print the backtrace of each GC thread
+    /// (`Collection::print_gc_thread_backtrace`). 0 disables the watchdog.
+    gc_watchdog_timeout:   usize                [env_var: true, command_line: true, live: false] [always_valid] = 0,
+    /// Abort the process after the GC watchdog reports a stuck GC. Otherwise, the watchdog reports the GC again after
+    /// each `gc_watchdog_timeout` seconds without progress.
+    gc_watchdog_abort:     bool                 [env_var: true, command_line: true, live: false] [always_valid] = false,
+    /// Report the mutators that are still in GC-critical regions (see `memory_manager::enter_gc_critical_region`) after a GC
+    /// has waited for them for this many seconds, and again after each further timeout. 0 disables the reports.
+    gc_critical_region_timeout: usize           [env_var: true, command_line: true, live: false] [always_valid] = 0,
+    /// The size of vmspace.
     // FIXME: This value is set for JikesRVM. We need a proper way to set options.
     //   We need to set these values programmatically in VM specific code.
-    vm_space_size:         usize                [env_var: true, command_line: true] [|v: &usize| *v > 0]    = 0x7cc_cccc,
-    // Perf events to measure
-    // Semicolons are used to separate events
-    // Each event is in the format of event_name,pid,cpu (see man perf_event_open for what pid and cpu mean).
-    // For example, PERF_COUNT_HW_CPU_CYCLES,0,-1 measures the CPU cycles for the current process on all the CPU cores.
-    //
-    // Measuring perf events for work packets. NOTE that be VERY CAREFUL when using this option, as this may greatly slowdown GC performance.
+    vm_space_size:         usize                [env_var: true, command_line: true, live: false] [|v: &usize| *v > 0, accepted: "a number of bytes larger than 0"]
+        = 0x7cc_cccc,
+    /// Perf events to measure
+    /// Semicolons are used to separate events
+    /// Each event is in the format of event_name,pid,cpu (see man perf_event_open for what pid and cpu mean).
+    /// For example, PERF_COUNT_HW_CPU_CYCLES,0,-1 measures the CPU cycles for the current process on all the CPU cores.
+    ///
+    /// Measuring perf events for work packets. NOTE that be VERY CAREFUL when using this option, as this may greatly slowdown GC performance.
     // TODO: Ideally this option should only be included when the features 'perf_counter' and 'work_packet_stats' are enabled. The current macro does not allow us to do this.
-    work_perf_events:       PerfEventOptions     [env_var: true, command_line: true] [|_| cfg!(all(feature = "perf_counter", feature = "work_packet_stats"))] = PerfEventOptions {events: vec![]},
-    // Measuring perf events for GC and mutators
+    work_perf_events:       PerfEventOptions     [env_var: true, command_line: true, live: false] [|_| cfg!(all(feature = "perf_counter", feature = "work_packet_stats")), accepted:
+        "a semicolon separated list of <event name>,<pid>,<cpu>, if MMTk is built with the features perf_counter and work_packet_stats"]
+        = PerfEventOptions {events: vec![]},
+    /// Measuring perf events for GC and mutators
     // TODO: Ideally this option should only be included when the features 'perf_counter' are enabled. The current macro does not allow us to do this.
-    phase_perf_events:      PerfEventOptions     [env_var: true, command_line: true] [|_| cfg!(feature = "perf_counter")] = PerfEventOptions {events: vec![]}
+    phase_perf_events:      PerfEventOptions     [env_var: true, command_line: true, live: false] [|_| cfg!(feature = "perf_counter"), accepted:
+        "a semicolon separated list of <event name>,<pid>,<cpu>, if MMTk is built with the feature perf_counter"]
+        = PerfEventOptions {events: vec![]}
+}
+
+impl Options {
+    /// Check the options that depend on each other. Returns an error if the combination of the options is invalid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.nursery.min > *self.heap_size {
+            return Err(format!(
+                "The minimal nursery size ({} bytes) is larger than the heap size ({} bytes)",
+                self.nursery.min, *self.heap_size
+            ));
+        }
+        let heap_pages = crate::util::conversions::bytes_to_pages_up(*self.heap_size);
+        let reserved_pages: usize = self
+            .space_reservations
+            .sizes
+            .iter()
+            .map(|(_, size)| size.to_pages(heap_pages))
+            .sum();
+        if reserved_pages > heap_pages {
+            return Err(format!(
+                "The space reservations ({} pages) are larger than the heap size ({} pages)",
+                reserved_pages, heap_pages
+            ));
+        }
+        if *self.memory_pressure_gc && !cfg!(target_os = "linux") {
+            return Err("memory_pressure_gc is only supported on Linux".to_string());
+        }
+        if *self.incremental_nursery && *self.survivor_age_threshold > 1 {
+            return Err("incremental_nursery cannot be used with survivor spaces".to_string());
+        }
+        if *self.max_tracing_memory != 0 && !cfg!(feature = "tracing_overflow") {
+            return Err("max_tracing_memory requires the feature tracing_overflow".to_string());
+        }
+        if *self.prefetch_distance != 0 && !cfg!(feature = "prefetch") {
+            return Err("prefetch_distance requires the feature prefetch".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -411,7 +1038,7 @@ mod tests {
     fn no_env_var() {
         serial_test(|| {
             let options = Options::default();
-            assert_eq!(*options.stress_factor, DEFAULT_STRESS_FACTOR);
+            assert_eq!(options.stress_factor.get(), DEFAULT_STRESS_FACTOR);
         })
     }
 
@@ -423,7 +1050,7 @@ mod tests {
                     std::env::set_var("MMTK_STRESS_FACTOR", "4096");
 
                     let options = Options::default();
-                    assert_eq!(*options.stress_factor, 4096);
+                    assert_eq!(options.stress_factor.get(), 4096);
                 },
                 || {
                     std::env::remove_var("MMTK_STRESS_FACTOR");
@@ -441,7 +1068,7 @@ mod tests {
                     std::env::set_var("MMTK_NO_FINALIZER", "true");
 
                     let options = Options::default();
-                    assert_eq!(*options.stress_factor, 4096);
+                    assert_eq!(options.stress_factor.get(), 4096);
                     assert!(*options.no_finalizer);
                 },
                 || {
@@ -461,7 +1088,7 @@ mod tests {
                     std::env::set_var("MMTK_STRESS_FACTOR", "abc");
 
                     let options = Options::default();
-                    assert_eq!(*options.stress_factor, DEFAULT_STRESS_FACTOR);
+                    assert_eq!(options.stress_factor.get(), DEFAULT_STRESS_FACTOR);
                 },
                 || {
                     std::env::remove_var("MMTK_STRESS_FACTOR");
@@ -479,7 +1106,7 @@ mod tests {
                     std::env::set_var("MMTK_ABC", "42");
 
                     let options = Options::default();
-                    assert_eq!(*options.stress_factor, DEFAULT_STRESS_FACTOR);
+                    assert_eq!(options.stress_factor.get(), DEFAULT_STRESS_FACTOR);
                 },
                 || {
                     std::env::remove_var("MMTK_ABC");
@@ -605,7 +1232,7 @@ mod tests {
             let success = options.set_bulk_from_command_line("no_finalizer=true stress_factor=42");
             assert!(success);
             assert!(*options.no_finalizer);
-            assert_eq!(*options.stress_factor, 42);
+            assert_eq!(options.stress_factor.get(), 42);
         })
     }
 
@@ -618,6 +1245,41 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_try_set_errors() {
+        serial_test(|| {
+            let mut options = Options::default();
+            assert!(options
+                .try_set_from_command_line("stress_factor", "42")
+                .is_ok());
+            assert_eq!(options.stress_factor.get(), 42);
+            assert!(matches!(
+                options.try_set_from_command_line("no_such_option", "1"),
+                Err(MMTKError::UnknownOption(_))
+            ));
+            assert!(matches!(
+                options.try_set_from_command_line("no_finalizer", "100"),
+                Err(MMTKError::InvalidOptionValue(OptionError {
+                    kind: OptionErrorKind::Unparsable { .. },
+                    default_applied: true,
+                    ..
+                }))
+            ));
+            match options.try_set_from_command_line("survivor_age_threshold", "16") {
+                Err(MMTKError::InvalidOptionValue(e)) => {
+                    assert_eq!(e.name, "survivor_age_threshold");
+                    assert_eq!(e.kind, OptionErrorKind::Invalid);
+                    assert_eq!(e.accepted, Some("1 to 15"));
+                }
+                result => panic!("Unexpected result: {:?}", result),
+            }
+            assert!(matches!(
+                options.try_set_bulk_from_command_line("no_finalizer=true stress_factor"),
+                Err(MMTKError::MalformedOptions(_))
+            ));
+        })
+    }
+
     #[test]
     fn test_set_typed_option_valid() {
         serial_test(|| {
@@ -629,13 +1291,197 @@ mod tests {
     }
 
     #[test]
-    fn test_set_typed_option_invalid() {
+    fn test_options_builder_valid() {
         serial_test(|| {
             let mut options = Options::default();
+            let result = OptionsBuilder::new(&mut options)
+                .threads(2)
+                .stress_factor(4096)
+                .done();
+            assert!(result.is_ok());
+            assert_eq!(*options.threads, 2);
+            assert_eq!(options.stress_factor.get(), 4096);
+        })
+    }
+
+    #[test]
+    fn test_options_builder_invalid() {
+        serial_test(|| {
+            let mut options = Options::default();
+            let result = OptionsBuilder::new(&mut options)
+                .heap_size(1 << 20)
+                .nursery(NurserySize::new(NurseryKind::Fixed, 2 << 20))
+                .done();
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn test_incremental_nursery_with_survivors() {
+        serial_test(|| {
+            let mut options = Options::default();
+            let result = OptionsBuilder::new(&mut options)
+                .incremental_nursery(true)
+                .survivor_age_threshold(2)
+                .done();
+            assert!(result.is_err());
+            let result = OptionsBuilder::new(&mut options)
+                .survivor_age_threshold(1)
+                .done();
+            assert!(result.is_ok());
+        })
+    }
+
+    #[test]
+    fn test_parse_nursery_size() {
+        let bounded: NurserySize = "Bounded:8192".parse().unwrap();
+        assert_eq!(bounded.kind, NurseryKind::Bounded);
+        assert_eq!(bounded.min, DEFAULT_MIN_NURSERY);
+        assert_eq!(bounded.max, 8192);
+
+        let bounded: NurserySize = "Bounded:4096:8192".parse().unwrap();
+        assert_eq!(bounded.min, 4096);
+        assert_eq!(bounded.max, 8192);
+
+        let proportional: NurserySize = "Proportional:25".parse().unwrap();
+        assert_eq!(proportional.kind, NurseryKind::Proportional);
+        assert_eq!(proportional.percent, 25);
+
+        assert!("Fixed:4096:8192".parse::<NurserySize>().is_err());
+        assert!("Fixed".parse::<NurserySize>().is_err());
+        assert!(!"Proportional:200"
+            .parse::<NurserySize>()
+            .unwrap()
+            .is_valid());
+    }
+
+    #[test]
+    fn test_parse_space_sizes() {
+        let sizes: SpaceSizes = "los:25%,nursery:8388608".parse().unwrap();
+        assert_eq!(sizes.get("los"), Some(SpaceSize::Percent(25)));
+        assert_eq!(sizes.get("nursery"), Some(SpaceSize::Bytes(8388608)));
+        assert_eq!(sizes.get("immortal"), None);
+        assert!("".parse::<SpaceSizes>().unwrap().is_empty());
+        assert!("los".parse::<SpaceSizes>().is_err());
+        assert!("los:abc".parse::<SpaceSizes>().is_err());
+    }
+
+    #[test]
+    fn test_set_live_option() {
+        serial_test(|| {
+            let options = Options::default();
+            assert!(options.set_live("stress_factor", "4096").is_ok());
+            assert_eq!(options.stress_factor.get(), 4096);
             let threads = *options.threads;
-            let success = options.threads.set(0);
-            assert!(!success);
+            assert!(matches!(
+                options.set_live("threads", "1"),
+                Err(MMTKError::OptionNotSettable(_))
+            ));
             assert_eq!(*options.threads, threads);
+            assert!(matches!(
+                options.set_live("no_such_option", "1"),
+                Err(MMTKError::UnknownOption(_))
+            ));
+            assert!(matches!(
+                options.set_live("precise_stress", "maybe"),
+                Err(MMTKError::InvalidOptionValues(_))
+            ));
+            assert!(options.precise_stress.get());
+        })
+    }
+
+    #[test]
+    fn test_set_typed_option_invalid() {
+        serial_test(|| {
+            let mut options = Options::default();
+            let heap_size = *options.heap_size;
+            let success = options.heap_size.set(0);
+            assert!(!success);
+            assert_eq!(*options.heap_size, heap_size);
+        })
+    }
+
+    #[test]
+    fn test_zero_threads() {
+        serial_test(|| {
+            let mut options = Options::default();
+            assert!(options.threads.set(0));
+            assert!(options.is_single_threaded());
+            assert!(options.threads.set(2));
+            assert_eq!(
+                options.is_single_threaded(),
+                cfg!(feature = "single_thread")
+            );
+        })
+    }
+
+    #[test]
+    fn test_env_var_diagnostics() {
+        serial_test(|| {
+            with_cleanup(
+                || {
+                    std::env::set_var("MMTK_THREADS", "abc");
+
+                    let mut options = Options::default();
+                    let diagnostics = options.diagnostics();
+                    assert_eq!(diagnostics.len(), 1);
+                    assert_eq!(diagnostics[0].name, "threads");
+                    assert_eq!(diagnostics[0].value, "abc");
+                    assert_eq!(diagnostics[0].source, OptionSource::EnvVar);
+                    assert!(matches!(
+                        diagnostics[0].kind,
+                        OptionErrorKind::Unparsable {
+                            expected: "usize",
+                            ..
+                        }
+                    ));
+                    assert!(diagnostics[0].default_applied);
+                    assert!(diagnostics[0]
+                        .to_string()
+                        .starts_with("MMTK_THREADS=\"abc\""));
+
+                    // A valid value replaces the invalid one.
+                    assert!(options.set_from_command_line("threads", "4"));
+                    assert!(options.diagnostics().is_empty());
+                },
+                || {
+                    std::env::remove_var("MMTK_THREADS");
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_command_line_diagnostics() {
+        serial_test(|| {
+            let mut options = Options::default();
+            assert!(!options.set_from_command_line("survivor_age_threshold", "20"));
+            assert!(options.set_from_command_line("stress_factor", "42"));
+            assert!(!options.set_from_command_line("stress_factor", "-1"));
+            let diagnostics = options.diagnostics();
+            assert_eq!(diagnostics.len(), 2);
+
+            assert_eq!(diagnostics[0].name, "survivor_age_threshold");
+            assert_eq!(diagnostics[0].source, OptionSource::CommandLine);
+            assert_eq!(diagnostics[0].kind, OptionErrorKind::Invalid);
+            assert_eq!(diagnostics[0].accepted, Some("1 to 15"));
+            assert!(diagnostics[0].default_applied);
+
+            // The value set before is kept.
+            assert_eq!(diagnostics[1].name, "stress_factor");
+            assert!(!diagnostics[1].default_applied);
+            assert_eq!(options.stress_factor.get(), 42);
+        })
+    }
+
+    #[test]
+    fn test_try_set_no_diagnostics() {
+        serial_test(|| {
+            let mut options = Options::default();
+            // The error is returned to the caller, so it is not recorded.
+            assert!(options.try_set_from_command_line("heap_size", "0").is_err());
+            assert!(options.set_live("stress_factor", "abc").is_err());
+            assert!(options.diagnostics().is_empty());
         })
     }
 }
@@ -0,0 +1,47 @@
+//! The periodic GC trigger. If the option `periodic_gc_ms` is set, a background thread requests
+//! a collection whenever no GC has happened within the interval, e.g. to return memory in an idle
+//! server process, or to collect before the heap fills up in a latency-sensitive one. The request
+//! is an internal collection request, so the plan decides what kind of collection to do, as it does
+//! for a GC triggered by memory pressure (e.g. a generational plan may do a nursery GC).
+
+use crate::util::platform::{platform, PlatformThread};
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::time::{Duration, Instant};
+
+/// How often the trigger checks if the VM is shutting down, at most.
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Spawn a thread that requests a collection if no GC has happened for `interval_ms`
+/// milliseconds. The thread exits when the MMTk instance shuts down.
+pub fn spawn_periodic_gc_trigger<VM: VMBinding>(
+    mmtk: &'static MMTK<VM>,
+    interval_ms: usize,
+) -> Box<dyn PlatformThread> {
+    let interval = Duration::from_millis(interval_ms as u64);
+    let trigger = move || {
+        let plan = mmtk.get_plan();
+        let mut last_gc_count = plan.base().gc_stats.gc_count();
+        let mut last_gc_time = Instant::now();
+        while !plan.base().gc_requester.is_shutting_down() {
+            platform().sleep(std::cmp::min(interval, SHUTDOWN_CHECK_INTERVAL));
+            let gc_count = plan.base().gc_stats.gc_count();
+            let now = Instant::now();
+            if gc_count != last_gc_count || plan.base().gc_in_progress() {
+                // A GC has happened (or is happening). Wait for another interval after it.
+                last_gc_count = gc_count;
+                last_gc_time = now;
+                continue;
+            }
+            if now - last_gc_time >= interval && plan.should_trigger_gc_when_heap_is_full() {
+                info!("No GC in the last {:?}, triggering collection", interval);
+                plan.base().trigger_internal_collection_request();
+                // Do not request again until the requested GC has happened.
+                last_gc_time = now;
+            }
+        }
+    };
+    platform()
+        .spawn_thread("MMTk Periodic GC Trigger", Box::new(trigger))
+        .expect("Failed to spawn the periodic GC trigger thread")
+}
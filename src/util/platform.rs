@@ -0,0 +1,336 @@
+//! The OS services that MMTk uses: the memory of the heap and its metadata, its own helper
+//! threads, and its thread-local state. The functions in [`crate::util::memory`] and the rest of
+//! MMTk call the platform instead of the OS directly, so a binding for a target without an OS, or
+//! with its own primitives (e.g. an OS kernel, or an embedded system), can implement [`Platform`]
+//! and install it with [`memory_manager::set_platform`](crate::memory_manager::set_platform).
+//! Otherwise MMTk uses [`OSPlatform`], which calls mmap and mprotect, and uses the threads and the
+//! thread-locals of `std`.
+//!
+//! GC threads are already spawned by the binding with `Collection::spawn_gc_thread`, and a build
+//! with the `single_thread` feature does not need them at all. MMTk still uses the locks and the
+//! collections of `std`, so it does not build with `no_std` yet, but it no longer needs anything
+//! else from the OS.
+
+use crate::util::error::MMTKError;
+use crate::util::Address;
+use libc::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use std::cell::{Cell, RefCell};
+use std::io::Result;
+use std::time::Duration;
+
+/// The state that MMTk keeps for each thread that calls into it. A platform keeps one for each
+/// thread (e.g. in a thread-local), and lends it to MMTk in `Platform::with_thread_state()`.
+#[derive(Default)]
+pub struct ThreadState {
+    /// Should allocation on the thread fail instead of triggering a GC when the heap is full?
+    pub(crate) no_gc_on_failure: Cell<bool>,
+    /// The allocation error on the thread to return to the caller, if any, while the errors are
+    /// returned rather than reported (see `util::error`).
+    pub(crate) allocation_error: RefCell<Option<Option<MMTKError>>>,
+    /// Is the thread refilling a zeroed block pool?
+    pub(crate) refilling_zeroed_block_pool: Cell<bool>,
+}
+
+impl ThreadState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A thread spawned by [`Platform::spawn_thread`].
+pub trait PlatformThread: Send {
+    /// Wait until the thread exits. If the thread panicked, this panics too.
+    fn join(self: Box<Self>);
+}
+
+/// The memory primitives of a platform. The addresses and sizes are aligned to pages. Errors are
+/// reported as `std::io::Error`: a platform should return `ErrorKind::AlreadyExists` if the memory
+/// is already mapped, and `ErrorKind::OutOfMemory` if it has no memory left, so MMTk can tell them
+/// from other errors.
+pub trait Platform: Sync + 'static {
+    /// Map the range as readable, writable and executable memory, and make sure it is zeroed. If
+    /// `replace` is true, replace any existing mapping of the range. Otherwise return an error if
+    /// any of the range is already mapped.
+    fn map_zeroed(&self, start: Address, size: usize, replace: bool) -> Result<()>;
+
+    /// Reserve the range without committing memory for it. The range is mapped with
+    /// `map_zeroed(start, size, true)` before it is used.
+    fn reserve(&self, start: Address, size: usize) -> Result<()>;
+
+    /// Unmap the range.
+    fn unmap(&self, start: Address, size: usize) -> Result<()>;
+
+    /// Make the range inaccessible.
+    fn protect(&self, start: Address, size: usize) -> Result<()>;
+
+    /// Make the range readable, writable and executable again after `protect()`.
+    fn unprotect(&self, start: Address, size: usize) -> Result<()>;
+
+    /// Give the physical memory of the range back to the platform, e.g. after the heap is shrunk.
+    /// The range stays mapped, and reads as zero when it is used again. A platform that cannot
+    /// do this may keep the memory.
+    fn decommit(&self, _start: Address, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Is the range mapped? This is only used for checks, and does not need to be fast.
+    fn is_mapped(&self, start: Address, size: usize) -> bool;
+
+    /// Spawn a thread that runs `body`. MMTk only spawns its helper threads with this, e.g. the
+    /// periodic GC trigger. The GC threads are spawned by the binding.
+    fn spawn_thread(
+        &self,
+        name: &str,
+        body: Box<dyn FnOnce() + Send>,
+    ) -> Result<Box<dyn PlatformThread>>;
+
+    /// Let other threads run, while the current thread waits for one of them.
+    fn yield_now(&self);
+
+    /// Block the current thread for the duration.
+    fn sleep(&self, duration: Duration);
+
+    /// Call `f` with the state of the current thread.
+    fn with_thread_state(&self, f: &mut dyn FnMut(&ThreadState));
+
+    /// The bytes of the mapped range that are resident in physical memory, or `None` if the
+    /// platform cannot tell. This is only used for statistics.
+    fn resident_bytes(&self, _start: Address, _size: usize) -> Option<usize> {
+        None
+    }
+}
+
+/// The platform of the OS, which maps memory with mmap.
+pub struct OSPlatform;
+
+impl OSPlatform {
+    fn mmap_fixed(
+        start: Address,
+        size: usize,
+        prot: libc::c_int,
+        flags: libc::c_int,
+    ) -> Result<()> {
+        let ptr = start.to_mut_ptr();
+        wrap_libc_call(
+            &|| unsafe { libc::mmap(start.to_mut_ptr(), size, prot, flags, -1, 0) },
+            ptr,
+        )
+    }
+}
+
+impl Platform for OSPlatform {
+    #[allow(clippy::let_and_return)] // Zeroing is not neceesary for some OS/s
+    fn map_zeroed(&self, start: Address, size: usize, replace: bool) -> Result<()> {
+        let prot = PROT_READ | PROT_WRITE | PROT_EXEC;
+        let fixed = if replace {
+            libc::MAP_FIXED
+        } else {
+            libc::MAP_FIXED_NOREPLACE
+        };
+        let flags = libc::MAP_ANON | libc::MAP_PRIVATE | fixed;
+        let ret = Self::mmap_fixed(start, size, prot, flags);
+        // We do not need to explicitly zero for Linux (memory is guaranteed to be zeroed)
+        #[cfg(not(target_os = "linux"))]
+        if ret.is_ok() {
+            crate::util::memory::zero(start, size)
+        }
+        ret
+    }
+
+    fn reserve(&self, start: Address, size: usize) -> Result<()> {
+        let flags =
+            libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE | libc::MAP_NORESERVE;
+        Self::mmap_fixed(start, size, PROT_NONE, flags)
+    }
+
+    fn unmap(&self, start: Address, size: usize) -> Result<()> {
+        wrap_libc_call(&|| unsafe { libc::munmap(start.to_mut_ptr(), size) }, 0)
+    }
+
+    fn protect(&self, start: Address, size: usize) -> Result<()> {
+        wrap_libc_call(
+            &|| unsafe { libc::mprotect(start.to_mut_ptr(), size, PROT_NONE) },
+            0,
+        )
+    }
+
+    fn unprotect(&self, start: Address, size: usize) -> Result<()> {
+        wrap_libc_call(
+            &|| unsafe {
+                libc::mprotect(start.to_mut_ptr(), size, PROT_READ | PROT_WRITE | PROT_EXEC)
+            },
+            0,
+        )
+    }
+
+    #[cfg(target_os = "linux")]
+    fn decommit(&self, start: Address, size: usize) -> Result<()> {
+        // The pages are zero-filled on the next access.
+        wrap_libc_call(
+            &|| unsafe { libc::madvise(start.to_mut_ptr(), size, libc::MADV_DONTNEED) },
+            0,
+        )
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn decommit(&self, start: Address, size: usize) -> Result<()> {
+        // Replace the pages with a fresh anonymous mapping, which is zeroed when it is touched.
+        let prot = PROT_READ | PROT_WRITE | PROT_EXEC;
+        let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED;
+        Self::mmap_fixed(start, size, prot, flags)
+    }
+
+    // Note that the checking has a side effect that it will map the memory if it was unmapped.
+    fn is_mapped(&self, start: Address, size: usize) -> bool {
+        let prot = PROT_READ | PROT_WRITE;
+        // MAP_FIXED_NOREPLACE returns EEXIST if already mapped
+        let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE;
+        match Self::mmap_fixed(start, size, prot, flags) {
+            Ok(_) => false,
+            Err(e) => {
+                assert!(
+                    e.kind() == std::io::ErrorKind::AlreadyExists,
+                    "Failed to check mapped: {:?}",
+                    e
+                );
+                true
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn resident_bytes(&self, start: Address, size: usize) -> Option<usize> {
+        // Query the pages in batches, so we do not need a large vector for a large range.
+        const BATCH_PAGES: usize = 4096;
+        // mincore() reports the pages of the OS, which may be larger than our pages.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let mut vec = vec![0u8; BATCH_PAGES];
+        let mut resident = 0;
+        let mut cursor = start;
+        let end = start + size;
+        while cursor < end {
+            let bytes = (end - cursor).min(BATCH_PAGES * page_size);
+            let pages = (bytes + page_size - 1) / page_size;
+            let ret = unsafe { libc::mincore(cursor.to_mut_ptr(), bytes, vec.as_mut_ptr() as _) };
+            if ret != 0 {
+                return None;
+            }
+            // The lowest bit of each byte tells if the page is resident.
+            resident += vec[..pages].iter().filter(|v| **v & 1 != 0).count();
+            cursor += bytes;
+        }
+        Some(resident * page_size)
+    }
+
+    fn spawn_thread(
+        &self,
+        name: &str,
+        body: Box<dyn FnOnce() + Send>,
+    ) -> Result<Box<dyn PlatformThread>> {
+        let handle = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(body)?;
+        Ok(Box::new(OSThread(handle)))
+    }
+
+    fn yield_now(&self) {
+        std::thread::yield_now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+
+    fn with_thread_state(&self, f: &mut dyn FnMut(&ThreadState)) {
+        thread_local! {
+            static THREAD_STATE: ThreadState = ThreadState::new();
+        }
+        THREAD_STATE.with(|state| f(state))
+    }
+}
+
+struct OSThread(std::thread::JoinHandle<()>);
+
+impl PlatformThread for OSThread {
+    fn join(self: Box<Self>) {
+        if let Err(payload) = self.0.join() {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+fn wrap_libc_call<T: PartialEq>(f: &dyn Fn() -> T, expect: T) -> Result<()> {
+    let ret = f();
+    if ret == expect {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+static PLATFORM: spin::Once<&'static dyn Platform> = spin::Once::new();
+
+/// Install the platform. This must be called before MMTk maps any memory.
+pub(crate) fn set_platform(platform: &'static dyn Platform) {
+    let mut installed = false;
+    PLATFORM.call_once(|| {
+        installed = true;
+        platform
+    });
+    assert!(
+        installed,
+        "The platform is already set, or MMTk has already used the default platform"
+    );
+}
+
+/// The platform in use. This is [`OSPlatform`] unless the binding installed another platform.
+pub(crate) fn platform() -> &'static dyn Platform {
+    *PLATFORM.call_once(|| &OSPlatform)
+}
+
+/// Call `f` with the state of the current thread, from the platform.
+pub(crate) fn with_thread_state<T>(f: impl FnOnce(&ThreadState) -> T) -> T {
+    let mut f = Some(f);
+    let mut result = None;
+    platform().with_thread_state(&mut |state| result = Some((f.take().unwrap())(state)));
+    result.expect("The platform did not call back with the thread state")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_thread_state_per_thread() {
+        with_thread_state(|state| state.no_gc_on_failure.set(true));
+        let seen = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let seen = seen.clone();
+            platform()
+                .spawn_thread(
+                    "test",
+                    Box::new(move || {
+                        let flag = with_thread_state(|state| state.no_gc_on_failure.get());
+                        seen.store(flag, Ordering::SeqCst);
+                    }),
+                )
+                .unwrap()
+        };
+        thread.join();
+        assert!(!seen.load(Ordering::SeqCst));
+        assert!(with_thread_state(|state| state
+            .no_gc_on_failure
+            .replace(false)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_join_panicked_thread() {
+        platform()
+            .spawn_thread("test", Box::new(|| panic!("The thread panics")))
+            .unwrap()
+            .join();
+    }
+}
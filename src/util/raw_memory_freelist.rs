@@ -210,9 +210,7 @@ impl Drop for RawMemoryFreeList {
     fn drop(&mut self) {
         let len = self.high_water - self.base;
         if len != 0 {
-            unsafe {
-                ::libc::munmap(self.base.as_usize() as _, len);
-            }
+            let _ = super::memory::munmap(self.base, len);
         }
     }
 }
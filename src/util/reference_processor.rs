@@ -1,9 +1,10 @@
 use std::collections::HashSet;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::vec::Vec;
 
+use crate::plan::CollectionScope;
 use crate::scheduler::ProcessEdgesWork;
 use crate::util::ObjectReference;
 use crate::util::VMWorkerThread;
@@ -77,27 +78,40 @@ impl ReferenceProcessors {
             .forward::<E>(trace, mmtk.plan.is_current_gc_nursery());
     }
 
+    /// The scope of the current GC, if it is a partial-heap GC that is not a nursery GC. The
+    /// objects outside the scope are not collected, so they are live. A nursery GC tells the
+    /// liveness of the mature objects itself.
+    fn partial_scope<VM: VMBinding>(mmtk: &'static MMTK<VM>) -> Option<Arc<CollectionScope>> {
+        if mmtk.plan.is_current_gc_nursery() {
+            None
+        } else {
+            mmtk.plan.base().collection_scope()
+        }
+    }
+
     // Methods for scanning weak references. It needs to be called in a decreasing order of reference strengths, i.e. soft > weak > phantom
 
     /// Scan soft references.
     pub fn scan_soft_refs<E: ProcessEdgesWork>(&self, trace: &mut E, mmtk: &'static MMTK<E::VM>) {
+        let scope = Self::partial_scope(mmtk);
         // For soft refs, it is up to the VM to decide when to reclaim this.
         // If this is not an emergency collection, we have no heap stress. We simply retain soft refs.
         if !mmtk.plan.is_emergency_collection() {
             // This step only retains the referents (keep the referents alive), it does not update its addresses.
             // We will call soft.scan() again with retain=false to update its addresses based on liveness.
             self.soft
-                .retain::<E>(trace, mmtk.plan.is_current_gc_nursery());
+                .retain::<E>(trace, mmtk.plan.is_current_gc_nursery(), scope.as_deref());
         }
         // This will update the references (and the referents).
         self.soft
-            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery());
+            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery(), scope.as_deref());
     }
 
     /// Scan weak references.
     pub fn scan_weak_refs<E: ProcessEdgesWork>(&self, trace: &mut E, mmtk: &'static MMTK<E::VM>) {
+        let scope = Self::partial_scope(mmtk);
         self.weak
-            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery());
+            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery(), scope.as_deref());
     }
 
     /// Scan phantom references.
@@ -106,8 +120,9 @@ impl ReferenceProcessors {
         trace: &mut E,
         mmtk: &'static MMTK<E::VM>,
     ) {
+        let scope = Self::partial_scope(mmtk);
         self.phantom
-            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery());
+            .scan::<E>(trace, mmtk.plan.is_current_gc_nursery(), scope.as_deref());
     }
 }
 
@@ -338,7 +353,12 @@ impl ReferenceProcessor {
     // TODO: nursery is currently ignored. We used to use Vec for the reference table, and use an int
     // to point to the reference that we last scanned. However, when we use HashSet for reference table,
     // we can no longer do that.
-    fn scan<E: ProcessEdgesWork>(&self, trace: &mut E, _nursery: bool) {
+    fn scan<E: ProcessEdgesWork>(
+        &self,
+        trace: &mut E,
+        _nursery: bool,
+        scope: Option<&CollectionScope>,
+    ) {
         let mut sync = self.sync.lock().unwrap();
 
         debug!("Starting ReferenceProcessor.scan({:?})", self.semantics);
@@ -357,7 +377,9 @@ impl ReferenceProcessor {
         let new_set: HashSet<ObjectReference> = sync
             .references
             .iter()
-            .filter_map(|reff| self.process_reference(trace, *reff, &mut enqueued_references))
+            .filter_map(|reff| {
+                self.process_reference(trace, *reff, &mut enqueued_references, scope)
+            })
             .collect();
 
         debug!(
@@ -377,7 +399,12 @@ impl ReferenceProcessor {
     /// It retains the referent if the reference is definitely reachable. This method does
     /// not update reference or referent. So after this method, scan() should be used to update
     /// the references/referents.
-    fn retain<E: ProcessEdgesWork>(&self, trace: &mut E, _nursery: bool) {
+    fn retain<E: ProcessEdgesWork>(
+        &self,
+        trace: &mut E,
+        _nursery: bool,
+        scope: Option<&CollectionScope>,
+    ) {
         debug_assert!(self.semantics == Semantics::SOFT);
 
         let sync = self.sync.lock().unwrap();
@@ -394,7 +421,7 @@ impl ReferenceProcessor {
 
             trace!("Processing reference: {:?}", reference);
 
-            if !reference.is_live() {
+            if !Self::is_live(*reference, scope) {
                 // Reference is currently unreachable but may get reachable by the
                 // following trace. We postpone the decision.
                 continue;
@@ -411,6 +438,12 @@ impl ReferenceProcessor {
         debug!("Ending ReferenceProcessor.retain({:?})", self.semantics);
     }
 
+    /// Is the object live? The objects outside the scope of a partial-heap GC are not collected by
+    /// the GC, so they are live.
+    fn is_live(object: ObjectReference, scope: Option<&CollectionScope>) -> bool {
+        object.is_live() || scope.map_or(false, |scope| !scope.includes(object))
+    }
+
     /// Process a reference.
     /// * If both the reference and the referent is alive, return the updated reference and update its referent properly.
     /// * If the reference is alive, and the referent is not null but not alive, return None and the reference (with cleared referent) is enqueued.
@@ -423,6 +456,7 @@ impl ReferenceProcessor {
         trace: &mut E,
         reference: ObjectReference,
         enqueued_references: &mut Vec<ObjectReference>,
+        scope: Option<&CollectionScope>,
     ) -> Option<ObjectReference> {
         debug_assert!(!reference.is_null());
 
@@ -430,7 +464,7 @@ impl ReferenceProcessor {
 
         // If the reference is dead, we're done with it. Let it (and
         // possibly its referent) be garbage-collected.
-        if !reference.is_live() {
+        if !Self::is_live(reference, scope) {
             <E::VM as VMBinding>::VMReferenceGlue::clear_referent(reference);
             trace!(" UNREACHABLE reference: {}", reference);
             trace!(" (unreachable)");
@@ -453,11 +487,11 @@ impl ReferenceProcessor {
 
         trace!(" => {}", new_reference);
 
-        if old_referent.is_live() {
+        if Self::is_live(old_referent, scope) {
             // Referent is still reachable in a way that is as strong as
             // or stronger than the current reference level.
             let new_referent = Self::get_forwarded_referent(trace, old_referent);
-            debug_assert!(new_referent.is_live());
+            debug_assert!(Self::is_live(new_referent, scope));
             trace!(" ~> {}", new_referent);
 
             // The reference object stays on the waiting list, and the
@@ -0,0 +1,329 @@
+//! Remembered sets of the references between regions. A plan that collects some regions of the
+//! heap without tracing the rest (a partial-heap GC) needs to know the references from the other
+//! regions into the regions it collects, as those references are roots of the GC, and need to be
+//! updated if the objects move.
+//!
+//! The remembered set of a region records the cards (small aligned ranges of memory) of the other
+//! regions that contain objects with references into the region. The cards are grouped by the
+//! region they are in. Each group is a hash set of cards, which is replaced by a bitmap of all the
+//! cards of the source region once it has too many cards. The write barrier records a reference
+//! on its slow path with [`RememberedSet::record`] (or [`RememberedSet::record_any`]), and the GC
+//! scans the remembered cards of the regions it collects with the packets from
+//! [`RememberedSet::scan_packets`].
+//!
+//! A card is remembered by the address of the object that holds the reference, and scanning a
+//! card scans the objects that start in the card, which are found by their alloc bits. So the
+//! plan must set the alloc bits of the objects in the source regions (e.g. with the feature
+//! `global_alloc_bit`).
+//!
+//! MarkSweep remembers the references into its LOS in a remembered set of chunks (see the option
+//! `remember_los_references`), so it can collect only the LOS. It also remembers the references
+//! into the objects of each heap from outside the heap in the same set (see the option
+//! `remember_heap_references`), so it can collect the objects of a heap.
+
+use crate::mmtk::MMTK;
+use crate::scheduler::{GCWork, GCWorker, ProcessEdgesWork};
+use crate::util::heap::regions::Region;
+use crate::util::linear_scan::{DefaultObjectSize, ObjectIterator};
+use crate::util::{Address, ObjectReference};
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+/// log2 of the bytes of a card.
+pub const LOG_BYTES_IN_CARD: usize = 9;
+/// The bytes of a card.
+pub const BYTES_IN_CARD: usize = 1 << LOG_BYTES_IN_CARD;
+
+/// A source region with more cards than this is recorded with a bitmap.
+const MAX_SPARSE_CARDS: usize = 128;
+/// The number of shards of the remembered sets. The remembered sets of different regions are
+/// usually in different shards, so barriers recording references into different regions do not
+/// contend for the same lock.
+const SHARDS: usize = 64;
+/// The number of cards scanned by a packet.
+const CARDS_PER_PACKET: usize = 64;
+
+/// The cards of a source region.
+enum CardSet {
+    /// A few cards, by their indices in the region.
+    Sparse(HashSet<usize>),
+    /// Many cards, as a bitmap of all the cards in the region.
+    Coarse(Vec<u64>),
+}
+
+impl CardSet {
+    /// Add a card. Return false if the card is already in the set.
+    fn insert(&mut self, card: usize, cards_in_region: usize) -> bool {
+        match self {
+            CardSet::Sparse(cards) => {
+                if !cards.insert(card) {
+                    return false;
+                }
+                if cards.len() > MAX_SPARSE_CARDS {
+                    let mut bitmap = vec![0u64; (cards_in_region + 63) / 64];
+                    for &card in cards.iter() {
+                        bitmap[card / 64] |= 1 << (card % 64);
+                    }
+                    *self = CardSet::Coarse(bitmap);
+                }
+                true
+            }
+            CardSet::Coarse(bitmap) => {
+                let bit = 1 << (card % 64);
+                let added = bitmap[card / 64] & bit == 0;
+                bitmap[card / 64] |= bit;
+                added
+            }
+        }
+    }
+
+    /// The indices of the cards in the set, in no particular order.
+    fn cards(&self) -> Vec<usize> {
+        match self {
+            CardSet::Sparse(cards) => cards.iter().copied().collect(),
+            CardSet::Coarse(bitmap) => (0..bitmap.len() * 64)
+                .filter(|&card| bitmap[card / 64] & (1 << (card % 64)) != 0)
+                .collect(),
+        }
+    }
+}
+
+/// The remembered set of a region: the cards with references into the region, by the regions
+/// they are in.
+type RegionRemSet = HashMap<Address, CardSet>;
+
+/// The remembered sets of the regions of type `R`.
+pub struct RememberedSet<R: Region> {
+    /// The remembered sets, by the start address of their regions.
+    shards: Vec<Mutex<HashMap<Address, RegionRemSet>>>,
+    _p: PhantomData<R>,
+}
+
+impl<R: Region> RememberedSet<R> {
+    const CARDS_IN_REGION: usize = R::BYTES >> LOG_BYTES_IN_CARD;
+
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            _p: PhantomData,
+        }
+    }
+
+    fn shard(&self, region: R) -> &Mutex<HashMap<Address, RegionRemSet>> {
+        &self.shards[(region.start() >> R::LOG_BYTES) % SHARDS]
+    }
+
+    /// Record that the object `src` has a reference to `target`. This is called on the slow path
+    /// of the write barrier. A reference within a region is not recorded. Return true if the card
+    /// of `src` was not in the remembered set of the region of `target`.
+    pub fn record(&self, src: ObjectReference, target: ObjectReference) -> bool {
+        let source = R::containing_address(src.to_address());
+        let region = R::containing_address(target.to_address());
+        if source == region {
+            return false;
+        }
+        self.record_any(src, target)
+    }
+
+    /// Record that the object `src` has a reference to `target`, even if they are in the same
+    /// region. This is for a GC that does not collect whole regions, e.g. a GC of the objects of
+    /// a heap (see [`crate::util::heap_id`]). Return true if the card of `src` was not in the
+    /// remembered set of the region of `target`.
+    pub fn record_any(&self, src: ObjectReference, target: ObjectReference) -> bool {
+        let source = R::containing_address(src.to_address());
+        let region = R::containing_address(target.to_address());
+        let card = (src.to_address() - source.start()) >> LOG_BYTES_IN_CARD;
+        let mut shard = self.shard(region).lock().unwrap();
+        shard
+            .entry(region.start())
+            .or_default()
+            .entry(source.start())
+            .or_insert_with(|| CardSet::Sparse(HashSet::new()))
+            .insert(card, Self::CARDS_IN_REGION)
+    }
+
+    /// The start addresses of the cards in the remembered set of the region, in no particular
+    /// order.
+    pub fn cards(&self, region: R) -> Vec<Address> {
+        let shard = self.shard(region).lock().unwrap();
+        match shard.get(&region.start()) {
+            Some(remset) => remset
+                .iter()
+                .flat_map(|(&source, cards)| {
+                    cards
+                        .cards()
+                        .into_iter()
+                        .map(move |card| source + (card << LOG_BYTES_IN_CARD))
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Clear the remembered set of the region, e.g. once the GC has evacuated the region.
+    pub fn clear(&self, region: R) {
+        self.shard(region).lock().unwrap().remove(&region.start());
+    }
+
+    /// Remove the cards of a region from all the remembered sets, e.g. when the region is
+    /// freed, so its memory is not scanned after it is reused.
+    pub fn remove_source(&self, source: R) {
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap();
+            shard.retain(|_, remset| {
+                remset.remove(&source.start());
+                !remset.is_empty()
+            });
+        }
+    }
+
+    /// Is the remembered set of the region empty?
+    pub fn is_empty(&self, region: R) -> bool {
+        !self
+            .shard(region)
+            .lock()
+            .unwrap()
+            .contains_key(&region.start())
+    }
+
+    /// The regions whose remembered sets are not empty, in no particular order.
+    pub fn regions(&self) -> Vec<R> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock().unwrap();
+                shard
+                    .keys()
+                    .map(|&start| R::from(start))
+                    .collect::<Vec<R>>()
+            })
+            .collect()
+    }
+
+    /// Create the packets that scan the cards in the remembered sets of the given regions, so the
+    /// GC visits the references into the regions. The cards are scanned in parallel.
+    pub fn scan_packets<E: ProcessEdgesWork>(&self, regions: &[R]) -> Vec<Box<dyn GCWork<E::VM>>> {
+        let mut cards: Vec<Address> = regions
+            .iter()
+            .flat_map(|&region| self.cards(region))
+            .collect();
+        // A card may be in the remembered sets of several regions.
+        cards.sort_unstable();
+        cards.dedup();
+        cards
+            .chunks(CARDS_PER_PACKET)
+            .map(|chunk| {
+                Box::new(ScanRememberedCards::<E>::new(chunk.to_vec())) as Box<dyn GCWork<E::VM>>
+            })
+            .collect()
+    }
+}
+
+impl<R: Region> Default for RememberedSet<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scan the objects that start in the remembered cards, with the object scanning packet of the
+/// plan.
+pub struct ScanRememberedCards<E: ProcessEdgesWork> {
+    cards: Vec<Address>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ScanRememberedCards<E> {
+    pub fn new(cards: Vec<Address>) -> Self {
+        Self {
+            cards,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanRememberedCards<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        let objects: Vec<ObjectReference> = self
+            .cards
+            .iter()
+            .flat_map(|&card| {
+                ObjectIterator::<E::VM, DefaultObjectSize<E::VM>, true>::new(
+                    card,
+                    card + BYTES_IN_CARD,
+                )
+            })
+            .collect();
+        if !objects.is_empty() {
+            // Use the scan work packet of the plan, so the plan can see the objects in the cards.
+            let process_edges_work = E::new(vec![], false, mmtk);
+            GCWork::do_work(
+                &mut process_edges_work.create_scan_work(objects, false),
+                worker,
+                mmtk,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::regions::Chunk;
+
+    fn object(address: usize) -> ObjectReference {
+        unsafe { Address::from_usize(address).to_object_reference() }
+    }
+
+    #[test]
+    fn test_record() {
+        let remset = RememberedSet::<Chunk>::new();
+        let region = Chunk::from(Address::from_usize(Chunk::BYTES * 16));
+        let source = Chunk::from(Address::from_usize(Chunk::BYTES * 17));
+        let target = object(region.start().as_usize() + 8);
+        // A reference within a region is not remembered.
+        assert!(!remset.record(object(region.start().as_usize() + 64), target));
+        assert!(remset.is_empty(region));
+
+        let src = object(source.start().as_usize() + BYTES_IN_CARD + 16);
+        assert!(remset.record(src, target));
+        // Another object in the same card.
+        assert!(!remset.record(object(source.start().as_usize() + BYTES_IN_CARD), target));
+        assert_eq!(remset.cards(region), vec![source.start() + BYTES_IN_CARD]);
+        assert!(remset.is_empty(source));
+
+        assert_eq!(remset.regions(), vec![region]);
+
+        remset.remove_source(source);
+        assert!(remset.is_empty(region));
+        assert!(remset.regions().is_empty());
+
+        // A reference within a region can still be remembered.
+        assert!(remset.record_any(object(region.start().as_usize() + 64), target));
+        assert_eq!(remset.cards(region), vec![region.start()]);
+    }
+
+    #[test]
+    fn test_coarse_cards() {
+        let remset = RememberedSet::<Chunk>::new();
+        let region = Chunk::from(Address::from_usize(Chunk::BYTES * 16));
+        let source = Chunk::from(Address::from_usize(Chunk::BYTES * 20));
+        let target = object(region.start().as_usize());
+        let count = MAX_SPARSE_CARDS * 2;
+        for i in 0..count {
+            let src = object(source.start().as_usize() + i * 2 * BYTES_IN_CARD);
+            assert!(remset.record(src, target));
+        }
+        // The cards are still recorded once they overflow to a bitmap.
+        assert!(!remset.record(object(source.start().as_usize()), target));
+        let mut cards = remset.cards(region);
+        cards.sort_unstable();
+        let expected: Vec<Address> = (0..count)
+            .map(|i| source.start() + i * 2 * BYTES_IN_CARD)
+            .collect();
+        assert_eq!(cards, expected);
+
+        remset.clear(region);
+        assert!(remset.cards(region).is_empty());
+    }
+}
@@ -9,12 +9,27 @@ use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::Ordering;
 
+/// A callback of the binding that validates an object reached by the sanity GC, e.g. that its
+/// class pointer is valid. It returns an error that tells what is wrong with the object, if
+/// anything. See
+/// [`memory_manager::add_sanity_object_validator`](crate::memory_manager::add_sanity_object_validator).
+pub type ObjectValidator = Box<dyn Fn(ObjectReference) -> Result<(), String> + Send>;
+
+/// The sanity GC reports the errors of at most this many objects that fail the validators.
+const MAX_REPORTED_INVALID_OBJECTS: usize = 10;
+
 #[allow(dead_code)]
 pub struct SanityChecker<ES: Edge> {
     /// Visited objects
     refs: HashSet<ObjectReference>,
     /// Cached root edges for sanity root scanning
     roots: Vec<Vec<ES>>,
+    /// The validators of the binding, called for each object visited.
+    validators: Vec<ObjectValidator>,
+    /// The number of objects that failed the validators in the current sanity GC.
+    invalid_objects: usize,
+    /// The errors of the first `MAX_REPORTED_INVALID_OBJECTS` objects that failed the validators.
+    validation_errors: Vec<String>,
 }
 
 impl<ES: Edge> Default for SanityChecker<ES> {
@@ -28,7 +43,47 @@ impl<ES: Edge> SanityChecker<ES> {
         Self {
             refs: HashSet::new(),
             roots: vec![],
+            validators: vec![],
+            invalid_objects: 0,
+            validation_errors: vec![],
+        }
+    }
+
+    /// Add a validator of the binding. It is called in every sanity GC after this.
+    pub fn add_validator(&mut self, validator: ObjectValidator) {
+        self.validators.push(validator);
+    }
+
+    /// Call the validators of the binding for an object that is visited.
+    fn validate(&mut self, object: ObjectReference) {
+        let errors: Vec<String> = self
+            .validators
+            .iter()
+            .filter_map(|validator| validator(object).err())
+            .collect();
+        if errors.is_empty() {
+            return;
+        }
+        if self.invalid_objects < MAX_REPORTED_INVALID_OBJECTS {
+            self.validation_errors
+                .push(format!("{}: {}", object, errors.join(", ")));
+        }
+        self.invalid_objects += 1;
+    }
+
+    /// Take the errors of the objects that failed the validators in the current sanity GC, if any.
+    fn take_validation_errors(&mut self) -> Option<String> {
+        if self.invalid_objects == 0 {
+            return None;
         }
+        let message = format!(
+            "{} objects failed the validators of the binding in the sanity GC: {}",
+            self.invalid_objects,
+            self.validation_errors.join("; ")
+        );
+        self.invalid_objects = 0;
+        self.validation_errors.clear();
+        Some(message)
     }
 
     /// Cache a list of root edges to the sanity checker.
@@ -105,6 +160,8 @@ impl<P: Plan> GCWork<P::VM> for SanityPrepare<P> {
         {
             let mut sanity_checker = mmtk.sanity_checker.lock().unwrap();
             sanity_checker.refs.clear();
+            sanity_checker.invalid_objects = 0;
+            sanity_checker.validation_errors.clear();
         }
         for mutator in <P::VM as VMBinding>::VMActivePlan::mutators() {
             mmtk.scheduler.work_buckets[WorkBucketStage::Prepare]
@@ -130,7 +187,14 @@ impl<P: Plan> SanityRelease<P> {
 impl<P: Plan> GCWork<P::VM> for SanityRelease<P> {
     fn do_work(&mut self, _worker: &mut GCWorker<P::VM>, mmtk: &'static MMTK<P::VM>) {
         mmtk.plan.leave_sanity();
-        mmtk.sanity_checker.lock().unwrap().clear_roots_cache();
+        let validation_errors = {
+            let mut sanity_checker = mmtk.sanity_checker.lock().unwrap();
+            sanity_checker.clear_roots_cache();
+            sanity_checker.take_validation_errors()
+        };
+        if let Some(errors) = validation_errors {
+            panic!("{}", errors);
+        }
         for mutator in <P::VM as VMBinding>::VMActivePlan::mutators() {
             mmtk.scheduler.work_buckets[WorkBucketStage::Release]
                 .add(ReleaseMutator::<P::VM>::new(mutator));
@@ -183,6 +247,7 @@ impl<VM: VMBinding> ProcessEdgesWork for SanityGCProcessEdges<VM> {
             assert!(object.is_sane(), "Invalid reference {:?}", object);
             // Object is not "marked"
             sanity_checker.refs.insert(object); // "Mark" it
+            sanity_checker.validate(object);
             self.nodes.enqueue(object);
         }
         object
@@ -196,3 +261,41 @@ impl<VM: VMBinding> ProcessEdgesWork for SanityGCProcessEdges<VM> {
         ScanObjects::<Self>::new(nodes, false, roots)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Address;
+
+    fn object(address: usize) -> ObjectReference {
+        unsafe { Address::from_usize(address).to_object_reference() }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut checker = SanityChecker::<Address>::new();
+        checker.validate(object(0x1000));
+        assert!(checker.take_validation_errors().is_none());
+
+        checker.add_validator(Box::new(|object| {
+            if object.to_address().is_aligned_to(0x100) {
+                Ok(())
+            } else {
+                Err("unaligned".to_string())
+            }
+        }));
+        checker.validate(object(0x1000));
+        assert!(checker.take_validation_errors().is_none());
+        for i in 0..(MAX_REPORTED_INVALID_OBJECTS + 5) {
+            checker.validate(object(0x1008 + i * 0x100));
+        }
+        let errors = checker.take_validation_errors().unwrap();
+        assert!(errors.starts_with(&format!("{} objects", MAX_REPORTED_INVALID_OBJECTS + 5)));
+        assert_eq!(
+            errors.matches("unaligned").count(),
+            MAX_REPORTED_INVALID_OBJECTS
+        );
+        // The errors are reset once they are taken.
+        assert!(checker.take_validation_errors().is_none());
+    }
+}
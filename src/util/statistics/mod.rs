@@ -1,5 +1,7 @@
 pub use self::counter::Counter;
+pub use self::counter::EventCounter;
+pub use self::counter::SizeCounter;
 pub use self::counter::Timer;
 
-pub mod counter;
-pub mod stats;
+pub(crate) mod counter;
+pub(crate) mod stats;
@@ -110,14 +110,13 @@ impl Stats {
         implicit_start: bool,
         merge_phases: bool,
     ) -> Arc<Mutex<EventCounter>> {
-        let mut guard = self.counters.lock().unwrap();
         let counter = Arc::new(Mutex::new(EventCounter::new(
             name.to_string(),
             self.shared.clone(),
             implicit_start,
             merge_phases,
         )));
-        guard.push(counter.clone());
+        self.add_counter(counter.clone());
         counter
     }
 
@@ -138,7 +137,6 @@ impl Stats {
         implicit_start: bool,
         merge_phases: bool,
     ) -> Arc<Mutex<Timer>> {
-        let mut guard = self.counters.lock().unwrap();
         let counter = Arc::new(Mutex::new(Timer::new(
             name.to_string(),
             self.shared.clone(),
@@ -146,10 +144,31 @@ impl Stats {
             merge_phases,
             MonotoneNanoTime {},
         )));
-        guard.push(counter.clone());
+        self.add_counter(counter.clone());
         counter
     }
 
+    /// Add a counter to the statistics. Counters may be added at any time, e.g. by the binding
+    /// or by an analysis. If a counter that starts implicitly is added while stats are being
+    /// gathered, it is started now, as `start_all` has already been called.
+    fn add_counter(&self, counter: Arc<Mutex<dyn Counter + Send>>) {
+        let mut counters = self.counters.lock().unwrap();
+        {
+            let mut new = counter.lock().unwrap();
+            assert!(
+                counters
+                    .iter()
+                    .all(|c| c.lock().unwrap().name() != new.name()),
+                "A counter named {} already exists",
+                new.name()
+            );
+            if self.get_gathering_stats() && new.implicitly_start() {
+                new.start();
+            }
+        }
+        counters.push(counter);
+    }
+
     pub fn start_gc(&self) {
         self.gc_count.fetch_add(1, Ordering::SeqCst);
         if !self.get_gathering_stats() {
@@ -267,3 +286,33 @@ impl Stats {
         self.shared.get_gathering_stats()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_added_while_gathering() {
+        let stats = Stats::new(&Options::default());
+        let before = stats.new_event_counter("before", true, true);
+        stats.start_all();
+        // A counter added after the stats started is started as well.
+        let after = stats.new_event_counter("after", true, true);
+        let manual = stats.new_event_counter("manual", false, true);
+        before.lock().unwrap().inc();
+        after.lock().unwrap().inc_by(2);
+        manual.lock().unwrap().inc();
+        stats.stop_all_counters();
+        assert_eq!(before.lock().unwrap().get_total(None), 1);
+        assert_eq!(after.lock().unwrap().get_total(None), 2);
+        assert_eq!(manual.lock().unwrap().get_total(None), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicate_counter() {
+        let stats = Stats::new(&Options::default());
+        stats.new_event_counter("counter", true, true);
+        stats.new_timer("counter", true, true);
+    }
+}
@@ -0,0 +1,235 @@
+//! Bounded memory for tracing. The work packets of the transitive closure hold the edges and the
+//! objects that are yet to be traced, and a heap with a wide frontier (e.g. long lists of objects
+//! that each point to many others) can make them take a lot of memory in a GC, or even run out of
+//! memory inside the GC. With the option `max_tracing_memory`, a `ProcessEdgesWork` whose closure
+//! stages already have that much memory in pending packets does not create a packet to scan the
+//! objects it has just marked. It sets an overflow bit in side metadata for each of the objects
+//! instead, and records the regions of the heap that have overflowed objects. Once the packets of
+//! the open stages are drained, and before the next stage opens, the overflowed objects are found
+//! from their bits, one packet per region, and scanned. An object is only marked once, so it only
+//! overflows once, and the rescans stop when no more objects overflow.
+//!
+//! The bits take 1 bit of side metadata per 8 bytes (4 bytes on 32 bits), and the set of regions
+//! one entry per region, so the memory taken by the overflowed objects is bounded by the heap size
+//! rather than by the shape of the object graph.
+//!
+//! The overflowed objects of a stage are scanned with the scanning packets of the last
+//! `ProcessEdgesWork` that overflowed them. This is the case for the plans in mmtk-core, which
+//! use one `ProcessEdgesWork` type for the closure of a GC.
+
+use crate::mmtk::MMTK;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::constants::{
+    BITS_IN_BYTE, LOG_BITS_IN_BYTE, LOG_BYTES_IN_PAGE, LOG_MIN_OBJECT_SIZE,
+};
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::{Address, ObjectReference};
+use crate::vm::VMBinding;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub(crate) const TRACING_OVERFLOW_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::TRACING_OVERFLOW;
+
+/// log2 of the bytes of a region. The overflow bits of a region are on one page of side metadata,
+/// and side metadata is mapped by pages, so the bits of the whole region of an overflowed object
+/// are mapped.
+pub const LOG_BYTES_IN_REGION: usize =
+    LOG_BYTES_IN_PAGE as usize + LOG_BITS_IN_BYTE as usize + LOG_MIN_OBJECT_SIZE as usize;
+/// The bytes of a region.
+pub const BYTES_IN_REGION: usize = 1 << LOG_BYTES_IN_REGION;
+/// The bytes of the overflow bits of a region.
+const META_BYTES_IN_REGION: usize = 1 << LOG_BYTES_IN_PAGE;
+
+/// The overflowed objects are scanned in packets of at most this many objects.
+const OBJECTS_IN_SCAN_PACKET: usize = 4096;
+
+/// Creates a packet that scans some overflowed objects.
+pub(crate) type ScanWorkFactory<VM> =
+    Arc<dyn Fn(Vec<ObjectReference>) -> Box<dyn GCWork<VM>> + Send + Sync>;
+
+/// The overflowed objects of the current GC.
+pub(crate) struct TracingOverflow<VM: VMBinding> {
+    /// The regions that have overflowed objects.
+    regions: Mutex<HashSet<Address>>,
+    /// Creates the packets that scan the overflowed objects.
+    scan_work: Mutex<Option<ScanWorkFactory<VM>>>,
+}
+
+impl<VM: VMBinding> TracingOverflow<VM> {
+    pub fn new() -> Self {
+        Self {
+            regions: Default::default(),
+            scan_work: Mutex::new(None),
+        }
+    }
+
+    /// Defer the scanning of objects that have just been marked. They are scanned with the packets
+    /// created by `scan_work`.
+    pub fn overflow(&self, objects: &[ObjectReference], scan_work: ScanWorkFactory<VM>) {
+        // The bits are set before the regions are recorded, and the regions are only taken once
+        // all the workers are parked, so no bit is missed.
+        let regions = set_overflow_bits(objects);
+        self.regions.lock().unwrap().extend(regions);
+        *self.scan_work.lock().unwrap() = Some(scan_work);
+    }
+
+    /// Take the packets that scan the overflowed objects, one packet per region. Return an empty
+    /// vec if no object has overflowed since the last call.
+    pub fn take_rescan_work(&self) -> Vec<Box<dyn GCWork<VM>>> {
+        let regions = std::mem::take(&mut *self.regions.lock().unwrap());
+        if regions.is_empty() {
+            return vec![];
+        }
+        debug!("Rescan {} regions with overflowed objects", regions.len());
+        let scan_work = self.scan_work.lock().unwrap().clone().unwrap();
+        regions
+            .into_iter()
+            .map(|region| {
+                Box::new(RescanOverflowRegion {
+                    region,
+                    scan_work: scan_work.clone(),
+                }) as Box<dyn GCWork<VM>>
+            })
+            .collect()
+    }
+}
+
+/// Set the overflow bits of the objects, and return the regions of the objects.
+fn set_overflow_bits(objects: &[ObjectReference]) -> HashSet<Address> {
+    for object in objects {
+        side_metadata::store_atomic(
+            &TRACING_OVERFLOW_SIDE_METADATA_SPEC,
+            object.to_address(),
+            1,
+            Ordering::Relaxed,
+        );
+    }
+    objects
+        .iter()
+        .map(|object| region_of(object.to_address()))
+        .collect()
+}
+
+/// The region that an address is in.
+fn region_of(address: Address) -> Address {
+    address.align_down(BYTES_IN_REGION)
+}
+
+/// Take the overflowed objects of a region, and clear their bits.
+fn take_objects(region: Address) -> Vec<ObjectReference> {
+    let meta_start =
+        side_metadata::address_to_meta_address(&TRACING_OVERFLOW_SIDE_METADATA_SPEC, region);
+    let mut objects = vec![];
+    for index in 0..META_BYTES_IN_REGION {
+        let meta = unsafe { &*(meta_start + index).to_ptr::<AtomicU8>() };
+        // Other workers may overflow objects in the region while it is rescanned, so the bits are
+        // cleared atomically with the load.
+        if meta.load(Ordering::Relaxed) != 0 {
+            push_objects(&mut objects, region, index, meta.swap(0, Ordering::Relaxed));
+        }
+    }
+    objects
+}
+
+/// Push the objects whose overflow bits are set in the `index`-th metadata byte of a region.
+fn push_objects(objects: &mut Vec<ObjectReference>, region: Address, index: usize, byte: u8) {
+    for bit in 0..BITS_IN_BYTE {
+        if byte & (1 << bit) != 0 {
+            let offset = ((index << LOG_BITS_IN_BYTE) + bit) << LOG_MIN_OBJECT_SIZE;
+            objects.push(unsafe { (region + offset).to_object_reference() });
+        }
+    }
+}
+
+/// Scan the overflowed objects of a region.
+struct RescanOverflowRegion<VM: VMBinding> {
+    region: Address,
+    scan_work: ScanWorkFactory<VM>,
+}
+
+impl<VM: VMBinding> GCWork<VM> for RescanOverflowRegion<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let objects = take_objects(self.region);
+        for objects in objects.chunks(OBJECTS_IN_SCAN_PACKET) {
+            (self.scan_work)(objects.to_vec()).do_work(worker, mmtk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::MIN_OBJECT_SIZE;
+    use crate::util::heap::layout::vm_layout_constants::{BYTES_IN_CHUNK, HEAP_START};
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+
+    #[test]
+    fn test_overflow_and_rescan() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![TRACING_OVERFLOW_SIDE_METADATA_SPEC],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_CHUNK)
+                        .unwrap();
+                    // A wide frontier: many objects in a few regions overflow at once.
+                    let object = |region: usize, index: usize| unsafe {
+                        (HEAP_START + region * BYTES_IN_REGION + index * 2 * MIN_OBJECT_SIZE)
+                            .to_object_reference()
+                    };
+                    let objects: Vec<ObjectReference> = (0..3)
+                        .flat_map(|region| (0..1000).map(move |index| object(region, index)))
+                        .collect();
+                    let regions = set_overflow_bits(&objects);
+                    assert_eq!(regions.len(), 3);
+
+                    // Each region is rescanned once, with all its overflowed objects.
+                    let mut rescanned = vec![];
+                    for region in regions {
+                        let taken = take_objects(region);
+                        assert_eq!(taken.len(), 1000);
+                        assert!(taken.iter().all(|o| region_of(o.to_address()) == region));
+                        rescanned.extend(taken);
+                        assert!(take_objects(region).is_empty());
+                    }
+                    rescanned.sort_by_key(|o| o.to_address());
+                    assert_eq!(rescanned, objects);
+                },
+                || {
+                    metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_CHUNK);
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn test_push_objects() {
+        let region = unsafe { Address::from_usize(0x1000_0000) };
+        assert_eq!(region_of(region + (BYTES_IN_REGION - 1)), region);
+        assert_eq!(
+            region_of(region + BYTES_IN_REGION),
+            region + BYTES_IN_REGION
+        );
+
+        let mut objects = vec![];
+        push_objects(&mut objects, region, 0, 0);
+        assert!(objects.is_empty());
+        push_objects(&mut objects, region, 0, 0b1000_0001);
+        push_objects(&mut objects, region, 2, 0b10);
+        let offsets: Vec<usize> = objects.iter().map(|o| o.to_address() - region).collect();
+        assert_eq!(
+            offsets,
+            vec![
+                0,
+                7 * MIN_OBJECT_SIZE,
+                (2 * BITS_IN_BYTE + 1) * MIN_OBJECT_SIZE
+            ]
+        );
+    }
+}
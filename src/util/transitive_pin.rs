@@ -0,0 +1,185 @@
+//! Transitive pinning keeps all the objects reachable from an object in place while a pinning
+//! region is active, e.g. while a graph of objects is exposed to native code.
+//!
+//! In each GC, the root of each active region is traced as a pinned root (see
+//! `RootsWorkFactory::create_process_pinned_roots_work`), which also keeps it alive, and
+//! `PinClosure` work packets propagate the pinning to the objects reachable from the roots before
+//! the closure starts.  The pinning of an object only lasts for the current GC, so exiting a
+//! region unpins its objects from the next GC on.
+//!
+//! The objects pinned in the current GC are marked with a bit of side metadata per 8 bytes (4
+//! bytes on 32 bits), so a `PinClosure` visits each object once. The packets hand the objects that
+//! they pin over in batches, and the bits of those objects are cleared at the end of the GC.
+
+use crate::mmtk::MMTK;
+use crate::util::metadata::side_metadata::{self, SideMetadataSpec};
+use crate::util::ObjectReference;
+use crate::vm::VMBinding;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+pub(crate) const TRANSITIVE_PIN_SIDE_METADATA_SPEC: SideMetadataSpec =
+    crate::util::metadata::side_metadata::spec_defs::TRANSITIVE_PIN;
+
+/// The pinning regions of an MMTk instance.
+pub(crate) struct TransitivePinning {
+    /// The root of each active region, by the region id.
+    regions: Mutex<HashMap<usize, ObjectReference>>,
+    /// The id for the next region.
+    next_id: AtomicUsize,
+    /// The batches of objects that are pinned in the current GC. Their pin bits are cleared at the
+    /// end of the GC.
+    pinned: Mutex<Vec<Vec<ObjectReference>>>,
+}
+
+impl TransitivePinning {
+    pub fn new() -> Self {
+        Self {
+            regions: Default::default(),
+            next_id: AtomicUsize::new(0),
+            pinned: Default::default(),
+        }
+    }
+
+    /// Start a region that pins the objects reachable from `root`, and return the region id.
+    pub fn enter(&self, root: ObjectReference) -> usize {
+        debug_assert!(!root.is_null());
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.regions.lock().unwrap().insert(id, root);
+        id
+    }
+
+    /// End the region with the given id.
+    pub fn exit(&self, id: usize) {
+        let root = self.regions.lock().unwrap().remove(&id);
+        debug_assert!(root.is_some(), "Pinning region {} is not active", id);
+    }
+
+    /// The roots of the active regions, without duplicates.
+    pub fn roots(&self) -> Vec<ObjectReference> {
+        let regions = self.regions.lock().unwrap();
+        let roots: HashSet<ObjectReference> = regions.values().copied().collect();
+        roots.into_iter().collect()
+    }
+
+    /// Mark the object as pinned in the current GC. Return false if it was already pinned. The
+    /// caller needs to pass each object that it has pinned to `add_pinned` before the GC ends.
+    #[inline]
+    pub fn try_pin(&self, object: ObjectReference) -> bool {
+        side_metadata::compare_exchange_atomic(
+            &TRANSITIVE_PIN_SIDE_METADATA_SPEC,
+            object.to_address(),
+            0,
+            1,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+    }
+
+    /// Record a batch of objects that have been pinned with `try_pin`.
+    pub fn add_pinned(&self, objects: Vec<ObjectReference>) {
+        if !objects.is_empty() {
+            self.pinned.lock().unwrap().push(objects);
+        }
+    }
+
+    /// Unpin all the objects. This is called at the end of each GC.
+    pub fn reset(&self) {
+        let batches = std::mem::take(&mut *self.pinned.lock().unwrap());
+        for object in batches.into_iter().flatten() {
+            side_metadata::store_atomic(
+                &TRANSITIVE_PIN_SIDE_METADATA_SPEC,
+                object.to_address(),
+                0,
+                Ordering::SeqCst,
+            );
+        }
+    }
+}
+
+/// An active pinning region. GCs do not move the objects reachable from the root of the region,
+/// until the region is dropped.  The root is kept alive while the region is active.
+#[must_use = "the objects are unpinned when the region is dropped"]
+pub struct PinningRegion<VM: VMBinding> {
+    mmtk: &'static MMTK<VM>,
+    id: usize,
+}
+
+impl<VM: VMBinding> PinningRegion<VM> {
+    pub(crate) fn enter(mmtk: &'static MMTK<VM>, root: ObjectReference) -> Self {
+        let id = mmtk.transitive_pinning.enter(root);
+        Self { mmtk, id }
+    }
+
+    /// End the region.  This is the same as dropping the region.
+    pub fn exit(self) {}
+}
+
+impl<VM: VMBinding> Drop for PinningRegion<VM> {
+    fn drop(&mut self) {
+        self.mmtk.transitive_pinning.exit(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::BYTES_IN_PAGE;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::metadata::side_metadata::SideMetadataContext;
+    use crate::util::test_util::{serial_test, with_cleanup};
+    use crate::util::Address;
+
+    fn object(addr: usize) -> ObjectReference {
+        unsafe { Address::from_usize(addr).to_object_reference() }
+    }
+
+    #[test]
+    fn test_nested_regions() {
+        let pinning = TransitivePinning::new();
+        let outer = pinning.enter(object(0x1000));
+        let inner = pinning.enter(object(0x1000));
+        let other = pinning.enter(object(0x2000));
+        let mut roots = pinning.roots();
+        roots.sort_by_key(|root| root.to_address());
+        assert_eq!(roots, vec![object(0x1000), object(0x2000)]);
+
+        // The object is still pinned by the outer region.
+        pinning.exit(inner);
+        pinning.exit(other);
+        assert_eq!(pinning.roots(), vec![object(0x1000)]);
+        pinning.exit(outer);
+        assert!(pinning.roots().is_empty());
+    }
+
+    #[test]
+    fn test_pinned_in_current_gc() {
+        serial_test(|| {
+            let metadata = SideMetadataContext {
+                global: vec![TRANSITIVE_PIN_SIDE_METADATA_SPEC],
+                local: vec![],
+            };
+            with_cleanup(
+                || {
+                    metadata
+                        .try_map_metadata_space(HEAP_START, BYTES_IN_PAGE)
+                        .unwrap();
+                    let pinning = TransitivePinning::new();
+                    let a = object(HEAP_START.as_usize());
+                    let b = object(HEAP_START.as_usize() + 16);
+                    assert!(pinning.try_pin(a));
+                    assert!(!pinning.try_pin(a));
+                    assert!(pinning.try_pin(b));
+                    pinning.add_pinned(vec![a, b]);
+                    pinning.reset();
+                    assert!(pinning.try_pin(a));
+                    assert!(pinning.try_pin(b));
+                    pinning.add_pinned(vec![a, b]);
+                    pinning.reset();
+                },
+                || metadata.ensure_unmap_metadata_space(HEAP_START, BYTES_IN_PAGE),
+            )
+        })
+    }
+}
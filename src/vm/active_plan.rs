@@ -60,6 +60,14 @@ pub trait ActivePlan<VM: VMBinding> {
     /// not need to be thread safe.
     fn get_next_mutator() -> Option<&'static mut Mutator<VM>>;
 
+    /// Remove a mutator from the mutators that `get_next_mutator()` returns, as the mutator is
+    /// being destroyed. MMTk calls this from [`crate::memory_manager::destroy_mutator`] after the
+    /// mutator is flushed, while no GC is in progress and none can start, so a GC never sees a
+    /// mutator that is partially destroyed. A binding that removes the mutator by itself before
+    /// calling `destroy_mutator()` does not need to implement this, but then it needs to make sure
+    /// that no GC happens between the removal and the destruction.
+    fn unregister_mutator(_mutator: &mut Mutator<VM>) {}
+
     /// A utility method to provide a thread-safe mutator iterator from `reset_mutator_iterator()` and `get_next_mutator()`.
     fn mutators<'a>() -> SynchronizedMutatorIterator<'a, VM> {
         SynchronizedMutatorIterator {
@@ -0,0 +1,240 @@
+//! Checks of the constants of a binding, so a binding whose constants MMTk cannot work with fails
+//! when it is built or when the MMTk instance is built, with a message that tells what is wrong,
+//! instead of failing an assertion deep in a GC.
+//!
+//! The constants of [`VMBinding`] are checked at compile time: the checks are associated constants
+//! that are evaluated when [`check_binding`] is instantiated for the binding. The metadata specs of
+//! [`ObjectModel`] are checked by [`check_binding`] when the MMTk instance is built
+//! (`MMTKBuilder::build()`), and all the problems are reported together.
+
+use crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC;
+use crate::util::constants::{BYTES_IN_PAGE, LOG_BYTES_IN_PAGE, LOG_MIN_OBJECT_SIZE};
+use crate::util::metadata::mark_bit;
+use crate::util::metadata::side_metadata::{
+    self, SideMetadataSpec, GLOBAL_SIDE_METADATA_VM_BASE_OFFSET, LOCAL_SIDE_METADATA_VM_BASE_OFFSET,
+};
+use crate::util::metadata::MetadataSpec;
+use crate::util::options::{Options, PlanSelector};
+use crate::vm::{ObjectModel, VMBinding};
+use std::marker::PhantomData;
+
+/// The checks of the constants of a binding that can be evaluated at compile time.
+struct ConstantChecks<VM>(PhantomData<VM>);
+
+impl<VM: VMBinding> ConstantChecks<VM> {
+    const ALIGNMENTS: () = {
+        assert!(
+            VM::MIN_ALIGNMENT.is_power_of_two(),
+            "VMBinding::MIN_ALIGNMENT must be a power of two"
+        );
+        assert!(
+            VM::MAX_ALIGNMENT.is_power_of_two() && VM::MAX_ALIGNMENT >= VM::MIN_ALIGNMENT,
+            "VMBinding::MAX_ALIGNMENT must be a power of two, and not smaller than MIN_ALIGNMENT"
+        );
+        assert!(
+            VM::MAX_ALIGNMENT <= BYTES_IN_PAGE,
+            "VMBinding::MAX_ALIGNMENT must not be larger than a page"
+        );
+        assert!(
+            VM::ALLOC_END_ALIGNMENT.is_power_of_two(),
+            "VMBinding::ALLOC_END_ALIGNMENT must be a power of two"
+        );
+    };
+}
+
+/// Check the constants of a binding for the options of an MMTk instance. Returns an error that
+/// describes all the problems, if there is any.
+pub(crate) fn check_binding<VM: VMBinding>(options: &Options) -> Result<(), String> {
+    // Evaluate the compile time checks for the binding.
+    #[allow(clippy::let_unit_value)]
+    let () = ConstantChecks::<VM>::ALIGNMENTS;
+
+    let mut errors = vec![];
+    if VM::VMObjectModel::VM_WORST_CASE_COPY_EXPANSION < 1.0 {
+        errors.push(format!(
+            "ObjectModel::VM_WORST_CASE_COPY_EXPANSION ({}) must not be smaller than 1",
+            VM::VMObjectModel::VM_WORST_CASE_COPY_EXPANSION
+        ));
+    }
+
+    // The per-object specs have a region no larger than the minimal object size, so no two
+    // objects share their metadata. The mark and nursery bits of the LOS are per page.
+    let mut specs: Vec<(&str, MetadataSpec, bool, usize)> = vec![
+        (
+            "GLOBAL_LOG_BIT_SPEC",
+            *VM::VMObjectModel::GLOBAL_LOG_BIT_SPEC,
+            true,
+            LOG_MIN_OBJECT_SIZE as usize,
+        ),
+        (
+            "LOCAL_FORWARDING_POINTER_SPEC",
+            *VM::VMObjectModel::LOCAL_FORWARDING_POINTER_SPEC,
+            false,
+            LOG_MIN_OBJECT_SIZE as usize,
+        ),
+        (
+            "LOCAL_FORWARDING_BITS_SPEC",
+            *VM::VMObjectModel::LOCAL_FORWARDING_BITS_SPEC,
+            false,
+            LOG_MIN_OBJECT_SIZE as usize,
+        ),
+        (
+            "LOCAL_MARK_BIT_SPEC",
+            *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+            false,
+            LOG_MIN_OBJECT_SIZE as usize,
+        ),
+        (
+            "LOCAL_LOS_MARK_NURSERY_SPEC",
+            *VM::VMObjectModel::LOCAL_LOS_MARK_NURSERY_SPEC,
+            false,
+            LOG_BYTES_IN_PAGE as usize,
+        ),
+    ];
+    if let Some(side_spec) = VM::VMObjectModel::LOCAL_MARK_BIT_SIDE_SPEC {
+        if side_spec.is_on_side() {
+            specs.push((
+                "LOCAL_MARK_BIT_SIDE_SPEC",
+                *side_spec,
+                false,
+                LOG_MIN_OBJECT_SIZE as usize,
+            ));
+        } else {
+            errors.push(
+                "ObjectModel::LOCAL_MARK_BIT_SIDE_SPEC must be a side metadata spec".to_string(),
+            );
+        }
+    }
+
+    let mut local_side_specs: Vec<(&str, SideMetadataSpec)> = vec![];
+    for (name, spec, is_global, max_log_bytes_in_region) in specs {
+        if let MetadataSpec::OnSide(side_spec) = spec {
+            if let Err(e) = check_side_spec(&side_spec, is_global, max_log_bytes_in_region) {
+                errors.push(format!("ObjectModel::{} {}", name, e));
+            }
+            if !is_global {
+                local_side_specs.push((name, side_spec));
+            }
+        }
+    }
+    // The local specs are used together by the policies, e.g. the forwarding bits and the mark
+    // bits of the Immix space.
+    for (i, (name_1, spec_1)) in local_side_specs.iter().enumerate() {
+        for (name_2, spec_2) in &local_side_specs[i + 1..] {
+            if spec_1 != spec_2 && side_metadata::verify_no_overlap_local(spec_1, spec_2).is_err() {
+                errors.push(format!(
+                    "ObjectModel::{} and ObjectModel::{} overlap",
+                    name_1, name_2
+                ));
+            }
+        }
+    }
+
+    // MallocSpace sweeps the mark bits on the side together with the alloc bits.
+    if matches!(*options.plan, PlanSelector::MarkSweep) {
+        if let MetadataSpec::OnSide(spec) = mark_bit_spec_for_malloc_space::<VM>() {
+            if let Err(e) = check_same_region(&spec, &ALLOC_SIDE_METADATA_SPEC) {
+                errors.push(format!("The mark bit of MallocSpace {}", e));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The binding is not compatible with MMTk: {}",
+            errors.join("; ")
+        ))
+    }
+}
+
+/// The mark bit that MallocSpace uses, if the side spec is valid.
+fn mark_bit_spec_for_malloc_space<VM: VMBinding>() -> MetadataSpec {
+    match VM::VMObjectModel::LOCAL_MARK_BIT_SIDE_SPEC {
+        Some(side_spec) if !side_spec.is_on_side() => *VM::VMObjectModel::LOCAL_MARK_BIT_SPEC,
+        _ => mark_bit::mark_bit_spec_for_space::<VM>("MallocSpace"),
+    }
+}
+
+/// Check a side metadata spec of the binding. It needs to be global or local as MMTk expects, to
+/// be laid out in the side metadata available to the binding, and to have a region no larger than
+/// `max_log_bytes_in_region`.
+fn check_side_spec(
+    spec: &SideMetadataSpec,
+    is_global: bool,
+    max_log_bytes_in_region: usize,
+) -> Result<(), String> {
+    if spec.is_global != is_global {
+        return Err(format!(
+            "must be a {} spec",
+            if is_global { "global" } else { "local" }
+        ));
+    }
+    let base = if is_global {
+        GLOBAL_SIDE_METADATA_VM_BASE_OFFSET
+    } else {
+        LOCAL_SIDE_METADATA_VM_BASE_OFFSET
+    };
+    if !spec.starts_at_or_after(base) {
+        return Err(
+            "overlaps the side metadata of MMTk. Lay it out from side_first() or side_after()"
+                .into(),
+        );
+    }
+    if spec.log_bytes_in_region > max_log_bytes_in_region {
+        return Err(format!(
+            "has a region of 2^{} bytes, which is larger than 2^{} bytes",
+            spec.log_bytes_in_region, max_log_bytes_in_region
+        ));
+    }
+    Ok(())
+}
+
+/// Check that two side metadata specs have the same region size.
+fn check_same_region(spec: &SideMetadataSpec, other: &SideMetadataSpec) -> Result<(), String> {
+    if spec.log_bytes_in_region != other.log_bytes_in_region {
+        return Err(format!(
+            "has a region of 2^{} bytes, but {} has a region of 2^{} bytes",
+            spec.log_bytes_in_region, other.name, other.log_bytes_in_region
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_spec(log_bytes_in_region: usize) -> SideMetadataSpec {
+        SideMetadataSpec {
+            name: "TestSpec",
+            is_global: false,
+            offset: LOCAL_SIDE_METADATA_VM_BASE_OFFSET,
+            log_num_of_bits: 0,
+            log_bytes_in_region,
+        }
+    }
+
+    #[test]
+    fn test_check_side_spec() {
+        let min = LOG_MIN_OBJECT_SIZE as usize;
+        assert!(check_side_spec(&local_spec(min), false, min).is_ok());
+        assert!(check_side_spec(&local_spec(min), true, min).is_err());
+        assert!(check_side_spec(&local_spec(min + 1), false, min).is_err());
+
+        // A spec at the start of the local side metadata overlaps the specs of MMTk.
+        let spec = SideMetadataSpec {
+            offset: side_metadata::LOCAL_SIDE_METADATA_BASE_OFFSET,
+            ..local_spec(min)
+        };
+        assert!(check_side_spec(&spec, false, min).is_err());
+    }
+
+    #[test]
+    fn test_check_same_region() {
+        let min = LOG_MIN_OBJECT_SIZE as usize;
+        assert!(check_same_region(&local_spec(min), &ALLOC_SIDE_METADATA_SPEC).is_ok());
+        assert!(check_same_region(&local_spec(min + 1), &ALLOC_SIDE_METADATA_SPEC).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::plan::MutatorContext;
 use crate::util::alloc::AllocationError;
+use crate::util::copy::CopyConfig;
 use crate::util::opaque_pointer::*;
+use crate::util::options::PlanSelector;
 use crate::vm::VMBinding;
 use crate::{scheduler::*, Mutator};
 
@@ -25,6 +27,9 @@ pub trait Collection<VM: VMBinding> {
     /// This method is called by a single thread in MMTk (the GC controller).
     /// This method should not return until all the threads are yielded.
     /// The actual thread synchronization mechanism is up to the VM, and MMTk does not make assumptions on that.
+    /// Without GC threads (the `single_thread` feature, or the option `threads=0`), this is called on the mutator thread
+    /// that triggered the GC, and that mutator is not blocked with `block_for_gc()`, so the binding should not wait for it
+    /// to yield.
     ///
     /// Arguments:
     /// * `tls`: The thread pointer for the GC controller/coordinator.
@@ -45,6 +50,9 @@ pub trait Collection<VM: VMBinding> {
     ///
     /// Arguments:
     /// * `tls`: The current thread pointer that should be blocked. The VM can optionally check if the current thread matches `tls`.
+    ///
+    /// Without GC threads (the `single_thread` feature, or the option `threads=0`), MMTk does the GC on the current thread
+    /// instead, and this is only called if another mutator is doing a GC on its thread, which will stop this one.
     fn block_for_gc(tls: VMMutatorThread);
 
     /// Ask the VM to spawn a GC thread for MMTk. A GC thread may later call into the VM through these VM traits. Some VMs
@@ -61,6 +69,8 @@ pub trait Collection<VM: VMBinding> {
     ///   * If `Worker` is passed, it means spawning a thread to run as a GC worker.
     ///     The spawned thread shall call `memory_manager::start_worker`.
     ///   In either case, the `Box` inside should be passed back to the called function.
+    ///
+    /// With the `single_thread` feature, or the option `threads=0`, MMTk does not have GC threads, and this is not called.
     fn spawn_gc_thread(tls: VMThread, ctx: GCThreadContext<VM>);
 
     /// Allow VM-specific behaviors for a mutator after all the mutators are stopped and before any actual GC work starts.
@@ -101,6 +111,54 @@ pub trait Collection<VM: VMBinding> {
     /// Inform the VM to do its VM-specific release work at the end of a GC.
     fn vm_release() {}
 
+    /// Inform the VM that a requested GC has been cancelled because the VM is shutting down (see
+    /// `memory_manager::prepare_for_shutdown`). The mutators have not been stopped for the GC, but
+    /// the mutators that requested the GC may be blocked in `block_for_gc()`, and the binding
+    /// should release them. The heap is not changed. The default implementation calls
+    /// `resume_mutators()`, which releases the blocked mutators for bindings that block them until
+    /// the mutators are resumed.
+    ///
+    /// Arguments:
+    /// * `tls`: The thread pointer for the GC controller/coordinator.
+    fn gc_cancelled(tls: VMWorkerThread) {
+        Self::resume_mutators(tls);
+    }
+
+    /// Inform the VM that a GC failed because a work packet panicked, e.g. a binding callback
+    /// panicked while scanning an object. The binding may report the failure with VM-specific
+    /// context, such as the state of the VM. MMTk has already printed the failure, and it aborts
+    /// the process after this returns, as the heap may be in an inconsistent state.
+    ///
+    /// Arguments:
+    /// * `failure`: The work packet that panicked, the GC thread that executed it, and the panic
+    ///   message.
+    fn gc_failed(_failure: &GCFailure) {}
+
+    /// Print the backtrace of a GC thread. MMTk calls this from the GC watchdog for each GC thread
+    /// when a GC has not made any progress for `gc_watchdog_timeout` seconds. MMTk cannot get
+    /// the stack of another thread portably, so the binding needs to do it, e.g. by sending
+    /// a signal to the thread. The default implementation does nothing.
+    ///
+    /// Arguments:
+    /// * `tls`: The thread pointer of the GC thread.
+    fn print_gc_thread_backtrace(_tls: VMWorkerThread) {}
+
+    /// Customize the copy config of the plan, e.g. to promote the objects copied by a nursery GC
+    /// directly to the mature space, or to give a copy semantics its own copying allocator, so the
+    /// binding does not need to change the plan for an unusual promotion policy. MMTk calls this
+    /// when it creates the copy context of each GC worker, with the config created by the plan.
+    /// The binding may map the copy semantics to other copying allocators, and add copying
+    /// allocators for the spaces already in the space mapping of the config. For the plans that
+    /// flip two semi-spaces, an added copying allocator for either semi-space copies to the
+    /// to-space in each GC, like those of the plan. MMTk panics if the customized config is invalid
+    /// (see `CopyConfig::validate`). The config is empty for plans that do not copy objects with
+    /// copying allocators. The default implementation does nothing.
+    ///
+    /// Arguments:
+    /// * `plan`: The plan of the MMTk instance.
+    /// * `config`: The copy config created by the plan.
+    fn customize_copy_config(_plan: PlanSelector, _config: &mut CopyConfig<VM>) {}
+
     /// Delegate to the VM binding for reference processing.
     fn process_weak_refs(_worker: &mut GCWorker<VM>) {} // FIXME: Add an appropriate factory/callback parameter.
 }
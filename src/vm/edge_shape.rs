@@ -1,9 +1,11 @@
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::marker::PhantomData;
 
 use atomic::Atomic;
 
 use crate::util::{Address, ObjectReference};
+use crate::vm::{ObjectModel, VMBinding};
 
 /// An abstract edge.  An edge holds an object reference.  When we load from it, we get an
 /// ObjectReference; we can also store an ObjectReference into it.
@@ -100,6 +102,83 @@ impl Edge for SimpleEdge {
     }
 }
 
+/// An edge that represents a word-sized slot where a reference is stored in the VM's own
+/// representation, e.g. with tag bits, or as a pointer into the middle of the object. The slot is
+/// decoded with `ObjectModel::decode_reference`, and encoded with `ObjectModel::encode_reference`,
+/// so a VM whose reference fields all use the same representation does not need to implement its
+/// own `Edge`. A tag that differs between slots (e.g. a tag for the type of the referent) should
+/// be kept by the encoding, for example by having `encode_reference` derive it from the object.
+#[repr(transparent)]
+pub struct EncodedEdge<VM: VMBinding> {
+    slot_addr: *mut Atomic<usize>,
+    _vm: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> EncodedEdge<VM> {
+    /// Create an encoded edge from an address.
+    ///
+    /// Arguments:
+    /// *   `address`: The address in memory where the encoded reference is stored.
+    #[inline(always)]
+    pub fn from_address(address: Address) -> Self {
+        Self {
+            slot_addr: address.to_mut_ptr(),
+            _vm: PhantomData,
+        }
+    }
+
+    /// Get the address of the edge.
+    #[inline(always)]
+    pub fn as_address(&self) -> Address {
+        Address::from_mut_ptr(self.slot_addr)
+    }
+}
+
+// The derived implementations would require `VM` to implement the traits.
+impl<VM: VMBinding> Clone for EncodedEdge<VM> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<VM: VMBinding> Copy for EncodedEdge<VM> {}
+
+impl<VM: VMBinding> Debug for EncodedEdge<VM> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EncodedEdge({})", self.as_address())
+    }
+}
+
+impl<VM: VMBinding> PartialEq for EncodedEdge<VM> {
+    fn eq(&self, other: &Self) -> bool {
+        self.slot_addr == other.slot_addr
+    }
+}
+
+impl<VM: VMBinding> Eq for EncodedEdge<VM> {}
+
+impl<VM: VMBinding> Hash for EncodedEdge<VM> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.slot_addr.hash(state)
+    }
+}
+
+unsafe impl<VM: VMBinding> Send for EncodedEdge<VM> {}
+
+impl<VM: VMBinding> Edge for EncodedEdge<VM> {
+    #[inline(always)]
+    fn load(&self) -> ObjectReference {
+        let encoded = unsafe { (*self.slot_addr).load(atomic::Ordering::Relaxed) };
+        VM::VMObjectModel::decode_reference(encoded)
+    }
+
+    #[inline(always)]
+    fn store(&self, object: ObjectReference) {
+        let encoded = VM::VMObjectModel::encode_reference(object);
+        unsafe { (*self.slot_addr).store(encoded, atomic::Ordering::Relaxed) }
+    }
+}
+
 /// For backword compatibility, we let `Address` implement `Edge` so that existing bindings that
 /// use `Address` to represent an edge can continue to work.
 ///
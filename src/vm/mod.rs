@@ -18,6 +18,7 @@
 use crate::util::constants::*;
 
 mod active_plan;
+pub(crate) mod checks;
 mod collection;
 pub mod edge_shape;
 mod object_model;
@@ -32,6 +33,8 @@ pub use self::reference_glue::Finalizable;
 pub use self::reference_glue::ReferenceGlue;
 pub use self::scanning::EdgeVisitor;
 pub use self::scanning::ObjectTracer;
+pub use self::scanning::RefArray;
+pub use self::scanning::RefFieldBitmap;
 pub use self::scanning::RootsWorkFactory;
 pub use self::scanning::Scanning;
 
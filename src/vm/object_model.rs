@@ -1,6 +1,7 @@
 use atomic::Ordering;
 
 use self::specs::*;
+use crate::util::constants::BYTES_IN_WORD;
 use crate::util::metadata::header_metadata::HeaderMetadataSpec;
 use crate::util::{Address, ObjectReference};
 use crate::vm::VMBinding;
@@ -83,6 +84,23 @@ pub trait ObjectModel<VM: VMBinding> {
     const LOCAL_MARK_BIT_SPEC: VMLocalMarkBitSpec;
     /// The metadata specification for the mark-and-nursery bits, used by most plans that has large object allocation. 2 bits.
     const LOCAL_LOS_MARK_NURSERY_SPEC: VMLocalLOSMarkNurserySpec;
+    /// An alternative metadata specification for the mark bit on the side, for a VM whose
+    /// `LOCAL_MARK_BIT_SPEC` is in the header, but that wants some of the spaces to keep their mark
+    /// bits on the side (see `use_side_mark_bit`), e.g. to keep the header bits for the hot spaces
+    /// only. It must be a local side metadata spec. If this is `None` (default), all the spaces use
+    /// `LOCAL_MARK_BIT_SPEC`.
+    const LOCAL_MARK_BIT_SIDE_SPEC: Option<VMLocalMarkBitSpec> = None;
+
+    /// Should the space of the given name keep its mark bits on the side with
+    /// `LOCAL_MARK_BIT_SIDE_SPEC`, instead of using `LOCAL_MARK_BIT_SPEC`? MMTk calls this once
+    /// when it creates each space that marks objects, and only if `LOCAL_MARK_BIT_SIDE_SPEC` is
+    /// `Some`. By default, no space uses the side spec.
+    ///
+    /// Arguments:
+    /// * `space_name`: The name of the space, e.g. `"immix"` or `"immortal"`.
+    fn use_side_mark_bit(_space_name: &str) -> bool {
+        false
+    }
 
     /// A function to load the specified per-object metadata's content.
     ///
@@ -264,6 +282,11 @@ pub trait ObjectModel<VM: VMBinding> {
 
     /// Return the lowest address of the storage associated with an object.
     ///
+    /// The object reference may point past the start of the object, e.g. after a large header,
+    /// and the offset may differ between objects. However, the start must not be after the object
+    /// reference, and the object reference must be within the object (or within
+    /// `OBJECT_REF_OFFSET_BEYOND_CELL` bytes from its start), which MMTk checks in a debug build.
+    ///
     /// Arguments:
     /// * `object`: The object to be queried.
     fn object_start_ref(object: ObjectReference) -> Address;
@@ -276,6 +299,49 @@ pub trait ObjectModel<VM: VMBinding> {
     // FIXME: this doesn't seem essential. E.g. `get_object_end_address` or `object_start_ref` can cover its functionality.
     fn ref_to_address(object: ObjectReference) -> Address;
 
+    /// Return the address of the word that holds the identity hash code of an object that has
+    /// moved since its hash code was taken (see `memory_manager::identity_hash`). When such
+    /// an object is first copied, MMTk stores its old address in this word of the new copy.
+    /// This is only used with the feature `object_hash`.
+    ///
+    /// By default, this is the last word of the object, i.e. the VM appends the word to the object,
+    /// and includes `memory_manager::hash_extra_bytes` in `get_current_size`, and
+    /// `memory_manager::hash_extra_bytes_when_copied` in `get_size_when_copied`.
+    ///
+    /// Arguments:
+    /// * `object`: The object to be queried.
+    #[inline(always)]
+    fn hash_word_address(object: ObjectReference) -> Address {
+        Self::object_start_ref(object) + Self::get_current_size(object) - BYTES_IN_WORD
+    }
+
+    /// Decode a reference in the VM's own representation to an `ObjectReference`. A VM that tags
+    /// the low bits of its references, or whose references point into the middle of an object,
+    /// can decode them here once, instead of converting them before every call into MMTk. The
+    /// result must be the `ObjectReference` MMTk uses for the object, and a null reference of the
+    /// VM must be decoded to `ObjectReference::NULL`. MMTk decodes the slots of an `EncodedEdge`
+    /// with this. By default, the reference is used as is.
+    ///
+    /// This is called on hot paths, and should be inlined.
+    ///
+    /// Arguments:
+    /// * `encoded`: The reference in the VM's representation.
+    #[inline(always)]
+    fn decode_reference(encoded: usize) -> ObjectReference {
+        unsafe { Address::from_usize(encoded).to_object_reference() }
+    }
+
+    /// Encode an `ObjectReference` to the VM's own representation, the opposite of
+    /// `decode_reference`. After an object is moved, MMTk encodes its new reference to store it in
+    /// the slots of an `EncodedEdge`. By default, the reference is used as is.
+    ///
+    /// Arguments:
+    /// * `object`: The object to be encoded.
+    #[inline(always)]
+    fn encode_reference(object: ObjectReference) -> usize {
+        object.value()
+    }
+
     /// Dump debugging information for an object.
     ///
     /// Arguments:
@@ -1,4 +1,6 @@
 use crate::plan::Mutator;
+use crate::util::constants::{BITS_IN_WORD, BYTES_IN_WORD};
+use crate::util::Address;
 use crate::util::ObjectReference;
 use crate::util::VMWorkerThread;
 use crate::vm::edge_shape::Edge;
@@ -8,6 +10,16 @@ use crate::vm::VMBinding;
 pub trait EdgeVisitor<ES: Edge> {
     /// Call this function for each edge.
     fn visit_edge(&mut self, edge: ES);
+
+    /// Call this function for a batch of edges, e.g. the elements of a reference array.  This is
+    /// equivalent to calling `visit_edge` for each edge, but an implementation may process the
+    /// edges in bulk.
+    #[inline(always)]
+    fn visit_edges(&mut self, edges: &[ES]) {
+        for edge in edges {
+            self.visit_edge(*edge);
+        }
+    }
 }
 
 /// This lets us use closures as EdgeVisitor.
@@ -17,6 +29,89 @@ impl<ES: Edge, F: FnMut(ES)> EdgeVisitor<ES> for F {
     }
 }
 
+/// The reference fields of an object, described as a bitmap of words. Bit `i` is set if the word
+/// at `start + i * BYTES_IN_WORD` is a reference field. A VM can describe objects that only have
+/// reference fields at fixed offsets (e.g. instances of most classes) with a bitmap, so that MMTk
+/// core can enqueue the edges of the objects without calling back to the VM for each field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefFieldBitmap {
+    /// The address of the word described by the lowest bit.
+    pub start: Address,
+    /// One bit for each word from `start`.
+    pub bitmap: usize,
+}
+
+impl RefFieldBitmap {
+    /// The number of words that a bitmap can describe.
+    pub const MAX_WORDS: usize = BITS_IN_WORD;
+
+    pub fn new(start: Address, bitmap: usize) -> Self {
+        debug_assert!(start.is_aligned_to(BYTES_IN_WORD));
+        Self { start, bitmap }
+    }
+
+    /// Iterate over the addresses of the reference fields, in increasing order.
+    #[inline(always)]
+    pub fn field_addresses(&self) -> impl Iterator<Item = Address> {
+        let start = self.start;
+        let mut bitmap = self.bitmap;
+        std::iter::from_fn(move || {
+            if bitmap == 0 {
+                return None;
+            }
+            let index = bitmap.trailing_zeros() as usize;
+            // Clear the lowest set bit.
+            bitmap &= bitmap - 1;
+            Some(start + index * BYTES_IN_WORD)
+        })
+    }
+}
+
+/// The elements of an array of references: `len` reference fields of `element_bytes` each,
+/// starting from `start`. MMTk core scans large reference arrays in multiple work packets, each of
+/// which covers a part of the array, so that the elements can be processed in parallel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefArray {
+    /// The address of the first element.
+    pub start: Address,
+    /// The number of elements.
+    pub len: usize,
+    /// The size of an element in bytes.
+    pub element_bytes: usize,
+}
+
+impl RefArray {
+    pub fn new(start: Address, len: usize, element_bytes: usize) -> Self {
+        debug_assert!(start.is_aligned_to(element_bytes));
+        Self {
+            start,
+            len,
+            element_bytes,
+        }
+    }
+
+    /// Split the array into the elements before `index`, and the elements from `index`.
+    #[inline(always)]
+    pub fn split_at(&self, index: usize) -> (RefArray, RefArray) {
+        debug_assert!(index <= self.len);
+        (
+            Self::new(self.start, index, self.element_bytes),
+            Self::new(
+                self.start + index * self.element_bytes,
+                self.len - index,
+                self.element_bytes,
+            ),
+        )
+    }
+
+    /// Iterate over the addresses of the elements.
+    #[inline(always)]
+    pub fn element_addresses(&self) -> impl Iterator<Item = Address> {
+        let (start, element_bytes) = (self.start, self.element_bytes);
+        (0..self.len).map(move |i| start + i * element_bytes)
+    }
+}
+
 /// Callback trait of scanning functions that directly trace through edges.
 pub trait ObjectTracer {
     /// Call this function for the content of each edge,
@@ -33,11 +128,19 @@ impl<F: FnMut(ObjectReference) -> ObjectReference> ObjectTracer for F {
 
 /// Root-scanning methods use this trait to create work packets for processing roots.
 ///
+/// The VM only reports roots to the factory.  MMTk core decides how the roots are put into work
+/// packets: the root edges reported in multiple small batches are put into the same packet, and a
+/// large batch is split into multiple packets.  MMTk core also filters out the duplicate root edges
+/// within each packet, so the VM does not need to avoid reporting an edge twice (e.g. an edge
+/// reachable from two root sets).
+///
 /// Notes on the required traits:
 ///
 /// -   `Clone`: The VM may divide one root-scanning call (such as `scan_vm_specific_roots`) into
-///     multiple work packets to scan roots in parallel.  In this case, the factory shall be cloned
-///     to be given to multiple work packets.
+///     multiple work packets, or multiple threads of its own, to scan roots in parallel.  In this
+///     case, the factory shall be cloned for each of them, and the clones can be used
+///     concurrently.  The edges reported to a clone are put into packets when the clone is
+///     dropped at the latest, so the VM should drop each clone once it has reported its roots.
 ///
 ///     Cloning may be expensive if a factory contains many states. If the states are immutable, a
 ///     `RootsWorkFactory` implementation may hold those states in an `Arc` field so that multiple
@@ -69,6 +172,26 @@ pub trait RootsWorkFactory<ES: Edge>: Clone + Send + 'static {
     /// Arguments:
     /// * `nodes`: A vector of references to objects pointed by root edges.
     fn create_process_node_roots_work(&mut self, nodes: Vec<ObjectReference>);
+
+    /// Create work packets to handle the objects pointed by pinned roots, i.e. roots whose
+    /// referents must not move in the current GC.
+    ///
+    /// Unlike `create_process_node_roots_work`, this can be used with some moving plans.  The
+    /// objects are traced before any other object, and the policies keep them in place in this GC
+    /// (e.g. Immix marks them in place instead of evacuating them).  This is useful for VMs that
+    /// cannot update some roots, such as references held by native frames.  The plan must be able
+    /// to keep any object in place (see `PlanConstraints::supports_pinned_roots`).  Plans with copy
+    /// spaces (such as SemiSpace and the generational plans) or a mark-compact space cannot, and
+    /// MMTk core panics if pinned roots are reported with them.
+    ///
+    /// The default implementation reports the objects as node roots, which keeps them in place
+    /// with plans that never move objects.
+    ///
+    /// Arguments:
+    /// * `nodes`: A vector of references to objects pointed by pinned roots.
+    fn create_process_pinned_roots_work(&mut self, nodes: Vec<ObjectReference>) {
+        self.create_process_node_roots_work(nodes);
+    }
 }
 
 /// VM-specific methods for scanning roots/objects.
@@ -81,6 +204,17 @@ pub trait Scanning<VM: VMBinding> {
     /// `SCAN_MUTATORS_IN_SAFEPOINT` should also be enabled
     const SINGLE_THREAD_MUTATOR_SCANNING: bool = true;
 
+    /// The offset from the object reference of a word in the header that identifies the type of
+    /// the object (e.g. a pointer to its class), or `None` if the VM does not register object
+    /// layouts.
+    ///
+    /// If this is `Some`, MMTk core reads the type id of each object that supports edge enqueuing
+    /// before scanning it. If the VM has registered a layout for the type id with
+    /// `memory_manager::register_object_layout`, MMTk core enqueues an edge for each reference
+    /// field in the layout (see `edge_for_field`), without calling any other method of this trait
+    /// for the object. A VM that sets this must implement `edge_for_field`.
+    const LAYOUT_TYPE_ID_OFFSET: Option<isize> = None;
+
     /// Return true if the given object supports edge enqueuing.
     ///
     /// -   If this returns true, MMTk core will call `scan_object` on the object.
@@ -99,6 +233,77 @@ pub trait Scanning<VM: VMBinding> {
         true
     }
 
+    /// Return a hint of the number of reference fields that `scan_object` will visit for the
+    /// object, or `None` if the VM does not know.  The VM may base the hint on the type of the
+    /// object, e.g. the length of a reference array, or the number of reference fields of a class.
+    ///
+    /// MMTk core uses the hint to batch edges into work packets: it avoids splitting the edges of
+    /// an object over two packets, and sizes the edge buffers accordingly.  The hint does not need
+    /// to be exact.  Like `support_edge_enqueuing`, this method is called for every object to be
+    /// scanned, so it must be fast.
+    ///
+    /// Arguments:
+    /// * `tls`: The VM-specific thread-local storage for the current worker.
+    /// * `object`: The object to be scanned.
+    #[inline(always)]
+    fn edge_count_hint(_tls: VMWorkerThread, _object: ObjectReference) -> Option<usize> {
+        None
+    }
+
+    /// Return the reference fields of the object as a bitmap, or `None` if the object cannot be
+    /// described by a [`RefFieldBitmap`] (or the VM does not support it).
+    ///
+    /// If this returns `Some`, MMTk core enqueues an edge for each reference field in the bitmap
+    /// (see `edge_for_field`), instead of calling `scan_object` on the object.  This avoids a
+    /// callback for each field when scanning small objects.  Objects with a variable size part,
+    /// such as arrays, should return `None`, and be scanned with `scan_object`, which can report
+    /// the elements in bulk with `EdgeVisitor::visit_edges`.
+    ///
+    /// This method is called for every object to be scanned that supports edge enqueuing, so it
+    /// must be fast.  A VM that returns `Some` must implement `edge_for_field`.
+    ///
+    /// Arguments:
+    /// * `tls`: The VM-specific thread-local storage for the current worker.
+    /// * `object`: The object to be scanned.
+    #[inline(always)]
+    fn reference_field_bitmap(
+        _tls: VMWorkerThread,
+        _object: ObjectReference,
+    ) -> Option<RefFieldBitmap> {
+        None
+    }
+
+    /// Return the elements of the object if it is an array of references, or `None` otherwise
+    /// (or if the VM does not support it).
+    ///
+    /// If this returns `Some`, MMTk core enqueues an edge for each element (see `edge_for_field`),
+    /// instead of calling `scan_object` on the object.  An array with more elements than the
+    /// capacity of an edge-processing packet is scanned in chunks by `ScanRefArrayChunk` work
+    /// packets, so a large array does not need to be scanned by a single worker.  The array must
+    /// not have reference fields other than its elements.
+    ///
+    /// This method is called for every object to be scanned that supports edge enqueuing, so it
+    /// must be fast.  A VM that returns `Some` must implement `edge_for_field`.
+    ///
+    /// Arguments:
+    /// * `tls`: The VM-specific thread-local storage for the current worker.
+    /// * `object`: The object to be scanned.
+    #[inline(always)]
+    fn reference_array(_tls: VMWorkerThread, _object: ObjectReference) -> Option<RefArray> {
+        None
+    }
+
+    /// Return the edge for the reference field at the given address.  This is used for the
+    /// reference fields described by `reference_field_bitmap` and by the registered object
+    /// layouts, and the elements of the arrays described by `reference_array`.
+    ///
+    /// Arguments:
+    /// * `field`: The address of a reference field in an object.
+    #[inline(always)]
+    fn edge_for_field(_field: Address) -> VM::VMEdge {
+        unreachable!("edge_for_field() will not be called when reference_field_bitmap() and reference_array() always return None, and no object layout is registered.")
+    }
+
     /// Delegated scanning of a object, visiting each reference field encountered.
     ///
     /// The VM shall call `edge_visitor.visit_edge` on each reference field.
@@ -148,6 +353,25 @@ pub trait Scanning<VM: VMBinding> {
     /// * `tls`: The GC thread that is performing the thread scan.
     fn notify_initial_thread_scan_complete(partial_scan: bool, tls: VMWorkerThread);
 
+    /// MMTk calls this method once all the mutators are stopped for a GC, before any of the roots
+    /// (including the stacks) is scanned. A binding with a JIT compiler may patch or deoptimize
+    /// compiled code here, e.g. to complete an on-stack replacement, so that the stacks and the
+    /// code are consistent when they are scanned.
+    ///
+    /// Arguments:
+    /// * `tls`: The GC thread that is performing this call.
+    fn pre_scan_roots(_tls: VMWorkerThread) {}
+
+    /// MMTk calls this method once all the roots of a GC (including the stacks and the VM-specific
+    /// roots) have been scanned, and before the transitive closure starts, so no object has been
+    /// moved yet. The mutators are still stopped. A binding may, for example, make the code that
+    /// embeds object pointers writable here, so that the pointers can be updated by the GC. The
+    /// roots scanned again later in the GC (e.g. by MarkCompact) are not covered by this method.
+    ///
+    /// Arguments:
+    /// * `tls`: The GC thread that is performing this call.
+    fn post_scan_roots(_tls: VMWorkerThread) {}
+
     /// Scan all the mutators for roots.
     ///
     /// Arguments:
@@ -168,7 +392,8 @@ pub trait Scanning<VM: VMBinding> {
     );
 
     /// Scan VM-specific roots. The creation of all root scan tasks (except thread scanning)
-    /// goes here.
+    /// goes here.  The VM may scan the roots (such as weak or global handles) from multiple of
+    /// its own threads with clones of `factory`.
     ///
     /// Arguments:
     /// * `tls`: The GC thread that is performing this scanning.
@@ -180,3 +405,56 @@ pub trait Scanning<VM: VMBinding> {
 
     fn prepare_for_roots_re_scanning();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ref_field_bitmap_addresses() {
+        let start = unsafe { Address::from_usize(0x1000) };
+        let bitmap = RefFieldBitmap::new(start, 0b1000_1011);
+        let fields: Vec<Address> = bitmap.field_addresses().collect();
+        assert_eq!(
+            fields,
+            vec![
+                start,
+                start + BYTES_IN_WORD,
+                start + 3 * BYTES_IN_WORD,
+                start + 7 * BYTES_IN_WORD
+            ]
+        );
+
+        assert_eq!(RefFieldBitmap::new(start, 0).field_addresses().count(), 0);
+
+        let last = RefFieldBitmap::new(start, 1 << (RefFieldBitmap::MAX_WORDS - 1));
+        assert_eq!(
+            last.field_addresses().collect::<Vec<Address>>(),
+            vec![start + (RefFieldBitmap::MAX_WORDS - 1) * BYTES_IN_WORD]
+        );
+    }
+
+    #[test]
+    fn test_ref_array_split() {
+        let start = unsafe { Address::from_usize(0x1000) };
+        let array = RefArray::new(start, 10, 4);
+        let (lower, upper) = array.split_at(4);
+        assert_eq!(lower, RefArray::new(start, 4, 4));
+        assert_eq!(upper, RefArray::new(start + 16usize, 6, 4));
+
+        // The two halves cover the same elements as the array.
+        let elements: Vec<Address> = lower
+            .element_addresses()
+            .chain(upper.element_addresses())
+            .collect();
+        assert_eq!(
+            elements,
+            array.element_addresses().collect::<Vec<Address>>()
+        );
+        assert_eq!(elements.last(), Some(&(start + 36usize)));
+
+        let (empty, all) = array.split_at(0);
+        assert_eq!(empty.element_addresses().count(), 0);
+        assert_eq!(all, array);
+    }
+}
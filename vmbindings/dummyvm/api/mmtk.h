@@ -100,6 +100,9 @@ extern size_t mmtk_used_bytes();
 // Return the current amount of total memory in bytes
 extern size_t mmtk_total_bytes();
 
+// Change the heap size. Shrinking below the current usage may trigger a GC
+extern bool mmtk_set_heap_size(void* tls, size_t bytes);
+
 // Return the starting address of MMTk's heap
 extern void* mmtk_starting_heap_address();
 
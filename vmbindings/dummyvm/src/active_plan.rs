@@ -4,6 +4,38 @@ use mmtk::util::opaque_pointer::*;
 use mmtk::Mutator;
 use crate::DummyVM;
 use crate::SINGLETON;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The addresses of the mutators that GCs stop. The dummy VM has no threads of its own, so the
+    /// tests that let a GC happen (with the option `threads=0`, which does the GC on the thread
+    /// that triggers it) register their mutators with `register_mutator()`.
+    static ref MUTATORS: Mutex<Vec<usize>> = Mutex::new(vec![]);
+}
+
+/// The index of the next mutator returned by `get_next_mutator()`.
+static NEXT_MUTATOR: AtomicUsize = AtomicUsize::new(0);
+
+/// Let GCs stop the mutator, until it is destroyed with `memory_manager::destroy_mutator()`.
+pub fn register_mutator(mutator: &mut Mutator<DummyVM>) {
+    MUTATORS
+        .lock()
+        .unwrap()
+        .push(mutator as *mut Mutator<DummyVM> as usize);
+}
+
+fn to_mutator(addr: usize) -> &'static mut Mutator<DummyVM> {
+    unsafe { &mut *(addr as *mut Mutator<DummyVM>) }
+}
+
+/// Visit all the registered mutators.
+pub fn for_each_mutator(mut visitor: impl FnMut(&'static mut Mutator<DummyVM>)) {
+    let mutators = MUTATORS.lock().unwrap().clone();
+    for addr in mutators {
+        visitor(to_mutator(addr));
+    }
+}
 
 pub struct VMActivePlan<> {}
 
@@ -13,7 +45,7 @@ impl ActivePlan<DummyVM> for VMActivePlan {
     }
 
     fn number_of_mutators() -> usize {
-        unimplemented!()
+        MUTATORS.lock().unwrap().len()
     }
 
     fn is_mutator(_tls: VMThread) -> bool {
@@ -21,15 +53,28 @@ impl ActivePlan<DummyVM> for VMActivePlan {
         true
     }
 
-    fn mutator(_tls: VMMutatorThread) -> &'static mut Mutator<DummyVM> {
-        unimplemented!()
+    fn mutator(tls: VMMutatorThread) -> &'static mut Mutator<DummyVM> {
+        let mutators = MUTATORS.lock().unwrap();
+        let addr = mutators
+            .iter()
+            .copied()
+            .find(|addr| to_mutator(*addr).mutator_tls == tls)
+            .expect("The mutator is not registered");
+        to_mutator(addr)
     }
 
     fn reset_mutator_iterator() {
-        unimplemented!()
+        NEXT_MUTATOR.store(0, Ordering::SeqCst);
     }
 
     fn get_next_mutator() -> Option<&'static mut Mutator<DummyVM>> {
-        unimplemented!()
+        let mutators = MUTATORS.lock().unwrap();
+        let index = NEXT_MUTATOR.fetch_add(1, Ordering::SeqCst);
+        mutators.get(index).copied().map(to_mutator)
+    }
+
+    fn unregister_mutator(mutator: &mut Mutator<DummyVM>) {
+        let addr = mutator as *mut Mutator<DummyVM> as usize;
+        MUTATORS.lock().unwrap().retain(|m| *m != addr);
     }
 }
@@ -5,15 +5,16 @@ use libc::c_char;
 use std::sync::atomic::Ordering;
 use std::ffi::CStr;
 use mmtk::memory_manager;
-use mmtk::AllocationSemantics;
 use mmtk::util::{ObjectReference, Address};
 use mmtk::util::opaque_pointer::*;
 use mmtk::scheduler::{GCController, GCWorker};
-use mmtk::Mutator;
 use crate::DummyVM;
 use crate::SINGLETON;
 use crate::BUILDER;
 
+// The core entry points, e.g. `mmtk_bind_mutator()` and `mmtk_alloc()`.
+mmtk::export_c_api!(DummyVM, SINGLETON);
+
 #[no_mangle]
 pub extern "C" fn mmtk_init(heap_size: usize) {
     // set heap size first
@@ -29,39 +30,6 @@ pub extern "C" fn mmtk_init(heap_size: usize) {
     lazy_static::initialize(&SINGLETON);
 }
 
-#[no_mangle]
-pub extern "C" fn mmtk_bind_mutator(tls: VMMutatorThread) -> *mut Mutator<DummyVM> {
-    Box::into_raw(memory_manager::bind_mutator(&SINGLETON, tls))
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_destroy_mutator(mutator: *mut Mutator<DummyVM>) {
-    memory_manager::destroy_mutator(unsafe { Box::from_raw(mutator) })
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_alloc(mutator: *mut Mutator<DummyVM>, size: usize,
-                    align: usize, offset: isize, mut semantics: AllocationSemantics) -> Address {
-    if size >= SINGLETON.get_plan().constraints().max_non_los_default_alloc_bytes {
-        semantics = AllocationSemantics::Los;
-    }
-    memory_manager::alloc::<DummyVM>(unsafe { &mut *mutator }, size, align, offset, semantics)
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_post_alloc(mutator: *mut Mutator<DummyVM>, refer: ObjectReference,
-                                        bytes: usize, mut semantics: AllocationSemantics) {
-    if bytes >= SINGLETON.get_plan().constraints().max_non_los_default_alloc_bytes {
-        semantics = AllocationSemantics::Los;
-    }
-    memory_manager::post_alloc::<DummyVM>(unsafe { &mut *mutator }, refer, bytes, semantics)
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_will_never_move(object: ObjectReference) -> bool {
-    !object.is_movable()
-}
-
 #[no_mangle]
 pub extern "C" fn mmtk_start_control_collector(tls: VMWorkerThread, controller: &'static mut GCController<DummyVM>) {
     memory_manager::start_control_collector(&SINGLETON, tls, controller);
@@ -87,37 +55,12 @@ pub extern "C" fn mmtk_enable_collection() {
     memory_manager::enable_collection(&SINGLETON)
 }
 
-#[no_mangle]
-pub extern "C" fn mmtk_used_bytes() -> usize {
-    memory_manager::used_bytes(&SINGLETON)
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_free_bytes() -> usize {
-    memory_manager::free_bytes(&SINGLETON)
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_total_bytes() -> usize {
-    memory_manager::total_bytes(&SINGLETON)
-}
-
-#[no_mangle]
-pub extern "C" fn mmtk_is_live_object(object: ObjectReference) -> bool{
-    memory_manager::is_live_object(object)
-}
-
 #[cfg(feature = "is_mmtk_object")]
 #[no_mangle]
 pub extern "C" fn mmtk_is_mmtk_object(addr: Address) -> bool {
     memory_manager::is_mmtk_object(addr)
 }
 
-#[no_mangle]
-pub extern "C" fn mmtk_is_in_mmtk_spaces(object: ObjectReference) -> bool {
-    memory_manager::is_in_mmtk_spaces(object)
-}
-
 #[no_mangle]
 pub extern "C" fn mmtk_is_mapped_address(address: Address) -> bool {
     memory_manager::is_mapped_address(address)
@@ -128,11 +71,6 @@ pub extern "C" fn mmtk_modify_check(object: ObjectReference) {
     memory_manager::modify_check(&SINGLETON, object)
 }
 
-#[no_mangle]
-pub extern "C" fn mmtk_handle_user_collection_request(tls: VMMutatorThread) {
-    memory_manager::handle_user_collection_request::<DummyVM>(&SINGLETON, tls);
-}
-
 #[no_mangle]
 pub extern "C" fn mmtk_add_weak_candidate(reff: ObjectReference) {
     memory_manager::add_weak_candidate(&SINGLETON, reff)
@@ -1,35 +1,152 @@
 use crate::DummyVM;
+use crate::SINGLETON;
+use mmtk::memory_manager;
+use mmtk::scheduler::{GCController, GCWorker};
 use mmtk::util::opaque_pointer::*;
 use mmtk::vm::Collection;
 use mmtk::vm::GCThreadContext;
 use mmtk::Mutator;
 use mmtk::MutatorContext;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// Does the dummy VM spawn the GC threads? This is false unless a test sets it, so the other
+/// tests do not need to shut the threads down.
+static GC_THREADS: AtomicBool = AtomicBool::new(false);
+
+/// The mutator threads of a test, which a GC stops at their safepoints.
+struct Safepoints {
+    /// The threads that run mutators. This is 1 unless a test sets it.
+    threads: usize,
+    /// Is a GC stopping the mutators?
+    stopping: bool,
+    /// The threads that are blocked at a safepoint.
+    blocked: usize,
+    /// The number of times the mutators were resumed.
+    resumed: usize,
+}
+
+lazy_static! {
+    static ref SAFEPOINTS: Mutex<Safepoints> = Mutex::new(Safepoints {
+        threads: 1,
+        stopping: false,
+        blocked: 0,
+        resumed: 0,
+    });
+    static ref SAFEPOINTS_CONDVAR: Condvar = Condvar::new();
+}
+
+/// Block the current thread until the mutators are resumed.
+fn block(mut safepoints: MutexGuard<Safepoints>) {
+    let resumed = safepoints.resumed;
+    safepoints.blocked += 1;
+    SAFEPOINTS_CONDVAR.notify_all();
+    while safepoints.resumed == resumed {
+        safepoints = SAFEPOINTS_CONDVAR.wait(safepoints).unwrap();
+    }
+    safepoints.blocked -= 1;
+}
+
+/// Spawn the GC threads of `SINGLETON` when the collection is initialized. The GCs then stop the
+/// mutator threads that block for them, and the ones at their safepoints.
+pub fn spawn_gc_threads() {
+    GC_THREADS.store(true, Ordering::SeqCst);
+}
+
+/// Let the GCs wait for `threads` mutator threads (including the one that does the GC) to reach
+/// a safepoint. The threads call `safepoint()` between their allocations.
+pub fn set_mutator_threads(threads: usize) {
+    SAFEPOINTS.lock().unwrap().threads = threads;
+    SAFEPOINTS_CONDVAR.notify_all();
+}
+
+/// A safepoint of a mutator thread. This blocks if a GC is stopping the mutators.
+pub fn safepoint() {
+    let safepoints = SAFEPOINTS.lock().unwrap();
+    if safepoints.stopping {
+        block(safepoints);
+    }
+}
+
+/// Run `exit` (e.g. to destroy the mutator of the thread) when no GC is stopping the mutators,
+/// and stop waiting for the current thread in the GCs.
+pub fn exit_mutator_thread(exit: impl FnOnce()) {
+    let mut safepoints = SAFEPOINTS.lock().unwrap();
+    while safepoints.stopping {
+        block(safepoints);
+        safepoints = SAFEPOINTS.lock().unwrap();
+    }
+    exit();
+    safepoints.threads -= 1;
+    SAFEPOINTS_CONDVAR.notify_all();
+}
 
 pub struct VMCollection {}
 
 impl Collection<DummyVM> for VMCollection {
-    fn stop_all_mutators<F>(_tls: VMWorkerThread, _mutator_visitor: F)
+    // The GCs are done with the option `threads=0`, on the mutator thread that triggers them, or
+    // by the GC threads if a test spawns them. The other mutator threads of a test are stopped at
+    // their safepoints, and the other registered mutators are not running during the test.
+    fn stop_all_mutators<F>(_tls: VMWorkerThread, mutator_visitor: F)
     where
         F: FnMut(&'static mut Mutator<DummyVM>),
     {
-        unimplemented!()
+        {
+            // Without GC threads, the current thread is a mutator thread.
+            let gc_on_mutator = !GC_THREADS.load(Ordering::SeqCst) as usize;
+            let mut safepoints = SAFEPOINTS.lock().unwrap();
+            safepoints.stopping = true;
+            while safepoints.blocked + gc_on_mutator < safepoints.threads {
+                safepoints = SAFEPOINTS_CONDVAR.wait(safepoints).unwrap();
+            }
+        }
+        crate::active_plan::for_each_mutator(mutator_visitor)
     }
 
     fn resume_mutators(_tls: VMWorkerThread) {
-        unimplemented!()
+        let mut safepoints = SAFEPOINTS.lock().unwrap();
+        safepoints.stopping = false;
+        safepoints.resumed += 1;
+        SAFEPOINTS_CONDVAR.notify_all();
     }
 
     fn block_for_gc(_tls: VMMutatorThread) {
-        panic!("block_for_gc is not implemented")
+        let safepoints = SAFEPOINTS.lock().unwrap();
+        // Without the GC threads, only another mutator thread can do the GC.
+        if !GC_THREADS.load(Ordering::SeqCst) && safepoints.threads <= 1 {
+            panic!("block_for_gc is not implemented")
+        }
+        block(safepoints);
     }
 
-    fn spawn_gc_thread(_tls: VMThread, _ctx: GCThreadContext<DummyVM>) {}
+    fn spawn_gc_thread(_tls: VMThread, ctx: GCThreadContext<DummyVM>) {
+        if !GC_THREADS.load(Ordering::SeqCst) {
+            return;
+        }
+        let tls = VMWorkerThread(VMThread::UNINITIALIZED);
+        // Pass the contexts to the threads as addresses, as they are not `Send`.
+        match ctx {
+            GCThreadContext::Controller(controller) => {
+                let controller = Box::into_raw(controller) as usize;
+                std::thread::spawn(move || {
+                    let controller = unsafe { &mut *(controller as *mut GCController<DummyVM>) };
+                    memory_manager::start_control_collector(&SINGLETON, tls, controller);
+                });
+            }
+            GCThreadContext::Worker(worker) => {
+                let worker = Box::into_raw(worker) as usize;
+                std::thread::spawn(move || {
+                    let worker = unsafe { &mut *(worker as *mut GCWorker<DummyVM>) };
+                    memory_manager::start_worker(&SINGLETON, tls, worker);
+                });
+            }
+        }
+    }
 
     fn prepare_mutator<T: MutatorContext<DummyVM>>(
         _tls_w: VMWorkerThread,
         _tls_m: VMMutatorThread,
         _mutator: &T,
     ) {
-        unimplemented!()
     }
 }
@@ -10,19 +10,15 @@ use mmtk::Mutator;
 pub struct VMScanning {}
 
 impl Scanning<DummyVM> for VMScanning {
-    fn scan_thread_roots(_tls: VMWorkerThread, _factory: impl RootsWorkFactory<DummyVMEdge>) {
-        unimplemented!()
-    }
+    // The dummy VM has no roots, so a GC finds no live objects.
+    fn scan_thread_roots(_tls: VMWorkerThread, _factory: impl RootsWorkFactory<DummyVMEdge>) {}
     fn scan_thread_root(
         _tls: VMWorkerThread,
         _mutator: &'static mut Mutator<DummyVM>,
         _factory: impl RootsWorkFactory<DummyVMEdge>,
     ) {
-        unimplemented!()
-    }
-    fn scan_vm_specific_roots(_tls: VMWorkerThread, _factory: impl RootsWorkFactory<DummyVMEdge>) {
-        unimplemented!()
     }
+    fn scan_vm_specific_roots(_tls: VMWorkerThread, _factory: impl RootsWorkFactory<DummyVMEdge>) {}
     fn scan_object<EV: EdgeVisitor<DummyVMEdge>>(
         _tls: VMWorkerThread,
         _object: ObjectReference,
@@ -30,13 +26,9 @@ impl Scanning<DummyVM> for VMScanning {
     ) {
         unimplemented!()
     }
-    fn notify_initial_thread_scan_complete(_partial_scan: bool, _tls: VMWorkerThread) {
-        unimplemented!()
-    }
+    fn notify_initial_thread_scan_complete(_partial_scan: bool, _tls: VMWorkerThread) {}
     fn supports_return_barrier() -> bool {
-        unimplemented!()
-    }
-    fn prepare_for_roots_re_scanning() {
-        unimplemented!()
+        false
     }
+    fn prepare_for_roots_re_scanning() {}
 }
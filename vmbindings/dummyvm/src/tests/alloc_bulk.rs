@@ -0,0 +1,35 @@
+use crate::api::*;
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::util::Address;
+use mmtk::AllocationSemantics;
+
+/// This test allocates a batch of objects in one call, and checks that each object gets its own
+/// properly aligned region in the heap.
+#[test]
+pub fn alloc_bulk() {
+    const MB: usize = 1024 * 1024;
+    const SIZE: usize = 20;
+    const ALIGN: usize = 8;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+
+    let mut addrs = [Address::ZERO; 16];
+    memory_manager::alloc_bulk::<DummyVM>(
+        mutator,
+        SIZE,
+        ALIGN,
+        0,
+        AllocationSemantics::Default,
+        &mut addrs,
+    );
+    for (i, addr) in addrs.iter().enumerate() {
+        assert!(!addr.is_zero());
+        assert!(addr.is_aligned_to(ALIGN));
+        if i > 0 {
+            assert!(*addr >= addrs[i - 1] + SIZE);
+        }
+    }
+}
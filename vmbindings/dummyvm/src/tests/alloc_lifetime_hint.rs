@@ -0,0 +1,25 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// Objects allocated with the lifetime hints are in MMTk spaces, whichever space the plan puts
+/// them in.
+#[test]
+pub fn alloc_lifetime_hint() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    for semantics in [
+        AllocationSemantics::LongLived,
+        AllocationSemantics::ShortLived,
+    ] {
+        let addr = mmtk_alloc(handle, 40, 8, 0, semantics);
+        assert!(!addr.is_zero());
+        let object = unsafe { addr.add(OBJECT_REF_OFFSET).to_object_reference() };
+        mmtk_post_alloc(handle, object, 40, semantics);
+        assert!(mmtk_is_in_mmtk_spaces(object));
+    }
+}
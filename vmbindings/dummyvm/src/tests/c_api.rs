@@ -0,0 +1,51 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::SINGLETON;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// This test calls the functions generated by `mmtk::export_c_api!` in `api.rs`.
+#[test]
+pub fn c_api() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let mutator = mmtk_bind_mutator(tls);
+
+    let addr = mmtk_alloc(mutator, 16, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    mmtk_post_alloc(mutator, object, 16, AllocationSemantics::Default);
+    assert!(mmtk_is_in_mmtk_spaces(object));
+    assert!(mmtk_is_live_object(object));
+    mmtk_object_reference_write_post(mutator, object);
+
+    // A policy that can pin the object can also unpin it.
+    let pinned = mmtk_pin_object(object);
+    assert_eq!(mmtk_unpin_object(object), pinned);
+
+    // A large object is allocated in the large object space, whatever the semantics.
+    let size = SINGLETON
+        .get_plan()
+        .constraints()
+        .max_non_los_default_alloc_bytes;
+    if size < MB / 4 {
+        let addr = mmtk_alloc(mutator, size, 8, 0, AllocationSemantics::Default);
+        assert!(!addr.is_zero());
+        let large = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+        mmtk_post_alloc(mutator, large, size, AllocationSemantics::Default);
+        assert!(mmtk_will_never_move(large));
+    }
+
+    assert!(mmtk_used_bytes() > 0);
+    assert_eq!(mmtk_total_bytes(), MB);
+    let stats = mmtk_gc_stats();
+    assert_eq!(stats.gc_count, 0);
+    assert!(stats.total_allocated_bytes > 0);
+
+    mmtk_gc_poll(tls);
+    mmtk_flush_mutator(mutator);
+    mmtk_destroy_mutator(mutator);
+}
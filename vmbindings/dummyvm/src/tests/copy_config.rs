@@ -0,0 +1,13 @@
+use crate::DummyVM;
+use mmtk::util::copy::{CopyConfig, CopySelector, CopySemantics};
+
+/// A customized copy config that uses a copying allocator without a space is rejected.
+#[test]
+#[should_panic(expected = "No space is mapped for the copying allocator")]
+pub fn copy_config_without_space() {
+    let mut config = CopyConfig::<DummyVM>::default();
+    // The empty config of a non copying plan is valid.
+    config.validate();
+    config.copy_mapping[CopySemantics::Nursery] = CopySelector::CopySpace(0);
+    config.validate();
+}
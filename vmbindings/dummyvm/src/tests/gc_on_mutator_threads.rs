@@ -0,0 +1,58 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::collection;
+use crate::{BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{Address, OpaquePointer, VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+/// Without GC threads, two mutator threads that trigger GCs at the same time do not deadlock:
+/// while one of them does a GC on its thread, the other one is stopped at a safepoint, instead of
+/// waiting for the GC controller.
+#[test]
+pub fn gc_on_mutator_threads() {
+    const MB: usize = 1024 * 1024;
+    const THREADS: usize = 2;
+    const OBJECT_SIZE: usize = 1024;
+    assert!(BUILDER.lock().unwrap().options.threads.set(0));
+    mmtk_init(4 * MB);
+    if matches!(*SINGLETON.get_options().plan, PlanSelector::NoGC) {
+        // NoGC cannot do the GCs.
+        return;
+    }
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+
+    // Register the mutators before the threads start, so a GC does not find a mutator that is
+    // not stopped at a safepoint.
+    collection::set_mutator_threads(THREADS);
+    let handles: Vec<usize> = (1..=THREADS)
+        .map(|i| {
+            let address = unsafe { Address::from_usize(i * 8) };
+            let tls = VMMutatorThread(VMThread(OpaquePointer::from_address(address)));
+            let handle = mmtk_bind_mutator(tls);
+            crate::active_plan::register_mutator(unsafe { &mut *handle });
+            handle as usize
+        })
+        .collect();
+    let threads: Vec<_> = handles
+        .into_iter()
+        .map(|handle| {
+            std::thread::spawn(move || {
+                let handle = handle as *mut mmtk::Mutator<crate::DummyVM>;
+                // Each thread allocates several times the heap, so both of them trigger GCs.
+                for _ in 0..(16 * MB / OBJECT_SIZE) {
+                    collection::safepoint();
+                    let addr = mmtk_alloc(handle, OBJECT_SIZE, 8, 0, AllocationSemantics::Default);
+                    assert!(!addr.is_zero());
+                }
+                collection::exit_mutator_thread(|| mmtk_destroy_mutator(handle));
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(memory_manager::gc_stats(&SINGLETON).gc_count >= 4);
+}
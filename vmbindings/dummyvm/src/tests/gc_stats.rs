@@ -0,0 +1,19 @@
+use crate::api::*;
+use crate::SINGLETON;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+#[test]
+pub fn gc_stats() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let addr = mmtk_alloc(handle, 1024, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+
+    let stats = memory_manager::gc_stats(&SINGLETON);
+    assert_eq!(stats.gc_count, 0);
+    assert!(stats.total_allocated_bytes >= 1024);
+    assert_eq!(stats.heap_size_bytes, MB as u64);
+}
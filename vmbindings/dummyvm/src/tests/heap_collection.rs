@@ -0,0 +1,78 @@
+// GITHUB-CI: MMTK_PLAN=MarkSweep
+// GITHUB-CI: FEATURES=heap_ids,is_mmtk_object
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::{DummyVM, BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{ObjectReference, VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+use mmtk::Mutator;
+
+fn alloc_object(
+    mutator: &mut Mutator<DummyVM>,
+    size: usize,
+    semantics: AllocationSemantics,
+) -> ObjectReference {
+    let addr = memory_manager::alloc::<DummyVM>(mutator, size, 8, 0, semantics);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, size, semantics);
+    object
+}
+
+/// MarkSweep collects only the objects of a heap if it remembers the references into the heaps.
+/// The dummy VM has no roots, so the GC frees the objects of the heap, in the malloc space and in
+/// the LOS, and keeps the objects of the other heaps. The GCs are done on the current thread, as
+/// there are no GC threads.
+#[test]
+pub fn heap_collection() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(builder.options.threads.set(0));
+        assert!(builder.options.remember_heap_references.set(true));
+    }
+    mmtk_init(16 * MB);
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    let mutator = unsafe { &mut *handle };
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+    if !matches!(*SINGLETON.get_options().plan, PlanSelector::MarkSweep) {
+        return;
+    }
+
+    memory_manager::set_mutator_heap_id(mutator, 1);
+    let small1 = alloc_object(mutator, 64, AllocationSemantics::Default);
+    let large1 = alloc_object(mutator, 64 * 1024, AllocationSemantics::Los);
+    memory_manager::set_mutator_heap_id(mutator, 2);
+    let small2 = alloc_object(mutator, 64, AllocationSemantics::Default);
+    let large2 = alloc_object(mutator, 64 * 1024, AllocationSemantics::Los);
+
+    // All the heaps may refer to the shared heap, so it cannot be collected by itself.
+    assert!(!memory_manager::handle_user_heap_collection_request(
+        &SINGLETON, tls, 0
+    ));
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 0);
+
+    // The dummy VM cannot scan objects, so no reference is reported to the barrier.
+    assert!(memory_manager::handle_user_heap_collection_request(
+        &SINGLETON, tls, 1
+    ));
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 1);
+    assert!(!SINGLETON.get_plan().last_collection_full_heap());
+    assert!(!memory_manager::is_mmtk_object(small1.to_address()));
+    assert!(!memory_manager::is_mmtk_object(large1.to_address()));
+    assert!(memory_manager::is_mmtk_object(small2.to_address()));
+    assert!(memory_manager::is_mmtk_object(large2.to_address()));
+
+    // A full heap GC collects the other heaps as well.
+    memory_manager::handle_user_collection_request(&SINGLETON, tls);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 2);
+    assert!(SINGLETON.get_plan().last_collection_full_heap());
+    assert!(!memory_manager::is_mmtk_object(small2.to_address()));
+    assert!(!memory_manager::is_mmtk_object(large2.to_address()));
+    mmtk_destroy_mutator(handle);
+}
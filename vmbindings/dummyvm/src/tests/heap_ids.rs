@@ -0,0 +1,39 @@
+// GITHUB-CI: MMTK_PLAN=all
+// GITHUB-CI: FEATURES=heap_ids
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::util::ObjectReference;
+use mmtk::AllocationSemantics;
+use mmtk::Mutator;
+
+fn alloc_object(mutator: &mut Mutator<DummyVM>) -> ObjectReference {
+    let addr = memory_manager::alloc::<DummyVM>(mutator, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, 64, AllocationSemantics::Default);
+    object
+}
+
+/// `post_alloc` tags the objects with the heap of their mutator, which is the shared heap until the
+/// binding sets another.
+#[test]
+pub fn heap_ids() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+
+    let shared = alloc_object(mutator);
+    assert_eq!(memory_manager::object_heap_id(shared), 0);
+    memory_manager::set_mutator_heap_id(mutator, 2);
+    let isolate = alloc_object(mutator);
+    assert_eq!(memory_manager::object_heap_id(isolate), 2);
+    assert_eq!(memory_manager::object_heap_id(shared), 0);
+
+    memory_manager::set_object_heap_id(shared, 1);
+    assert_eq!(memory_manager::object_heap_id(shared), 1);
+}
@@ -0,0 +1,65 @@
+// GITHUB-CI: MMTK_PLAN=MarkSweep
+// GITHUB-CI: FEATURES=is_mmtk_object
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::{DummyVM, BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{ObjectReference, VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+use mmtk::Mutator;
+
+fn alloc_object(
+    mutator: &mut Mutator<DummyVM>,
+    size: usize,
+    semantics: AllocationSemantics,
+) -> ObjectReference {
+    let addr = memory_manager::alloc::<DummyVM>(mutator, size, 8, 0, semantics);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, size, semantics);
+    object
+}
+
+/// MarkSweep collects only the LOS if it remembers the references into the LOS. The dummy VM has
+/// no roots, so the GC frees the large object, but it does not sweep the malloc space. The GCs are
+/// done on the current thread, as there are no GC threads.
+#[test]
+pub fn los_collection() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(builder.options.threads.set(0));
+        assert!(builder.options.remember_los_references.set(true));
+    }
+    mmtk_init(16 * MB);
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    let mutator = unsafe { &mut *handle };
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+    if !matches!(*SINGLETON.get_options().plan, PlanSelector::MarkSweep) {
+        return;
+    }
+
+    let small = alloc_object(mutator, 64, AllocationSemantics::Default);
+    let large = alloc_object(mutator, 64 * 1024, AllocationSemantics::Los);
+    // The dummy VM cannot scan objects, so no reference is reported to the barrier.
+    assert!(memory_manager::handle_user_partial_collection_request(
+        &SINGLETON,
+        tls,
+        &["los"]
+    ));
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 1);
+    assert!(!SINGLETON.get_plan().last_collection_full_heap());
+    assert!(!memory_manager::is_mmtk_object(large.to_address()));
+    assert!(memory_manager::is_mmtk_object(small.to_address()));
+
+    // A full heap GC sweeps the malloc space as well.
+    memory_manager::handle_user_collection_request(&SINGLETON, tls);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 2);
+    assert!(SINGLETON.get_plan().last_collection_full_heap());
+    assert!(!memory_manager::is_mmtk_object(small.to_address()));
+    mmtk_destroy_mutator(handle);
+}
@@ -0,0 +1,21 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::MMTKBuilder;
+
+/// An MMTk instance can be shut down even if the VM has not started the GC threads (the dummy
+/// VM never does), and a new instance can be created in the released address space afterwards.
+#[test]
+pub fn shutdown_and_recreate() {
+    const MB: usize = 1024 * 1024;
+    for _ in 0..2 {
+        let mut builder = MMTKBuilder::new();
+        assert!(builder.options.heap_size.set(MB));
+        let mmtk: &'static mmtk::MMTK<DummyVM> =
+            Box::leak(memory_manager::mmtk_init::<DummyVM>(&builder));
+        memory_manager::initialize_collection(mmtk, VMThread::UNINITIALIZED);
+        mmtk.shutdown();
+    }
+}
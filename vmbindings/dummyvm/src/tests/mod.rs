@@ -11,13 +11,43 @@ mod allocate_without_initialize_collection;
 mod allocate_with_initialize_collection;
 mod allocate_with_disable_collection;
 mod allocate_with_re_enable_collection;
+mod set_heap_size;
+mod shrink_heap_size;
+mod try_alloc;
+mod alloc_bulk;
+mod alloc_lifetime_hint;
+mod copy_config;
+mod resize_object;
 #[cfg(not(feature = "malloc_counted_size"))]
 mod malloc_api;
 #[cfg(feature = "malloc_counted_size")]
 mod malloc_counted;
+#[cfg(feature = "raw_memory_space")]
+mod raw_memory;
 mod malloc_ms;
 #[cfg(feature = "is_mmtk_object")]
 mod conservatism;
+#[cfg(feature = "object_start_map")]
+mod object_start_map;
 mod is_in_mmtk_spaces;
+mod query_spaces;
+mod partial_collection;
+#[cfg(feature = "is_mmtk_object")]
+mod los_collection;
+mod request_gc_blocking;
+mod request_gc_blocking_single_thread;
+mod gc_stats;
+mod stats_windows;
 mod fixtures;
 mod edges_test;
+mod c_api;
+mod mmtk_shutdown;
+mod space_growth_trigger;
+mod gc_on_mutator_threads;
+mod object_generation;
+#[cfg(feature = "heap_ids")]
+mod heap_ids;
+#[cfg(all(feature = "heap_ids", feature = "is_mmtk_object"))]
+mod heap_collection;
+mod mutator_assist;
+mod zeroed_block_pool;
@@ -0,0 +1,59 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::{BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+fn pooled_pages() -> usize {
+    SINGLETON
+        .spaces()
+        .iter()
+        .map(|space| space.zeroed_block_pool_pages)
+        .sum()
+}
+
+/// Without GC threads, the zeroed block pools are refilled by the mutator assists after a GC: the
+/// mutator zeroes the blocks on its allocation slow path, as a tax for the bytes it allocates.
+#[test]
+pub fn mutator_assist() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(builder.options.threads.set(0));
+        assert!(builder.options.zeroed_block_pool.set(4));
+        assert!(builder.options.mutator_assist_bytes.set(1));
+    }
+    mmtk_init(16 * MB);
+    if matches!(*SINGLETON.get_options().plan, PlanSelector::NoGC) {
+        // NoGC cannot do the GC.
+        return;
+    }
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    memory_manager::handle_user_collection_request(&SINGLETON, tls);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 1);
+    // The GC does not refill the pools itself, as there are no GC workers.
+    assert_eq!(pooled_pages(), 0);
+
+    // The first allocation after the GC goes to the slow path, which pays for the whole buffer of
+    // the allocator, more than the quanta that are left.
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    if pooled_pages() == 0 {
+        // The plan has no space with a pool.
+        mmtk_destroy_mutator(handle);
+        return;
+    }
+    for space in SINGLETON.spaces() {
+        assert!(space.zeroed_block_pool_pages <= space.reserved_pages);
+    }
+    mmtk_destroy_mutator(handle);
+}
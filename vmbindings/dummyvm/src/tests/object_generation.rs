@@ -0,0 +1,45 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::{DummyVM, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::plan::{FixedTenuring, ObjectGeneration};
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+/// The objects allocated in the nursery have not survived any GC. The other plans have no
+/// generations, and no tenuring policy. The large objects are not checked, as the dummy VM keeps no
+/// header metadata for the nursery bit of the large object space.
+#[test]
+pub fn object_generation() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+
+    let generational = matches!(
+        *SINGLETON.get_options().plan,
+        PlanSelector::GenCopy | PlanSelector::GenImmix
+    );
+    let addr = memory_manager::alloc::<DummyVM>(mutator, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, 64, AllocationSemantics::Default);
+
+    let expected = if generational {
+        Some(ObjectGeneration::Young { age: 0 })
+    } else {
+        None
+    };
+    assert_eq!(
+        memory_manager::object_generation(&SINGLETON, object),
+        expected
+    );
+
+    assert_eq!(
+        memory_manager::set_tenuring_policy(&SINGLETON, Box::new(FixedTenuring(1))),
+        generational
+    );
+}
@@ -0,0 +1,42 @@
+// GITHUB-CI: MMTK_PLAN=all
+// GITHUB-CI: FEATURES=object_start_map
+
+use crate::tests::fixtures::{Fixture, SingleObject};
+use mmtk::memory_manager;
+use mmtk::util::object_start_map;
+
+lazy_static! {
+    static ref SINGLE_OBJECT: Fixture<SingleObject> = Fixture::new();
+}
+
+#[test]
+pub fn first_object_in_card() {
+    SINGLE_OBJECT.with_fixture(|fixture| {
+        let objref = fixture.objref;
+        assert_eq!(
+            object_start_map::first_object_in_card(objref.to_address()),
+            Some(objref)
+        );
+    });
+}
+
+#[test]
+pub fn interior_pointers() {
+    SINGLE_OBJECT.with_fixture(|fixture| {
+        let objref = fixture.objref;
+        let addr = objref.to_address();
+        assert_eq!(
+            memory_manager::find_object_from_internal_pointer(addr, 0),
+            Some(objref)
+        );
+        assert_eq!(
+            memory_manager::find_object_from_internal_pointer(addr + 16usize, 64),
+            Some(objref)
+        );
+        // The object is further away than the search range.
+        assert_eq!(
+            memory_manager::find_object_from_internal_pointer(addr + 200usize, 100),
+            None
+        );
+    });
+}
@@ -0,0 +1,50 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::{BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::{VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+/// A partial-heap collection of spaces that the plan does not have is rejected without a GC. A
+/// collection of the nursery is a nursery GC in the generational plans. The GCs are done on the
+/// current thread, as there are no GC threads.
+#[test]
+pub fn partial_collection() {
+    const MB: usize = 1024 * 1024;
+    assert!(BUILDER.lock().unwrap().options.threads.set(0));
+    mmtk_init(16 * MB);
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+
+    assert!(!memory_manager::handle_user_partial_collection_request(
+        &SINGLETON,
+        tls,
+        &["no_such_space"]
+    ));
+    assert!(!memory_manager::handle_user_partial_collection_request(
+        &SINGLETON,
+        tls,
+        &[]
+    ));
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 0);
+
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    if !memory_manager::handle_user_partial_collection_request(&SINGLETON, tls, &["nursery"]) {
+        // The plan is not generational.
+        assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 0);
+        return;
+    }
+    let stats = memory_manager::gc_stats(&SINGLETON);
+    assert_eq!(stats.gc_count, 1);
+    assert_eq!(stats.nursery_gc_count, 1);
+    assert!(!SINGLETON.get_plan().last_collection_full_heap());
+
+    // The mutator can allocate again after the GC.
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    mmtk_destroy_mutator(handle);
+}
@@ -0,0 +1,21 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::SINGLETON;
+
+#[test]
+pub fn query_spaces() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    assert!(!SINGLETON.plan_name().is_empty());
+    let spaces = SINGLETON.spaces();
+    assert!(!spaces.is_empty());
+    for space in spaces {
+        assert!(!space.name.is_empty());
+        assert!(!space.policy.is_empty());
+        assert!(!space.policy.contains("::"));
+        if let Some((start, end)) = space.bounds {
+            assert!(start < end);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+// GITHUB-CI: FEATURES=raw_memory_space
+
+use crate::api::*;
+use crate::SINGLETON;
+use mmtk::memory_manager;
+use mmtk::util::constants::BYTES_IN_PAGE;
+use mmtk::util::VMThread;
+
+#[test]
+pub fn alloc_and_free_raw() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let tls = VMThread::UNINITIALIZED;
+    let used_before = memory_manager::raw_memory_used_bytes(&SINGLETON);
+
+    // A small request is rounded up to its size class.
+    let small = memory_manager::alloc_raw(&SINGLETON, tls, 24, 8);
+    assert!(!small.is_zero());
+    assert!(small.is_aligned_to(8));
+    assert_eq!(
+        memory_manager::raw_memory_used_bytes(&SINGLETON),
+        used_before + 32
+    );
+
+    // A large request gets its own pages.
+    let large_bytes = 2 * BYTES_IN_PAGE + 1;
+    let large = memory_manager::alloc_raw(&SINGLETON, tls, large_bytes, 16);
+    assert!(!large.is_zero());
+    assert!(large.is_aligned_to(BYTES_IN_PAGE));
+    assert_eq!(
+        memory_manager::raw_memory_used_bytes(&SINGLETON),
+        used_before + 32 + 3 * BYTES_IN_PAGE
+    );
+
+    // The memory is usable.
+    unsafe {
+        small.store(42usize);
+        (large + (large_bytes - 1)).store(1u8);
+        assert_eq!(small.load::<usize>(), 42);
+    }
+
+    memory_manager::free_raw(&SINGLETON, small, 24, 8);
+    memory_manager::free_raw(&SINGLETON, large, large_bytes, 16);
+    assert_eq!(
+        memory_manager::raw_memory_used_bytes(&SINGLETON),
+        used_before
+    );
+
+    // The freed cell is reused.
+    let again = memory_manager::alloc_raw(&SINGLETON, tls, 30, 8);
+    assert_eq!(again, small);
+    memory_manager::free_raw(&SINGLETON, again, 30, 8);
+}
@@ -0,0 +1,53 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::collection;
+use crate::SINGLETON;
+use mmtk::memory_manager;
+use mmtk::plan::{BlockingGCError, GCKind};
+use mmtk::util::options::PlanSelector;
+use mmtk::util::VMThread;
+
+/// A blocking GC request is refused without a GC before the collection is initialized. Once the
+/// GC threads are spawned, each request is served by a GC, and a full-heap request by a full-heap
+/// GC.
+#[test]
+pub fn request_gc_blocking() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    assert_eq!(
+        memory_manager::request_gc_blocking(&SINGLETON, GCKind::FullHeap),
+        Err(BlockingGCError::NotInitialized)
+    );
+    assert_eq!(
+        memory_manager::request_gc_blocking(&SINGLETON, GCKind::Default),
+        Err(BlockingGCError::NotInitialized)
+    );
+    if matches!(*SINGLETON.get_options().plan, PlanSelector::NoGC) {
+        // NoGC cannot do the GCs.
+        return;
+    }
+
+    // The GCs do not wait for any mutator thread, as the test thread is not a mutator.
+    collection::set_mutator_threads(0);
+    collection::spawn_gc_threads();
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+
+    assert_eq!(
+        memory_manager::request_gc_blocking(&SINGLETON, GCKind::Default),
+        Ok(())
+    );
+    let stats = memory_manager::gc_stats(&SINGLETON);
+    assert_eq!(stats.gc_count, 1);
+
+    assert_eq!(
+        memory_manager::request_gc_blocking(&SINGLETON, GCKind::FullHeap),
+        Ok(())
+    );
+    let full_heap_stats = memory_manager::gc_stats(&SINGLETON);
+    assert_eq!(full_heap_stats.gc_count, 2);
+    assert_eq!(
+        full_heap_stats.full_heap_gc_count,
+        stats.full_heap_gc_count + 1
+    );
+}
@@ -0,0 +1,29 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::plan::{BlockingGCError, GCKind};
+use mmtk::util::opaque_pointer::*;
+use mmtk::MMTKBuilder;
+
+/// Without GC threads, a blocking GC request is refused instead of waiting forever for a GC that
+/// no thread would do.
+#[test]
+pub fn request_gc_blocking_without_gc_threads() {
+    const MB: usize = 1024 * 1024;
+    let mut builder = MMTKBuilder::new();
+    assert!(builder.options.heap_size.set(MB));
+    assert!(builder.options.threads.set(0));
+    let mmtk: &'static mmtk::MMTK<DummyVM> =
+        Box::leak(memory_manager::mmtk_init::<DummyVM>(&builder));
+    memory_manager::initialize_collection(mmtk, VMThread::UNINITIALIZED);
+    assert_eq!(
+        memory_manager::request_gc_blocking(mmtk, GCKind::Default),
+        Err(BlockingGCError::NoGCThreads)
+    );
+    assert_eq!(
+        memory_manager::request_gc_blocking(mmtk, GCKind::FullHeap),
+        Err(BlockingGCError::NoGCThreads)
+    );
+    mmtk.shutdown();
+}
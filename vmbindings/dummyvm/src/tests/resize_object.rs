@@ -0,0 +1,45 @@
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::util::constants::BYTES_IN_PAGE;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// This test allocates an object in the large object space, and grows and shrinks it in place.
+#[test]
+pub fn resize_object() {
+    const MB: usize = 1024 * 1024;
+    // Allocate a bit more than a page, so the object gets two pages.
+    const SIZE: usize = BYTES_IN_PAGE + 64;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+
+    let addr = memory_manager::alloc::<DummyVM>(mutator, SIZE, 8, 0, AllocationSemantics::Los);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, SIZE, AllocationSemantics::Los);
+
+    // Shrinking is always allowed.
+    assert!(memory_manager::shrink_object(object, SIZE, SIZE - 32));
+    // Growing within the pages of the object is allowed.
+    assert!(memory_manager::grow_object_in_place(
+        object,
+        SIZE - 32,
+        SIZE + 64
+    ));
+    // Growing beyond the pages of the object is not allowed.
+    assert!(!memory_manager::grow_object_in_place(
+        object,
+        SIZE + 64,
+        4 * BYTES_IN_PAGE
+    ));
+
+    // Shrinking the object to one page gives the second page back.
+    let used = mmtk_used_bytes();
+    assert!(memory_manager::shrink_object(object, SIZE + 64, 64));
+    assert_eq!(mmtk_used_bytes(), used - BYTES_IN_PAGE);
+    // So the object can no longer grow into the second page.
+    assert!(!memory_manager::grow_object_in_place(object, 64, SIZE));
+}
@@ -0,0 +1,22 @@
+use crate::api::*;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// This test grows the heap before allocating. Without growing the heap, allocating 2MB in a 1MB heap
+/// would trigger a GC, and MMTk would panic as we haven't called initialize_collection().
+#[test]
+pub fn set_heap_size() {
+    const MB: usize = 1024 * 1024;
+    // 1MB heap
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    // Grow the heap to 8MB. This should not trigger a GC.
+    assert!(mmtk_set_heap_size(
+        VMMutatorThread(VMThread::UNINITIALIZED),
+        8 * MB
+    ));
+    assert_eq!(mmtk_total_bytes(), 8 * MB);
+    // Allocate 2MB memory. This fits in the new heap, so no GC is triggered.
+    let addr = mmtk_alloc(handle, 2 * MB, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+}
@@ -0,0 +1,61 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::object_model::OBJECT_REF_OFFSET;
+use crate::{DummyVM, BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::constants::BYTES_IN_PAGE;
+use mmtk::util::conversions;
+use mmtk::util::memory;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+/// Shrinking the heap below the pages in use triggers a GC, and the pages released in the GC are
+/// given back to the OS. The dummy VM has no roots, so the GC frees the large object. NoGC never
+/// triggers a GC. The GC is done on the current thread, as there are no GC threads.
+#[test]
+pub fn shrink_heap_size() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(builder.options.threads.set(0));
+    }
+    mmtk_init(16 * MB);
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    let mutator = unsafe { &mut *handle };
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+    if matches!(*SINGLETON.get_options().plan, PlanSelector::NoGC) {
+        return;
+    }
+
+    let size = 4 * MB;
+    let addr = memory_manager::alloc::<DummyVM>(mutator, size, 8, 0, AllocationSemantics::Los);
+    assert!(!addr.is_zero());
+    let object = unsafe { (addr + OBJECT_REF_OFFSET).to_object_reference() };
+    memory_manager::post_alloc::<DummyVM>(mutator, object, size, AllocationSemantics::Los);
+    // Touch every page of the object, so it is resident.
+    for offset in (0..size).step_by(BYTES_IN_PAGE) {
+        unsafe { (addr + offset).store(1usize) };
+    }
+    if let Some(resident) = memory::resident_bytes(addr, size) {
+        assert_eq!(resident, size);
+    }
+    let reserved = SINGLETON.get_plan().get_reserved_pages();
+    assert!(conversions::pages_to_bytes(reserved) > size);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 0);
+
+    // Shrink the heap below the reserved pages. This triggers a GC, and blocks until it is done.
+    assert!(mmtk_set_heap_size(tls, 2 * MB));
+    assert_eq!(mmtk_total_bytes(), 2 * MB);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 1);
+    let released = reserved - SINGLETON.get_plan().get_reserved_pages();
+    assert!(conversions::pages_to_bytes(released) >= size);
+    // The pages of the object are mapped, but no longer resident.
+    if let Some(resident) = memory::resident_bytes(addr, size) {
+        assert_eq!(resident, 0);
+    }
+    mmtk_destroy_mutator(handle);
+}
@@ -0,0 +1,41 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::{DummyVM, BUILDER};
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// Once the LOS has grown by its growth trigger, it is treated as full, though the heap is not,
+/// so try_alloc() returns None instead of triggering a GC.
+#[test]
+pub fn los_growth_trigger() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(memory_manager::process(
+            &mut builder,
+            "space_growth_triggers",
+            "los:1048576"
+        ));
+    }
+    mmtk_init(16 * MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+    // The first object is below the trigger.
+    let addr =
+        memory_manager::try_alloc::<DummyVM>(mutator, MB / 2, 8, 0, AllocationSemantics::Los);
+    assert!(addr.is_some());
+    // The LOS reaches the trigger after allocating 1MB, far below the heap size.
+    let allocated = (0..8)
+        .take_while(|_| {
+            memory_manager::try_alloc::<DummyVM>(mutator, MB / 2, 8, 0, AllocationSemantics::Los)
+                .is_some()
+        })
+        .count();
+    assert!(allocated <= 1);
+    // Other spaces are not affected.
+    let addr =
+        memory_manager::try_alloc::<DummyVM>(mutator, 16, 8, 0, AllocationSemantics::Default);
+    assert!(addr.is_some());
+}
@@ -0,0 +1,32 @@
+use crate::api::*;
+use crate::SINGLETON;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+#[test]
+pub fn stats_windows() {
+    const MB: usize = 1024 * 1024;
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    assert!(memory_manager::stats_window(&SINGLETON, "phase").is_none());
+
+    assert!(memory_manager::start_stats_window(&SINGLETON, "phase"));
+    let addr = mmtk_alloc(handle, 1024, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    let phase = memory_manager::stop_stats_window(&SINGLETON, "phase").unwrap();
+    assert!(!phase.running);
+    assert!(phase.stats.total_allocated_bytes >= 1024);
+    assert_eq!(phase.stats.gc_count, 0);
+
+    // A stopped window does not count later allocation.
+    let allocated = phase.stats.total_allocated_bytes;
+    let big = mmtk_alloc(handle, 64 * 1024, 8, 0, AllocationSemantics::Default);
+    assert!(!big.is_zero());
+    let phase = memory_manager::stats_window(&SINGLETON, "phase").unwrap();
+    assert_eq!(phase.stats.total_allocated_bytes, allocated);
+
+    assert!(memory_manager::reset_stats_window(&SINGLETON, "phase"));
+    let phase = memory_manager::stats_window(&SINGLETON, "phase").unwrap();
+    assert_eq!(phase.stats.total_allocated_bytes, 0);
+}
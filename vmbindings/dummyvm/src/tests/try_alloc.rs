@@ -0,0 +1,24 @@
+use crate::api::*;
+use crate::DummyVM;
+use mmtk::memory_manager;
+use mmtk::util::opaque_pointer::*;
+use mmtk::AllocationSemantics;
+
+/// This test allocates without calling initialize_collection(). When we exceed the heap limit, try_alloc()
+/// should return None instead of triggering a GC (which would panic as GC is not initialized).
+#[test]
+pub fn try_alloc() {
+    const MB: usize = 1024 * 1024;
+    // 1MB heap
+    mmtk_init(MB);
+    let handle = mmtk_bind_mutator(VMMutatorThread(VMThread::UNINITIALIZED));
+    let mutator = unsafe { &mut *handle };
+    // A small allocation should succeed.
+    let addr =
+        memory_manager::try_alloc::<DummyVM>(mutator, 16, 8, 0, AllocationSemantics::Default);
+    assert!(addr.is_some());
+    // Attempt to allocate 2MB memory. This would require a GC, so try_alloc() returns None.
+    let addr =
+        memory_manager::try_alloc::<DummyVM>(mutator, 2 * MB, 8, 0, AllocationSemantics::Los);
+    assert!(addr.is_none());
+}
@@ -0,0 +1,64 @@
+// GITHUB-CI: MMTK_PLAN=all
+
+use crate::api::*;
+use crate::{BUILDER, SINGLETON};
+use mmtk::memory_manager;
+use mmtk::util::constants::BYTES_IN_PAGE;
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{VMMutatorThread, VMThread};
+use mmtk::AllocationSemantics;
+
+fn pooled_pages() -> usize {
+    SINGLETON
+        .spaces()
+        .iter()
+        .map(|space| space.zeroed_block_pool_pages)
+        .sum()
+}
+
+/// The pools are refilled after a GC, their pages are counted as used, and the allocations after
+/// the GC take their blocks. The GC is done on the current thread, which also refills the pools,
+/// as there are no GC threads.
+#[test]
+pub fn zeroed_block_pool() {
+    const MB: usize = 1024 * 1024;
+    {
+        let mut builder = BUILDER.lock().unwrap();
+        assert!(builder.options.threads.set(0));
+        assert!(builder.options.zeroed_block_pool.set(4));
+    }
+    mmtk_init(16 * MB);
+    if matches!(*SINGLETON.get_options().plan, PlanSelector::NoGC) {
+        // NoGC cannot do the GC.
+        return;
+    }
+    mmtk_initialize_collection(VMThread::UNINITIALIZED);
+    let tls = VMMutatorThread(VMThread::UNINITIALIZED);
+    let handle = mmtk_bind_mutator(tls);
+    crate::active_plan::register_mutator(unsafe { &mut *handle });
+
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    // The pools are only refilled after a GC.
+    assert_eq!(pooled_pages(), 0);
+    memory_manager::handle_user_collection_request(&SINGLETON, tls);
+    assert_eq!(memory_manager::gc_stats(&SINGLETON).gc_count, 1);
+
+    let pooled = pooled_pages();
+    if pooled == 0 {
+        // The plan has no space with a pool.
+        mmtk_destroy_mutator(handle);
+        return;
+    }
+    for space in SINGLETON.spaces() {
+        assert!(space.zeroed_block_pool_pages <= space.reserved_pages);
+    }
+    // The pooled pages are part of the used memory, even in a plan with from-spaces.
+    assert!(pooled * BYTES_IN_PAGE <= memory_manager::used_bytes(&SINGLETON));
+
+    // The allocator takes a block from the pool of its space.
+    let addr = mmtk_alloc(handle, 64, 8, 0, AllocationSemantics::Default);
+    assert!(!addr.is_zero());
+    assert!(pooled_pages() < pooled);
+    mmtk_destroy_mutator(handle);
+}